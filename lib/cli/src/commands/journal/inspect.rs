@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use wasmer_wasix::journal::{copy_journal, LogFileJournal, PrintingJournal};
+use wasmer_wasix::journal::{copy_journal, JournalStatistics, LogFileJournal, PrintingJournal};
 
 use crate::commands::CliCommand;
 
@@ -11,12 +11,30 @@ pub struct CmdJournalInspect {
     /// Path to the journal that will be printed
     #[clap(index = 1)]
     journal_path: PathBuf,
+
+    /// Prints a report of statistics about the journal as JSON instead
+    /// of the events themselves (i.e. counts and sizes per event type,
+    /// thread timelines, snapshot positions and the largest memory regions)
+    #[clap(long = "stats")]
+    stats: bool,
+
+    /// Maximum number of memory regions that are included in the
+    /// statistics report
+    #[clap(long = "top-memory-regions", default_value = "10")]
+    top_memory_regions: usize,
 }
 
 impl CliCommand for CmdJournalInspect {
     type Output = ();
 
     fn run(self) -> Result<(), anyhow::Error> {
+        if self.stats {
+            let journal = LogFileJournal::new_readonly(self.journal_path)?;
+            let stats = JournalStatistics::collect(&journal, self.top_memory_regions)?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+            return Ok(());
+        }
+
         let journal = LogFileJournal::new(self.journal_path)?;
         let printer = PrintingJournal::default();
         copy_journal(&journal, &printer)?;
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use wasmer_wasix::journal::{JournalUpgraderRegistry, LogFileJournal, JOURNAL_FORMAT_VERSION};
+
+use crate::commands::CliCommand;
+
+/// Migrates a journal that was written by an older version of Wasmer
+/// to the current journal format
+#[derive(Debug, Parser)]
+pub struct CmdJournalMigrate {
+    /// Path to the journal that will be migrated
+    #[clap(index = 1)]
+    source_path: PathBuf,
+    /// Path to the journal that will be created, if this is not
+    /// specified then the journal is migrated in place
+    #[clap(index = 2)]
+    target_path: Option<PathBuf>,
+}
+
+impl CliCommand for CmdJournalMigrate {
+    type Output = ();
+
+    fn run(self) -> Result<(), anyhow::Error> {
+        // When migrating in place the journal is written to a temporary
+        // file which then replaces the original once its complete
+        let target_path = match &self.target_path {
+            Some(path) => path.clone(),
+            None => {
+                let mut path = self.source_path.clone().into_os_string();
+                path.push(".migrating");
+                path.into()
+            }
+        };
+
+        let from_version = LogFileJournal::migrate(
+            &self.source_path,
+            &target_path,
+            JournalUpgraderRegistry::builtin(),
+        )?;
+        if self.target_path.is_none() {
+            std::fs::rename(&target_path, &self.source_path)?;
+        }
+
+        println!("Migrated journal from format version {from_version} to {JOURNAL_FORMAT_VERSION}");
+        Ok(())
+    }
+}
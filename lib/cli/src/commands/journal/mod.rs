@@ -6,6 +6,7 @@ mod extract;
 mod filter;
 mod import;
 mod inspect;
+mod migrate;
 #[cfg(feature = "fuse")]
 mod mount;
 
@@ -15,6 +16,7 @@ pub use extract::*;
 pub use filter::*;
 pub use import::*;
 pub use inspect::*;
+pub use migrate::*;
 #[cfg(feature = "fuse")]
 pub use mount::*;
 
@@ -31,6 +33,8 @@ pub enum CmdJournal {
     Inspect(CmdJournalInspect),
     /// Filters out certain events from a journal
     Filter(CmdJournalFilter),
+    /// Migrates a journal to the current journal format
+    Migrate(CmdJournalMigrate),
     /// Mounts the journal at a particular directory
     #[cfg(feature = "fuse")]
     Mount(CmdJournalMount),
@@ -48,6 +52,7 @@ impl CliCommand for CmdJournal {
             Self::Export(cmd) => cmd.run(),
             Self::Inspect(cmd) => cmd.run(),
             Self::Filter(cmd) => cmd.run(),
+            Self::Migrate(cmd) => cmd.run(),
             #[cfg(feature = "fuse")]
             Self::Mount(cmd) => cmd.run(),
             Self::Extract(cmd) => cmd.run(),
@@ -7,6 +7,10 @@ use super::fs::JournalFileSystemBuilder;
 use crate::commands::CliCommand;
 
 /// Mounts a journal as a file system on the local machine
+///
+/// The mount is read-write, changes made through it (e.g. patching a config
+/// file) are appended to the journal as file system events which will then
+/// be replayed when the journal is restored.
 #[derive(Debug, Parser)]
 pub struct CmdJournalMount {
     /// Path to the journal that will be printed
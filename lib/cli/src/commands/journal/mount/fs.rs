@@ -127,6 +127,19 @@ impl JournalFileSystemBuilder {
     }
 }
 
+/// Moves the inodes of a path, and of all the paths under it, to the path
+/// it was renamed to
+fn rename_inos(inos: &mut HashMap<u64, Cow<'static, str>>, old_path: &str, new_path: &str) {
+    for path in inos.values_mut() {
+        let renamed = match path.strip_prefix(old_path) {
+            Some("") => new_path.to_string(),
+            Some(rest) if rest.starts_with('/') => format!("{new_path}{rest}"),
+            _ => continue,
+        };
+        *path = renamed.into();
+    }
+}
+
 pub fn copy_journal_with_progress<R: ReadableJournal, W: WritableJournal>(
     from: &R,
     to: &W,
@@ -246,6 +259,7 @@ impl WritableJournal for MutexState {
                     .mem_fs
                     .new_open_options()
                     .create(o_flags.contains(Oflags::CREATE))
+                    .create_new(o_flags.contains(Oflags::CREATE | Oflags::EXCL))
                     .truncate(o_flags.contains(Oflags::TRUNC))
                     .write(true)
                     .read(true)
@@ -284,6 +298,39 @@ impl WritableJournal for MutexState {
                     })?;
                 }
             }
+            JournalEntry::FileDescriptorSetTimesV1 {
+                fd,
+                st_atim,
+                st_mtim,
+                fst_flags,
+            } => {
+                let (atime, mtime) = fst_flags_to_times(fst_flags, st_atim, st_mtim);
+                let handle = state.handle.clone();
+                if let Some(file) = state.lookup.get(&fd) {
+                    handle.block_on(async {
+                        let mut file = file.lock().await;
+                        file.set_times(atime, mtime)
+                    })?;
+                }
+            }
+            JournalEntry::PathSetTimesV1 {
+                path,
+                st_atim,
+                st_mtim,
+                fst_flags,
+                ..
+            } => {
+                let (atime, mtime) = fst_flags_to_times(fst_flags, st_atim, st_mtim);
+                // Directories do not carry times in the memory file system
+                if let Ok(mut file) = state
+                    .mem_fs
+                    .new_open_options()
+                    .write(true)
+                    .open(path.as_ref())
+                {
+                    file.set_times(atime, mtime)?;
+                }
+            }
             JournalEntry::FileDescriptorAllocateV1 { fd, offset, len } => {
                 let handle = state.handle.clone();
                 if let Some(file) = state.lookup.get(&fd) {
@@ -323,6 +370,9 @@ impl WritableJournal for MutexState {
             JournalEntry::EpollCreateV1 { fd } => {
                 state.seed.clip_val(fd + 1);
             }
+            JournalEntry::WatchCreateV1 { fd, .. } => {
+                state.seed.clip_val(fd + 1);
+            }
             JournalEntry::EpollCtlV1 {
                 epfd,
                 op,
@@ -347,6 +397,27 @@ impl WritableJournal for MutexState {
 }
 
 impl JournalFileSystem {
+    /// Applies the entries to the mounted file system and then appends them
+    /// to the journal, entries are only appended to the journal when they
+    /// were successfully applied so that the journal replays cleanly
+    fn record<'a>(&'a self, entries: Vec<JournalEntry<'a>>) -> Result<(), libc::c_int> {
+        for entry in entries {
+            if let Err(err) = self.state.write(entry.clone()) {
+                tracing::trace!("fs::record apply err={err}");
+                return Err(anyhow_err_to_errno(&err));
+            }
+            if let Err(err) = self.journal.write(entry) {
+                tracing::trace!("fs::record journal err=EIO - {err}");
+                return Err(libc::EIO);
+            }
+        }
+        if let Err(err) = self.journal.flush() {
+            tracing::trace!("fs::record flush err=EIO - {err}");
+            return Err(libc::EIO);
+        }
+        Ok(())
+    }
+
     fn compute_path<'a>(&'a self, parent: u64, name: &'a OsStr) -> Result<Cow<'_, str>, i32> {
         // Get the path from the ino otherwise it is not a known
         // path (this means the other methods have to be hit first)
@@ -374,7 +445,11 @@ impl Filesystem for JournalFileSystem {
         Ok(())
     }
 
-    fn destroy(&mut self, _req: &Request) {}
+    fn destroy(&mut self, _req: &Request) {
+        if let Err(err) = self.journal.flush() {
+            tracing::warn!("failed to flush the journal - {err}");
+        }
+    }
 
     fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
         let path = match self.compute_path(parent, name) {
@@ -418,8 +493,8 @@ impl Filesystem for JournalFileSystem {
         _uid: Option<u32>,
         _gid: Option<u32>,
         size: Option<u64>,
-        _atime: Option<time01::Timespec>,
-        _mtime: Option<time01::Timespec>,
+        atime: Option<time01::Timespec>,
+        mtime: Option<time01::Timespec>,
         fh: Option<u64>,
         _crtime: Option<time01::Timespec>,
         _chgtime: Option<time01::Timespec>,
@@ -427,158 +502,99 @@ impl Filesystem for JournalFileSystem {
         _flags: Option<u32>,
         reply: ReplyAttr,
     ) {
-        let mut entries = Vec::new();
+        let path = match self.reverse_ino(ino) {
+            Ok(a) => a,
+            Err(err) => {
+                tracing::trace!("fs::setattr reverse_ino({ino}) errno={err}");
+                reply.error(err);
+                return;
+            }
+        };
+
+        let mut fst_flags = wasi::Fstflags::empty();
+        let mut st_atim = 0;
+        let mut st_mtim = 0;
+        if let Some(atime) = atime {
+            fst_flags.insert(wasi::Fstflags::SET_ATIM);
+            st_atim = timespec_to_nanos(atime);
+        }
+        if let Some(mtime) = mtime {
+            fst_flags.insert(wasi::Fstflags::SET_MTIM);
+            st_mtim = timespec_to_nanos(mtime);
+        }
 
-        let attr = match fh {
+        let mut entries = Vec::new();
+        match fh {
             Some(fd) => {
                 let fd = fd as u32;
-                let mut state = self.state.inner.lock().unwrap();
-                let file = match state.lookup.get_mut(&fd) {
-                    Some(f) => f.clone(),
-                    None => {
-                        tracing::trace!("fs::getattr noent (fd={fd})");
+                {
+                    let state = self.state.inner.lock().unwrap();
+                    if !state.lookup.contains_key(&fd) {
+                        tracing::trace!("fs::setattr noent (fd={fd})");
                         reply.error(libc::ENOENT);
                         return;
                     }
-                };
-
-                self.handle.block_on(async {
-                    let mut file = file.lock().await;
-
-                    if let Some(size) = size {
-                        entries.push(JournalEntry::FileDescriptorSetSizeV1 {
-                            fd: fd as u32,
-                            st_size: size,
-                        })
-                    }
+                }
 
-                    FileAttr {
-                        ino,
-                        size: file.size(),
-                        blocks: (1u64.max(file.size()) - 1 / 512) + 1,
-                        atime: time01::Timespec::new(file.last_accessed() as i64, 0),
-                        mtime: time01::Timespec::new(file.last_modified() as i64, 0),
-                        ctime: time01::Timespec::new(file.created_time() as i64, 0),
-                        crtime: time01::Timespec::new(file.created_time() as i64, 0),
-                        kind: fuser::FileType::RegularFile,
-                        perm: 0o644,
-                        nlink: 1,
-                        uid: 0,
-                        gid: 0,
-                        rdev: 0,
-                        flags: 0,
-                    }
-                })
+                if let Some(size) = size {
+                    entries.push(JournalEntry::FileDescriptorSetSizeV1 { fd, st_size: size });
+                }
+                if !fst_flags.is_empty() {
+                    entries.push(JournalEntry::FileDescriptorSetTimesV1 {
+                        fd,
+                        st_atim,
+                        st_mtim,
+                        fst_flags,
+                    });
+                }
             }
             None => {
-                let path = match self.reverse_ino(ino) {
-                    Ok(a) => a,
-                    Err(err) => {
-                        tracing::trace!("fs::setattr reverse_ino({ino}) errno={err}");
-                        reply.error(err);
-                        return;
-                    }
-                };
-
-                let fh;
-                let mut state = self.state.inner.lock().unwrap();
-                let file = state
-                    .mem_fs
-                    .new_open_options()
-                    .read(true)
-                    .write(true)
-                    .open(&Path::new(path.as_ref()));
-                match file {
-                    Ok(file) => {
-                        // Reserve a file descriptor and close the state
-                        fh = state.seed.next_val();
-                        drop(state);
-
-                        entries.push(JournalEntry::OpenFileDescriptorV1 {
-                            fd: fh,
-                            dirfd: VIRTUAL_ROOT_FD,
-                            dirflags: 0,
-                            path,
-                            o_flags: wasi::Oflags::empty(),
-                            fs_rights_base: wasi::Rights::all(),
-                            fs_rights_inheriting: wasi::Rights::all(),
-                            fs_flags: wasi::Fdflags::empty(),
-                        });
-                        if let Some(size) = size {
-                            entries.push(JournalEntry::FileDescriptorSetSizeV1 {
-                                fd: fh as u32,
-                                st_size: size,
-                            })
-                        }
-                        entries.push(JournalEntry::CloseFileDescriptorV1 { fd: fh });
-
-                        for entry in entries.iter() {
-                            if self.state.write(entry.clone()).is_err() {
-                                tracing::trace!("fs::open err=EIO");
-                                reply.error(libc::EIO);
-                                return;
-                            }
-                        }
-                        for entry in entries.iter() {
-                            if self.journal.write(entry.clone()).is_err() {
-                                tracing::trace!("fs::open err=EIO");
-                                reply.error(libc::EIO);
-                                return;
-                            }
-                        }
-                        FileAttr {
-                            ino,
-                            size: file.size(),
-                            blocks: (1u64.max(file.size()) - 1 / 512) + 1,
-                            atime: time01::Timespec::new(file.last_accessed() as i64, 0),
-                            mtime: time01::Timespec::new(file.last_modified() as i64, 0),
-                            ctime: time01::Timespec::new(file.created_time() as i64, 0),
-                            crtime: time01::Timespec::new(file.created_time() as i64, 0),
-                            kind: fuser::FileType::RegularFile,
-                            perm: 0o644,
-                            nlink: 1,
-                            uid: 0,
-                            gid: 0,
-                            rdev: 0,
-                            flags: 0,
-                        }
-                    }
-                    Err(FsError::EntryNotFound) => {
-                        // Maybe its a directory, in which case we are done
-                        if let Ok(meta) = state.mem_fs.metadata(&Path::new(path.as_ref())) {
-                            FileAttr {
-                                ino,
-                                size: meta.len,
-                                blocks: (1u64.max(meta.len) - 1 / 512) + 1,
-                                atime: time01::Timespec::new(meta.accessed as i64, 0),
-                                mtime: time01::Timespec::new(meta.modified as i64, 0),
-                                ctime: time01::Timespec::new(meta.created as i64, 0),
-                                crtime: time01::Timespec::new(meta.created as i64, 0),
-                                kind: file_type_to_kind(meta.ft),
-                                perm: 0o644,
-                                nlink: 1,
-                                uid: 0,
-                                gid: 0,
-                                rdev: 0,
-                                flags: 0,
-                            }
-                        } else {
-                            tracing::trace!("fs::setattr open_file({path}) err=ENOENT");
-                            reply.error(libc::ENOENT);
-                            return;
-                        }
-                    }
-                    Err(err) => {
-                        tracing::trace!("fs::setattr open_file({path}) err={err}");
-                        reply.error(libc::EIO);
-                        return;
-                    }
+                // Truncating a file by path (e.g. `truncate -s`) requires the
+                // file to be opened as thats how the guest would have done it
+                if let Some(size) = size {
+                    let fh = {
+                        let mut state = self.state.inner.lock().unwrap();
+                        state.seed.next_val()
+                    };
+                    entries.push(JournalEntry::OpenFileDescriptorV1 {
+                        fd: fh,
+                        dirfd: VIRTUAL_ROOT_FD,
+                        dirflags: 0,
+                        path: path.clone(),
+                        o_flags: wasi::Oflags::empty(),
+                        fs_rights_base: wasi::Rights::all(),
+                        fs_rights_inheriting: wasi::Rights::all(),
+                        fs_flags: wasi::Fdflags::empty(),
+                    });
+                    entries.push(JournalEntry::FileDescriptorSetSizeV1 {
+                        fd: fh,
+                        st_size: size,
+                    });
+                    entries.push(JournalEntry::CloseFileDescriptorV1 { fd: fh });
+                }
+                if !fst_flags.is_empty() {
+                    entries.push(JournalEntry::PathSetTimesV1 {
+                        fd: VIRTUAL_ROOT_FD,
+                        flags: 0,
+                        path: path.clone(),
+                        st_atim,
+                        st_mtim,
+                        fst_flags,
+                    });
                 }
             }
-        };
+        }
 
-        // Return the data
-        reply.attr(&time01::Timespec::new(1, 0), &attr)
+        if let Err(err) = self.record(entries) {
+            tracing::trace!("fs::setattr err={err}");
+            reply.error(err);
+            return;
+        }
+
+        match self.attr(path) {
+            Ok(attr) => reply.attr(&time01::Timespec::new(1, 0), &attr),
+            Err(err) => reply.error(err),
+        }
     }
 
     fn setxattr(
@@ -624,12 +640,16 @@ impl Filesystem for JournalFileSystem {
         };
 
         // Write the journals
+        let mut o_flags = wasi::Oflags::empty();
+        if (flags as i32 & libc::O_TRUNC) != 0 {
+            o_flags.insert(wasi::Oflags::TRUNC);
+        }
         let entry = JournalEntry::OpenFileDescriptorV1 {
             fd: fh,
             dirfd: VIRTUAL_ROOT_FD,
             dirflags: 0,
             path,
-            o_flags: wasi::Oflags::empty(),
+            o_flags,
             fs_rights_base: wasi::Rights::all(),
             fs_rights_inheriting: wasi::Rights::all(),
             fs_flags: wasi::Fdflags::empty(),
@@ -683,6 +703,11 @@ impl Filesystem for JournalFileSystem {
             reply.error(libc::EIO);
             return;
         }
+        if self.journal.flush().is_err() {
+            tracing::trace!("fs::release flush err=EIO");
+            reply.error(libc::EIO);
+            return;
+        }
 
         tracing::trace!("fs::release ok");
         reply.ok();
@@ -714,19 +739,26 @@ impl Filesystem for JournalFileSystem {
         };
 
         // Write the journals
+        let mut o_flags = wasi::Oflags::CREATE;
+        if (flags as i32 & libc::O_TRUNC) != 0 {
+            o_flags.insert(wasi::Oflags::TRUNC);
+        }
+        if (flags as i32 & libc::O_EXCL) != 0 {
+            o_flags.insert(wasi::Oflags::EXCL);
+        }
         let entry = JournalEntry::OpenFileDescriptorV1 {
             fd: fh,
             dirfd: VIRTUAL_ROOT_FD,
             dirflags: 0,
             path,
-            o_flags: wasi::Oflags::CREATE,
+            o_flags,
             fs_rights_base: wasi::Rights::all(),
             fs_rights_inheriting: wasi::Rights::all(),
             fs_flags: wasi::Fdflags::empty(),
         };
         if let Err(err) = self.state.write(entry.clone()) {
-            tracing::trace!("fs::create (j1) err=EIO - {err}");
-            reply.error(libc::EIO);
+            tracing::trace!("fs::create (j1) err={err}");
+            reply.error(anyhow_err_to_errno(&err));
             return;
         }
         if let Err(err) = self.journal.write(entry) {
@@ -978,8 +1010,10 @@ impl Filesystem for JournalFileSystem {
             fd: VIRTUAL_ROOT_FD,
             path: path.clone(),
         };
-        self.state.write(entry.clone());
-        self.journal.write(entry);
+        if let Err(err) = self.record(vec![entry]) {
+            tracing::trace!("fs::mkdir err={err}");
+            return reply.error(err);
+        }
 
         match self.attr(path) {
             Ok(meta) => {
@@ -1006,8 +1040,10 @@ impl Filesystem for JournalFileSystem {
             fd: VIRTUAL_ROOT_FD,
             path: path.clone(),
         };
-        self.state.write(entry.clone());
-        self.journal.write(entry);
+        if let Err(err) = self.record(vec![entry]) {
+            tracing::trace!("fs::rmdir err={err}");
+            return reply.error(err);
+        }
         tracing::trace!("fs::rmdir ok");
         reply.ok();
     }
@@ -1025,8 +1061,10 @@ impl Filesystem for JournalFileSystem {
             fd: VIRTUAL_ROOT_FD,
             path: path.clone(),
         };
-        self.state.write(entry.clone());
-        self.journal.write(entry);
+        if let Err(err) = self.record(vec![entry]) {
+            tracing::trace!("fs::unlink err={err}");
+            return reply.error(err);
+        }
         tracing::trace!("fs::unlink ok");
         reply.ok();
     }
@@ -1068,14 +1106,46 @@ impl Filesystem for JournalFileSystem {
     fn rename(
         &mut self,
         _req: &Request,
-        _parent: u64,
-        _name: &OsStr,
-        _newparent: u64,
-        _newname: &OsStr,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
         reply: ReplyEmpty,
     ) {
-        tracing::trace!("fs::rename err=ENOSYS");
-        reply.error(libc::ENOSYS);
+        let old_path = match self.compute_path(parent, name) {
+            Ok(a) => a,
+            Err(err) => {
+                tracing::trace!("fs::rename err={err}");
+                return reply.error(err);
+            }
+        };
+        let new_path = match self.compute_path(newparent, newname) {
+            Ok(a) => a,
+            Err(err) => {
+                tracing::trace!("fs::rename err={err}");
+                return reply.error(err);
+            }
+        };
+
+        let entry = JournalEntry::PathRenameV1 {
+            old_fd: VIRTUAL_ROOT_FD,
+            old_path: old_path.clone(),
+            new_fd: VIRTUAL_ROOT_FD,
+            new_path: new_path.clone(),
+        };
+        if let Err(err) = self.record(vec![entry]) {
+            tracing::trace!("fs::rename err={err}");
+            return reply.error(err);
+        }
+
+        // The kernel keeps using the inodes it already knows for the entry
+        // (and everything under it) so they now have to lead to the new path
+        {
+            let mut state = self.state.inner.lock().unwrap();
+            rename_inos(&mut state.inos, &old_path, &new_path);
+        }
+        tracing::trace!("fs::rename ok");
+        reply.ok();
     }
 
     fn link(
@@ -1091,8 +1161,13 @@ impl Filesystem for JournalFileSystem {
     }
 
     fn fsync(&mut self, _req: &Request, _ino: u64, _fh: u64, _datasync: bool, reply: ReplyEmpty) {
-        tracing::trace!("fs::fsync err=ENOSYS");
-        reply.error(libc::ENOSYS);
+        if let Err(err) = self.journal.flush() {
+            tracing::trace!("fs::fsync err=EIO - {err}");
+            reply.error(libc::EIO);
+            return;
+        }
+        tracing::trace!("fs::fsync ok");
+        reply.ok();
     }
 
     fn opendir(&mut self, _req: &Request, _ino: u64, _flags: u32, reply: ReplyOpen) {
@@ -1218,3 +1293,71 @@ fn file_type_to_kind(ft: virtual_fs::FileType) -> fuser::FileType {
         fuser::FileType::RegularFile
     }
 }
+
+fn timespec_to_nanos(time: time01::Timespec) -> u64 {
+    (time.sec.max(0) as u64) * 1_000_000_000 + (time.nsec.max(0) as u64)
+}
+
+fn fst_flags_to_times(
+    fst_flags: wasi::Fstflags,
+    st_atim: wasi::Timestamp,
+    st_mtim: wasi::Timestamp,
+) -> (Option<u64>, Option<u64>) {
+    let now = || timespec_to_nanos(time01::get_time());
+    let atime = if fst_flags.contains(wasi::Fstflags::SET_ATIM_NOW) {
+        Some(now())
+    } else if fst_flags.contains(wasi::Fstflags::SET_ATIM) {
+        Some(st_atim)
+    } else {
+        None
+    };
+    let mtime = if fst_flags.contains(wasi::Fstflags::SET_MTIM_NOW) {
+        Some(now())
+    } else if fst_flags.contains(wasi::Fstflags::SET_MTIM) {
+        Some(st_mtim)
+    } else {
+        None
+    };
+    (atime, mtime)
+}
+
+fn anyhow_err_to_errno(err: &anyhow::Error) -> libc::c_int {
+    match err.downcast_ref::<FsError>() {
+        Some(FsError::EntryNotFound) => libc::ENOENT,
+        Some(FsError::AlreadyExists) => libc::EEXIST,
+        Some(FsError::DirectoryNotEmpty) => libc::ENOTEMPTY,
+        Some(FsError::BaseNotDirectory) => libc::ENOTDIR,
+        Some(FsError::NotAFile) => libc::EISDIR,
+        Some(FsError::PermissionDenied) => libc::EACCES,
+        Some(FsError::InvalidInput) => libc::EINVAL,
+        Some(FsError::StorageFull) => libc::ENOSPC,
+        _ => libc::EIO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_inos() {
+        let mut inos: HashMap<u64, Cow<'static, str>> = HashMap::new();
+        inos.insert(1, "/a".into());
+        inos.insert(2, "/a/b.txt".into());
+        inos.insert(3, "/a/c/d.txt".into());
+        inos.insert(4, "/ab.txt".into());
+        inos.insert(5, "/other".into());
+
+        rename_inos(&mut inos, "/a", "/x/y");
+        assert_eq!(inos[&1], "/x/y");
+        assert_eq!(inos[&2], "/x/y/b.txt");
+        assert_eq!(inos[&3], "/x/y/c/d.txt");
+        // Siblings that only share a prefix are left alone
+        assert_eq!(inos[&4], "/ab.txt");
+        assert_eq!(inos[&5], "/other");
+
+        rename_inos(&mut inos, "/x/y/b.txt", "/b.txt");
+        assert_eq!(inos[&2], "/b.txt");
+        assert_eq!(inos[&1], "/x/y");
+    }
+}
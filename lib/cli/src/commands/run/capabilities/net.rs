@@ -1,7 +1,7 @@
 use std::{
     fmt::Display,
     net::{IpAddr, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::OnceLock,
     time::Duration,
@@ -14,7 +14,8 @@ use dialoguer::theme::ColorfulTheme;
 use virtual_net::{
     DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, StreamSecurity,
     UnsupportedVirtualNetworking, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
-    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualUnixDatagramSocket,
+    VirtualUnixListener, VirtualUnixSocket,
 };
 
 /// A custom implementation of the [`virtual_net::VirtualNetwork`] that asks users if they want to
@@ -305,4 +306,24 @@ impl VirtualNetworking for AskingNetworking {
     ) -> Result<Vec<IpAddr>> {
         call!(self, resolve, host, port, dns_server);
     }
+
+    /// Binds a unix domain socket to a path of the host file system that
+    /// connections can then be accepted on
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixListener + Sync>> {
+        call!(self, listen_unix, path);
+    }
+
+    /// Connects to the unix domain socket at a path of the host file system
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        call!(self, connect_unix, path);
+    }
+
+    /// Opens a datagram unix domain socket, which is bound to a path of the
+    /// host file system if one is given
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        call!(self, bind_unix_datagram, path);
+    }
 }
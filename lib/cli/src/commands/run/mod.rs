@@ -413,11 +413,15 @@ impl Run {
         config: &mut wcgi::Config,
         uses: Vec<BinaryPackage>,
     ) -> Result<(), Error> {
+        let mut mapped_dirs = self.wasi.mapped_dirs.clone();
+        let quota_dirs = self.wasi.build_quota_directories(&mut mapped_dirs)?;
+
         config
             .args(self.args.clone())
             .addr(self.wcgi.addr)
             .envs(self.wasi.env_vars.clone())
-            .map_directories(self.wasi.mapped_dirs.clone())
+            .map_directories(mapped_dirs)
+            .mount_directories(quota_dirs)
             .callbacks(Callbacks::new(self.wcgi.addr))
             .inject_packages(uses);
         *config.capabilities() = self.wasi.capabilities();
@@ -523,8 +527,10 @@ impl Run {
 
         let mut runner = WasiRunner::new();
 
-        let (is_home_mapped, is_tmp_mapped, mapped_diretories) =
+        let (is_home_mapped, is_tmp_mapped, mut mapped_diretories) =
             self.wasi.build_mapped_directories()?;
+        let quota_directories = self.wasi.build_quota_directories(&mut mapped_diretories)?;
+        let is_tmp_mapped = is_tmp_mapped || quota_directories.iter().any(|d| d.guest == "/tmp");
 
         runner
             .with_args(&self.args)
@@ -532,6 +538,8 @@ impl Run {
             .with_envs(self.wasi.env_vars.clone())
             .with_mapped_host_commands(self.wasi.build_mapped_commands()?)
             .with_mapped_directories(mapped_diretories)
+            .with_mounted_directories(self.wasi.build_mounted_archives()?)
+            .with_mounted_directories(quota_directories)
             .with_home_mapped(is_home_mapped)
             .with_tmp_mapped(is_tmp_mapped)
             .with_forward_host_env(self.wasi.forward_host_env)
@@ -541,6 +549,12 @@ impl Run {
             runner.with_entry_function(entry_function);
         }
 
+        if let Some(identity) = self.wasi.identity {
+            runner.with_identity(identity);
+        }
+
+        runner.with_host_socket_directories(self.wasi.host_socket_dirs.clone());
+
         #[cfg(feature = "journal")]
         {
             for trigger in self.wasi.snapshot_on.iter().cloned() {
@@ -1070,6 +1084,10 @@ impl<R: wasmer_wasix::Runtime + Send + Sync> wasmer_wasix::Runtime for Monitorin
         self.runtime.tty()
     }
 
+    fn tls(&self) -> Option<&virtual_net::tls::TlsConfig> {
+        self.runtime.tls()
+    }
+
     #[cfg(feature = "journal")]
     fn read_only_journals<'a>(
         &'a self,
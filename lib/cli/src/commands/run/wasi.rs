@@ -1,5 +1,6 @@
 use std::{
     collections::{BTreeSet, HashMap},
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
     str::FromStr,
     sync::{mpsc::Sender, Arc},
@@ -8,16 +9,27 @@ use std::{
 
 use anyhow::{bail, Context, Result};
 use bytes::Bytes;
+use bytesize::ByteSize;
 use clap::Parser;
 use tokio::runtime::Handle;
 use url::Url;
-use virtual_fs::{DeviceFile, FileSystem, PassthruFileSystem, RootFileSystemBuilder};
-use virtual_net::ruleset::Ruleset;
+use virtual_fs::{
+    ArchiveFileSystem, DeviceFile, FileSystem, PassthruFileSystem, QuotaFileSystem,
+    RootFileSystemBuilder, TmpFileSystem,
+};
+use virtual_net::{
+    dns::{CachingResolver, DnsTransport, StaticHostsResolver, SystemResolver, UpstreamResolver},
+    ruleset::Ruleset,
+    CaptureNetworking, DynDnsResolver, DynVirtualNetworking, RecordingNetworking, ReplayNetworking,
+    ThrottleLimits, ThrottledNetworking, TlsConfig, VirtualNetworking,
+};
 use wasmer::{Engine, Function, Instance, Memory32, Memory64, Module, RuntimeError, Store, Value};
 use wasmer_config::package::PackageSource as PackageSpecifier;
 use wasmer_types::ModuleHash;
 #[cfg(feature = "journal")]
-use wasmer_wasix::journal::{LogFileJournal, SnapshotTrigger};
+use wasmer_wasix::journal::{
+    DedupMemoryJournal, JournalRestorePoint, LogFileJournal, RestorePointJournal, SnapshotTrigger,
+};
 use wasmer_wasix::{
     bin_factory::BinaryPackage,
     capabilities::Capabilities,
@@ -27,7 +39,7 @@ use wasmer_wasix::{
     os::{tty_sys::SysTty, TtyBridge},
     rewind_ext,
     runners::MAPPED_CURRENT_DIR_DEFAULT_PATH,
-    runners::{MappedCommand, MappedDirectory},
+    runners::{MappedCommand, MappedDirectory, MountedDirectory},
     runtime::{
         module_cache::{FileSystemCache, ModuleCache},
         package_loader::{BuiltinPackageLoader, PackageLoader},
@@ -42,12 +54,15 @@ use wasmer_wasix::{
     types::__WASI_STDIN_FILENO,
     wasmer_wasix_types::wasi::Errno,
     PluggableRuntime, RewindState, Runtime, WasiEnv, WasiEnvBuilder, WasiError, WasiFunctionEnv,
-    WasiVersion,
+    WasiIdentity, WasiVersion,
 };
 
 use crate::{
     config::{UserRegistry, WasmerEnv},
-    utils::{parse_envvar, parse_mapdir},
+    utils::{
+        parse_dns_server, parse_envvar, parse_host_entry, parse_identity, parse_mapdir,
+        parse_mount_archive, parse_quota, DirectoryQuota,
+    },
 };
 
 use super::{
@@ -72,6 +87,25 @@ pub struct Wasi {
     )]
     pub(crate) mapped_dirs: Vec<MappedDirectory>,
 
+    /// Mount a `.tar`, `.tar.gz` or `.zip` archive as a read-only directory
+    /// for the Wasm module, without unpacking it
+    #[clap(
+        long = "mount-archive",
+        name = "GUEST_DIR:ARCHIVE",
+        value_parser=parse_mount_archive,
+    )]
+    pub(crate) mounted_archives: Vec<MappedDirectory>,
+
+    /// Limit the disk usage of a directory of the guest, optionally along
+    /// with its number of entries. Directories mapped from the host are
+    /// limited in place, any other directory is created in memory
+    #[clap(
+        long = "quota",
+        name = "GUEST_DIR:SIZE[:ENTRIES]",
+        value_parser=parse_quota,
+    )]
+    pub(crate) quotas: Vec<DirectoryQuota>,
+
     /// Pass custom environment variables
     #[clap(
         long = "env",
@@ -84,6 +118,25 @@ pub struct Wasi {
     #[clap(long, env)]
     pub(crate) forward_host_env: bool,
 
+    /// Run as a particular user (and group), the permissions of files and
+    /// directories are then enforced
+    #[clap(
+        long = "user",
+        name = "UID[:GID]",
+        value_parser=parse_identity,
+    )]
+    pub(crate) identity: Option<WasiIdentity>,
+
+    /// Pass the unix domain sockets of a guest directory through to the
+    /// sockets of a host directory, other unix domain sockets only exist
+    /// within the virtual file system
+    #[clap(
+        long = "unix-socket-dir",
+        name = "GUEST_DIR:HOST_SOCKET_DIR",
+        value_parser=parse_mapdir,
+    )]
+    pub(crate) host_socket_dirs: Vec<MappedDirectory>,
+
     /// List of other containers this module depends on
     #[clap(long = "use", name = "USE")]
     pub(crate) uses: Vec<String>,
@@ -120,6 +173,83 @@ pub struct Wasi {
     // and when --net=<ruleset> is specified, the inner Option will be initialized: Some(Some(ruleset))
     pub networking: Option<Option<String>>,
 
+    /// Logs every allow or deny decision that is taken by the --net ruleset
+    #[clap(long = "net-audit")]
+    pub net_audit: bool,
+
+    /// Logs the accesses that the --net ruleset would deny but lets them
+    /// through anyway, which helps with rolling out a new ruleset
+    #[clap(long = "net-dry-run")]
+    pub net_dry_run: bool,
+
+    /// Limits the number of bytes per second that can be sent over the
+    /// network (e.g. 1MiB), writes are slowed down once it is reached
+    #[clap(long = "net-upload-limit")]
+    pub net_upload_limit: Option<ByteSize>,
+
+    /// Limits the number of bytes per second that can be received over the
+    /// network (e.g. 1MiB), reads are slowed down once it is reached
+    #[clap(long = "net-download-limit")]
+    pub net_download_limit: Option<ByteSize>,
+
+    /// Limits the number of sockets that can be open at the same time,
+    /// opening another one waits until one of them is closed
+    #[clap(long = "net-max-sockets")]
+    pub net_max_sockets: Option<usize>,
+
+    /// Limits the number of new connections (both inbound and outbound)
+    /// per second
+    #[clap(long = "net-connection-rate")]
+    pub net_connection_rate: Option<u32>,
+
+    /// Records the network traffic of the instance to a pcapng file that
+    /// can be opened with Wireshark
+    #[clap(long = "net-capture", value_name = "FILE")]
+    pub net_capture: Option<PathBuf>,
+
+    /// Records the name resolutions, TCP traffic and datagrams of the
+    /// instance to a file that --net-replay can play back later on
+    #[clap(long = "net-record", value_name = "FILE")]
+    pub net_record: Option<PathBuf>,
+
+    /// Serves the name resolutions, TCP traffic and datagrams of a
+    /// --net-record recording (in the recorded order, without waiting for the recorded
+    /// times) instead of using the host network
+    #[clap(
+        long = "net-replay",
+        value_name = "FILE",
+        conflicts_with = "net_record"
+    )]
+    pub net_replay: Option<PathBuf>,
+
+    /// Resolves a hostname to a fixed address for the guest, in the form
+    /// `<host>=<ip>` (e.g. `api.example.com=127.0.0.1`)
+    #[clap(long = "add-host", value_name = "HOST=IP", value_parser = parse_host_entry)]
+    pub add_hosts: Vec<(String, IpAddr)>,
+
+    /// Resolves the hostnames of the guest with this DNS server instead of
+    /// the resolver of the host (e.g. `1.1.1.1`, `tcp://[::1]:5353`)
+    #[clap(long = "dns-server", value_name = "ADDR", value_parser = parse_dns_server)]
+    pub dns_server: Option<(SocketAddr, DnsTransport)>,
+
+    /// Lets the guest upgrade its TCP sockets to TLS that is terminated by
+    /// the host, which trusts the Mozilla root certificates by default
+    #[clap(long = "host-tls")]
+    pub host_tls: bool,
+
+    /// Also trusts the certificates of this PEM file for host TLS
+    #[clap(long = "tls-ca", value_name = "FILE")]
+    pub tls_cas: Vec<PathBuf>,
+
+    /// The PEM certificate chain presented by sockets that the guest
+    /// upgrades to the server side of host TLS
+    #[clap(long = "tls-cert", value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// The PEM private key of the --tls-cert certificate
+    #[clap(long = "tls-key", value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
     /// Disables the TTY bridge
     #[clap(long = "no-tty")]
     pub no_tty: bool,
@@ -177,6 +307,13 @@ pub struct Wasi {
     #[clap(long = "with-compact-on-growth", default_value = "0.15")]
     pub with_compact_on_growth: f32,
 
+    /// Flag that indicates if memory written to the journal will be broken
+    /// into pages that are only stored once, which greatly reduces the size
+    /// of journals that take many snapshots of mostly unchanged memory
+    #[cfg(feature = "journal")]
+    #[clap(long = "enable-memory-dedup")]
+    pub enable_memory_dedup: bool,
+
     /// Indicates what events will cause a snapshot to be taken
     /// and written to the journal file.
     ///
@@ -209,6 +346,22 @@ pub struct Wasi {
     #[clap(long = "skip-journal-stdio")]
     pub skip_stdio_during_bootstrap: bool,
 
+    /// Stops restoring the journal at a particular point rather than replaying
+    /// it all the way to the end, which makes it possible to reproduce the
+    /// state of a process at an earlier moment in time.
+    ///
+    /// The restore point is either a record offset within the journal
+    /// (e.g. '4096' or '0x1000'), in which case the restore stops just before
+    /// the record at that offset, or a zero-based snapshot index
+    /// (e.g. 'snapshot-3'), in which case the restore stops just after that
+    /// snapshot.
+    ///
+    /// The restore point applies to the last read-only journal that is specified
+    /// and can not be combined with writable journals.
+    #[cfg(feature = "journal")]
+    #[clap(long = "journal-restore-to")]
+    pub journal_restore_to: Option<JournalRestorePoint>,
+
     /// Allow instances to send http requests.
     ///
     /// Access to domains is granted by default.
@@ -303,8 +456,12 @@ impl Wasi {
 
         let mut builder = {
             // If we preopen anything from the host then shallow copy it over
+            let mut host_dirs = self.mapped_dirs.clone();
+            let quota_dirs = self.build_quota_directories(&mut host_dirs)?;
+
             let root_fs = RootFileSystemBuilder::new()
                 .with_tty(Box::new(DeviceFile::new(__WASI_STDIN_FILENO)))
+                .with_tmp(quota_dirs.iter().all(|d| d.guest != "/tmp"))
                 .build();
 
             let mut mapped_dirs = Vec::new();
@@ -391,7 +548,7 @@ impl Wasi {
                 // TODO: should we expose the common ancestor instead of root?
                 let fs_backing: Arc<dyn FileSystem + Send + Sync> =
                     Arc::new(PassthruFileSystem::new(default_fs_backing()));
-                for MappedDirectory { host, guest } in self.mapped_dirs.clone() {
+                for MappedDirectory { host, guest } in host_dirs {
                     let host = if !host.is_absolute() {
                         Path::new("/").join(host)
                     } else {
@@ -401,6 +558,11 @@ impl Wasi {
                 }
             }
 
+            let mounted_dirs = self.build_mounted_archives()?.into_iter().chain(quota_dirs);
+            for MountedDirectory { guest, fs } in mounted_dirs {
+                root_fs.mount(guest.into(), &fs, "/".into())?;
+            }
+
             // Open the root of the new filesystem
             let b = builder
                 .sandbox_fs(root_fs)
@@ -416,6 +578,14 @@ impl Wasi {
 
         *builder.capabilities_mut() = self.capabilities();
 
+        if let Some(identity) = self.identity {
+            builder.set_identity(identity);
+        }
+
+        for MappedDirectory { host, guest } in &self.host_socket_dirs {
+            builder.add_host_socket_dir(guest, host);
+        }
+
         #[cfg(feature = "journal")]
         {
             for trigger in self.snapshot_on.iter().cloned() {
@@ -445,15 +615,30 @@ impl Wasi {
     pub fn build_journals(
         &self,
     ) -> anyhow::Result<(Vec<Arc<DynReadableJournal>>, Vec<Arc<DynJournal>>)> {
+        if self.journal_restore_to.is_some() {
+            if self.read_only_journals.is_empty() {
+                bail!("A journal restore point requires a read-only journal (--journal)");
+            }
+            if !self.writable_journals.is_empty() {
+                bail!("A journal restore point can not be combined with writable journals");
+            }
+        }
+
         let mut readable = Vec::new();
-        for journal in self.read_only_journals.clone() {
+        let num_readable = self.read_only_journals.len();
+        for (index, journal) in self.read_only_journals.iter().cloned().enumerate() {
             if matches!(std::fs::metadata(&journal), Err(e) if e.kind() == std::io::ErrorKind::NotFound)
             {
                 bail!("Read-only journal file does not exist: {journal:?}");
             }
 
-            readable
-                .push(Arc::new(LogFileJournal::new_readonly(journal)?) as Arc<DynReadableJournal>);
+            let journal = LogFileJournal::new_readonly(journal)?;
+            match self.journal_restore_to {
+                Some(restore_point) if index + 1 == num_readable => readable
+                    .push(Arc::new(RestorePointJournal::new(journal, restore_point))
+                        as Arc<DynReadableJournal>),
+                _ => readable.push(Arc::new(journal) as Arc<DynReadableJournal>),
+            }
         }
 
         let mut writable = Vec::new();
@@ -466,9 +651,18 @@ impl Wasi {
                 if self.with_compact_on_growth.is_normal() && self.with_compact_on_growth != 0f32 {
                     journal = journal.with_compact_on_factor_size(self.with_compact_on_growth);
                 }
-                writable.push(Arc::new(journal) as Arc<DynJournal>);
+                if self.enable_memory_dedup {
+                    writable.push(Arc::new(DedupMemoryJournal::new(journal)?) as Arc<DynJournal>);
+                } else {
+                    writable.push(Arc::new(journal) as Arc<DynJournal>);
+                }
             } else {
-                writable.push(Arc::new(LogFileJournal::new(journal)?));
+                let journal = LogFileJournal::new(journal)?;
+                if self.enable_memory_dedup {
+                    writable.push(Arc::new(DedupMemoryJournal::new(journal)?));
+                } else {
+                    writable.push(Arc::new(journal));
+                }
             }
         }
         Ok((readable, writable))
@@ -479,6 +673,51 @@ impl Wasi {
         Ok(Vec::new())
     }
 
+    /// Opens the archives passed with `--mount-archive`, returning the guest
+    /// directory each of them is mounted at.
+    pub fn build_mounted_archives(&self) -> Result<Vec<MountedDirectory>, anyhow::Error> {
+        self.mounted_archives
+            .iter()
+            .map(|MappedDirectory { host, guest }| {
+                let fs = ArchiveFileSystem::from_path(host).with_context(|| {
+                    format!("Unable to open the archive at \"{}\"", host.display())
+                })?;
+                Ok(MountedDirectory {
+                    guest: guest.clone(),
+                    fs: Arc::new(fs),
+                })
+            })
+            .collect()
+    }
+
+    /// Applies the `--quota` limits, the mapped directories that have a
+    /// quota are taken out of `mapped_dirs` as they get mounted instead.
+    pub fn build_quota_directories(
+        &self,
+        mapped_dirs: &mut Vec<MappedDirectory>,
+    ) -> Result<Vec<MountedDirectory>, anyhow::Error> {
+        let mut mounted_dirs = Vec::new();
+        for DirectoryQuota { guest, limits } in &self.quotas {
+            let fs: Arc<dyn FileSystem + Send + Sync> =
+                match mapped_dirs.iter().position(|d| &d.guest == guest) {
+                    Some(index) => {
+                        let MappedDirectory { host, .. } = mapped_dirs.remove(index);
+                        let fs = virtual_fs::host_fs::FileSystem::new(Handle::current(), &host)?;
+                        let fs = QuotaFileSystem::new(fs, *limits).with_context(|| {
+                            format!("Unable to measure the disk usage of \"{}\"", host.display())
+                        })?;
+                        Arc::new(fs)
+                    }
+                    None => Arc::new(QuotaFileSystem::new(TmpFileSystem::new(), *limits)?),
+                };
+            mounted_dirs.push(MountedDirectory {
+                guest: guest.clone(),
+                fs,
+            });
+        }
+        Ok(mounted_dirs)
+    }
+
     pub fn build_mapped_directories(
         &self,
     ) -> Result<(bool, bool, Vec<MappedDirectory>), anyhow::Error> {
@@ -610,6 +849,110 @@ impl Wasi {
         caps
     }
 
+    /// Limits that are put on the networking of the instance
+    pub fn net_throttle_limits(&self) -> ThrottleLimits {
+        ThrottleLimits {
+            upload_bytes_per_sec: self.net_upload_limit.map(|limit| limit.as_u64()),
+            download_bytes_per_sec: self.net_download_limit.map(|limit| limit.as_u64()),
+            max_sockets: self.net_max_sockets,
+            connections_per_sec: self.net_connection_rate,
+        }
+    }
+
+    /// Applies the auditing flags to the --net ruleset
+    fn configure_ruleset(&self, ruleset: Ruleset) -> Ruleset {
+        let ruleset = ruleset.with_dry_run(self.net_dry_run);
+        if !self.net_audit {
+            return ruleset;
+        }
+        ruleset.with_event_handler(|event| {
+            tracing::info!(
+                rule = ?event.rule,
+                direction = ?event.direction,
+                addr = ?event.addr,
+                domain = ?event.domain,
+                allowed = event.allowed,
+                dry_run = event.dry_run,
+                "network ruleset decision"
+            );
+        })
+    }
+
+    /// Records the traffic of the networking if a capture file was given
+    fn capture_networking<N: VirtualNetworking>(&self, network: N) -> Result<DynVirtualNetworking> {
+        match &self.net_capture {
+            Some(path) => {
+                let network = CaptureNetworking::create(network, path).with_context(|| {
+                    format!(
+                        "Unable to create the capture file at \"{}\"",
+                        path.display()
+                    )
+                })?;
+                Ok(Arc::new(network))
+            }
+            None => Ok(Arc::new(network)),
+        }
+    }
+
+    fn record_networking<N: VirtualNetworking>(&self, network: N) -> Result<DynVirtualNetworking> {
+        match &self.net_record {
+            Some(path) => {
+                let network = RecordingNetworking::create(network, path).with_context(|| {
+                    format!(
+                        "Unable to create the recording file at \"{}\"",
+                        path.display()
+                    )
+                })?;
+                self.capture_networking(network)
+            }
+            None => self.capture_networking(network),
+        }
+    }
+
+    /// The resolver for the hostnames of the guest, `None` to use the
+    /// resolver of the host
+    fn dns_resolver(&self) -> Option<DynDnsResolver> {
+        let upstream = self.dns_server.map(|(addr, transport)| {
+            Arc::new(CachingResolver::new(UpstreamResolver::new(addr, transport))) as DynDnsResolver
+        });
+        if self.add_hosts.is_empty() {
+            return upstream;
+        }
+
+        let mut hosts = StaticHostsResolver::new();
+        for (host, addr) in &self.add_hosts {
+            hosts.insert(host, *addr);
+        }
+        let fallback = upstream.unwrap_or_else(|| Arc::new(SystemResolver));
+        Some(Arc::new(hosts.with_fallback(fallback)))
+    }
+
+    /// The certificates for host TLS, `None` when it is disabled
+    fn tls_config(&self) -> Result<Option<TlsConfig>> {
+        if !self.host_tls && self.tls_cas.is_empty() && self.tls_cert.is_none() {
+            return Ok(None);
+        }
+
+        let mut config = TlsConfig::new();
+        for path in &self.tls_cas {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Unable to read \"{}\"", path.display()))?;
+            config
+                .add_root_certificates_pem(&pem)
+                .with_context(|| format!("Invalid certificates in \"{}\"", path.display()))?;
+        }
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            let chain = std::fs::read(cert)
+                .with_context(|| format!("Unable to read \"{}\"", cert.display()))?;
+            let key = std::fs::read(key)
+                .with_context(|| format!("Unable to read \"{}\"", key.display()))?;
+            config
+                .set_server_identity_pem(&chain, &key)
+                .context("Invalid TLS certificate or private key")?;
+        }
+        Ok(Some(config))
+    }
+
     pub fn prepare_runtime<I>(
         &self,
         engine: Engine,
@@ -634,25 +977,50 @@ impl Wasi {
             .clone()
             .flatten()
             .map(|ruleset| Ruleset::from_str(&ruleset))
-            .transpose()?;
+            .transpose()?
+            .map(|ruleset| self.configure_ruleset(ruleset));
 
         let network = if let Some(ruleset) = ruleset {
             virtual_net::host::LocalNetworking::with_ruleset(ruleset)
         } else {
             virtual_net::host::LocalNetworking::default()
         };
+        let network = match self.dns_resolver() {
+            Some(resolver) => network.with_dns_resolver(resolver),
+            None => network,
+        };
+
+        let limits = self.net_throttle_limits();
+        let network = if let Some(path) = &self.net_replay {
+            let replay = ReplayNetworking::open(path).with_context(|| {
+                format!("Unable to read the recording at \"{}\"", path.display())
+            })?;
+            if limits.is_unlimited() {
+                self.capture_networking(replay)?
+            } else {
+                self.capture_networking(ThrottledNetworking::new(replay, limits))?
+            }
+        } else if limits.is_unlimited() {
+            self.record_networking(network)?
+        } else {
+            self.record_networking(ThrottledNetworking::new(network, limits))?
+        };
 
         if has_networking {
-            rt.set_networking_implementation(network);
+            rt.networking = network;
         } else {
             let net = super::capabilities::net::AskingNetworking::new(
                 pkg_cache_path.to_path_buf(),
-                Arc::new(network),
+                network,
             );
 
             rt.set_networking_implementation(net);
         }
 
+        if let Some(tls) = self.tls_config()? {
+            rt.set_tls(tls);
+        }
+
         #[cfg(feature = "journal")]
         {
             let (r, w) = self.build_journals()?;
@@ -7,6 +7,7 @@ pub(crate) mod timestamp;
 pub(crate) mod unpack;
 
 use std::{
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -14,7 +15,9 @@ use std::{
 use anyhow::{bail, Context as _, Result};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use wasmer_wasix::runners::MappedDirectory;
+use virtual_fs::QuotaLimits;
+use virtual_net::dns::DnsTransport;
+use wasmer_wasix::{runners::MappedDirectory, WasiIdentity};
 
 fn retrieve_alias_pathbuf(alias: &str, real_dir: &str) -> Result<MappedDirectory> {
     let pb = PathBuf::from(&real_dir).canonicalize()?;
@@ -48,6 +51,29 @@ pub fn parse_mapdir(entry: &str) -> Result<MappedDirectory> {
     }
 }
 
+/// Parses an archive that gets mounted into the guest, in the form
+/// `<guest_dir>:<archive>` (or `<guest_dir>::<archive>`)
+pub fn parse_mount_archive(entry: &str) -> Result<MappedDirectory> {
+    let Some((guest, archive)) = entry.split_once("::").or_else(|| entry.split_once(':')) else {
+        bail!(
+            "Archive mounts must consist of a guest directory and an archive separated by a `::` or `:`. Found {}",
+            &entry
+        )
+    };
+
+    let host = PathBuf::from(archive)
+        .canonicalize()
+        .with_context(|| format!("Archive \"{archive}\" does not exist"))?;
+    if !host.is_file() {
+        bail!("\"{}\" exists, but it is not a file", &archive);
+    }
+
+    Ok(MappedDirectory {
+        guest: guest.to_string(),
+        host,
+    })
+}
+
 /// Parses an environment variable.
 pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     let entry = entry.trim();
@@ -72,6 +98,104 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     }
 }
 
+/// Parses the user (and optionally the group) a program runs as, the
+/// group defaults to the user ID when it is not given.
+pub fn parse_identity(entry: &str) -> Result<WasiIdentity> {
+    let entry = entry.trim();
+    let (uid, gid) = match entry.split_once(':') {
+        Some((uid, gid)) => (uid, Some(gid)),
+        None => (entry, None),
+    };
+
+    let uid: u32 = uid
+        .parse()
+        .with_context(|| format!("Invalid user ID in `{entry}`, expected `<uid>[:<gid>]`"))?;
+    let gid: u32 = match gid {
+        Some(gid) => gid
+            .parse()
+            .with_context(|| format!("Invalid group ID in `{entry}`, expected `<uid>[:<gid>]`"))?,
+        None => uid,
+    };
+
+    Ok(WasiIdentity::new(uid, gid))
+}
+
+/// The limits placed on a directory of the guest with `--quota`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryQuota {
+    pub guest: String,
+    pub limits: QuotaLimits,
+}
+
+/// Parses a directory quota, in the form `<guest_dir>:<size>[:<entries>]`
+/// where the size may use units (e.g. `/data:10GiB:100000`)
+pub fn parse_quota(entry: &str) -> Result<DirectoryQuota> {
+    let mut parts = entry.trim().split(':');
+    let (Some(guest), Some(size)) = (parts.next(), parts.next()) else {
+        bail!("Quotas must be in the form `<guest_dir>:<size>[:<entries>]`. Found {entry}");
+    };
+    let entries = parts.next();
+    if guest.is_empty() || parts.next().is_some() {
+        bail!("Quotas must be in the form `<guest_dir>:<size>[:<entries>]`. Found {entry}");
+    }
+
+    let size: bytesize::ByteSize = size
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid size in `{entry}`: {e}"))?;
+    let entries: Option<u64> = entries
+        .map(|entries| {
+            entries
+                .parse()
+                .with_context(|| format!("Invalid number of entries in `{entry}`"))
+        })
+        .transpose()?;
+
+    Ok(DirectoryQuota {
+        guest: guest.to_string(),
+        limits: QuotaLimits {
+            max_bytes: Some(size.as_u64()),
+            max_inodes: entries,
+        },
+    })
+}
+
+/// Parses a static hosts entry, in the form `<host>=<ip>`
+pub fn parse_host_entry(entry: &str) -> Result<(String, IpAddr)> {
+    let entry = entry.trim();
+    let Some((host, ip)) = entry.split_once('=') else {
+        bail!("Host entries must be in the form `<host>=<ip>`. Found {entry}");
+    };
+    if host.is_empty() {
+        bail!("Host entries must be in the form `<host>=<ip>`. Found {entry}");
+    }
+    let ip = ip
+        .parse()
+        .with_context(|| format!("Invalid IP address in `{entry}`"))?;
+    Ok((host.to_string(), ip))
+}
+
+/// Parses the address of a DNS server, in the form
+/// `[udp://|tcp://]<ip>[:<port>]` (e.g. `1.1.1.1` or `tcp://[::1]:5353`)
+pub fn parse_dns_server(entry: &str) -> Result<(SocketAddr, DnsTransport)> {
+    let entry = entry.trim();
+    let (transport, addr) = if let Some(addr) = entry.strip_prefix("tcp://") {
+        (DnsTransport::Tcp, addr)
+    } else {
+        (
+            DnsTransport::Udp,
+            entry.strip_prefix("udp://").unwrap_or(entry),
+        )
+    };
+
+    let addr = match addr.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, 53),
+        Err(_) => addr.parse().with_context(|| {
+            format!("DNS servers must be in the form `[udp://|tcp://]<ip>[:<port>]`. Found {entry}")
+        })?,
+    };
+    Ok((addr, transport))
+}
+
 pub(crate) const DEFAULT_PACKAGE_MANIFEST_FILE: &str = "wasmer.toml";
 
 /// Load a package manifest from the manifest file.
@@ -249,6 +373,20 @@ c: c
         assert_eq!(merged, c);
     }
 
+    #[test]
+    fn test_parse_identity() {
+        assert_eq!(
+            parse_identity("1000").unwrap(),
+            WasiIdentity::new(1000, 1000)
+        );
+        assert_eq!(
+            parse_identity("1000:100").unwrap(),
+            WasiIdentity::new(1000, 100)
+        );
+        assert!(parse_identity("alice").is_err());
+        assert!(parse_identity("1000:").is_err());
+    }
+
     #[test]
     fn parse_valid_identifiers() {
         let inputs = [
@@ -310,4 +448,83 @@ c: c
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_mount_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("data.tar");
+        std::fs::write(&archive, b"").unwrap();
+
+        let mount = parse_mount_archive(&format!("/data:{}", archive.display())).unwrap();
+        assert_eq!(mount.guest, "/data");
+        assert_eq!(mount.host, archive.canonicalize().unwrap());
+
+        assert!(parse_mount_archive(&format!("/data:{}", dir.path().display())).is_err());
+        assert!(parse_mount_archive("/data").is_err());
+    }
+
+    #[test]
+    fn test_parse_quota() {
+        assert_eq!(
+            parse_quota("/data:1KiB:10").unwrap(),
+            DirectoryQuota {
+                guest: "/data".to_string(),
+                limits: QuotaLimits {
+                    max_bytes: Some(1024),
+                    max_inodes: Some(10),
+                },
+            }
+        );
+        assert_eq!(
+            parse_quota("/tmp:100").unwrap().limits,
+            QuotaLimits {
+                max_bytes: Some(100),
+                max_inodes: None,
+            }
+        );
+
+        assert!(parse_quota("/data").is_err());
+        assert!(parse_quota("/data:lots").is_err());
+        assert!(parse_quota("/data:1KiB:many").is_err());
+        assert!(parse_quota(":1KiB").is_err());
+    }
+
+    #[test]
+    fn test_parse_host_entry() {
+        assert_eq!(
+            parse_host_entry("api.example.com=127.0.0.1").unwrap(),
+            ("api.example.com".to_string(), IpAddr::from([127, 0, 0, 1]))
+        );
+        assert_eq!(
+            parse_host_entry("db=::1").unwrap().1,
+            IpAddr::from(std::net::Ipv6Addr::LOCALHOST)
+        );
+
+        assert!(parse_host_entry("db").is_err());
+        assert!(parse_host_entry("=127.0.0.1").is_err());
+        assert!(parse_host_entry("db=localhost").is_err());
+    }
+
+    #[test]
+    fn test_parse_dns_server() {
+        assert_eq!(
+            parse_dns_server("1.1.1.1").unwrap(),
+            ("1.1.1.1:53".parse().unwrap(), DnsTransport::Udp)
+        );
+        assert_eq!(
+            parse_dns_server("udp://1.1.1.1:5353").unwrap(),
+            ("1.1.1.1:5353".parse().unwrap(), DnsTransport::Udp)
+        );
+        assert_eq!(
+            parse_dns_server("tcp://[::1]:53").unwrap(),
+            ("[::1]:53".parse().unwrap(), DnsTransport::Tcp)
+        );
+        assert_eq!(
+            parse_dns_server("::1").unwrap(),
+            ("[::1]:53".parse().unwrap(), DnsTransport::Udp)
+        );
+
+        assert!(parse_dns_server("dns.google").is_err());
+        assert!(parse_dns_server("https://1.1.1.1").is_err());
+    }
 }
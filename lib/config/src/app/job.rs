@@ -350,6 +350,7 @@ mod tests {
                     volumes: Some(vec![crate::app::AppVolume {
                         name: "vol".to_owned(),
                         mount: "/path/to/volume".to_owned(),
+                        quota: None,
                     }]),
                 }),
             },
@@ -131,6 +131,26 @@ pub enum AppScalingModeV1 {
 pub struct AppVolume {
     pub name: String,
     pub mount: String,
+    /// Limits on what can be stored in the volume.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<AppVolumeQuota>,
+}
+
+/// Disk quota of a volume, writes that would go over it fail as if the
+/// disk was full.
+#[derive(
+    serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone, Debug, PartialEq, Eq,
+)]
+pub struct AppVolumeQuota {
+    /// Maximum size of the files stored in the volume.
+    ///
+    /// Format: [digit][unit], where unit is Mb/Gb/MiB/GiB,...
+    #[schemars(with = "Option<String>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<ByteSize>,
+    /// Maximum number of files, directories and symbolic links in the volume.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<u64>,
 }
 
 #[derive(
@@ -206,6 +226,10 @@ pub struct AppConfigCapabilityMapV1 {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub instaboot: Option<AppConfigCapabilityInstaBootV1>,
 
+    /// Network bandwidth and connection limits.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<AppConfigCapabilityNetworkV1>,
+
     /// Additional unknown capabilities.
     ///
     /// This provides a small bit of forwards compatibility for newly added
@@ -244,6 +268,39 @@ pub struct AppConfigCapabilityRuntimeV1 {
     pub async_threads: Option<bool>,
 }
 
+/// Network capability settings.
+///
+/// All the limits are shared by the sockets of an instance. Once a limit is
+/// reached the instance is slowed down rather than seeing errors, opening a
+/// socket beyond `max_sockets` waits until another socket is closed.
+///
+/// The settings are applied by the runtime that hosts the app, which wraps
+/// the networking of every instance in a throttle. Locally the same limits
+/// are set with the `--net-*` flags of `wasmer run`.
+#[derive(
+    serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone, Debug, PartialEq, Eq,
+)]
+pub struct AppConfigCapabilityNetworkV1 {
+    /// Maximum number of bytes per second that can be sent.
+    ///
+    /// Format: [digit][unit], where unit is Mb/Gb/MiB/GiB,...
+    #[schemars(with = "Option<String>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_limit: Option<ByteSize>,
+    /// Maximum number of bytes per second that can be received.
+    ///
+    /// Format: [digit][unit], where unit is Mb/Gb/MiB/GiB,...
+    #[schemars(with = "Option<String>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_limit: Option<ByteSize>,
+    /// Maximum number of sockets that can be open at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_sockets: Option<u32>,
+    /// Maximum number of new connections per second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connections_per_second: Option<u32>,
+}
+
 /// Enables accelerated instance boot times with startup snapshots.
 ///
 /// How it works:
@@ -408,6 +465,9 @@ volumes:
     mount: /vol1
   - name: vol2
     mount: /vol2
+    quota:
+      max_size: 10GiB
+      max_entries: 1000
 
 "#;
 
@@ -416,10 +476,15 @@ volumes:
             AppVolume {
                 name: "vol1".to_string(),
                 mount: "/vol1".to_string(),
+                quota: None,
             },
             AppVolume {
                 name: "vol2".to_string(),
                 mount: "/vol2".to_string(),
+                quota: Some(AppVolumeQuota {
+                    max_size: Some(ByteSize::gib(10)),
+                    max_entries: Some(1000),
+                }),
             },
         ];
         if let Some(actual_volumes) = parsed.volumes {
@@ -428,4 +493,29 @@ volumes:
             panic!("Parsed volumes are None, expected Some({expected_volumes:?})");
         }
     }
+
+    #[test]
+    fn test_network_capability_deserialization() {
+        let config = r#"
+kind: wasmer.io/App.v0
+name: test
+package: ns/name@0.1.0
+capabilities:
+  network:
+    upload_limit: 1MiB
+    download_limit: 10MiB
+    max_sockets: 64
+"#;
+
+        let parsed = AppConfigV1::parse_yaml(config).unwrap();
+        assert_eq!(
+            parsed.capabilities.unwrap().network,
+            Some(AppConfigCapabilityNetworkV1 {
+                upload_limit: Some(ByteSize::mib(1)),
+                download_limit: Some(ByteSize::mib(10)),
+                max_sockets: Some(64),
+                connections_per_second: None,
+            })
+        );
+    }
 }
@@ -12,6 +12,13 @@ use super::*;
 pub const JOURNAL_MAGIC_NUMBER: u64 = 0x310d6dd027362979;
 pub const JOURNAL_MAGIC_NUMBER_BYTES: [u8; 8] = JOURNAL_MAGIC_NUMBER.to_be_bytes();
 
+/// Magic number of the journals that carry a format header, runtimes that
+/// predate the header do not know it and refuse the journal instead of
+/// reading it as an empty one
+pub const JOURNAL_VERSIONED_MAGIC_NUMBER: u64 = 0x310d6dd0273629f2;
+pub const JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES: [u8; 8] =
+    JOURNAL_VERSIONED_MAGIC_NUMBER.to_be_bytes();
+
 #[repr(u16)]
 #[derive(
     Debug,
@@ -90,6 +97,13 @@ pub enum JournalEntryRecordType {
     DuplicateFileDescriptorV2 = 62,
     FileDescriptorSetFdFlagsV1 = 63,
     SocketPairV1 = 64,
+    MemoryPageV1 = 65,
+    UpdateMemoryRegionPagesV1 = 66,
+    FileDescriptorLockV1 = 67,
+    FileDescriptorLockRangeV1 = 68,
+    WatchCreateV1 = 69,
+    WatchAddV1 = 70,
+    WatchRemoveV1 = 71,
 }
 
 impl JournalEntryRecordType {
@@ -124,6 +138,12 @@ impl JournalEntryRecordType {
             JournalEntryRecordType::UpdateMemoryRegionV1 => {
                 ArchivedJournalEntry::UpdateMemoryRegionV1(rkyv::access_unchecked(data))
             }
+            JournalEntryRecordType::MemoryPageV1 => {
+                ArchivedJournalEntry::MemoryPageV1(rkyv::access_unchecked(data))
+            }
+            JournalEntryRecordType::UpdateMemoryRegionPagesV1 => {
+                ArchivedJournalEntry::UpdateMemoryRegionPagesV1(rkyv::access_unchecked(data))
+            }
             JournalEntryRecordType::SetClockTimeV1 => {
                 ArchivedJournalEntry::SetClockTimeV1(rkyv::access_unchecked(data))
             }
@@ -166,6 +186,12 @@ impl JournalEntryRecordType {
             JournalEntryRecordType::FileDescriptorSetFlagsV1 => {
                 ArchivedJournalEntry::FileDescriptorSetFlagsV1(rkyv::access_unchecked(data))
             }
+            JournalEntryRecordType::FileDescriptorLockV1 => {
+                ArchivedJournalEntry::FileDescriptorLockV1(rkyv::access_unchecked(data))
+            }
+            JournalEntryRecordType::FileDescriptorLockRangeV1 => {
+                ArchivedJournalEntry::FileDescriptorLockRangeV1(rkyv::access_unchecked(data))
+            }
             JournalEntryRecordType::FileDescriptorSetRightsV1 => {
                 ArchivedJournalEntry::FileDescriptorSetRightsV1(rkyv::access_unchecked(data))
             }
@@ -196,6 +222,15 @@ impl JournalEntryRecordType {
             JournalEntryRecordType::EpollCtlV1 => {
                 ArchivedJournalEntry::EpollCtlV1(rkyv::access_unchecked(data))
             }
+            JournalEntryRecordType::WatchCreateV1 => {
+                ArchivedJournalEntry::WatchCreateV1(rkyv::access_unchecked(data))
+            }
+            JournalEntryRecordType::WatchAddV1 => {
+                ArchivedJournalEntry::WatchAddV1(rkyv::access_unchecked(data))
+            }
+            JournalEntryRecordType::WatchRemoveV1 => {
+                ArchivedJournalEntry::WatchRemoveV1(rkyv::access_unchecked(data))
+            }
             JournalEntryRecordType::TtySetV1 => {
                 ArchivedJournalEntry::TtySetV1(rkyv::access_unchecked(data))
             }
@@ -294,6 +329,10 @@ impl JournalEntry<'_> {
             Self::InitModuleV1 { .. } => JournalEntryRecordType::InitModuleV1,
             Self::ClearEtherealV1 { .. } => JournalEntryRecordType::ClearEtherealV1,
             Self::UpdateMemoryRegionV1 { .. } => JournalEntryRecordType::UpdateMemoryRegionV1,
+            Self::MemoryPageV1 { .. } => JournalEntryRecordType::MemoryPageV1,
+            Self::UpdateMemoryRegionPagesV1 { .. } => {
+                JournalEntryRecordType::UpdateMemoryRegionPagesV1
+            }
             Self::ProcessExitV1 { .. } => JournalEntryRecordType::ProcessExitV1,
             Self::SetThreadV1 { .. } => JournalEntryRecordType::SetThreadV1,
             Self::CloseThreadV1 { .. } => JournalEntryRecordType::CloseThreadV1,
@@ -324,6 +363,10 @@ impl JournalEntry<'_> {
             Self::FileDescriptorSetFlagsV1 { .. } => {
                 JournalEntryRecordType::FileDescriptorSetFlagsV1
             }
+            Self::FileDescriptorLockV1 { .. } => JournalEntryRecordType::FileDescriptorLockV1,
+            Self::FileDescriptorLockRangeV1 { .. } => {
+                JournalEntryRecordType::FileDescriptorLockRangeV1
+            }
             Self::FileDescriptorSetRightsV1 { .. } => {
                 JournalEntryRecordType::FileDescriptorSetRightsV1
             }
@@ -339,6 +382,9 @@ impl JournalEntry<'_> {
             Self::ChangeDirectoryV1 { .. } => JournalEntryRecordType::ChangeDirectoryV1,
             Self::EpollCreateV1 { .. } => JournalEntryRecordType::EpollCreateV1,
             Self::EpollCtlV1 { .. } => JournalEntryRecordType::EpollCtlV1,
+            Self::WatchCreateV1 { .. } => JournalEntryRecordType::WatchCreateV1,
+            Self::WatchAddV1 { .. } => JournalEntryRecordType::WatchAddV1,
+            Self::WatchRemoveV1 { .. } => JournalEntryRecordType::WatchRemoveV1,
             Self::TtySetV1 { .. } => JournalEntryRecordType::TtySetV1,
             Self::CreatePipeV1 { .. } => JournalEntryRecordType::CreatePipeV1,
             Self::CreateEventV1 { .. } => JournalEntryRecordType::CreateEventV1,
@@ -406,6 +452,29 @@ impl JournalEntry<'_> {
                 },
                 serializer,
             ),
+            JournalEntry::MemoryPageV1 {
+                hash,
+                compressed_data,
+            } => serialize_using(
+                &JournalEntryMemoryPageV1 {
+                    compressed_data: compressed_data.into(),
+                    hash,
+                },
+                serializer,
+            ),
+            JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_size,
+                page_hashes,
+            } => serialize_using(
+                &JournalEntryUpdateMemoryRegionPagesV1 {
+                    page_hashes: page_hashes.into(),
+                    start: region.start,
+                    end: region.end,
+                    page_size,
+                },
+                serializer,
+            ),
             JournalEntry::ProcessExitV1 { exit_code } => serialize_using(
                 &JournalEntryProcessExitV1 {
                     exit_code: exit_code.map(|e| e.into()),
@@ -603,6 +672,19 @@ impl JournalEntry<'_> {
                 },
                 serializer,
             ),
+            JournalEntry::FileDescriptorLockV1 { fd, op } => serialize_using(
+                &JournalEntryFileDescriptorLockV1 { fd, op: op.into() },
+                serializer,
+            ),
+            JournalEntry::FileDescriptorLockRangeV1 { fd, op, start, len } => serialize_using(
+                &JournalEntryFileDescriptorLockRangeV1 {
+                    fd,
+                    op: op.into(),
+                    start,
+                    len,
+                },
+                serializer,
+            ),
             JournalEntry::FileDescriptorSetRightsV1 {
                 fd,
                 fs_rights_base,
@@ -707,6 +789,34 @@ impl JournalEntry<'_> {
                 },
                 serializer,
             ),
+            JournalEntry::WatchCreateV1 { fd, flags } => serialize_using(
+                &JournalEntryWatchCreateV1 {
+                    fd,
+                    flags: flags.bits(),
+                },
+                serializer,
+            ),
+            JournalEntry::WatchAddV1 {
+                fd,
+                dirfd,
+                flags,
+                path,
+                mask,
+                wd,
+            } => serialize_using(
+                &JournalEntryWatchAddV1 {
+                    fd,
+                    dirfd,
+                    flags,
+                    path: path.into(),
+                    mask,
+                    wd,
+                },
+                serializer,
+            ),
+            JournalEntry::WatchRemoveV1 { fd, wd } => {
+                serialize_using(&JournalEntryWatchRemoveV1 { fd, wd }, serializer)
+            }
             JournalEntry::TtySetV1 { tty, line_feeds } => serialize_using(
                 &JournalEntryTtySetV1 {
                     cols: tty.cols,
@@ -987,6 +1097,8 @@ pub enum ArchivedJournalEntry<'a> {
     FileDescriptorSeekV1(&'a ArchivedJournalEntryFileDescriptorSeekV1),
     FileDescriptorWriteV1(&'a ArchivedJournalEntryFileDescriptorWriteV1<'a>),
     UpdateMemoryRegionV1(&'a ArchivedJournalEntryUpdateMemoryRegionV1<'a>),
+    MemoryPageV1(&'a ArchivedJournalEntryMemoryPageV1<'a>),
+    UpdateMemoryRegionPagesV1(&'a ArchivedJournalEntryUpdateMemoryRegionPagesV1<'a>),
     SetClockTimeV1(&'a ArchivedJournalEntrySetClockTimeV1),
     OpenFileDescriptorV1(&'a ArchivedJournalEntryOpenFileDescriptorV1<'a>),
     OpenFileDescriptorV2(&'a ArchivedJournalEntryOpenFileDescriptorV2<'a>),
@@ -1001,6 +1113,8 @@ pub enum ArchivedJournalEntry<'a> {
     FileDescriptorSetSizeV1(&'a ArchivedJournalEntryFileDescriptorSetSizeV1),
     FileDescriptorSetFdFlagsV1(&'a ArchivedJournalEntryFileDescriptorSetFdFlagsV1),
     FileDescriptorSetFlagsV1(&'a ArchivedJournalEntryFileDescriptorSetFlagsV1),
+    FileDescriptorLockV1(&'a ArchivedJournalEntryFileDescriptorLockV1),
+    FileDescriptorLockRangeV1(&'a ArchivedJournalEntryFileDescriptorLockRangeV1),
     FileDescriptorSetRightsV1(&'a ArchivedJournalEntryFileDescriptorSetRightsV1),
     FileDescriptorAdviseV1(&'a ArchivedJournalEntryFileDescriptorAdviseV1),
     FileDescriptorAllocateV1(&'a ArchivedJournalEntryFileDescriptorAllocateV1),
@@ -1011,6 +1125,9 @@ pub enum ArchivedJournalEntry<'a> {
     ChangeDirectoryV1(&'a ArchivedJournalEntryChangeDirectoryV1<'a>),
     EpollCreateV1(&'a ArchivedJournalEntryEpollCreateV1),
     EpollCtlV1(&'a ArchivedJournalEntryEpollCtlV1),
+    WatchCreateV1(&'a ArchivedJournalEntryWatchCreateV1),
+    WatchAddV1(&'a ArchivedJournalEntryWatchAddV1<'a>),
+    WatchRemoveV1(&'a ArchivedJournalEntryWatchRemoveV1),
     TtySetV1(&'a ArchivedJournalEntryTtySetV1),
     CreatePipeV1(&'a ArchivedJournalEntryCreatePipeV1),
     CreateEventV1(&'a ArchivedJournalEntryCreateEventV1),
@@ -1126,6 +1243,26 @@ pub struct JournalEntryUpdateMemoryRegionV1<'a> {
     pub end: u64,
 }
 
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(attr(repr(align(8))))]
+pub struct JournalEntryMemoryPageV1<'a> {
+    pub compressed_data: AlignedCowVec<'a, u8>,
+    pub hash: [u8; 32],
+}
+
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(attr(repr(align(8))))]
+pub struct JournalEntryUpdateMemoryRegionPagesV1<'a> {
+    pub page_hashes: AlignedCowVec<'a, u8>,
+    pub start: u64,
+    pub end: u64,
+    pub page_size: u64,
+}
+
 #[repr(C)]
 #[repr(align(8))]
 #[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
@@ -1271,6 +1408,26 @@ pub struct JournalEntryFileDescriptorSetFlagsV1 {
     pub flags: u16,
 }
 
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(derive(Debug), attr(repr(align(8))))]
+pub struct JournalEntryFileDescriptorLockV1 {
+    pub fd: u32,
+    pub op: JournalFlockopV1,
+}
+
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(derive(Debug), attr(repr(align(8))))]
+pub struct JournalEntryFileDescriptorLockRangeV1 {
+    pub fd: u32,
+    pub op: JournalFlockopV1,
+    pub start: u64,
+    pub len: u64,
+}
+
 #[repr(C)]
 #[repr(align(8))]
 #[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
@@ -1371,6 +1528,37 @@ pub struct JournalEntryEpollCtlV1 {
     pub event: Option<JournalEpollEventCtlV1>,
 }
 
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(derive(Debug), attr(repr(align(8))))]
+pub struct JournalEntryWatchCreateV1 {
+    pub fd: u32,
+    pub flags: u16,
+}
+
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(attr(repr(align(8))))]
+pub struct JournalEntryWatchAddV1<'a> {
+    pub fd: u32,
+    pub dirfd: u32,
+    pub flags: u32,
+    pub path: AlignedCowStr<'a>,
+    pub mask: u32,
+    pub wd: i32,
+}
+
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(derive(Debug), attr(repr(align(8))))]
+pub struct JournalEntryWatchRemoveV1 {
+    pub fd: u32,
+    pub wd: i32,
+}
+
 #[repr(C)]
 #[repr(align(8))]
 #[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
@@ -1663,6 +1851,16 @@ pub enum JournalWhenceV1 {
     Unknown = 255,
 }
 
+#[repr(C)]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(derive(Debug))]
+pub enum JournalFlockopV1 {
+    Shared,
+    Exclusive,
+    Unlock,
+    Unknown = 255,
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
 #[rkyv(derive(Debug))]
@@ -78,6 +78,39 @@ impl From<&'_ ArchivedJournalWhenceV1> for wasi::Whence {
     }
 }
 
+impl From<wasi::Flockop> for JournalFlockopV1 {
+    fn from(val: wasi::Flockop) -> Self {
+        match val {
+            wasi::Flockop::Shared => JournalFlockopV1::Shared,
+            wasi::Flockop::Exclusive => JournalFlockopV1::Exclusive,
+            wasi::Flockop::Unlock => JournalFlockopV1::Unlock,
+            wasi::Flockop::Unknown => JournalFlockopV1::Unknown,
+        }
+    }
+}
+
+impl From<JournalFlockopV1> for wasi::Flockop {
+    fn from(val: JournalFlockopV1) -> Self {
+        match val {
+            JournalFlockopV1::Shared => wasi::Flockop::Shared,
+            JournalFlockopV1::Exclusive => wasi::Flockop::Exclusive,
+            JournalFlockopV1::Unlock => wasi::Flockop::Unlock,
+            JournalFlockopV1::Unknown => wasi::Flockop::Unknown,
+        }
+    }
+}
+
+impl From<&'_ ArchivedJournalFlockopV1> for wasi::Flockop {
+    fn from(val: &'_ ArchivedJournalFlockopV1) -> Self {
+        match val {
+            ArchivedJournalFlockopV1::Shared => wasi::Flockop::Shared,
+            ArchivedJournalFlockopV1::Exclusive => wasi::Flockop::Exclusive,
+            ArchivedJournalFlockopV1::Unlock => wasi::Flockop::Unlock,
+            ArchivedJournalFlockopV1::Unknown => wasi::Flockop::Unknown,
+        }
+    }
+}
+
 impl From<wasi::Advice> for JournalAdviceV1 {
     fn from(val: wasi::Advice) -> Self {
         match val {
@@ -665,6 +698,25 @@ impl<'a> TryFrom<ArchivedJournalEntry<'a>> for JournalEntry<'a> {
                 region: (start.to_native())..(end.to_native()),
                 compressed_data: Cow::Borrowed(compressed_data.as_ref()),
             },
+            ArchivedJournalEntry::MemoryPageV1(ArchivedJournalEntryMemoryPageV1 {
+                compressed_data,
+                hash,
+            }) => Self::MemoryPageV1 {
+                hash: *hash,
+                compressed_data: Cow::Borrowed(compressed_data.as_ref()),
+            },
+            ArchivedJournalEntry::UpdateMemoryRegionPagesV1(
+                ArchivedJournalEntryUpdateMemoryRegionPagesV1 {
+                    page_hashes,
+                    start,
+                    end,
+                    page_size,
+                },
+            ) => Self::UpdateMemoryRegionPagesV1 {
+                region: (start.to_native())..(end.to_native()),
+                page_size: page_size.to_native(),
+                page_hashes: Cow::Borrowed(page_hashes.as_ref()),
+            },
             ArchivedJournalEntry::ProcessExitV1(ArchivedJournalEntryProcessExitV1 {
                 exit_code,
             }) => Self::ProcessExitV1 {
@@ -888,6 +940,25 @@ impl<'a> TryFrom<ArchivedJournalEntry<'a>> for JournalEntry<'a> {
                 fd: fd.to_native(),
                 flags: wasi::Fdflags::from_bits_truncate(flags.to_native()),
             },
+            ArchivedJournalEntry::FileDescriptorLockV1(
+                ArchivedJournalEntryFileDescriptorLockV1 { fd, ref op },
+            ) => Self::FileDescriptorLockV1 {
+                fd: fd.to_native(),
+                op: op.into(),
+            },
+            ArchivedJournalEntry::FileDescriptorLockRangeV1(
+                ArchivedJournalEntryFileDescriptorLockRangeV1 {
+                    fd,
+                    ref op,
+                    start,
+                    len,
+                },
+            ) => Self::FileDescriptorLockRangeV1 {
+                fd: fd.to_native(),
+                op: op.into(),
+                start: start.to_native(),
+                len: len.to_native(),
+            },
             ArchivedJournalEntry::FileDescriptorSetRightsV1(
                 ArchivedJournalEntryFileDescriptorSetRightsV1 {
                     fd,
@@ -953,6 +1024,34 @@ impl<'a> TryFrom<ArchivedJournalEntry<'a>> for JournalEntry<'a> {
             ArchivedJournalEntry::EpollCreateV1(ArchivedJournalEntryEpollCreateV1 { fd }) => {
                 Self::EpollCreateV1 { fd: fd.to_native() }
             }
+            ArchivedJournalEntry::WatchCreateV1(ArchivedJournalEntryWatchCreateV1 {
+                fd,
+                flags,
+            }) => Self::WatchCreateV1 {
+                fd: fd.to_native(),
+                flags: wasi::Fdflags::from_bits_truncate(flags.to_native()),
+            },
+            ArchivedJournalEntry::WatchAddV1(ArchivedJournalEntryWatchAddV1 {
+                fd,
+                dirfd,
+                flags,
+                path,
+                mask,
+                wd,
+            }) => Self::WatchAddV1 {
+                fd: fd.to_native(),
+                dirfd: dirfd.to_native(),
+                flags: flags.to_native(),
+                path: String::from_utf8_lossy(path.as_ref()),
+                mask: mask.to_native(),
+                wd: wd.to_native(),
+            },
+            ArchivedJournalEntry::WatchRemoveV1(ArchivedJournalEntryWatchRemoveV1 { fd, wd }) => {
+                Self::WatchRemoveV1 {
+                    fd: fd.to_native(),
+                    wd: wd.to_native(),
+                }
+            }
             ArchivedJournalEntry::EpollCtlV1(ArchivedJournalEntryEpollCtlV1 {
                 epfd,
                 ref op,
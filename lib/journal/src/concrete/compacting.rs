@@ -73,6 +73,14 @@ struct State {
     descriptor_seed: u64,
     // We maintain a memory map of the events that are significant
     memory_map: HashMap<MemoryRange, usize>,
+    // Pages referenced by the memory updates in the memory map
+    memory_page_refs: HashMap<MemoryRange, Vec<MemoryPageHash>>,
+    // Distinct memory pages that memory updates may reference, only
+    // those still referenced by the memory map are retained
+    memory_pages: HashMap<MemoryPageHash, usize>,
+    // Pages written since the last memory update, these are retained
+    // as the update that references them may not have been written yet
+    staged_memory_pages: Vec<MemoryPageHash>,
     // List of all the snapshots
     snapshots: Vec<usize>,
     // Last tty event thats been set
@@ -118,6 +126,8 @@ struct State {
     event_descriptors: HashMap<Fd, SubGroupIndex>,
     // Epoll events
     epoll_descriptors: HashMap<Fd, SubGroupIndex>,
+    // File system watches and the paths they observe
+    watch_descriptors: HashMap<Fd, SubGroupIndex>,
     // We abstract the descriptor state so that multiple file descriptors
     // can refer to the same file descriptors
     sub_events: HashMap<SubGroupIndex, SubGroupOfevents>,
@@ -163,6 +173,15 @@ impl State {
             .chain(self.init_module.as_ref().into_iter())
             .chain(self.snapshots.iter())
             .chain(self.memory_map.values())
+            .chain(
+                self.memory_page_refs
+                    .values()
+                    .flatten()
+                    .chain(self.staged_memory_pages.iter())
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .filter_map(|hash| self.memory_pages.get(hash)),
+            )
             .chain(self.thread_map.values())
             .chain(self.remove_directory.values())
             .chain(self.unlink_file.values())
@@ -209,6 +228,11 @@ impl State {
                     .values()
                     .filter_map(|l| self.sub_events.get(l)),
             )
+            .chain(
+                self.watch_descriptors
+                    .values()
+                    .filter_map(|l| self.sub_events.get(l)),
+            )
             .chain(
                 self.open_pipes
                     .values()
@@ -288,6 +312,7 @@ impl State {
             .or_else(|| self.open_pipes.get(fd).cloned())
             .or_else(|| self.keep_descriptors.get(fd).cloned())
             .or_else(|| self.event_descriptors.get(fd).cloned())
+            .or_else(|| self.watch_descriptors.get(fd).cloned())
             .or_else(|| self.stdio_descriptors.get(fd).cloned())
     }
 
@@ -301,6 +326,9 @@ impl State {
         self.accepted_sockets.clear();
         self.event_descriptors.clear();
         self.memory_map.clear();
+        self.memory_page_refs.clear();
+        self.memory_pages.clear();
+        self.staged_memory_pages.clear();
         self.open_pipes.clear();
         self.open_sockets.clear();
         self.snapshots.clear();
@@ -308,6 +336,7 @@ impl State {
         self.stdio_descriptors.clear();
         self.suspect_descriptors.clear();
         self.thread_map.clear();
+        self.watch_descriptors.clear();
         for i in 0..=2 {
             let lookup = self.insert_new_sub_events_empty();
             self.stdio_descriptors.insert(i, lookup);
@@ -357,6 +386,9 @@ impl CompactingJournal {
             init_module: None,
             snapshots: Default::default(),
             memory_map: Default::default(),
+            memory_page_refs: Default::default(),
+            memory_pages: Default::default(),
+            staged_memory_pages: Default::default(),
             thread_map: Default::default(),
             staged_thread_map: Default::default(),
             open_sockets: Default::default(),
@@ -371,6 +403,7 @@ impl CompactingJournal {
             stdio_descriptors: Default::default(),
             event_descriptors: Default::default(),
             epoll_descriptors: Default::default(),
+            watch_descriptors: Default::default(),
             descriptor_seed: 0,
             sub_events: Default::default(),
             whitelist: Default::default(),
@@ -537,6 +570,25 @@ impl WritableJournal for CompactingJournalTx {
         match &entry {
             JournalEntry::UpdateMemoryRegionV1 { region, .. } => {
                 state.memory_map.insert(region.clone().into(), event_index);
+                state.memory_page_refs.remove(&region.clone().into());
+                state.staged_memory_pages.clear();
+            }
+            JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_hashes,
+                ..
+            } => {
+                state.memory_map.insert(region.clone().into(), event_index);
+                let hashes = page_hashes
+                    .chunks_exact(std::mem::size_of::<MemoryPageHash>())
+                    .filter_map(|hash| hash.try_into().ok())
+                    .collect();
+                state.memory_page_refs.insert(region.clone().into(), hashes);
+                state.staged_memory_pages.clear();
+            }
+            JournalEntry::MemoryPageV1 { hash, .. } => {
+                state.memory_pages.entry(*hash).or_insert(event_index);
+                state.staged_memory_pages.push(*hash);
             }
             JournalEntry::SetThreadV1 { id, .. } => {
                 state.staged_thread_map.insert(*id, event_index);
@@ -623,6 +675,8 @@ impl WritableJournal for CompactingJournalTx {
             // We keep non-mutable events for file descriptors that are suspect
             JournalEntry::FileDescriptorSeekV1 { fd, .. }
             | JournalEntry::FileDescriptorSetFdFlagsV1 { fd, .. }
+            | JournalEntry::FileDescriptorLockV1 { fd, .. }
+            | JournalEntry::FileDescriptorLockRangeV1 { fd, .. }
             | JournalEntry::FileDescriptorSetFlagsV1 { fd, .. }
             | JournalEntry::SocketBindV1 { fd, .. }
             | JournalEntry::SocketSendFileV1 { socket_fd: fd, .. }
@@ -654,6 +708,8 @@ impl WritableJournal for CompactingJournalTx {
                     state.sub_events.remove(&lookup);
                 } else if let Some(lookup) = state.epoll_descriptors.remove(fd) {
                     state.sub_events.remove(&lookup);
+                } else if let Some(lookup) = state.watch_descriptors.remove(fd) {
+                    state.sub_events.remove(&lookup);
                 } else if let Some(lookup) = state.keep_descriptors.remove(fd) {
                     state.append_to_sub_events(&lookup, event_index);
                     state.kept_descriptors.push(lookup);
@@ -692,6 +748,9 @@ impl WritableJournal for CompactingJournalTx {
                 } else if let Some(lookup) = state.event_descriptors.get(original_fd).cloned() {
                     state.event_descriptors.insert(*copied_fd, lookup);
                     state.append_to_sub_events(&lookup, event_index);
+                } else if let Some(lookup) = state.watch_descriptors.get(original_fd).cloned() {
+                    state.watch_descriptors.insert(*copied_fd, lookup);
+                    state.append_to_sub_events(&lookup, event_index);
                 }
             }
             // Renumbered file descriptors will retain their suspect status
@@ -717,6 +776,9 @@ impl WritableJournal for CompactingJournalTx {
                 } else if let Some(lookup) = state.event_descriptors.remove(old_fd) {
                     state.event_descriptors.insert(*new_fd, lookup);
                     state.append_to_sub_events(&lookup, event_index);
+                } else if let Some(lookup) = state.watch_descriptors.remove(old_fd) {
+                    state.watch_descriptors.insert(*new_fd, lookup);
+                    state.append_to_sub_events(&lookup, event_index);
                 }
             }
             // Creating a new directory only needs to be done once
@@ -775,6 +837,14 @@ impl WritableJournal for CompactingJournalTx {
                     state.find_sub_events_and_append(epfd, event_index);
                 }
             }
+            // Watches live as long as their descriptor
+            JournalEntry::WatchCreateV1 { fd, .. } => {
+                let lookup = state.insert_new_sub_events(event_index);
+                state.watch_descriptors.insert(*fd, lookup);
+            }
+            JournalEntry::WatchAddV1 { fd, .. } | JournalEntry::WatchRemoveV1 { fd, .. } => {
+                state.find_sub_events_and_append(fd, event_index);
+            }
             JournalEntry::SocketConnectedV1 { fd, .. } => {
                 let lookup = state.insert_new_sub_events(event_index);
                 state.accepted_sockets.insert(*fd, lookup);
@@ -1561,4 +1631,82 @@ mod tests {
         )
         .unwrap()
     }
+
+    #[tracing_test::traced_test]
+    #[test]
+    pub fn test_compact_memory_pages() {
+        let page1 = JournalEntry::MemoryPageV1 {
+            hash: [1u8; 32],
+            compressed_data: Cow::Borrowed(b"page1"),
+        };
+        let page2 = JournalEntry::MemoryPageV1 {
+            hash: [2u8; 32],
+            compressed_data: Cow::Borrowed(b"page2"),
+        };
+        let update = |hash: u8| JournalEntry::UpdateMemoryRegionPagesV1 {
+            region: 0..4096,
+            page_size: 4096,
+            page_hashes: vec![hash; 32].into(),
+        };
+        run_test(
+            vec![
+                page1.clone(),
+                update(1),
+                page2.clone(),
+                update(2),
+                // The page is already stored so the update only references it
+                update(1),
+            ],
+            vec![page1, update(1)],
+        )
+        .unwrap()
+    }
+
+    #[tracing_test::traced_test]
+    #[test]
+    pub fn test_compact_memory_pages_unreferenced() {
+        run_test(
+            vec![
+                JournalEntry::MemoryPageV1 {
+                    hash: [1u8; 32],
+                    compressed_data: Cow::Borrowed(b"page1"),
+                },
+                JournalEntry::UpdateMemoryRegionPagesV1 {
+                    region: 0..4096,
+                    page_size: 4096,
+                    page_hashes: vec![1u8; 32].into(),
+                },
+                JournalEntry::UpdateMemoryRegionV1 {
+                    region: 0..4096,
+                    compressed_data: Cow::Borrowed(b"data"),
+                },
+            ],
+            vec![JournalEntry::UpdateMemoryRegionV1 {
+                region: 0..4096,
+                compressed_data: Cow::Borrowed(b"data"),
+            }],
+        )
+        .unwrap()
+    }
+
+    #[tracing_test::traced_test]
+    #[test]
+    pub fn test_compact_memory_pages_purged_on_exit() {
+        run_test(
+            vec![
+                JournalEntry::MemoryPageV1 {
+                    hash: [1u8; 32],
+                    compressed_data: Cow::Borrowed(b"page1"),
+                },
+                JournalEntry::UpdateMemoryRegionPagesV1 {
+                    region: 0..4096,
+                    page_size: 4096,
+                    page_hashes: vec![1u8; 32].into(),
+                },
+                JournalEntry::ProcessExitV1 { exit_code: None },
+            ],
+            vec![JournalEntry::ProcessExitV1 { exit_code: None }],
+        )
+        .unwrap()
+    }
 }
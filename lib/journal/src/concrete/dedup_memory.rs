@@ -0,0 +1,596 @@
+use std::{
+    collections::{hash_map, HashMap},
+    ops::Range,
+    sync::Mutex,
+};
+
+use lz4_flex::{compress_prepend_size, decompress_size_prepended};
+
+use super::*;
+
+/// Default size of the pages that memory regions are broken into
+/// before they are deduplicated
+pub const DEFAULT_MEMORY_PAGE_SIZE: u64 = 4096;
+
+/// Hash of the contents of a page of memory
+pub type MemoryPageHash = [u8; 32];
+
+/// Computes the hash that identifies a page of memory
+pub fn memory_page_hash(data: &[u8]) -> MemoryPageHash {
+    blake3::hash(data).into()
+}
+
+/// Splits the page references of a memory update into the hashes of the pages
+fn parse_page_hashes(page_hashes: &[u8]) -> Vec<MemoryPageHash> {
+    page_hashes
+        .chunks_exact(std::mem::size_of::<MemoryPageHash>())
+        .filter_map(|hash| hash.try_into().ok())
+        .collect()
+}
+
+/// Splits a region of memory into the pages that are used for
+/// deduplication, the pages are aligned on `page_size` boundaries
+/// which means the first and last page may be partial pages
+pub fn memory_region_pages(region: Range<u64>, page_size: u64) -> impl Iterator<Item = Range<u64>> {
+    let page_size = page_size.max(1);
+    let mut cur = region.start;
+    std::iter::from_fn(move || {
+        if cur >= region.end {
+            return None;
+        }
+        let next = ((cur / page_size) + 1) * page_size;
+        let end = next.min(region.end);
+        let ret = cur..end;
+        cur = end;
+        Some(ret)
+    })
+}
+
+/// Holds the distinct memory pages that have been read from a journal
+/// so that memory updates that reference them can be resolved
+#[derive(Debug, Default)]
+pub struct MemoryPageCache {
+    pages: HashMap<MemoryPageHash, Vec<u8>>,
+}
+
+impl MemoryPageCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a page to the cache using its compressed form as it is
+    /// stored in the journal
+    pub fn insert_compressed(
+        &mut self,
+        hash: MemoryPageHash,
+        compressed_data: &[u8],
+    ) -> anyhow::Result<()> {
+        if let hash_map::Entry::Vacant(entry) = self.pages.entry(hash) {
+            let data = decompress_size_prepended(compressed_data)
+                .map_err(|err| anyhow::format_err!("failed to decompress memory page - {err}"))?;
+            entry.insert(data);
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the contents of a memory region from the pages it references
+    pub fn resolve(
+        &self,
+        region: Range<u64>,
+        page_size: u64,
+        page_hashes: &[u8],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut ret = Vec::with_capacity((region.end - region.start) as usize);
+        let mut hashes = page_hashes.chunks_exact(32);
+        for page in memory_region_pages(region.clone(), page_size) {
+            let hash = hashes.next().ok_or_else(|| {
+                anyhow::format_err!("memory region ({region:?}) is missing page references")
+            })?;
+            let data = self.pages.get(hash).ok_or_else(|| {
+                anyhow::format_err!("memory region ({region:?}) references an unknown page")
+            })?;
+            if data.len() as u64 != page.end - page.start {
+                anyhow::bail!(
+                    "memory page referenced by region ({region:?}) has the wrong size ({} vs {})",
+                    data.len(),
+                    page.end - page.start
+                );
+            }
+            ret.extend_from_slice(data);
+        }
+        Ok(ret)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+    }
+}
+
+/// The dedup memory journal breaks memory updates into pages and only
+/// writes each distinct page to the inner journal once, later memory
+/// updates then reference the pages by the hash of their contents.
+///
+/// Journals of long running instances that snapshot periodically tend to
+/// rewrite the same memory over and over again, which this journal avoids.
+///
+/// The pages are forgotten whenever the process restarts (i.e. on module
+/// initialization, process exit or when the ethereal events are cleared)
+/// and once no memory region references them anymore, which are also the
+/// points where the compactor drops them. Hence pages are never referenced
+/// after they were compacted away.
+#[derive(Debug)]
+pub struct DedupMemoryJournal {
+    tx: DedupMemoryJournalTx,
+    rx: DedupMemoryJournalRx,
+}
+
+#[derive(Debug)]
+pub struct DedupMemoryJournalTx {
+    inner: Box<DynWritableJournal>,
+    page_size: u64,
+    known_pages: Mutex<KnownPages>,
+}
+
+/// Tracks the pages that are in the journal the same way the compactor
+/// does, so that no page is assumed to be stored after it was compacted
+#[derive(Debug, Default)]
+struct KnownPages {
+    /// Pages already in the journal and the records they were written to
+    pages: HashMap<MemoryPageHash, LogWriteResult>,
+    /// Pages referenced by the last update of every memory region
+    regions: HashMap<Range<u64>, Vec<MemoryPageHash>>,
+    /// Number of memory regions that reference each page
+    refs: HashMap<MemoryPageHash, usize>,
+    /// Pages written since the last memory update
+    staged: Vec<MemoryPageHash>,
+}
+
+impl KnownPages {
+    fn insert(&mut self, hash: MemoryPageHash, res: LogWriteResult) {
+        self.pages.entry(hash).or_insert(res);
+        self.staged.push(hash);
+    }
+
+    /// Records the pages a memory region now references, the pages that
+    /// are no longer referenced by any region are forgotten
+    fn update_region(&mut self, region: Range<u64>, hashes: Vec<MemoryPageHash>) {
+        for hash in hashes.iter() {
+            *self.refs.entry(*hash).or_default() += 1;
+        }
+        let old = self.regions.insert(region, hashes).unwrap_or_default();
+        for hash in old.iter() {
+            if let Some(refs) = self.refs.get_mut(hash) {
+                *refs -= 1;
+            }
+        }
+        for hash in old.into_iter().chain(self.staged.drain(..)) {
+            if self.refs.get(&hash).copied().unwrap_or_default() == 0 {
+                self.refs.remove(&hash);
+                self.pages.remove(&hash);
+            }
+        }
+    }
+
+    /// Learns from an entry that was written to the journal
+    fn learn(&mut self, entry: &JournalEntry<'_>, res: impl FnOnce() -> LogWriteResult) {
+        match entry {
+            JournalEntry::MemoryPageV1 { hash, .. } => self.insert(*hash, res()),
+            JournalEntry::UpdateMemoryRegionV1 { region, .. } => {
+                self.update_region(region.clone(), Vec::new())
+            }
+            JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_hashes,
+                ..
+            } => self.update_region(region.clone(), parse_page_hashes(page_hashes)),
+            JournalEntry::InitModuleV1 { .. }
+            | JournalEntry::ProcessExitV1 { .. }
+            | JournalEntry::ClearEtherealV1 => self.clear(),
+            _ => {}
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.regions.clear();
+        self.refs.clear();
+        self.staged.clear();
+    }
+}
+
+#[derive(Debug)]
+pub struct DedupMemoryJournalRx {
+    inner: Box<DynReadableJournal>,
+}
+
+impl DedupMemoryJournal {
+    /// Creates a new dedup journal, any pages that already exist in
+    /// the inner journal will be learned so they are not written again
+    pub fn new<J>(inner: J) -> anyhow::Result<Self>
+    where
+        J: Journal,
+    {
+        let (tx, rx) = inner.split();
+
+        let mut known_pages = KnownPages::default();
+        let replay_rx = rx.as_restarted()?;
+        while let Some(entry) = replay_rx.read()? {
+            known_pages.learn(&entry.record, || LogWriteResult {
+                record_start: entry.record_start,
+                record_end: entry.record_end,
+            });
+        }
+
+        Ok(Self {
+            tx: DedupMemoryJournalTx {
+                inner: tx,
+                page_size: DEFAULT_MEMORY_PAGE_SIZE,
+                known_pages: Mutex::new(known_pages),
+            },
+            rx: DedupMemoryJournalRx { inner: rx },
+        })
+    }
+
+    /// Sets the size of the pages that memory will be broken into
+    pub fn with_page_size(mut self, page_size: u64) -> Self {
+        self.tx.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn into_split(self) -> (DedupMemoryJournalTx, DedupMemoryJournalRx) {
+        (self.tx, self.rx)
+    }
+}
+
+impl DedupMemoryJournalTx {
+    fn write_region(
+        &self,
+        region: Range<u64>,
+        compressed_data: &[u8],
+    ) -> anyhow::Result<LogWriteResult> {
+        let data = decompress_size_prepended(compressed_data)
+            .map_err(|err| anyhow::format_err!("failed to decompress memory region - {err}"))?;
+        if data.len() as u64 != region.end - region.start {
+            anyhow::bail!(
+                "memory region ({region:?}) does not match the size of its data ({})",
+                data.len()
+            );
+        }
+
+        let mut record_start = None;
+        let mut page_hashes = Vec::new();
+        let mut data = &data[..];
+        let mut known_pages = self.known_pages.lock().unwrap();
+        for page in memory_region_pages(region.clone(), self.page_size) {
+            let (page_data, remaining) = data.split_at((page.end - page.start) as usize);
+            data = remaining;
+
+            let hash = memory_page_hash(page_data);
+            if !known_pages.pages.contains_key(&hash) {
+                let res = self.inner.write(JournalEntry::MemoryPageV1 {
+                    hash,
+                    compressed_data: compress_prepend_size(page_data).into(),
+                })?;
+                record_start.get_or_insert(res.record_start);
+                known_pages.insert(hash, res);
+            }
+            page_hashes.push(hash);
+        }
+
+        let res = self.inner.write(JournalEntry::UpdateMemoryRegionPagesV1 {
+            region: region.clone(),
+            page_size: self.page_size,
+            page_hashes: page_hashes.concat().into(),
+        })?;
+        known_pages.update_region(region, page_hashes);
+        Ok(LogWriteResult {
+            record_start: record_start.unwrap_or(res.record_start),
+            record_end: res.record_end,
+        })
+    }
+}
+
+impl WritableJournal for DedupMemoryJournalTx {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        match &entry {
+            JournalEntry::UpdateMemoryRegionV1 {
+                region,
+                compressed_data,
+            } => {
+                return self.write_region(region.clone(), compressed_data.as_ref());
+            }
+            JournalEntry::MemoryPageV1 { hash, .. } => {
+                // Pages that are already known do not need to be written again
+                // instead the record that already holds the page is returned
+                let hash = *hash;
+                let mut known_pages = self.known_pages.lock().unwrap();
+                if let Some(res) = known_pages.pages.get(&hash) {
+                    return Ok(res.clone());
+                }
+                let res = self.inner.write(entry)?;
+                known_pages.insert(hash, res.clone());
+                return Ok(res);
+            }
+            JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_hashes,
+                ..
+            } => {
+                let (region, hashes) = (region.clone(), parse_page_hashes(page_hashes));
+                let mut known_pages = self.known_pages.lock().unwrap();
+                let res = self.inner.write(entry)?;
+                known_pages.update_region(region, hashes);
+                return Ok(res);
+            }
+            JournalEntry::InitModuleV1 { .. }
+            | JournalEntry::ProcessExitV1 { .. }
+            | JournalEntry::ClearEtherealV1 => {
+                self.known_pages.lock().unwrap().clear();
+            }
+            _ => {}
+        }
+        self.inner.write(entry)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.inner.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        // Pages that were written since the last commit are discarded, as
+        // it is unknown which ones those are all of them are forgotten
+        let mut known_pages = self.known_pages.lock().unwrap();
+        known_pages.clear();
+        self.inner.rollback()
+    }
+}
+
+impl ReadableJournal for DedupMemoryJournalRx {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        self.inner.read()
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        self.inner.as_restarted()
+    }
+}
+
+impl WritableJournal for DedupMemoryJournal {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        self.tx.write(entry)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.tx.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.tx.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.tx.rollback()
+    }
+}
+
+impl ReadableJournal for DedupMemoryJournal {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        self.rx.read()
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        self.rx.as_restarted()
+    }
+}
+
+impl Journal for DedupMemoryJournal {
+    fn split(self) -> (Box<DynWritableJournal>, Box<DynReadableJournal>) {
+        (Box::new(self.tx), Box::new(self.rx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn write_memory(journal: &impl WritableJournal, region: Range<u64>, data: &[u8]) {
+        journal
+            .write(JournalEntry::UpdateMemoryRegionV1 {
+                region,
+                compressed_data: compress_prepend_size(data).into(),
+            })
+            .unwrap();
+    }
+
+    fn snapshot(journal: &impl WritableJournal) {
+        journal
+            .write(JournalEntry::SnapshotV1 {
+                when: SystemTime::UNIX_EPOCH,
+                trigger: SnapshotTrigger::Idle,
+            })
+            .unwrap();
+    }
+
+    #[test]
+    pub fn test_memory_region_pages() {
+        let pages = memory_region_pages(100..9000, 4096).collect::<Vec<_>>();
+        assert_eq!(pages, vec![100..4096, 4096..8192, 8192..9000]);
+        assert_eq!(memory_region_pages(10..10, 4096).count(), 0);
+    }
+
+    #[test]
+    pub fn test_dedup_memory_pages() {
+        let inner = BufferedJournal::default();
+        let journal = DedupMemoryJournal::new(inner).unwrap().with_page_size(1024);
+
+        // The same contents repeated in multiple regions should only
+        // be stored once
+        let data = vec![7u8; 4096];
+        write_memory(&journal, 0..4096, &data);
+        snapshot(&journal);
+        write_memory(&journal, 8192..12288, &data);
+        snapshot(&journal);
+
+        let mut pages = MemoryPageCache::new();
+        let mut n_pages = 0;
+        let mut regions = Vec::new();
+        let rx = journal.as_restarted().unwrap();
+        while let Some(entry) = rx.read().unwrap() {
+            match entry.into_inner() {
+                JournalEntry::MemoryPageV1 {
+                    hash,
+                    compressed_data,
+                } => {
+                    n_pages += 1;
+                    pages.insert_compressed(hash, &compressed_data).unwrap();
+                }
+                JournalEntry::UpdateMemoryRegionPagesV1 {
+                    region,
+                    page_size,
+                    page_hashes,
+                } => {
+                    regions.push(pages.resolve(region, page_size, &page_hashes).unwrap());
+                }
+                JournalEntry::UpdateMemoryRegionV1 { .. } => {
+                    panic!("memory updates should have been deduplicated")
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(n_pages, 1);
+        assert_eq!(regions, vec![data.clone(), data]);
+    }
+
+    #[test]
+    pub fn test_dedup_memory_learns_existing_pages() {
+        let inner = BufferedJournal::default();
+        let (tx, rx) = inner.split();
+        let inner = RecombinedJournal::new(tx, rx);
+        let journal = DedupMemoryJournal::new(inner).unwrap();
+        write_memory(&journal, 0..100, &[1u8; 100]);
+
+        // A new dedup journal over the same records should not
+        // write the page again
+        let (tx, rx) = journal.split();
+        let journal = DedupMemoryJournal::new(RecombinedJournal::new(tx, rx)).unwrap();
+        write_memory(&journal, 200..300, &[1u8; 100]);
+
+        let rx = journal.as_restarted().unwrap();
+        let mut n_pages = 0;
+        while let Some(entry) = rx.read().unwrap() {
+            if let JournalEntry::MemoryPageV1 { .. } = entry.record {
+                n_pages += 1;
+            }
+        }
+        assert_eq!(n_pages, 1);
+    }
+
+    #[test]
+    pub fn test_dedup_memory_duplicate_page_offsets() {
+        let journal = DedupMemoryJournal::new(BufferedJournal::default()).unwrap();
+        let page = || JournalEntry::MemoryPageV1 {
+            hash: [1u8; 32],
+            compressed_data: compress_prepend_size(&[1u8; 100]).into(),
+        };
+
+        // Writing a page that is already stored returns the record
+        // that holds it rather than writing it again
+        let first = journal.write(page()).unwrap();
+        let second = journal.write(page()).unwrap();
+        assert!(first.record_end > first.record_start);
+        assert_eq!(second.record_start, first.record_start);
+        assert_eq!(second.record_end, first.record_end);
+
+        let rx = journal.as_restarted().unwrap();
+        let mut n_pages = 0;
+        while let Some(entry) = rx.read().unwrap() {
+            if let JournalEntry::MemoryPageV1 { .. } = entry.record {
+                n_pages += 1;
+            }
+        }
+        assert_eq!(n_pages, 1);
+    }
+
+    #[test]
+    pub fn test_dedup_memory_forgets_pages_on_rollback() {
+        let journal = DedupMemoryJournal::new(BufferedJournal::default()).unwrap();
+        write_memory(&journal, 0..100, &[1u8; 100]);
+
+        // The page may have been discarded by the rollback so it is
+        // written again
+        journal.rollback().unwrap();
+        write_memory(&journal, 0..100, &[1u8; 100]);
+
+        let rx = journal.as_restarted().unwrap();
+        let mut n_pages = 0;
+        while let Some(entry) = rx.read().unwrap() {
+            if let JournalEntry::MemoryPageV1 { .. } = entry.record {
+                n_pages += 1;
+            }
+        }
+        assert_eq!(n_pages, 2);
+    }
+
+    #[cfg(feature = "log-file")]
+    #[test]
+    pub fn test_dedup_memory_with_compaction() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        // Compacting after every record also compacts while the pages of
+        // a memory update are being written
+        let journal = CompactingLogFileJournal::new(file.path())
+            .unwrap()
+            .with_compact_on_n_records(1);
+        let journal = DedupMemoryJournal::new(journal)
+            .unwrap()
+            .with_page_size(1024);
+
+        let data = |seed: u8| {
+            (0..4096)
+                .map(|i| seed * 16 + (i / 1024) as u8)
+                .collect::<Vec<_>>()
+        };
+        write_memory(&journal, 0..4096, &data(1));
+        snapshot(&journal);
+        // The first pages are no longer referenced and get compacted away
+        write_memory(&journal, 0..4096, &data(2));
+        snapshot(&journal);
+        // Hence they must be written again
+        write_memory(&journal, 0..4096, &data(1));
+        snapshot(&journal);
+        drop(journal);
+
+        let journal = LogFileJournal::new_readonly(file.path()).unwrap();
+        let mut pages = MemoryPageCache::new();
+        let mut memory = None;
+        while let Some(entry) = journal.read().unwrap() {
+            match entry.into_inner() {
+                JournalEntry::MemoryPageV1 {
+                    hash,
+                    compressed_data,
+                } => pages.insert_compressed(hash, &compressed_data).unwrap(),
+                JournalEntry::UpdateMemoryRegionPagesV1 {
+                    region,
+                    page_size,
+                    page_hashes,
+                } => memory = Some(pages.resolve(region, page_size, &page_hashes).unwrap()),
+                _ => {}
+            }
+        }
+        assert_eq!(memory, Some(data(1)));
+    }
+}
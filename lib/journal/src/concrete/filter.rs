@@ -241,6 +241,9 @@ impl<W: WritableJournal> WritableJournal for FilteredJournalTx<W> {
             | JournalEntry::ProcessExitV1 { .. }
             | JournalEntry::EpollCreateV1 { .. }
             | JournalEntry::EpollCtlV1 { .. }
+            | JournalEntry::WatchCreateV1 { .. }
+            | JournalEntry::WatchAddV1 { .. }
+            | JournalEntry::WatchRemoveV1 { .. }
             | JournalEntry::TtySetV1 { .. } => {
                 if self.config.filter_core {
                     return Ok(LogWriteResult {
@@ -260,7 +263,9 @@ impl<W: WritableJournal> WritableJournal for FilteredJournalTx<W> {
                 }
                 entry
             }
-            JournalEntry::UpdateMemoryRegionV1 { .. } => {
+            JournalEntry::UpdateMemoryRegionV1 { .. }
+            | JournalEntry::MemoryPageV1 { .. }
+            | JournalEntry::UpdateMemoryRegionPagesV1 { .. } => {
                 if self.config.filter_memory {
                     return Ok(LogWriteResult {
                         record_start: 0,
@@ -282,6 +287,8 @@ impl<W: WritableJournal> WritableJournal for FilteredJournalTx<W> {
                 original_fd: fd, ..
             }
             | JournalEntry::FileDescriptorSetFdFlagsV1 { fd, .. }
+            | JournalEntry::FileDescriptorLockV1 { fd, .. }
+            | JournalEntry::FileDescriptorLockRangeV1 { fd, .. }
             | JournalEntry::FileDescriptorSetFlagsV1 { fd, .. }
             | JournalEntry::FileDescriptorAdviseV1 { fd, .. }
             | JournalEntry::FileDescriptorAllocateV1 { fd, .. }
@@ -11,10 +11,14 @@ use rkyv::{
 };
 use shared_buffer::OwnedBuffer;
 use std::{
+    collections::VecDeque,
     fs::File,
     io::{Seek, SeekFrom, Write},
     path::Path,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
 };
 use virtual_fs::mem_fs::OffloadBackingStore;
 
@@ -31,6 +35,11 @@ use super::*;
 ///
 /// The logfile snapshot capturer uses a 64bit number as a entry encoding
 /// delimiter.
+///
+/// The (versioned) magic number is followed by a header that holds the
+/// version of the format the records were written in. Journals written in
+/// an older format are upgraded as they are read using the registered
+/// upgraders.
 #[derive(Debug)]
 pub struct LogFileJournal {
     tx: LogFileJournalTx,
@@ -49,6 +58,10 @@ struct TxState {
 
     /// The latest position in the file the serializator got to
     pos: usize,
+
+    /// Set when the existing records are in an older format, in which case
+    /// a new magic number and format header is written before the next record
+    needs_header: bool,
 }
 
 impl TxState {
@@ -85,6 +98,7 @@ impl std::fmt::Debug for TxState {
 #[derive(Debug, Clone)]
 pub struct LogFileJournalTx {
     state: Arc<Mutex<TxState>>,
+    upgraders: Arc<JournalUpgraderRegistry>,
 }
 
 #[derive(Debug)]
@@ -93,9 +107,67 @@ pub struct LogFileJournalRx {
     buffer_pos: Mutex<usize>,
     buffer: OwnedBuffer,
     store: OffloadBackingStore,
+    /// Format version of the records that are currently being read
+    version: AtomicU32,
+    /// Format version of the records at the start of the journal
+    initial_version: u32,
+    upgraders: Arc<JournalUpgraderRegistry>,
+    /// Records that were produced by upgrading an older record
+    upgraded: Mutex<VecDeque<LogReadResult<'static>>>,
 }
 
 impl LogFileJournalRx {
+    fn new(
+        tx: Option<LogFileJournalTx>,
+        buffer: OwnedBuffer,
+        store: OffloadBackingStore,
+        upgraders: Arc<JournalUpgraderRegistry>,
+    ) -> anyhow::Result<Self> {
+        // If the buffer exists we valid the magic number and read
+        // the format header that follows it
+        let mut buffer_pos = 0;
+        let mut version = JOURNAL_LEGACY_FORMAT_VERSION;
+        let mut buffer_ptr = buffer.as_ref();
+        if buffer_ptr.len() >= 8 {
+            let magic = u64::from_be_bytes(buffer_ptr[0..8].try_into().unwrap());
+            match magic {
+                JOURNAL_MAGIC_NUMBER => {}
+                JOURNAL_VERSIONED_MAGIC_NUMBER => {
+                    version = read_journal_format_header(&buffer_ptr[8..])?
+                        .ok_or_else(|| anyhow::format_err!("journal format header is missing"))?;
+                    buffer_pos += JOURNAL_HEADER_SIZE;
+                }
+                _ => {
+                    return Err(anyhow::format_err!(
+                        "invalid magic number of journal ({} vs {})",
+                        magic,
+                        JOURNAL_VERSIONED_MAGIC_NUMBER
+                    ));
+                }
+            }
+            buffer_ptr.advance(8);
+            buffer_pos += 8;
+        } else {
+            tracing::trace!("journal has no magic (could be empty?)");
+        }
+
+        Ok(LogFileJournalRx {
+            tx,
+            buffer_pos: Mutex::new(buffer_pos),
+            buffer,
+            store,
+            version: AtomicU32::new(version),
+            initial_version: version,
+            upgraders,
+            upgraded: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Version of the journal format that the journal was written in
+    pub fn format_version(&self) -> u32 {
+        self.initial_version
+    }
+
     pub fn owned_buffer(&self) -> OwnedBuffer {
         self.store.owned_buffer().clone()
     }
@@ -112,31 +184,9 @@ impl LogFileJournalTx {
 
         let store = OffloadBackingStore::from_file(&file);
         let buffer = store.owned_buffer();
+        drop(state);
 
-        // If the buffer exists we valid the magic number
-        let mut buffer_pos = 0;
-        let mut buffer_ptr = buffer.as_ref();
-        if buffer_ptr.len() >= 8 {
-            let magic = u64::from_be_bytes(buffer_ptr[0..8].try_into().unwrap());
-            if magic != JOURNAL_MAGIC_NUMBER {
-                return Err(anyhow::format_err!(
-                    "invalid magic number of journal ({} vs {})",
-                    magic,
-                    JOURNAL_MAGIC_NUMBER
-                ));
-            }
-            buffer_ptr.advance(8);
-            buffer_pos += 8;
-        } else {
-            tracing::trace!("journal has no magic (could be empty?)");
-        }
-
-        Ok(LogFileJournalRx {
-            tx: Some(self.clone()),
-            buffer_pos: Mutex::new(buffer_pos),
-            buffer,
-            store,
-        })
+        LogFileJournalRx::new(Some(self.clone()), buffer, store, self.upgraders.clone())
     }
 }
 
@@ -164,8 +214,51 @@ impl LogFileJournal {
         self.rx.backing_store()
     }
 
+    /// Version of the journal format that the journal was written in
+    pub fn format_version(&self) -> u32 {
+        self.rx.format_version()
+    }
+
+    /// Rewrites the journal at `source_path` into a new journal at
+    /// `target_path` that uses the current journal format, records of older
+    /// formats are upgraded by the supplied upgraders. Returns the version
+    /// of the format the source journal was written in.
+    pub fn migrate(
+        source_path: impl AsRef<Path>,
+        target_path: impl AsRef<Path>,
+        upgraders: JournalUpgraderRegistry,
+    ) -> anyhow::Result<u32> {
+        let target_path = target_path.as_ref();
+        if target_path.exists() {
+            anyhow::bail!("the target journal already exists: {target_path:?}");
+        }
+
+        let file = std::fs::File::options().read(true).open(source_path)?;
+        let source = Self::from_file_with_upgraders(file, upgraders.clone())?;
+        let from_version = source.format_version();
+        upgraders.check(from_version, JOURNAL_FORMAT_VERSION)?;
+
+        let target = Self::new(target_path)?;
+        copy_journal(&source, &target).map_err(|err| {
+            err.context(format!(
+                "failed to migrate the journal from format version {from_version}"
+            ))
+        })?;
+        target.flush()?;
+        Ok(from_version)
+    }
+
     /// Create a new journal from a file
-    pub fn from_file(mut file: std::fs::File) -> anyhow::Result<Self> {
+    pub fn from_file(file: std::fs::File) -> anyhow::Result<Self> {
+        Self::from_file_with_upgraders(file, JournalUpgraderRegistry::builtin())
+    }
+
+    /// Create a new journal from a file which will use the supplied
+    /// upgraders to read records of older journal formats
+    pub fn from_file_with_upgraders(
+        mut file: std::fs::File,
+        upgraders: JournalUpgraderRegistry,
+    ) -> anyhow::Result<Self> {
         // Move to the end of the file and write the
         // magic if one is needed
         let underlying_file = file.try_clone()?;
@@ -178,15 +271,15 @@ impl LogFileJournal {
             arena,
             file,
             pos: end_pos as usize,
+            needs_header: false,
         };
 
         let mut serializer = tx.get_serializer();
         let serializer = TxState::to_high(&mut serializer);
 
         if serializer.pos() == 0 {
-            let magic = JOURNAL_MAGIC_NUMBER;
-            let magic = magic.to_be_bytes();
-            serializer.write(&magic)?;
+            serializer.write(&JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES)?;
+            serializer.write(&journal_format_header(JOURNAL_FORMAT_VERSION))?;
         }
 
         let last_pos = serializer.pos();
@@ -198,11 +291,18 @@ impl LogFileJournal {
         // Create the tx
         let tx = LogFileJournalTx {
             state: Arc::new(Mutex::new(tx)),
+            upgraders: Arc::new(upgraders),
         };
 
         // First we create the readable journal
         let rx = tx.as_rx()?;
 
+        // Records of the current format can not be mixed with older
+        // records, hence the new ones get their own header
+        if rx.format_version() != JOURNAL_FORMAT_VERSION {
+            tx.state.lock().unwrap().needs_header = true;
+        }
+
         Ok(Self { rx, tx })
     }
 
@@ -210,12 +310,26 @@ impl LogFileJournal {
     pub fn from_buffer(
         buffer: OwnedBuffer,
     ) -> RecombinedJournal<UnsupportedJournal, LogFileJournalRx> {
-        // Create the rx
+        Self::from_buffer_with_upgraders(buffer, JournalUpgraderRegistry::builtin())
+    }
+
+    /// Create a new journal from a buffer which will use the supplied
+    /// upgraders to read records of older journal formats
+    pub fn from_buffer_with_upgraders(
+        buffer: OwnedBuffer,
+        upgraders: JournalUpgraderRegistry,
+    ) -> RecombinedJournal<UnsupportedJournal, LogFileJournalRx> {
+        // Create the rx (the magic number and header are read as the
+        // journal is read)
         let rx = LogFileJournalRx {
             tx: None,
             buffer_pos: Mutex::new(0),
             buffer: buffer.clone(),
             store: OffloadBackingStore::from_buffer(buffer),
+            version: AtomicU32::new(JOURNAL_LEGACY_FORMAT_VERSION),
+            initial_version: JOURNAL_LEGACY_FORMAT_VERSION,
+            upgraders: Arc::new(upgraders),
+            upgraded: Mutex::new(VecDeque::new()),
         };
 
         // Create an unsupported write journal
@@ -231,11 +345,16 @@ impl WritableJournal for LogFileJournalTx {
         tracing::debug!("journal event: {:?}", entry);
 
         let mut state = self.state.lock().unwrap();
+        let needs_header = std::mem::take(&mut state.needs_header);
 
         // Write the header (with a record size of zero)
         let record_type: JournalEntryRecordType = entry.archive_record_type();
         let mut serializer = state.get_serializer();
         let serializer = TxState::to_high(&mut serializer);
+        if needs_header {
+            serializer.write(&JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES)?;
+            serializer.write(&journal_format_header(JOURNAL_FORMAT_VERSION))?;
+        }
         let offset_header = serializer.pos() as u64;
         tracing::trace!("serpos is {offset_header}");
         serializer.write(&[0u8; 8])?;
@@ -290,6 +409,12 @@ impl ReadableJournal for LogFileJournalRx {
         let mut buffer_ptr = self.buffer.as_ref();
         buffer_ptr.advance(*buffer_pos);
         loop {
+            // Records that were upgraded from an older format are
+            // returned before anything else is read
+            if let Some(next) = self.upgraded.lock().unwrap().pop_front() {
+                return Ok(Some(next));
+            }
+
             // Read the headers and advance
             if buffer_ptr.len() < 8 {
                 return Ok(None);
@@ -307,6 +432,19 @@ impl ReadableJournal for LogFileJournalRx {
                 if b[0..8] == JOURNAL_MAGIC_NUMBER_BYTES[0..8] {
                     buffer_ptr.advance(8);
                     *buffer_pos += 8;
+                    self.version
+                        .store(JOURNAL_LEGACY_FORMAT_VERSION, Ordering::SeqCst);
+                    continue;
+                }
+
+                // The versioned magic is followed by the format header that
+                // describes the records that follow it
+                if b[0..8] == JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES[0..8] {
+                    let version = read_journal_format_header(&b[8..])?
+                        .ok_or_else(|| anyhow::format_err!("journal format header is missing"))?;
+                    buffer_ptr.advance(8 + JOURNAL_HEADER_SIZE);
+                    *buffer_pos += 8 + JOURNAL_HEADER_SIZE;
+                    self.version.store(version, Ordering::SeqCst);
                     continue;
                 }
 
@@ -316,6 +454,60 @@ impl ReadableJournal for LogFileJournalRx {
                     record_size: u64::from_be_bytes([0u8, 0u8, b[2], b[3], b[4], b[5], b[6], b[7]]),
                 };
 
+                // Records of older formats must be upgraded before they
+                // can be interpreted
+                let version = self.version.load(Ordering::SeqCst);
+                if version != JOURNAL_FORMAT_VERSION {
+                    let record_start = (*buffer_pos + 8) as u64;
+                    let record_end = record_start + header.record_size;
+                    let record_size = usize::try_from(header.record_size)
+                        .ok()
+                        .filter(|size| buffer_ptr.len() - 8 >= *size)
+                        .ok_or_else(|| {
+                            anyhow::format_err!(
+                                "journal record at {} is truncated ({} bytes are missing)",
+                                record_start,
+                                header
+                                    .record_size
+                                    .saturating_sub((buffer_ptr.len() - 8) as u64)
+                            )
+                        })?;
+                    let data = &buffer_ptr[8..(8 + record_size)];
+                    buffer_ptr.advance(8 + record_size);
+                    *buffer_pos += 8 + record_size;
+
+                    let records = self.upgraders.upgrade(
+                        version,
+                        JOURNAL_FORMAT_VERSION,
+                        JournalRawRecord {
+                            record_type: header.record_type,
+                            data: data.into(),
+                        },
+                    )?;
+
+                    let mut upgraded = self.upgraded.lock().unwrap();
+                    for record in records {
+                        let record_type: JournalEntryRecordType =
+                            record.record_type.try_into().map_err(|_| {
+                                anyhow::format_err!(
+                                    "upgraded journal entry has an unknown type ({})",
+                                    record.record_type
+                                )
+                            })?;
+
+                        // The upgraded data is copied so that it is aligned
+                        let mut data = rkyv::util::AlignedVec::<16>::new();
+                        data.extend_from_slice(&record.data);
+                        let record = unsafe { record_type.deserialize_archive(&data)? };
+                        upgraded.push_back(LogReadResult {
+                            record_start,
+                            record_end,
+                            record: record.into_owned(),
+                        });
+                    }
+                    continue;
+                }
+
                 // Now we read the entry
                 record_type = match header.record_type.try_into() {
                     Ok(t) => t,
@@ -358,6 +550,10 @@ impl ReadableJournal for LogFileJournalRx {
                 buffer_pos: Mutex::new(0),
                 buffer: self.buffer.clone(),
                 store: self.store.clone(),
+                version: AtomicU32::new(JOURNAL_LEGACY_FORMAT_VERSION),
+                initial_version: self.initial_version,
+                upgraders: self.upgraders.clone(),
+                upgraded: Mutex::new(VecDeque::new()),
             }))
         }
     }
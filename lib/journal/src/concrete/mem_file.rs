@@ -2,7 +2,7 @@ use std::{
     fs::File,
     io::{Seek, Write},
     path::Path,
-    sync::RwLock,
+    sync::{Arc, Mutex, RwLock},
 };
 
 use lz4_flex::{block, decompress};
@@ -15,6 +15,7 @@ use super::*;
 #[derive(Debug)]
 pub struct MemFileJournal {
     file: RwLock<File>,
+    pages: Arc<Mutex<MemoryPageCache>>,
 }
 
 impl MemFileJournal {
@@ -27,6 +28,7 @@ impl MemFileJournal {
                     .write(true)
                     .open(path)?,
             ),
+            pages: Default::default(),
         })
     }
 }
@@ -42,6 +44,7 @@ impl Clone for MemFileJournal {
         let file = self.file.read().unwrap();
         Self {
             file: RwLock::new(file.try_clone().unwrap()),
+            pages: self.pages.clone(),
         }
     }
 }
@@ -72,6 +75,27 @@ impl WritableJournal for MemFileJournal {
                 file.seek(std::io::SeekFrom::Start(region.start))?;
                 file.write_all(&decompressed_data)?;
             }
+            JournalEntry::MemoryPageV1 {
+                hash,
+                compressed_data,
+            } => {
+                let mut pages = self.pages.lock().unwrap();
+                pages.insert_compressed(hash, &compressed_data)?;
+            }
+            JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_size,
+                page_hashes,
+            } => {
+                let data = {
+                    let pages = self.pages.lock().unwrap();
+                    pages.resolve(region.clone(), page_size, &page_hashes)?
+                };
+
+                let mut file = self.file.write().unwrap();
+                file.seek(std::io::SeekFrom::Start(region.start))?;
+                file.write_all(&data)?;
+            }
             JournalEntry::ProcessExitV1 { .. } | JournalEntry::InitModuleV1 { .. } => {
                 let file = self.file.read().unwrap();
                 file.set_len(0)?;
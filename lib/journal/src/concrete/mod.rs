@@ -11,6 +11,7 @@ mod compacting;
 mod compacting_log_file;
 mod compacting_transaction;
 mod counting;
+mod dedup_memory;
 mod filter;
 #[cfg(feature = "log-file")]
 mod log_file;
@@ -19,10 +20,13 @@ mod null;
 mod pipe;
 mod printing;
 mod recombined;
+mod restore_point;
+mod statistics;
 #[cfg(test)]
 mod tests;
 mod transaction;
 mod unsupported;
+mod versioning;
 
 pub(super) use super::*;
 
@@ -36,6 +40,7 @@ pub use compacting::*;
 pub use compacting_log_file::*;
 pub use compacting_transaction::*;
 pub use counting::*;
+pub use dedup_memory::*;
 pub use filter::*;
 #[cfg(feature = "log-file")]
 pub use log_file::*;
@@ -44,5 +49,8 @@ pub use null::*;
 pub use pipe::*;
 pub use printing::*;
 pub use recombined::*;
+pub use restore_point::*;
+pub use statistics::*;
 pub use transaction::*;
 pub use unsupported::*;
+pub use versioning::*;
@@ -89,6 +89,30 @@ impl fmt::Display for JournalEntry<'_> {
                     .unwrap_or_else(|_| compressed_data.as_ref().len()),
                 compressed_data.len()
             ),
+            JournalEntry::MemoryPageV1 {
+                hash,
+                compressed_data,
+            } => write!(
+                f,
+                "memory-page (hash={:x?}, data.len={}, compressed.len={})",
+                hash,
+                uncompressed_size(compressed_data.as_ref())
+                    .map(|a| a.0)
+                    .unwrap_or_else(|_| compressed_data.as_ref().len()),
+                compressed_data.len()
+            ),
+            JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_size,
+                page_hashes,
+            } => write!(
+                f,
+                "memory-update-pages (start={}, end={}, page_size={}, pages={})",
+                region.start,
+                region.end,
+                page_size,
+                page_hashes.len() / 32
+            ),
             JournalEntry::ProcessExitV1 { exit_code } => {
                 write!(f, "process-exit (code={exit_code:?})")
             }
@@ -175,6 +199,15 @@ impl fmt::Display for JournalEntry<'_> {
             JournalEntry::FileDescriptorSetFlagsV1 { fd, flags } => {
                 write!(f, "fd-set-flags (fd={fd}, flags={flags:?})")
             }
+            JournalEntry::FileDescriptorLockV1 { fd, op } => {
+                write!(f, "fd-lock (fd={fd}, op={op:?})")
+            }
+            JournalEntry::FileDescriptorLockRangeV1 { fd, op, start, len } => {
+                write!(
+                    f,
+                    "fd-lock-range (fd={fd}, op={op:?}, start={start}, len={len})"
+                )
+            }
             JournalEntry::FileDescriptorSetRightsV1 {
                 fd,
                 fs_rights_base,
@@ -204,6 +237,14 @@ impl fmt::Display for JournalEntry<'_> {
             JournalEntry::EpollCtlV1 { epfd, op, fd, .. } => {
                 write!(f, "epoll-ctl (epfd={epfd}, op={op:?}, fd={fd})")
             }
+            JournalEntry::WatchCreateV1 { fd, .. } => write!(f, "watch-create (fd={fd})"),
+            JournalEntry::WatchAddV1 {
+                fd, path, mask, wd, ..
+            } => write!(
+                f,
+                "watch-add (fd={fd}, path={path}, mask={mask:#x}, wd={wd})"
+            ),
+            JournalEntry::WatchRemoveV1 { fd, wd } => write!(f, "watch-remove (fd={fd}, wd={wd})"),
             JournalEntry::TtySetV1 { tty, line_feeds } => write!(f, "tty-set (echo={}, buffering={}, feeds={})", tty.echo, tty.line_buffered, line_feeds),
             JournalEntry::CreatePipeV1 { read_fd, write_fd } => {
                 write!(f, "fd-pipe (read_fd={read_fd}, write_fd={write_fd})")
@@ -0,0 +1,221 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use super::*;
+
+/// Represents a position within a journal that a restore will stop at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JournalRestorePoint {
+    /// Stops the restore just before the record that starts at (or after)
+    /// this offset within the journal
+    RecordOffset(u64),
+    /// Stops the restore just after the Nth (zero-based) snapshot
+    /// marker in the journal
+    Snapshot(usize),
+}
+
+impl FromStr for JournalRestorePoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if let Some(index) = s
+            .strip_prefix("snapshot-")
+            .or_else(|| s.strip_prefix("snap-"))
+        {
+            return index
+                .parse()
+                .map(Self::Snapshot)
+                .map_err(|err| format!("invalid snapshot index ({index}) - {err}"));
+        }
+
+        let offset = match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => s.parse(),
+        };
+        offset.map(Self::RecordOffset).map_err(|err| {
+            format!("invalid restore point ({s}), expected '<offset>' or 'snapshot-<n>' - {err}")
+        })
+    }
+}
+
+impl fmt::Display for JournalRestorePoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RecordOffset(offset) => write!(f, "{offset}"),
+            Self::Snapshot(index) => write!(f, "snapshot-{index}"),
+        }
+    }
+}
+
+/// The restore point journal will replay the records of an inner journal
+/// up until a particular restore point and then stop, this makes it possible
+/// to restore a process to the state it was in at an earlier moment in time
+/// (e.g. just before a bug occurred).
+#[derive(Debug)]
+pub struct RestorePointJournal<R: ReadableJournal> {
+    inner: R,
+    restore_point: JournalRestorePoint,
+    snapshot_index: AtomicUsize,
+    reached: AtomicBool,
+}
+
+impl<R: ReadableJournal> RestorePointJournal<R> {
+    pub fn new(inner: R, restore_point: JournalRestorePoint) -> Self {
+        Self {
+            inner,
+            restore_point,
+            snapshot_index: AtomicUsize::new(0),
+            reached: AtomicBool::new(false),
+        }
+    }
+
+    pub fn restore_point(&self) -> JournalRestorePoint {
+        self.restore_point
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: ReadableJournal> ReadableJournal for RestorePointJournal<R> {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        if self.reached.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+
+        let next = match self.inner.read()? {
+            Some(next) => next,
+            None => {
+                // Restoring to a snapshot that does not exist is almost certainly
+                // a mistake so rather than silently restoring everything we fail
+                if let JournalRestorePoint::Snapshot(index) = self.restore_point {
+                    let found = self.snapshot_index.load(Ordering::SeqCst);
+                    anyhow::bail!(
+                        "journal restore point (snapshot-{index}) not found - the journal only contains {found} snapshot(s)"
+                    );
+                }
+                return Ok(None);
+            }
+        };
+
+        match self.restore_point {
+            JournalRestorePoint::RecordOffset(offset) => {
+                if next.record_start >= offset {
+                    tracing::debug!(offset, "journal restore point reached");
+                    self.reached.store(true, Ordering::SeqCst);
+                    return Ok(None);
+                }
+            }
+            JournalRestorePoint::Snapshot(index) => {
+                if let JournalEntry::SnapshotV1 { .. } = &next.record {
+                    if self.snapshot_index.fetch_add(1, Ordering::SeqCst) >= index {
+                        tracing::debug!(index, "journal restore point reached");
+                        self.reached.store(true, Ordering::SeqCst);
+                    }
+                }
+            }
+        }
+        Ok(Some(next))
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        Ok(Box::new(RestorePointJournal::new(
+            self.inner.as_restarted()?,
+            self.restore_point,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn build_journal() -> BufferedJournal {
+        let journal = BufferedJournal::default();
+        for fd in 0..6 {
+            journal
+                .write(JournalEntry::CloseFileDescriptorV1 { fd })
+                .unwrap();
+            if fd % 2 == 1 {
+                journal
+                    .write(JournalEntry::SnapshotV1 {
+                        when: SystemTime::UNIX_EPOCH,
+                        trigger: SnapshotTrigger::Idle,
+                    })
+                    .unwrap();
+            }
+        }
+        journal
+    }
+
+    fn count_records(journal: &DynReadableJournal) -> anyhow::Result<usize> {
+        let mut cnt = 0;
+        while journal.read()?.is_some() {
+            cnt += 1;
+        }
+        Ok(cnt)
+    }
+
+    #[test]
+    pub fn test_parse_restore_point() {
+        assert_eq!(
+            "1234".parse::<JournalRestorePoint>(),
+            Ok(JournalRestorePoint::RecordOffset(1234))
+        );
+        assert_eq!(
+            "0x10".parse::<JournalRestorePoint>(),
+            Ok(JournalRestorePoint::RecordOffset(16))
+        );
+        assert_eq!(
+            "snapshot-2".parse::<JournalRestorePoint>(),
+            Ok(JournalRestorePoint::Snapshot(2))
+        );
+        assert!("snapshot-x".parse::<JournalRestorePoint>().is_err());
+        assert!("blah".parse::<JournalRestorePoint>().is_err());
+    }
+
+    #[test]
+    pub fn test_restore_to_record_offset() {
+        let journal = RestorePointJournal::new(
+            build_journal().as_restarted().unwrap(),
+            JournalRestorePoint::RecordOffset(4),
+        );
+        assert_eq!(count_records(&journal).unwrap(), 4);
+
+        // Restarting the journal should reapply the restore point
+        let journal = journal.as_restarted().unwrap();
+        assert_eq!(count_records(journal.as_ref()).unwrap(), 4);
+    }
+
+    #[test]
+    pub fn test_restore_to_snapshot() {
+        let journal = RestorePointJournal::new(
+            build_journal().as_restarted().unwrap(),
+            JournalRestorePoint::Snapshot(1),
+        );
+        let mut last = None;
+        let mut cnt = 0;
+        while let Some(next) = journal.read().unwrap() {
+            last = Some(next.into_inner());
+            cnt += 1;
+        }
+        assert_eq!(cnt, 6);
+        assert!(matches!(last, Some(JournalEntry::SnapshotV1 { .. })));
+    }
+
+    #[test]
+    pub fn test_restore_to_missing_snapshot() {
+        let journal = RestorePointJournal::new(
+            build_journal().as_restarted().unwrap(),
+            JournalRestorePoint::Snapshot(3),
+        );
+        assert!(count_records(&journal).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use wasmer_wasix_types::wasi::ExitCode;
+
+use super::*;
+
+/// Report that describes what a journal is made of, which is useful when
+/// trying to work out why a journal has grown large or when tuning the
+/// snapshot triggers of a process.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JournalStatistics {
+    /// Total number of records in the journal
+    pub total_records: u64,
+    /// Total number of bytes consumed by the records in the journal
+    pub total_bytes: u64,
+    /// Number of records and bytes for each type of event
+    pub event_types: BTreeMap<String, JournalEventTypeStatistics>,
+    /// Timeline of the events that relate to each thread
+    pub threads: BTreeMap<u32, Vec<JournalThreadEvent>>,
+    /// Position of every snapshot within the journal
+    pub snapshots: Vec<JournalSnapshotPosition>,
+    /// Memory regions that have had the most bytes written to them
+    pub memory_regions: Vec<JournalMemoryRegionStatistics>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEventTypeStatistics {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalThreadEvent {
+    pub record_start: u64,
+    #[serde(flatten)]
+    pub kind: JournalThreadEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalThreadEventKind {
+    /// The state of the thread was written to the journal
+    Set { bytes: u64 },
+    /// The thread exited
+    Close { exit_code: Option<ExitCode> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalSnapshotPosition {
+    /// Zero-based index of the snapshot (as used by restore points)
+    pub index: usize,
+    pub record_start: u64,
+    pub when: SystemTime,
+    pub trigger: SnapshotTrigger,
+    /// Number of records written since the previous snapshot
+    pub records_since_previous: u64,
+    /// Number of bytes written since the previous snapshot
+    pub bytes_since_previous: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalMemoryRegionStatistics {
+    pub start: u64,
+    pub end: u64,
+    /// Number of times the region was written to the journal
+    pub writes: u64,
+    /// Number of bytes the writes consumed in the journal
+    pub bytes: u64,
+}
+
+impl JournalStatistics {
+    /// Reads all the records in a journal and builds a report from them,
+    /// only the `max_memory_regions` largest memory regions are retained
+    pub fn collect<R>(journal: &R, max_memory_regions: usize) -> anyhow::Result<Self>
+    where
+        R: ReadableJournal + ?Sized,
+    {
+        let mut ret = Self::default();
+        let mut regions: HashMap<Range<u64>, JournalMemoryRegionStatistics> = HashMap::new();
+        let mut since_snapshot = (0u64, 0u64);
+
+        while let Some(next) = journal.read()? {
+            let bytes = next.record_end.saturating_sub(next.record_start);
+            ret.total_records += 1;
+            ret.total_bytes += bytes;
+            since_snapshot.0 += 1;
+            since_snapshot.1 += bytes;
+
+            let event_type = ret
+                .event_types
+                .entry(format!("{:?}", next.record.archive_record_type()))
+                .or_default();
+            event_type.count += 1;
+            event_type.bytes += bytes;
+
+            match &next.record {
+                JournalEntry::SetThreadV1 { id, .. } => {
+                    ret.threads
+                        .entry(*id)
+                        .or_default()
+                        .push(JournalThreadEvent {
+                            record_start: next.record_start,
+                            kind: JournalThreadEventKind::Set { bytes },
+                        });
+                }
+                JournalEntry::CloseThreadV1 { id, exit_code } => {
+                    ret.threads
+                        .entry(*id)
+                        .or_default()
+                        .push(JournalThreadEvent {
+                            record_start: next.record_start,
+                            kind: JournalThreadEventKind::Close {
+                                exit_code: *exit_code,
+                            },
+                        });
+                }
+                JournalEntry::SnapshotV1 { when, trigger } => {
+                    ret.snapshots.push(JournalSnapshotPosition {
+                        index: ret.snapshots.len(),
+                        record_start: next.record_start,
+                        when: *when,
+                        trigger: *trigger,
+                        records_since_previous: since_snapshot.0,
+                        bytes_since_previous: since_snapshot.1,
+                    });
+                    since_snapshot = (0, 0);
+                }
+                JournalEntry::UpdateMemoryRegionV1 { region, .. }
+                | JournalEntry::UpdateMemoryRegionPagesV1 { region, .. } => {
+                    let stats = regions.entry(region.clone()).or_insert_with(|| {
+                        JournalMemoryRegionStatistics {
+                            start: region.start,
+                            end: region.end,
+                            writes: 0,
+                            bytes: 0,
+                        }
+                    });
+                    stats.writes += 1;
+                    stats.bytes += bytes;
+                }
+                _ => {}
+            }
+        }
+
+        let mut regions = regions.into_values().collect::<Vec<_>>();
+        regions.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.start.cmp(&b.start)));
+        regions.truncate(max_memory_regions);
+        ret.memory_regions = regions;
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_journal_statistics() {
+        let journal = BufferedJournal::default();
+        let records = vec![
+            JournalEntry::UpdateMemoryRegionV1 {
+                region: 0..4096,
+                compressed_data: [1u8; 100].to_vec().into(),
+            },
+            JournalEntry::CloseThreadV1 {
+                id: 2,
+                exit_code: None,
+            },
+            JournalEntry::SnapshotV1 {
+                when: SystemTime::UNIX_EPOCH,
+                trigger: SnapshotTrigger::Idle,
+            },
+            JournalEntry::UpdateMemoryRegionV1 {
+                region: 0..4096,
+                compressed_data: [2u8; 100].to_vec().into(),
+            },
+            JournalEntry::UpdateMemoryRegionV1 {
+                region: 8192..12288,
+                compressed_data: [3u8; 100].to_vec().into(),
+            },
+            JournalEntry::SnapshotV1 {
+                when: SystemTime::UNIX_EPOCH,
+                trigger: SnapshotTrigger::Explicit,
+            },
+        ];
+        for record in records {
+            journal.write(record).unwrap();
+        }
+
+        let stats = JournalStatistics::collect(&journal.as_restarted().unwrap(), 1).unwrap();
+        assert_eq!(stats.total_records, 6);
+        assert_eq!(stats.event_types["UpdateMemoryRegionV1"].count, 3);
+        assert_eq!(stats.event_types["SnapshotV1"].count, 2);
+        assert_eq!(
+            stats.threads[&2],
+            vec![JournalThreadEvent {
+                record_start: 1,
+                kind: JournalThreadEventKind::Close { exit_code: None },
+            }]
+        );
+
+        assert_eq!(stats.snapshots.len(), 2);
+        assert_eq!(stats.snapshots[0].trigger, SnapshotTrigger::Idle);
+        assert_eq!(stats.snapshots[0].records_since_previous, 3);
+        assert_eq!(stats.snapshots[1].index, 1);
+        assert_eq!(stats.snapshots[1].trigger, SnapshotTrigger::Explicit);
+        assert_eq!(stats.snapshots[1].records_since_previous, 3);
+
+        assert_eq!(stats.memory_regions.len(), 1);
+        assert_eq!(stats.memory_regions[0].start, 0);
+        assert_eq!(stats.memory_regions[0].writes, 2);
+
+        // The report must be representable as JSON
+        let json = serde_json::to_string(&stats).unwrap();
+        let stats2: JournalStatistics = serde_json::from_str(&json).unwrap();
+        assert_eq!(stats, stats2);
+    }
+}
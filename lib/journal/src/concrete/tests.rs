@@ -321,6 +321,34 @@ pub fn test_record_epoll_create() {
     run_test(JournalEntry::EpollCreateV1 { fd: 45384752 });
 }
 
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_watch_create() {
+    run_test(JournalEntry::WatchCreateV1 {
+        fd: 3452345,
+        flags: wasi::Fdflags::NONBLOCK,
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_watch_add() {
+    run_test(JournalEntry::WatchAddV1 {
+        fd: 3452345,
+        dirfd: 4,
+        flags: 1,
+        path: "/var/log".into(),
+        mask: 0x302,
+        wd: 7,
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_watch_remove() {
+    run_test(JournalEntry::WatchRemoveV1 { fd: 3452345, wd: 7 });
+}
+
 #[tracing_test::traced_test]
 #[test]
 pub fn test_record_epoll_ctl() {
@@ -587,6 +615,57 @@ pub fn test_record_socket_send() {
     });
 }
 
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_net_events() {
+    let events = [
+        virtual_net::replay::NetEvent::Connected {
+            conn: 7,
+            local_addr: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 4000),
+            peer_addr: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 80),
+        },
+        virtual_net::replay::NetEvent::Send {
+            conn: 7,
+            data: b"ping".to_vec(),
+        },
+        virtual_net::replay::NetEvent::SendTo {
+            socket: 8,
+            peer: Some(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 53)),
+            data: b"query".to_vec(),
+        },
+    ];
+    for event in events.iter() {
+        let record = JournalEntry::from_net_event(event).unwrap();
+        assert_eq!(record.to_net_event().as_ref(), Some(event));
+        run_test(record);
+    }
+
+    // Neither received data, datagrams without a peer nor oversized
+    // connection numbers fit a journal
+    assert!(
+        JournalEntry::from_net_event(&virtual_net::replay::NetEvent::Recv {
+            conn: 7,
+            data: b"pong".to_vec(),
+        })
+        .is_none()
+    );
+    assert!(
+        JournalEntry::from_net_event(&virtual_net::replay::NetEvent::SendTo {
+            socket: 8,
+            peer: None,
+            data: b"frame".to_vec(),
+        })
+        .is_none()
+    );
+    assert!(
+        JournalEntry::from_net_event(&virtual_net::replay::NetEvent::Send {
+            conn: u64::MAX,
+            data: Vec::new(),
+        })
+        .is_none()
+    );
+}
+
 #[tracing_test::traced_test]
 #[test]
 pub fn test_record_socket_set_opt_flag() {
@@ -690,6 +769,9 @@ pub fn test_record_alignment() {
     assert_eq!(std::mem::align_of::<JournalEntryChangeDirectoryV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntryEpollCreateV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntryEpollCtlV1>(), 8);
+    assert_eq!(std::mem::align_of::<JournalEntryWatchCreateV1>(), 8);
+    assert_eq!(std::mem::align_of::<JournalEntryWatchAddV1>(), 8);
+    assert_eq!(std::mem::align_of::<JournalEntryWatchRemoveV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntryTtySetV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntryCreatePipeV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntryCreateEventV1>(), 8);
@@ -729,3 +811,42 @@ pub fn test_record_alignment() {
     assert_eq!(std::mem::align_of::<JournalEntrySocketShutdownV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntrySnapshotV1>(), 8);
 }
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_memory_page() {
+    run_test(JournalEntry::MemoryPageV1 {
+        hash: [7u8; 32],
+        compressed_data: compress_prepend_size(&[4u8; 4096]).into(),
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_update_memory_pages() {
+    run_test(JournalEntry::UpdateMemoryRegionPagesV1 {
+        region: 4096..12288,
+        page_size: 4096,
+        page_hashes: [[1u8; 32], [2u8; 32]].concat().into(),
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_descriptor_lock() {
+    run_test(JournalEntry::FileDescriptorLockV1 {
+        fd: 1234,
+        op: wasi::Flockop::Exclusive,
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_descriptor_lock_range() {
+    run_test(JournalEntry::FileDescriptorLockRangeV1 {
+        fd: 1234,
+        op: wasi::Flockop::Shared,
+        start: 4096,
+        len: 0,
+    });
+}
@@ -0,0 +1,391 @@
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
+/// Version of the on-disk format that journals are written in, this must be
+/// incremented whenever the archived layout of an existing record changes
+/// (adding new record types does not require a new version) and an upgrader
+/// must then be registered that converts records of the previous version
+pub const JOURNAL_FORMAT_VERSION: u32 = 1;
+
+/// Journals written before the format header was introduced (which start
+/// with [`JOURNAL_MAGIC_NUMBER`]) carry no version and are treated as this
+/// version
+pub const JOURNAL_LEGACY_FORMAT_VERSION: u32 = 1;
+
+/// Reserved record type of the header that follows the
+/// [`JOURNAL_VERSIONED_MAGIC_NUMBER`] and describes the format of the
+/// records that come after it
+pub const JOURNAL_HEADER_RECORD_TYPE: u16 = 0xFFFF;
+
+/// Size of the format header record (including its record header)
+pub const JOURNAL_HEADER_SIZE: usize = 16;
+
+/// Builds the format header that is written after the versioned magic number
+pub fn journal_format_header(version: u32) -> [u8; JOURNAL_HEADER_SIZE] {
+    let mut ret = [0u8; JOURNAL_HEADER_SIZE];
+    ret[0..2].copy_from_slice(&JOURNAL_HEADER_RECORD_TYPE.to_be_bytes());
+    ret[2..8].copy_from_slice(&8u64.to_be_bytes()[2..8]);
+    ret[8..12].copy_from_slice(&version.to_be_bytes());
+    ret
+}
+
+/// Attempts to read a format header from the start of the buffer, returns the
+/// version it describes or `None` if the buffer does not start with one
+pub fn read_journal_format_header(buffer: &[u8]) -> anyhow::Result<Option<u32>> {
+    if buffer.len() < 8 || buffer[0..2] != JOURNAL_HEADER_RECORD_TYPE.to_be_bytes() {
+        return Ok(None);
+    }
+    if buffer.len() < JOURNAL_HEADER_SIZE {
+        anyhow::bail!("journal format header is truncated");
+    }
+    let version = u32::from_be_bytes(buffer[8..12].try_into().unwrap());
+    if version > JOURNAL_FORMAT_VERSION {
+        anyhow::bail!(
+            "journal format version ({version}) is newer than the versions supported by this runtime (up to {JOURNAL_FORMAT_VERSION})"
+        );
+    }
+    Ok(Some(version))
+}
+
+/// Record as it is stored in the journal before it is interpreted, upgraders
+/// operate on these as older layouts can not be read as a [`JournalEntry`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalRawRecord<'a> {
+    pub record_type: u16,
+    pub data: Cow<'a, [u8]>,
+}
+
+/// Converts the records of one version of the journal format into the
+/// records of the next version
+pub trait JournalUpgrader: std::fmt::Debug + Send + Sync {
+    /// The version of the records that this upgrader accepts, the records
+    /// it returns are of the next version
+    fn source_version(&self) -> u32;
+
+    /// Upgrades a single record which may result in any number of records
+    fn upgrade<'a>(
+        &self,
+        record: JournalRawRecord<'a>,
+    ) -> anyhow::Result<Vec<JournalRawRecord<'a>>>;
+}
+
+/// Registry of the upgraders that are used to read journals which were
+/// written in an older version of the journal format
+#[derive(Debug, Clone, Default)]
+pub struct JournalUpgraderRegistry {
+    upgraders: BTreeMap<u32, Arc<dyn JournalUpgrader>>,
+}
+
+impl JournalUpgraderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry holding the upgraders of every older journal format that
+    /// this version of the journal knows how to read, this is where the
+    /// upgrader of a format is registered when the format changes
+    pub fn builtin() -> Self {
+        Self::new()
+    }
+
+    /// Registers an upgrader, replacing any upgrader that already exists
+    /// for the same version
+    pub fn register(&mut self, upgrader: impl JournalUpgrader + 'static) {
+        self.upgraders
+            .insert(upgrader.source_version(), Arc::new(upgrader));
+    }
+
+    pub fn with_upgrader(mut self, upgrader: impl JournalUpgrader + 'static) -> Self {
+        self.register(upgrader);
+        self
+    }
+
+    /// Returns an error if records of a particular version can not be
+    /// upgraded all the way to the target version
+    pub fn check(&self, from_version: u32, to_version: u32) -> anyhow::Result<()> {
+        for version in from_version..to_version {
+            if !self.upgraders.contains_key(&version) {
+                anyhow::bail!(
+                    "no upgrader is registered for journal format version {version} (required to upgrade to version {to_version})"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Upgrades a record through every version between `from_version`
+    /// and `to_version`
+    pub fn upgrade<'a>(
+        &self,
+        from_version: u32,
+        to_version: u32,
+        record: JournalRawRecord<'a>,
+    ) -> anyhow::Result<Vec<JournalRawRecord<'a>>> {
+        self.check(from_version, to_version)?;
+
+        let mut records = vec![record];
+        for version in from_version..to_version {
+            let upgrader = &self.upgraders[&version];
+            let mut next = Vec::with_capacity(records.len());
+            for record in records {
+                next.extend(upgrader.upgrade(record)?);
+            }
+            records = next;
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(all(test, feature = "log-file"))]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use shared_buffer::OwnedBuffer;
+    use wasmer_wasix_types::{
+        wasi,
+        wasix::{ThreadStartType, WasiMemoryLayout},
+    };
+
+    use super::*;
+    use crate::*;
+
+    const GOLDEN_LEGACY: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/journal-v1-legacy.bin"
+    );
+    const GOLDEN_V1: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/journal-v1.bin");
+
+    /// Upgrader that splits every record into two copies of itself
+    #[derive(Debug)]
+    struct DuplicatingUpgrader(u32);
+
+    impl JournalUpgrader for DuplicatingUpgrader {
+        fn source_version(&self) -> u32 {
+            self.0
+        }
+
+        fn upgrade<'a>(
+            &self,
+            record: JournalRawRecord<'a>,
+        ) -> anyhow::Result<Vec<JournalRawRecord<'a>>> {
+            Ok(vec![record.clone(), record])
+        }
+    }
+
+    fn golden_entries() -> Vec<JournalEntry<'static>> {
+        vec![
+            JournalEntry::InitModuleV1 {
+                wasm_hash: Box::new([13u8; 8]),
+            },
+            JournalEntry::UpdateMemoryRegionV1 {
+                region: 0..4096,
+                compressed_data: lz4_flex::compress_prepend_size(&[74u8; 4096]).into(),
+            },
+            JournalEntry::SetThreadV1 {
+                id: 1,
+                call_stack: vec![1, 2, 3].into(),
+                memory_stack: vec![4, 5, 6, 7].into(),
+                store_data: vec![10, 11].into(),
+                start: ThreadStartType::MainThread,
+                layout: WasiMemoryLayout {
+                    stack_upper: 0,
+                    stack_lower: 1024,
+                    guard_size: 16,
+                    stack_size: 1024,
+                    tls_base: None,
+                },
+                is_64bit: false,
+            },
+            JournalEntry::OpenFileDescriptorV1 {
+                fd: 5,
+                dirfd: 3,
+                dirflags: 1,
+                path: "/etc/app.conf".into(),
+                o_flags: wasi::Oflags::CREATE,
+                fs_rights_base: wasi::Rights::all(),
+                fs_rights_inheriting: wasi::Rights::all(),
+                fs_flags: wasi::Fdflags::APPEND,
+            },
+            JournalEntry::FileDescriptorWriteV1 {
+                fd: 5,
+                offset: 0,
+                data: b"hello=world\n".to_vec().into(),
+                is_64bit: false,
+            },
+            JournalEntry::CloseFileDescriptorV1 { fd: 5 },
+            JournalEntry::CloseThreadV1 {
+                id: 1,
+                exit_code: Some(wasi::ExitCode::from(0u16)),
+            },
+            JournalEntry::SnapshotV1 {
+                when: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                trigger: SnapshotTrigger::Idle,
+            },
+        ]
+    }
+
+    fn write_golden_journal() -> Vec<u8> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let journal = LogFileJournal::from_file(file.as_file().try_clone().unwrap()).unwrap();
+        for entry in golden_entries() {
+            journal.write(entry).unwrap();
+        }
+        journal.flush().unwrap();
+        std::fs::read(file.path()).unwrap()
+    }
+
+    fn read_all(journal: &impl ReadableJournal) -> Vec<JournalEntry<'static>> {
+        let mut ret = Vec::new();
+        while let Some(next) = journal.read().unwrap() {
+            ret.push(next.into_inner().into_owned());
+        }
+        ret
+    }
+
+    #[test]
+    pub fn test_format_header() {
+        let header = journal_format_header(JOURNAL_FORMAT_VERSION);
+        assert_eq!(
+            read_journal_format_header(&header).unwrap(),
+            Some(JOURNAL_FORMAT_VERSION)
+        );
+        assert_eq!(read_journal_format_header(&[0u8; 16]).unwrap(), None);
+        assert!(read_journal_format_header(&journal_format_header(u32::MAX)).is_err());
+    }
+
+    #[test]
+    pub fn test_upgrader_registry() {
+        let record = JournalRawRecord {
+            record_type: 1,
+            data: Cow::Borrowed(&[1, 2, 3]),
+        };
+
+        let registry = JournalUpgraderRegistry::new()
+            .with_upgrader(DuplicatingUpgrader(1))
+            .with_upgrader(DuplicatingUpgrader(2));
+        assert_eq!(registry.upgrade(1, 3, record.clone()).unwrap().len(), 4);
+        assert_eq!(registry.upgrade(2, 3, record.clone()).unwrap().len(), 2);
+        assert_eq!(
+            registry.upgrade(3, 3, record.clone()).unwrap(),
+            vec![record.clone()]
+        );
+
+        // Gaps in the chain of upgraders must be reported
+        assert!(registry.upgrade(0, 3, record).is_err());
+    }
+
+    #[test]
+    pub fn test_read_upgraded_journal() {
+        // Version zero never existed, however it lets us exercise the
+        // upgrade path of the log file journal
+        let mut data = JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES.to_vec();
+        data.extend_from_slice(&journal_format_header(0));
+        data.extend_from_slice(&std::fs::read(GOLDEN_LEGACY).unwrap()[8..]);
+
+        let journal = LogFileJournal::from_buffer(OwnedBuffer::from_bytes(data.clone()));
+        let err = journal.read().unwrap_err();
+        assert!(err.to_string().contains("no upgrader"), "{err}");
+
+        let journal = LogFileJournal::from_buffer_with_upgraders(
+            OwnedBuffer::from_bytes(data),
+            JournalUpgraderRegistry::new().with_upgrader(DuplicatingUpgrader(0)),
+        );
+        let entries = read_all(&journal);
+        let expected = golden_entries()
+            .into_iter()
+            .flat_map(|e| [e.clone(), e])
+            .collect::<Vec<_>>();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    pub fn test_migrate_journal() {
+        let dir = tempfile::TempDir::new().unwrap();
+
+        // Legacy journals are rewritten with the format header
+        let target = dir.path().join("legacy.bin");
+        let from_version =
+            LogFileJournal::migrate(GOLDEN_LEGACY, &target, JournalUpgraderRegistry::builtin())
+                .unwrap();
+        assert_eq!(from_version, JOURNAL_LEGACY_FORMAT_VERSION);
+        assert_eq!(std::fs::read(&target).unwrap(), write_golden_journal());
+        assert!(
+            LogFileJournal::migrate(GOLDEN_LEGACY, &target, JournalUpgraderRegistry::builtin())
+                .is_err(),
+            "existing journals are not overwritten",
+        );
+
+        // Older formats go through the upgraders
+        let source = dir.path().join("v0.bin");
+        let mut data = JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES.to_vec();
+        data.extend_from_slice(&journal_format_header(0));
+        data.extend_from_slice(&std::fs::read(GOLDEN_LEGACY).unwrap()[8..]);
+        std::fs::write(&source, data).unwrap();
+
+        let target = dir.path().join("v0-migrated.bin");
+        let err = LogFileJournal::migrate(&source, &target, JournalUpgraderRegistry::builtin())
+            .unwrap_err();
+        assert!(err.to_string().contains("no upgrader"), "{err}");
+
+        let from_version = LogFileJournal::migrate(
+            &source,
+            &target,
+            JournalUpgraderRegistry::new().with_upgrader(DuplicatingUpgrader(0)),
+        )
+        .unwrap();
+        assert_eq!(from_version, 0);
+
+        let migrated = LogFileJournal::new_readonly(&target).unwrap();
+        assert_eq!(migrated.format_version(), JOURNAL_FORMAT_VERSION);
+        let expected = golden_entries()
+            .into_iter()
+            .flat_map(|e| [e.clone(), e])
+            .collect::<Vec<_>>();
+        assert_eq!(read_all(&migrated), expected);
+    }
+
+    #[test]
+    pub fn test_read_malformed_journal() {
+        // The versioned magic must be followed by a format header
+        let mut data = JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES.to_vec();
+        data.extend_from_slice(&std::fs::read(GOLDEN_LEGACY).unwrap()[8..]);
+        let err = LogFileJournal::from_buffer(OwnedBuffer::from_bytes(data))
+            .read()
+            .unwrap_err();
+        assert!(err.to_string().contains("format header"), "{err}");
+
+        // Records that run past the end of the journal are reported
+        let mut data = JOURNAL_VERSIONED_MAGIC_NUMBER_BYTES.to_vec();
+        data.extend_from_slice(&journal_format_header(0));
+        let legacy = std::fs::read(GOLDEN_LEGACY).unwrap();
+        data.extend_from_slice(&legacy[8..legacy.len() - 4]);
+        let journal = LogFileJournal::from_buffer_with_upgraders(
+            OwnedBuffer::from_bytes(data),
+            JournalUpgraderRegistry::new().with_upgrader(DuplicatingUpgrader(0)),
+        );
+        let err = std::iter::from_fn(|| journal.read().transpose())
+            .find_map(Result::err)
+            .unwrap();
+        assert!(err.to_string().contains("truncated"), "{err}");
+    }
+
+    /// The golden journals guard the on-disk format, if this test fails then
+    /// the format has changed and a new format version (with an upgrader)
+    /// is required. Set `WASMER_UPDATE_GOLDEN_JOURNALS=1` to regenerate the
+    /// fixture of the current version.
+    #[test]
+    pub fn test_golden_journals() {
+        let written = write_golden_journal();
+        if std::env::var("WASMER_UPDATE_GOLDEN_JOURNALS").is_ok() {
+            std::fs::write(GOLDEN_V1, &written).unwrap();
+        }
+
+        let golden = std::fs::read(GOLDEN_V1).unwrap();
+        assert_eq!(written, golden, "the journal format has changed");
+
+        for path in [GOLDEN_LEGACY, GOLDEN_V1] {
+            let journal = LogFileJournal::new_readonly(path).unwrap();
+            assert_eq!(journal.format_version(), 1, "{path}");
+            assert_eq!(read_all(&journal), golden_entries(), "{path}");
+        }
+    }
+}
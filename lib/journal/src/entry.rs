@@ -3,10 +3,10 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::net::{Shutdown, SocketAddr};
 use std::time::{Duration, SystemTime};
 use std::{borrow::Cow, ops::Range};
-use virtual_net::{IpCidr, StreamSecurity};
+use virtual_net::{replay::NetEvent, IpCidr, StreamSecurity};
 use wasmer_wasix_types::wasi::{
     Addressfamily, Advice, EpollCtl, EpollEventCtl, EventFdFlags, ExitCode, Fdflags, Fdflagsext,
-    FileDelta, Filesize, Fstflags, LookupFlags, Oflags, Rights, SiFlags, Snapshot0Clockid,
+    FileDelta, Filesize, Flockop, Fstflags, LookupFlags, Oflags, Rights, SiFlags, Snapshot0Clockid,
     SockProto, Sockoption, Socktype, Timestamp, Tty, Whence,
 };
 use wasmer_wasix_types::wasix::{ThreadStartType, WasiMemoryLayout};
@@ -91,6 +91,24 @@ pub enum JournalEntry<'a> {
         #[serde(with = "base64")]
         compressed_data: Cow<'a, [u8]>,
     },
+    /// Stores a distinct page of memory (identified by the hash of its
+    /// contents) exactly once so that later memory updates can reference it
+    MemoryPageV1 {
+        hash: [u8; 32],
+        #[debug(ignore)]
+        #[serde(with = "base64")]
+        compressed_data: Cow<'a, [u8]>,
+    },
+    /// Updates a region of memory using pages that were previously stored
+    /// in the journal, the region is split on `page_size` boundaries and
+    /// each page is represented by a 32 byte hash in `page_hashes`
+    UpdateMemoryRegionPagesV1 {
+        region: Range<u64>,
+        page_size: u64,
+        #[debug(ignore)]
+        #[serde(with = "base64")]
+        page_hashes: Cow<'a, [u8]>,
+    },
     ProcessExitV1 {
         exit_code: Option<ExitCode>,
     },
@@ -201,6 +219,16 @@ pub enum JournalEntry<'a> {
         fd: Fd,
         flags: Fdflags,
     },
+    FileDescriptorLockV1 {
+        fd: Fd,
+        op: Flockop,
+    },
+    FileDescriptorLockRangeV1 {
+        fd: Fd,
+        op: Flockop,
+        start: u64,
+        len: u64,
+    },
     FileDescriptorSetRightsV1 {
         fd: Fd,
         fs_rights_base: Rights,
@@ -255,6 +283,22 @@ pub enum JournalEntry<'a> {
         fd: Fd,
         event: Option<EpollEventCtl>,
     },
+    WatchCreateV1 {
+        fd: Fd,
+        flags: Fdflags,
+    },
+    WatchAddV1 {
+        fd: Fd,
+        dirfd: Fd,
+        flags: LookupFlags,
+        path: Cow<'a, str>,
+        mask: u32,
+        wd: i32,
+    },
+    WatchRemoveV1 {
+        fd: Fd,
+        wd: i32,
+    },
     TtySetV1 {
         tty: Tty,
         line_feeds: bool,
@@ -407,6 +451,22 @@ impl JournalEntry<'_> {
                 region,
                 compressed_data: compressed_data.into_owned().into(),
             },
+            Self::MemoryPageV1 {
+                hash,
+                compressed_data,
+            } => JournalEntry::MemoryPageV1 {
+                hash,
+                compressed_data: compressed_data.into_owned().into(),
+            },
+            Self::UpdateMemoryRegionPagesV1 {
+                region,
+                page_size,
+                page_hashes,
+            } => JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_size,
+                page_hashes: page_hashes.into_owned().into(),
+            },
             Self::ProcessExitV1 { exit_code } => JournalEntry::ProcessExitV1 { exit_code },
             Self::SetThreadV1 {
                 id,
@@ -540,6 +600,10 @@ impl JournalEntry<'_> {
             Self::FileDescriptorSetFdFlagsV1 { fd, flags } => {
                 JournalEntry::FileDescriptorSetFdFlagsV1 { fd, flags }
             }
+            Self::FileDescriptorLockV1 { fd, op } => JournalEntry::FileDescriptorLockV1 { fd, op },
+            Self::FileDescriptorLockRangeV1 { fd, op, start, len } => {
+                JournalEntry::FileDescriptorLockRangeV1 { fd, op, start, len }
+            }
             Self::FileDescriptorSetFlagsV1 { fd, flags } => {
                 JournalEntry::FileDescriptorSetFlagsV1 { fd, flags }
             }
@@ -610,6 +674,23 @@ impl JournalEntry<'_> {
                 path: path.into_owned().into(),
             },
             Self::EpollCreateV1 { fd } => JournalEntry::EpollCreateV1 { fd },
+            Self::WatchCreateV1 { fd, flags } => JournalEntry::WatchCreateV1 { fd, flags },
+            Self::WatchAddV1 {
+                fd,
+                dirfd,
+                flags,
+                path,
+                mask,
+                wd,
+            } => JournalEntry::WatchAddV1 {
+                fd,
+                dirfd,
+                flags,
+                path: path.into_owned().into(),
+                mask,
+                wd,
+            },
+            Self::WatchRemoveV1 { fd, wd } => JournalEntry::WatchRemoveV1 { fd, wd },
             Self::EpollCtlV1 {
                 epfd,
                 op,
@@ -783,6 +864,12 @@ impl JournalEntry<'_> {
             JournalEntry::UpdateMemoryRegionV1 {
                 compressed_data, ..
             } => base_size + compressed_data.len(),
+            JournalEntry::MemoryPageV1 {
+                compressed_data, ..
+            } => base_size + compressed_data.len(),
+            JournalEntry::UpdateMemoryRegionPagesV1 { page_hashes, .. } => {
+                base_size + page_hashes.len()
+            }
             JournalEntry::ProcessExitV1 { .. } => base_size,
             JournalEntry::SetThreadV1 {
                 call_stack,
@@ -805,6 +892,8 @@ impl JournalEntry<'_> {
             JournalEntry::PathSetTimesV1 { path, .. } => base_size + path.len(),
             JournalEntry::FileDescriptorSetTimesV1 { .. } => base_size,
             JournalEntry::FileDescriptorSetFdFlagsV1 { .. } => base_size,
+            JournalEntry::FileDescriptorLockV1 { .. } => base_size,
+            JournalEntry::FileDescriptorLockRangeV1 { .. } => base_size,
             JournalEntry::FileDescriptorSetFlagsV1 { .. } => base_size,
             JournalEntry::FileDescriptorSetRightsV1 { .. } => base_size,
             JournalEntry::FileDescriptorSetSizeV1 { .. } => base_size,
@@ -823,6 +912,9 @@ impl JournalEntry<'_> {
             JournalEntry::ChangeDirectoryV1 { path } => base_size + path.len(),
             JournalEntry::EpollCreateV1 { .. } => base_size,
             JournalEntry::EpollCtlV1 { .. } => base_size,
+            JournalEntry::WatchCreateV1 { .. } => base_size,
+            JournalEntry::WatchAddV1 { path, .. } => base_size + path.len(),
+            JournalEntry::WatchRemoveV1 { .. } => base_size,
             JournalEntry::TtySetV1 { .. } => base_size,
             JournalEntry::CreatePipeV1 { .. } => base_size,
             JournalEntry::CreateEventV1 { .. } => base_size,
@@ -859,3 +951,67 @@ impl JournalEntry<'_> {
         }
     }
 }
+
+impl<'a> JournalEntry<'a> {
+    /// Converts an event of a network recording into the journal entry that
+    /// carries the same information, with the connection number in place of
+    /// the file descriptor. Only connects and sends (of payload and of
+    /// datagrams to a peer) have such an entry.
+    pub fn from_net_event(event: &'a NetEvent) -> Option<Self> {
+        match event {
+            NetEvent::Connected {
+                conn,
+                local_addr,
+                peer_addr,
+            } => Some(Self::SocketConnectedV1 {
+                fd: Fd::try_from(*conn).ok()?,
+                local_addr: *local_addr,
+                peer_addr: *peer_addr,
+            }),
+            NetEvent::Send { conn, data } => Some(Self::SocketSendV1 {
+                fd: Fd::try_from(*conn).ok()?,
+                data: Cow::Borrowed(&data[..]),
+                flags: 0,
+                is_64bit: false,
+            }),
+            NetEvent::SendTo {
+                socket,
+                peer: Some(peer),
+                data,
+            } => Some(Self::SocketSendToV1 {
+                fd: Fd::try_from(*socket).ok()?,
+                data: Cow::Borrowed(&data[..]),
+                flags: 0,
+                addr: *peer,
+                is_64bit: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Converts a journal entry into an event of a network recording, the
+    /// reverse of [`JournalEntry::from_net_event`]
+    pub fn to_net_event(&self) -> Option<NetEvent> {
+        match self {
+            Self::SocketConnectedV1 {
+                fd,
+                local_addr,
+                peer_addr,
+            } => Some(NetEvent::Connected {
+                conn: *fd as u64,
+                local_addr: *local_addr,
+                peer_addr: *peer_addr,
+            }),
+            Self::SocketSendV1 { fd, data, .. } => Some(NetEvent::Send {
+                conn: *fd as u64,
+                data: data.to_vec(),
+            }),
+            Self::SocketSendToV1 { fd, data, addr, .. } => Some(NetEvent::SendTo {
+                socket: *fd as u64,
+                peer: Some(*addr),
+                data: data.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
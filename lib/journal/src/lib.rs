@@ -13,7 +13,7 @@ use serde::{Deserialize, Serialize};
 use std::{ops::Deref, str::FromStr};
 
 /// The results of an operation to write a log entry to the log
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LogWriteResult {
     // Start of the actual entry
     pub record_start: u64,
@@ -0,0 +1,114 @@
+//! Compares the throughput of the `host_fs` backends: `tokio::fs` (which
+//! hands every operation to a blocking thread pool) and, when built with
+//! the `io-uring` feature on Linux, `io_uring`.
+//!
+//! Run with `cargo bench -p virtual-fs --features io-uring --bench host_fs`.
+
+use std::{
+    io::SeekFrom,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use virtual_fs::{host_fs, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, FileSystem};
+
+const FILE_SIZE: usize = 64 * 1024 * 1024;
+const BLOCK_SIZE: usize = 64 * 1024;
+const RANDOM_READ_SIZE: usize = 4096;
+const RANDOM_READS: usize = 10_000;
+const ROUNDS: usize = 3;
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let backends: &[(&str, bool)] = if cfg!(all(target_os = "linux", feature = "io-uring")) {
+        &[("tokio::fs", false), ("io_uring", true)]
+    } else {
+        &[("tokio::fs", false)]
+    };
+
+    for (name, io_uring) in backends {
+        let fs = host_fs::FileSystem::new(runtime.handle().clone(), dir.path()).unwrap();
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let fs = fs.with_io_uring(*io_uring);
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        let _ = io_uring;
+
+        let path = Path::new("/bench.bin");
+        let write = best_of(|| runtime.block_on(sequential_write(&fs, path)));
+        let read = best_of(|| runtime.block_on(sequential_read(&fs, path)));
+        let random = best_of(|| runtime.block_on(random_reads(&fs, path)));
+
+        let mib = FILE_SIZE as f64 / (1024.0 * 1024.0);
+        println!("{name}:");
+        println!(
+            "  sequential write: {:>10.1} MiB/s",
+            mib / write.as_secs_f64()
+        );
+        println!(
+            "  sequential read:  {:>10.1} MiB/s",
+            mib / read.as_secs_f64()
+        );
+        println!(
+            "  random 4K reads:  {:>10.0} reads/s",
+            RANDOM_READS as f64 / random.as_secs_f64()
+        );
+    }
+}
+
+fn best_of(mut f: impl FnMut() -> Duration) -> Duration {
+    (0..ROUNDS).map(|_| f()).min().unwrap()
+}
+
+async fn sequential_write(fs: &host_fs::FileSystem, path: &Path) -> Duration {
+    let block = vec![0xa5u8; BLOCK_SIZE];
+    let start = Instant::now();
+    let mut file = fs
+        .new_open_options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .unwrap();
+    for _ in 0..FILE_SIZE / BLOCK_SIZE {
+        file.write_all(&block).await.unwrap();
+    }
+    file.flush().await.unwrap();
+    start.elapsed()
+}
+
+async fn sequential_read(fs: &host_fs::FileSystem, path: &Path) -> Duration {
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let start = Instant::now();
+    let mut file = fs.new_open_options().read(true).open(path).unwrap();
+    let mut total = 0;
+    loop {
+        match file.read(&mut block).await.unwrap() {
+            0 => break,
+            read => total += read,
+        }
+    }
+    assert_eq!(total, FILE_SIZE);
+    start.elapsed()
+}
+
+async fn random_reads(fs: &host_fs::FileSystem, path: &Path) -> Duration {
+    let mut block = vec![0u8; RANDOM_READ_SIZE];
+    let blocks = (FILE_SIZE / RANDOM_READ_SIZE) as u64;
+    // A small LCG keeps the offsets the same between the backends
+    let mut state = 0x2545f4914f6cdd1du64;
+    let start = Instant::now();
+    let mut file = fs.new_open_options().read(true).open(path).unwrap();
+    for _ in 0..RANDOM_READS {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let offset = (state >> 33) % blocks * RANDOM_READ_SIZE as u64;
+        file.seek(SeekFrom::Start(offset)).await.unwrap();
+        file.read_exact(&mut block).await.unwrap();
+    }
+    start.elapsed()
+}
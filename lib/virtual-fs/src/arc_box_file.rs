@@ -111,6 +111,43 @@ impl VirtualFile for ArcBoxFile {
         let mut inner = self.inner.lock().unwrap();
         inner.set_len(new_size)
     }
+    fn try_lock(
+        &mut self,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> crate::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.try_lock(owner, lock, range)
+    }
+    fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> Poll<crate::Result<()>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.poll_lock(cx, owner, lock, range)
+    }
+    fn test_lock(
+        &mut self,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> crate::Result<Option<(crate::FileLock, crate::LockRange)>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.test_lock(owner, lock, range)
+    }
+    fn unlock_all(&mut self, owner: crate::LockOwner) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.unlock_all(owner)
+    }
+    fn poll_sync(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut guard = self.inner.lock().unwrap();
+        let file = Pin::new(guard.as_mut());
+        file.poll_sync(cx)
+    }
     fn unlink(&mut self) -> crate::Result<()> {
         let mut inner = self.inner.lock().unwrap();
         inner.unlink()
@@ -0,0 +1,853 @@
+//! A read-only file system backed by a `.tar`, `.tar.gz` or `.zip` archive.
+//!
+//! The archive is never unpacked, instead an index of its entries is built
+//! the first time the file system is accessed. Files inside a plain tar
+//! archive (and files stored without compression inside a zip archive) are
+//! served straight from the archive's buffer, compressed zip entries are
+//! decompressed when they are opened.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    ffi::OsString,
+    io::{Cursor, Read},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use futures::future::BoxFuture;
+use shared_buffer::OwnedBuffer;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::{
+    DirEntry, FileOpener, FileSystem, FileType, FsError, Metadata, OpenOptionsConfig, Permissions,
+    ReadDir, Result, StaticFile, VirtualFile,
+};
+
+/// Maximum number of symbolic links that are followed while resolving a
+/// single path (the same limit Linux uses)
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// The kinds of archives an [`ArchiveFileSystem`] can be created from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Detects the format of an archive from its first few bytes, anything
+    /// that is neither a zip nor a gzip stream is assumed to be a tar archive
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+            ArchiveFormat::Zip
+        } else if bytes.starts_with(&[0x1f, 0x8b]) {
+            ArchiveFormat::TarGz
+        } else {
+            ArchiveFormat::Tar
+        }
+    }
+}
+
+/// A read-only [`FileSystem`] that exposes the contents of an archive.
+///
+/// The index of the archive is built lazily, so creating the file system is
+/// cheap and errors in the archive are only reported once it is accessed.
+#[derive(Debug, Clone)]
+pub struct ArchiveFileSystem {
+    inner: Arc<ArchiveInner>,
+}
+
+#[derive(Debug)]
+struct ArchiveInner {
+    format: ArchiveFormat,
+    bytes: OwnedBuffer,
+    index: OnceLock<Result<Index>>,
+}
+
+impl ArchiveFileSystem {
+    /// Creates a file system from the raw bytes of an archive, the format
+    /// is detected from the contents.
+    pub fn new(bytes: impl Into<OwnedBuffer>) -> Self {
+        let bytes = bytes.into();
+        let format = ArchiveFormat::detect(&bytes);
+        Self::with_format(bytes, format)
+    }
+
+    /// Creates a file system from the raw bytes of an archive of a known
+    /// format.
+    pub fn with_format(bytes: impl Into<OwnedBuffer>, format: ArchiveFormat) -> Self {
+        ArchiveFileSystem {
+            inner: Arc::new(ArchiveInner {
+                format,
+                bytes: bytes.into(),
+                index: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Memory maps an archive from the host file system.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)?;
+        let bytes = OwnedBuffer::from_file(&file).map_err(|e| {
+            tracing::debug!(
+                error = &e as &dyn std::error::Error,
+                path=%path.display(),
+                "Unable to map the archive into memory",
+            );
+            FsError::IOError
+        })?;
+        Ok(Self::new(bytes))
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.inner.format
+    }
+
+    fn index(&self) -> Result<&Index> {
+        self.inner
+            .index
+            .get_or_init(|| {
+                let index = match self.inner.format {
+                    ArchiveFormat::Tar => Index::from_tar(self.inner.bytes.clone()),
+                    ArchiveFormat::TarGz => {
+                        let mut decoder = flate2::read::MultiGzDecoder::new(&self.inner.bytes[..]);
+                        let mut tar = Vec::new();
+                        decoder.read_to_end(&mut tar).map_err(invalid_archive)?;
+                        Index::from_tar(tar.into())
+                    }
+                    ArchiveFormat::Zip => Index::from_zip(self.inner.bytes.clone()),
+                };
+                if let Err(e) = &index {
+                    tracing::warn!(format = ?self.inner.format, error = %e, "Unable to index the archive");
+                }
+                index
+            })
+            .as_ref()
+            .map_err(|e| *e)
+    }
+}
+
+impl FileSystem for ArchiveFileSystem {
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        let (_, entry) = self.index()?.lookup(path, false)?;
+        match &entry.kind {
+            EntryKind::Symlink(target) => Ok(target.clone()),
+            _ => Err(FsError::InvalidInput),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let index = self.index()?;
+        let (resolved, entry) = index.lookup(path, true)?;
+        let EntryKind::Dir(children) = &entry.kind else {
+            return Err(FsError::BaseNotDirectory);
+        };
+
+        let entries = children
+            .iter()
+            .map(|name| DirEntry {
+                path: path.join(name),
+                metadata: index
+                    .entries
+                    .get(&resolved.join(name))
+                    .map(|child| child.metadata.clone())
+                    .ok_or(FsError::EntryNotFound),
+            })
+            .collect();
+
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        // the directory shouldn't exist yet
+        if self.symlink_metadata(path).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+        match self.metadata(parent) {
+            // Creating it would normally be fine, but archives are read-only
+            Ok(parent_meta) if parent_meta.is_dir() => Err(FsError::PermissionDenied),
+            Ok(_) | Err(FsError::EntryNotFound) => Err(FsError::BaseNotDirectory),
+            Err(other) => Err(other),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if !self.symlink_metadata(path)?.is_dir() {
+            return Err(FsError::BaseNotDirectory);
+        }
+
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename<'a>(&'a self, from: &'a Path, _to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let _ = self.symlink_metadata(from)?;
+            Err(FsError::PermissionDenied)
+        })
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let (_, entry) = self.index()?.lookup(path, true)?;
+        Ok(entry.metadata.clone())
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        let (_, entry) = self.index()?.lookup(path, false)?;
+        Ok(entry.metadata.clone())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.symlink_metadata(path)?.is_dir() {
+            return Err(FsError::NotAFile);
+        }
+
+        Err(FsError::PermissionDenied)
+    }
+
+    fn symlink(&self, _original: &Path, link: &Path) -> Result<()> {
+        match self.symlink_metadata(link) {
+            Ok(_) => Err(FsError::AlreadyExists),
+            Err(FsError::EntryNotFound) => Err(FsError::PermissionDenied),
+            Err(other) => Err(other),
+        }
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let _ = self.symlink_metadata(original)?;
+        self.symlink(original, link)
+    }
+
+    fn set_permissions(&self, path: &Path, _permissions: Permissions) -> Result<()> {
+        let _ = self.metadata(path)?;
+        Err(FsError::PermissionDenied)
+    }
+
+    fn new_open_options(&self) -> crate::OpenOptions {
+        crate::OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        _name: String,
+        _path: &Path,
+        _fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+}
+
+impl FileOpener for ArchiveFileSystem {
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let index = self.index()?;
+
+        let data = match index.lookup(path, true) {
+            Ok((_, entry)) => match &entry.kind {
+                EntryKind::File(_) if conf.create_new() => return Err(FsError::AlreadyExists),
+                EntryKind::File(data) => data,
+                _ => return Err(FsError::NotAFile),
+            },
+            // The file would normally be created, but archives are read-only
+            Err(FsError::EntryNotFound) if conf.create() || conf.create_new() => {
+                return Err(FsError::PermissionDenied)
+            }
+            Err(e) => return Err(e),
+        };
+
+        if conf.would_mutate() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let contents = match data {
+            FileData::Range(range) => index.data.slice(range.clone()),
+            FileData::Zip(file_number) => {
+                // The archive is cloned so concurrent opens don't contend on
+                // the reader (the central directory itself is shared)
+                let mut archive = index.zip.clone().ok_or(FsError::UnknownError)?;
+                let mut file = archive.by_index(*file_number).map_err(|e| {
+                    tracing::debug!(
+                        error = &e as &dyn std::error::Error,
+                        "Unable to open a zip entry"
+                    );
+                    FsError::IOError
+                })?;
+                let mut contents = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut contents)?;
+                contents.into()
+            }
+        };
+
+        Ok(Box::new(StaticFile::new(contents)))
+    }
+}
+
+/// All the entries of an archive, keyed by their absolute path.
+#[derive(Debug)]
+struct Index {
+    entries: BTreeMap<PathBuf, Entry>,
+    /// Buffer that [`FileData::Range`] points into (for compressed tar
+    /// archives this is the decompressed tarball)
+    data: OwnedBuffer,
+    zip: Option<ZipArchive<Cursor<OwnedBuffer>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    metadata: Metadata,
+    kind: EntryKind,
+}
+
+#[derive(Debug, Clone)]
+enum EntryKind {
+    Dir(BTreeSet<OsString>),
+    File(FileData),
+    Symlink(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+enum FileData {
+    Range(Range<usize>),
+    Zip(usize),
+}
+
+impl Index {
+    fn new(data: OwnedBuffer) -> Self {
+        let mut entries = BTreeMap::new();
+        entries.insert(PathBuf::from("/"), Entry::implicit_dir());
+        Index {
+            entries,
+            data,
+            zip: None,
+        }
+    }
+
+    fn from_tar(data: OwnedBuffer) -> Result<Self> {
+        let mut index = Index::new(data.clone());
+        let mut archive = tar::Archive::new(&data[..]);
+
+        for entry in archive.entries().map_err(invalid_archive)? {
+            let entry = entry.map_err(invalid_archive)?;
+            let header = entry.header();
+            let path = entry.path().map_err(invalid_archive)?.into_owned();
+            let link_name = entry
+                .link_name()
+                .map_err(invalid_archive)?
+                .map(|link| link.into_owned());
+
+            let permissions = Permissions::new(
+                header.mode().unwrap_or(0o644),
+                header.uid().unwrap_or(0) as u32,
+                header.gid().unwrap_or(0) as u32,
+            );
+            let modified = header.mtime().unwrap_or(0).saturating_mul(1_000_000_000);
+
+            let kind = match header.entry_type() {
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let start = entry.raw_file_position() as usize;
+                    let end = start
+                        .checked_add(entry.size() as usize)
+                        .filter(|end| *end <= data.len())
+                        .ok_or(FsError::InvalidData)?;
+                    EntryKind::File(FileData::Range(start..end))
+                }
+                tar::EntryType::Directory => EntryKind::Dir(BTreeSet::new()),
+                tar::EntryType::Symlink => match link_name {
+                    Some(target) => EntryKind::Symlink(target),
+                    None => return Err(FsError::InvalidData),
+                },
+                tar::EntryType::Link => {
+                    // Hard links refer to an entry that appeared earlier in
+                    // the archive, so they simply share its data
+                    let target = link_name.ok_or(FsError::InvalidData)?;
+                    match index.entries.get(&normalize(&target)) {
+                        Some(Entry {
+                            kind: kind @ EntryKind::File(_),
+                            metadata,
+                        }) => {
+                            let entry = Entry {
+                                metadata: metadata.clone(),
+                                kind: kind.clone(),
+                            };
+                            index.insert(&path, entry);
+                        }
+                        _ => tracing::debug!(
+                            path = %path.display(),
+                            target = %target.display(),
+                            "Skipping a hard link to an unknown file",
+                        ),
+                    }
+                    continue;
+                }
+                other => {
+                    tracing::debug!(
+                        path = %path.display(),
+                        entry_type = ?other,
+                        "Skipping an unsupported tar entry",
+                    );
+                    continue;
+                }
+            };
+
+            let len = match &kind {
+                EntryKind::File(FileData::Range(range)) => range.len() as u64,
+                _ => 0,
+            };
+            index.insert(
+                &path,
+                Entry {
+                    metadata: Metadata {
+                        ft: kind.file_type(),
+                        modified,
+                        len,
+                        permissions: Some(permissions),
+                        ..Default::default()
+                    },
+                    kind,
+                },
+            );
+        }
+
+        Ok(index)
+    }
+
+    fn from_zip(data: OwnedBuffer) -> Result<Self> {
+        let mut index = Index::new(data.clone());
+        let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| {
+            tracing::debug!(error = &e as &dyn std::error::Error, "Invalid zip archive");
+            FsError::InvalidData
+        })?;
+
+        for file_number in 0..archive.len() {
+            let mut file = archive
+                .by_index(file_number)
+                .map_err(|_| FsError::InvalidData)?;
+
+            // Skips entries that would escape the archive (e.g. `../foo`)
+            let Some(path) = file.enclosed_name() else {
+                tracing::debug!(
+                    name = file.name(),
+                    "Skipping a zip entry with an unsafe path"
+                );
+                continue;
+            };
+
+            let kind = if file.is_dir() {
+                EntryKind::Dir(BTreeSet::new())
+            } else if file.is_symlink() {
+                // The target of a symbolic link is stored as its contents
+                let mut target = String::new();
+                file.read_to_string(&mut target)?;
+                EntryKind::Symlink(PathBuf::from(target))
+            } else if file.compression() == CompressionMethod::Stored {
+                // The central directory may claim more data than the archive has
+                let start = file.data_start() as usize;
+                let end = start
+                    .checked_add(file.size() as usize)
+                    .filter(|end| *end <= index.data.len())
+                    .ok_or(FsError::InvalidData)?;
+                EntryKind::File(FileData::Range(start..end))
+            } else {
+                EntryKind::File(FileData::Zip(file_number))
+            };
+
+            let metadata = Metadata {
+                ft: kind.file_type(),
+                modified: file.last_modified().map(zip_timestamp).unwrap_or(0),
+                len: if file.is_file() { file.size() } else { 0 },
+                permissions: file.unix_mode().map(|mode| Permissions::new(mode, 0, 0)),
+                ..Default::default()
+            };
+
+            index.insert(&path, Entry { metadata, kind });
+        }
+
+        index.zip = Some(archive);
+        Ok(index)
+    }
+
+    /// Adds an entry to the index, along with any of its parent directories
+    /// that the archive doesn't list explicitly.
+    fn insert(&mut self, path: &Path, mut entry: Entry) {
+        let path = normalize(path);
+        let Some(name) = path.file_name().map(|name| name.to_os_string()) else {
+            // Only the root directory has no name, it is always present
+            if let EntryKind::Dir(_) = entry.kind {
+                self.entries.get_mut(&path).unwrap().metadata = entry.metadata;
+            }
+            return;
+        };
+
+        let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+        match self.entries.get_mut(parent) {
+            Some(Entry {
+                kind: EntryKind::Dir(children),
+                ..
+            }) => {
+                children.insert(name);
+            }
+            _ => {
+                let mut dir = Entry::implicit_dir();
+                if let EntryKind::Dir(children) = &mut dir.kind {
+                    children.insert(name);
+                }
+                self.insert(parent, dir);
+            }
+        }
+
+        // Directories can be listed after their contents
+        if let (
+            EntryKind::Dir(children),
+            Some(Entry {
+                kind: EntryKind::Dir(existing),
+                ..
+            }),
+        ) = (&mut entry.kind, self.entries.get(&path))
+        {
+            children.extend(existing.iter().cloned());
+        }
+
+        self.entries.insert(path, entry);
+    }
+
+    /// Resolves a path to its entry, symbolic links are followed for all the
+    /// intermediate components and (when `follow` is set) the last one.
+    fn lookup(&self, path: &Path, follow: bool) -> Result<(PathBuf, &Entry)> {
+        let mut pending: VecDeque<OsString> = components(path).collect();
+        let mut current = PathBuf::from("/");
+        let mut hops = 0;
+
+        while let Some(name) = pending.pop_front() {
+            if name == ".." {
+                current.pop();
+                continue;
+            }
+
+            let next = current.join(&name);
+            let entry = self.entries.get(&next).ok_or(FsError::EntryNotFound)?;
+            match &entry.kind {
+                EntryKind::Symlink(target) if follow || !pending.is_empty() => {
+                    hops += 1;
+                    if hops > MAX_SYMLINK_HOPS {
+                        return Err(FsError::InvalidInput);
+                    }
+                    if target.has_root() {
+                        current = PathBuf::from("/");
+                    }
+                    for component in components(target).collect::<Vec<_>>().into_iter().rev() {
+                        pending.push_front(component);
+                    }
+                }
+                EntryKind::File(_) if !pending.is_empty() => {
+                    return Err(FsError::BaseNotDirectory);
+                }
+                _ => current = next,
+            }
+        }
+
+        let entry = &self.entries[&current];
+        Ok((current, entry))
+    }
+}
+
+impl Entry {
+    /// A directory that only exists because the archive contains entries
+    /// inside of it
+    fn implicit_dir() -> Self {
+        Entry {
+            metadata: Metadata {
+                ft: FileType::new_dir(),
+                permissions: Some(Permissions::new_dir()),
+                ..Default::default()
+            },
+            kind: EntryKind::Dir(BTreeSet::new()),
+        }
+    }
+}
+
+impl EntryKind {
+    fn file_type(&self) -> FileType {
+        match self {
+            EntryKind::Dir(_) => FileType::new_dir(),
+            EntryKind::File(_) => FileType::new_file(),
+            EntryKind::Symlink(_) => FileType {
+                symlink: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The names that make up a path, with `.` and the root removed
+fn components(path: &Path) -> impl Iterator<Item = OsString> + '_ {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name.to_os_string()),
+        Component::ParentDir => Some(OsString::from("..")),
+        Component::RootDir | Component::CurDir | Component::Prefix(_) => None,
+    })
+}
+
+/// Lexically normalizes a path inside the archive into an absolute path
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for name in components(path) {
+        if name == ".." {
+            normalized.pop();
+        } else {
+            normalized.push(name);
+        }
+    }
+    normalized
+}
+
+/// Converts the MS-DOS timestamp of a zip entry into nanoseconds since the
+/// epoch (zip timestamps don't have a time zone so UTC is assumed)
+fn zip_timestamp(timestamp: zip::DateTime) -> u64 {
+    // Days since the epoch of a date in the proleptic Gregorian calendar
+    let (year, month, day) = (
+        timestamp.year() as i64,
+        timestamp.month() as i64,
+        timestamp.day() as i64,
+    );
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400
+        + timestamp.hour() as i64 * 3_600
+        + timestamp.minute() as i64 * 60
+        + timestamp.second() as i64;
+    (seconds.max(0) as u64).saturating_mul(1_000_000_000)
+}
+
+fn invalid_archive(e: std::io::Error) -> FsError {
+    tracing::debug!(error = &e as &dyn std::error::Error, "Invalid tar archive");
+    FsError::InvalidData
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    fn tar_archive() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o750);
+        header.set_uid(1000);
+        header.set_gid(1000);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "etc/", std::io::empty())
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o640);
+        header.set_mtime(1_700_000_000);
+        header.set_size(5);
+        builder
+            .append_data(&mut header, "etc/hostname", &b"wasix"[..])
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(12);
+        builder
+            .append_data(&mut header, "./usr/share/doc/README", &b"Hello, World"[..])
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "docs", "usr/share/doc")
+            .unwrap();
+
+        builder.into_inner().unwrap()
+    }
+
+    fn zip_archive() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+        let stored = zip::write::SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .unix_permissions(0o600);
+        writer.start_file("stored.txt", stored).unwrap();
+        writer.write_all(b"stored").unwrap();
+
+        let deflated = zip::write::SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated);
+        writer.add_directory("nested/", deflated).unwrap();
+        writer.start_file("nested/deflated.txt", deflated).unwrap();
+        writer.write_all(&[b'a'; 1024]).unwrap();
+
+        writer.add_symlink("link", "nested", deflated).unwrap();
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    async fn read_to_end(fs: &ArchiveFileSystem, path: &str) -> Vec<u8> {
+        let mut file = fs.new_open_options().read(true).open(path).unwrap();
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await.unwrap();
+        contents
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(ArchiveFormat::detect(&tar_archive()), ArchiveFormat::Tar);
+        assert_eq!(ArchiveFormat::detect(&zip_archive()), ArchiveFormat::Zip);
+        assert_eq!(
+            ArchiveFormat::detect(&[0x1f, 0x8b, 8]),
+            ArchiveFormat::TarGz
+        );
+    }
+
+    #[tokio::test]
+    async fn tar_archive_contents() {
+        let fs = ArchiveFileSystem::new(tar_archive());
+
+        let etc = fs.metadata(Path::new("/etc")).unwrap();
+        assert!(etc.is_dir());
+        assert_eq!(etc.permissions, Some(Permissions::new(0o750, 1000, 1000)));
+
+        let hostname = fs.metadata(Path::new("/etc/hostname")).unwrap();
+        assert!(hostname.is_file());
+        assert_eq!(hostname.len(), 5);
+        assert_eq!(hostname.modified(), 1_700_000_000_000_000_000);
+        assert_eq!(read_to_end(&fs, "/etc/hostname").await, b"wasix");
+
+        // Parent directories that aren't in the archive are implied
+        assert!(fs.metadata(Path::new("/usr/share")).unwrap().is_dir());
+        let names: Vec<_> = fs
+            .read_dir(Path::new("/"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["docs", "etc", "usr"]);
+
+        // Symbolic links are followed inside the archive
+        assert_eq!(
+            fs.readlink(Path::new("/docs")).unwrap(),
+            Path::new("usr/share/doc")
+        );
+        assert!(fs
+            .symlink_metadata(Path::new("/docs"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(read_to_end(&fs, "/docs/README").await, b"Hello, World");
+
+        assert_eq!(
+            fs.metadata(Path::new("/missing")),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn tar_gz_archive_contents() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&tar_archive()).unwrap();
+        let fs = ArchiveFileSystem::new(encoder.finish().unwrap());
+
+        assert_eq!(fs.format(), ArchiveFormat::TarGz);
+        assert_eq!(read_to_end(&fs, "/etc/hostname").await, b"wasix");
+        assert_eq!(read_to_end(&fs, "/docs/README").await, b"Hello, World");
+    }
+
+    #[tokio::test]
+    async fn zip_archive_contents() {
+        let fs = ArchiveFileSystem::new(zip_archive());
+
+        let stored = fs.metadata(Path::new("/stored.txt")).unwrap();
+        assert_eq!(stored.len(), 6);
+        assert_eq!(stored.permissions.map(|p| p.mode), Some(0o600));
+        assert_eq!(read_to_end(&fs, "/stored.txt").await, b"stored");
+
+        assert!(fs.metadata(Path::new("/nested")).unwrap().is_dir());
+        assert_eq!(
+            read_to_end(&fs, "/nested/deflated.txt").await,
+            vec![b'a'; 1024]
+        );
+        assert_eq!(
+            read_to_end(&fs, "/link/deflated.txt").await,
+            vec![b'a'; 1024]
+        );
+    }
+
+    #[test]
+    fn archives_are_read_only() {
+        let fs = ArchiveFileSystem::new(tar_archive());
+
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .open("/etc/hostname")
+                .map(|_| ()),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open("/etc/new")
+                .map(|_| ()),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.create_dir(Path::new("/etc/new")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.remove_file(Path::new("/etc/hostname")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.remove_dir(Path::new("/missing")),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[test]
+    fn invalid_archives_fail_on_access() {
+        let fs = ArchiveFileSystem::with_format(&b"not a zip file"[..], ArchiveFormat::Zip);
+
+        assert_eq!(fs.metadata(Path::new("/")), Err(FsError::InvalidData));
+    }
+
+    #[test]
+    fn overstated_stored_zip_entries_are_invalid() {
+        let mut archive = zip_archive();
+
+        // Claims that the first (stored) entry is larger than the archive
+        let header = archive
+            .windows(4)
+            .position(|w| w == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        archive[header + 20..header + 28].copy_from_slice(&[0x00, 0x10, 0, 0, 0x00, 0x10, 0, 0]);
+
+        let fs = ArchiveFileSystem::new(archive);
+        assert_eq!(
+            fs.new_open_options()
+                .read(true)
+                .open("/stored.txt")
+                .map(|_| ()),
+            Err(FsError::InvalidData)
+        );
+    }
+}
@@ -87,6 +87,11 @@ impl VirtualFile for BufferFile {
     fn unlink(&mut self) -> crate::Result<()> {
         Ok(())
     }
+    fn duplicate(&self) -> Option<Box<dyn VirtualFile + Send + Sync>> {
+        Some(Box::new(Self {
+            data: self.data.clone(),
+        }))
+    }
     fn poll_read_ready(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
         let cur = self.data.stream_position().unwrap_or_default();
         let len = self.data.seek(SeekFrom::End(0)).unwrap_or_default();
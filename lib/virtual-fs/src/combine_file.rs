@@ -18,6 +18,10 @@ impl CombineFile {
 }
 
 impl VirtualFile for CombineFile {
+    fn poll_sync(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(self.tx.as_mut()).poll_sync(cx)
+    }
+
     fn last_accessed(&self) -> u64 {
         self.rx.last_accessed()
     }
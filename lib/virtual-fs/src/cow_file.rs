@@ -202,6 +202,14 @@ impl AsyncRead for CopyOnWriteFile {
 }
 
 impl VirtualFile for CopyOnWriteFile {
+    fn poll_sync(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.poll_copy_start_and_progress(cx) {
+            Poll::Ready(Ok(())) => {}
+            p => return p,
+        }
+        Pin::new(self.state.inner_mut()).poll_sync(cx)
+    }
+
     fn last_accessed(&self) -> u64 {
         self.last_accessed
     }
@@ -50,6 +50,42 @@ impl VirtualFile for DualWriteFile {
         self.inner.set_len(new_size)
     }
 
+    fn try_lock(
+        &mut self,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> crate::Result<()> {
+        self.inner.try_lock(owner, lock, range)
+    }
+
+    fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> Poll<crate::Result<()>> {
+        self.inner.poll_lock(cx, owner, lock, range)
+    }
+
+    fn test_lock(
+        &mut self,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> crate::Result<Option<(crate::FileLock, crate::LockRange)>> {
+        self.inner.test_lock(owner, lock, range)
+    }
+
+    fn unlock_all(&mut self, owner: crate::LockOwner) {
+        self.inner.unlock_all(owner)
+    }
+
+    fn poll_sync(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(self.inner.as_mut()).poll_sync(cx)
+    }
+
     fn unlink(&mut self) -> Result<()> {
         self.inner.unlink()
     }
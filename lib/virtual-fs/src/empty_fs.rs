@@ -50,6 +50,7 @@ impl FileSystem for EmptyFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                permissions: None,
             })
         } else {
             Err(FsError::EntryNotFound)
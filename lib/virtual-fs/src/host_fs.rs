@@ -1,11 +1,13 @@
 use crate::{
-    DirEntry, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir, Result,
-    VirtualFile,
+    DirEntry, FileLock, FileType, FsError, LockOwner, LockRange, Metadata, OpenOptions,
+    OpenOptionsConfig, Permissions, ReadDir, Result, VirtualFile, Watch, WatchMask,
 };
 use bytes::{Buf, Bytes};
 use futures::future::BoxFuture;
 #[cfg(feature = "enable-serde")]
 use serde::{de, Deserialize, Serialize};
+#[cfg(unix)]
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fs;
 use std::io::{self, Seek};
@@ -18,12 +20,21 @@ use tokio::fs as tfs;
 use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
 use tokio::runtime::Handle;
 
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct FileSystem {
     #[cfg_attr(feature = "enable-serde", serde(skip, default = "default_handle"))]
     handle: Handle,
     root: PathBuf,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    io_uring: bool,
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    inotify: Arc<inotify::Inotify>,
 }
 
 #[allow(dead_code)]
@@ -71,7 +82,22 @@ impl FileSystem {
     pub fn new(handle: Handle, root: impl Into<PathBuf>) -> Result<Self> {
         let root = canonicalize(&root.into())?;
 
-        Ok(FileSystem { handle, root })
+        Ok(FileSystem {
+            handle,
+            root,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            io_uring: false,
+            #[cfg(target_os = "linux")]
+            inotify: Arc::default(),
+        })
+    }
+
+    /// Chooses whether the files are read and written through `io_uring`
+    /// (when the kernel supports it) or `tokio::fs` (the default)
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn with_io_uring(mut self, enabled: bool) -> Self {
+        self.io_uring = enabled;
+        self
     }
 }
 
@@ -96,7 +122,14 @@ impl crate::FileSystem for FileSystem {
     fn readlink(&self, path: &Path) -> Result<PathBuf> {
         let path = self.prepare_path(path);
 
-        fs::read_link(path).map_err(Into::into)
+        let target = fs::read_link(path)?;
+
+        // Absolute targets are stored relative to the host root (see `symlink`)
+        // so they need to be mapped back into the file system
+        match target.strip_prefix(&self.root) {
+            Ok(target) => Ok(Path::new("/").join(target)),
+            Err(_) => Ok(target),
+        }
     }
 
     fn read_dir(&self, path: &Path) -> Result<ReadDir> {
@@ -216,6 +249,92 @@ impl crate::FileSystem for FileSystem {
         fs::remove_file(path).map_err(Into::into)
     }
 
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        let link = self.prepare_path(link);
+        if link.parent().is_none() {
+            return Err(FsError::BaseNotDirectory);
+        }
+
+        // Absolute targets must resolve within the root of this file system
+        // when they are followed by the host, relative ones are followed from
+        // the directory of the link and must not climb out of it either
+        let original = if original.has_root() {
+            self.prepare_path(original)
+        } else {
+            let parent = link.parent().ok_or(FsError::BaseNotDirectory)?;
+            if !normalize_path(&parent.join(original)).starts_with(&self.root) {
+                return Err(FsError::PermissionDenied);
+            }
+            original.to_owned()
+        };
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(original, link).map_err(Into::into)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (original, link);
+            Err(FsError::Unsupported)
+        }
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let original = self.prepare_path(original);
+        let link = self.prepare_path(link);
+        if link.parent().is_none() {
+            return Err(FsError::BaseNotDirectory);
+        }
+
+        fs::hard_link(original, link).map_err(Into::into)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        let path = self.prepare_path(path);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+            let metadata = fs::metadata(&path)?;
+            if metadata.uid() != permissions.uid || metadata.gid() != permissions.gid {
+                std::os::unix::fs::chown(&path, Some(permissions.uid), Some(permissions.gid))?;
+            }
+            fs::set_permissions(&path, fs::Permissions::from_mode(permissions.mode))
+                .map_err(Into::into)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (path, permissions);
+            Err(FsError::Unsupported)
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        xattr::get(&self.prepare_path(path), name)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        xattr::set(&self.prepare_path(path), name, value)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>> {
+        xattr::list(&self.prepare_path(path))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        xattr::remove(&self.prepare_path(path), name)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+        self.inotify.watch(&self.prepare_path(path), mask)
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         OpenOptions::new(self)
     }
@@ -246,6 +365,345 @@ impl crate::FileSystem for FileSystem {
     }
 }
 
+/// Extended attributes of the host, only the `user.*` namespace is passed
+/// through as the other namespaces belong to the host system itself.
+#[cfg(target_os = "linux")]
+mod xattr {
+    use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path, ptr};
+
+    use crate::{FsError, Result};
+
+    const NAMESPACE: &str = "user.";
+
+    fn c_path(path: &Path) -> Result<CString> {
+        CString::new(path.as_os_str().as_bytes()).map_err(|_| FsError::InvalidInput)
+    }
+
+    fn c_name(name: &str) -> Result<CString> {
+        if !name.starts_with(NAMESPACE) {
+            return Err(FsError::Unsupported);
+        }
+        CString::new(name).map_err(|_| FsError::InvalidInput)
+    }
+
+    fn into_fs_error(err: io::Error) -> FsError {
+        match err.raw_os_error() {
+            Some(libc::ENODATA) => FsError::AttributeNotFound,
+            Some(libc::ENOTSUP) => FsError::Unsupported,
+            Some(libc::E2BIG) | Some(libc::ERANGE) => FsError::InvalidInput,
+            _ => err.into(),
+        }
+    }
+
+    /// Calls `op` once to learn the size of the buffer and then again to
+    /// fill it, retrying if the data grew in between the two calls.
+    fn read_buffer(op: impl Fn(*mut libc::c_void, usize) -> libc::ssize_t) -> Result<Vec<u8>> {
+        loop {
+            let size = op(ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(into_fs_error(io::Error::last_os_error()));
+            }
+
+            let mut buf = vec![0u8; size as usize];
+            let read = op(buf.as_mut_ptr().cast(), buf.len());
+            if read >= 0 {
+                buf.truncate(read as usize);
+                return Ok(buf);
+            }
+
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ERANGE) {
+                return Err(into_fs_error(err));
+            }
+        }
+    }
+
+    pub(super) fn get(path: &Path, name: &str) -> Result<Vec<u8>> {
+        let (path, name) = (c_path(path)?, c_name(name)?);
+        // SAFETY: the strings are NUL terminated and `buf` is valid for `len` bytes
+        read_buffer(|buf, len| unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, len) })
+    }
+
+    pub(super) fn set(path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        let (path, name) = (c_path(path)?, c_name(name)?);
+        // SAFETY: the strings are NUL terminated and `value` is valid for its length
+        let ret = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+        if ret != 0 {
+            return Err(into_fs_error(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    pub(super) fn list(path: &Path) -> Result<Vec<String>> {
+        let path = c_path(path)?;
+        // SAFETY: the path is NUL terminated and `buf` is valid for `len` bytes
+        let names =
+            read_buffer(|buf, len| unsafe { libc::listxattr(path.as_ptr(), buf.cast(), len) })?;
+
+        Ok(names
+            .split(|b| *b == 0)
+            .filter_map(|name| std::str::from_utf8(name).ok())
+            .filter(|name| name.starts_with(NAMESPACE))
+            .map(String::from)
+            .collect())
+    }
+
+    pub(super) fn remove(path: &Path, name: &str) -> Result<()> {
+        let (path, name) = (c_path(path)?, c_name(name)?);
+        // SAFETY: both strings are NUL terminated
+        let ret = unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) };
+        if ret != 0 {
+            return Err(into_fs_error(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+/// Watches of the host, all the watches of a file system share a single
+/// `inotify(7)` instance whose events are read by a single thread.
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::{
+        collections::HashMap,
+        ffi::{CString, OsStr},
+        io, mem,
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::ffi::OsStrExt,
+        },
+        path::Path,
+        ptr,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{FsError, Result, Watch, WatchEvent, WatchEventKind, WatchMask, WatchSender};
+
+    /// Large enough for several events with a name of `NAME_MAX` bytes
+    const BUFFER_SIZE: usize = 4096;
+
+    const KINDS: [WatchEventKind; 11] = [
+        WatchEventKind::Modify,
+        WatchEventKind::Attrib,
+        WatchEventKind::CloseWrite,
+        WatchEventKind::MovedFrom,
+        WatchEventKind::MovedTo,
+        WatchEventKind::Create,
+        WatchEventKind::Delete,
+        WatchEventKind::DeleteSelf,
+        WatchEventKind::MoveSelf,
+        WatchEventKind::Overflow,
+        WatchEventKind::Ignored,
+    ];
+
+    /// The `inotify(7)` instance of a file system, it is created (along
+    /// with the thread that reads it) when the first watch is added and
+    /// is closed once every clone of the file system is dropped
+    #[derive(Debug, Default)]
+    pub(super) struct Inotify {
+        shared: Mutex<Option<Arc<Shared>>>,
+    }
+
+    #[derive(Debug)]
+    struct Shared {
+        fd: OwnedFd,
+        /// An `eventfd(2)` that tells the reader thread to stop
+        stop: OwnedFd,
+        /// The watches that receive the events of each watch descriptor
+        watches: Mutex<HashMap<i32, Vec<WatchSender>>>,
+    }
+
+    impl Inotify {
+        pub(super) fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+            let path =
+                CString::new(path.as_os_str().as_bytes()).map_err(|_| FsError::InvalidInput)?;
+            let shared = self.shared()?;
+
+            let mut watches = shared.watches.lock().unwrap();
+            // Watches of the same entry share its watch descriptor, which
+            // reports the events that any of them is interested in
+            // SAFETY: the path is NUL terminated
+            let wd = unsafe {
+                libc::inotify_add_watch(
+                    shared.fd.as_raw_fd(),
+                    path.as_ptr(),
+                    mask.bits() | libc::IN_MASK_ADD,
+                )
+            };
+            if wd < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            let (sender, watch) = Watch::channel(mask);
+            watches.entry(wd).or_default().push(sender);
+            shared.prune(&mut watches);
+            Ok(watch)
+        }
+
+        fn shared(&self) -> Result<Arc<Shared>> {
+            let mut guard = self.shared.lock().unwrap();
+            if let Some(shared) = guard.as_ref() {
+                return Ok(shared.clone());
+            }
+
+            // SAFETY: no pointers are involved
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            // SAFETY: the descriptor was just created and is owned by nobody else
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+            // SAFETY: no pointers are involved
+            let stop = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+            if stop < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            // SAFETY: the descriptor was just created and is owned by nobody else
+            let stop = unsafe { OwnedFd::from_raw_fd(stop) };
+
+            let shared = Arc::new(Shared {
+                fd,
+                stop,
+                watches: Mutex::new(HashMap::new()),
+            });
+            let reader = shared.clone();
+            std::thread::Builder::new()
+                .name("inotify".to_string())
+                .spawn(move || reader.run())?;
+
+            *guard = Some(shared.clone());
+            Ok(shared)
+        }
+    }
+
+    impl Drop for Inotify {
+        fn drop(&mut self) {
+            let shared = self.shared.get_mut().ok().and_then(|shared| shared.take());
+            if let Some(shared) = shared {
+                let one = 1u64;
+                // SAFETY: the value is valid for its size
+                unsafe {
+                    libc::write(
+                        shared.stop.as_raw_fd(),
+                        ptr::addr_of!(one).cast(),
+                        mem::size_of::<u64>(),
+                    )
+                };
+            }
+        }
+    }
+
+    impl Shared {
+        /// Forgets the watches that were dropped and removes the watch
+        /// descriptors that no watch uses anymore
+        fn prune(&self, watches: &mut HashMap<i32, Vec<WatchSender>>) {
+            watches.retain(|wd, senders| {
+                senders.retain(|sender| !sender.is_closed());
+                if senders.is_empty() {
+                    // SAFETY: no pointers are involved
+                    unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), *wd) };
+                }
+                !senders.is_empty()
+            });
+        }
+
+        fn run(&self) {
+            let mut buf = vec![0u8; BUFFER_SIZE];
+            loop {
+                let mut pollfds = [
+                    libc::pollfd {
+                        fd: self.fd.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                    libc::pollfd {
+                        fd: self.stop.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                ];
+                // SAFETY: `pollfds` is valid for the duration of the call
+                let ret = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as _, -1) };
+                if ret < 0 {
+                    match io::Error::last_os_error().raw_os_error() {
+                        Some(libc::EINTR) => continue,
+                        _ => break,
+                    }
+                }
+                if pollfds[1].revents != 0 {
+                    break;
+                }
+
+                // SAFETY: `buf` is valid for its length
+                let read =
+                    unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+                if read < 0 {
+                    match io::Error::last_os_error().raw_os_error() {
+                        Some(libc::EINTR) | Some(libc::EAGAIN) => continue,
+                        _ => break,
+                    }
+                }
+
+                let mut watches = self.watches.lock().unwrap();
+                for (wd, event) in parse_events(&buf[..read as usize]) {
+                    let senders: Vec<_> = match event.kind {
+                        // Overflows are not about any watch in particular
+                        WatchEventKind::Overflow => watches.values().flatten().cloned().collect(),
+                        WatchEventKind::Ignored => watches.remove(&wd).unwrap_or_default(),
+                        _ => watches.get(&wd).cloned().unwrap_or_default(),
+                    };
+                    for sender in senders {
+                        sender.send(event.clone());
+                    }
+                }
+                self.prune(&mut watches);
+            }
+
+            // The watches that are still alive will not receive any more events
+            let mut watches = self.watches.lock().unwrap();
+            for sender in watches.drain().flat_map(|(_, senders)| senders) {
+                sender.send(WatchEvent::new(WatchEventKind::Ignored));
+            }
+        }
+    }
+
+    /// Converts the `inotify_event` records read from the descriptor
+    fn parse_events(mut buf: &[u8]) -> Vec<(i32, WatchEvent)> {
+        const HEADER: usize = mem::size_of::<libc::inotify_event>();
+
+        let mut events = Vec::new();
+        while buf.len() >= HEADER {
+            // SAFETY: the buffer holds at least one header, which may not be aligned
+            let raw: libc::inotify_event = unsafe { ptr::read_unaligned(buf.as_ptr().cast()) };
+            let end = (HEADER + raw.len as usize).min(buf.len());
+            // The name is padded with NUL bytes
+            let name = buf[HEADER..end]
+                .split(|b| *b == 0)
+                .next()
+                .unwrap_or_default();
+            buf = &buf[end..];
+
+            for kind in KINDS.into_iter().filter(|k| raw.mask & k.bits() != 0) {
+                let mut event = WatchEvent::new(kind).with_cookie(raw.cookie);
+                if !name.is_empty() {
+                    event =
+                        event.with_name(OsStr::from_bytes(name), raw.mask & libc::IN_ISDIR != 0);
+                }
+                events.push((raw.wd, event));
+            }
+        }
+        events
+    }
+}
+
 impl TryInto<Metadata> for std::fs::Metadata {
     type Error = io::Error;
 
@@ -268,6 +726,14 @@ impl TryInto<Metadata> for std::fs::Metadata {
             }
         };
 
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::MetadataExt;
+            Some(Permissions::new(self.mode(), self.uid(), self.gid()))
+        };
+        #[cfg(not(unix))]
+        let permissions = None;
+
         Ok(Metadata {
             ft: FileType {
                 dir: filetype.is_dir(),
@@ -300,6 +766,7 @@ impl TryInto<Metadata> for std::fs::Metadata {
                 })
                 .map_or(0, |time| time.as_nanos() as u64),
             len: self.len(),
+            permissions,
         })
     }
 }
@@ -333,14 +800,21 @@ impl crate::FileOpener for FileSystem {
             .open(&path)
             .map_err(Into::into)
             .map(|file| {
-                Box::new(File::new(
+                let file = File::new(
                     self.handle.clone(),
                     file,
                     path.to_owned(),
                     read,
                     write,
                     append,
-                )) as Box<dyn VirtualFile + Send + Sync + 'static>
+                );
+                #[cfg(all(target_os = "linux", feature = "io-uring"))]
+                let file = if self.io_uring {
+                    file.with_io_uring()
+                } else {
+                    file
+                };
+                Box::new(file) as Box<dyn VirtualFile + Send + Sync + 'static>
             })
     }
 }
@@ -355,9 +829,18 @@ pub struct File {
     inner: tfs::File,
     #[cfg_attr(feature = "enable-serde", serde(skip_serializing))]
     inner_std: fs::File,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    uring: Option<uring::UringFile>,
+    /// Sync of the file that runs on a blocking thread
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    sync: Option<tokio::task::JoinHandle<io::Result<()>>>,
     pub host_path: PathBuf,
     #[cfg(feature = "enable-serde")]
     flags: u16,
+    #[cfg(unix)]
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    locks: HashMap<LockOwner, HostLock>,
 }
 
 #[cfg(feature = "enable-serde")]
@@ -402,8 +885,13 @@ impl<'de> Deserialize<'de> for File {
                     handle: Handle::current(),
                     inner: tokio::fs::File::from_std(inner.try_clone().unwrap()),
                     inner_std: inner,
+                    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+                    uring: None,
+                    sync: None,
                     host_path,
                     flags,
+                    #[cfg(unix)]
+                    locks: HashMap::new(),
                 })
             }
 
@@ -441,8 +929,13 @@ impl<'de> Deserialize<'de> for File {
                     handle: Handle::current(),
                     inner: tokio::fs::File::from_std(inner.try_clone().unwrap()),
                     inner_std: inner,
+                    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+                    uring: None,
+                    sync: None,
                     host_path,
                     flags,
+                    #[cfg(unix)]
+                    locks: HashMap::new(),
                 })
             }
         }
@@ -485,18 +978,125 @@ impl File {
             handle,
             inner_std: file,
             inner: async_file,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            uring: None,
+            sync: None,
             host_path,
             #[cfg(feature = "enable-serde")]
             flags: _flags,
+            #[cfg(unix)]
+            locks: HashMap::new(),
         }
     }
 
+    /// Reads, writes, seeks and syncs the file through `io_uring` instead of
+    /// `tokio::fs`, unless the kernel does not support it
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn with_io_uring(mut self) -> Self {
+        self.uring = uring::UringFile::new(&self.inner_std);
+        self
+    }
+
     fn metadata(&self) -> std::fs::Metadata {
         // FIXME: no unwrap!
         self.inner_std.metadata().unwrap()
     }
 }
 
+/// The host file description that the advisory locks of an owner are
+/// placed through, this way owners that share a [`File`] still conflict
+/// with each other (and with processes outside of the sandbox)
+#[cfg(unix)]
+#[derive(Debug)]
+struct HostLock {
+    file: fs::File,
+    /// A lock that is being waited for on a blocking thread
+    pending: Option<(FileLock, LockRange, tokio::task::JoinHandle<Result<()>>)>,
+}
+
+#[cfg(unix)]
+impl File {
+    fn host_lock(&mut self, owner: LockOwner) -> Result<&mut HostLock> {
+        use std::collections::hash_map::Entry;
+
+        match self.locks.entry(owner) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                // Exclusive byte range locks can only be placed on a file
+                // that was opened for writing
+                let file = fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&self.host_path)
+                    .or_else(|_| fs::File::open(&self.host_path))?;
+                Ok(entry.insert(HostLock {
+                    file,
+                    pending: None,
+                }))
+            }
+        }
+    }
+}
+
+/// Places a lock on a host file description, when `wait` is set this
+/// blocks until any conflicting lock is released
+#[cfg(unix)]
+fn place_host_lock(file: &fs::File, lock: FileLock, range: LockRange, wait: bool) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let ret = match range {
+        LockRange::File => {
+            let op = match lock {
+                FileLock::Shared => libc::LOCK_SH,
+                FileLock::Exclusive => libc::LOCK_EX,
+                FileLock::Unlock => libc::LOCK_UN,
+            };
+            let op = if wait { op } else { op | libc::LOCK_NB };
+            // SAFETY: the descriptor stays open for as long as `file` is alive
+            unsafe { libc::flock(file.as_raw_fd(), op) }
+        }
+        #[cfg(target_os = "linux")]
+        LockRange::Bytes { start, len } => {
+            let mut flock = host_flock(lock, start, len)?;
+            let cmd = if wait {
+                libc::F_OFD_SETLKW
+            } else {
+                libc::F_OFD_SETLK
+            };
+            // SAFETY: the descriptor stays open for as long as `file` is alive
+            unsafe { libc::fcntl(file.as_raw_fd(), cmd, &mut flock) }
+        }
+        #[cfg(not(target_os = "linux"))]
+        LockRange::Bytes { .. } => return Err(FsError::Unsupported),
+    };
+    if ret != 0 {
+        let err = io::Error::last_os_error();
+        // `fcntl` may report conflicting locks with `EACCES` rather than `EAGAIN`
+        if err.raw_os_error() == Some(libc::EACCES) {
+            return Err(FsError::WouldBlock);
+        }
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn host_flock(lock: FileLock, start: u64, len: u64) -> Result<libc::flock> {
+    let l_type = match lock {
+        FileLock::Shared => libc::F_RDLCK,
+        FileLock::Exclusive => libc::F_WRLCK,
+        FileLock::Unlock => libc::F_UNLCK,
+    };
+    // SAFETY: `flock` is plain old data, open file description locks
+    // require `l_pid` to be zero
+    let mut flock: libc::flock = unsafe { std::mem::zeroed() };
+    flock.l_type = l_type as _;
+    flock.l_whence = libc::SEEK_SET as _;
+    flock.l_start = start.try_into().map_err(|_| FsError::InvalidInput)?;
+    flock.l_len = len.try_into().map_err(|_| FsError::InvalidInput)?;
+    Ok(flock)
+}
+
 //#[cfg_attr(feature = "enable-serde", typetag::serde)]
 #[async_trait::async_trait]
 impl VirtualFile for File {
@@ -547,11 +1147,140 @@ impl VirtualFile for File {
         fs::remove_file(&self.host_path).map_err(Into::into)
     }
 
+    #[cfg(unix)]
+    fn try_lock(&mut self, owner: LockOwner, lock: FileLock, range: LockRange) -> Result<()> {
+        let host = self.host_lock(owner)?;
+        place_host_lock(&host.file, lock, range, false)
+    }
+
+    #[cfg(unix)]
+    fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Poll<Result<()>> {
+        use std::future::Future;
+
+        let handle = self.handle.clone();
+        let host = match self.host_lock(owner) {
+            Ok(host) => host,
+            Err(err) => return Poll::Ready(Err(err)),
+        };
+
+        if let Some((pending_lock, pending_range, task)) = host.pending.as_mut() {
+            let res = std::task::ready!(Pin::new(task).poll(cx));
+            let requested = (*pending_lock, *pending_range) == (lock, range);
+            host.pending = None;
+            if requested {
+                return Poll::Ready(res.unwrap_or(Err(FsError::UnknownError)));
+            }
+        }
+
+        match place_host_lock(&host.file, lock, range, false) {
+            Err(FsError::WouldBlock) => {}
+            res => return Poll::Ready(res),
+        }
+
+        // The conflicting lock may be held by a process outside of the
+        // sandbox which can not tell us when it is released, instead the
+        // lock is waited for on a blocking thread through a duplicate of
+        // the description (which shares its locks)
+        let file = match host.file.try_clone() {
+            Ok(file) => file,
+            Err(err) => return Poll::Ready(Err(err.into())),
+        };
+        let mut task = handle.spawn_blocking(move || place_host_lock(&file, lock, range, true));
+        match Pin::new(&mut task).poll(cx) {
+            Poll::Ready(res) => Poll::Ready(res.unwrap_or(Err(FsError::UnknownError))),
+            Poll::Pending => {
+                host.pending = Some((lock, range, task));
+                Poll::Pending
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn test_lock(
+        &mut self,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Result<Option<(FileLock, LockRange)>> {
+        use std::os::unix::io::AsRawFd;
+
+        // Whole file locks can not be queried on the host
+        let LockRange::Bytes { start, len } = range else {
+            return Err(FsError::Unsupported);
+        };
+        if lock == FileLock::Unlock {
+            return Ok(None);
+        }
+
+        let host = self.host_lock(owner)?;
+        let mut flock = host_flock(lock, start, len)?;
+        // SAFETY: the descriptor stays open for as long as `host` is alive
+        let ret = unsafe { libc::fcntl(host.file.as_raw_fd(), libc::F_OFD_GETLK, &mut flock) };
+        if ret != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let lock = match flock.l_type as i32 {
+            libc::F_UNLCK => return Ok(None),
+            libc::F_RDLCK => FileLock::Shared,
+            _ => FileLock::Exclusive,
+        };
+        Ok(Some((
+            lock,
+            LockRange::Bytes {
+                start: flock.l_start as u64,
+                len: flock.l_len as u64,
+            },
+        )))
+    }
+
+    #[cfg(unix)]
+    fn unlock_all(&mut self, owner: LockOwner) {
+        // Closing the description releases its locks
+        self.locks.remove(&owner);
+    }
+
+    fn poll_sync(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        use std::future::Future;
+
+        let this = self.get_mut();
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = this.uring.as_mut() {
+            return uring.poll_sync(cx);
+        }
+        if this.sync.is_none() {
+            match Pin::new(&mut *this).poll_flush(cx) {
+                Poll::Ready(Ok(())) => {}
+                p => return p,
+            }
+            // The file is synced on a blocking thread through a duplicate
+            // of the descriptor (which syncs the same file)
+            let file = this.inner_std.try_clone()?;
+            this.sync = Some(this.handle.spawn_blocking(move || file.sync_all()));
+        }
+        let task = this.sync.as_mut().expect("a sync is in progress");
+        let res = futures::ready!(Pin::new(task).poll(cx));
+        this.sync = None;
+        Poll::Ready(res.unwrap_or_else(|err| Err(io::Error::other(err))))
+    }
+
     fn get_special_fd(&self) -> Option<u32> {
         None
     }
 
     fn poll_read_ready(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_ref() {
+            let size = self.metadata().len();
+            return Poll::Ready(Ok(size.saturating_sub(uring.cursor()) as usize));
+        }
+
         let cursor = match self.inner_std.stream_position() {
             Ok(a) => a,
             Err(err) => return Poll::Ready(Err(err)),
@@ -577,6 +1306,10 @@ impl AsyncRead for File {
         cx: &mut Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_mut() {
+            return uring.poll_read(cx, buf);
+        }
         let _guard = Handle::try_current().map_err(|_| self.handle.enter());
         let inner = Pin::new(&mut self.inner);
         inner.poll_read(cx, buf)
@@ -589,18 +1322,30 @@ impl AsyncWrite for File {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_mut() {
+            return uring.poll_write(cx, buf);
+        }
         let _guard = Handle::try_current().map_err(|_| self.handle.enter());
         let inner = Pin::new(&mut self.inner);
         inner.poll_write(cx, buf)
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_mut() {
+            return uring.poll_flush(cx);
+        }
         let _guard = Handle::try_current().map_err(|_| self.handle.enter());
         let inner = Pin::new(&mut self.inner);
         inner.poll_flush(cx)
     }
 
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_mut() {
+            return uring.poll_shutdown(cx);
+        }
         let _guard = Handle::try_current().map_err(|_| self.handle.enter());
         let inner = Pin::new(&mut self.inner);
         inner.poll_shutdown(cx)
@@ -611,24 +1356,40 @@ impl AsyncWrite for File {
         cx: &mut Context<'_>,
         bufs: &[io::IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_mut() {
+            return uring.poll_write_vectored(cx, bufs);
+        }
         let _guard = Handle::try_current().map_err(|_| self.handle.enter());
         let inner = Pin::new(&mut self.inner);
         inner.poll_write_vectored(cx, bufs)
     }
 
     fn is_write_vectored(&self) -> bool {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if self.uring.is_some() {
+            return true;
+        }
         self.inner.is_write_vectored()
     }
 }
 
 impl AsyncSeek for File {
     fn start_seek(mut self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_mut() {
+            return uring.start_seek(position);
+        }
         let _guard = Handle::try_current().map_err(|_| self.handle.enter());
         let inner = Pin::new(&mut self.inner);
         inner.start_seek(position)
     }
 
     fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = self.uring.as_mut() {
+            return uring.poll_complete(cx);
+        }
         let _guard = Handle::try_current().map_err(|_| self.handle.enter());
         let inner = Pin::new(&mut self.inner);
         inner.poll_complete(cx)
@@ -1069,6 +1830,8 @@ mod tests {
     use super::FileSystem;
     use crate::FileSystem as FileSystemTrait;
     use crate::FsError;
+    use crate::Permissions;
+    use crate::{new_lock_owner, FileLock, LockRange};
     use std::path::Path;
 
     #[tokio::test]
@@ -1490,4 +2253,417 @@ mod tests {
             panic!("next: {s:?}");
         }
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlink_and_hard_link() {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+
+        assert_eq!(
+            fs.symlink(Path::new("/a.txt"), Path::new("/abs.txt")),
+            Ok(())
+        );
+        assert_eq!(
+            fs.symlink(Path::new("a.txt"), Path::new("/rel.txt")),
+            Ok(())
+        );
+        assert_eq!(
+            fs.symlink(Path::new("a.txt"), Path::new("/rel.txt")),
+            Err(FsError::AlreadyExists)
+        );
+
+        // Relative targets cannot point out of the root
+        std::fs::create_dir(temp.path().join("dir")).unwrap();
+        assert_eq!(
+            fs.symlink(Path::new("../a.txt"), Path::new("/dir/up.txt")),
+            Ok(())
+        );
+        assert_eq!(
+            fs.symlink(
+                Path::new("../../../../etc/passwd"),
+                Path::new("/dir/escape.txt")
+            ),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.symlink(Path::new("dir/../../a.txt"), Path::new("/escape.txt")),
+            Err(FsError::PermissionDenied)
+        );
+        assert!(!temp.path().join("dir/escape.txt").exists());
+
+        // Absolute targets are kept within the root of the file system
+        assert_eq!(
+            std::fs::read_link(temp.path().join("abs.txt")).unwrap(),
+            fs.root.join("a.txt")
+        );
+        assert_eq!(
+            fs.readlink(Path::new("/abs.txt")),
+            Ok(Path::new("/a.txt").to_owned())
+        );
+        assert_eq!(
+            fs.readlink(Path::new("/rel.txt")),
+            Ok(Path::new("a.txt").to_owned())
+        );
+        assert!(fs
+            .symlink_metadata(Path::new("/abs.txt"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            std::fs::read(temp.path().join("abs.txt")).unwrap(),
+            b"hello"
+        );
+
+        assert_eq!(
+            fs.hard_link(Path::new("/a.txt"), Path::new("/b.txt")),
+            Ok(())
+        );
+        assert_eq!(fs.remove_file(Path::new("/a.txt")), Ok(()));
+        assert_eq!(std::fs::read(temp.path().join("b.txt")).unwrap(), b"hello");
+        assert_eq!(
+            fs.hard_link(Path::new("/a.txt"), Path::new("/c.txt")),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_set_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+
+        let permissions = fs
+            .metadata(Path::new("/a.txt"))
+            .unwrap()
+            .permissions()
+            .expect("unix hosts report permissions");
+        assert_eq!(
+            fs.set_permissions(Path::new("/a.txt"), permissions.with_mode(0o600)),
+            Ok(())
+        );
+        assert_eq!(
+            std::fs::metadata(temp.path().join("a.txt"))
+                .unwrap()
+                .permissions()
+                .mode()
+                & Permissions::MODE_MASK,
+            0o600
+        );
+        assert_eq!(
+            fs.metadata(Path::new("/a.txt")).unwrap().permissions(),
+            Some(permissions.with_mode(0o600))
+        );
+        assert_eq!(
+            fs.set_permissions(Path::new("/b.txt"), permissions),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_try_lock() {
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+        let open = || {
+            fs.new_open_options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(Path::new("/a.txt"))
+                .unwrap()
+        };
+
+        let mut a = open();
+        let mut b = open();
+        let (oa, ob) = (new_lock_owner(), new_lock_owner());
+        let file = LockRange::File;
+
+        assert_eq!(a.try_lock(oa, FileLock::Shared, file), Ok(()));
+        assert_eq!(b.try_lock(ob, FileLock::Shared, file), Ok(()));
+        assert_eq!(
+            b.try_lock(ob, FileLock::Exclusive, file),
+            Err(FsError::WouldBlock),
+            "another owner holds a shared lock",
+        );
+
+        // Owners that share a handle still conflict with each other
+        assert_eq!(
+            a.try_lock(ob, FileLock::Exclusive, file),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(a.try_lock(oa, FileLock::Unlock, file), Ok(()));
+        assert_eq!(a.try_lock(ob, FileLock::Exclusive, file), Ok(()));
+        assert_eq!(
+            a.try_lock(oa, FileLock::Shared, file),
+            Err(FsError::WouldBlock)
+        );
+
+        // Waiters are woken up once the conflicting lock is released
+        let mut wait = Box::pin(futures::future::poll_fn(|cx| {
+            b.poll_lock(cx, oa, FileLock::Shared, file)
+        }));
+        assert!(futures::poll!(&mut wait).is_pending());
+        a.unlock_all(ob);
+        assert_eq!(wait.await, Ok(()));
+
+        drop(b);
+        assert_eq!(
+            a.try_lock(ob, FileLock::Exclusive, file),
+            Ok(()),
+            "closing a handle releases its locks",
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_try_lock_range() {
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(Path::new("/a.txt"))
+            .unwrap();
+        let (oa, ob) = (new_lock_owner(), new_lock_owner());
+        let bytes = |start, len| LockRange::Bytes { start, len };
+
+        assert_eq!(
+            file.try_lock(oa, FileLock::Exclusive, bytes(10, 10)),
+            Ok(())
+        );
+        assert_eq!(file.try_lock(ob, FileLock::Exclusive, bytes(0, 10)), Ok(()));
+        assert_eq!(
+            file.try_lock(ob, FileLock::Shared, bytes(15, 10)),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            file.test_lock(ob, FileLock::Shared, bytes(15, 10)),
+            Ok(Some((FileLock::Exclusive, bytes(10, 10))))
+        );
+        assert_eq!(
+            file.test_lock(oa, FileLock::Shared, bytes(15, 10)),
+            Ok(None)
+        );
+
+        assert_eq!(file.try_lock(oa, FileLock::Unlock, bytes(10, 10)), Ok(()));
+        assert_eq!(file.try_lock(ob, FileLock::Shared, bytes(15, 10)), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        use crate::AsyncWriteExt;
+        use std::pin::Pin;
+
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(Path::new("/a.txt"))
+            .unwrap();
+
+        file.write_all(b"hello").await.unwrap();
+        futures::future::poll_fn(|cx| Pin::new(&mut *file).poll_sync(cx))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("a.txt")).unwrap(),
+            "hello"
+        );
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[tokio::test]
+    async fn test_io_uring() {
+        use crate::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+        use std::{
+            io::{self, SeekFrom},
+            pin::Pin,
+        };
+
+        if super::uring::ring().is_none() {
+            eprintln!("io_uring is not available, skipping");
+            return;
+        }
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path())
+            .expect("get filesystem")
+            .with_io_uring(true);
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(Path::new("/a.txt"))
+            .unwrap();
+
+        let bufs = [io::IoSlice::new(b"hello"), io::IoSlice::new(b" world")];
+        assert_eq!(file.write_vectored(&bufs).await.unwrap(), 11);
+        file.flush().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("a.txt")).unwrap(),
+            "hello world"
+        );
+        assert_eq!(file.seek(SeekFrom::Start(6)).await.unwrap(), 6);
+        file.write_all(b"there").await.unwrap();
+        futures::future::poll_fn(|cx| Pin::new(&mut *file).poll_sync(cx))
+            .await
+            .unwrap();
+        assert_eq!(file.seek(SeekFrom::Current(-11)).await.unwrap(), 0);
+        let mut buf = String::new();
+        file.read_to_string(&mut buf).await.unwrap();
+        assert_eq!(buf, "hello there");
+        assert_eq!(file.seek(SeekFrom::End(-5)).await.unwrap(), 6);
+        assert!(file.seek(SeekFrom::Current(-7)).await.is_err());
+        drop(file);
+
+        let mut file = fs
+            .new_open_options()
+            .append(true)
+            .open(Path::new("/a.txt"))
+            .unwrap();
+        file.write_all(b"!").await.unwrap();
+        drop(file);
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("a.txt")).unwrap(),
+            "hello there!"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_watch() {
+        use crate::{WatchEvent, WatchEventKind, WatchMask};
+        use std::time::{Duration, Instant};
+
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+        let watch = fs
+            .watch(Path::new("/"), WatchMask::CREATE | WatchMask::DELETE)
+            .unwrap();
+
+        std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+        std::fs::remove_file(temp.path().join("a.txt")).unwrap();
+
+        let mut events = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while events.len() < 2 && Instant::now() < deadline {
+            match watch.try_recv() {
+                Some(event) => events.push(event),
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        assert_eq!(
+            events,
+            vec![
+                WatchEvent::new(WatchEventKind::Create).with_name("a.txt", false),
+                WatchEvent::new(WatchEventKind::Delete).with_name("a.txt", false),
+            ]
+        );
+
+        assert_eq!(
+            fs.watch(Path::new("/missing"), WatchMask::ALL).map(|_| ()),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_watches_share_an_instance() {
+        use crate::{Watch, WatchEvent, WatchEventKind, WatchMask};
+        use std::time::{Duration, Instant};
+
+        fn recv(watch: &Watch) -> Option<WatchEvent> {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while Instant::now() < deadline {
+                match watch.try_recv() {
+                    Some(event) => return Some(event),
+                    None => std::thread::sleep(Duration::from_millis(10)),
+                }
+            }
+            None
+        }
+
+        let temp = TempDir::new().unwrap();
+        std::fs::create_dir(temp.path().join("dir")).unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+
+        // Watches of the same entry each receive the events they asked for
+        let creates = fs.watch(Path::new("/"), WatchMask::CREATE).unwrap();
+        let deletes = fs.watch(Path::new("/"), WatchMask::DELETE).unwrap();
+        let dir = fs.watch(Path::new("/dir"), WatchMask::CREATE).unwrap();
+        let dropped = fs.watch(Path::new("/dir"), WatchMask::CREATE).unwrap();
+        drop(dropped);
+
+        std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+        std::fs::write(temp.path().join("dir/b.txt"), b"hello").unwrap();
+        std::fs::remove_file(temp.path().join("a.txt")).unwrap();
+
+        assert_eq!(
+            recv(&creates),
+            Some(WatchEvent::new(WatchEventKind::Create).with_name("a.txt", false))
+        );
+        assert_eq!(
+            recv(&deletes),
+            Some(WatchEvent::new(WatchEventKind::Delete).with_name("a.txt", false))
+        );
+        assert_eq!(
+            recv(&dir),
+            Some(WatchEvent::new(WatchEventKind::Create).with_name("b.txt", false))
+        );
+        assert_eq!(creates.try_recv(), None);
+
+        // The watches are told that they will not receive any more events
+        // once the file system is gone
+        drop(fs);
+        assert_eq!(
+            recv(&creates),
+            Some(WatchEvent::new(WatchEventKind::Ignored))
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_xattrs() {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+
+        // Not every file system of the host supports user attributes
+        match fs.set_xattr(Path::new("/a.txt"), "user.comment", b"hi") {
+            Ok(()) => {}
+            Err(FsError::Unsupported) => return,
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+
+        assert_eq!(
+            fs.get_xattr(Path::new("/a.txt"), "user.comment"),
+            Ok(b"hi".to_vec())
+        );
+        assert!(fs
+            .list_xattr(Path::new("/a.txt"))
+            .unwrap()
+            .contains(&"user.comment".to_string()));
+        assert_eq!(fs.remove_xattr(Path::new("/a.txt"), "user.comment"), Ok(()));
+        assert_eq!(
+            fs.get_xattr(Path::new("/a.txt"), "user.comment"),
+            Err(FsError::AttributeNotFound)
+        );
+
+        // Only the user namespace is passed through to the host
+        assert_eq!(
+            fs.set_xattr(Path::new("/a.txt"), "trusted.comment", b"hi"),
+            Err(FsError::Unsupported)
+        );
+    }
 }
@@ -0,0 +1,860 @@
+//! Reads, writes and syncs of host files through `io_uring(7)`, instead of
+//! the blocking thread pool that `tokio::fs` dispatches them to.
+//!
+//! A single ring is shared by the whole process. Operations are submitted
+//! as soon as they are created and a dedicated thread reaps their
+//! completions and wakes up the tasks waiting for them. Every operation
+//! owns its buffer (and keeps the file open) until the kernel is done with
+//! it, so the futures waiting for them can safely be dropped at any time.
+//!
+//! No more operations are kept in flight than the completion queue holds,
+//! further submissions wait until earlier operations complete.
+
+use std::{
+    ffi::c_void,
+    fs, io,
+    os::fd::{AsRawFd, OwnedFd},
+    ptr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use futures::ready;
+use rustix::io_uring::{
+    io_uring_cqe, io_uring_enter, io_uring_params, io_uring_ptr, io_uring_setup, io_uring_sqe,
+    io_uring_user_data, IoringEnterFlags, IoringFeatureFlags, IoringOp, IORING_OFF_CQ_RING,
+    IORING_OFF_SQES, IORING_OFF_SQ_RING,
+};
+use slab::Slab;
+use tokio::io::ReadBuf;
+
+/// Number of entries of the submission queue (the completion queue is
+/// twice as large)
+const RING_ENTRIES: u32 = 256;
+
+/// Largest read or write that is submitted at once
+const MAX_IO_SIZE: usize = 1024 * 1024;
+
+/// Returns the ring of the process, or `None` if `io_uring` is not available
+/// (old kernels, or when it is blocked by a seccomp filter)
+pub(super) fn ring() -> Option<&'static Ring> {
+    static RING: OnceLock<Option<Ring>> = OnceLock::new();
+    RING.get_or_init(|| match Ring::new(RING_ENTRIES) {
+        Ok(ring) => Some(ring),
+        Err(err) => {
+            tracing::debug!(%err, "io_uring is not available, falling back to tokio::fs");
+            None
+        }
+    })
+    .as_ref()
+}
+
+/// A memory mapped region of the ring
+struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(fd: &OwnedFd, len: usize, offset: u64) -> io::Result<Self> {
+        // SAFETY: the ring is mapped with the offsets and sizes reported by the kernel
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd.as_raw_fd(),
+                offset as libc::off_t,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    /// # Safety
+    ///
+    /// `offset` must be the offset of a `T` inside of the mapping
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        self.ptr.cast::<u8>().add(offset as usize).cast()
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: the region was mapped by `Mmap::new`
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+struct SubmissionQueue {
+    _ring: Mmap,
+    sqes: Mmap,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    entries: u32,
+    array: *mut u32,
+}
+
+struct CompletionQueue {
+    _ring: Option<Mmap>,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    cqes: *const io_uring_cqe,
+}
+
+/// An operation that was submitted to the ring
+struct OpState {
+    /// The buffer the kernel reads from or writes into
+    buf: Vec<u8>,
+    /// Keeps the file open until the operation completes
+    _file: Arc<fs::File>,
+    result: Option<i32>,
+    waker: Option<Waker>,
+    /// The [`Op`] was dropped, the state is freed when the operation completes
+    abandoned: bool,
+}
+
+#[derive(Default)]
+struct Ops {
+    slab: Slab<OpState>,
+    /// Number of operations whose completion has not been reaped yet
+    in_flight: usize,
+    /// Tasks waiting for an operation to complete so they can submit theirs
+    waiters: Vec<Waker>,
+}
+
+pub(super) struct Ring {
+    fd: OwnedFd,
+    sq: Mutex<SubmissionQueue>,
+    ops: Mutex<Ops>,
+    /// Size of the completion queue, more operations in flight could
+    /// overflow it
+    max_in_flight: usize,
+}
+
+// SAFETY: the raw pointers point into the mappings owned by the ring, the
+// submission queue is guarded by a mutex and the completion queue is only
+// ever touched by the completion thread.
+unsafe impl Send for SubmissionQueue {}
+unsafe impl Send for CompletionQueue {}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = io_uring_params::default();
+        let fd = io_uring_setup(entries, &mut params)?;
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len = params.cq_off.cqes as usize
+            + params.cq_entries as usize * std::mem::size_of::<io_uring_cqe>();
+        let single_mmap = params.features.contains(IoringFeatureFlags::SINGLE_MMAP);
+
+        let sq_ring = Mmap::new(
+            &fd,
+            if single_mmap {
+                sq_len.max(cq_len)
+            } else {
+                sq_len
+            },
+            IORING_OFF_SQ_RING,
+        )?;
+        let cq_ring = if single_mmap {
+            None
+        } else {
+            Some(Mmap::new(&fd, cq_len, IORING_OFF_CQ_RING)?)
+        };
+        let sqes = Mmap::new(
+            &fd,
+            params.sq_entries as usize * std::mem::size_of::<io_uring_sqe>(),
+            IORING_OFF_SQES,
+        )?;
+
+        // SAFETY: all the offsets come from the kernel
+        let (sq, cq) = unsafe {
+            let cq_mmap = cq_ring.as_ref().unwrap_or(&sq_ring);
+            let (head, tail) = (
+                cq_mmap.at(params.cq_off.head),
+                cq_mmap.at(params.cq_off.tail),
+            );
+            let mask = *cq_mmap.at::<u32>(params.cq_off.ring_mask);
+            let cqes = cq_mmap.at(params.cq_off.cqes);
+            let cq = CompletionQueue {
+                _ring: cq_ring,
+                head,
+                tail,
+                mask,
+                cqes,
+            };
+            let sq = SubmissionQueue {
+                head: sq_ring.at(params.sq_off.head),
+                tail: sq_ring.at(params.sq_off.tail),
+                mask: *sq_ring.at::<u32>(params.sq_off.ring_mask),
+                entries: *sq_ring.at::<u32>(params.sq_off.ring_entries),
+                array: sq_ring.at(params.sq_off.array),
+                sqes,
+                _ring: sq_ring,
+            };
+            (sq, cq)
+        };
+
+        let ring = Self {
+            fd,
+            sq: Mutex::new(sq),
+            ops: Mutex::new(Ops::default()),
+            max_in_flight: params.cq_entries as usize,
+        };
+        let completion_fd = ring.fd.try_clone()?;
+        std::thread::Builder::new()
+            .name("io-uring".to_string())
+            .spawn(move || {
+                // Blocks until the ring is stored in its `OnceLock`
+                let ring = self::ring().expect("the ring was initialized");
+                reap_completions(&completion_fd, cq, ring);
+            })?;
+        Ok(ring)
+    }
+
+    /// Submits an operation that reads up to `len` bytes at `offset`
+    pub(super) fn poll_read(
+        &'static self,
+        cx: &mut Context<'_>,
+        file: &Arc<fs::File>,
+        offset: u64,
+        len: usize,
+    ) -> Poll<io::Result<Op>> {
+        ready!(self.poll_reserve(cx));
+        let mut buf = vec![0u8; len];
+        let mut sqe = io_uring_sqe {
+            opcode: IoringOp::Read,
+            fd: file.as_raw_fd(),
+            ..Default::default()
+        };
+        sqe.off_or_addr2.off = offset;
+        sqe.addr_or_splice_off_in.addr = io_uring_ptr::from(buf.as_mut_ptr().cast::<c_void>());
+        sqe.len.len = len as u32;
+        Poll::Ready(self.submit(sqe, file, buf, Self::enter))
+    }
+
+    /// Submits an operation that writes the data at `offset` (at the end of
+    /// the file when it was opened in append mode), the data is only taken
+    /// once the operation can be submitted
+    pub(super) fn poll_write(
+        &'static self,
+        cx: &mut Context<'_>,
+        file: &Arc<fs::File>,
+        offset: u64,
+        data: impl FnOnce() -> Vec<u8>,
+    ) -> Poll<io::Result<Op>> {
+        ready!(self.poll_reserve(cx));
+        let mut data = data();
+        let mut sqe = io_uring_sqe {
+            opcode: IoringOp::Write,
+            fd: file.as_raw_fd(),
+            ..Default::default()
+        };
+        sqe.off_or_addr2.off = offset;
+        sqe.addr_or_splice_off_in.addr = io_uring_ptr::from(data.as_mut_ptr().cast::<c_void>());
+        sqe.len.len = data.len() as u32;
+        Poll::Ready(self.submit(sqe, file, data, Self::enter))
+    }
+
+    /// Submits an operation that flushes the file to the disk
+    pub(super) fn poll_fsync(
+        &'static self,
+        cx: &mut Context<'_>,
+        file: &Arc<fs::File>,
+    ) -> Poll<io::Result<Op>> {
+        ready!(self.poll_reserve(cx));
+        let sqe = io_uring_sqe {
+            opcode: IoringOp::Fsync,
+            fd: file.as_raw_fd(),
+            ..Default::default()
+        };
+        Poll::Ready(self.submit(sqe, file, Vec::new(), Self::enter))
+    }
+
+    /// Reserves a slot for an operation in the completion queue, the slot
+    /// is released when the completion of the operation is reaped
+    fn poll_reserve(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut ops = self.ops.lock().unwrap();
+        if ops.in_flight < self.max_in_flight {
+            ops.in_flight += 1;
+            Poll::Ready(())
+        } else {
+            ops.waiters.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn release(&self, ops: &mut Ops) {
+        ops.in_flight -= 1;
+        ops.waiters.drain(..).for_each(Waker::wake);
+    }
+
+    /// Submits the entries that were added to the submission queue
+    fn enter(fd: &OwnedFd) -> rustix::io::Result<u32> {
+        loop {
+            // SAFETY: the submitted entries point at buffers owned by the table of operations
+            match unsafe { io_uring_enter(fd, 1, 0, IoringEnterFlags::empty(), ptr::null(), 0) } {
+                Err(rustix::io::Errno::INTR) => continue,
+                other => break other,
+            }
+        }
+    }
+
+    /// Submits an operation, a slot must have been reserved for it
+    fn submit(
+        &'static self,
+        mut sqe: io_uring_sqe,
+        file: &Arc<fs::File>,
+        buf: Vec<u8>,
+        enter: impl FnOnce(&OwnedFd) -> rustix::io::Result<u32>,
+    ) -> io::Result<Op> {
+        // The heap allocation of the buffer (that the entry points to) does
+        // not move when the buffer is moved into the table
+        let key = self.ops.lock().unwrap().slab.insert(OpState {
+            buf,
+            _file: file.clone(),
+            result: None,
+            waker: None,
+            abandoned: false,
+        });
+        sqe.user_data = io_uring_user_data::from_u64(key as u64);
+
+        let sq = self.sq.lock().unwrap();
+        // SAFETY: the pointers point into the submission queue, whose tail is
+        // only written while holding the lock
+        let submitted = unsafe {
+            let head = (*sq.head).load(Ordering::Acquire);
+            let tail = (*sq.tail).load(Ordering::Relaxed);
+            // Entries are submitted one at a time and taken back when they
+            // are not consumed so the kernel has consumed all earlier ones
+            debug_assert_eq!(head, tail);
+            debug_assert!(sq.entries > 0);
+            let index = tail & sq.mask;
+            *sq.sqes.at::<io_uring_sqe>(0).add(index as usize) = sqe;
+            *sq.array.add(index as usize) = index;
+            (*sq.tail).store(tail.wrapping_add(1), Ordering::Release);
+            let ret = enter(&self.fd);
+
+            // The kernel only consumes entries in `io_uring_enter`, an entry
+            // it did not consume is taken out of the queue again so that it
+            // is not submitted later on (after its buffer was freed)
+            if (*sq.head).load(Ordering::Acquire) == tail {
+                (*sq.tail).store(tail, Ordering::Release);
+                match ret {
+                    Err(err) => Err(err),
+                    Ok(_) => Err(rustix::io::Errno::AGAIN),
+                }
+            } else {
+                Ok(())
+            }
+        };
+        drop(sq);
+
+        if let Err(err) = submitted {
+            let mut ops = self.ops.lock().unwrap();
+            ops.slab.remove(key);
+            self.release(&mut ops);
+            return Err(err.into());
+        }
+        Ok(Op {
+            ring: self,
+            key: Some(key),
+        })
+    }
+
+    fn complete(&self, key: usize, result: i32) {
+        let mut ops = self.ops.lock().unwrap();
+        self.release(&mut ops);
+        let Some(op) = ops.slab.get_mut(key) else {
+            return;
+        };
+        if op.abandoned {
+            ops.slab.remove(key);
+            return;
+        }
+        op.result = Some(result);
+        let waker = op.waker.take();
+        drop(ops);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+fn reap_completions(fd: &OwnedFd, cq: CompletionQueue, ring: &'static Ring) {
+    loop {
+        // SAFETY: waiting for completions does not touch any memory of ours
+        let ret = unsafe { io_uring_enter(fd, 0, 1, IoringEnterFlags::GETEVENTS, ptr::null(), 0) };
+        match ret {
+            Ok(_) | Err(rustix::io::Errno::INTR) | Err(rustix::io::Errno::BUSY) => {}
+            Err(err) => {
+                tracing::error!(%err, "failed to wait for io_uring completions");
+                return;
+            }
+        }
+
+        // SAFETY: the completion queue is only consumed by this thread
+        unsafe {
+            let mut head = (*cq.head).load(Ordering::Relaxed);
+            let tail = (*cq.tail).load(Ordering::Acquire);
+            while head != tail {
+                let cqe = &*cq.cqes.add((head & cq.mask) as usize);
+                let (key, result) = (cqe.user_data.u64_() as usize, cqe.res);
+                head = head.wrapping_add(1);
+                (*cq.head).store(head, Ordering::Release);
+                ring.complete(key, result);
+            }
+        }
+    }
+}
+
+/// An operation that is in flight
+pub(super) struct Op {
+    ring: &'static Ring,
+    key: Option<usize>,
+}
+
+impl Op {
+    /// Waits for the operation to complete, returns its result (the number
+    /// of bytes that were transferred) and its buffer
+    pub(super) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<(io::Result<usize>, Vec<u8>)> {
+        let key = self.key.expect("polled after completion");
+        let mut ops = self.ring.ops.lock().unwrap();
+        let op = &mut ops.slab[key];
+        match op.result {
+            Some(result) => {
+                let op = ops.slab.remove(key);
+                self.key = None;
+                let result = if result < 0 {
+                    Err(io::Error::from_raw_os_error(-result))
+                } else {
+                    Ok(result as usize)
+                };
+                Poll::Ready((result, op.buf))
+            }
+            None => {
+                op.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Op {
+    fn drop(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+        let mut ops = self.ring.ops.lock().unwrap();
+        if ops.slab[key].result.is_some() {
+            ops.slab.remove(key);
+        } else {
+            ops.slab[key].abandoned = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpKind {
+    Read,
+    Write,
+    Fsync,
+}
+
+/// The state of a host file whose reads, writes and syncs go through the ring
+pub(super) struct UringFile {
+    ring: &'static Ring,
+    file: Arc<fs::File>,
+    cursor: u64,
+    append: bool,
+    pending: Option<(OpKind, Op)>,
+    /// The rest of a short write that still has to be submitted
+    remainder: Option<Vec<u8>>,
+    seek: Option<io::SeekFrom>,
+}
+
+impl std::fmt::Debug for UringFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UringFile")
+            .field("cursor", &self.cursor)
+            .field("append", &self.append)
+            .field("pending", &self.pending.as_ref().map(|(kind, _)| kind))
+            .finish()
+    }
+}
+
+impl UringFile {
+    /// Returns `None` when `io_uring` is not available
+    pub(super) fn new(file: &fs::File) -> Option<Self> {
+        let ring = ring()?;
+        // SAFETY: only reads the flags of a descriptor we own
+        let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+        let append = flags >= 0 && flags & libc::O_APPEND != 0;
+        let file = file.try_clone().ok()?;
+        Some(Self {
+            ring,
+            file: Arc::new(file),
+            cursor: 0,
+            append,
+            pending: None,
+            remainder: None,
+            seek: None,
+        })
+    }
+
+    pub(super) fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Waits for the operation in flight, writes move the cursor right away
+    /// while reads leave it to the caller (which knows how much it consumed)
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<(OpKind, io::Result<usize>, Vec<u8>)> {
+        let (kind, op) = self.pending.as_mut().expect("an operation is in flight");
+        let (result, buf) = ready!(op.poll(cx));
+        let kind = *kind;
+        self.pending = None;
+
+        if let (OpKind::Write, Ok(written)) = (kind, &result) {
+            self.cursor = match self.append {
+                true => self.file.metadata().map(|m| m.len()).unwrap_or(self.cursor),
+                false => self.cursor + *written as u64,
+            };
+        }
+        Poll::Ready((kind, result, buf))
+    }
+
+    /// Waits until no operation is in flight. Nobody is waiting anymore for
+    /// the result of the operation that is finished here, so the rest of a
+    /// short write is written again and errors are returned to the caller.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.remainder.is_some() {
+                let offset = self.offset();
+                let remainder = &mut self.remainder;
+                let op = ready!(self.ring.poll_write(cx, &self.file, offset, || {
+                    remainder.take().unwrap_or_default()
+                }))?;
+                self.pending = Some((OpKind::Write, op));
+            }
+            if self.pending.is_none() {
+                return Poll::Ready(Ok(()));
+            }
+            match ready!(self.poll_pending(cx)) {
+                (OpKind::Write, result, mut data) => match result? {
+                    0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                    written if written < data.len() => {
+                        data.drain(..written);
+                        self.remainder = Some(data);
+                    }
+                    _ => {}
+                },
+                // Nothing is lost when the result of a read is dropped
+                (OpKind::Read, _, _) => {}
+                (OpKind::Fsync, result, _) => {
+                    result?;
+                }
+            }
+        }
+    }
+
+    /// Offset that writes are submitted at, an offset of -1 writes at the
+    /// end of files opened for appending
+    fn offset(&self) -> u64 {
+        if self.append {
+            u64::MAX
+        } else {
+            self.cursor
+        }
+    }
+
+    pub(super) fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            match self.pending.as_ref() {
+                Some((OpKind::Read, _)) => {
+                    let (_, result, data) = ready!(self.poll_pending(cx));
+                    let read = result?.min(buf.remaining());
+                    buf.put_slice(&data[..read]);
+                    self.cursor += read as u64;
+                    return Poll::Ready(Ok(()));
+                }
+                Some(_) => ready!(self.poll_idle(cx))?,
+                None if self.remainder.is_some() => ready!(self.poll_idle(cx))?,
+                None => {
+                    let len = buf.remaining().min(MAX_IO_SIZE);
+                    if len == 0 {
+                        return Poll::Ready(Ok(()));
+                    }
+                    let op = ready!(self.ring.poll_read(cx, &self.file, self.cursor, len))?;
+                    self.pending = Some((OpKind::Read, op));
+                }
+            }
+        }
+    }
+
+    /// Writes as much of the buffers as fits in a single operation, the
+    /// result is the number of bytes the kernel actually wrote
+    pub(super) fn poll_write_vectored(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match self.pending.as_ref() {
+                Some((OpKind::Write, _)) => {
+                    let (_, result, _) = ready!(self.poll_pending(cx));
+                    return Poll::Ready(result);
+                }
+                Some(_) => ready!(self.poll_idle(cx))?,
+                None if self.remainder.is_some() => ready!(self.poll_idle(cx))?,
+                None => {
+                    let len = bufs.iter().map(|b| b.len()).sum::<usize>().min(MAX_IO_SIZE);
+                    if len == 0 {
+                        return Poll::Ready(Ok(0));
+                    }
+                    let op = ready!(self.ring.poll_write(cx, &self.file, self.offset(), || {
+                        let mut data = Vec::with_capacity(len);
+                        for buf in bufs {
+                            let take = buf.len().min(len - data.len());
+                            data.extend_from_slice(&buf[..take]);
+                        }
+                        data
+                    }))?;
+                    self.pending = Some((OpKind::Write, op));
+                }
+            }
+        }
+    }
+
+    pub(super) fn poll_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored(cx, &[io::IoSlice::new(buf)])
+    }
+
+    /// Waits for the operation in flight (if any), the data is handed to
+    /// the kernel as soon as it is written so there is nothing else to do
+    pub(super) fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_idle(cx)
+    }
+
+    /// Flushes the file and then syncs it to the disk
+    pub(super) fn poll_sync(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            match self.pending.as_ref() {
+                Some((OpKind::Fsync, _)) => {
+                    let (_, result, _) = ready!(self.poll_pending(cx));
+                    return Poll::Ready(result.map(|_| ()));
+                }
+                Some(_) => ready!(self.poll_idle(cx))?,
+                None if self.remainder.is_some() => ready!(self.poll_idle(cx))?,
+                None => {
+                    let op = ready!(self.ring.poll_fsync(cx, &self.file))?;
+                    self.pending = Some((OpKind::Fsync, op));
+                }
+            }
+        }
+    }
+
+    pub(super) fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_idle(cx)
+    }
+
+    pub(super) fn start_seek(&mut self, position: io::SeekFrom) -> io::Result<()> {
+        self.seek = Some(position);
+        Ok(())
+    }
+
+    /// Seeks once the operation in flight (which might move the cursor) completed
+    pub(super) fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        ready!(self.poll_idle(cx))?;
+        let Some(position) = self.seek.take() else {
+            return Poll::Ready(Ok(self.cursor));
+        };
+        let cursor = match position {
+            io::SeekFrom::Start(offset) => Some(offset),
+            io::SeekFrom::End(offset) => self.file.metadata()?.len().checked_add_signed(offset),
+            io::SeekFrom::Current(offset) => self.cursor.checked_add_signed(offset),
+        };
+        match cursor {
+            Some(cursor) => {
+                self.cursor = cursor;
+                Poll::Ready(Ok(cursor))
+            }
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        future::poll_fn,
+        io::{self, Read},
+        os::fd::{AsRawFd, FromRawFd, OwnedFd},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use rustix::io_uring::{io_uring_ptr, io_uring_sqe, IoringOp};
+
+    use super::{ring, OpKind, UringFile};
+
+    /// Returns the ends of a non-blocking pipe that holds a single page
+    fn small_pipe() -> (fs::File, fs::File) {
+        let mut fds = [0; 2];
+        // SAFETY: the descriptors are owned by the returned files
+        unsafe {
+            assert_eq!(libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK), 0);
+            assert!(libc::fcntl(fds[1], libc::F_SETPIPE_SZ, 4096) >= 0);
+            (
+                fs::File::from(OwnedFd::from_raw_fd(fds[0])),
+                fs::File::from(OwnedFd::from_raw_fd(fds[1])),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_short_write_is_returned() {
+        if ring().is_none() {
+            eprintln!("io_uring is not available, skipping");
+            return;
+        }
+        let (_rx, tx) = small_pipe();
+        let mut file = UringFile::new(&tx).unwrap();
+
+        let data = vec![1u8; 8192];
+        let written = poll_fn(|cx| file.poll_write(cx, &data)).await.unwrap();
+        assert_eq!(written, 4096);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_short_write_is_finished() {
+        if ring().is_none() {
+            eprintln!("io_uring is not available, skipping");
+            return;
+        }
+        let (mut rx, tx) = small_pipe();
+        let mut file = UringFile::new(&tx).unwrap();
+
+        // The caller stopped waiting for a write that only fits in part,
+        // the flush writes the rest once the pipe is drained
+        let op = poll_fn(|cx| file.ring.poll_write(cx, &file.file, 0, || vec![1u8; 8192]))
+            .await
+            .unwrap();
+        file.pending = Some((OpKind::Write, op));
+        let reader = std::thread::spawn(move || {
+            let mut read = Vec::new();
+            while read.len() < 8192 {
+                let mut buf = [0u8; 4096];
+                match rx.read(&mut buf) {
+                    Ok(n) => read.extend_from_slice(&buf[..n]),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(1))
+                    }
+                    Err(err) => panic!("{err}"),
+                }
+            }
+            read
+        });
+        poll_fn(|cx| file.poll_flush(cx)).await.unwrap();
+        assert!(file.pending.is_none());
+        assert_eq!(reader.join().unwrap(), vec![1u8; 8192]);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_write_errors_are_returned() {
+        if ring().is_none() {
+            eprintln!("io_uring is not available, skipping");
+            return;
+        }
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        let read_only = fs::File::open(temp.path()).unwrap();
+        let mut file = UringFile::new(&read_only).unwrap();
+
+        let op = poll_fn(|cx| {
+            file.ring
+                .poll_write(cx, &file.file, 0, || b"hello".to_vec())
+        })
+        .await
+        .unwrap();
+        file.pending = Some((OpKind::Write, op));
+        let err = poll_fn(|cx| file.poll_flush(cx)).await.unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+        assert!(file.pending.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_flush_and_sync() {
+        if ring().is_none() {
+            eprintln!("io_uring is not available, skipping");
+            return;
+        }
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        let mut file = UringFile::new(temp.as_file()).unwrap();
+
+        poll_fn(|cx| file.poll_write(cx, b"hello")).await.unwrap();
+        // Nothing is in flight so the flush completes without submitting
+        // an operation of its own
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        assert!(file.poll_flush(&mut cx).is_ready());
+        assert!(file.pending.is_none());
+
+        poll_fn(|cx| file.poll_sync(cx)).await.unwrap();
+        assert!(file.pending.is_none());
+        assert_eq!(std::fs::read(temp.path()).unwrap(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_failed_submit_is_taken_back() {
+        let Some(ring) = ring() else {
+            eprintln!("io_uring is not available, skipping");
+            return;
+        };
+        let temp = tempfile::NamedTempFile::new().unwrap();
+
+        // The kernel refuses the entry (e.g. when the completion queue
+        // overflowed), its buffer is freed right away
+        let file = Arc::new(temp.reopen().unwrap());
+        let mut data = b"stale".to_vec();
+        let mut sqe = io_uring_sqe {
+            opcode: IoringOp::Write,
+            fd: file.as_raw_fd(),
+            ..Default::default()
+        };
+        sqe.off_or_addr2.off = 100;
+        sqe.addr_or_splice_off_in.addr = io_uring_ptr::from(data.as_mut_ptr().cast());
+        sqe.len.len = data.len() as u32;
+        poll_fn(|cx| ring.poll_reserve(cx)).await;
+        let err = ring
+            .submit(sqe, &file, data, |_| Err(rustix::io::Errno::BUSY))
+            .err()
+            .unwrap();
+        assert_eq!(err.raw_os_error(), Some(libc::EBUSY));
+
+        // Later submissions do not submit the refused entry along with theirs
+        let mut file = UringFile::new(temp.as_file()).unwrap();
+        poll_fn(|cx| file.poll_write(cx, b"hello")).await.unwrap();
+        poll_fn(|cx| file.poll_sync(cx)).await.unwrap();
+        assert_eq!(std::fs::read(temp.path()).unwrap(), b"hello");
+    }
+}
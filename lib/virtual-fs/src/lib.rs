@@ -20,6 +20,8 @@ use thiserror::Error;
 pub mod arc_box_file;
 pub mod arc_file;
 pub mod arc_fs;
+#[cfg(feature = "archive-fs")]
+mod archive_fs;
 pub mod buffer_file;
 pub mod builder;
 pub mod combine_file;
@@ -40,11 +42,14 @@ pub mod zero_file;
 mod filesystems;
 pub(crate) mod ops;
 mod overlay_fs;
+mod permissions;
 pub mod pipe;
+mod quota_fs;
 mod static_file;
 #[cfg(feature = "static-fs")]
 pub mod static_fs;
 mod trace_fs;
+pub mod watch;
 #[cfg(feature = "webc-fs")]
 mod webc_volume_fs;
 
@@ -53,6 +58,8 @@ pub mod limiter;
 pub use arc_box_file::*;
 pub use arc_file::*;
 pub use arc_fs::*;
+#[cfg(feature = "archive-fs")]
+pub use archive_fs::{ArchiveFileSystem, ArchiveFormat};
 pub use buffer_file::*;
 pub use builder::*;
 pub use combine_file::*;
@@ -63,12 +70,15 @@ pub use filesystems::FileSystems;
 pub use null_file::*;
 pub use overlay_fs::OverlayFileSystem;
 pub use passthru_fs::*;
+pub use permissions::Permissions;
 pub use pipe::*;
+pub use quota_fs::{QuotaFileSystem, QuotaLimits, QuotaUsage};
 pub use special_file::*;
 pub use static_file::StaticFile;
 pub use tmp_fs::*;
 pub use trace_fs::TraceFileSystem;
 pub use union_fs::*;
+pub use watch::{Watch, WatchEvent, WatchEventKind, WatchMask, WatchSender};
 #[cfg(feature = "webc-fs")]
 pub use webc_volume_fs::WebcVolumeFileSystem;
 pub use zero_file::*;
@@ -93,11 +103,63 @@ pub trait FileSystem: fmt::Debug + Send + Sync + 'static + Upcastable {
     fn rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>>;
     fn metadata(&self, path: &Path) -> Result<Metadata>;
     /// This method gets metadata without following symlinks in the path.
-    /// Currently identical to `metadata` because symlinks aren't implemented
-    /// yet.
     fn symlink_metadata(&self, path: &Path) -> Result<Metadata>;
     fn remove_file(&self, path: &Path) -> Result<()>;
 
+    /// Creates a symbolic link at `link` that points to `original`, the
+    /// target is stored as is and does not need to exist.
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        let _ = (original, link);
+        Err(FsError::Unsupported)
+    }
+
+    /// Creates a new hard link at `link` that refers to the same file
+    /// as `original`.
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let _ = (original, link);
+        Err(FsError::Unsupported)
+    }
+
+    /// Changes the mode bits and ownership of an entry, symbolic links
+    /// are followed.
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        let _ = (path, permissions);
+        Err(FsError::Unsupported)
+    }
+
+    /// Returns the value of an extended attribute of an entry, symbolic
+    /// links are followed.
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        let _ = (path, name);
+        Err(FsError::Unsupported)
+    }
+
+    /// Creates or replaces an extended attribute of an entry.
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        let _ = (path, name, value);
+        Err(FsError::Unsupported)
+    }
+
+    /// Lists the names of the extended attributes of an entry.
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>> {
+        let _ = path;
+        Err(FsError::Unsupported)
+    }
+
+    /// Removes an extended attribute from an entry.
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        let _ = (path, name);
+        Err(FsError::Unsupported)
+    }
+
+    /// Starts watching an entry for changes (see [`watch`]), symbolic
+    /// links are followed. When the entry is a directory the changes made
+    /// to its immediate children are reported as well.
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+        let _ = (path, mask);
+        Err(FsError::Unsupported)
+    }
+
     fn new_open_options(&self) -> OpenOptions;
 
     fn mount(&self, name: String, path: &Path, fs: Box<dyn FileSystem + Send + Sync>)
@@ -153,6 +215,38 @@ where
         (**self).remove_file(path)
     }
 
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        (**self).symlink(original, link)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        (**self).hard_link(original, link)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        (**self).set_permissions(path, permissions)
+    }
+
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        (**self).get_xattr(path, name)
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        (**self).set_xattr(path, name, value)
+    }
+
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>> {
+        (**self).list_xattr(path)
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        (**self).remove_xattr(path, name)
+    }
+
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+        (**self).watch(path, mask)
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         (**self).new_open_options()
     }
@@ -366,6 +460,53 @@ pub trait VirtualFile:
     /// Request deletion of the file
     fn unlink(&mut self) -> Result<()>;
 
+    /// Places (or releases) an advisory lock of `owner` on the file, the
+    /// lock is held until it is changed, [`VirtualFile::unlock_all`] is
+    /// called for the owner or the handle is dropped. Fails with
+    /// [`FsError::WouldBlock`] when another owner holds a conflicting lock.
+    fn try_lock(&mut self, owner: LockOwner, lock: FileLock, range: LockRange) -> Result<()> {
+        let _ = (owner, lock, range);
+        Err(FsError::Unsupported)
+    }
+
+    /// Same as [`VirtualFile::try_lock`] except that instead of failing
+    /// when another owner holds a conflicting lock the task is woken up
+    /// once that lock is released
+    fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Poll<Result<()>> {
+        let _ = cx;
+        Poll::Ready(self.try_lock(owner, lock, range))
+    }
+
+    /// Returns a lock held by another owner that would prevent `owner`
+    /// from placing `lock` on the file (see `F_OFD_GETLK`)
+    fn test_lock(
+        &mut self,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Result<Option<(FileLock, LockRange)>> {
+        let _ = (owner, lock, range);
+        Err(FsError::Unsupported)
+    }
+
+    /// Releases all the locks that `owner` holds on the file, this is
+    /// called when the open file description they belong to is closed
+    fn unlock_all(&mut self, owner: LockOwner) {
+        let _ = owner;
+    }
+
+    /// Flushes the file and makes sure its data reached the storage that
+    /// backs it (see `fsync(2)`). Defaults to only flushing the file
+    fn poll_sync(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+
     /// Indicates if the file is opened or closed. This function must not block
     /// Defaults to a status of being constantly open
     fn is_open(&self) -> bool {
@@ -379,6 +520,14 @@ pub trait VirtualFile:
         None
     }
 
+    /// Creates an independent copy of the file, used when the file system
+    /// holding it is forked or snapshotted. Returns `None` for the files
+    /// that can not be copied (for instance pipes to the host), those
+    /// stay shared by the copies of the file system.
+    fn duplicate(&self) -> Option<Box<dyn VirtualFile + Send + Sync>> {
+        None
+    }
+
     /// Writes to this file using an mmap offset and reference
     /// (this method only works for mmap optimized file systems)
     fn write_from_mmap(&mut self, _offset: u64, _len: u64) -> std::io::Result<()> {
@@ -442,6 +591,39 @@ impl<T: Any + fmt::Debug + 'static> Upcastable for T {
     }
 }
 
+/// Advisory locks that can be placed on a file (see `flock(2)` and `fcntl(2)`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FileLock {
+    /// Any number of owners may hold a shared lock at the same time
+    Shared,
+    /// Only a single owner may hold an exclusive lock
+    Exclusive,
+    /// Releases the lock held by the owner
+    Unlock,
+}
+
+/// The part of a file that an advisory lock covers, whole file locks and
+/// byte range locks are independent of each other (as they are on Linux)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LockRange {
+    /// The whole file, as locked by `flock(2)`
+    File,
+    /// A range of bytes, as locked by `fcntl(2)` with `F_OFD_SETLK`. A
+    /// `len` of zero extends the range to the end of the file, however
+    /// large it grows.
+    Bytes { start: u64, len: u64 },
+}
+
+/// Identifies who holds an advisory lock, this is the open file
+/// description that placed it which is shared by all of its duplicates
+pub type LockOwner = u64;
+
+/// Returns a lock owner that has never been handed out before
+pub fn new_lock_owner() -> LockOwner {
+    static NEXT_OWNER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+    NEXT_OWNER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
 /// Determines the mode that stdio handlers will operate in
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum StdioMode {
@@ -533,6 +715,9 @@ pub enum FsError {
     DirectoryNotEmpty,
     #[error("storage full")]
     StorageFull,
+    /// The entry does not have the requested extended attribute
+    #[error("extended attribute not found")]
+    AttributeNotFound,
     /// Some other unhandled error. If you see this, it's probably a bug.
     #[error("unknown error found")]
     UnknownError,
@@ -561,8 +746,7 @@ impl From<io::Error> for FsError {
             io::ErrorKind::UnexpectedEof => FsError::UnexpectedEof,
             io::ErrorKind::WouldBlock => FsError::WouldBlock,
             io::ErrorKind::WriteZero => FsError::WriteZero,
-            // NOTE: Add this once the "io_error_more" Rust feature is stabilized
-            // io::ErrorKind::StorageFull => FsError::StorageFull,
+            io::ErrorKind::StorageFull => FsError::StorageFull,
             io::ErrorKind::Other => FsError::IOError,
             // if the following triggers, a new error type was added to this non-exhaustive enum
             _ => FsError::UnknownError,
@@ -598,10 +782,9 @@ impl From<FsError> for io::Error {
             FsError::NoDevice => io::ErrorKind::Other,
             FsError::DirectoryNotEmpty => io::ErrorKind::Other,
             FsError::UnknownError => io::ErrorKind::Other,
-            FsError::StorageFull => io::ErrorKind::Other,
+            FsError::StorageFull => io::ErrorKind::StorageFull,
+            FsError::AttributeNotFound => io::ErrorKind::NotFound,
             FsError::Unsupported => io::ErrorKind::Unsupported,
-            // NOTE: Add this once the "io_error_more" Rust feature is stabilized
-            // FsError::StorageFull => io::ErrorKind::StorageFull,
         };
         kind.into()
     }
@@ -665,6 +848,9 @@ pub struct Metadata {
     pub created: u64,
     pub modified: u64,
     pub len: u64,
+    /// Mode bits and ownership, `None` when the file system does not keep
+    /// track of them (in which case permissive defaults are assumed)
+    pub permissions: Option<Permissions>,
 }
 
 impl Metadata {
@@ -695,6 +881,10 @@ impl Metadata {
     pub fn len(&self) -> u64 {
         self.len
     }
+
+    pub fn permissions(&self) -> Option<Permissions> {
+        self.permissions
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
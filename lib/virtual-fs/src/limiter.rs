@@ -110,6 +110,18 @@ mod tracked_vec {
             }
             Ok(())
         }
+
+        /// Copies the data, the copy is accounted for by the limiter
+        pub fn try_clone(&self) -> Result<Self, FsError> {
+            let data = self.data.clone();
+            if let Some(limiter) = &self.limiter {
+                limiter.on_grow(data.capacity())?;
+            }
+            Ok(Self {
+                data,
+                limiter: self.limiter.clone(),
+            })
+        }
     }
 
     impl Drop for TrackedVec {
@@ -192,6 +204,12 @@ mod tracked_vec {
             self.data.reserve_exact(additional);
             Ok(())
         }
+
+        pub fn try_clone(&self) -> Result<Self, FsError> {
+            Ok(Self {
+                data: self.data.clone(),
+            })
+        }
     }
 
     impl std::ops::Deref for TrackedVec {
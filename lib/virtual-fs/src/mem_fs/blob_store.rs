@@ -0,0 +1,211 @@
+//! A content-addressed store for the contents of offloaded files.
+//!
+//! The files are split into chunks of [`CHUNK_SIZE`] bytes (aligned on their
+//! offset in the file) which are stored on the local disk under the SHA-256
+//! of their contents. Identical chunks written by any of the file systems
+//! that share a [`BlobStore`] are thus only stored once, and as the chunks
+//! are memory mapped they also only take up memory once.
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs, io,
+    ops::RangeBounds,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use sha2::{Digest, Sha256};
+use shared_buffer::OwnedBuffer;
+
+/// Size of the chunks that the files are split into
+pub const CHUNK_SIZE: u64 = 256 * 1024;
+
+type Hash = [u8; 32];
+
+/// Content-addressed, reference counted store of chunks of file data kept
+/// in a directory of the local disk.
+///
+/// The directory belongs to the store: the chunks that are no longer used
+/// by any file are removed from it.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    inner: Arc<BlobStoreInner>,
+}
+
+#[derive(Debug)]
+struct BlobStoreInner {
+    root: PathBuf,
+    chunks: Mutex<HashMap<Hash, Weak<Chunk>>>,
+}
+
+#[derive(Debug)]
+struct Chunk {
+    hash: Hash,
+    data: OwnedBuffer,
+    store: Arc<BlobStoreInner>,
+}
+
+/// A reference to (part of) a chunk of a [`BlobStore`], the chunk is kept
+/// around for as long as it is referenced.
+#[derive(Debug, Clone)]
+pub struct BlobChunk {
+    chunk: Arc<Chunk>,
+    data: OwnedBuffer,
+}
+
+impl BlobStore {
+    /// Opens (or creates) a store in a directory, the chunks that are
+    /// already in there are reused.
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Self {
+            inner: Arc::new(BlobStoreInner {
+                root,
+                chunks: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Number of distinct chunks that are currently referenced
+    pub fn chunk_count(&self) -> usize {
+        let chunks = self.inner.chunks.lock().unwrap();
+        chunks.values().filter(|c| c.strong_count() > 0).count()
+    }
+
+    /// Stores a chunk of data, or takes another reference to it if an
+    /// identical one is already stored
+    pub(super) fn insert(&self, data: &[u8]) -> io::Result<BlobChunk> {
+        let hash: Hash = Sha256::digest(data).into();
+
+        let mut chunks = self.inner.chunks.lock().unwrap();
+        if let Some(chunk) = chunks.get(&hash).and_then(Weak::upgrade) {
+            return Ok(BlobChunk::new(chunk));
+        }
+
+        let path = self.inner.chunk_path(&hash);
+        let exists = fs::metadata(&path).is_ok_and(|m| m.len() == data.len() as u64);
+        if !exists {
+            self.inner.write_chunk(&path, data)?;
+        }
+        let file = fs::File::open(&path)?;
+        let mapped = OwnedBuffer::from_file(&file)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+        let chunk = Arc::new(Chunk {
+            hash,
+            data: mapped,
+            store: self.inner.clone(),
+        });
+        chunks.insert(hash, Arc::downgrade(&chunk));
+        Ok(BlobChunk::new(chunk))
+    }
+}
+
+impl BlobStoreInner {
+    fn chunk_path(&self, hash: &Hash) -> PathBuf {
+        let name = hash.iter().fold(String::new(), |mut name, b| {
+            let _ = write!(name, "{b:02x}");
+            name
+        });
+        self.root.join(&name[..2]).join(name)
+    }
+
+    /// Writes the chunk under a temporary name first so that a chunk is
+    /// never seen half written
+    fn write_chunk(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir)?;
+        let temp = dir.join(format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, data)?;
+        fs::rename(&temp, path).inspect_err(|_| {
+            fs::remove_file(&temp).ok();
+        })
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        let mut chunks = self.store.chunks.lock().unwrap();
+        // The chunk might have been stored again after the last reference
+        // was dropped but before we got the lock
+        if chunks
+            .get(&self.hash)
+            .is_some_and(|chunk| chunk.strong_count() == 0)
+        {
+            chunks.remove(&self.hash);
+            let path = self.store.chunk_path(&self.hash);
+            if let Err(err) = fs::remove_file(&path) {
+                tracing::debug!(path=%path.display(), %err, "failed to remove an unused chunk");
+            }
+        }
+    }
+}
+
+impl BlobChunk {
+    fn new(chunk: Arc<Chunk>) -> Self {
+        let data = chunk.data.clone();
+        Self { chunk, data }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
+        Self {
+            chunk: self.chunk.clone(),
+            data: self.data.slice(range),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(dir.path()).unwrap();
+
+        let a = store.insert(b"hello world").unwrap();
+        let b = store.insert(b"hello world").unwrap();
+        let c = store.insert(b"something else").unwrap();
+        assert_eq!(store.chunk_count(), 2);
+        assert_eq!(a.as_slice(), b"hello world");
+        assert_eq!(b.slice(6..).as_slice(), b"world");
+        assert_eq!(c.len(), 14);
+
+        // Chunks are removed once they are no longer referenced
+        let path = store
+            .inner
+            .chunk_path(&Sha256::digest(b"hello world").into());
+        assert!(path.exists());
+        let world = a.slice(6..);
+        drop((a, b, c));
+        assert_eq!(store.chunk_count(), 1);
+        assert!(path.exists());
+        drop(world);
+        assert_eq!(store.chunk_count(), 0);
+        assert!(!path.exists());
+    }
+}
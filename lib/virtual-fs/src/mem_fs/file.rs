@@ -11,8 +11,12 @@ use self::offloaded_file::OffloadWrite;
 
 use super::*;
 use crate::limiter::TrackedVec;
-use crate::{CopyOnWriteFile, FsError, Result, VirtualFile};
+use crate::{
+    CopyOnWriteFile, FileLock, FsError, LockOwner, LockRange, Result, VirtualFile, WatchEvent,
+    WatchEventKind,
+};
 use std::cmp;
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::fmt;
 use std::io;
@@ -34,6 +38,7 @@ pub(super) struct FileHandle {
     append_mode: bool,
     cursor: u64,
     arc_file: Option<Result<Box<dyn VirtualFile + Send + Sync + 'static>>>,
+    lock_owners: HashSet<LockOwner>,
 }
 
 impl Clone for FileHandle {
@@ -46,6 +51,30 @@ impl Clone for FileHandle {
             append_mode: self.append_mode,
             cursor: self.cursor,
             arc_file: None,
+            lock_owners: HashSet::new(),
+        }
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        if self.lock_owners.is_empty() && !self.writable {
+            return;
+        }
+        if let Ok(mut fs) = self.filesystem.inner.write() {
+            // Advisory locks are released when the handle they were placed through is closed
+            for owner in self.lock_owners.drain() {
+                fs.locks.release(self.inode, owner);
+            }
+            if self.writable {
+                // Closing a file moves what was written to it into the blob store
+                if let Some(Node::OffloadedFile(node)) = fs.storage.get_mut(self.inode) {
+                    if let Err(err) = node.file.flush() {
+                        tracing::warn!(inode = self.inode, %err, "failed to flush an offloaded file");
+                    }
+                }
+                fs.notify_changed(self.inode, WatchEventKind::CloseWrite);
+            }
         }
     }
 }
@@ -67,6 +96,14 @@ impl FileHandle {
             append_mode,
             cursor,
             arc_file: None,
+            lock_owners: HashSet::new(),
+        }
+    }
+
+    /// Tells the watches of the file that it was written to
+    fn notify_modified(&self) {
+        if let Ok(mut fs) = self.filesystem.inner.write() {
+            fs.notify_changed(self.inode, WatchEventKind::Modify);
         }
     }
 
@@ -197,32 +234,35 @@ impl VirtualFile for FileHandle {
     }
 
     fn set_len(&mut self, new_size: u64) -> Result<()> {
-        let mut fs = self.filesystem.inner.write().map_err(|_| FsError::Lock)?;
+        {
+            let mut fs = self.filesystem.inner.write().map_err(|_| FsError::Lock)?;
 
-        let inode = fs.storage.get_mut(self.inode);
-        match inode {
-            Some(Node::File(FileNode { file, metadata, .. })) => {
-                file.buffer
-                    .resize(new_size.try_into().map_err(|_| FsError::UnknownError)?, 0)?;
-                metadata.len = new_size;
-            }
-            Some(Node::OffloadedFile(OffloadedFileNode { file, metadata, .. })) => {
-                file.resize(new_size, 0);
-                metadata.len = new_size;
-            }
-            Some(Node::CustomFile(node)) => {
-                let mut file = node.file.lock().unwrap();
-                file.set_len(new_size)?;
-                node.metadata.len = new_size;
-            }
-            Some(Node::ReadOnlyFile { .. }) => return Err(FsError::PermissionDenied),
-            Some(Node::ArcFile { .. }) => {
-                drop(fs);
-                let file = self.lazy_load_arc_file_mut()?;
-                file.set_len(new_size)?;
+            let inode = fs.storage.get_mut(self.inode);
+            match inode {
+                Some(Node::File(FileNode { file, metadata, .. })) => {
+                    file.buffer_mut()?
+                        .resize(new_size.try_into().map_err(|_| FsError::UnknownError)?, 0)?;
+                    metadata.len = new_size;
+                }
+                Some(Node::OffloadedFile(OffloadedFileNode { file, metadata, .. })) => {
+                    file.resize(new_size, 0);
+                    metadata.len = new_size;
+                }
+                Some(Node::CustomFile(node)) => {
+                    let mut file = node.file.lock().unwrap();
+                    file.set_len(new_size)?;
+                    node.metadata.len = new_size;
+                }
+                Some(Node::ReadOnlyFile { .. }) => return Err(FsError::PermissionDenied),
+                Some(Node::ArcFile { .. }) => {
+                    drop(fs);
+                    let file = self.lazy_load_arc_file_mut()?;
+                    file.set_len(new_size)?;
+                }
+                _ => return Err(FsError::NotAFile),
             }
-            _ => return Err(FsError::NotAFile),
         }
+        self.notify_modified();
 
         Ok(())
     }
@@ -265,16 +305,79 @@ impl VirtualFile for FileHandle {
             // Write lock.
             let mut fs = filesystem.inner.write().map_err(|_| FsError::Lock)?;
 
-            // Remove the file from the storage.
-            fs.storage.remove(inode_of_file);
+            let name_of_file = fs
+                .storage
+                .get(inode_of_file)
+                .ok_or(FsError::UnknownError)?
+                .name()
+                .to_os_string();
+
+            // Remove the file from the storage and its parent directory.
+            fs.unlink_node(inode_of_parent, position, inode_of_file)?;
+            fs.notify_entry(
+                inode_of_parent,
+                WatchEvent::new(WatchEventKind::Delete).with_name(name_of_file, false),
+            );
+        }
+
+        Ok(())
+    }
 
-            // Remove the child from the parent directory.
-            fs.remove_child_from_node(inode_of_parent, position)?;
+    fn try_lock(&mut self, owner: LockOwner, lock: FileLock, range: LockRange) -> Result<()> {
+        let mut fs = self.filesystem.inner.write().map_err(|_| FsError::Lock)?;
+        if fs.storage.get(self.inode).is_none() {
+            return Err(FsError::EntryNotFound);
         }
 
+        fs.locks.try_lock(self.inode, owner, lock, range)?;
+        self.lock_owners.insert(owner);
         Ok(())
     }
 
+    fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Poll<Result<()>> {
+        let mut fs = match self.filesystem.inner.write() {
+            Ok(fs) => fs,
+            Err(_) => return Poll::Ready(Err(FsError::Lock)),
+        };
+        if fs.storage.get(self.inode).is_none() {
+            return Poll::Ready(Err(FsError::EntryNotFound));
+        }
+
+        let res = fs.locks.poll_lock(cx, self.inode, owner, lock, range);
+        if let Poll::Ready(Ok(())) = res {
+            self.lock_owners.insert(owner);
+        }
+        res
+    }
+
+    fn test_lock(
+        &mut self,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Result<Option<(FileLock, LockRange)>> {
+        let fs = self.filesystem.inner.read().map_err(|_| FsError::Lock)?;
+        if fs.storage.get(self.inode).is_none() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        Ok(fs.locks.test_lock(self.inode, owner, lock, range))
+    }
+
+    fn unlock_all(&mut self, owner: LockOwner) {
+        if self.lock_owners.remove(&owner) {
+            if let Ok(mut fs) = self.filesystem.inner.write() {
+                fs.locks.release(self.inode, owner);
+            }
+        }
+    }
+
     fn get_special_fd(&self) -> Option<u32> {
         let fs = match self.filesystem.inner.read() {
             Ok(a) => a,
@@ -327,13 +430,15 @@ impl VirtualFile for FileHandle {
                         created: src.created_time(),
                         modified: src.last_modified(),
                         len: src.size(),
+                        permissions: inode.metadata().permissions,
                     };
 
                     *inode = Node::CustomFile(CustomFileNode {
                         inode: inode.inode(),
                         name: inode.name().to_string_lossy().to_string().into(),
-                        file: Mutex::new(Box::new(CopyOnWriteFile::new(src))),
+                        file: Arc::new(Mutex::new(Box::new(CopyOnWriteFile::new(src)))),
                         metadata,
+                        xattrs: inode.xattrs().cloned().unwrap_or_default(),
                     });
                     Ok(())
                 }
@@ -359,6 +464,7 @@ impl VirtualFile for FileHandle {
                         created: 1,
                         modified: 1,
                         len: src.len() as u64,
+                        permissions: inode.metadata().permissions,
                     };
 
                     *inode = Node::ReadOnlyFile(ReadOnlyFileNode {
@@ -366,6 +472,7 @@ impl VirtualFile for FileHandle {
                         name: inode.name().to_string_lossy().to_string().into(),
                         file: ReadOnlyFile { buffer: src },
                         metadata,
+                        xattrs: inode.xattrs().cloned().unwrap_or_default(),
                     });
                     Ok(())
                 }
@@ -505,13 +612,14 @@ impl VirtualFile for FileHandle {
             }
         }
         self.cursor = cursor;
+        self.notify_modified();
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod test_virtual_file {
-    use crate::{mem_fs::*, FileSystem as FS};
+    use crate::{mem_fs::*, new_lock_owner, FileLock, FileSystem as FS, FsError, LockRange};
     use std::thread::sleep;
     use std::time::Duration;
 
@@ -684,6 +792,68 @@ mod test_virtual_file {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_try_lock() {
+        let fs = FileSystem::default();
+        let open = |fs: &FileSystem| {
+            fs.new_open_options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(path!("/foo.txt"))
+                .unwrap()
+        };
+
+        let mut a = open(&fs);
+        let mut b = open(&fs);
+        let (oa, ob, oc) = (new_lock_owner(), new_lock_owner(), new_lock_owner());
+        let file = LockRange::File;
+
+        assert_eq!(a.try_lock(oa, FileLock::Shared, file), Ok(()));
+        assert_eq!(b.try_lock(ob, FileLock::Shared, file), Ok(()));
+        assert_eq!(
+            b.try_lock(ob, FileLock::Exclusive, file),
+            Err(FsError::WouldBlock),
+            "another owner holds a shared lock",
+        );
+
+        drop(a);
+        assert_eq!(
+            b.try_lock(ob, FileLock::Exclusive, file),
+            Ok(()),
+            "closing a handle releases its lock",
+        );
+
+        // Locks belong to their owner rather than to the handle
+        assert_eq!(
+            b.try_lock(oc, FileLock::Shared, file),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            b.test_lock(oc, FileLock::Shared, file),
+            Ok(Some((FileLock::Exclusive, file)))
+        );
+        b.unlock_all(ob);
+        assert_eq!(b.try_lock(oc, FileLock::Shared, file), Ok(()));
+
+        let mut c = open(&fs);
+        assert_eq!(
+            c.try_lock(oa, FileLock::Exclusive, file),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            c.try_lock(
+                oa,
+                FileLock::Exclusive,
+                LockRange::Bytes { start: 0, len: 0 }
+            ),
+            Ok(()),
+            "byte range locks are independent of whole file locks",
+        );
+        assert_eq!(b.try_lock(oc, FileLock::Unlock, file), Ok(()));
+        assert_eq!(c.try_lock(oa, FileLock::Exclusive, file), Ok(()));
+    }
 }
 
 impl AsyncRead for FileHandle {
@@ -985,6 +1155,9 @@ impl AsyncWrite for FileHandle {
             }
         };
         self.cursor = cursor;
+        if bytes_written > 0 {
+            self.notify_modified();
+        }
         Poll::Ready(Ok(bytes_written))
     }
 
@@ -1053,6 +1226,9 @@ impl AsyncWrite for FileHandle {
             }
         };
         self.cursor = cursor;
+        if matches!(ret, Poll::Ready(Ok(bytes_written)) if bytes_written > 0) {
+            self.notify_modified();
+        }
         ret
     }
 
@@ -1453,20 +1629,34 @@ impl fmt::Debug for FileHandle {
 
 /// The real file! It is simply a buffer of bytes with a cursor that
 /// represents a read/write position in the buffer.
-#[derive(Debug)]
+///
+/// The buffer is shared between the copies of the file made by snapshots
+/// of the file system, it is only copied once one of them writes to it.
+#[derive(Debug, Clone)]
 pub(super) struct File {
-    buffer: TrackedVec,
+    buffer: Arc<TrackedVec>,
 }
 
 impl File {
     pub(super) fn new(limiter: Option<crate::limiter::DynFsMemoryLimiter>) -> Self {
         Self {
-            buffer: TrackedVec::new(limiter),
+            buffer: Arc::new(TrackedVec::new(limiter)),
         }
     }
 
     pub(super) fn truncate(&mut self) {
-        self.buffer.clear();
+        match Arc::get_mut(&mut self.buffer) {
+            Some(buffer) => buffer.clear(),
+            None => self.buffer = Arc::new(TrackedVec::new(self.buffer.limiter().cloned())),
+        }
+    }
+
+    /// Gives mutable access to the buffer, copying it first if it is shared
+    fn buffer_mut(&mut self) -> Result<&mut TrackedVec> {
+        if Arc::get_mut(&mut self.buffer).is_none() {
+            self.buffer = Arc::new(self.buffer.try_clone()?);
+        }
+        Ok(Arc::get_mut(&mut self.buffer).expect("the buffer was just copied"))
     }
 
     pub(super) fn len(&self) -> usize {
@@ -1531,15 +1721,16 @@ impl File {
 impl File {
     pub fn write(&mut self, buf: &[u8], cursor: &mut u64) -> io::Result<usize> {
         let position = *cursor as usize;
+        let buffer = self.buffer_mut()?;
 
-        if position + buf.len() > self.buffer.len() {
+        if position + buf.len() > buffer.len() {
             // Writing past the end of the current buffer, must reallocate
-            let len_after_end = (position + buf.len()) - self.buffer.len();
+            let len_after_end = (position + buf.len()) - buffer.len();
             let let_to_end = buf.len() - len_after_end;
-            self.buffer[position..position + let_to_end].copy_from_slice(&buf[0..let_to_end]);
-            self.buffer.extend_from_slice(&buf[let_to_end..buf.len()])?;
+            buffer[position..position + let_to_end].copy_from_slice(&buf[0..let_to_end]);
+            buffer.extend_from_slice(&buf[let_to_end..buf.len()])?;
         } else {
-            self.buffer[position..position + buf.len()].copy_from_slice(buf);
+            buffer[position..position + buf.len()].copy_from_slice(buf);
         }
 
         *cursor += buf.len() as u64;
@@ -1553,7 +1744,7 @@ impl File {
 }
 
 /// Read only file that uses copy-on-write
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(super) struct ReadOnlyFile {
     buffer: OwnedBuffer,
 }
@@ -1,6 +1,9 @@
 use super::filesystem::InodeResolution;
 use super::*;
-use crate::{FileType, FsError, Metadata, OpenOptionsConfig, Result, VirtualFile};
+use crate::{
+    FileType, FsError, Metadata, OpenOptionsConfig, Permissions, Result, VirtualFile,
+    WatchEventKind,
+};
 use shared_buffer::OwnedBuffer;
 use std::path::Path;
 use tracing::*;
@@ -32,7 +35,7 @@ impl FileSystem {
                 let file_len = file.len() as u64;
 
                 // Creating the file in the storage.
-                let inode_of_file = fs.storage.vacant_entry().key();
+                let inode_of_file = fs.storage.vacant_key();
                 let real_inode_of_file = fs.storage.insert(Node::ReadOnlyFile(ReadOnlyFileNode {
                     inode: inode_of_file,
                     name: name_of_file,
@@ -49,8 +52,10 @@ impl FileSystem {
                             created: time,
                             modified: time,
                             len: file_len,
+                            permissions: Some(Permissions::new_file()),
                         }
                     },
+                    xattrs: Default::default(),
                 }));
 
                 assert_eq!(
@@ -60,6 +65,7 @@ impl FileSystem {
 
                 // Adding the new directory to its parent.
                 fs.add_child_to_node(inode_of_parent, inode_of_file)?;
+                fs.notify_new_entry(inode_of_parent, inode_of_file);
 
                 inode_of_file
             }
@@ -109,12 +115,13 @@ impl FileSystem {
                             created: time,
                             modified: time,
                             len: 0,
+                            permissions: Some(Permissions::new_file()),
                         }
                     }
                 };
 
                 // Creating the file in the storage.
-                let inode_of_file = fs_lock.storage.vacant_entry().key();
+                let inode_of_file = fs_lock.storage.vacant_key();
                 let real_inode_of_file = fs_lock.storage.insert(Node::ArcFile(ArcFileNode {
                     inode: inode_of_file,
                     name: name_of_file,
@@ -176,7 +183,7 @@ impl FileSystem {
                 let mut fs_lock = self.inner.write().map_err(|_| FsError::Lock)?;
 
                 // Creating the file in the storage.
-                let inode_of_file = fs_lock.storage.vacant_entry().key();
+                let inode_of_file = fs_lock.storage.vacant_key();
                 let real_inode_of_file =
                     fs_lock.storage.insert(Node::ArcDirectory(ArcDirectoryNode {
                         inode: inode_of_file,
@@ -194,6 +201,7 @@ impl FileSystem {
                                 created: time,
                                 modified: time,
                                 len: 0,
+                                permissions: Some(Permissions::new_file()),
                             }
                         },
                     }));
@@ -249,11 +257,11 @@ impl FileSystem {
         let mut fs_lock = self.inner.write().map_err(|_| FsError::Lock)?;
 
         // Creating the file in the storage.
-        let inode_of_file = fs_lock.storage.vacant_entry().key();
+        let inode_of_file = fs_lock.storage.vacant_key();
         let real_inode_of_file = fs_lock.storage.insert(Node::CustomFile(CustomFileNode {
             inode: inode_of_file,
             name: name_of_file,
-            file: Mutex::new(file),
+            file: Arc::new(Mutex::new(file)),
             metadata: {
                 let time = time();
                 Metadata {
@@ -265,8 +273,10 @@ impl FileSystem {
                     created: time,
                     modified: time,
                     len: 0,
+                    permissions: Some(Permissions::new_file()),
                 }
             },
+            xattrs: Default::default(),
         }));
 
         assert_eq!(
@@ -311,7 +321,12 @@ impl FileSystem {
         // Find the inode of the file if it exists.
         let maybe_inode_of_file = fs
             .as_parent_get_position_and_inode_of_file(inode_of_parent, &name_of_file)?
-            .map(|(_nth, inode)| inode);
+            .map(|(_nth, inode)| match inode {
+                InodeResolution::Found(inode) => {
+                    InodeResolution::Found(fs.resolve_hard_link(inode))
+                }
+                redirect => redirect,
+            });
 
         Ok((
             InodeResolution::Found(inode_of_parent),
@@ -353,7 +368,14 @@ impl crate::FileOpener for FileSystem {
             write = false;
         }
 
-        let (inode_of_parent, maybe_inode_of_file, name_of_file) = self.insert_inode(path)?;
+        // Opening a symbolic link opens the file that it points to.
+        let path = self
+            .inner
+            .read()
+            .map_err(|_| FsError::Lock)?
+            .resolve_symlinks(path)?;
+
+        let (inode_of_parent, maybe_inode_of_file, name_of_file) = self.insert_inode(&path)?;
 
         let inode_of_parent = match inode_of_parent {
             InodeResolution::Found(a) => a,
@@ -476,6 +498,10 @@ impl crate::FileOpener for FileSystem {
                     _ => return Err(FsError::NotAFile),
                 }
 
+                if truncate {
+                    fs.notify_changed(inode_of_file, WatchEventKind::Modify);
+                }
+
                 inode_of_file
             }
 
@@ -497,9 +523,10 @@ impl crate::FileOpener for FileSystem {
                         created: time,
                         modified: time,
                         len: 0,
+                        permissions: Some(Permissions::new_file()),
                     }
                 };
-                let inode_of_file = fs.storage.vacant_entry().key();
+                let inode_of_file = fs.storage.vacant_key();
 
                 // We might be in optimized mode
                 let file = if let Some(offload) = fs.backing_offload.clone() {
@@ -509,6 +536,7 @@ impl crate::FileOpener for FileSystem {
                         name: name_of_file,
                         file,
                         metadata,
+                        xattrs: Default::default(),
                     })
                 } else {
                     let file = File::new(fs.limiter.clone());
@@ -517,6 +545,7 @@ impl crate::FileOpener for FileSystem {
                         name: name_of_file,
                         file,
                         metadata,
+                        xattrs: Default::default(),
                     })
                 };
 
@@ -530,6 +559,7 @@ impl crate::FileOpener for FileSystem {
 
                 // Adding the new directory to its parent.
                 fs.add_child_to_node(inode_of_parent, inode_of_file)?;
+                fs.notify_new_entry(inode_of_parent, inode_of_file);
 
                 inode_of_file
             }
@@ -1,9 +1,14 @@
 //! This module contains the [`FileSystem`] type itself.
 
 use self::offloaded_file::OffloadBackingStore;
+use super::locks::LockTable;
+use super::watches::{new_rename_cookie, WatchTable};
 
 use super::*;
-use crate::{DirEntry, FileType, FsError, Metadata, OpenOptions, ReadDir, Result};
+use crate::{
+    DirEntry, FileType, FsError, Metadata, OpenOptions, Permissions, ReadDir, Result, Watch,
+    WatchEvent, WatchEventKind, WatchMask,
+};
 use futures::future::{BoxFuture, Either};
 use slab::Slab;
 use std::collections::VecDeque;
@@ -13,6 +18,11 @@ use std::fmt;
 use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
+/// The longest name an extended attribute can have (as on Linux)
+const XATTR_NAME_MAX: usize = 255;
+/// The largest value an extended attribute can hold (as on Linux)
+const XATTR_SIZE_MAX: usize = 64 * 1024;
+
 /// The in-memory file system!
 ///
 /// This `FileSystem` type can be cloned, it's a light copy of the
@@ -45,6 +55,55 @@ impl FileSystem {
         Ok(self)
     }
 
+    /// Takes a point-in-time snapshot of all the entries of the file system.
+    ///
+    /// This is O(1): the snapshot shares the nodes and the contents of the
+    /// files with the file system, a change made after the snapshot copies
+    /// the table of pointers to the nodes (once) and the node it changes,
+    /// the contents of a file are copied when it is written to. The memory
+    /// limiter only accounts for the copies of the contents.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let lock = self.inner.read().map_err(|_| FsError::Lock)?;
+        Ok(Snapshot {
+            storage: lock.storage.clone(),
+            backing_offload: lock.backing_offload.clone(),
+            limiter: lock.limiter.clone(),
+        })
+    }
+
+    /// Creates a new file system that starts out with the entries of a
+    /// snapshot, without any lock or watch.
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let inner = FileSystemInner {
+            storage: snapshot.storage.clone(),
+            backing_offload: snapshot.backing_offload.clone(),
+            limiter: snapshot.limiter.clone(),
+            locks: LockTable::default(),
+            watches: WatchTable::default(),
+        };
+        Self {
+            inner: Arc::new(RwLock::new(inner)),
+        }
+    }
+
+    /// Copies the file system in O(1), unlike [`Clone::clone`] (which gives
+    /// another handle to the same file system) the copy is independent.
+    pub fn fork(&self) -> Result<Self> {
+        Ok(Self::from_snapshot(&self.snapshot()?))
+    }
+
+    /// Brings all the entries of the file system back to how they were
+    /// when the snapshot was taken.
+    ///
+    /// The watches are all removed. Handles that are still open refer to
+    /// their entry by its inode, so they should be closed beforehand.
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<()> {
+        let mut lock = self.inner.write().map_err(|_| FsError::Lock)?;
+        lock.storage = snapshot.storage.clone();
+        lock.watches.clear();
+        Ok(())
+    }
+
     /// Canonicalize a path without validating that it actually exists.
     pub fn canonicalize_unchecked(&self, path: &Path) -> Result<PathBuf> {
         let lock = self.inner.read().map_err(|_| FsError::Lock)?;
@@ -221,7 +280,7 @@ impl FileSystem {
             let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
             // Creating the directory in the storage.
-            let inode_of_directory = fs.storage.vacant_entry().key();
+            let inode_of_directory = fs.storage.vacant_key();
             let real_inode_of_directory = fs.storage.insert(Node::ArcDirectory(ArcDirectoryNode {
                 inode: inode_of_directory,
                 name: name_of_directory,
@@ -239,6 +298,7 @@ impl FileSystem {
                         created: time,
                         modified: time,
                         len: 0,
+                        permissions: Some(Permissions::new_dir()),
                     }
                 },
             }));
@@ -261,11 +321,20 @@ impl crate::FileSystem for FileSystem {
         // Read lock.
         let guard = self.inner.read().map_err(|_| FsError::Lock)?;
 
-        // Canonicalize the path.
-        let (_, inode_of_directory) = guard.canonicalize(path)?;
-        match inode_of_directory {
-            InodeResolution::Found(_) => Err(FsError::InvalidInput),
-            InodeResolution::Redirect(fs, path) => fs.readlink(path.as_path()),
+        // Canonicalize the path, the link itself must not be followed.
+        let path = guard.canonicalize_without_inode(path)?;
+        match guard.inode_of_no_follow(&path)? {
+            InodeResolution::Found(inode) => match guard.storage.get(inode) {
+                Some(Node::Symlink(SymlinkNode { target, .. })) => Ok(target.clone()),
+                _ => Err(FsError::InvalidInput),
+            },
+            InodeResolution::Redirect(fs, mount_path) => {
+                drop(guard);
+                let target = fs.readlink(mount_path.as_path())?;
+
+                let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+                guard.link_target_from_mount(&path, target)
+            }
         }
     }
 
@@ -295,7 +364,11 @@ impl crate::FileSystem for FileSystem {
 
                         entry_path
                     },
-                    metadata: Ok(node.metadata().clone()),
+                    metadata: guard
+                        .storage
+                        .get(guard.resolve_hard_link(node.inode()))
+                        .map(|node| node.metadata().clone())
+                        .ok_or(FsError::UnknownError),
                 })
                 .collect(),
 
@@ -353,7 +426,7 @@ impl crate::FileSystem for FileSystem {
             let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
             // Creating the directory in the storage.
-            let inode_of_directory = fs.storage.vacant_entry().key();
+            let inode_of_directory = fs.storage.vacant_key();
             let real_inode_of_directory = fs.storage.insert(Node::Directory(DirectoryNode {
                 inode: inode_of_directory,
                 name: name_of_directory,
@@ -370,8 +443,10 @@ impl crate::FileSystem for FileSystem {
                         created: time,
                         modified: time,
                         len: 0,
+                        permissions: Some(Permissions::new_dir()),
                     }
                 },
+                xattrs: Default::default(),
             }));
 
             assert_eq!(
@@ -381,6 +456,7 @@ impl crate::FileSystem for FileSystem {
 
             // Adding the new directory to its parent.
             fs.add_child_to_node(inode_of_parent, inode_of_directory)?;
+            fs.notify_new_entry(inode_of_parent, inode_of_directory);
         }
 
         Ok(())
@@ -437,10 +513,16 @@ impl crate::FileSystem for FileSystem {
             let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
             // Remove the directory from the storage.
-            fs.storage.remove(inode_of_directory);
+            let name_of_directory = fs.storage.remove(inode_of_directory).name().to_os_string();
 
             // Remove the child from the parent directory.
             fs.remove_child_from_node(inode_of_parent, position)?;
+
+            fs.notify_entry(
+                inode_of_parent,
+                WatchEvent::new(WatchEventKind::Delete).with_name(name_of_directory, true),
+            );
+            fs.watches.remove(inode_of_directory);
         }
 
         Ok(())
@@ -534,19 +616,34 @@ impl crate::FileSystem for FileSystem {
                         let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
                         if let Some((position, inode_of_file)) = inode_dest {
-                            // Remove the file from the storage.
+                            // Remove the file from the storage and its parent.
                             match inode_of_file {
                                 InodeResolution::Found(inode_of_file) => {
-                                    fs.storage.remove(inode_of_file);
+                                    fs.unlink_node(inode_of_to_parent, position, inode_of_file)?;
                                 }
                                 InodeResolution::Redirect(..) => {
                                     return Err(FsError::InvalidInput);
                                 }
                             }
-
-                            fs.remove_child_from_node(inode_of_to_parent, position)?;
                         }
 
+                        let is_dir = fs.storage.get(inode).is_some_and(Node::is_dir);
+                        let cookie = new_rename_cookie();
+                        fs.notify_entry(
+                            inode_of_from_parent,
+                            WatchEvent::new(WatchEventKind::MovedFrom)
+                                .with_name(name_of_from, is_dir)
+                                .with_cookie(cookie),
+                        );
+                        fs.notify_entry(
+                            inode_of_to_parent,
+                            WatchEvent::new(WatchEventKind::MovedTo)
+                                .with_name(name_of_to.clone(), is_dir)
+                                .with_cookie(cookie),
+                        );
+                        fs.watches
+                            .notify(inode, WatchEvent::new(WatchEventKind::MoveSelf));
+
                         // Update the file name, and update the modified time.
                         fs.update_node_name(inode, name_of_to)?;
 
@@ -625,7 +722,7 @@ impl crate::FileSystem for FileSystem {
     fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
         // Read lock.
         let guard = self.inner.read().map_err(|_| FsError::Lock)?;
-        match guard.inode_of(path)? {
+        match guard.inode_of_no_follow(path)? {
             InodeResolution::Found(inode) => Ok(guard
                 .storage
                 .get(inode)
@@ -686,16 +783,279 @@ impl crate::FileSystem for FileSystem {
             // Write lock.
             let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
-            // Remove the file from the storage.
-            fs.storage.remove(inode_of_file);
+            let name_of_file = fs
+                .storage
+                .get(inode_of_file)
+                .ok_or(FsError::UnknownError)?
+                .name()
+                .to_os_string();
 
-            // Remove the child from the parent directory.
-            fs.remove_child_from_node(inode_of_parent, position)?;
+            // Remove the file from the storage and its parent directory.
+            fs.unlink_node(inode_of_parent, position, inode_of_file)?;
+            fs.notify_entry(
+                inode_of_parent,
+                WatchEvent::new(WatchEventKind::Delete).with_name(name_of_file, false),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        let (inode_of_parent, name_of_link) = {
+            // Read lock.
+            let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+
+            let (inode_of_parent, name_of_link) = guard.new_entry_location(link)?;
+            let inode_of_parent = match inode_of_parent {
+                InodeResolution::Found(a) => a,
+                InodeResolution::Redirect(fs, mut parent_path) => {
+                    let link = guard.canonicalize_without_inode(link)?;
+                    let original = guard.link_target_to_mount(&link, original)?;
+                    drop(guard);
+                    parent_path.push(name_of_link);
+                    return fs.symlink(&original, parent_path.as_path());
+                }
+            };
+
+            (inode_of_parent, name_of_link)
+        };
+
+        {
+            // Write lock.
+            let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
+
+            // Creating the link in the storage.
+            let inode_of_link = fs.storage.vacant_key();
+            let real_inode_of_link = fs.storage.insert(Node::Symlink(SymlinkNode {
+                inode: inode_of_link,
+                name: name_of_link,
+                target: original.to_owned(),
+                metadata: {
+                    let time = time();
+
+                    Metadata {
+                        ft: FileType {
+                            symlink: true,
+                            ..Default::default()
+                        },
+                        accessed: time,
+                        created: time,
+                        modified: time,
+                        len: original.as_os_str().len() as u64,
+                        permissions: Some(Permissions::new_symlink()),
+                    }
+                },
+            }));
+
+            assert_eq!(
+                inode_of_link, real_inode_of_link,
+                "new symlink inode should have been correctly calculated",
+            );
+
+            // Adding the new link to its parent.
+            fs.add_child_to_node(inode_of_parent, inode_of_link)?;
+            fs.notify_new_entry(inode_of_parent, inode_of_link);
         }
 
         Ok(())
     }
 
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let (inode_of_original, inode_of_parent, name_of_link) = {
+            // Read lock.
+            let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+
+            let original = guard.canonicalize_without_inode(original)?;
+            let inode_of_original = guard.inode_of(&original)?;
+
+            let (inode_of_parent, name_of_link) = guard.new_entry_location(link)?;
+            let (inode_of_original, inode_of_parent) = match (inode_of_original, inode_of_parent) {
+                (InodeResolution::Found(a), InodeResolution::Found(b)) => (a, b),
+
+                // Links within the same mounted file system are passed on to it
+                (
+                    InodeResolution::Redirect(original_fs, original_path),
+                    InodeResolution::Redirect(link_fs, mut link_path),
+                ) if Arc::ptr_eq(&original_fs, &link_fs) => {
+                    drop(guard);
+                    link_path.push(name_of_link);
+                    return link_fs.hard_link(original_path.as_path(), link_path.as_path());
+                }
+
+                // Hard links can not span file systems
+                _ => return Err(FsError::InvalidInput),
+            };
+
+            match guard.storage.get(inode_of_original) {
+                Some(Node::Directory(..)) | Some(Node::ArcDirectory(..)) => {
+                    return Err(FsError::PermissionDenied)
+                }
+                Some(_) => {}
+                None => return Err(FsError::EntryNotFound),
+            }
+
+            (inode_of_original, inode_of_parent, name_of_link)
+        };
+
+        {
+            // Write lock.
+            let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
+
+            let metadata = fs
+                .storage
+                .get(inode_of_original)
+                .ok_or(FsError::EntryNotFound)?
+                .metadata()
+                .clone();
+
+            // Creating the link in the storage.
+            let inode_of_link = fs.storage.vacant_key();
+            let real_inode_of_link = fs.storage.insert(Node::HardLink(HardLinkNode {
+                inode: inode_of_link,
+                name: name_of_link,
+                target: inode_of_original,
+                metadata,
+            }));
+
+            assert_eq!(
+                inode_of_link, real_inode_of_link,
+                "new hard link inode should have been correctly calculated",
+            );
+
+            // Adding the new link to its parent.
+            fs.add_child_to_node(inode_of_parent, inode_of_link)?;
+            fs.notify_new_entry(inode_of_parent, inode_of_link);
+        }
+
+        Ok(())
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        // Write lock.
+        let mut guard = self.inner.write().map_err(|_| FsError::Lock)?;
+        match guard.inode_of(path)? {
+            InodeResolution::Found(inode) => {
+                guard
+                    .storage
+                    .get_mut(inode)
+                    .ok_or(FsError::UnknownError)?
+                    .metadata_mut()
+                    .permissions = Some(permissions);
+                guard.notify_changed(inode, WatchEventKind::Attrib);
+                Ok(())
+            }
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.set_permissions(path.as_path(), permissions)
+            }
+        }
+    }
+
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        // Read lock.
+        let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+        match guard.inode_of_backing(path)? {
+            InodeResolution::Found(inode) => guard
+                .storage
+                .get(inode)
+                .and_then(Node::xattrs)
+                .ok_or(FsError::UnknownError)?
+                .get(name)
+                .cloned()
+                .ok_or(FsError::AttributeNotFound),
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.get_xattr(path.as_path(), name)
+            }
+        }
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        if name.is_empty() || name.len() > XATTR_NAME_MAX || name.contains('\0') {
+            return Err(FsError::InvalidInput);
+        }
+        if value.len() > XATTR_SIZE_MAX {
+            return Err(FsError::InvalidInput);
+        }
+
+        // Write lock.
+        let mut guard = self.inner.write().map_err(|_| FsError::Lock)?;
+        match guard.inode_of_backing(path)? {
+            InodeResolution::Found(inode) => {
+                guard
+                    .storage
+                    .get_mut(inode)
+                    .and_then(Node::xattrs_mut)
+                    .ok_or(FsError::UnknownError)?
+                    .insert(name.to_string(), value.to_vec());
+                guard.notify_changed(inode, WatchEventKind::Attrib);
+                Ok(())
+            }
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.set_xattr(path.as_path(), name, value)
+            }
+        }
+    }
+
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>> {
+        // Read lock.
+        let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+        match guard.inode_of_backing(path)? {
+            InodeResolution::Found(inode) => Ok(guard
+                .storage
+                .get(inode)
+                .and_then(Node::xattrs)
+                .ok_or(FsError::UnknownError)?
+                .keys()
+                .cloned()
+                .collect()),
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.list_xattr(path.as_path())
+            }
+        }
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        // Write lock.
+        let mut guard = self.inner.write().map_err(|_| FsError::Lock)?;
+        match guard.inode_of_backing(path)? {
+            InodeResolution::Found(inode) => {
+                guard
+                    .storage
+                    .get_mut(inode)
+                    .and_then(Node::xattrs_mut)
+                    .ok_or(FsError::UnknownError)?
+                    .remove(name)
+                    .ok_or(FsError::AttributeNotFound)?;
+                guard.notify_changed(inode, WatchEventKind::Attrib);
+                Ok(())
+            }
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.remove_xattr(path.as_path(), name)
+            }
+        }
+    }
+
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+        // Write lock.
+        let mut guard = self.inner.write().map_err(|_| FsError::Lock)?;
+        match guard.inode_of_backing(path)? {
+            InodeResolution::Found(inode) => {
+                let (sender, watch) = Watch::channel(mask);
+                guard.watches.add(inode, sender);
+                Ok(watch)
+            }
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.watch(path.as_path(), mask)
+            }
+        }
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         OpenOptions::new(self)
     }
@@ -719,12 +1079,66 @@ impl fmt::Debug for FileSystem {
     }
 }
 
+/// Maximum number of symbolic links that are followed while resolving a
+/// single path, this prevents cycles of links from looping forever.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// A point-in-time copy of the entries of a [`FileSystem`], see
+/// [`FileSystem::snapshot`].
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    storage: NodeStorage,
+    backing_offload: Option<OffloadBackingStore>,
+    limiter: Option<crate::limiter::DynFsMemoryLimiter>,
+}
+
+/// The slab of `Node`s, shared with the snapshots of the file system until
+/// it is modified. Every node sits behind its own `Arc` so a change only
+/// copies the table of pointers (if it is shared) and the node changed.
+#[derive(Debug, Clone, Default)]
+pub(super) struct NodeStorage(Arc<Slab<Arc<Node>>>);
+
+impl NodeStorage {
+    pub(super) fn get(&self, inode: Inode) -> Option<&Node> {
+        self.0.get(inode).map(|node| node.as_ref())
+    }
+
+    pub(super) fn get_mut(&mut self, inode: Inode) -> Option<&mut Node> {
+        Arc::make_mut(&mut self.0).get_mut(inode).map(Arc::make_mut)
+    }
+
+    /// Inode that the next inserted node gets
+    pub(super) fn vacant_key(&self) -> Inode {
+        self.0.vacant_key()
+    }
+
+    pub(super) fn insert(&mut self, node: Node) -> Inode {
+        Arc::make_mut(&mut self.0).insert(Arc::new(node))
+    }
+
+    pub(super) fn remove(&mut self, inode: Inode) -> Node {
+        let node = Arc::make_mut(&mut self.0).remove(inode);
+        Arc::try_unwrap(node).unwrap_or_else(|node| node.as_ref().clone())
+    }
+
+    #[cfg(test)]
+    pub(super) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = (Inode, &Node)> {
+        self.0.iter().map(|(inode, node)| (inode, node.as_ref()))
+    }
+}
+
 /// The core of the file system. It contains a collection of `Node`s,
 /// indexed by their respective `Inode` in a slab.
 pub(super) struct FileSystemInner {
-    pub(super) storage: Slab<Node>,
+    pub(super) storage: NodeStorage,
     pub(super) backing_offload: Option<OffloadBackingStore>,
     pub(super) limiter: Option<crate::limiter::DynFsMemoryLimiter>,
+    pub(super) locks: LockTable,
+    pub(super) watches: WatchTable,
 }
 
 #[derive(Debug)]
@@ -746,8 +1160,108 @@ impl InodeResolution {
 }
 
 impl FileSystemInner {
-    /// Get the inode associated to a path if it exists.
+    /// Get the inode associated to a path if it exists, symbolic links
+    /// are followed.
     pub(super) fn inode_of(&self, path: &Path) -> Result<InodeResolution> {
+        let path = self.resolve_symlinks(path)?;
+        self.inode_of_resolved(&path)
+    }
+
+    /// Like `Self::inode_of` but a symbolic link in the last component
+    /// of the path is not followed.
+    pub(super) fn inode_of_no_follow(&self, path: &Path) -> Result<InodeResolution> {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => {
+                let mut path = self.resolve_symlinks(parent)?;
+                path.push(name);
+                self.inode_of_resolved(&path)
+            }
+            _ => self.inode_of(path),
+        }
+    }
+
+    /// Expands the symbolic links in a path, components that do not exist
+    /// (or that are within a mounted file system) are kept as they are.
+    pub(super) fn resolve_symlinks(&self, path: &Path) -> Result<PathBuf> {
+        self.resolve_symlinks_with_hops(path, 0)
+    }
+
+    fn resolve_symlinks_with_hops(&self, path: &Path, hops: usize) -> Result<PathBuf> {
+        let mut components = path.components();
+        match components.next() {
+            Some(Component::RootDir) => {}
+            _ => return Ok(path.to_owned()),
+        }
+
+        // SAFETY: The root node always exists, so it's safe to unwrap here.
+        let mut node = self.storage.get(ROOT_INODE).unwrap();
+        let mut resolved = PathBuf::from("/");
+
+        while let Some(component) = components.next() {
+            let child = match node {
+                Node::Directory(DirectoryNode { children, .. }) => children
+                    .iter()
+                    .filter_map(|inode| self.storage.get(*inode))
+                    .find(|node| node.name() == component.as_os_str()),
+                _ => None,
+            };
+
+            match child {
+                Some(Node::Symlink(SymlinkNode { target, .. })) => {
+                    if hops >= MAX_SYMLINK_HOPS {
+                        return Err(FsError::InvalidInput);
+                    }
+
+                    // Relative targets are relative to the directory of the link
+                    let mut target = resolved.join(target);
+                    target.extend(components);
+                    let target = self.canonicalize_without_inode(&target)?;
+                    return self.resolve_symlinks_with_hops(&target, hops + 1);
+                }
+                Some(child) => {
+                    resolved.push(component);
+                    node = child;
+                }
+                None => {
+                    resolved.push(component);
+                    resolved.extend(components);
+                    break;
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Resolves the node that keeps the extended attributes (and the
+    /// watches) of `path`, entries that are backed by another file system
+    /// redirect to it.
+    fn inode_of_backing(&self, path: &Path) -> Result<InodeResolution> {
+        match self.inode_of(path)? {
+            InodeResolution::Found(inode) => match self.storage.get(inode) {
+                Some(Node::ArcFile(ArcFileNode { fs, path, .. }))
+                | Some(Node::ArcDirectory(ArcDirectoryNode { fs, path, .. })) => {
+                    Ok(InodeResolution::Redirect(fs.clone(), path.clone()))
+                }
+                Some(_) => Ok(InodeResolution::Found(inode)),
+                None => Err(FsError::EntryNotFound),
+            },
+            redirect => Ok(redirect),
+        }
+    }
+
+    /// Returns the inode of the node that a hard link refers to, or the
+    /// inode itself when it is not a hard link.
+    pub(super) fn resolve_hard_link(&self, inode: Inode) -> Inode {
+        match self.storage.get(inode) {
+            Some(Node::HardLink(HardLinkNode { target, .. })) => *target,
+            _ => inode,
+        }
+    }
+
+    /// Get the inode associated to a path whose symbolic links have
+    /// already been resolved.
+    fn inode_of_resolved(&self, path: &Path) -> Result<InodeResolution> {
         // SAFETY: The root node always exists, so it's safe to unwrap here.
         let mut node = self.storage.get(ROOT_INODE).unwrap();
         let mut components = path.components();
@@ -778,7 +1292,114 @@ impl FileSystemInner {
             };
         }
 
-        Ok(InodeResolution::Found(node.inode()))
+        Ok(InodeResolution::Found(self.resolve_hard_link(node.inode())))
+    }
+
+    /// Returns the path of the mount point that contains `path` (whose
+    /// symbolic links have already been resolved), along with the path that
+    /// the mount point refers to in the mounted file system.
+    fn mount_point_of_resolved(&self, path: &Path) -> Option<(PathBuf, PathBuf)> {
+        // SAFETY: The root node always exists, so it's safe to unwrap here.
+        let mut node = self.storage.get(ROOT_INODE).unwrap();
+        let mut mount_point = PathBuf::from("/");
+
+        for component in path.components().skip(1) {
+            node = match node {
+                Node::Directory(DirectoryNode { children, .. }) => children
+                    .iter()
+                    .filter_map(|inode| self.storage.get(*inode))
+                    .find(|node| node.name() == component.as_os_str())?,
+                Node::ArcDirectory(ArcDirectoryNode { path, .. }) => {
+                    return Some((mount_point, path.clone()));
+                }
+                _ => return None,
+            };
+            mount_point.push(component);
+        }
+
+        match node {
+            Node::ArcDirectory(ArcDirectoryNode { path, .. }) => Some((mount_point, path.clone())),
+            _ => None,
+        }
+    }
+
+    /// Converts the target of a symbolic link created at `link`, within a
+    /// mounted file system, into the target stored by that file system.
+    /// Absolute targets are rebased onto the mount point. Links can not span
+    /// mounts, so targets outside of the mount point (absolute ones, or
+    /// relative ones that climb out of it) are rejected.
+    fn link_target_to_mount(&self, link: &Path, original: &Path) -> Result<PathBuf> {
+        let parent = self.resolve_symlinks(link.parent().ok_or(FsError::BaseNotDirectory)?)?;
+        let (mount_point, mount_path) = self
+            .mount_point_of_resolved(&parent)
+            .ok_or(FsError::InvalidInput)?;
+
+        let (mut depth, target) = match original.strip_prefix(&mount_point) {
+            Ok(target) => (0, target),
+            Err(_) if original.has_root() => return Err(FsError::InvalidInput),
+            Err(_) => (
+                parent.components().count() - mount_point.components().count(),
+                original,
+            ),
+        };
+        for component in target.components() {
+            match component {
+                Component::ParentDir if depth == 0 => return Err(FsError::InvalidInput),
+                Component::ParentDir => depth -= 1,
+                Component::Normal(_) => depth += 1,
+                _ => {}
+            }
+        }
+
+        if original.has_root() {
+            Ok(mount_path.join(target))
+        } else {
+            Ok(original.to_owned())
+        }
+    }
+
+    /// Reverses [`FileSystemInner::link_target_to_mount`] so the target is
+    /// returned as it was given when the link was created.
+    fn link_target_from_mount(&self, link: &Path, target: PathBuf) -> Result<PathBuf> {
+        let parent = self.resolve_symlinks(link.parent().ok_or(FsError::BaseNotDirectory)?)?;
+        let Some((mount_point, mount_path)) = self.mount_point_of_resolved(&parent) else {
+            return Ok(target);
+        };
+
+        Ok(match target.strip_prefix(&mount_path) {
+            Ok(rest) if target.has_root() => mount_point.join(rest),
+            _ => target,
+        })
+    }
+
+    /// Returns the parent inode and the name of a new entry that is about
+    /// to be created at `path`, fails if the entry already exists.
+    fn new_entry_location(&self, path: &Path) -> Result<(InodeResolution, OsString)> {
+        // Canonicalize the path without checking the path exists,
+        // because it's about to be created.
+        let path = self.canonicalize_without_inode(path)?;
+
+        // Check the path has a parent.
+        let parent_of_path = path.parent().ok_or(FsError::BaseNotDirectory)?;
+
+        // Check the entry name.
+        let name = path
+            .file_name()
+            .ok_or(FsError::InvalidInput)?
+            .to_os_string();
+
+        // Find the parent inode.
+        let inode_of_parent = self.inode_of_parent(parent_of_path)?;
+        if let InodeResolution::Found(inode_of_parent) = inode_of_parent {
+            if self
+                .as_parent_get_position_and_inode(inode_of_parent, &name)?
+                .is_some()
+            {
+                return Err(FsError::AlreadyExists);
+            }
+        }
+
+        Ok((inode_of_parent, name))
     }
 
     /// Get the inode associated to a “parent path”. The returned
@@ -867,6 +1488,8 @@ impl FileSystemInner {
                     | Node::ReadOnlyFile(ReadOnlyFileNode { inode, name, .. })
                     | Node::CustomFile(CustomFileNode { inode, name, .. })
                     | Node::ArcFile(ArcFileNode { inode, name, .. })
+                    | Node::Symlink(SymlinkNode { inode, name, .. })
+                    | Node::HardLink(HardLinkNode { inode, name, .. })
                         if name.as_os_str() == name_of_file =>
                     {
                         Some(Some((nth, InodeResolution::Found(*inode))))
@@ -908,6 +1531,8 @@ impl FileSystemInner {
                     | Node::ReadOnlyFile(ReadOnlyFileNode { inode, name, .. })
                     | Node::CustomFile(CustomFileNode { inode, name, .. })
                     | Node::ArcFile(ArcFileNode { inode, name, .. })
+                    | Node::Symlink(SymlinkNode { inode, name, .. })
+                    | Node::HardLink(HardLinkNode { inode, name, .. })
                         if name.as_os_str() == name_of =>
                     {
                         Some(Some((nth, InodeResolution::Found(*inode))))
@@ -986,6 +1611,108 @@ impl FileSystemInner {
         }
     }
 
+    /// Removes the node at position `position` of the directory node
+    /// `inode_of_parent` and deletes it from the storage.
+    ///
+    /// When hard links refer to the node then the first of them takes
+    /// its place instead, so the data remains reachable through the
+    /// other links.
+    pub(super) fn unlink_node(
+        &mut self,
+        inode_of_parent: Inode,
+        position: usize,
+        inode: Inode,
+    ) -> Result<()> {
+        self.remove_child_from_node(inode_of_parent, position)?;
+
+        let link = self
+            .storage
+            .iter()
+            .find_map(|(inode_of_link, node)| match node {
+                Node::HardLink(HardLinkNode { target, .. }) if *target == inode => {
+                    Some(inode_of_link)
+                }
+                _ => None,
+            });
+        let Some(inode_of_link) = link else {
+            self.storage.remove(inode);
+            self.locks.remove(inode);
+            self.watches.remove(inode);
+            return Ok(());
+        };
+
+        // Find where the link lives so the node can be moved there.
+        let (inode_of_link_parent, position_of_link) = self
+            .storage
+            .iter()
+            .find_map(|(inode_of_dir, node)| match node {
+                Node::Directory(DirectoryNode { children, .. }) => children
+                    .iter()
+                    .position(|child| *child == inode_of_link)
+                    .map(|position| (inode_of_dir, position)),
+                _ => None,
+            })
+            .ok_or(FsError::UnknownError)?;
+
+        let name_of_link = self.storage.remove(inode_of_link).name().to_os_string();
+        self.storage
+            .get_mut(inode)
+            .ok_or(FsError::UnknownError)?
+            .set_name(name_of_link);
+        match self.storage.get_mut(inode_of_link_parent) {
+            Some(Node::Directory(DirectoryNode { children, .. })) => {
+                children[position_of_link] = inode;
+            }
+            _ => return Err(FsError::UnknownError),
+        }
+
+        Ok(())
+    }
+
+    /// Delivers an event about one of the children of a directory to the
+    /// watches of that directory.
+    pub(super) fn notify_entry(&mut self, inode_of_parent: Inode, event: WatchEvent) {
+        self.watches.notify(inode_of_parent, event);
+    }
+
+    /// Tells the watches of a directory that a child was created in it.
+    pub(super) fn notify_new_entry(&mut self, inode_of_parent: Inode, inode: Inode) {
+        if !self.watches.is_watched(inode_of_parent) {
+            return;
+        }
+        if let Some(node) = self.storage.get(inode) {
+            let event =
+                WatchEvent::new(WatchEventKind::Create).with_name(node.name(), node.is_dir());
+            self.watches.notify(inode_of_parent, event);
+        }
+    }
+
+    /// Tells the watches of a node, and those of the directory that
+    /// contains it, that the node changed.
+    pub(super) fn notify_changed(&mut self, inode: Inode, kind: WatchEventKind) {
+        if self.watches.is_empty() {
+            return;
+        }
+        self.watches.notify(inode, WatchEvent::new(kind));
+
+        let Some(node) = self.storage.get(inode) else {
+            return;
+        };
+        let event = WatchEvent::new(kind).with_name(node.name(), node.is_dir());
+        let parent = self
+            .storage
+            .iter()
+            .find_map(|(inode_of_dir, node)| match node {
+                Node::Directory(DirectoryNode { children, .. }) if children.contains(&inode) => {
+                    Some(inode_of_dir)
+                }
+                _ => None,
+            });
+        if let Some(inode_of_parent) = parent {
+            self.watches.notify(inode_of_parent, event);
+        }
+    }
+
     /// Canonicalize a path, i.e. try to resolve to a canonical,
     /// absolute form of the path with all intermediate components
     /// normalized:
@@ -1073,6 +1800,8 @@ impl fmt::Debug for FileSystemInner {
                         Node::CustomFile { .. } => "custom-file",
                         Node::Directory { .. } => "dir",
                         Node::ArcDirectory { .. } => "arc-dir",
+                        Node::Symlink { .. } => "symlink",
+                        Node::HardLink { .. } => "hard-link",
                     },
                     name = node.name().to_string_lossy(),
                     indentation_symbol = " ",
@@ -1108,8 +1837,8 @@ impl Default for FileSystemInner {
     fn default() -> Self {
         let time = time();
 
-        let mut slab = Slab::new();
-        slab.insert(Node::Directory(DirectoryNode {
+        let mut storage = NodeStorage::default();
+        storage.insert(Node::Directory(DirectoryNode {
             inode: ROOT_INODE,
             name: OsString::from("/"),
             children: Vec::new(),
@@ -1122,13 +1851,17 @@ impl Default for FileSystemInner {
                 created: time,
                 modified: time,
                 len: 0,
+                permissions: Some(Permissions::new_dir()),
             },
+            xattrs: Default::default(),
         }));
 
         Self {
-            storage: slab,
+            storage,
             backing_offload: None,
             limiter: None,
+            locks: LockTable::default(),
+            watches: WatchTable::default(),
         }
     }
 }
@@ -1154,9 +1887,9 @@ mod test_filesystem {
     use std::path::Path;
 
     use shared_buffer::OwnedBuffer;
-    use tokio::io::AsyncReadExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-    use crate::{mem_fs::*, ops, DirEntry, FileSystem as FS, FileType, FsError};
+    use crate::{mem_fs::*, ops, DirEntry, FileSystem as FS, FileType, FsError, Permissions};
 
     macro_rules! path {
         ($path:expr) => {
@@ -1597,7 +2330,8 @@ mod test_filesystem {
                 accessed,
                 created,
                 modified,
-                len: 0
+                len: 0,
+                ..
             }) if accessed == created && created == modified && modified > 0
         ));
 
@@ -1614,7 +2348,8 @@ mod test_filesystem {
                 accessed,
                 created,
                 modified,
-                len: 0
+                len: 0,
+                ..
             } if accessed == created && created == modified && modified > 0
         ));
 
@@ -1630,7 +2365,8 @@ mod test_filesystem {
                     accessed,
                     created,
                     modified,
-                    len: 0
+                    len: 0,
+                    ..
                 }) if
                     accessed == foo_metadata.accessed &&
                     created == foo_metadata.created &&
@@ -1646,7 +2382,8 @@ mod test_filesystem {
                     accessed,
                     created,
                     modified,
-                    len: 0
+                    len: 0,
+                    ..
                 }) if
                     accessed <= foo_metadata.accessed &&
                     created <= foo_metadata.created &&
@@ -1731,6 +2468,485 @@ mod test_filesystem {
         );
     }
 
+    #[tokio::test]
+    async fn test_symlink() {
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        ops::write(&fs, "/foo/hello.txt", b"hello").await.unwrap();
+
+        assert_eq!(
+            fs.symlink(path!("/foo/hello.txt"), path!("/abs.txt")),
+            Ok(()),
+            "creating an absolute symlink",
+        );
+        assert_eq!(
+            fs.symlink(path!("hello.txt"), path!("/foo/rel.txt")),
+            Ok(()),
+            "creating a relative symlink",
+        );
+        assert_eq!(
+            fs.symlink(path!("foo"), path!("/dir")),
+            Ok(()),
+            "creating a symlink to a directory",
+        );
+        assert_eq!(
+            fs.symlink(path!("foo"), path!("/dir")),
+            Err(FsError::AlreadyExists),
+            "the link already exists",
+        );
+
+        assert_eq!(
+            fs.readlink(path!("/foo/rel.txt")),
+            Ok(path!("hello.txt").to_owned())
+        );
+        assert_eq!(
+            fs.readlink(path!("/foo/hello.txt")),
+            Err(FsError::InvalidInput),
+            "regular files are not links",
+        );
+
+        for path in ["/abs.txt", "/foo/rel.txt", "/dir/hello.txt", "/dir/rel.txt"] {
+            assert_eq!(
+                ops::read_to_string(&fs, path).await.unwrap(),
+                "hello",
+                "reading through {path}",
+            );
+            assert!(fs.metadata(path!(path)).unwrap().is_file());
+        }
+        assert!(fs
+            .symlink_metadata(path!("/abs.txt"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs.metadata(path!("/dir")).unwrap().is_dir());
+
+        // Removing the link leaves the target alone
+        assert_eq!(fs.remove_file(path!("/abs.txt")), Ok(()));
+        assert!(fs.metadata(path!("/foo/hello.txt")).is_ok());
+
+        // Dangling links are created when they are written to
+        assert_eq!(fs.symlink(path!("/foo/new.txt"), path!("/new.txt")), Ok(()));
+        assert_eq!(fs.metadata(path!("/new.txt")), Err(FsError::EntryNotFound));
+        ops::write(&fs, "/new.txt", b"world").await.unwrap();
+        assert_eq!(
+            ops::read_to_string(&fs, "/foo/new.txt").await.unwrap(),
+            "world"
+        );
+
+        // Cycles of links are detected
+        assert_eq!(fs.symlink(path!("/loop2"), path!("/loop1")), Ok(()));
+        assert_eq!(fs.symlink(path!("/loop1"), path!("/loop2")), Ok(()));
+        assert_eq!(fs.metadata(path!("/loop1")), Err(FsError::InvalidInput));
+    }
+
+    #[tokio::test]
+    async fn test_symlink_in_mount() {
+        let fs = FileSystem::default();
+        let mounted = FileSystem::default();
+        assert_eq!(mounted.create_dir(path!("/data")), Ok(()));
+        ops::write(&mounted, "/data/hello.txt", b"hello")
+            .await
+            .unwrap();
+
+        let mounted: Arc<dyn crate::FileSystem + Send + Sync> = Arc::new(mounted);
+        fs.mount(path!(buf "/mnt"), &mounted, path!(buf "/data"))
+            .unwrap();
+        assert_eq!(fs.create_dir(path!("/mnt/sub")), Ok(()));
+
+        for (link, target) in [
+            ("/mnt/abs.txt", "/mnt/hello.txt"),
+            ("/mnt/sub/rel.txt", "../hello.txt"),
+        ] {
+            assert_eq!(
+                fs.symlink(path!(target), path!(link)),
+                Ok(()),
+                "creating a symlink to {target}",
+            );
+            assert_eq!(
+                fs.readlink(path!(link)),
+                Ok(path!(target).to_owned()),
+                "the target of {link} is returned as it was given",
+            );
+            assert_eq!(
+                ops::read_to_string(&fs, link).await.unwrap(),
+                "hello",
+                "reading through {link}",
+            );
+        }
+        assert_eq!(
+            mounted.readlink(path!("/data/abs.txt")),
+            Ok(path!(buf "/data/hello.txt")),
+            "the mounted file system stores a target it can follow",
+        );
+
+        for target in ["/foo/hello.txt", "../../foo/hello.txt", "/mnt/../foo"] {
+            assert_eq!(
+                fs.symlink(path!(target), path!("/mnt/sub/link.txt")),
+                Err(FsError::InvalidInput),
+                "symlinks to {target} can not span mounts",
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hard_link() {
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        ops::write(&fs, "/foo/a.txt", b"hello").await.unwrap();
+
+        assert_eq!(fs.hard_link(path!("/foo/a.txt"), path!("/b.txt")), Ok(()));
+        assert_eq!(
+            fs.hard_link(path!("/foo/a.txt"), path!("/b.txt")),
+            Err(FsError::AlreadyExists),
+        );
+        assert_eq!(
+            fs.hard_link(path!("/foo"), path!("/bar")),
+            Err(FsError::PermissionDenied),
+            "directories can not be hard linked",
+        );
+
+        // Writes through one name are visible through the other
+        ops::write(&fs, "/b.txt", b"world!").await.unwrap();
+        assert_eq!(
+            ops::read_to_string(&fs, "/foo/a.txt").await.unwrap(),
+            "world!"
+        );
+        assert_eq!(fs.metadata(path!("/b.txt")).unwrap().len(), 6);
+        let entry = fs
+            .read_dir(path!("/"))
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.path == path!("/b.txt"))
+            .unwrap();
+        assert_eq!(entry.metadata.unwrap().len(), 6);
+
+        // Removing the original keeps the data around for the link
+        assert_eq!(fs.remove_file(path!("/foo/a.txt")), Ok(()));
+        assert_eq!(
+            fs.metadata(path!("/foo/a.txt")),
+            Err(FsError::EntryNotFound)
+        );
+        assert_eq!(ops::read_to_string(&fs, "/b.txt").await.unwrap(), "world!");
+        assert_eq!(fs.remove_file(path!("/b.txt")), Ok(()));
+        assert_eq!(
+            fs.inner.read().unwrap().storage.len(),
+            2,
+            "only `/` and `/foo` remain"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_permissions() {
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        ops::write(&fs, "/foo/a.txt", b"hello").await.unwrap();
+        assert_eq!(fs.symlink(path!("/foo/a.txt"), path!("/link")), Ok(()));
+
+        assert_eq!(
+            fs.metadata(path!("/foo")).unwrap().permissions(),
+            Some(Permissions::new_dir()),
+        );
+        assert_eq!(
+            fs.metadata(path!("/foo/a.txt")).unwrap().permissions(),
+            Some(Permissions::new_file()),
+        );
+
+        // Symbolic links are followed
+        let permissions = Permissions::new(0o600, 1000, 1000);
+        assert_eq!(fs.set_permissions(path!("/link"), permissions), Ok(()));
+        assert_eq!(
+            fs.metadata(path!("/foo/a.txt")).unwrap().permissions(),
+            Some(permissions),
+        );
+        assert_eq!(
+            fs.symlink_metadata(path!("/link")).unwrap().permissions(),
+            Some(Permissions::new_symlink()),
+        );
+
+        // Writing to the file keeps its permissions
+        ops::write(&fs, "/foo/a.txt", b"world").await.unwrap();
+        assert_eq!(
+            fs.metadata(path!("/foo/a.txt")).unwrap().permissions(),
+            Some(permissions),
+        );
+
+        assert_eq!(
+            fs.set_permissions(path!("/foo/b.txt"), permissions),
+            Err(FsError::EntryNotFound),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_xattrs() {
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        ops::write(&fs, "/foo/a.txt", b"hello").await.unwrap();
+        assert_eq!(fs.hard_link(path!("/foo/a.txt"), path!("/b.txt")), Ok(()));
+
+        assert_eq!(fs.list_xattr(path!("/foo/a.txt")), Ok(Vec::new()));
+        assert_eq!(
+            fs.set_xattr(path!("/foo/a.txt"), "user.mime_type", b"text/plain"),
+            Ok(())
+        );
+        assert_eq!(fs.set_xattr(path!("/foo"), "user.comment", b"dir"), Ok(()));
+
+        // Hard links share the attributes of the file they refer to
+        assert_eq!(
+            fs.get_xattr(path!("/b.txt"), "user.mime_type"),
+            Ok(b"text/plain".to_vec())
+        );
+        assert_eq!(
+            fs.list_xattr(path!("/foo")),
+            Ok(vec!["user.comment".to_string()])
+        );
+
+        // Writing to the file keeps its attributes
+        ops::write(&fs, "/foo/a.txt", b"world").await.unwrap();
+        assert_eq!(
+            fs.list_xattr(path!("/foo/a.txt")),
+            Ok(vec!["user.mime_type".to_string()])
+        );
+
+        assert_eq!(
+            fs.remove_xattr(path!("/foo/a.txt"), "user.mime_type"),
+            Ok(())
+        );
+        assert_eq!(
+            fs.get_xattr(path!("/foo/a.txt"), "user.mime_type"),
+            Err(FsError::AttributeNotFound)
+        );
+        assert_eq!(
+            fs.remove_xattr(path!("/foo/a.txt"), "user.mime_type"),
+            Err(FsError::AttributeNotFound)
+        );
+        assert_eq!(
+            fs.set_xattr(path!("/foo/a.txt"), "", b""),
+            Err(FsError::InvalidInput)
+        );
+        assert_eq!(
+            fs.get_xattr(path!("/foo/c.txt"), "user.comment"),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        use crate::{WatchEvent, WatchEventKind, WatchMask};
+
+        let fs = FileSystem::default();
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        let dir = fs.watch(path!("/foo"), WatchMask::ALL).unwrap();
+
+        ops::write(&fs, "/foo/a.txt", b"hello").await.unwrap();
+        assert_eq!(
+            dir.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Create).with_name("a.txt", false))
+        );
+        assert_eq!(
+            dir.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Modify).with_name("a.txt", false))
+        );
+        assert_eq!(
+            dir.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::CloseWrite).with_name("a.txt", false))
+        );
+        assert_eq!(dir.try_recv(), None);
+
+        let file = fs
+            .watch(
+                path!("/foo/a.txt"),
+                WatchMask::DELETE_SELF | WatchMask::MOVE_SELF,
+            )
+            .unwrap();
+        assert_eq!(fs.create_dir(path!("/foo/sub")), Ok(()));
+        assert_eq!(
+            dir.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Create).with_name("sub", true))
+        );
+
+        // Both halves of a rename share a cookie
+        assert_eq!(
+            fs.rename(path!("/foo/a.txt"), path!("/foo/b.txt")).await,
+            Ok(())
+        );
+        let from = dir.try_recv().unwrap();
+        let to = dir.try_recv().unwrap();
+        assert_eq!(from.kind, WatchEventKind::MovedFrom);
+        assert_eq!(from.name.as_deref(), Some("a.txt".as_ref()));
+        assert_eq!(to.kind, WatchEventKind::MovedTo);
+        assert_eq!(to.name.as_deref(), Some("b.txt".as_ref()));
+        assert_ne!(from.cookie, 0);
+        assert_eq!(from.cookie, to.cookie);
+        assert_eq!(
+            file.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::MoveSelf))
+        );
+
+        assert_eq!(fs.remove_file(path!("/foo/b.txt")), Ok(()));
+        assert_eq!(
+            dir.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Delete).with_name("b.txt", false))
+        );
+        assert_eq!(
+            file.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::DeleteSelf))
+        );
+        assert_eq!(
+            file.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Ignored))
+        );
+
+        assert_eq!(
+            fs.watch(path!("/foo/b.txt"), WatchMask::ALL).map(|_| ()),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let fs = FileSystem::default();
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        ops::write(&fs, "/foo/a.txt", b"hello").await.unwrap();
+        let snapshot = fs.snapshot().unwrap();
+
+        // The fork and the original change independently
+        let fork = fs.fork().unwrap();
+        ops::write(&fork, "/foo/a.txt", b"fork").await.unwrap();
+        assert_eq!(fork.create_dir(path!("/bar")), Ok(()));
+        ops::write(&fs, "/foo/b.txt", b"original").await.unwrap();
+        assert_eq!(
+            ops::read_to_string(&fs, "/foo/a.txt").await.unwrap(),
+            "hello"
+        );
+        assert_eq!(fs.metadata(path!("/bar")), Err(FsError::EntryNotFound));
+        assert_eq!(
+            fork.metadata(path!("/foo/b.txt")),
+            Err(FsError::EntryNotFound)
+        );
+
+        // Restoring brings back the entries as they were
+        let watch = fs.watch(path!("/foo"), crate::WatchMask::ALL).unwrap();
+        ops::write(&fs, "/foo/a.txt", b"changed").await.unwrap();
+        assert_eq!(fs.restore(&snapshot), Ok(()));
+        assert_eq!(
+            ops::read_to_string(&fs, "/foo/a.txt").await.unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            fs.metadata(path!("/foo/b.txt")),
+            Err(FsError::EntryNotFound)
+        );
+        assert_eq!(
+            std::iter::from_fn(|| watch.try_recv()).last().unwrap().kind,
+            crate::WatchEventKind::Ignored
+        );
+        assert_eq!(
+            ops::read_to_string(&fork, "/foo/a.txt").await.unwrap(),
+            "fork"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_copies_changed_nodes() {
+        let fs = FileSystem::default();
+        ops::write(&fs, "/a.txt", b"a").await.unwrap();
+        ops::write(&fs, "/b.txt", b"b").await.unwrap();
+        let fork = fs.fork().unwrap();
+        ops::write(&fork, "/a.txt", b"changed").await.unwrap();
+
+        let inode_of = |name: &str| {
+            let guard = fs.inner.read().unwrap();
+            let (inode, _) = guard
+                .storage
+                .iter()
+                .find(|(_, node)| node.name() == name)
+                .unwrap();
+            inode
+        };
+        let same_node = |inode: Inode| {
+            let fs = fs.inner.read().unwrap();
+            let fork = fork.inner.read().unwrap();
+            Arc::ptr_eq(
+                fs.storage.0.get(inode).unwrap(),
+                fork.storage.0.get(inode).unwrap(),
+            )
+        };
+        assert!(!same_node(inode_of("a.txt")));
+        assert!(same_node(inode_of("b.txt")));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_custom_files() {
+        let fs = FileSystem::default();
+        fs.new_open_options_ext()
+            .insert_device_file(
+                PathBuf::from("/buffer"),
+                Box::new(crate::BufferFile {
+                    data: std::io::Cursor::new(b"hello".to_vec()),
+                }),
+            )
+            .unwrap();
+        let fork = fs.fork().unwrap();
+
+        // The fork writes into its own copy of the file
+        let mut file = fork
+            .new_open_options()
+            .write(true)
+            .open(path!("/buffer"))
+            .unwrap();
+        file.write_all(b"HE").await.unwrap();
+        drop(file);
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/buffer"))
+            .unwrap();
+        let mut data = String::new();
+        file.read_to_string(&mut data).await.unwrap();
+        assert_eq!(data, "hello");
+    }
+
+    #[cfg(feature = "tracking")]
+    #[tokio::test]
+    async fn test_snapshot_memory_limiter() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Debug, Default)]
+        struct Counter(AtomicUsize);
+
+        impl crate::limiter::FsMemoryLimiter for Counter {
+            fn on_grow(&self, grown_bytes: usize) -> Result<(), FsError> {
+                self.0.fetch_add(grown_bytes, Ordering::SeqCst);
+                Ok(())
+            }
+
+            fn on_shrink(&self, shrunk_bytes: usize) {
+                self.0.fetch_sub(shrunk_bytes, Ordering::SeqCst);
+            }
+        }
+
+        let counter = std::sync::Arc::new(Counter::default());
+        let fs = FileSystem::default();
+        fs.set_memory_limiter(counter.clone());
+        ops::write(&fs, "/a.txt", vec![1; 4096]).await.unwrap();
+        let used = counter.0.load(Ordering::SeqCst);
+        assert!(used >= 4096);
+
+        // Shared contents are only accounted for once
+        let fork = fs.fork().unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), used);
+        ops::write(&fork, "/a.txt", vec![2; 4096]).await.unwrap();
+        assert!(counter.0.load(Ordering::SeqCst) >= used + 4096);
+
+        drop(fork);
+        assert_eq!(counter.0.load(Ordering::SeqCst), used);
+    }
+
     #[tokio::test]
     async fn test_readdir() {
         let fs = FileSystem::default();
@@ -0,0 +1,401 @@
+use std::{
+    collections::{HashMap, HashSet},
+    task::{Context, Poll, Waker},
+};
+
+use super::Inode;
+use crate::{FileLock, FsError, LockOwner, LockRange, Result};
+
+/// A lock on the bytes `start..end` of a file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ByteLock {
+    owner: LockOwner,
+    exclusive: bool,
+    start: u64,
+    end: u64,
+}
+
+impl ByteLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn range(&self) -> LockRange {
+        LockRange::Bytes {
+            start: self.start,
+            len: match self.end {
+                u64::MAX => 0,
+                end => end - self.start,
+            },
+        }
+    }
+}
+
+/// Converts a range into its `start..end` bounds
+fn bounds(start: u64, len: u64) -> (u64, u64) {
+    match len {
+        0 => (start, u64::MAX),
+        len => (start, start.saturating_add(len)),
+    }
+}
+
+#[derive(Debug, Default)]
+struct LockState {
+    shared: HashSet<LockOwner>,
+    exclusive: Option<LockOwner>,
+    ranges: Vec<ByteLock>,
+    waiters: Vec<Waker>,
+}
+
+impl LockState {
+    fn is_empty(&self) -> bool {
+        self.shared.is_empty()
+            && self.exclusive.is_none()
+            && self.ranges.is_empty()
+            && self.waiters.is_empty()
+    }
+
+    /// Wakes up the tasks waiting for a lock on the file, they check their
+    /// lock again so waking them when nothing was released is harmless
+    fn wake(&mut self) {
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Returns the whole file lock that conflicts with `lock`
+    fn file_conflict(&self, owner: LockOwner, lock: FileLock) -> Option<FileLock> {
+        if self.exclusive.is_some_and(|o| o != owner) {
+            return Some(FileLock::Exclusive);
+        }
+        if lock == FileLock::Exclusive && self.shared.iter().any(|o| *o != owner) {
+            return Some(FileLock::Shared);
+        }
+        None
+    }
+
+    /// Returns the byte range lock that conflicts with `lock`
+    fn range_conflict(
+        &self,
+        owner: LockOwner,
+        lock: FileLock,
+        start: u64,
+        end: u64,
+    ) -> Option<&ByteLock> {
+        self.ranges.iter().find(|l| {
+            l.owner != owner
+                && l.overlaps(start, end)
+                && (l.exclusive || lock == FileLock::Exclusive)
+        })
+    }
+
+    /// Replaces the locks of `owner` on `start..end` with `lock`
+    fn set_range(&mut self, owner: LockOwner, lock: FileLock, start: u64, end: u64) {
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 2);
+        for l in self.ranges.drain(..) {
+            if l.owner != owner || !l.overlaps(start, end) {
+                ranges.push(l);
+                continue;
+            }
+            if l.start < start {
+                ranges.push(ByteLock { end: start, ..l });
+            }
+            if end < l.end {
+                ranges.push(ByteLock { start: end, ..l });
+            }
+        }
+        if lock != FileLock::Unlock {
+            ranges.push(ByteLock {
+                owner,
+                exclusive: lock == FileLock::Exclusive,
+                start,
+                end,
+            });
+        }
+        self.ranges = ranges;
+    }
+}
+
+/// The advisory locks held on the files of a file system. Whole file locks
+/// follow the semantics of `flock(2)` where an owner holds at most one lock
+/// on a file and converting between lock kinds is not atomic, byte range
+/// locks follow those of open file description locks in `fcntl(2)`.
+#[derive(Debug, Default)]
+pub(super) struct LockTable {
+    locks: HashMap<Inode, LockState>,
+}
+
+impl LockTable {
+    /// Places (or releases) the lock of `owner` on a file, fails with
+    /// [`FsError::WouldBlock`] when another owner holds a conflicting lock
+    pub(super) fn try_lock(
+        &mut self,
+        inode: Inode,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Result<()> {
+        let state = self.locks.entry(inode).or_default();
+        match range {
+            LockRange::File => {
+                if lock != FileLock::Unlock && state.file_conflict(owner, lock).is_some() {
+                    return Err(FsError::WouldBlock);
+                }
+                state.shared.remove(&owner);
+                if state.exclusive == Some(owner) {
+                    state.exclusive = None;
+                }
+                match lock {
+                    FileLock::Shared => {
+                        state.shared.insert(owner);
+                    }
+                    FileLock::Exclusive => state.exclusive = Some(owner),
+                    FileLock::Unlock => {}
+                }
+            }
+            LockRange::Bytes { start, len } => {
+                let (start, end) = bounds(start, len);
+                if lock != FileLock::Unlock
+                    && state.range_conflict(owner, lock, start, end).is_some()
+                {
+                    return Err(FsError::WouldBlock);
+                }
+                state.set_range(owner, lock, start, end);
+            }
+        }
+
+        state.wake();
+        if state.is_empty() {
+            self.locks.remove(&inode);
+        }
+        Ok(())
+    }
+
+    /// Same as [`LockTable::try_lock`] except that the task is woken up
+    /// when a lock on the file is released instead of failing
+    pub(super) fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        inode: Inode,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Poll<Result<()>> {
+        match self.try_lock(inode, owner, lock, range) {
+            Err(FsError::WouldBlock) => {
+                let state = self.locks.entry(inode).or_default();
+                state.waiters.push(cx.waker().clone());
+                Poll::Pending
+            }
+            res => Poll::Ready(res),
+        }
+    }
+
+    /// Returns the lock of another owner that conflicts with `lock`
+    pub(super) fn test_lock(
+        &self,
+        inode: Inode,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Option<(FileLock, LockRange)> {
+        let state = self.locks.get(&inode)?;
+        if lock == FileLock::Unlock {
+            return None;
+        }
+        match range {
+            LockRange::File => state
+                .file_conflict(owner, lock)
+                .map(|lock| (lock, LockRange::File)),
+            LockRange::Bytes { start, len } => {
+                let (start, end) = bounds(start, len);
+                state
+                    .range_conflict(owner, lock, start, end)
+                    .map(|l| match l.exclusive {
+                        true => (FileLock::Exclusive, l.range()),
+                        false => (FileLock::Shared, l.range()),
+                    })
+            }
+        }
+    }
+
+    /// Releases all the locks that `owner` holds on a file
+    pub(super) fn release(&mut self, inode: Inode, owner: LockOwner) {
+        if let Some(state) = self.locks.get_mut(&inode) {
+            state.shared.remove(&owner);
+            if state.exclusive == Some(owner) {
+                state.exclusive = None;
+            }
+            state.ranges.retain(|l| l.owner != owner);
+            state.wake();
+            if state.is_empty() {
+                self.locks.remove(&inode);
+            }
+        }
+    }
+
+    /// Forgets all the locks on a file that has been removed
+    pub(super) fn remove(&mut self, inode: Inode) {
+        if let Some(mut state) = self.locks.remove(&inode) {
+            state.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::Wake,
+    };
+
+    use super::*;
+    use crate::new_lock_owner;
+
+    const FILE: LockRange = LockRange::File;
+
+    fn bytes(start: u64, len: u64) -> LockRange {
+        LockRange::Bytes { start, len }
+    }
+
+    #[test]
+    fn test_lock_table() {
+        let mut table = LockTable::default();
+        let (a, b) = (new_lock_owner(), new_lock_owner());
+
+        // Shared locks can be held by many owners
+        assert_eq!(table.try_lock(1, a, FileLock::Shared, FILE), Ok(()));
+        assert_eq!(table.try_lock(1, b, FileLock::Shared, FILE), Ok(()));
+        assert_eq!(
+            table.try_lock(1, a, FileLock::Exclusive, FILE),
+            Err(FsError::WouldBlock)
+        );
+
+        // Once the other owner is gone the lock can be upgraded
+        assert_eq!(table.try_lock(1, b, FileLock::Unlock, FILE), Ok(()));
+        assert_eq!(table.try_lock(1, a, FileLock::Exclusive, FILE), Ok(()));
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Shared, FILE),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            table.test_lock(1, b, FileLock::Shared, FILE),
+            Some((FileLock::Exclusive, FILE))
+        );
+
+        // Locks on other files are independent
+        assert_eq!(table.try_lock(2, b, FileLock::Exclusive, FILE), Ok(()));
+
+        table.release(1, a);
+        assert_eq!(table.try_lock(1, b, FileLock::Shared, FILE), Ok(()));
+        table.remove(1);
+        table.remove(2);
+        assert!(table.locks.is_empty());
+    }
+
+    #[test]
+    fn test_byte_range_locks() {
+        let mut table = LockTable::default();
+        let (a, b) = (new_lock_owner(), new_lock_owner());
+
+        assert_eq!(
+            table.try_lock(1, a, FileLock::Exclusive, bytes(10, 10)),
+            Ok(())
+        );
+
+        // Ranges that do not overlap are independent, and so are whole file locks
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Exclusive, bytes(0, 10)),
+            Ok(())
+        );
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Exclusive, bytes(20, 0)),
+            Ok(())
+        );
+        assert_eq!(table.try_lock(1, b, FileLock::Exclusive, FILE), Ok(()));
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Shared, bytes(15, 1)),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            table.test_lock(1, b, FileLock::Shared, bytes(5, 10)),
+            Some((FileLock::Exclusive, bytes(10, 10)))
+        );
+        assert_eq!(
+            table.test_lock(1, b, FileLock::Exclusive, bytes(100, 5)),
+            None,
+            "the lock to the end of the file belongs to the same owner",
+        );
+        assert_eq!(
+            table.test_lock(1, a, FileLock::Shared, bytes(100, 5)),
+            Some((FileLock::Exclusive, bytes(20, 0)))
+        );
+
+        // Unlocking the middle of a range splits it
+        assert_eq!(table.try_lock(1, a, FileLock::Unlock, bytes(12, 4)), Ok(()));
+        assert_eq!(table.try_lock(1, b, FileLock::Shared, bytes(12, 4)), Ok(()));
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Shared, bytes(11, 2)),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            table.test_lock(1, b, FileLock::Shared, bytes(16, 10)),
+            Some((FileLock::Exclusive, bytes(16, 4)))
+        );
+
+        // Downgrading a range lets others share it
+        assert_eq!(
+            table.try_lock(1, a, FileLock::Shared, bytes(10, 10)),
+            Ok(())
+        );
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Shared, bytes(10, 10)),
+            Ok(())
+        );
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Exclusive, bytes(10, 1)),
+            Err(FsError::WouldBlock)
+        );
+
+        table.release(1, a);
+        assert_eq!(
+            table.try_lock(1, b, FileLock::Exclusive, bytes(0, 0)),
+            Ok(())
+        );
+        table.release(1, b);
+        assert!(table.locks.is_empty());
+    }
+
+    #[test]
+    fn test_waiters_are_woken() {
+        struct CountingWaker(AtomicUsize);
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut table = LockTable::default();
+        let (a, b) = (new_lock_owner(), new_lock_owner());
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        assert_eq!(table.try_lock(1, a, FileLock::Exclusive, FILE), Ok(()));
+        assert_eq!(
+            table.poll_lock(&mut cx, 1, b, FileLock::Shared, FILE),
+            Poll::Pending
+        );
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+        table.release(1, a);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            table.poll_lock(&mut cx, 1, b, FileLock::Shared, FILE),
+            Poll::Ready(Ok(()))
+        );
+    }
+}
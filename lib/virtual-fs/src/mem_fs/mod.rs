@@ -1,11 +1,15 @@
+mod blob_store;
 mod file;
 mod file_opener;
 mod filesystem;
+mod locks;
 mod offloaded_file;
 mod stdio;
+mod watches;
 
+pub use blob_store::BlobStore;
 use file::{File, FileHandle, ReadOnlyFile};
-pub use filesystem::FileSystem;
+pub use filesystem::{FileSystem, Snapshot};
 pub use offloaded_file::OffloadBackingStore;
 #[cfg(not(feature = "js"))]
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -15,6 +19,7 @@ pub use web_time::{SystemTime, UNIX_EPOCH};
 
 use crate::Metadata;
 use std::{
+    collections::BTreeMap,
     ffi::{OsStr, OsString},
     path::PathBuf,
     sync::{Arc, Mutex},
@@ -25,31 +30,37 @@ use self::offloaded_file::OffloadedFile;
 type Inode = usize;
 const ROOT_INODE: Inode = 0;
 
-#[derive(Debug)]
+/// The extended attributes of a node, indexed by their name
+type Xattrs = BTreeMap<String, Vec<u8>>;
+
+#[derive(Debug, Clone)]
 struct FileNode {
     inode: Inode,
     name: OsString,
     file: File,
     metadata: Metadata,
+    xattrs: Xattrs,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ReadOnlyFileNode {
     inode: Inode,
     name: OsString,
     file: ReadOnlyFile,
     metadata: Metadata,
+    xattrs: Xattrs,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct OffloadedFileNode {
     inode: Inode,
     name: OsString,
     file: OffloadedFile,
     metadata: Metadata,
+    xattrs: Xattrs,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ArcFileNode {
     inode: Inode,
     name: OsString,
@@ -64,19 +75,41 @@ struct ArcFileNode {
 struct CustomFileNode {
     inode: Inode,
     name: OsString,
-    file: Mutex<Box<dyn crate::VirtualFile + Send + Sync>>,
+    /// Device behind the node (see `insert_device_file`)
+    file: Arc<Mutex<Box<dyn crate::VirtualFile + Send + Sync>>>,
     metadata: Metadata,
+    xattrs: Xattrs,
 }
 
-#[derive(Debug)]
+impl Clone for CustomFileNode {
+    /// The copies of the node made for snapshots and forks get their own
+    /// copy of the file (see [`crate::VirtualFile::duplicate`]), the files
+    /// that can not be copied (such as pipes to the host) stay shared.
+    fn clone(&self) -> Self {
+        let file = match self.file.lock().unwrap().duplicate() {
+            Some(file) => Arc::new(Mutex::new(file)),
+            None => Arc::clone(&self.file),
+        };
+        Self {
+            inode: self.inode,
+            name: self.name.clone(),
+            file,
+            metadata: self.metadata.clone(),
+            xattrs: self.xattrs.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct DirectoryNode {
     inode: Inode,
     name: OsString,
     children: Vec<Inode>,
     metadata: Metadata,
+    xattrs: Xattrs,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ArcDirectoryNode {
     inode: Inode,
     name: OsString,
@@ -85,7 +118,26 @@ struct ArcDirectoryNode {
     metadata: Metadata,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+struct SymlinkNode {
+    inode: Inode,
+    name: OsString,
+    target: PathBuf,
+    metadata: Metadata,
+}
+
+/// Additional name for a file node, lookups of the link resolve to the
+/// node it refers to (the metadata is only a copy taken when the link
+/// was created)
+#[derive(Debug, Clone)]
+struct HardLinkNode {
+    inode: Inode,
+    name: OsString,
+    target: Inode,
+    metadata: Metadata,
+}
+
+#[derive(Debug, Clone)]
 enum Node {
     File(FileNode),
     OffloadedFile(OffloadedFileNode),
@@ -94,6 +146,8 @@ enum Node {
     CustomFile(CustomFileNode),
     Directory(DirectoryNode),
     ArcDirectory(ArcDirectoryNode),
+    Symlink(SymlinkNode),
+    HardLink(HardLinkNode),
 }
 
 impl Node {
@@ -106,6 +160,8 @@ impl Node {
             Self::CustomFile(CustomFileNode { inode, .. }) => inode,
             Self::Directory(DirectoryNode { inode, .. }) => inode,
             Self::ArcDirectory(ArcDirectoryNode { inode, .. }) => inode,
+            Self::Symlink(SymlinkNode { inode, .. }) => inode,
+            Self::HardLink(HardLinkNode { inode, .. }) => inode,
         }
     }
 
@@ -118,6 +174,8 @@ impl Node {
             Self::CustomFile(CustomFileNode { name, .. }) => name.as_os_str(),
             Self::Directory(DirectoryNode { name, .. }) => name.as_os_str(),
             Self::ArcDirectory(ArcDirectoryNode { name, .. }) => name.as_os_str(),
+            Self::Symlink(SymlinkNode { name, .. }) => name.as_os_str(),
+            Self::HardLink(HardLinkNode { name, .. }) => name.as_os_str(),
         }
     }
 
@@ -130,6 +188,8 @@ impl Node {
             Self::CustomFile(CustomFileNode { metadata, .. }) => metadata,
             Self::Directory(DirectoryNode { metadata, .. }) => metadata,
             Self::ArcDirectory(ArcDirectoryNode { metadata, .. }) => metadata,
+            Self::Symlink(SymlinkNode { metadata, .. }) => metadata,
+            Self::HardLink(HardLinkNode { metadata, .. }) => metadata,
         }
     }
 
@@ -142,9 +202,39 @@ impl Node {
             Self::CustomFile(CustomFileNode { metadata, .. }) => metadata,
             Self::Directory(DirectoryNode { metadata, .. }) => metadata,
             Self::ArcDirectory(ArcDirectoryNode { metadata, .. }) => metadata,
+            Self::Symlink(SymlinkNode { metadata, .. }) => metadata,
+            Self::HardLink(HardLinkNode { metadata, .. }) => metadata,
+        }
+    }
+
+    /// Extended attributes are only kept for the nodes whose content lives
+    /// in this file system (links are resolved before getting here)
+    fn xattrs(&self) -> Option<&Xattrs> {
+        match self {
+            Self::File(FileNode { xattrs, .. })
+            | Self::OffloadedFile(OffloadedFileNode { xattrs, .. })
+            | Self::ReadOnlyFile(ReadOnlyFileNode { xattrs, .. })
+            | Self::CustomFile(CustomFileNode { xattrs, .. })
+            | Self::Directory(DirectoryNode { xattrs, .. }) => Some(xattrs),
+            Self::ArcFile(_) | Self::ArcDirectory(_) | Self::Symlink(_) | Self::HardLink(_) => None,
+        }
+    }
+
+    fn xattrs_mut(&mut self) -> Option<&mut Xattrs> {
+        match self {
+            Self::File(FileNode { xattrs, .. })
+            | Self::OffloadedFile(OffloadedFileNode { xattrs, .. })
+            | Self::ReadOnlyFile(ReadOnlyFileNode { xattrs, .. })
+            | Self::CustomFile(CustomFileNode { xattrs, .. })
+            | Self::Directory(DirectoryNode { xattrs, .. }) => Some(xattrs),
+            Self::ArcFile(_) | Self::ArcDirectory(_) | Self::Symlink(_) | Self::HardLink(_) => None,
         }
     }
 
+    fn is_dir(&self) -> bool {
+        matches!(self, Self::Directory(..) | Self::ArcDirectory(..))
+    }
+
     fn set_name(&mut self, new_name: OsString) {
         match self {
             Self::File(FileNode { name, .. }) => *name = new_name,
@@ -154,6 +244,8 @@ impl Node {
             Self::CustomFile(CustomFileNode { name, .. }) => *name = new_name,
             Self::Directory(DirectoryNode { name, .. }) => *name = new_name,
             Self::ArcDirectory(ArcDirectoryNode { name, .. }) => *name = new_name,
+            Self::Symlink(SymlinkNode { name, .. }) => *name = new_name,
+            Self::HardLink(HardLinkNode { name, .. }) => *name = new_name,
         }
     }
 }
@@ -8,13 +8,15 @@ use std::{
     sync::{Arc, Mutex, MutexGuard},
 };
 
+use super::blob_store::{BlobChunk, BlobStore, CHUNK_SIZE};
 use crate::limiter::DynFsMemoryLimiter;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FileExtent {
     MmapOffload { offset: u64, size: u64 },
     RepeatingBytes { value: u8, cnt: u64 },
     InMemory { data: Bytes },
+    Blob { chunk: BlobChunk },
 }
 
 impl FileExtent {
@@ -23,6 +25,7 @@ impl FileExtent {
             FileExtent::MmapOffload { size, .. } => *size,
             FileExtent::RepeatingBytes { cnt, .. } => *cnt,
             FileExtent::InMemory { data } => data.len() as u64,
+            FileExtent::Blob { chunk } => chunk.len(),
         }
     }
 
@@ -33,6 +36,9 @@ impl FileExtent {
             FileExtent::InMemory { data } => {
                 *data = data.slice(..(new_size as usize));
             }
+            FileExtent::Blob { chunk } => {
+                *chunk = chunk.slice(..(new_size as usize));
+            }
         }
     }
 }
@@ -90,6 +96,7 @@ impl OffloadBackingStoreState {
 #[derive(Debug, Clone)]
 pub struct OffloadBackingStore {
     state: Arc<Mutex<OffloadBackingStoreState>>,
+    blob_store: Option<BlobStore>,
 }
 
 impl OffloadBackingStore {
@@ -99,9 +106,28 @@ impl OffloadBackingStore {
                 mmap_file,
                 mmap_offload,
             })),
+            blob_store: None,
         }
     }
 
+    /// Creates a backing store that keeps the contents of the files in a
+    /// content-addressed [`BlobStore`], see [`Self::with_blob_store`].
+    pub fn from_blob_store(blob_store: BlobStore) -> Self {
+        Self::from_buffer(OwnedBuffer::new()).with_blob_store(blob_store)
+    }
+
+    /// Moves the data written to the files into a [`BlobStore`] when they
+    /// are flushed (or closed), so that identical contents are only stored
+    /// once no matter how many files (or file systems) hold them.
+    pub fn with_blob_store(mut self, blob_store: BlobStore) -> Self {
+        self.blob_store = Some(blob_store);
+        self
+    }
+
+    pub fn blob_store(&self) -> Option<&BlobStore> {
+        self.blob_store.as_ref()
+    }
+
     pub fn from_file(file: &File) -> Self {
         let file = file.try_clone().unwrap();
         let buffer = OwnedBuffer::from_file(&file).unwrap();
@@ -122,7 +148,7 @@ impl OffloadBackingStore {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OffloadedFile {
     backing: OffloadBackingStore,
     #[allow(dead_code)]
@@ -134,6 +160,7 @@ pub struct OffloadedFile {
 pub enum OffloadWrite<'a> {
     MmapOffset { offset: u64, size: u64 },
     Buffer(&'a [u8]),
+    Blob(BlobChunk),
 }
 
 impl OffloadWrite<'_> {
@@ -141,6 +168,7 @@ impl OffloadWrite<'_> {
         match self {
             OffloadWrite::MmapOffset { size, .. } => *size as usize,
             OffloadWrite::Buffer(data) => data.len(),
+            OffloadWrite::Blob(chunk) => chunk.len() as usize,
         }
     }
 }
@@ -224,6 +252,12 @@ impl OffloadedFile {
                     buf[..data_len].copy_from_slice(&data[..data_len]);
                     data_len
                 }
+                FileExtent::Blob { chunk } => {
+                    let data = &chunk.as_slice()[extent_offset as usize..];
+                    let data_len = cmp::min(buf.len(), data.len());
+                    buf[..data_len].copy_from_slice(&data[..data_len]);
+                    data_len
+                }
             };
 
             *cursor += read as u64;
@@ -270,6 +304,9 @@ impl OffloadedFile {
                         FileExtent::InMemory { data: other_data } => FileExtent::InMemory {
                             data: other_data.slice((split_at as usize)..),
                         },
+                        FileExtent::Blob { chunk: other_chunk } => FileExtent::Blob {
+                            chunk: other_chunk.slice((split_at as usize)..),
+                        },
                     };
                     extent.resize(split_at);
                     self.extents.insert(index + 1, new_extent);
@@ -352,6 +389,14 @@ impl OffloadedFile {
                 };
                 self.extents.insert(index, new_extent);
             }
+            OffloadWrite::Blob(ref chunk) => {
+                self.extents.insert(
+                    index,
+                    FileExtent::Blob {
+                        chunk: chunk.clone(),
+                    },
+                );
+            }
         }
         self.size = self.size.max(original_extent_offset + data.len() as u64);
 
@@ -361,6 +406,38 @@ impl OffloadedFile {
     }
 
     pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(blob_store) = self.backing.blob_store().cloned() {
+            self.store_blobs(&blob_store)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the data held in memory with chunks of the blob store, the
+    /// chunks are aligned on their offset in the file so that identical
+    /// files end up with identical chunks however they were written.
+    fn store_blobs(&mut self, blob_store: &BlobStore) -> io::Result<()> {
+        let mut chunks: Vec<u64> = Vec::new();
+        let mut offset = 0u64;
+        for extent in self.extents.iter() {
+            let end = offset + extent.size();
+            if matches!(extent, FileExtent::InMemory { .. }) && end > offset {
+                for index in offset / CHUNK_SIZE..=(end - 1) / CHUNK_SIZE {
+                    if chunks.last() != Some(&index) {
+                        chunks.push(index);
+                    }
+                }
+            }
+            offset = end;
+        }
+
+        let mut buf = vec![0u8; CHUNK_SIZE as usize];
+        for index in chunks {
+            let start = index * CHUNK_SIZE;
+            let len = CHUNK_SIZE.min(self.size - start) as usize;
+            let read = self.read(&mut buf[..len], &mut start.clone())?;
+            let chunk = blob_store.insert(&buf[..read])?;
+            self.write(OffloadWrite::Blob(chunk), &mut start.clone())?;
+        }
         Ok(())
     }
 
@@ -482,4 +559,47 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    pub fn test_offload_blob_store() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = BlobStore::new(dir.path())?;
+        let backing = OffloadBackingStore::from_blob_store(store.clone());
+        let data = (0..CHUNK_SIZE * 2 + 100)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        // The same contents written in different ways share their chunks
+        let mut a = OffloadedFile::new(None, backing.clone());
+        a.write(OffloadWrite::Buffer(&data), &mut 0)?;
+        a.flush()?;
+        let mut b = OffloadedFile::new(None, backing.clone());
+        let mut cursor = 0u64;
+        for piece in data.chunks(1000) {
+            b.write(OffloadWrite::Buffer(piece), &mut cursor)?;
+        }
+        b.flush()?;
+        assert_eq!(store.chunk_count(), 3);
+        assert!(a
+            .extents
+            .iter()
+            .all(|e| matches!(e, FileExtent::Blob { .. })));
+
+        let mut result = vec![0u8; data.len()];
+        assert_eq!(b.read(&mut result, &mut 0)?, data.len());
+        assert_eq!(result, data);
+
+        // Changing a file only replaces the chunks that were changed
+        b.write(OffloadWrite::Buffer(b"changed"), &mut 10)?;
+        b.flush()?;
+        assert_eq!(store.chunk_count(), 4);
+        let mut result = vec![0u8; 20];
+        b.read(&mut result, &mut 0)?;
+        assert_eq!(&result[10..17], b"changed");
+        assert_eq!(&result[..10], &data[..10]);
+
+        drop((a, b));
+        assert_eq!(store.chunk_count(), 0);
+        Ok(())
+    }
 }
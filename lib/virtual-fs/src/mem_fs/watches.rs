@@ -0,0 +1,91 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::Inode;
+use crate::{WatchEvent, WatchEventKind, WatchSender};
+
+/// Returns the cookie that ties together the two halves of a rename
+pub(super) fn new_rename_cookie() -> u32 {
+    static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+    NEXT_COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The watches placed on the nodes of a file system, the senders of the
+/// watches that were dropped are pruned whenever an event is delivered.
+#[derive(Debug, Default)]
+pub(super) struct WatchTable {
+    watches: HashMap<Inode, Vec<WatchSender>>,
+}
+
+impl WatchTable {
+    pub(super) fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub(super) fn is_watched(&self, inode: Inode) -> bool {
+        self.watches.contains_key(&inode)
+    }
+
+    pub(super) fn add(&mut self, inode: Inode, sender: WatchSender) {
+        let senders = self.watches.entry(inode).or_default();
+        senders.retain(|s| !s.is_closed());
+        senders.push(sender);
+    }
+
+    /// Delivers an event to all the watches of a node
+    pub(super) fn notify(&mut self, inode: Inode, event: WatchEvent) {
+        if let Some(senders) = self.watches.get_mut(&inode) {
+            senders.retain(|s| s.send(event.clone()));
+            if senders.is_empty() {
+                self.watches.remove(&inode);
+            }
+        }
+    }
+
+    /// Removes all the watches, they are told that they are gone
+    pub(super) fn clear(&mut self) {
+        for sender in self.watches.drain().flat_map(|(_, senders)| senders) {
+            sender.send(WatchEvent::new(WatchEventKind::Ignored));
+        }
+    }
+
+    /// Tells the watches of a node that was removed that they are gone
+    pub(super) fn remove(&mut self, inode: Inode) {
+        for sender in self.watches.remove(&inode).into_iter().flatten() {
+            sender.send(WatchEvent::new(WatchEventKind::DeleteSelf));
+            sender.send(WatchEvent::new(WatchEventKind::Ignored));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Watch, WatchMask};
+
+    #[test]
+    fn test_watch_table() {
+        let mut table = WatchTable::default();
+        let (sender, watch) = Watch::channel(WatchMask::ALL);
+        table.add(1, sender);
+
+        table.notify(1, WatchEvent::new(WatchEventKind::Modify));
+        table.notify(2, WatchEvent::new(WatchEventKind::Modify));
+        assert_eq!(watch.try_recv().unwrap().kind, WatchEventKind::Modify);
+        assert_eq!(watch.try_recv(), None);
+
+        table.remove(1);
+        assert_eq!(watch.try_recv().unwrap().kind, WatchEventKind::DeleteSelf);
+        assert_eq!(watch.try_recv().unwrap().kind, WatchEventKind::Ignored);
+        assert!(table.is_empty());
+
+        // Watches that are dropped are forgotten
+        let (sender, watch) = Watch::channel(WatchMask::ALL);
+        table.add(1, sender);
+        drop(watch);
+        table.notify(1, WatchEvent::new(WatchEventKind::Modify));
+        assert!(table.is_empty());
+    }
+}
@@ -14,7 +14,7 @@ use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
 
 use crate::{
     ops, FileOpener, FileSystem, FileSystems, FsError, Metadata, OpenOptions, OpenOptionsConfig,
-    ReadDir, VirtualFile,
+    Permissions, ReadDir, VirtualFile, Watch, WatchMask,
 };
 
 /// A primary filesystem and chain of secondary filesystems that are overlayed
@@ -105,6 +105,58 @@ where
 
         Err(FsError::EntryNotFound)
     }
+
+    /// Copies a file that only exists in one of the secondaries up to the
+    /// primary so that it can be modified.
+    fn copy_file_to_primary(&self, path: &Path) -> Result<(), FsError> {
+        for fs in self.secondaries.filesystems() {
+            if fs.metadata(path).is_ok() {
+                if let Some(parent) = path.parent() {
+                    ops::create_dir_all(&self.primary, parent)?;
+                }
+                futures::executor::block_on(ops::copy_reference(fs, self.primary.as_ref(), path))?;
+                return Ok(());
+            }
+        }
+
+        Err(FsError::EntryNotFound)
+    }
+}
+
+impl<P, S> OverlayFileSystem<P, S>
+where
+    P: FileSystem + Send + 'static,
+    S: for<'a> FileSystems<'a> + Send + Sync + 'static,
+    for<'a> <<S as FileSystems<'a>>::Iter as IntoIterator>::IntoIter: Send,
+{
+    /// Brings an entry that only exists in one of the secondaries (along
+    /// with its extended attributes) up to the primary so that it can be
+    /// modified, the secondaries are never modified.
+    fn copy_entry_to_primary(&self, path: &Path) -> Result<(), FsError> {
+        let xattrs = self
+            .list_xattr(path)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|name| {
+                let value = self.get_xattr(path, &name).ok()?;
+                Some((name, value))
+            })
+            .collect::<Vec<_>>();
+
+        if self.metadata(path)?.is_dir() {
+            ops::create_dir_all(&self.primary, path)?;
+        } else {
+            self.copy_file_to_primary(path)?;
+        }
+
+        for (name, value) in xattrs {
+            match self.primary.set_xattr(path, &name, &value) {
+                Ok(()) | Err(FsError::Unsupported) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<P, S> FileSystem for OverlayFileSystem<P, S>
@@ -411,6 +463,188 @@ where
         self.permission_error_or_not_found(path)
     }
 
+    fn symlink(&self, original: &Path, link: &Path) -> Result<(), FsError> {
+        // You can not create links that use the whiteout prefix
+        if ops::is_white_out(link).is_some() {
+            return Err(FsError::InvalidInput);
+        }
+        if self.symlink_metadata(link).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        // The link may replace an entry that was earlier whited out and its
+        // parent may so far only exist in the secondaries
+        ops::remove_white_out(self.primary.as_ref(), link);
+        if let Some(parent) = link.parent() {
+            if self.read_dir(parent).is_ok() {
+                ops::create_dir_all(&self.primary, parent).ok();
+            }
+        }
+
+        // Links are always created in the primary
+        match self.primary.symlink(original, link) {
+            Err(e) if should_continue(e) => {}
+            other => return other,
+        }
+
+        Err(FsError::EntryNotFound)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<(), FsError> {
+        // Whiteout files can not be linked to
+        if ops::is_white_out(original).is_some() || ops::is_white_out(link).is_some() {
+            return Err(FsError::InvalidInput);
+        }
+        if self.symlink_metadata(link).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        // Files that only exist in a secondary are copied to the primary
+        // first so that both names refer to the same (writable) file
+        if self.primary.metadata(original).is_err() {
+            let meta = self.metadata(original)?;
+            if meta.is_dir() {
+                return Err(FsError::PermissionDenied);
+            }
+            self.copy_file_to_primary(original)?;
+        }
+
+        ops::remove_white_out(self.primary.as_ref(), link);
+        if let Some(parent) = link.parent() {
+            if self.read_dir(parent).is_ok() {
+                ops::create_dir_all(&self.primary, parent).ok();
+            }
+        }
+
+        self.primary.hard_link(original, link)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<(), FsError> {
+        // Whiteout files have no permissions of their own
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        match self.primary.set_permissions(path, permissions) {
+            Err(e) if should_continue(e) => {}
+            other => return other,
+        }
+
+        // Entries from the secondaries are brought into the primary before
+        // they are changed
+        self.copy_entry_to_primary(path)?;
+        self.primary.set_permissions(path, permissions)
+    }
+
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>, FsError> {
+        // Whiteout files can not be read, they are just markers
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        match self.primary.get_xattr(path, name) {
+            Err(e) if should_continue(e) => {}
+            other => return other,
+        }
+
+        if ops::has_white_out(&self.primary, path) {
+            return Err(FsError::EntryNotFound);
+        }
+
+        for fs in self.secondaries.filesystems() {
+            match fs.get_xattr(path, name) {
+                Err(e) if should_continue(e) => continue,
+                other => return other,
+            }
+        }
+
+        Err(FsError::EntryNotFound)
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), FsError> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        match self.primary.set_xattr(path, name, value) {
+            Err(e) if should_continue(e) => {}
+            other => return other,
+        }
+
+        self.copy_entry_to_primary(path)?;
+        self.primary.set_xattr(path, name, value)
+    }
+
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>, FsError> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        match self.primary.list_xattr(path) {
+            Err(e) if should_continue(e) => {}
+            other => return other,
+        }
+
+        if ops::has_white_out(&self.primary, path) {
+            return Err(FsError::EntryNotFound);
+        }
+
+        for fs in self.secondaries.filesystems() {
+            match fs.list_xattr(path) {
+                Err(e) if should_continue(e) => continue,
+                other => return other,
+            }
+        }
+
+        Err(FsError::EntryNotFound)
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<(), FsError> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        match self.primary.remove_xattr(path, name) {
+            Err(e) if should_continue(e) => {}
+            other => return other,
+        }
+
+        // Make sure the attribute exists before copying anything around
+        self.get_xattr(path, name)?;
+        self.copy_entry_to_primary(path)?;
+        self.primary.remove_xattr(path, name)
+    }
+
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch, FsError> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        match self.primary.watch(path, mask) {
+            Err(e) if should_continue(e) => {}
+            other => return other,
+        }
+
+        if ops::has_white_out(&self.primary, path) {
+            return Err(FsError::EntryNotFound);
+        }
+
+        // New entries always end up in the primary, so directories are
+        // brought up to it (which is cheap) to see them being created
+        if self.metadata(path)?.is_dir() && self.copy_entry_to_primary(path).is_ok() {
+            return self.primary.watch(path, mask);
+        }
+
+        for fs in self.secondaries.filesystems() {
+            match fs.watch(path, mask) {
+                Err(e) if should_continue(e) => continue,
+                other => return other,
+            }
+        }
+
+        Err(FsError::EntryNotFound)
+    }
+
     fn new_open_options(&self) -> OpenOptions<'_> {
         OpenOptions::new(self)
     }
@@ -845,6 +1079,22 @@ where
     where
         P: FileSystem + 'static,
     {
+        fn poll_sync(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> Poll<Result<(), std::io::Error>> {
+            match self.poll_copy_progress(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+            // There is nothing to sync in read-only state
+            match self.state {
+                CowState::ReadOnly(_) => Poll::Ready(Ok(())),
+                _ => Pin::new(self.state.as_mut()).poll_sync(cx),
+            }
+        }
+
         fn last_accessed(&self) -> u64 {
             self.state.as_ref().last_accessed()
         }
@@ -1457,6 +1707,143 @@ mod tests {
         assert!(ops::is_file(&fs.secondaries[0], "/secondary/file.txt"));
     }
 
+    #[tokio::test]
+    async fn link_files_from_secondary_fs() {
+        let primary = MemFS::default();
+        let secondary = MemFS::default();
+        ops::create_dir_all(&secondary, "/secondary").unwrap();
+        ops::write(&secondary, "/secondary/file.txt", b"Hello, World!")
+            .await
+            .unwrap();
+
+        let fs = OverlayFileSystem::new(primary, [secondary]);
+
+        // Symlinks are created in the primary even when the parent
+        // directory only exists in a secondary
+        fs.symlink(Path::new("file.txt"), Path::new("/secondary/link.txt"))
+            .unwrap();
+        assert_eq!(
+            fs.readlink(Path::new("/secondary/link.txt")).unwrap(),
+            Path::new("file.txt")
+        );
+        assert!(fs
+            .symlink_metadata(Path::new("/secondary/link.txt"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs.symlink(Path::new("file.txt"), Path::new("/secondary/file.txt")),
+            Err(FsError::AlreadyExists)
+        );
+
+        // Hard links copy the file into the primary first
+        fs.hard_link(
+            Path::new("/secondary/file.txt"),
+            Path::new("/secondary/hard.txt"),
+        )
+        .unwrap();
+        assert!(ops::is_file(&fs.primary, "/secondary/file.txt"));
+        assert_eq!(
+            ops::read_to_string(&fs, "/secondary/hard.txt")
+                .await
+                .unwrap(),
+            "Hello, World!"
+        );
+        assert_eq!(
+            ops::read_to_string(&fs.secondaries[0], "/secondary/file.txt")
+                .await
+                .unwrap(),
+            "Hello, World!"
+        );
+    }
+
+    #[tokio::test]
+    async fn set_permissions_of_secondary_entries() {
+        let primary = MemFS::default();
+        let secondary = MemFS::default();
+        ops::create_dir_all(&secondary, "/secondary").unwrap();
+        ops::write(&secondary, "/secondary/file.txt", b"Hello, World!")
+            .await
+            .unwrap();
+
+        let fs = OverlayFileSystem::new(primary, [secondary]);
+        let permissions = Permissions::new(0o700, 1000, 1000);
+
+        // Files are copied into the primary first
+        fs.set_permissions(Path::new("/secondary/file.txt"), permissions)
+            .unwrap();
+        assert!(ops::is_file(&fs.primary, "/secondary/file.txt"));
+        assert_eq!(
+            fs.metadata(Path::new("/secondary/file.txt"))
+                .unwrap()
+                .permissions(),
+            Some(permissions)
+        );
+        assert_eq!(
+            fs.secondaries[0]
+                .metadata(Path::new("/secondary/file.txt"))
+                .unwrap()
+                .permissions(),
+            Some(Permissions::new_file())
+        );
+
+        // Directories are recreated in the primary
+        fs.set_permissions(Path::new("/secondary"), permissions)
+            .unwrap();
+        assert_eq!(
+            fs.primary
+                .metadata(Path::new("/secondary"))
+                .unwrap()
+                .permissions(),
+            Some(permissions)
+        );
+        assert_eq!(
+            fs.set_permissions(Path::new("/missing"), permissions),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn xattrs_of_secondary_entries() {
+        let primary = MemFS::default();
+        let secondary = MemFS::default();
+        ops::write(&secondary, "/file.txt", b"Hello, World!")
+            .await
+            .unwrap();
+        secondary
+            .set_xattr(Path::new("/file.txt"), "user.a", b"1")
+            .unwrap();
+
+        let fs = OverlayFileSystem::new(primary, [secondary]);
+        assert_eq!(
+            fs.get_xattr(Path::new("/file.txt"), "user.a"),
+            Ok(b"1".to_vec())
+        );
+
+        // Changing an attribute brings the file (and its other attributes)
+        // into the primary
+        fs.set_xattr(Path::new("/file.txt"), "user.b", b"2")
+            .unwrap();
+        assert_eq!(
+            fs.primary.list_xattr(Path::new("/file.txt")),
+            Ok(vec!["user.a".to_string(), "user.b".to_string()])
+        );
+        assert_eq!(
+            fs.secondaries[0].list_xattr(Path::new("/file.txt")),
+            Ok(vec!["user.a".to_string()])
+        );
+
+        fs.remove_xattr(Path::new("/file.txt"), "user.a").unwrap();
+        assert_eq!(
+            fs.list_xattr(Path::new("/file.txt")),
+            Ok(vec!["user.b".to_string()])
+        );
+        assert_eq!(
+            fs.remove_xattr(Path::new("/file.txt"), "user.a"),
+            Err(FsError::AttributeNotFound)
+        );
+    }
+
     #[tokio::test]
     async fn rmdir_from_secondary_fs() {
         let primary = MemFS::default();
@@ -0,0 +1,111 @@
+//! POSIX style permission bits and ownership of the entries in a file system
+
+/// Mode bits and ownership of a file, directory or link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    /// Permission bits (including the setuid, setgid and sticky bits)
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Permissions {
+    /// User ID of the superuser which bypasses all the permission checks
+    pub const ROOT_UID: u32 = 0;
+
+    /// Mask of the bits in `mode` that can be changed with `chmod`
+    pub const MODE_MASK: u32 = 0o7777;
+
+    /// Access flag that requests the ability to read an entry
+    pub const READ: u32 = 0o4;
+    /// Access flag that requests the ability to write an entry
+    pub const WRITE: u32 = 0o2;
+    /// Access flag that requests the ability to execute a file (or to
+    /// search a directory)
+    pub const EXECUTE: u32 = 0o1;
+
+    pub fn new(mode: u32, uid: u32, gid: u32) -> Self {
+        Self {
+            mode: mode & Self::MODE_MASK,
+            uid,
+            gid,
+        }
+    }
+
+    /// Default permissions of new files (`rw-r--r--` owned by root)
+    pub fn new_file() -> Self {
+        Self::new(0o644, Self::ROOT_UID, 0)
+    }
+
+    /// Default permissions of new directories (`rwxr-xr-x` owned by root)
+    pub fn new_dir() -> Self {
+        Self::new(0o755, Self::ROOT_UID, 0)
+    }
+
+    /// Default permissions of new symbolic links (`rwxrwxrwx` owned by root)
+    pub fn new_symlink() -> Self {
+        Self::new(0o777, Self::ROOT_UID, 0)
+    }
+
+    /// Returns a copy with the mode bits replaced
+    pub fn with_mode(self, mode: u32) -> Self {
+        Self::new(mode, self.uid, self.gid)
+    }
+
+    /// Returns a copy with a different owner
+    pub fn with_owner(self, uid: u32, gid: u32) -> Self {
+        Self::new(self.mode, uid, gid)
+    }
+
+    /// Determines if the user `uid` (with primary group `gid`) is allowed
+    /// the `access` (a combination of [`Self::READ`], [`Self::WRITE`] and
+    /// [`Self::EXECUTE`]), only the first class that matches the user is
+    /// considered just like POSIX does.
+    pub fn allows(&self, uid: u32, gid: u32, access: u32) -> bool {
+        if uid == Self::ROOT_UID {
+            return true;
+        }
+
+        let granted = if uid == self.uid {
+            self.mode >> 6
+        } else if gid == self.gid {
+            self.mode >> 3
+        } else {
+            self.mode
+        } & 0o7;
+
+        granted & access == access
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permissions_allows() {
+        let perms = Permissions::new(0o640, 1000, 100);
+
+        assert!(perms.allows(1000, 100, Permissions::READ | Permissions::WRITE));
+        assert!(!perms.allows(1000, 100, Permissions::EXECUTE));
+        assert!(perms.allows(1001, 100, Permissions::READ));
+        assert!(!perms.allows(1001, 100, Permissions::WRITE));
+        assert!(!perms.allows(1001, 101, Permissions::READ));
+
+        // The superuser is allowed everything
+        assert!(perms.allows(Permissions::ROOT_UID, 0, Permissions::EXECUTE));
+
+        // The owner class is used even when it is more restrictive
+        let perms = Permissions::new(0o077, 1000, 100);
+        assert!(!perms.allows(1000, 100, Permissions::READ));
+        assert!(perms.allows(1001, 100, Permissions::READ));
+    }
+
+    #[test]
+    fn test_permissions_mode_mask() {
+        let perms = Permissions::new(0o100644, 0, 0);
+        assert_eq!(perms.mode, 0o644);
+        assert_eq!(perms.with_mode(0o4755).mode, 0o4755);
+        assert_eq!(perms.with_owner(1, 2), Permissions::new(0o644, 1, 2));
+    }
+}
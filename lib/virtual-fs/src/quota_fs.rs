@@ -0,0 +1,634 @@
+//! A [`FileSystem`] wrapper that limits the number of bytes and entries that
+//! can be stored in another file system.
+
+use std::{
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+use crate::{
+    FileLock, FileOpener, FileSystem, FsError, LockOwner, LockRange, Metadata, OpenOptions,
+    OpenOptionsConfig, Permissions, ReadDir, Result, VirtualFile, Watch, WatchMask,
+};
+
+/// The limits enforced by a [`QuotaFileSystem`], `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaLimits {
+    /// Maximum number of bytes stored in files
+    pub max_bytes: Option<u64>,
+    /// Maximum number of files, directories and symbolic links
+    pub max_inodes: Option<u64>,
+}
+
+/// What is currently stored in a [`QuotaFileSystem`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub bytes: u64,
+    pub inodes: u64,
+}
+
+#[derive(Debug)]
+struct QuotaState {
+    limits: QuotaLimits,
+    bytes: AtomicU64,
+    inodes: AtomicU64,
+}
+
+impl QuotaState {
+    fn reserve(counter: &AtomicU64, limit: Option<u64>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                let used = used.checked_add(amount)?;
+                match limit {
+                    Some(limit) if used > limit => None,
+                    _ => Some(used),
+                }
+            })
+            .map(|_| ())
+            .map_err(|_| FsError::StorageFull)
+    }
+
+    fn release(counter: &AtomicU64, amount: u64) {
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                Some(used.saturating_sub(amount))
+            })
+            .ok();
+    }
+
+    fn reserve_bytes(&self, amount: u64) -> Result<()> {
+        Self::reserve(&self.bytes, self.limits.max_bytes, amount)
+    }
+
+    fn release_bytes(&self, amount: u64) {
+        Self::release(&self.bytes, amount)
+    }
+
+    fn reserve_inode(&self) -> Result<()> {
+        Self::reserve(&self.inodes, self.limits.max_inodes, 1)
+    }
+
+    fn release_inode(&self) {
+        Self::release(&self.inodes, 1)
+    }
+
+    /// Number of bytes that can still be stored
+    fn available_bytes(&self) -> u64 {
+        match self.limits.max_bytes {
+            Some(limit) => limit.saturating_sub(self.bytes.load(Ordering::Acquire)),
+            None => u64::MAX,
+        }
+    }
+
+    /// Gives back what an entry that was removed used
+    fn release_entry(&self, meta: &Metadata) {
+        if meta.is_file() {
+            self.release_bytes(meta.len());
+        }
+        self.release_inode();
+    }
+}
+
+/// Enforces [`QuotaLimits`] on the files and directories stored in another
+/// [`FileSystem`], operations that would go over the limits fail with
+/// [`FsError::StorageFull`].
+///
+/// The entries that already exist are counted when the file system is
+/// wrapped. Hard links are counted as if they were copies of the file, and
+/// the size of a file is only counted for the bytes that are actually
+/// written (sparse files are not detected).
+#[derive(Debug, Clone)]
+pub struct QuotaFileSystem<F> {
+    inner: F,
+    state: Arc<QuotaState>,
+}
+
+impl<F> QuotaFileSystem<F>
+where
+    F: FileSystem,
+{
+    /// Wraps a file system, counting the entries it already contains
+    pub fn new(inner: F, limits: QuotaLimits) -> Result<Self> {
+        let mut usage = QuotaUsage::default();
+        Self::scan(&inner, Path::new("/"), &mut usage)?;
+
+        Ok(QuotaFileSystem {
+            inner,
+            state: Arc::new(QuotaState {
+                limits,
+                bytes: AtomicU64::new(usage.bytes),
+                inodes: AtomicU64::new(usage.inodes),
+            }),
+        })
+    }
+
+    fn scan(fs: &F, path: &Path, usage: &mut QuotaUsage) -> Result<()> {
+        for entry in fs.read_dir(path)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            usage.inodes += 1;
+            if meta.is_dir() {
+                Self::scan(fs, &entry.path, usage)?;
+            } else if meta.is_file() {
+                usage.bytes += meta.len();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<F> QuotaFileSystem<F> {
+    pub fn limits(&self) -> QuotaLimits {
+        self.state.limits
+    }
+
+    pub fn usage(&self) -> QuotaUsage {
+        QuotaUsage {
+            bytes: self.state.bytes.load(Ordering::Acquire),
+            inodes: self.state.inodes.load(Ordering::Acquire),
+        }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F> FileSystem for QuotaFileSystem<F>
+where
+    F: FileSystem,
+{
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.state.reserve_inode()?;
+        self.inner
+            .create_dir(path)
+            .inspect_err(|_| self.state.release_inode())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.inner.remove_dir(path)?;
+        self.state.release_inode();
+        Ok(())
+    }
+
+    fn rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            // Whatever gets replaced by the rename is released
+            let replaced = match from == to {
+                true => None,
+                false => self.inner.symlink_metadata(to).ok(),
+            };
+            self.inner.rename(from, to).await?;
+            if let Some(meta) = replaced {
+                self.state.release_entry(&meta);
+            }
+            Ok(())
+        })
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let meta = self.inner.symlink_metadata(path)?;
+        self.inner.remove_file(path)?;
+        self.state.release_entry(&meta);
+        Ok(())
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        self.state.reserve_inode()?;
+        self.inner
+            .symlink(original, link)
+            .inspect_err(|_| self.state.release_inode())
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let len = self.inner.metadata(original)?.len();
+        self.state.reserve_inode()?;
+        if let Err(e) = self.state.reserve_bytes(len) {
+            self.state.release_inode();
+            return Err(e);
+        }
+        self.inner.hard_link(original, link).inspect_err(|_| {
+            self.state.release_inode();
+            self.state.release_bytes(len);
+        })
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        self.inner.set_permissions(path, permissions)
+    }
+
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        self.inner.get_xattr(path, name)
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        self.inner.set_xattr(path, name, value)
+    }
+
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>> {
+        self.inner.list_xattr(path)
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        self.inner.remove_xattr(path, name)
+    }
+
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+        self.inner.watch(path, mask)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        name: String,
+        path: &Path,
+        fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> Result<()> {
+        self.inner.mount(name, path, fs)
+    }
+}
+
+impl<F> FileOpener for QuotaFileSystem<F>
+where
+    F: FileSystem,
+{
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let existing = self.inner.metadata(path).ok();
+        let creates = existing.is_none() && (conf.create || conf.create_new);
+        if creates {
+            self.state.reserve_inode()?;
+        }
+
+        let file = self
+            .inner
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)
+            .inspect_err(|_| {
+                if creates {
+                    self.state.release_inode();
+                }
+            })?;
+
+        if let Some(meta) = existing.filter(|_| conf.truncate && conf.write) {
+            self.state.release_bytes(meta.len());
+        }
+
+        Ok(Box::new(QuotaFile {
+            inner: file,
+            state: self.state.clone(),
+            cursor: 0,
+            append: conf.append,
+        }))
+    }
+}
+
+/// A file of a [`QuotaFileSystem`], it keeps track of its cursor to know
+/// how much a write grows the file.
+#[derive(Debug)]
+struct QuotaFile {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+    state: Arc<QuotaState>,
+    cursor: u64,
+    append: bool,
+}
+
+impl QuotaFile {
+    /// Reserves the bytes a file grows by when its size changes from `size`
+    /// to `new_size`, or releases them when it shrinks
+    fn resize(&self, size: u64, new_size: u64) -> Result<()> {
+        if new_size > size {
+            self.state.reserve_bytes(new_size - size)
+        } else {
+            self.state.release_bytes(size - new_size);
+            Ok(())
+        }
+    }
+}
+
+impl VirtualFile for QuotaFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn set_times(&mut self, atime: Option<u64>, mtime: Option<u64>) -> Result<()> {
+        self.inner.set_times(atime, mtime)
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        let size = self.inner.size();
+        self.resize(size, new_size)?;
+        self.inner.set_len(new_size).inspect_err(|_| {
+            self.resize(new_size, size).ok();
+        })
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        let size = self.inner.size();
+        self.inner.unlink()?;
+        self.state.release_bytes(size);
+        self.state.release_inode();
+        Ok(())
+    }
+
+    fn try_lock(&mut self, owner: LockOwner, lock: FileLock, range: LockRange) -> Result<()> {
+        self.inner.try_lock(owner, lock, range)
+    }
+
+    fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Poll<Result<()>> {
+        self.inner.poll_lock(cx, owner, lock, range)
+    }
+
+    fn test_lock(
+        &mut self,
+        owner: LockOwner,
+        lock: FileLock,
+        range: LockRange,
+    ) -> Result<Option<(FileLock, LockRange)>> {
+        self.inner.test_lock(owner, lock, range)
+    }
+
+    fn unlock_all(&mut self, owner: LockOwner) {
+        self.inner.unlock_all(owner)
+    }
+
+    fn poll_sync(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_sync(cx)
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn get_special_fd(&self) -> Option<u32> {
+        self.inner.get_special_fd()
+    }
+
+    fn write_from_mmap(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        let size = self.inner.size();
+        let growth = offset.saturating_add(len).saturating_sub(size);
+        self.state.reserve_bytes(growth)?;
+        self.inner
+            .write_from_mmap(offset, len)
+            .inspect_err(|_| self.state.release_bytes(growth))
+    }
+
+    fn poll_read_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.inner).poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.inner).poll_write_ready(cx)
+    }
+}
+
+impl AsyncRead for QuotaFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut *self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            self.cursor += (buf.filled().len() - before) as u64;
+        }
+        result
+    }
+}
+
+impl AsyncWrite for QuotaFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let size = self.inner.size();
+        if self.append {
+            self.cursor = size;
+        }
+
+        // Writes that only partially fit are shortened
+        let end = size.saturating_add(self.state.available_bytes());
+        let allowed = end.saturating_sub(self.cursor).min(buf.len() as u64) as usize;
+        if allowed == 0 && !buf.is_empty() {
+            return Poll::Ready(Err(FsError::StorageFull.into()));
+        }
+        let buf = &buf[..allowed];
+
+        let growth = (self.cursor + buf.len() as u64).saturating_sub(size);
+        if let Err(e) = self.state.reserve_bytes(growth) {
+            return Poll::Ready(Err(e.into()));
+        }
+
+        let result = Pin::new(&mut *self.inner).poll_write(cx, buf);
+        let written = match &result {
+            Poll::Ready(Ok(written)) => *written as u64,
+            _ => 0,
+        };
+        self.cursor += written;
+
+        // Only what was actually written is kept
+        let new_size = self.inner.size().max(size);
+        self.state
+            .release_bytes(growth.saturating_sub(new_size - size));
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_shutdown(cx)
+    }
+}
+
+impl AsyncSeek for QuotaFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        Pin::new(&mut *self.inner).start_seek(position)
+    }
+
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        let result = Pin::new(&mut *self.inner).poll_complete(cx);
+        if let Poll::Ready(Ok(position)) = result {
+            self.cursor = position;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{mem_fs, ops};
+
+    fn quota_fs(
+        max_bytes: Option<u64>,
+        max_inodes: Option<u64>,
+    ) -> QuotaFileSystem<mem_fs::FileSystem> {
+        QuotaFileSystem::new(
+            mem_fs::FileSystem::default(),
+            QuotaLimits {
+                max_bytes,
+                max_inodes,
+            },
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_quota_bytes() {
+        let fs = quota_fs(Some(10), None);
+
+        ops::write(&fs, "/a.txt", b"hello").await.unwrap();
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 5,
+                inodes: 1
+            }
+        );
+
+        // Writes that do not fit are shortened, and then refused
+        let mut f = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open("/b.txt")
+            .unwrap();
+        assert_eq!(f.write(b"0123456789").await.unwrap(), 5);
+        assert_eq!(
+            f.write(b"x").await.unwrap_err().kind(),
+            io::ErrorKind::StorageFull
+        );
+
+        // Overwriting does not use more space
+        f.seek(SeekFrom::Start(0)).await.unwrap();
+        f.write_all(b"abcde").await.unwrap();
+        assert_eq!(fs.usage().bytes, 10);
+        assert_eq!(f.set_len(20), Err(FsError::StorageFull));
+        assert_eq!(f.set_len(2), Ok(()));
+        assert_eq!(fs.usage().bytes, 7);
+        drop(f);
+
+        assert_eq!(fs.remove_file(Path::new("/a.txt")), Ok(()));
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 2,
+                inodes: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quota_inodes() {
+        let fs = quota_fs(None, Some(2));
+
+        assert_eq!(fs.create_dir(Path::new("/foo")), Ok(()));
+        ops::write(&fs, "/foo/a.txt", b"hello").await.unwrap();
+        assert_eq!(fs.create_dir(Path::new("/bar")), Err(FsError::StorageFull));
+        assert!(fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open("/b.txt")
+            .is_err());
+        assert_eq!(
+            fs.symlink(Path::new("/foo"), Path::new("/link")),
+            Err(FsError::StorageFull)
+        );
+
+        // Failed operations do not use anything up
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 5,
+                inodes: 2
+            }
+        );
+
+        // Renaming over an existing entry releases it
+        ops::write(&fs.inner, "/b.txt", b"world!").await.unwrap();
+        let fs = QuotaFileSystem::new(fs.into_inner(), QuotaLimits::default()).unwrap();
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 11,
+                inodes: 3
+            }
+        );
+        assert_eq!(
+            fs.rename(Path::new("/b.txt"), Path::new("/foo/a.txt"))
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 6,
+                inodes: 2
+            }
+        );
+    }
+}
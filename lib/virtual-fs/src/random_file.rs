@@ -79,6 +79,9 @@ impl VirtualFile for RandomFile {
     fn unlink(&mut self) -> crate::Result<()> {
         Ok(())
     }
+    fn duplicate(&self) -> Option<Box<dyn VirtualFile + Send + Sync>> {
+        Some(Box::new(Self::default()))
+    }
     fn poll_read_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
         Poll::Ready(Ok(0))
     }
@@ -99,6 +99,9 @@ impl VirtualFile for DeviceFile {
     fn get_special_fd(&self) -> Option<u32> {
         Some(self.fd)
     }
+    fn duplicate(&self) -> Option<Box<dyn VirtualFile + Send + Sync>> {
+        Some(Box::new(Self::new(self.fd)))
+    }
     fn poll_read_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
         Poll::Ready(Ok(0))
     }
@@ -230,6 +230,7 @@ fn transform_into_read_dir(path: &Path, fs_entries: &[FsEntry<'_>]) -> crate::Re
                 created: 0,
                 modified: 0,
                 len: e.get_len(),
+                permissions: None,
             }),
         })
         .collect();
@@ -321,6 +322,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: fs_entry.get_len(),
+                permissions: None,
             })
         } else if let Some(_fs) = self.volumes.values().find_map(|v| v.read_dir(&path).ok()) {
             Ok(Metadata {
@@ -329,6 +331,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                permissions: None,
             })
         } else {
             self.memory.metadata(Path::new(&path))
@@ -364,6 +367,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: fs_entry.get_len(),
+                permissions: None,
             })
         } else if self
             .volumes
@@ -377,6 +381,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                permissions: None,
             })
         } else {
             self.memory.symlink_metadata(Path::new(&path))
@@ -9,7 +9,7 @@ use std::{
 
 use crate::{
     limiter::DynFsMemoryLimiter, mem_fs, BoxFuture, FileSystem, Metadata, OpenOptions, ReadDir,
-    Result,
+    Result, Watch, WatchMask,
 };
 
 #[derive(Debug, Default, Clone)]
@@ -93,6 +93,38 @@ impl FileSystem for TmpFileSystem {
         self.fs.remove_file(path)
     }
 
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        self.fs.symlink(original, link)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        self.fs.hard_link(original, link)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: crate::Permissions) -> Result<()> {
+        self.fs.set_permissions(path, permissions)
+    }
+
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        self.fs.get_xattr(path, name)
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        self.fs.set_xattr(path, name, value)
+    }
+
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>> {
+        self.fs.list_xattr(path)
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        self.fs.remove_xattr(path, name)
+    }
+
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+        self.fs.watch(path, mask)
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         self.fs.new_open_options()
     }
@@ -7,7 +7,7 @@ use std::{
 use futures::future::BoxFuture;
 use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
 
-use crate::{FileOpener, FileSystem, OpenOptionsConfig, VirtualFile};
+use crate::{FileOpener, FileSystem, OpenOptionsConfig, VirtualFile, Watch, WatchMask};
 
 /// A [`FileSystem`] wrapper that will automatically log all operations at the
 /// `trace` level.
@@ -83,6 +83,46 @@ where
         self.0.remove_file(path)
     }
 
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn symlink(&self, original: &Path, link: &Path) -> crate::Result<()> {
+        self.0.symlink(original, link)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn hard_link(&self, original: &Path, link: &Path) -> crate::Result<()> {
+        self.0.hard_link(original, link)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn set_permissions(&self, path: &Path, permissions: crate::Permissions) -> crate::Result<()> {
+        self.0.set_permissions(path, permissions)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn get_xattr(&self, path: &Path, name: &str) -> crate::Result<Vec<u8>> {
+        self.0.get_xattr(path, name)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self, value), fields(value_len = value.len()), err)]
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> crate::Result<()> {
+        self.0.set_xattr(path, name, value)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn list_xattr(&self, path: &Path) -> crate::Result<Vec<String>> {
+        self.0.list_xattr(path)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn remove_xattr(&self, path: &Path, name: &str) -> crate::Result<()> {
+        self.0.remove_xattr(path, name)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn watch(&self, path: &Path, mask: WatchMask) -> crate::Result<Watch> {
+        self.0.watch(path, mask)
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     fn new_open_options(&self) -> crate::OpenOptions {
         crate::OpenOptions::new(self)
@@ -154,6 +194,55 @@ impl VirtualFile for TraceFile {
         self.file.set_len(new_size)
     }
 
+    #[tracing::instrument(level = "trace", skip(self), fields(path=%self.path.display()), err)]
+    fn try_lock(
+        &mut self,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> crate::Result<()> {
+        self.file.try_lock(owner, lock, range)
+    }
+
+    fn poll_lock(
+        &mut self,
+        cx: &mut Context<'_>,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> Poll<crate::Result<()>> {
+        self.file.poll_lock(cx, owner, lock, range)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), fields(path=%self.path.display()), err)]
+    fn test_lock(
+        &mut self,
+        owner: crate::LockOwner,
+        lock: crate::FileLock,
+        range: crate::LockRange,
+    ) -> crate::Result<Option<(crate::FileLock, crate::LockRange)>> {
+        self.file.test_lock(owner, lock, range)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), fields(path=%self.path.display()))]
+    fn unlock_all(&mut self, owner: crate::LockOwner) {
+        self.file.unlock_all(owner)
+    }
+
+    #[tracing::instrument(level = "trace", skip_all, fields(path=%self.path.display()))]
+    fn poll_sync(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        let result = Pin::new(&mut *self.file).poll_sync(cx);
+
+        if let Poll::Ready(Err(e)) = &result {
+            tracing::trace!(error = e as &dyn std::error::Error);
+        }
+
+        result
+    }
+
     fn unlink(&mut self) -> crate::Result<()> {
         self.file.unlink()
     }
@@ -84,6 +84,59 @@ impl UnionFileSystem {
 
         None
     }
+
+    /// Number of directories between the root of `path` and its parent
+    fn parent_depth(path: &Path) -> usize {
+        path.parent()
+            .map(|p| {
+                p.components()
+                    .filter(|c| matches!(c, std::path::Component::Normal(_)))
+                    .count()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Converts the target of a symbolic link in the union into the target
+    /// stored by the mount. Absolute targets within the mount are rebased
+    /// onto the root of the mount. Links can not span mounts, so targets
+    /// outside of the mount (absolute ones, or relative ones that climb out
+    /// of it) are rejected as the file system of the mount would not be
+    /// able to follow them.
+    fn link_target_to_mount(&self, prefix: &Path, link: &Path, original: &Path) -> Result<PathBuf> {
+        use std::path::Component;
+
+        if original.has_root() {
+            let original = self.prepare_path(original);
+            return original
+                .strip_prefix(prefix)
+                .map(|original| PathBuf::from("/").join(original))
+                .map_err(|_| FsError::InvalidInput);
+        }
+        if self.is_root() {
+            return Ok(original.to_owned());
+        }
+
+        let mut depth = Self::parent_depth(link);
+        for component in original.components() {
+            match component {
+                Component::ParentDir if depth == 0 => return Err(FsError::InvalidInput),
+                Component::ParentDir => depth -= 1,
+                Component::Normal(_) => depth += 1,
+                _ => {}
+            }
+        }
+        Ok(original.to_owned())
+    }
+
+    /// Reverses [`UnionFileSystem::link_target_to_mount`] so the target is
+    /// returned as it was given when the link was created.
+    fn link_target_from_mount(&self, prefix: &Path, target: PathBuf) -> PathBuf {
+        if target.has_root() {
+            let target: PathBuf = target.components().skip(1).collect();
+            return PathBuf::from("/").join(prefix).join(target);
+        }
+        target
+    }
 }
 
 impl FileSystem for UnionFileSystem {
@@ -92,8 +145,9 @@ impl FileSystem for UnionFileSystem {
 
         if path.as_os_str().is_empty() {
             Err(FsError::NotAFile)
-        } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
-            fs.readlink(&path)
+        } else if let Some((prefix, path, fs)) = self.find_mount(path.to_owned()) {
+            let target = fs.readlink(&path)?;
+            Ok(self.link_target_from_mount(&prefix, target))
         } else {
             Err(FsError::EntryNotFound)
         }
@@ -114,6 +168,7 @@ impl FileSystem for UnionFileSystem {
                         created: 0,
                         modified: 0,
                         len: 0,
+                        permissions: None,
                     }),
                 })
                 .collect::<Vec<_>>();
@@ -191,6 +246,7 @@ impl FileSystem for UnionFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                permissions: None,
             })
         } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
             fs.metadata(&path)
@@ -208,6 +264,7 @@ impl FileSystem for UnionFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                permissions: None,
             })
         } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
             fs.symlink_metadata(&path)
@@ -226,6 +283,104 @@ impl FileSystem for UnionFileSystem {
             Err(FsError::EntryNotFound)
         }
     }
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        let link = self.prepare_path(link);
+
+        if link.as_os_str().is_empty() {
+            Err(FsError::AlreadyExists)
+        } else if let Some((prefix, link, fs)) = self.find_mount(link.to_owned()) {
+            let original = self.link_target_to_mount(&prefix, &link, original)?;
+            fs.symlink(&original, &link)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let original = self.prepare_path(original);
+        let link = self.prepare_path(link);
+
+        if original.as_os_str().is_empty() {
+            Err(FsError::PermissionDenied)
+        } else if let Some((prefix, original, fs)) = self.find_mount(original.to_owned()) {
+            // Hard links can not span mounts
+            let link = link
+                .strip_prefix(prefix)
+                .map_err(|_| FsError::InvalidInput)?;
+            let link = PathBuf::from("/").join(link);
+
+            fs.hard_link(&original, &link)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        let path = self.prepare_path(path);
+
+        if path.as_os_str().is_empty() {
+            Err(FsError::PermissionDenied)
+        } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
+            fs.set_permissions(&path, permissions)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
+    fn get_xattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        let path = self.prepare_path(path);
+
+        if path.as_os_str().is_empty() {
+            Err(FsError::AttributeNotFound)
+        } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
+            fs.get_xattr(&path, name)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        let path = self.prepare_path(path);
+
+        if path.as_os_str().is_empty() {
+            Err(FsError::PermissionDenied)
+        } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
+            fs.set_xattr(&path, name, value)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
+    fn list_xattr(&self, path: &Path) -> Result<Vec<String>> {
+        let path = self.prepare_path(path);
+
+        if path.as_os_str().is_empty() {
+            Ok(Vec::new())
+        } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
+            fs.list_xattr(&path)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        let path = self.prepare_path(path);
+
+        if path.as_os_str().is_empty() {
+            Err(FsError::AttributeNotFound)
+        } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
+            fs.remove_xattr(&path, name)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
+
+    fn watch(&self, path: &Path, mask: WatchMask) -> Result<Watch> {
+        let path = self.prepare_path(path);
+
+        if path.as_os_str().is_empty() {
+            // The root only changes when file systems are mounted
+            Err(FsError::Unsupported)
+        } else if let Some((_, path, fs)) = self.find_mount(path.to_owned()) {
+            fs.watch(&path, mask)
+        } else {
+            Err(FsError::EntryNotFound)
+        }
+    }
     fn new_open_options(&self) -> OpenOptions {
         OpenOptions::new(self)
     }
@@ -949,6 +1104,146 @@ mod tests {
         let _ = fs_extra::remove_items(&["./test_readdir"]);
     }
 
+    #[tokio::test]
+    async fn test_links() {
+        let fs = gen_filesystem();
+
+        crate::ops::write(&fs, "/test_rename/a.txt", b"hello")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs.symlink(
+                Path::new("/test_rename/a.txt"),
+                Path::new("/test_rename/b.txt")
+            ),
+            Ok(()),
+            "creating a symlink within a mount",
+        );
+        assert_eq!(
+            fs.readlink(Path::new("/test_rename/b.txt")),
+            Ok(PathBuf::from("/test_rename/a.txt")),
+            "the target is returned as it was given",
+        );
+        assert_eq!(
+            crate::ops::read_to_string(&fs, "/test_rename/b.txt")
+                .await
+                .unwrap(),
+            "hello"
+        );
+
+        crate::ops::create_dir_all(&fs, "/test_rename/sub").unwrap();
+        for (link, target) in [
+            ("/test_rename/sub/e.txt", "../a.txt"),
+            ("/test_rename/sub/f.txt", "/test_rename/sub/../a.txt"),
+        ] {
+            assert_eq!(
+                fs.symlink(Path::new(target), Path::new(link)),
+                Ok(()),
+                "creating a symlink to {target}",
+            );
+            assert_eq!(
+                fs.readlink(Path::new(link)),
+                Ok(PathBuf::from(target)),
+                "the target of {link} is returned as it was given",
+            );
+        }
+        assert_eq!(
+            crate::ops::read_to_string(&fs, "/test_rename/sub/e.txt")
+                .await
+                .unwrap(),
+            "hello"
+        );
+        for (link, target) in [
+            ("/test_rename/sub/d.txt", "/test_remove_dir/missing.txt"),
+            (
+                "/test_rename/sub/g.txt",
+                "../../test_remove_dir/missing.txt",
+            ),
+        ] {
+            assert_eq!(
+                fs.symlink(Path::new(target), Path::new(link)),
+                Err(FsError::InvalidInput),
+                "symlinks to {target} can not span mounts",
+            );
+        }
+
+        assert_eq!(
+            fs.hard_link(
+                Path::new("/test_rename/a.txt"),
+                Path::new("/test_rename/c.txt")
+            ),
+            Ok(()),
+            "creating a hard link within a mount",
+        );
+        assert_eq!(
+            crate::ops::read_to_string(&fs, "/test_rename/c.txt")
+                .await
+                .unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            fs.hard_link(
+                Path::new("/test_rename/a.txt"),
+                Path::new("/test_remove_dir/a.txt")
+            ),
+            Err(FsError::InvalidInput),
+            "hard links can not span mounts",
+        );
+    }
+
+    #[cfg(all(unix, feature = "host-fs"))]
+    #[tokio::test]
+    async fn test_links_over_host() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let host = crate::host_fs::FileSystem::new(tokio::runtime::Handle::current(), temp.path())
+            .unwrap();
+        std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+        std::fs::create_dir(temp.path().join("sub")).unwrap();
+
+        let fs = UnionFileSystem::new();
+        fs.mount("host".to_string(), Path::new("/mnt/host"), Box::new(host))
+            .unwrap();
+        fs.mount(
+            "mem_fs".to_string(),
+            Path::new("/usr"),
+            Box::new(mem_fs::FileSystem::default()),
+        )
+        .unwrap();
+
+        for (link, target) in [
+            ("/mnt/host/b.txt", "/mnt/host/a.txt"),
+            ("/mnt/host/sub/c.txt", "../a.txt"),
+        ] {
+            assert_eq!(
+                fs.symlink(Path::new(target), Path::new(link)),
+                Ok(()),
+                "creating a symlink to {target}",
+            );
+            assert_eq!(
+                fs.readlink(Path::new(link)),
+                Ok(PathBuf::from(target)),
+                "the target of {link} is returned as it was given",
+            );
+        }
+        assert_eq!(
+            std::fs::read_link(temp.path().join("b.txt")).unwrap(),
+            temp.path().join("a.txt"),
+            "the host follows the link within its root",
+        );
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("sub/c.txt")).unwrap(),
+            "hello"
+        );
+
+        assert_eq!(
+            fs.symlink(Path::new("/usr/lib/x"), Path::new("/mnt/host/link")),
+            Err(FsError::InvalidInput),
+            "symlinks can not span mounts",
+        );
+        assert!(!temp.path().join("link").exists());
+    }
+
     /*
     #[tokio::test]
     async fn test_canonicalize() {
@@ -0,0 +1,352 @@
+//! Notifications about the changes made to the entries of a file system,
+//! modelled after Linux's `inotify(7)`.
+//!
+//! A [`Watch`] is created with [`crate::FileSystem::watch`], the file system
+//! then pushes [`WatchEvent`]s into it through a [`WatchSender`] for as long
+//! as the [`Watch`] is alive.
+
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    ops::{BitOr, BitOrAssign},
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+};
+
+/// Maximum number of events that are queued on a watch before the newer
+/// ones are dropped (the default `max_queued_events` of Linux)
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+/// The kinds of events a [`Watch`] is interested in, the bits are the same
+/// as the `IN_*` constants of Linux.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct WatchMask(u32);
+
+impl WatchMask {
+    /// A file was written to (or truncated)
+    pub const MODIFY: Self = Self(0x2);
+    /// The metadata changed (permissions, ownership, extended attributes)
+    pub const ATTRIB: Self = Self(0x4);
+    /// A file that was opened for writing was closed
+    pub const CLOSE_WRITE: Self = Self(0x8);
+    /// An entry was renamed away from the watched directory
+    pub const MOVED_FROM: Self = Self(0x40);
+    /// An entry was renamed into the watched directory
+    pub const MOVED_TO: Self = Self(0x80);
+    /// An entry was created in the watched directory
+    pub const CREATE: Self = Self(0x100);
+    /// An entry was removed from the watched directory
+    pub const DELETE: Self = Self(0x200);
+    /// The watched entry itself was removed
+    pub const DELETE_SELF: Self = Self(0x400);
+    /// The watched entry itself was renamed
+    pub const MOVE_SELF: Self = Self(0x800);
+    /// All of the above
+    pub const ALL: Self = Self(0xfce);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Converts the raw bits of a mask, unknown bits are ignored
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for WatchMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for WatchMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl std::fmt::Debug for WatchMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WatchMask({:#x})", self.0)
+    }
+}
+
+/// What happened to the entry a [`WatchEvent`] is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WatchEventKind {
+    Modify,
+    Attrib,
+    CloseWrite,
+    MovedFrom,
+    MovedTo,
+    Create,
+    Delete,
+    DeleteSelf,
+    MoveSelf,
+    /// Events were lost because the queue of the watch was full
+    Overflow,
+    /// The watch was removed (explicitly or because the watched entry no
+    /// longer exists), it will not receive any more events
+    Ignored,
+}
+
+impl WatchEventKind {
+    /// The `IN_*` bit of Linux that represents this kind of event
+    pub const fn bits(&self) -> u32 {
+        match self {
+            WatchEventKind::Modify => WatchMask::MODIFY.0,
+            WatchEventKind::Attrib => WatchMask::ATTRIB.0,
+            WatchEventKind::CloseWrite => WatchMask::CLOSE_WRITE.0,
+            WatchEventKind::MovedFrom => WatchMask::MOVED_FROM.0,
+            WatchEventKind::MovedTo => WatchMask::MOVED_TO.0,
+            WatchEventKind::Create => WatchMask::CREATE.0,
+            WatchEventKind::Delete => WatchMask::DELETE.0,
+            WatchEventKind::DeleteSelf => WatchMask::DELETE_SELF.0,
+            WatchEventKind::MoveSelf => WatchMask::MOVE_SELF.0,
+            WatchEventKind::Overflow => 0x4000,
+            WatchEventKind::Ignored => 0x8000,
+        }
+    }
+
+    /// Overflows and the removal of a watch are reported regardless of
+    /// the mask of the watch
+    fn is_wanted(&self, mask: WatchMask) -> bool {
+        matches!(self, WatchEventKind::Overflow | WatchEventKind::Ignored)
+            || mask.0 & self.bits() != 0
+    }
+}
+
+/// A change made to a watched entry, or to one of the entries of a
+/// watched directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub kind: WatchEventKind,
+    /// Name of the entry inside the watched directory that changed, `None`
+    /// when the event is about the watched entry itself
+    pub name: Option<OsString>,
+    /// Whether the entry that changed is a directory
+    pub is_dir: bool,
+    /// Identifies the [`WatchEventKind::MovedFrom`] and
+    /// [`WatchEventKind::MovedTo`] pair of a single rename, zero otherwise
+    pub cookie: u32,
+}
+
+impl WatchEvent {
+    pub fn new(kind: WatchEventKind) -> Self {
+        Self {
+            kind,
+            name: None,
+            is_dir: false,
+            cookie: 0,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<OsString>, is_dir: bool) -> Self {
+        self.name = Some(name.into());
+        self.is_dir = is_dir;
+        self
+    }
+
+    pub fn with_cookie(mut self, cookie: u32) -> Self {
+        self.cookie = cookie;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct WatchState {
+    events: VecDeque<WatchEvent>,
+    /// Set once the overflow event was queued, so that only one is queued
+    overflowed: bool,
+    wakers: Vec<Waker>,
+}
+
+#[derive(Debug)]
+struct WatchQueue {
+    mask: WatchMask,
+    state: Mutex<WatchState>,
+}
+
+/// The receiving end of a watch on an entry of a file system, the watch
+/// is removed when this is dropped.
+#[derive(Debug)]
+pub struct Watch {
+    queue: Arc<WatchQueue>,
+}
+
+/// Used by file systems to deliver events to a [`Watch`].
+#[derive(Debug, Clone)]
+pub struct WatchSender {
+    mask: WatchMask,
+    queue: Weak<WatchQueue>,
+}
+
+impl Watch {
+    /// Creates a new watch that receives the events selected by `mask`
+    pub fn channel(mask: WatchMask) -> (WatchSender, Watch) {
+        let queue = Arc::new(WatchQueue {
+            mask,
+            state: Mutex::new(WatchState::default()),
+        });
+        let sender = WatchSender {
+            mask,
+            queue: Arc::downgrade(&queue),
+        };
+        (sender, Watch { queue })
+    }
+
+    pub fn mask(&self) -> WatchMask {
+        self.queue.mask
+    }
+
+    /// Takes the oldest event of the queue, if there is one
+    pub fn try_recv(&self) -> Option<WatchEvent> {
+        let mut state = self.queue.state.lock().unwrap();
+        Self::pop(&mut state)
+    }
+
+    /// Waits for the next event
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<WatchEvent> {
+        let mut state = self.queue.state.lock().unwrap();
+        match Self::pop(&mut state) {
+            Some(event) => Poll::Ready(event),
+            None => {
+                add_waker(&mut state.wakers, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn pop(state: &mut WatchState) -> Option<WatchEvent> {
+        let event = state.events.pop_front()?;
+        if event.kind == WatchEventKind::Overflow {
+            state.overflowed = false;
+        }
+        Some(event)
+    }
+
+    /// Returns the number of queued events, or registers the waker to be
+    /// notified when one arrives
+    pub fn poll_ready(&self, waker: &Waker) -> Poll<usize> {
+        let mut state = self.queue.state.lock().unwrap();
+        match state.events.len() {
+            0 => {
+                add_waker(&mut state.wakers, waker);
+                Poll::Pending
+            }
+            len => Poll::Ready(len),
+        }
+    }
+}
+
+impl WatchSender {
+    pub fn mask(&self) -> WatchMask {
+        self.mask
+    }
+
+    /// Determines if the [`Watch`] was dropped
+    pub fn is_closed(&self) -> bool {
+        self.queue.strong_count() == 0
+    }
+
+    /// Queues an event (unless the watch is not interested in it), returns
+    /// `false` when the [`Watch`] was dropped.
+    pub fn send(&self, event: WatchEvent) -> bool {
+        let Some(queue) = self.queue.upgrade() else {
+            return false;
+        };
+        if !event.kind.is_wanted(self.mask) {
+            return true;
+        }
+
+        let mut state = queue.state.lock().unwrap();
+        // Identical consecutive events are merged, just like Linux does
+        if state.events.back() == Some(&event) {
+            return true;
+        }
+        if state.events.len() >= MAX_QUEUED_EVENTS {
+            if !state.overflowed {
+                state.overflowed = true;
+                state
+                    .events
+                    .push_back(WatchEvent::new(WatchEventKind::Overflow));
+            }
+        } else {
+            state.events.push_back(event);
+        }
+
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        true
+    }
+}
+
+fn add_waker(wakers: &mut Vec<Waker>, waker: &Waker) {
+    if !wakers.iter().any(|w| w.will_wake(waker)) {
+        wakers.push(waker.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_channel() {
+        let (sender, watch) = Watch::channel(WatchMask::CREATE | WatchMask::DELETE);
+
+        assert!(sender.send(WatchEvent::new(WatchEventKind::Create).with_name("a", false)));
+        // Events outside of the mask and duplicates are dropped
+        assert!(sender.send(WatchEvent::new(WatchEventKind::Modify).with_name("a", false)));
+        assert!(sender.send(WatchEvent::new(WatchEventKind::Create).with_name("a", false)));
+        assert!(sender.send(WatchEvent::new(WatchEventKind::Delete).with_name("a", false)));
+        assert!(sender.send(WatchEvent::new(WatchEventKind::Ignored)));
+
+        assert_eq!(
+            watch.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Create).with_name("a", false))
+        );
+        assert_eq!(
+            watch.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Delete).with_name("a", false))
+        );
+        assert_eq!(
+            watch.try_recv(),
+            Some(WatchEvent::new(WatchEventKind::Ignored))
+        );
+        assert_eq!(watch.try_recv(), None);
+
+        drop(watch);
+        assert!(sender.is_closed());
+        assert!(!sender.send(WatchEvent::new(WatchEventKind::Create)));
+    }
+
+    #[test]
+    fn test_watch_overflow() {
+        let (sender, watch) = Watch::channel(WatchMask::ALL);
+
+        for i in 0..MAX_QUEUED_EVENTS + 10 {
+            sender.send(WatchEvent::new(WatchEventKind::Create).with_name(i.to_string(), false));
+        }
+
+        let events: Vec<_> = std::iter::from_fn(|| watch.try_recv()).collect();
+        assert_eq!(events.len(), MAX_QUEUED_EVENTS + 1);
+        assert_eq!(events.last().unwrap().kind, WatchEventKind::Overflow);
+    }
+}
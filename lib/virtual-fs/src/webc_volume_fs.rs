@@ -457,6 +457,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 6148,
+                    permissions: None,
                 }),
             },
             DirEntry {
@@ -470,6 +471,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 0,
+                    permissions: None,
                 }),
             },
             DirEntry {
@@ -483,6 +485,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 4694941,
+                    permissions: None,
                 }),
             },
             DirEntry {
@@ -496,6 +499,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 0,
+                    permissions: None,
                 }),
             },
         ];
@@ -520,6 +524,7 @@ mod tests {
             created: 0,
             modified,
             len: 4694941,
+            permissions: None,
         };
         assert_eq!(
             fs.metadata("/lib/python.wasm".as_ref()).unwrap(),
@@ -546,6 +551,7 @@ mod tests {
                 created: 0,
                 modified,
                 len: 0,
+                permissions: None,
             },
         );
         assert_eq!(
@@ -0,0 +1,1208 @@
+//! Captures the traffic of a [`VirtualNetworking`] implementation to a
+//! pcapng file that can be opened with Wireshark.
+//!
+//! Sockets only ever see payloads, so [`CaptureNetworking`] synthesizes the
+//! IP, TCP and UDP headers around them (including a TCP handshake and
+//! sequence numbers that let Wireshark reassemble streams). Raw sockets
+//! already deal in Ethernet frames which are recorded as is on a second
+//! interface of the capture.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use virtual_mio::InterestHandler;
+
+use crate::{
+    IpCidr, IpRoute, Result, SocketStatus, StreamSecurity, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualIoSource, VirtualNetworking,
+    VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualUnixDatagramSocket, VirtualUnixListener, VirtualUnixSocket,
+};
+
+const LINKTYPE_ETHERNET: u16 = 1;
+const LINKTYPE_RAW: u16 = 101;
+
+const IP_PROTO_ICMP: u8 = 1;
+const IP_PROTO_TCP: u8 = 6;
+const IP_PROTO_UDP: u8 = 17;
+const IP_PROTO_ICMPV6: u8 = 58;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Largest TCP payload that is put into a single synthesized segment
+const MAX_SEGMENT: usize = 65_000;
+
+/// Packets that can wait for the writer before new ones are dropped
+const MAX_QUEUED_PACKETS: usize = 4096;
+
+/// Longest time written packets stay in the buffer of the writer
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Wraps a [`VirtualNetworking`] implementation and records the traffic of
+/// its TCP, UDP, ICMP and raw sockets to a pcapng file.
+///
+/// Unix domain sockets are passed through without being captured.
+#[derive(Debug, Clone)]
+pub struct CaptureNetworking<N> {
+    inner: N,
+    capture: Arc<Capture>,
+}
+
+impl<N: VirtualNetworking> CaptureNetworking<N> {
+    /// Captures the traffic into `writer`, which receives the pcapng
+    /// headers straight away
+    pub fn new(inner: N, writer: impl Write + Send + 'static) -> std::io::Result<Self> {
+        Ok(Self {
+            inner,
+            capture: Arc::new(Capture::new(Box::new(writer))?),
+        })
+    }
+
+    /// Captures the traffic into a newly created file
+    pub fn create(inner: N, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = File::create(path)?;
+        Self::new(inner, BufWriter::new(file))
+    }
+
+    /// Returns the networking implementation that is being captured
+    pub fn inner(&self) -> &N {
+        &self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<N: VirtualNetworking> VirtualNetworking for CaptureNetworking<N> {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let socket = self.inner.bind_raw().await?;
+        Ok(Box::new(CaptureRawSocket {
+            inner: socket,
+            capture: self.capture.clone(),
+        }))
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let listener = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(CaptureTcpListener {
+            inner: listener,
+            capture: self.capture.clone(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let socket = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(CaptureUdpSocket {
+            local: socket.addr_local().unwrap_or(addr),
+            inner: socket,
+            capture: self.capture.clone(),
+        }))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let socket = self.inner.bind_icmp(addr).await?;
+        Ok(Box::new(CaptureIcmpSocket {
+            local: socket.addr_local().unwrap_or(SocketAddr::new(addr, 0)),
+            inner: socket,
+            capture: self.capture.clone(),
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let socket = self.inner.connect_tcp(addr, peer).await?;
+        let local = socket.addr_local().unwrap_or(addr);
+        let peer = socket.addr_peer().unwrap_or(peer);
+        Ok(Box::new(CaptureTcpSocket {
+            inner: socket,
+            flow: TcpFlow::connected(self.capture.clone(), local, peer),
+        }))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixListener + Sync>> {
+        self.inner.listen_unix(path).await
+    }
+
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        self.inner.connect_unix(path).await
+    }
+
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        self.inner.bind_unix_datagram(path).await
+    }
+}
+
+/// Interfaces that are described in the pcapng section header, in order
+#[derive(Debug, Clone, Copy)]
+enum Interface {
+    /// Synthesized IP packets
+    Ip = 0,
+    /// Ethernet frames of raw sockets
+    Ethernet = 1,
+}
+
+/// Writes pcapng blocks, see <https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-01.html>
+struct PcapWriter {
+    writer: Box<dyn Write + Send>,
+}
+
+impl PcapWriter {
+    fn new(writer: Box<dyn Write + Send>) -> std::io::Result<Self> {
+        let mut ret = Self { writer };
+
+        // Section Header Block
+        let mut body = Vec::with_capacity(16);
+        body.extend(0x1A2B3C4Du32.to_le_bytes());
+        body.extend(1u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend((-1i64).to_le_bytes());
+        ret.write_block(0x0A0D0D0A, &body)?;
+
+        // Interface Description Blocks, a snap length of zero means unlimited
+        for link_type in [LINKTYPE_RAW, LINKTYPE_ETHERNET] {
+            let mut body = Vec::with_capacity(8);
+            body.extend(link_type.to_le_bytes());
+            body.extend(0u16.to_le_bytes());
+            body.extend(0u32.to_le_bytes());
+            ret.write_block(0x00000001, &body)?;
+        }
+
+        ret.writer.flush()?;
+        Ok(ret)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+        let padding = (4 - body.len() % 4) % 4;
+        let len = (12 + body.len() + padding) as u32;
+        self.writer.write_all(&block_type.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&[0u8; 3][..padding])?;
+        self.writer.write_all(&len.to_le_bytes())
+    }
+
+    /// Writes an Enhanced Packet Block with a timestamp in microseconds
+    fn write_packet(&mut self, record: &Record) -> std::io::Result<()> {
+        let Record {
+            interface,
+            timestamp,
+            ref packet,
+        } = *record;
+
+        let mut body = Vec::with_capacity(20 + packet.len());
+        body.extend((interface as u32).to_le_bytes());
+        body.extend(((timestamp >> 32) as u32).to_le_bytes());
+        body.extend((timestamp as u32).to_le_bytes());
+        body.extend((packet.len() as u32).to_le_bytes());
+        body.extend((packet.len() as u32).to_le_bytes());
+        body.extend(packet);
+        self.write_block(0x00000006, &body)
+    }
+
+    /// Writes the packets until all the senders are gone. Captures are
+    /// mostly looked at after a process went wrong, so nothing is left
+    /// sitting in a buffer for longer than [`FLUSH_INTERVAL`].
+    fn run(mut self, rx: Receiver<Record>) {
+        let mut flush_at: Option<Instant> = None;
+        loop {
+            let record = match flush_at {
+                Some(at) => rx.recv_timeout(at.saturating_duration_since(Instant::now())),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let result = match record {
+                Ok(record) => {
+                    flush_at.get_or_insert_with(|| Instant::now() + FLUSH_INTERVAL);
+                    self.write_packet(&record)
+                }
+                Err(RecvTimeoutError::Timeout) => Ok(()),
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Err(err) = self.writer.flush() {
+                        tracing::warn!("failed to flush the network capture - {err}");
+                    }
+                    return;
+                }
+            };
+            let result = result.and_then(|()| match flush_at {
+                Some(at) if at <= Instant::now() => {
+                    flush_at = None;
+                    self.writer.flush()
+                }
+                _ => Ok(()),
+            });
+            if let Err(err) = result {
+                tracing::warn!("stopped capturing network traffic - {err}");
+                return;
+            }
+        }
+    }
+}
+
+/// A packet that waits to be written to the capture
+struct Record {
+    interface: Interface,
+    /// Microseconds since the UNIX epoch at which the packet was seen
+    timestamp: u64,
+    packet: Vec<u8>,
+}
+
+/// Destination of the captured packets that is shared by all the sockets.
+///
+/// The packets are handed over to a dedicated thread that writes them, so
+/// that sockets never wait for the file (when the thread falls too far
+/// behind packets are left out of the capture instead).
+struct Capture {
+    tx: Option<SyncSender<Record>>,
+    writer: Option<JoinHandle<()>>,
+    dropping: AtomicBool,
+}
+
+impl std::fmt::Debug for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Capture").finish_non_exhaustive()
+    }
+}
+
+impl Capture {
+    fn new(writer: Box<dyn Write + Send>) -> std::io::Result<Self> {
+        let writer = PcapWriter::new(writer)?;
+        let (tx, rx) = mpsc::sync_channel(MAX_QUEUED_PACKETS);
+        let writer = std::thread::Builder::new()
+            .name("pcap-writer".to_string())
+            .spawn(move || writer.run(rx))?;
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+            dropping: AtomicBool::new(false),
+        })
+    }
+
+    fn record(&self, interface: Interface, packet: Vec<u8>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let record = Record {
+            interface,
+            timestamp,
+            packet,
+        };
+        let Some(tx) = self.tx.as_ref() else {
+            return;
+        };
+        match tx.try_send(record) {
+            Ok(()) => {
+                self.dropping.store(false, Ordering::Relaxed);
+            }
+            Err(TrySendError::Full(_)) => {
+                if !self.dropping.swap(true, Ordering::Relaxed) {
+                    tracing::warn!("the capture file can not keep up, dropping packets");
+                }
+            }
+            // The writer already reported why it stopped
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    fn record_ip(&self, packet: Vec<u8>) {
+        self.record(Interface::Ip, packet)
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        // The writer drains the queue and flushes once the channel closes
+        self.tx.take();
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+    }
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Both ends of a packet need to be of the same family, IPv4 addresses are
+/// mapped into IPv6 when they are not
+fn same_family(src: IpAddr, dst: IpAddr) -> (IpAddr, IpAddr) {
+    fn to_v6(ip: IpAddr) -> Ipv6Addr {
+        match ip {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        }
+    }
+    match (src, dst) {
+        (IpAddr::V4(_), IpAddr::V4(_)) => (src, dst),
+        _ => (to_v6(src).into(), to_v6(dst).into()),
+    }
+}
+
+/// Checksum of a TCP or UDP segment including the IP pseudo header
+fn transport_checksum(src: IpAddr, dst: IpAddr, protocol: u8, segment: &[u8]) -> u16 {
+    let mut pseudo = Vec::with_capacity(40 + segment.len());
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            pseudo.extend(src.octets());
+            pseudo.extend(dst.octets());
+            pseudo.extend([0, protocol]);
+            pseudo.extend((segment.len() as u16).to_be_bytes());
+        }
+        (src, dst) => {
+            let (IpAddr::V6(src), IpAddr::V6(dst)) = same_family(src, dst) else {
+                unreachable!()
+            };
+            pseudo.extend(src.octets());
+            pseudo.extend(dst.octets());
+            pseudo.extend((segment.len() as u32).to_be_bytes());
+            pseudo.extend([0, 0, 0, protocol]);
+        }
+    }
+    pseudo.extend(segment);
+    checksum(&pseudo)
+}
+
+/// Puts an IP header in front of a transport segment
+fn ip_packet(src: IpAddr, dst: IpAddr, protocol: u8, segment: &[u8]) -> Vec<u8> {
+    match same_family(src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut packet = Vec::with_capacity(20 + segment.len());
+            packet.extend([0x45, 0]);
+            packet.extend(((20 + segment.len()) as u16).to_be_bytes());
+            // Identification and the don't fragment flag
+            packet.extend([0, 0, 0x40, 0]);
+            packet.extend([64, protocol, 0, 0]);
+            packet.extend(src.octets());
+            packet.extend(dst.octets());
+            let sum = checksum(&packet);
+            packet[10..12].copy_from_slice(&sum.to_be_bytes());
+            packet.extend(segment);
+            packet
+        }
+        (src, dst) => {
+            let (IpAddr::V6(src), IpAddr::V6(dst)) = (src, dst) else {
+                unreachable!()
+            };
+            let mut packet = Vec::with_capacity(40 + segment.len());
+            packet.extend([0x60, 0, 0, 0]);
+            packet.extend((segment.len() as u16).to_be_bytes());
+            packet.extend([protocol, 64]);
+            packet.extend(src.octets());
+            packet.extend(dst.octets());
+            packet.extend(segment);
+            packet
+        }
+    }
+}
+
+fn tcp_packet(
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let (src_ip, dst_ip) = same_family(src.ip(), dst.ip());
+    let mut segment = Vec::with_capacity(20 + payload.len());
+    segment.extend(src.port().to_be_bytes());
+    segment.extend(dst.port().to_be_bytes());
+    segment.extend(seq.to_be_bytes());
+    segment.extend(ack.to_be_bytes());
+    segment.extend([5 << 4, flags]);
+    segment.extend(u16::MAX.to_be_bytes());
+    // Checksum and urgent pointer
+    segment.extend([0, 0, 0, 0]);
+    segment.extend(payload);
+    let sum = transport_checksum(src_ip, dst_ip, IP_PROTO_TCP, &segment);
+    segment[16..18].copy_from_slice(&sum.to_be_bytes());
+    ip_packet(src_ip, dst_ip, IP_PROTO_TCP, &segment)
+}
+
+fn udp_packet(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let (src_ip, dst_ip) = same_family(src.ip(), dst.ip());
+    let mut segment = Vec::with_capacity(8 + payload.len());
+    segment.extend(src.port().to_be_bytes());
+    segment.extend(dst.port().to_be_bytes());
+    segment.extend(((8 + payload.len()) as u16).to_be_bytes());
+    segment.extend([0, 0]);
+    segment.extend(payload);
+    // A zero checksum means that there is none, so it is sent as all ones
+    let sum = match transport_checksum(src_ip, dst_ip, IP_PROTO_UDP, &segment) {
+        0 => 0xffff,
+        sum => sum,
+    };
+    segment[6..8].copy_from_slice(&sum.to_be_bytes());
+    ip_packet(src_ip, dst_ip, IP_PROTO_UDP, &segment)
+}
+
+/// ICMP messages already carry their own header and checksum
+fn icmp_packet(src: IpAddr, dst: IpAddr, message: &[u8]) -> Vec<u8> {
+    let protocol = match same_family(src, dst) {
+        (IpAddr::V4(_), _) => IP_PROTO_ICMP,
+        _ => IP_PROTO_ICMPV6,
+    };
+    ip_packet(src, dst, protocol, message)
+}
+
+/// Synthesizes the TCP segments of a connection from what its socket sends
+/// and receives, both sides start with an initial sequence number of zero
+#[derive(Debug)]
+struct TcpFlow {
+    capture: Arc<Capture>,
+    local: SocketAddr,
+    peer: SocketAddr,
+    local_seq: u32,
+    peer_seq: u32,
+    local_fin: bool,
+    peer_fin: bool,
+}
+
+impl TcpFlow {
+    fn new(capture: Arc<Capture>, local: SocketAddr, peer: SocketAddr) -> Self {
+        Self {
+            capture,
+            local,
+            peer,
+            local_seq: 0,
+            peer_seq: 0,
+            local_fin: false,
+            peer_fin: false,
+        }
+    }
+
+    /// Flow of a connection that the guest opened
+    fn connected(capture: Arc<Capture>, local: SocketAddr, peer: SocketAddr) -> Self {
+        let mut ret = Self::new(capture, local, peer);
+        ret.outbound(TCP_SYN, &[]);
+        ret.inbound(TCP_SYN | TCP_ACK, &[]);
+        ret.outbound(TCP_ACK, &[]);
+        ret
+    }
+
+    /// Flow of a connection that the guest accepted
+    fn accepted(capture: Arc<Capture>, local: SocketAddr, peer: SocketAddr) -> Self {
+        let mut ret = Self::new(capture, local, peer);
+        ret.inbound(TCP_SYN, &[]);
+        ret.outbound(TCP_SYN | TCP_ACK, &[]);
+        ret.inbound(TCP_ACK, &[]);
+        ret
+    }
+
+    fn outbound(&mut self, flags: u8, payload: &[u8]) {
+        let packet = tcp_packet(
+            self.local,
+            self.peer,
+            self.local_seq,
+            self.peer_seq,
+            flags,
+            payload,
+        );
+        self.capture.record_ip(packet);
+        self.local_seq = self
+            .local_seq
+            .wrapping_add(payload.len() as u32 + control_len(flags));
+    }
+
+    fn inbound(&mut self, flags: u8, payload: &[u8]) {
+        let packet = tcp_packet(
+            self.peer,
+            self.local,
+            self.peer_seq,
+            self.local_seq,
+            flags,
+            payload,
+        );
+        self.capture.record_ip(packet);
+        self.peer_seq = self
+            .peer_seq
+            .wrapping_add(payload.len() as u32 + control_len(flags));
+    }
+
+    fn send(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_SEGMENT) {
+            self.outbound(TCP_PSH | TCP_ACK, chunk);
+        }
+    }
+
+    fn recv(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_SEGMENT) {
+            self.inbound(TCP_PSH | TCP_ACK, chunk);
+        }
+    }
+
+    fn send_fin(&mut self) {
+        if !self.local_fin {
+            self.local_fin = true;
+            self.outbound(TCP_FIN | TCP_ACK, &[]);
+        }
+    }
+
+    fn recv_fin(&mut self) {
+        if !self.peer_fin {
+            self.peer_fin = true;
+            self.inbound(TCP_FIN | TCP_ACK, &[]);
+        }
+    }
+}
+
+/// SYN and FIN take up a sequence number of their own
+fn control_len(flags: u8) -> u32 {
+    (flags & (TCP_SYN | TCP_FIN) != 0) as u32
+}
+
+/// Returns the bytes that were received into `buf`
+fn received(buf: &[MaybeUninit<u8>], amt: usize) -> &[u8] {
+    let buf = &buf[..amt];
+    // The inner socket just wrote these bytes
+    unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, buf.len()) }
+}
+
+#[derive(Debug)]
+struct CaptureTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    capture: Arc<Capture>,
+}
+
+impl VirtualIoSource for CaptureTcpListener {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for CaptureTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (socket, peer) = self.inner.try_accept()?;
+        let local = socket.addr_local().or_else(|_| self.inner.addr_local())?;
+        let socket = CaptureTcpSocket {
+            inner: socket,
+            flow: TcpFlow::accepted(self.capture.clone(), local, peer),
+        };
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+#[derive(Debug)]
+struct CaptureTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    flow: TcpFlow,
+}
+
+impl Drop for CaptureTcpSocket {
+    fn drop(&mut self) {
+        self.flow.send_fin();
+    }
+}
+
+impl VirtualIoSource for CaptureTcpSocket {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for CaptureTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for CaptureTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let sent = self.inner.try_send(data)?;
+        self.flow.send(&data[..sent]);
+        Ok(sent)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()?;
+        self.flow.send_fin();
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let read = self.inner.try_recv(buf, peek)?;
+        if !peek {
+            match read {
+                0 if !buf.is_empty() => self.flow.recv_fin(),
+                read => self.flow.recv(received(buf, read)),
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl VirtualTcpSocket for CaptureTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, reuse: bool) -> Result<()> {
+        self.inner.set_nodelay(reuse)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_dontroute(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_dontroute(keepalive)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)?;
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.flow.send_fin();
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// Implements the parts that are common to all the datagram sockets
+macro_rules! capture_datagram_socket {
+    ($name:ident) => {
+        impl VirtualIoSource for $name {
+            fn remove_handler(&mut self) {
+                self.inner.remove_handler()
+            }
+
+            fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+                self.inner.poll_read_ready(cx)
+            }
+
+            fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+                self.inner.poll_write_ready(cx)
+            }
+        }
+
+        impl VirtualSocket for $name {
+            fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+                self.inner.set_ttl(ttl)
+            }
+
+            fn ttl(&self) -> Result<u32> {
+                self.inner.ttl()
+            }
+
+            fn addr_local(&self) -> Result<SocketAddr> {
+                self.inner.addr_local()
+            }
+
+            fn status(&self) -> Result<SocketStatus> {
+                self.inner.status()
+            }
+
+            fn set_handler(
+                &mut self,
+                handler: Box<dyn InterestHandler + Send + Sync>,
+            ) -> Result<()> {
+                self.inner.set_handler(handler)
+            }
+        }
+    };
+}
+
+#[derive(Debug)]
+struct CaptureUdpSocket {
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+    local: SocketAddr,
+    capture: Arc<Capture>,
+}
+
+capture_datagram_socket!(CaptureUdpSocket);
+
+impl VirtualConnectionlessSocket for CaptureUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let sent = self.inner.try_send_to(data, addr)?;
+        self.capture
+            .record_ip(udp_packet(self.local, addr, &data[..sent]));
+        Ok(sent)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        let (read, addr) = self.inner.try_recv_from(buf, peek)?;
+        if !peek {
+            self.capture
+                .record_ip(udp_packet(addr, self.local, received(buf, read)));
+        }
+        Ok((read, addr))
+    }
+}
+
+impl VirtualUdpSocket for CaptureUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+#[derive(Debug)]
+struct CaptureIcmpSocket {
+    inner: Box<dyn VirtualIcmpSocket + Sync>,
+    local: SocketAddr,
+    capture: Arc<Capture>,
+}
+
+capture_datagram_socket!(CaptureIcmpSocket);
+
+impl VirtualConnectionlessSocket for CaptureIcmpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let sent = self.inner.try_send_to(data, addr)?;
+        self.capture
+            .record_ip(icmp_packet(self.local.ip(), addr.ip(), &data[..sent]));
+        Ok(sent)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        let (read, addr) = self.inner.try_recv_from(buf, peek)?;
+        if !peek {
+            self.capture
+                .record_ip(icmp_packet(addr.ip(), self.local.ip(), received(buf, read)));
+        }
+        Ok((read, addr))
+    }
+}
+
+impl VirtualIcmpSocket for CaptureIcmpSocket {}
+
+#[derive(Debug)]
+struct CaptureRawSocket {
+    inner: Box<dyn VirtualRawSocket + Sync>,
+    capture: Arc<Capture>,
+}
+
+capture_datagram_socket!(CaptureRawSocket);
+
+impl VirtualRawSocket for CaptureRawSocket {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let sent = self.inner.try_send(data)?;
+        self.capture
+            .record(Interface::Ethernet, data[..sent].to_vec());
+        Ok(sent)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let read = self.inner.try_recv(buf, peek)?;
+        if !peek {
+            self.capture
+                .record(Interface::Ethernet, received(buf, read).to_vec());
+        }
+        Ok(read)
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.inner.set_promiscuous(promiscuous)
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        self.inner.promiscuous()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoopbackNetworking;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Splits a pcapng file into its blocks
+    fn blocks(mut data: &[u8]) -> Vec<(u32, Vec<u8>)> {
+        let mut ret = Vec::new();
+        while !data.is_empty() {
+            let block_type = u32::from_le_bytes(data[0..4].try_into().unwrap());
+            let len = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+            assert_eq!(&data[len - 4..len], &data[4..8]);
+            ret.push((block_type, data[8..len - 4].to_vec()));
+            data = &data[len..];
+        }
+        ret
+    }
+
+    /// Returns the TCP flags and payload of the synthesized packets
+    fn tcp_segments(data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        blocks(data)
+            .into_iter()
+            .filter(|(block_type, _)| *block_type == 6)
+            .map(|(_, body)| {
+                let len = u32::from_le_bytes(body[12..16].try_into().unwrap()) as usize;
+                let packet = &body[20..20 + len];
+                assert_eq!(packet[9], IP_PROTO_TCP);
+                assert_eq!(checksum(&packet[..20]), 0);
+                let segment = &packet[20..];
+                assert_eq!(
+                    transport_checksum(
+                        Ipv4Addr::LOCALHOST.into(),
+                        Ipv4Addr::LOCALHOST.into(),
+                        IP_PROTO_TCP,
+                        segment
+                    ),
+                    0
+                );
+                (segment[13], segment[20..].to_vec())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_checksum() {
+        // IPv4 header with a well known checksum
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(checksum(&header), 0xb861);
+    }
+
+    #[tokio::test]
+    async fn test_capture_tcp() {
+        let buffer = SharedBuffer::default();
+        let net = CaptureNetworking::new(LoopbackNetworking::new(), buffer.clone()).unwrap();
+
+        let addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8080);
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let mut client = net
+            .inner()
+            .loopback_connect_to(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0), addr)
+            .unwrap();
+        let (mut server, _) = listener.try_accept().unwrap();
+
+        server.try_send(b"hello").unwrap();
+        let mut buf = [MaybeUninit::uninit(); 16];
+        assert_eq!(client.try_recv(&mut buf, false).unwrap(), 5);
+        client.try_send(b"world").unwrap();
+        assert_eq!(server.try_recv(&mut buf, true).unwrap(), 5);
+        assert_eq!(server.try_recv(&mut buf, false).unwrap(), 5);
+        drop(server);
+        // The capture waits for its writer once the last socket is gone
+        drop(listener);
+        drop(net);
+
+        let data = buffer.0.lock().unwrap().clone();
+        let blocks = blocks(&data);
+        assert_eq!(blocks[0].0, 0x0A0D0D0A);
+        assert_eq!(blocks[1].0, 1);
+        assert_eq!(blocks[2].0, 1);
+
+        assert_eq!(
+            tcp_segments(&data),
+            vec![
+                (TCP_SYN, vec![]),
+                (TCP_SYN | TCP_ACK, vec![]),
+                (TCP_ACK, vec![]),
+                (TCP_PSH | TCP_ACK, b"hello".to_vec()),
+                (TCP_PSH | TCP_ACK, b"world".to_vec()),
+                (TCP_FIN | TCP_ACK, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_capture_is_flushed_while_open() {
+        let buffer = SharedBuffer::default();
+        let writer = BufWriter::with_capacity(1 << 20, buffer.clone());
+        let capture = Capture::new(Box::new(writer)).unwrap();
+        let headers = buffer.0.lock().unwrap().len();
+
+        let src = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1234);
+        capture.record_ip(udp_packet(src, src, b"ping"));
+
+        let started = Instant::now();
+        while buffer.0.lock().unwrap().len() == headers {
+            assert!(started.elapsed() < Duration::from_secs(5), "never flushed");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let data = buffer.0.lock().unwrap().clone();
+        assert_eq!(blocks(&data).last().unwrap().0, 6);
+    }
+
+    #[test]
+    fn test_udp_packet() {
+        let src = SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 1234);
+        let dst = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 53);
+        let packet = udp_packet(src, dst, b"query");
+
+        // Mixed families are mapped into IPv6
+        assert_eq!(packet[0] >> 4, 6);
+        assert_eq!(packet[6], IP_PROTO_UDP);
+        assert_eq!(u16::from_be_bytes([packet[4], packet[5]]), 13);
+        let (src_ip, dst_ip) = same_family(src.ip(), dst.ip());
+        assert_eq!(
+            transport_checksum(src_ip, dst_ip, IP_PROTO_UDP, &packet[40..]),
+            0
+        );
+        assert_eq!(&packet[48..], b"query");
+    }
+}
@@ -196,6 +196,24 @@ impl RemoteNetworkingClient {
         Self::new(tx, rx, rx_work)
     }
 
+    /// Authenticates with the server on the other end of the stream using
+    /// `credentials` and then talks to it over an encrypted channel, see
+    /// [`crate::secure`] for the details
+    #[cfg(feature = "remote-secure")]
+    pub async fn new_from_secure_async_io<TX, RX>(
+        tx: TX,
+        rx: RX,
+        format: FrameSerializationFormat,
+        credentials: &crate::secure::RemoteCredentials,
+    ) -> Result<(Self, RemoteNetworkingClientDriver)>
+    where
+        TX: AsyncWrite + Send + 'static,
+        RX: AsyncRead + Send + 'static,
+    {
+        let (tx, rx) = crate::secure::client_handshake(tx, rx, credentials).await?;
+        Ok(Self::new_from_async_io(tx, rx, format))
+    }
+
     /// Creates a new interface on the remote location using
     /// a unique interface ID and a pair of channels
     #[cfg(feature = "hyper")]
@@ -250,6 +268,58 @@ impl RemoteNetworkingClient {
         Self::new(tx, rx, rx_work)
     }
 
+    /// Authenticates with the server on the other end of the websocket
+    /// using `credentials` and then talks to it over an encrypted channel,
+    /// the same way as [`Self::new_from_secure_async_io`]
+    #[cfg(all(feature = "hyper", feature = "remote-secure"))]
+    pub async fn new_from_secure_hyper_ws_io(
+        tx: futures_util::stream::SplitSink<
+            hyper_tungstenite::WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+            hyper_tungstenite::tungstenite::Message,
+        >,
+        rx: futures_util::stream::SplitStream<
+            hyper_tungstenite::WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+        >,
+        format: FrameSerializationFormat,
+        credentials: &crate::secure::RemoteCredentials,
+    ) -> Result<(Self, RemoteNetworkingClientDriver)> {
+        Self::new_from_secure_async_io(
+            crate::secure::WebSocketWriter::new(tx),
+            crate::secure::WebSocketReader::new(rx),
+            format,
+            credentials,
+        )
+        .await
+    }
+
+    /// Authenticates with the server on the other end of the websocket
+    /// using `credentials` and then talks to it over an encrypted channel,
+    /// the same way as [`Self::new_from_secure_async_io`]
+    #[cfg(all(feature = "tokio-tungstenite", feature = "remote-secure"))]
+    pub async fn new_from_secure_tokio_ws_io(
+        tx: futures_util::stream::SplitSink<
+            tokio_tungstenite::WebSocketStream<
+                tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+            >,
+            tokio_tungstenite::tungstenite::Message,
+        >,
+        rx: futures_util::stream::SplitStream<
+            tokio_tungstenite::WebSocketStream<
+                tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+            >,
+        >,
+        format: FrameSerializationFormat,
+        credentials: &crate::secure::RemoteCredentials,
+    ) -> Result<(Self, RemoteNetworkingClientDriver)> {
+        Self::new_from_secure_async_io(
+            crate::secure::WebSocketWriter::new(tx),
+            crate::secure::WebSocketReader::new(rx),
+            format,
+            credentials,
+        )
+        .await
+    }
+
     fn new_socket(&self, id: SocketId) -> RemoteSocket {
         let (tx, rx_recv) = tokio::sync::mpsc::channel(100);
         self.common.recv_tx.lock().unwrap().insert(id, tx);
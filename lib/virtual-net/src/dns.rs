@@ -0,0 +1,594 @@
+//! Pluggable name resolution for the guests of a [`VirtualNetworking`](crate::VirtualNetworking)
+//! implementation.
+//!
+//! A [`DnsResolver`] turns a hostname into addresses. Resolvers can be
+//! stacked, for instance a [`StaticHostsResolver`] that pins a few names to
+//! loopback stand-ins in front of a [`CachingResolver`] that caches the
+//! answers of an upstream server.
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{NetworkError, Result};
+
+/// Addresses that a hostname resolved to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsLookup {
+    pub addrs: Vec<IpAddr>,
+    /// How long the answer may be cached for, `None` if the resolver does
+    /// not know
+    pub ttl: Option<Duration>,
+}
+
+/// Resolves hostnames into addresses
+#[async_trait::async_trait]
+pub trait DnsResolver: fmt::Debug + Send + Sync + 'static {
+    /// Resolves `host` into its IPv4 and IPv6 addresses
+    async fn lookup(&self, host: &str) -> Result<DnsLookup>;
+}
+
+pub type DynDnsResolver = Arc<dyn DnsResolver>;
+
+#[async_trait::async_trait]
+impl<R: DnsResolver + ?Sized> DnsResolver for Arc<R> {
+    async fn lookup(&self, host: &str) -> Result<DnsLookup> {
+        (**self).lookup(host).await
+    }
+}
+
+/// Hostnames are case insensitive and may be fully qualified
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Resolves hostnames from a fixed table, like `/etc/hosts` does, and
+/// optionally hands the names it does not know to another resolver
+#[derive(Debug, Clone, Default)]
+pub struct StaticHostsResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+    fallback: Option<DynDnsResolver>,
+}
+
+impl StaticHostsResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an address that `host` resolves to
+    pub fn insert(&mut self, host: impl AsRef<str>, addr: IpAddr) {
+        let addrs = self.hosts.entry(normalize_host(host.as_ref())).or_default();
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+
+    /// Adds the entries of a table in the format of `/etc/hosts`, lines
+    /// that do not start with an address are ignored
+    pub fn add_hosts_file(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut parts = line.split_whitespace();
+            let Some(Ok(addr)) = parts.next().map(str::parse::<IpAddr>) else {
+                continue;
+            };
+            for host in parts {
+                self.insert(host, addr);
+            }
+        }
+    }
+
+    /// Hostnames that are not in the table are resolved by `fallback`
+    pub fn with_fallback(mut self, fallback: DynDnsResolver) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl DnsResolver for StaticHostsResolver {
+    async fn lookup(&self, host: &str) -> Result<DnsLookup> {
+        if let Some(addrs) = self.hosts.get(&normalize_host(host)) {
+            return Ok(DnsLookup {
+                addrs: addrs.clone(),
+                ttl: None,
+            });
+        }
+        match self.fallback.as_ref() {
+            Some(fallback) => fallback.lookup(host).await,
+            None => Err(NetworkError::AddressNotAvailable),
+        }
+    }
+}
+
+/// Caches the answers of another resolver for as long as their TTL says
+#[derive(Debug)]
+pub struct CachingResolver<R> {
+    inner: R,
+    default_ttl: Duration,
+    max_ttl: Duration,
+    max_entries: usize,
+    cache: Mutex<HashMap<String, (Instant, Vec<IpAddr>)>>,
+}
+
+impl<R: DnsResolver> CachingResolver<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            default_ttl: Duration::from_secs(30),
+            max_ttl: Duration::from_secs(3600),
+            max_entries: 1024,
+            cache: Default::default(),
+        }
+    }
+
+    /// Sets how long answers without a TTL are cached for, and the longest
+    /// time that any answer is cached for
+    pub fn with_ttls(mut self, default_ttl: Duration, max_ttl: Duration) -> Self {
+        self.default_ttl = default_ttl;
+        self.max_ttl = max_ttl;
+        self
+    }
+
+    /// Forgets all the cached answers
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: DnsResolver> DnsResolver for CachingResolver<R> {
+    async fn lookup(&self, host: &str) -> Result<DnsLookup> {
+        let key = normalize_host(host);
+        {
+            let now = Instant::now();
+            let cache = self.cache.lock().unwrap();
+            if let Some((expires, addrs)) = cache.get(&key) {
+                if *expires > now {
+                    return Ok(DnsLookup {
+                        addrs: addrs.clone(),
+                        ttl: Some(*expires - now),
+                    });
+                }
+            }
+        }
+
+        let lookup = self.inner.lookup(host).await?;
+        let ttl = lookup.ttl.unwrap_or(self.default_ttl).min(self.max_ttl);
+        if !ttl.is_zero() {
+            let now = Instant::now();
+            let mut cache = self.cache.lock().unwrap();
+            if cache.len() >= self.max_entries {
+                cache.retain(|_, (expires, _)| *expires > now);
+                if cache.len() >= self.max_entries {
+                    cache.clear();
+                }
+            }
+            cache.insert(key, (now + ttl, lookup.addrs.clone()));
+        }
+
+        Ok(DnsLookup {
+            addrs: lookup.addrs,
+            ttl: Some(ttl),
+        })
+    }
+}
+
+#[cfg(feature = "host-net")]
+pub use self::host::*;
+
+#[cfg(feature = "host-net")]
+mod host {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{wire, DnsLookup, DnsResolver};
+    use crate::{io_err_into_net_error, NetworkError, Result};
+
+    /// Resolves hostnames with the resolver of the host operating system
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SystemResolver;
+
+    #[async_trait::async_trait]
+    impl DnsResolver for SystemResolver {
+        async fn lookup(&self, host: &str) -> Result<DnsLookup> {
+            let addrs = tokio::net::lookup_host((host, 0))
+                .await
+                .map_err(io_err_into_net_error)?
+                .map(|addr| addr.ip())
+                .collect();
+            Ok(DnsLookup { addrs, ttl: None })
+        }
+    }
+
+    /// How queries are sent to an upstream DNS server
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DnsTransport {
+        /// Queries go over UDP and are retried over TCP when the answer
+        /// does not fit into a datagram
+        Udp,
+        Tcp,
+    }
+
+    /// Resolves hostnames by querying a DNS server
+    #[derive(Debug)]
+    pub struct UpstreamResolver {
+        server: SocketAddr,
+        transport: DnsTransport,
+        timeout: Duration,
+        next_id: AtomicU16,
+    }
+
+    impl UpstreamResolver {
+        pub fn new(server: SocketAddr, transport: DnsTransport) -> Self {
+            Self {
+                server,
+                transport,
+                timeout: Duration::from_secs(5),
+                next_id: AtomicU16::new(std::process::id() as u16),
+            }
+        }
+
+        /// Sets how long to wait for the server to answer a query
+        pub fn with_timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = timeout;
+            self
+        }
+
+        async fn query(&self, host: &str, record: u16) -> Result<DnsLookup> {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let request = wire::encode_query(id, host, record)?;
+            let exchange = async {
+                match self.transport {
+                    DnsTransport::Udp => {
+                        let response = self.exchange_udp(&request).await?;
+                        if wire::is_truncated(&response) {
+                            self.exchange_tcp(&request).await
+                        } else {
+                            Ok(response)
+                        }
+                    }
+                    DnsTransport::Tcp => self.exchange_tcp(&request).await,
+                }
+            };
+            let response = tokio::time::timeout(self.timeout, exchange)
+                .await
+                .map_err(|_| NetworkError::TimedOut)??;
+            wire::decode_response(id, record, &response)
+        }
+
+        async fn exchange_udp(&self, request: &[u8]) -> Result<Vec<u8>> {
+            let bind: SocketAddr = match self.server {
+                SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+                SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+            };
+            let socket = tokio::net::UdpSocket::bind(bind)
+                .await
+                .map_err(io_err_into_net_error)?;
+            socket
+                .connect(self.server)
+                .await
+                .map_err(io_err_into_net_error)?;
+            socket.send(request).await.map_err(io_err_into_net_error)?;
+
+            let mut buf = vec![0u8; 4096];
+            loop {
+                let read = socket.recv(&mut buf).await.map_err(io_err_into_net_error)?;
+                // Stray answers to earlier queries are skipped
+                if read >= 2 && buf[..2] == request[..2] {
+                    buf.truncate(read);
+                    return Ok(buf);
+                }
+            }
+        }
+
+        async fn exchange_tcp(&self, request: &[u8]) -> Result<Vec<u8>> {
+            let mut stream = tokio::net::TcpStream::connect(self.server)
+                .await
+                .map_err(io_err_into_net_error)?;
+
+            // Messages over TCP are prefixed with their length
+            let mut framed = Vec::with_capacity(2 + request.len());
+            framed.extend((request.len() as u16).to_be_bytes());
+            framed.extend(request);
+            stream
+                .write_all(&framed)
+                .await
+                .map_err(io_err_into_net_error)?;
+
+            let mut len = [0u8; 2];
+            stream
+                .read_exact(&mut len)
+                .await
+                .map_err(io_err_into_net_error)?;
+            let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+            stream
+                .read_exact(&mut buf)
+                .await
+                .map_err(io_err_into_net_error)?;
+            Ok(buf)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl DnsResolver for UpstreamResolver {
+        async fn lookup(&self, host: &str) -> Result<DnsLookup> {
+            let v4 = self.query(host, wire::TYPE_A).await;
+            let v6 = self.query(host, wire::TYPE_AAAA).await;
+
+            let mut ret = DnsLookup::default();
+            let mut error = None;
+            for lookup in [v4, v6] {
+                match lookup {
+                    Ok(lookup) => {
+                        ret.addrs.extend(lookup.addrs);
+                        ret.ttl = match (ret.ttl, lookup.ttl) {
+                            (Some(a), Some(b)) => Some(a.min(b)),
+                            (a, b) => a.or(b),
+                        };
+                    }
+                    Err(err) => {
+                        error.get_or_insert(err);
+                    }
+                }
+            }
+
+            if ret.addrs.is_empty() {
+                return Err(error.unwrap_or(NetworkError::AddressNotAvailable));
+            }
+            Ok(ret)
+        }
+    }
+}
+
+/// Encoding of DNS queries and decoding of their answers (RFC 1035)
+#[cfg(feature = "host-net")]
+mod wire {
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    use super::DnsLookup;
+    use crate::{NetworkError, Result};
+
+    pub const TYPE_A: u16 = 1;
+    pub const TYPE_AAAA: u16 = 28;
+    const CLASS_IN: u16 = 1;
+
+    const RCODE_NAME_ERROR: u8 = 3;
+
+    pub fn encode_query(id: u16, host: &str, record: u16) -> Result<Vec<u8>> {
+        let mut msg = Vec::with_capacity(18 + host.len());
+        msg.extend(id.to_be_bytes());
+        // Recursion desired
+        msg.extend([0x01, 0x00]);
+        // One question and no other records
+        msg.extend([0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in host.trim_end_matches('.').split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(NetworkError::InvalidInput);
+            }
+            msg.push(label.len() as u8);
+            msg.extend(label.as_bytes());
+        }
+        msg.push(0);
+        msg.extend(record.to_be_bytes());
+        msg.extend(CLASS_IN.to_be_bytes());
+        Ok(msg)
+    }
+
+    pub fn is_truncated(msg: &[u8]) -> bool {
+        msg.len() > 2 && msg[2] & 0x02 != 0
+    }
+
+    fn read_u16(msg: &[u8], pos: usize) -> Result<u16> {
+        msg.get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or(NetworkError::InvalidData)
+    }
+
+    fn read_u32(msg: &[u8], pos: usize) -> Result<u32> {
+        msg.get(pos..pos + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(NetworkError::InvalidData)
+    }
+
+    /// Returns the position right after the name that starts at `pos`
+    fn skip_name(msg: &[u8], mut pos: usize) -> Result<usize> {
+        loop {
+            let len = *msg.get(pos).ok_or(NetworkError::InvalidData)? as usize;
+            match len {
+                0 => return Ok(pos + 1),
+                // A compression pointer always ends the name
+                len if len & 0xC0 == 0xC0 => return Ok(pos + 2),
+                len => pos += 1 + len,
+            }
+        }
+    }
+
+    pub fn decode_response(id: u16, record: u16, msg: &[u8]) -> Result<DnsLookup> {
+        if msg.len() < 12 || read_u16(msg, 0)? != id || msg[2] & 0x80 == 0 {
+            return Err(NetworkError::InvalidData);
+        }
+        match msg[3] & 0x0F {
+            0 => {}
+            RCODE_NAME_ERROR => return Err(NetworkError::AddressNotAvailable),
+            _ => return Err(NetworkError::UnknownError),
+        }
+
+        let questions = read_u16(msg, 4)?;
+        let answers = read_u16(msg, 6)?;
+        let mut pos = 12;
+        for _ in 0..questions {
+            pos = skip_name(msg, pos)? + 4;
+        }
+
+        let mut ret = DnsLookup::default();
+        for _ in 0..answers {
+            pos = skip_name(msg, pos)?;
+            let rtype = read_u16(msg, pos)?;
+            let class = read_u16(msg, pos + 2)?;
+            let ttl = Duration::from_secs(read_u32(msg, pos + 4)? as u64);
+            let len = read_u16(msg, pos + 8)? as usize;
+            pos += 10;
+            let data = msg.get(pos..pos + len).ok_or(NetworkError::InvalidData)?;
+            pos += len;
+
+            // Other records (such as the CNAMEs that lead to the address)
+            // are of no interest
+            if class != CLASS_IN || rtype != record {
+                continue;
+            }
+            let addr = match (rtype, data.len()) {
+                (TYPE_A, 4) => IpAddr::from(<[u8; 4]>::try_from(data).unwrap()),
+                (TYPE_AAAA, 16) => IpAddr::from(<[u8; 16]>::try_from(data).unwrap()),
+                _ => return Err(NetworkError::InvalidData),
+            };
+            ret.addrs.push(addr);
+            ret.ttl = Some(ret.ttl.map_or(ttl, |t| t.min(ttl)));
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct CountingResolver {
+        lookups: AtomicUsize,
+        ttl: Option<Duration>,
+    }
+
+    #[async_trait::async_trait]
+    impl DnsResolver for CountingResolver {
+        async fn lookup(&self, _host: &str) -> Result<DnsLookup> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            Ok(DnsLookup {
+                addrs: vec![Ipv4Addr::new(10, 0, 0, 1).into()],
+                ttl: self.ttl,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_static_hosts() {
+        let mut hosts = StaticHostsResolver::new();
+        hosts.insert("Example.com", Ipv4Addr::LOCALHOST.into());
+        hosts.add_hosts_file(
+            "# comment\n::1 localhost ip6-localhost\n127.0.0.2 api.internal # trailing\nbogus line\n",
+        );
+
+        assert_eq!(
+            hosts.lookup("example.com.").await.unwrap().addrs,
+            vec![IpAddr::from(Ipv4Addr::LOCALHOST)]
+        );
+        assert_eq!(
+            hosts.lookup("ip6-localhost").await.unwrap().addrs,
+            vec![IpAddr::from(Ipv6Addr::LOCALHOST)]
+        );
+        assert_eq!(
+            hosts.lookup("api.internal").await.unwrap().addrs,
+            vec![IpAddr::from([127, 0, 0, 2])]
+        );
+        assert_eq!(
+            hosts.lookup("other.com").await.unwrap_err(),
+            NetworkError::AddressNotAvailable
+        );
+
+        let hosts = hosts.with_fallback(Arc::new(CountingResolver::default()));
+        assert_eq!(
+            hosts.lookup("other.com").await.unwrap().addrs,
+            vec![IpAddr::from([10, 0, 0, 1])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_caching() {
+        let cache = CachingResolver::new(CountingResolver {
+            ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        });
+        cache.lookup("a.com").await.unwrap();
+        let lookup = cache.lookup("A.com").await.unwrap();
+        assert!(lookup.ttl.unwrap() <= Duration::from_secs(60));
+        assert_eq!(cache.inner.lookups.load(Ordering::SeqCst), 1);
+
+        cache.clear();
+        cache.lookup("a.com").await.unwrap();
+        assert_eq!(cache.inner.lookups.load(Ordering::SeqCst), 2);
+
+        // Answers with a TTL of zero are never cached
+        let cache = CachingResolver::new(CountingResolver {
+            ttl: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        cache.lookup("a.com").await.unwrap();
+        cache.lookup("a.com").await.unwrap();
+        assert_eq!(cache.inner.lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "host-net")]
+    #[test]
+    fn test_wire_format() {
+        let query = wire::encode_query(0x1234, "www.example.com.", wire::TYPE_A).unwrap();
+        assert_eq!(&query[..2], &[0x12, 0x34]);
+        assert_eq!(&query[12..29], b"\x03www\x07example\x03com\x00");
+
+        // The answer repeats the question and holds a CNAME followed by
+        // the address, both using compressed names
+        let mut answer = query.clone();
+        answer[2] |= 0x80;
+        answer[7] = 2;
+        answer.extend([0xC0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xC0, 16]);
+        answer.extend([0xC0, 16, 0, 1, 0, 1, 0, 0, 0, 30, 0, 4, 93, 184, 216, 34]);
+
+        let lookup = wire::decode_response(0x1234, wire::TYPE_A, &answer).unwrap();
+        assert_eq!(lookup.addrs, vec![IpAddr::from([93, 184, 216, 34])]);
+        assert_eq!(lookup.ttl, Some(Duration::from_secs(30)));
+
+        assert_eq!(
+            wire::decode_response(0x4321, wire::TYPE_A, &answer).unwrap_err(),
+            NetworkError::InvalidData
+        );
+        answer[3] |= 3;
+        assert_eq!(
+            wire::decode_response(0x1234, wire::TYPE_A, &answer).unwrap_err(),
+            NetworkError::AddressNotAvailable
+        );
+        assert!(wire::encode_query(1, "a..com", wire::TYPE_A).is_err());
+    }
+
+    #[cfg(feature = "host-net")]
+    #[tokio::test]
+    async fn test_upstream() {
+        let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            loop {
+                let (read, peer) = server.recv_from(&mut buf).await.unwrap();
+                let mut answer = buf[..read].to_vec();
+                answer[2] |= 0x80;
+                // Only A records are answered
+                if answer[read - 3] == wire::TYPE_A as u8 {
+                    answer[7] = 1;
+                    answer.extend([0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 10, 0, 4, 127, 0, 0, 9]);
+                }
+                server.send_to(&answer, peer).await.unwrap();
+            }
+        });
+
+        let resolver = UpstreamResolver::new(addr, DnsTransport::Udp);
+        let lookup = resolver.lookup("db.internal").await.unwrap();
+        assert_eq!(lookup.addrs, vec![IpAddr::from([127, 0, 0, 9])]);
+        assert_eq!(lookup.ttl, Some(Duration::from_secs(10)));
+    }
+}
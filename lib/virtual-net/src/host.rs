@@ -1,4 +1,5 @@
 #![allow(unused_variables)]
+use crate::dns::DynDnsResolver;
 use crate::ruleset::{Direction, Ruleset};
 use crate::{io_err_into_net_error, VirtualIoSource};
 #[allow(unused_imports)]
@@ -6,6 +7,7 @@ use crate::{
     IpCidr, IpRoute, NetworkError, Result, SocketStatus, StreamSecurity, VirtualConnectedSocket,
     VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
     VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualUnixDatagramSocket, VirtualUnixListener, VirtualUnixSocket,
 };
 use bytes::{Buf, BytesMut};
 use std::collections::VecDeque;
@@ -18,6 +20,7 @@ use std::os::fd::AsRawFd;
 use std::os::fd::RawFd;
 #[cfg(windows)]
 use std::os::windows::io::AsRawSocket;
+use std::path::{Path, PathBuf};
 
 use std::sync::Arc;
 use std::task::Poll;
@@ -34,6 +37,7 @@ pub struct LocalNetworking {
     selector: Arc<Selector>,
     handle: Handle,
     ruleset: Option<Ruleset>,
+    dns_resolver: Option<DynDnsResolver>,
 }
 
 impl LocalNetworking {
@@ -42,6 +46,7 @@ impl LocalNetworking {
             selector: Selector::new(),
             handle: Handle::current(),
             ruleset: None,
+            dns_resolver: None,
         }
     }
 
@@ -50,8 +55,16 @@ impl LocalNetworking {
             selector: Selector::new(),
             handle: Handle::current(),
             ruleset: Some(ruleset),
+            dns_resolver: None,
         }
     }
+
+    /// Resolves hostnames with `resolver` instead of the resolver of the
+    /// host operating system
+    pub fn with_dns_resolver(mut self, resolver: DynDnsResolver) -> Self {
+        self.dns_resolver = Some(resolver);
+        self
+    }
 }
 
 impl Drop for LocalNetworking {
@@ -189,18 +202,28 @@ impl VirtualNetworking for LocalNetworking {
             }
         }
 
-        let host_to_lookup = if host.contains(':') {
-            host.to_string()
+        let addrs = if let Ok(addr) = host.parse::<IpAddr>() {
+            vec![addr]
+        } else if let Some(resolver) = self.dns_resolver.clone() {
+            let host = host.to_string();
+            self.handle
+                .spawn(async move { resolver.lookup(&host).await })
+                .await
+                .map_err(|_| NetworkError::IOError)??
+                .addrs
         } else {
-            format!("{}:{}", host, port.unwrap_or(0))
+            let host_to_lookup = if host.contains(':') {
+                host.to_string()
+            } else {
+                format!("{}:{}", host, port.unwrap_or(0))
+            };
+            self.handle
+                .spawn(tokio::net::lookup_host(host_to_lookup))
+                .await
+                .map_err(|_| NetworkError::IOError)?
+                .map(|a| a.map(|a| a.ip()).collect::<Vec<_>>())
+                .map_err(io_err_into_net_error)?
         };
-        let addrs = self
-            .handle
-            .spawn(tokio::net::lookup_host(host_to_lookup))
-            .await
-            .map_err(|_| NetworkError::IOError)?
-            .map(|a| a.map(|a| a.ip()).collect::<Vec<_>>())
-            .map_err(io_err_into_net_error)?;
 
         if let Some(ruleset) = self.ruleset.as_ref() {
             if let Err(e) = ruleset.expand_domain(host, &addrs) {
@@ -212,6 +235,44 @@ impl VirtualNetworking for LocalNetworking {
 
         Ok(addrs)
     }
+
+    #[cfg(unix)]
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixListener + Sync>> {
+        let listener = mio::net::UnixListener::bind(path).map_err(io_err_into_net_error)?;
+        Ok(Box::new(LocalUnixListener {
+            listener,
+            selector: self.selector.clone(),
+            handler_guard: HandlerGuardState::None,
+            backlog: Default::default(),
+        }))
+    }
+
+    #[cfg(unix)]
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        let stream = mio::net::UnixStream::connect(path).map_err(io_err_into_net_error)?;
+        Ok(Box::new(LocalUnixStream::new(
+            self.selector.clone(),
+            stream,
+        )))
+    }
+
+    #[cfg(unix)]
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        let socket = match path {
+            Some(path) => mio::net::UnixDatagram::bind(path),
+            None => mio::net::UnixDatagram::unbound(),
+        }
+        .map_err(io_err_into_net_error)?;
+        Ok(Box::new(LocalUnixDatagram {
+            socket,
+            selector: self.selector.clone(),
+            handler_guard: HandlerGuardState::None,
+            backlog: Default::default(),
+        }))
+    }
 }
 
 #[derive(Debug)]
@@ -1016,3 +1077,472 @@ impl VirtualIoSource for LocalUdpSocket {
         Poll::Pending
     }
 }
+
+/// Returns the path of a unix domain socket address, unnamed and abstract
+/// addresses have none
+#[cfg(unix)]
+fn unix_addr_path(addr: std::os::unix::net::SocketAddr) -> Option<PathBuf> {
+    addr.as_pathname().map(Path::to_path_buf)
+}
+
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct LocalUnixListener {
+    listener: mio::net::UnixListener,
+    selector: Arc<Selector>,
+    handler_guard: HandlerGuardState,
+    backlog: VecDeque<Box<dyn VirtualUnixSocket + Sync>>,
+}
+
+#[cfg(unix)]
+impl LocalUnixListener {
+    fn try_accept_internal(&mut self) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        match self.listener.accept().map_err(io_err_into_net_error) {
+            Ok((stream, _)) => Ok(Box::new(LocalUnixStream::new(
+                self.selector.clone(),
+                stream,
+            ))),
+            Err(NetworkError::WouldBlock) => {
+                if let HandlerGuardState::WakerMap(_, map) = &mut self.handler_guard {
+                    map.pop(InterestType::Readable);
+                    map.pop(InterestType::Writable);
+                }
+                Err(NetworkError::WouldBlock)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn split_borrow(
+        &mut self,
+    ) -> (
+        &mut HandlerGuardState,
+        &Arc<Selector>,
+        &mut mio::net::UnixListener,
+    ) {
+        (&mut self.handler_guard, &self.selector, &mut self.listener)
+    }
+}
+
+#[cfg(unix)]
+impl VirtualUnixListener for LocalUnixListener {
+    fn listen(&mut self, _backlog: usize) -> Result<()> {
+        // Host sockets are already listening once they are bound
+        Ok(())
+    }
+
+    fn try_accept(&mut self) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        if let Some(child) = self.backlog.pop_front() {
+            return Ok(child);
+        }
+        self.try_accept_internal()
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        if let HandlerGuardState::ExternalHandler(guard) = &mut self.handler_guard {
+            match guard.replace_handler(handler) {
+                Ok(()) => return Ok(()),
+                Err(h) => handler = h,
+            }
+
+            // the handler could not be replaced so we need to build a new handler instead
+            if let Err(err) = guard.unregister(&mut self.listener) {
+                tracing::debug!("failed to unregister previous token - {}", err);
+            }
+        }
+
+        let guard = InterestGuard::new(
+            &self.selector,
+            handler,
+            &mut self.listener,
+            mio::Interest::READABLE.add(mio::Interest::WRITABLE),
+        )
+        .map_err(io_err_into_net_error)?;
+
+        self.handler_guard = HandlerGuardState::ExternalHandler(guard);
+
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<Option<PathBuf>> {
+        self.listener
+            .local_addr()
+            .map(unix_addr_path)
+            .map_err(io_err_into_net_error)
+    }
+}
+
+#[cfg(unix)]
+impl VirtualIoSource for LocalUnixListener {
+    fn remove_handler(&mut self) {
+        let mut guard = HandlerGuardState::None;
+        std::mem::swap(&mut guard, &mut self.handler_guard);
+        match guard {
+            HandlerGuardState::ExternalHandler(mut guard) => {
+                guard.unregister(&mut self.listener).ok();
+            }
+            HandlerGuardState::WakerMap(mut guard, _) => {
+                guard.unregister(&mut self.listener).ok();
+            }
+            HandlerGuardState::None => {}
+        }
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<usize>> {
+        if !self.backlog.is_empty() {
+            return Poll::Ready(Ok(self.backlog.len()));
+        }
+
+        let (state, selector, source) = self.split_borrow();
+        let map = state_as_waker_map(state, selector, source).map_err(io_err_into_net_error)?;
+        map.add(InterestType::Readable, cx.waker());
+
+        if let Ok(child) = self.try_accept_internal() {
+            self.backlog.push_back(child);
+            return Poll::Ready(Ok(1));
+        }
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<usize>> {
+        self.poll_read_ready(cx)
+    }
+}
+
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct LocalUnixStream {
+    stream: mio::net::UnixStream,
+    selector: Arc<Selector>,
+    handler_guard: HandlerGuardState,
+    buffer: BytesMut,
+}
+
+#[cfg(unix)]
+impl LocalUnixStream {
+    fn new(selector: Arc<Selector>, stream: mio::net::UnixStream) -> Self {
+        Self {
+            stream,
+            selector,
+            handler_guard: HandlerGuardState::None,
+            buffer: BytesMut::new(),
+        }
+    }
+
+    fn split_borrow(
+        &mut self,
+    ) -> (
+        &mut HandlerGuardState,
+        &Arc<Selector>,
+        &mut mio::net::UnixStream,
+        &mut BytesMut,
+    ) {
+        (
+            &mut self.handler_guard,
+            &self.selector,
+            &mut self.stream,
+            &mut self.buffer,
+        )
+    }
+
+    /// Reads whatever is waiting on the socket into the buffer
+    fn fill_buffer(&mut self) -> io::Result<usize> {
+        self.buffer.reserve(10240);
+        let uninit: &mut [MaybeUninit<u8>] = self.buffer.spare_capacity_mut();
+        let uninit_unsafe: &mut [u8] = unsafe { std::mem::transmute(uninit) };
+        let amt = self.stream.read(uninit_unsafe)?;
+        unsafe {
+            self.buffer.set_len(self.buffer.len() + amt);
+        }
+        Ok(amt)
+    }
+}
+
+#[cfg(unix)]
+impl VirtualUnixSocket for LocalUnixStream {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let ret = self.stream.write(data).map_err(io_err_into_net_error);
+        match &ret {
+            Ok(0) | Err(NetworkError::WouldBlock) => {
+                if let HandlerGuardState::WakerMap(_, map) = &mut self.handler_guard {
+                    map.pop(InterestType::Writable);
+                }
+            }
+            _ => {}
+        }
+        ret
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.stream.flush().map_err(io_err_into_net_error)
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let buf: &mut [u8] = unsafe { std::mem::transmute(buf) };
+        if self.buffer.is_empty() {
+            if !peek {
+                return self.stream.read(buf).map_err(io_err_into_net_error);
+            }
+            // The host socket can not be peeked so the data is kept aside
+            self.fill_buffer().map_err(io_err_into_net_error)?;
+        }
+
+        let amt = buf.len().min(self.buffer.len());
+        buf[..amt].copy_from_slice(&self.buffer[..amt]);
+        if !peek {
+            self.buffer.advance(amt);
+        }
+        Ok(amt)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.stream.shutdown(how).map_err(io_err_into_net_error)
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        if let HandlerGuardState::ExternalHandler(guard) = &mut self.handler_guard {
+            match guard.replace_handler(handler) {
+                Ok(()) => return Ok(()),
+                Err(h) => handler = h,
+            }
+
+            // the handler could not be replaced so we need to build a new handler instead
+            if let Err(err) = guard.unregister(&mut self.stream) {
+                tracing::debug!("failed to unregister previous token - {}", err);
+            }
+        }
+
+        let guard = InterestGuard::new(
+            &self.selector,
+            handler,
+            &mut self.stream,
+            mio::Interest::READABLE.add(mio::Interest::WRITABLE),
+        )
+        .map_err(io_err_into_net_error)?;
+
+        self.handler_guard = HandlerGuardState::ExternalHandler(guard);
+
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<Option<PathBuf>> {
+        self.stream
+            .local_addr()
+            .map(unix_addr_path)
+            .map_err(io_err_into_net_error)
+    }
+
+    fn addr_peer(&self) -> Result<Option<PathBuf>> {
+        self.stream
+            .peer_addr()
+            .map(unix_addr_path)
+            .map_err(io_err_into_net_error)
+    }
+
+    fn is_closed(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(unix)]
+impl VirtualIoSource for LocalUnixStream {
+    fn remove_handler(&mut self) {
+        let mut guard = HandlerGuardState::None;
+        std::mem::swap(&mut guard, &mut self.handler_guard);
+        match guard {
+            HandlerGuardState::ExternalHandler(mut guard) => {
+                guard.unregister(&mut self.stream).ok();
+            }
+            HandlerGuardState::WakerMap(mut guard, _) => {
+                guard.unregister(&mut self.stream).ok();
+            }
+            HandlerGuardState::None => {}
+        }
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<usize>> {
+        if !self.buffer.is_empty() {
+            return Poll::Ready(Ok(self.buffer.len()));
+        }
+
+        let (state, selector, stream, _) = self.split_borrow();
+        let map = state_as_waker_map(state, selector, stream).map_err(io_err_into_net_error)?;
+        map.pop(InterestType::Readable);
+        map.add(InterestType::Readable, cx.waker());
+
+        match self.fill_buffer() {
+            Ok(amt) => Poll::Ready(Ok(amt)),
+            Err(err) if err.kind() == io::ErrorKind::ConnectionReset => Poll::Ready(Ok(0)),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+            Err(err) => Poll::Ready(Err(io_err_into_net_error(err))),
+        }
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<usize>> {
+        let (state, selector, stream, _) = self.split_borrow();
+        let map = state_as_waker_map(state, selector, stream).map_err(io_err_into_net_error)?;
+        map.pop(InterestType::Writable);
+        map.add(InterestType::Writable, cx.waker());
+        map.add(InterestType::Closed, cx.waker());
+        if map.has_interest(InterestType::Closed) {
+            return Poll::Ready(Ok(0));
+        }
+
+        match libc_poll(stream.as_raw_fd(), libc::POLLOUT | libc::POLLHUP) {
+            Some(val) if (val & libc::POLLHUP) != 0 => Poll::Ready(Ok(0)),
+            Some(val) if (val & libc::POLLOUT) != 0 => Poll::Ready(Ok(10240)),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct LocalUnixDatagram {
+    socket: mio::net::UnixDatagram,
+    selector: Arc<Selector>,
+    handler_guard: HandlerGuardState,
+    backlog: VecDeque<(BytesMut, Option<PathBuf>)>,
+}
+
+#[cfg(unix)]
+impl LocalUnixDatagram {
+    fn split_borrow(
+        &mut self,
+    ) -> (
+        &mut HandlerGuardState,
+        &Arc<Selector>,
+        &mut mio::net::UnixDatagram,
+    ) {
+        (&mut self.handler_guard, &self.selector, &mut self.socket)
+    }
+
+    /// Receives the next datagram into the backlog
+    fn fill_backlog(&mut self) -> io::Result<usize> {
+        let mut buffer = BytesMut::default();
+        buffer.reserve(65536);
+        let uninit: &mut [MaybeUninit<u8>] = buffer.spare_capacity_mut();
+        let uninit_unsafe: &mut [u8] = unsafe { std::mem::transmute(uninit) };
+        let (amt, from) = self.socket.recv_from(uninit_unsafe)?;
+        unsafe {
+            buffer.set_len(amt);
+        }
+        self.backlog.push_back((buffer, unix_addr_path(from)));
+        Ok(amt)
+    }
+}
+
+#[cfg(unix)]
+impl VirtualUnixDatagramSocket for LocalUnixDatagram {
+    fn try_send_to(&mut self, data: &[u8], path: &Path) -> Result<usize> {
+        let ret = self
+            .socket
+            .send_to(data, path)
+            .map_err(io_err_into_net_error);
+        if let Err(NetworkError::WouldBlock) = &ret {
+            if let HandlerGuardState::WakerMap(_, map) = &mut self.handler_guard {
+                map.pop(InterestType::Writable);
+            }
+        }
+        ret
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, Option<PathBuf>)> {
+        if self.backlog.is_empty() {
+            self.fill_backlog().map_err(io_err_into_net_error)?;
+        }
+        let (data, from) = match peek {
+            true => self.backlog.front().cloned(),
+            false => self.backlog.pop_front(),
+        }
+        .ok_or(NetworkError::WouldBlock)?;
+
+        let buf: &mut [u8] = unsafe { std::mem::transmute(buf) };
+        let amt = buf.len().min(data.len());
+        buf[..amt].copy_from_slice(&data[..amt]);
+        Ok((amt, from))
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        if let HandlerGuardState::ExternalHandler(guard) = &mut self.handler_guard {
+            match guard.replace_handler(handler) {
+                Ok(()) => return Ok(()),
+                Err(h) => handler = h,
+            }
+
+            // the handler could not be replaced so we need to build a new handler instead
+            if let Err(err) = guard.unregister(&mut self.socket) {
+                tracing::debug!("failed to unregister previous token - {}", err);
+            }
+        }
+
+        let guard = InterestGuard::new(
+            &self.selector,
+            handler,
+            &mut self.socket,
+            mio::Interest::READABLE.add(mio::Interest::WRITABLE),
+        )
+        .map_err(io_err_into_net_error)?;
+
+        self.handler_guard = HandlerGuardState::ExternalHandler(guard);
+
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<Option<PathBuf>> {
+        self.socket
+            .local_addr()
+            .map(unix_addr_path)
+            .map_err(io_err_into_net_error)
+    }
+}
+
+#[cfg(unix)]
+impl VirtualIoSource for LocalUnixDatagram {
+    fn remove_handler(&mut self) {
+        let mut guard = HandlerGuardState::None;
+        std::mem::swap(&mut guard, &mut self.handler_guard);
+        match guard {
+            HandlerGuardState::ExternalHandler(mut guard) => {
+                guard.unregister(&mut self.socket).ok();
+            }
+            HandlerGuardState::WakerMap(mut guard, _) => {
+                guard.unregister(&mut self.socket).ok();
+            }
+            HandlerGuardState::None => {}
+        }
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<usize>> {
+        if let Some((data, _)) = self.backlog.front() {
+            return Poll::Ready(Ok(data.len()));
+        }
+
+        let (state, selector, socket) = self.split_borrow();
+        let map = state_as_waker_map(state, selector, socket).map_err(io_err_into_net_error)?;
+        map.pop(InterestType::Readable);
+        map.add(InterestType::Readable, cx.waker());
+
+        match self.fill_backlog() {
+            Ok(amt) => Poll::Ready(Ok(amt)),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+            Err(err) => Poll::Ready(Err(io_err_into_net_error(err))),
+        }
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<usize>> {
+        let (state, selector, socket) = self.split_borrow();
+        let map = state_as_waker_map(state, selector, socket).map_err(io_err_into_net_error)?;
+        map.pop(InterestType::Writable);
+        map.add(InterestType::Writable, cx.waker());
+
+        match libc_poll(socket.as_raw_fd(), libc::POLLOUT) {
+            Some(val) if (val & libc::POLLOUT) != 0 => Poll::Ready(Ok(10240)),
+            _ => Poll::Pending,
+        }
+    }
+}
@@ -1,29 +1,45 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 #![allow(clippy::multiple_bound_locations)]
+pub mod capture;
 #[cfg(feature = "remote")]
 pub mod client;
 pub mod composite;
+pub mod dns;
 #[cfg(feature = "host-net")]
 pub mod host;
 pub mod loopback;
 pub mod meta;
+pub mod replay;
 pub mod ruleset;
 #[cfg(feature = "remote")]
 pub mod rx_tx;
+#[cfg(feature = "remote-secure")]
+pub mod secure;
 #[cfg(feature = "remote")]
 pub mod server;
 pub mod tcp_pair;
 #[cfg(feature = "tokio")]
 #[cfg(test)]
 mod tests;
-
+pub mod throttle;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod unix;
+#[cfg(feature = "user-space")]
+pub mod user_space;
+
+pub use capture::CaptureNetworking;
 #[cfg(feature = "remote")]
 pub use client::{RemoteNetworkingClient, RemoteNetworkingClientDriver};
 pub use composite::CompositeTcpListener;
+pub use dns::{DnsResolver, DynDnsResolver};
 pub use loopback::LoopbackNetworking;
 use pin_project_lite::pin_project;
+pub use replay::{RecordingNetworking, ReplayNetworking};
 #[cfg(feature = "rkyv")]
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
+#[cfg(feature = "remote-secure")]
+pub use secure::{RemoteAuthenticator, RemoteCredentials};
 #[cfg(feature = "remote")]
 pub use server::{RemoteNetworkingServer, RemoteNetworkingServerDriver};
 use std::fmt;
@@ -33,16 +49,23 @@ use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 use std::net::Shutdown;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
 use std::time::Duration;
 use thiserror::Error;
+pub use throttle::{ThrottleLimits, ThrottledNetworking};
+#[cfg(feature = "tls")]
+pub use tls::{TlsConfig, TlsSession};
 #[cfg(feature = "tokio")]
 use tokio::io::AsyncRead;
 #[cfg(feature = "tokio")]
 use tokio::io::AsyncWrite;
+pub use unix::UnixEndpoint;
+#[cfg(feature = "user-space")]
+pub use user_space::UserSpaceNetworking;
 
 pub use bytes::Bytes;
 pub use bytes::BytesMut;
@@ -219,6 +242,26 @@ pub trait VirtualNetworking: fmt::Debug + Send + Sync + 'static {
     ) -> Result<Vec<IpAddr>> {
         Err(NetworkError::Unsupported)
     }
+
+    /// Binds a unix domain socket to a path of the host file system that
+    /// connections can then be accepted on
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixListener + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Connects to the unix domain socket at a path of the host file system
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Opens a datagram unix domain socket, which is bound to a path of the
+    /// host file system if one is given
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
 }
 
 pub type DynVirtualNetworking = Arc<dyn VirtualNetworking>;
@@ -769,6 +812,70 @@ pub trait VirtualUdpSocket:
     fn addr_peer(&self) -> Result<Option<SocketAddr>>;
 }
 
+pub trait VirtualUnixListener: VirtualIoSource + fmt::Debug + Send + Sync + 'static {
+    /// Starts accepting connections, up to `backlog` of them are queued up
+    /// before any further ones are refused
+    fn listen(&mut self, backlog: usize) -> Result<()>;
+
+    /// Tries to accept a new connection
+    fn try_accept(&mut self) -> Result<Box<dyn VirtualUnixSocket + Sync>>;
+
+    /// Registers a waker for when a new connection has arrived. This uses
+    /// a stack machine which means more than one waker can be registered
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()>;
+
+    /// Returns the path that this listener is bound to
+    fn addr_local(&self) -> Result<Option<PathBuf>>;
+}
+
+/// Connected stream of a unix domain socket
+pub trait VirtualUnixSocket: VirtualIoSource + fmt::Debug + Send + Sync + 'static {
+    /// Tries to send out a buffer of data to the peer
+    fn try_send(&mut self, data: &[u8]) -> Result<usize>;
+
+    /// Flushes any data that is waiting to be sent
+    fn try_flush(&mut self) -> Result<()>;
+
+    /// Tries to read a buffer of data that was sent by the peer
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize>;
+
+    /// Shuts down either the READER or WRITER sides of the socket
+    /// connection.
+    fn shutdown(&mut self, how: Shutdown) -> Result<()>;
+
+    /// Registers a waker for when this connection is ready to receive
+    /// more data or has data ready to be read
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()>;
+
+    /// Returns the path that this socket is bound to (if any)
+    fn addr_local(&self) -> Result<Option<PathBuf>>;
+
+    /// Returns the path of the socket on the other end (if it is bound)
+    fn addr_peer(&self) -> Result<Option<PathBuf>>;
+
+    /// Return true if the socket is closed
+    fn is_closed(&self) -> bool;
+}
+
+/// Datagram socket of the unix domain
+pub trait VirtualUnixDatagramSocket: VirtualIoSource + fmt::Debug + Send + Sync + 'static {
+    /// Sends a datagram to the socket that is bound to a path
+    fn try_send_to(&mut self, data: &[u8], path: &Path) -> Result<usize>;
+
+    /// Reads a datagram along with the path of the socket that sent it
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, Option<PathBuf>)>;
+
+    /// Registers a waker for when datagrams have arrived
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()>;
+
+    /// Returns the path that this socket is bound to (if any)
+    fn addr_local(&self) -> Result<Option<PathBuf>>;
+}
+
 #[derive(Debug, Default)]
 pub struct UnsupportedVirtualNetworking {}
 
@@ -0,0 +1,1871 @@
+//! Records the network interactions of a guest so that they can be replayed
+//! later on without any real network.
+//!
+//! [`RecordingNetworking`] wraps a [`VirtualNetworking`] implementation and
+//! writes every name resolution, TCP connect, listen and accept, every
+//! chunk of payload that goes in either direction and every datagram of the
+//! UDP, ICMP and raw sockets to a recording, along with the time at which it
+//! happened. [`ReplayNetworking`] serves the same
+//! answers and responses from a recording, which makes bugs that depend on
+//! what the network returned reproducible.
+//!
+//! Replays reproduce the order of the traffic, not its timing. A replayed
+//! guest does not run at the speed at which it was recorded, so waiting for
+//! the recorded times would not bring back the conditions of the recording
+//! either. What a guest depends on is that a response only shows up after
+//! the request that caused it, which replays do guarantee. The times are
+//! kept for the people that inspect a recording (see [`read_records`]).
+//!
+//! Connections and datagram sockets are identified by numbers that are
+//! assigned in the order in which they were opened. [`NetEvent::Connected`],
+//! [`NetEvent::Send`] and [`NetEvent::SendTo`] carry the same information as
+//! the `SocketConnectedV1`, `SocketSendV1` and `SocketSendToV1` journal
+//! entries (with the number in place of the file descriptor), everything
+//! that is received has no journal counterpart.
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use bytes::{Buf, BytesMut};
+use serde::{Deserialize, Serialize};
+use virtual_mio::{InterestHandler, InterestType};
+
+use crate::{
+    IpCidr, IpRoute, NetworkError, Result, SocketStatus, StreamSecurity, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualIoSource, VirtualNetworking,
+    VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualUnixDatagramSocket, VirtualUnixListener, VirtualUnixSocket,
+};
+
+/// Written at the start of every recording
+const MAGIC: &[u8; 8] = b"WNETREC1";
+
+/// Something that happened on the network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetEvent {
+    /// A hostname was resolved
+    Resolved {
+        host: String,
+        result: std::result::Result<Vec<IpAddr>, NetworkError>,
+    },
+    /// A TCP listener was opened on `addr`
+    Listening {
+        listener: u64,
+        addr: SocketAddr,
+        local_addr: SocketAddr,
+    },
+    /// Opening a TCP listener on `addr` failed
+    ListenFailed {
+        addr: SocketAddr,
+        error: NetworkError,
+    },
+    /// A TCP connection was opened to `peer_addr`
+    Connected {
+        conn: u64,
+        local_addr: SocketAddr,
+        peer_addr: SocketAddr,
+    },
+    /// Opening a TCP connection to `peer_addr` failed
+    ConnectFailed {
+        peer_addr: SocketAddr,
+        error: NetworkError,
+    },
+    /// A TCP connection was accepted by a listener
+    Accepted {
+        listener: u64,
+        conn: u64,
+        local_addr: SocketAddr,
+        peer_addr: SocketAddr,
+    },
+    /// Payload was sent over a connection
+    Send { conn: u64, data: Vec<u8> },
+    /// Payload was received from a connection
+    Recv { conn: u64, data: Vec<u8> },
+    /// The peer closed its end of a connection
+    Closed { conn: u64 },
+    /// A UDP, ICMP or raw socket was bound
+    Bound {
+        socket: u64,
+        bind: DatagramBind,
+        local_addr: SocketAddr,
+    },
+    /// Binding a UDP, ICMP or raw socket failed
+    BindFailed {
+        bind: DatagramBind,
+        error: NetworkError,
+    },
+    /// A datagram was sent, raw sockets send them without a peer
+    SendTo {
+        socket: u64,
+        peer: Option<SocketAddr>,
+        data: Vec<u8>,
+    },
+    /// A datagram was received, raw sockets receive them without a peer
+    RecvFrom {
+        socket: u64,
+        peer: Option<SocketAddr>,
+        data: Vec<u8>,
+    },
+}
+
+/// What a datagram socket was bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DatagramBind {
+    Udp(SocketAddr),
+    Icmp(IpAddr),
+    Raw,
+}
+
+/// A [`NetEvent`] and how long after the recording started it happened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetRecord {
+    /// Informational, [`ReplayNetworking`] does not wait for it (see the
+    /// module documentation)
+    pub at: Duration,
+    pub event: NetEvent,
+}
+
+/// Reads all the records of a recording
+pub fn read_records(reader: impl Read) -> io::Result<Vec<NetRecord>> {
+    let mut reader = BufReader::new(reader);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a network recording",
+        ));
+    }
+
+    let mut records = Vec::new();
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(record) => records.push(record),
+            Err(err) => match *err {
+                // The recording ends with the last complete record, the
+                // process might have stopped half way through writing one
+                bincode::ErrorKind::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                err => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            },
+        }
+    }
+    Ok(records)
+}
+
+/// Destination of the records that is shared by all the sockets
+struct Recorder {
+    started: Instant,
+    next_id: AtomicU64,
+    writer: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder")
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    fn new(mut writer: Box<dyn Write + Send>) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.flush()?;
+        Ok(Self {
+            started: Instant::now(),
+            next_id: AtomicU64::new(0),
+            writer: Mutex::new(Some(writer)),
+        })
+    }
+
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn record(&self, event: NetEvent) {
+        let record = NetRecord {
+            at: self.started.elapsed(),
+            event,
+        };
+        let mut guard = self.writer.lock().unwrap();
+        if let Some(writer) = guard.as_mut() {
+            // Recordings are mostly looked at after a process went wrong, so
+            // nothing is left sitting in a buffer
+            let ret = bincode::serialize_into(&mut *writer, &record)
+                .map_err(|err| err.to_string())
+                .and_then(|_| writer.flush().map_err(|err| err.to_string()));
+            if let Err(err) = ret {
+                tracing::warn!("stopped recording network traffic - {err}");
+                guard.take();
+            }
+        }
+    }
+}
+
+/// Wraps a [`VirtualNetworking`] implementation and records the name
+/// resolutions, the TCP traffic and the datagrams that go through it.
+///
+/// Unix domain sockets are passed through without being recorded.
+#[derive(Debug, Clone)]
+pub struct RecordingNetworking<N> {
+    inner: N,
+    recorder: Arc<Recorder>,
+}
+
+impl<N: VirtualNetworking> RecordingNetworking<N> {
+    /// Records into `writer`, which receives the header straight away
+    pub fn new(inner: N, writer: impl Write + Send + 'static) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            recorder: Arc::new(Recorder::new(Box::new(writer))?),
+        })
+    }
+
+    /// Records into a newly created file
+    pub fn create(inner: N, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Self::new(inner, BufWriter::new(file))
+    }
+
+    /// Returns the networking implementation that is being recorded
+    pub fn inner(&self) -> &N {
+        &self.inner
+    }
+
+    fn record_bind<S>(
+        &self,
+        bind: DatagramBind,
+        result: Result<Box<S>>,
+    ) -> Result<RecordingDatagramSocket<S>>
+    where
+        S: VirtualSocket + ?Sized,
+    {
+        let inner = match result {
+            Ok(inner) => inner,
+            Err(error) => {
+                self.recorder.record(NetEvent::BindFailed { bind, error });
+                return Err(error);
+            }
+        };
+
+        let socket = self.recorder.next_id();
+        let local_addr = inner.addr_local().unwrap_or(match bind {
+            DatagramBind::Udp(addr) => addr,
+            DatagramBind::Icmp(addr) => SocketAddr::new(addr, 0),
+            DatagramBind::Raw => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        });
+        self.recorder.record(NetEvent::Bound {
+            socket,
+            bind,
+            local_addr,
+        });
+        Ok(RecordingDatagramSocket {
+            inner,
+            socket,
+            recorder: self.recorder.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<N: VirtualNetworking> VirtualNetworking for RecordingNetworking<N> {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let socket = self.record_bind(DatagramBind::Raw, self.inner.bind_raw().await)?;
+        Ok(Box::new(socket))
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let listener = match self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await
+        {
+            Ok(listener) => listener,
+            Err(error) => {
+                self.recorder.record(NetEvent::ListenFailed { addr, error });
+                return Err(error);
+            }
+        };
+
+        let id = self.recorder.next_id();
+        self.recorder.record(NetEvent::Listening {
+            listener: id,
+            addr,
+            local_addr: listener.addr_local().unwrap_or(addr),
+        });
+        Ok(Box::new(RecordingTcpListener {
+            inner: listener,
+            id,
+            recorder: self.recorder.clone(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let socket = self.record_bind(
+            DatagramBind::Udp(addr),
+            self.inner.bind_udp(addr, reuse_port, reuse_addr).await,
+        )?;
+        Ok(Box::new(socket))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let socket =
+            self.record_bind(DatagramBind::Icmp(addr), self.inner.bind_icmp(addr).await)?;
+        Ok(Box::new(socket))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let socket = match self.inner.connect_tcp(addr, peer).await {
+            Ok(socket) => socket,
+            Err(error) => {
+                self.recorder.record(NetEvent::ConnectFailed {
+                    peer_addr: peer,
+                    error,
+                });
+                return Err(error);
+            }
+        };
+
+        let conn = self.recorder.next_id();
+        self.recorder.record(NetEvent::Connected {
+            conn,
+            local_addr: socket.addr_local().unwrap_or(addr),
+            peer_addr: peer,
+        });
+        Ok(Box::new(RecordingTcpSocket {
+            inner: socket,
+            conn,
+            recorder: self.recorder.clone(),
+        }))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        let result = self.inner.resolve(host, port, dns_server).await;
+        self.recorder.record(NetEvent::Resolved {
+            host: host.to_string(),
+            result: result.clone(),
+        });
+        result
+    }
+
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixListener + Sync>> {
+        self.inner.listen_unix(path).await
+    }
+
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        self.inner.connect_unix(path).await
+    }
+
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        self.inner.bind_unix_datagram(path).await
+    }
+}
+
+#[derive(Debug)]
+struct RecordingTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    id: u64,
+    recorder: Arc<Recorder>,
+}
+
+impl VirtualIoSource for RecordingTcpListener {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for RecordingTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (socket, peer) = self.inner.try_accept()?;
+        let conn = self.recorder.next_id();
+        self.recorder.record(NetEvent::Accepted {
+            listener: self.id,
+            conn,
+            local_addr: socket.addr_local().or_else(|_| self.inner.addr_local())?,
+            peer_addr: peer,
+        });
+        let socket = RecordingTcpSocket {
+            inner: socket,
+            conn,
+            recorder: self.recorder.clone(),
+        };
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+#[derive(Debug)]
+struct RecordingTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    conn: u64,
+    recorder: Arc<Recorder>,
+}
+
+impl VirtualIoSource for RecordingTcpSocket {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for RecordingTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for RecordingTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let sent = self.inner.try_send(data)?;
+        if sent > 0 {
+            self.recorder.record(NetEvent::Send {
+                conn: self.conn,
+                data: data[..sent].to_vec(),
+            });
+        }
+        Ok(sent)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let read = self.inner.try_recv(buf, peek)?;
+        if !peek {
+            let event = match read {
+                0 if !buf.is_empty() => NetEvent::Closed { conn: self.conn },
+                0 => return Ok(0),
+                read => NetEvent::Recv {
+                    conn: self.conn,
+                    // Safety: the socket initialized the bytes that it read
+                    data: buf[..read]
+                        .iter()
+                        .map(|b| unsafe { b.assume_init() })
+                        .collect(),
+                },
+            };
+            self.recorder.record(event);
+        }
+        Ok(read)
+    }
+}
+
+impl VirtualTcpSocket for RecordingTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, reuse: bool) -> Result<()> {
+        self.inner.set_nodelay(reuse)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_dontroute(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_dontroute(keepalive)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+#[derive(Debug)]
+struct RecordingDatagramSocket<S: ?Sized> {
+    inner: Box<S>,
+    socket: u64,
+    recorder: Arc<Recorder>,
+}
+
+impl<S: ?Sized> RecordingDatagramSocket<S> {
+    fn record_send(&self, peer: Option<SocketAddr>, data: &[u8], sent: usize) {
+        self.recorder.record(NetEvent::SendTo {
+            socket: self.socket,
+            peer,
+            data: data[..sent].to_vec(),
+        });
+    }
+
+    fn record_recv(&self, peer: Option<SocketAddr>, buf: &[MaybeUninit<u8>], read: usize) {
+        self.recorder.record(NetEvent::RecvFrom {
+            socket: self.socket,
+            peer,
+            // Safety: the socket initialized the bytes that it read
+            data: buf[..read]
+                .iter()
+                .map(|b| unsafe { b.assume_init() })
+                .collect(),
+        });
+    }
+}
+
+impl<S: VirtualIoSource + ?Sized> VirtualIoSource for RecordingDatagramSocket<S> {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl<S: VirtualSocket + ?Sized> VirtualSocket for RecordingDatagramSocket<S> {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl<S: VirtualConnectionlessSocket + ?Sized> VirtualConnectionlessSocket
+    for RecordingDatagramSocket<S>
+{
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let sent = self.inner.try_send_to(data, addr)?;
+        self.record_send(Some(addr), data, sent);
+        Ok(sent)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        let (read, peer) = self.inner.try_recv_from(buf, peek)?;
+        if !peek {
+            self.record_recv(Some(peer), buf, read);
+        }
+        Ok((read, peer))
+    }
+}
+
+impl VirtualIcmpSocket for RecordingDatagramSocket<dyn VirtualIcmpSocket + Sync> {}
+
+impl VirtualUdpSocket for RecordingDatagramSocket<dyn VirtualUdpSocket + Sync> {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+impl VirtualRawSocket for RecordingDatagramSocket<dyn VirtualRawSocket + Sync> {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let sent = self.inner.try_send(data)?;
+        self.record_send(None, data, sent);
+        Ok(sent)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let read = self.inner.try_recv(buf, peek)?;
+        if !peek {
+            self.record_recv(None, buf, read);
+        }
+        Ok(read)
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.inner.set_promiscuous(promiscuous)
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        self.inner.promiscuous()
+    }
+}
+
+/// What happens next on a replayed connection
+#[derive(Debug)]
+enum Step {
+    /// The guest is expected to send these bytes
+    Send(BytesMut),
+    /// These bytes are received once the guest sent everything before them
+    Recv(BytesMut),
+    /// The peer closes the connection
+    Closed,
+}
+
+/// Recorded outcomes of the same kind of request, oldest first
+type Outcomes<T> = VecDeque<std::result::Result<T, NetworkError>>;
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    resolves: HashMap<String, Outcomes<Vec<IpAddr>>>,
+    listens: HashMap<SocketAddr, Outcomes<(u64, SocketAddr)>>,
+    connects: HashMap<SocketAddr, Outcomes<u64>>,
+    accepts: HashMap<u64, VecDeque<u64>>,
+    conns: HashMap<u64, ReplayTcpSocket>,
+    binds: HashMap<DatagramBind, Outcomes<u64>>,
+    datagram_sockets: HashMap<u64, ReplayDatagramSocket>,
+}
+
+impl ReplayState {
+    fn new(records: impl IntoIterator<Item = NetRecord>) -> Self {
+        let mut state = Self::default();
+        for record in records {
+            match record.event {
+                NetEvent::Resolved { host, result } => {
+                    state.resolves.entry(host).or_default().push_back(result)
+                }
+                NetEvent::Listening {
+                    listener,
+                    addr,
+                    local_addr,
+                } => state
+                    .listens
+                    .entry(addr)
+                    .or_default()
+                    .push_back(Ok((listener, local_addr))),
+                NetEvent::ListenFailed { addr, error } => {
+                    state.listens.entry(addr).or_default().push_back(Err(error))
+                }
+                NetEvent::Connected {
+                    conn,
+                    local_addr,
+                    peer_addr,
+                } => {
+                    state
+                        .connects
+                        .entry(peer_addr)
+                        .or_default()
+                        .push_back(Ok(conn));
+                    state
+                        .conns
+                        .insert(conn, ReplayTcpSocket::new(local_addr, peer_addr));
+                }
+                NetEvent::ConnectFailed { peer_addr, error } => state
+                    .connects
+                    .entry(peer_addr)
+                    .or_default()
+                    .push_back(Err(error)),
+                NetEvent::Accepted {
+                    listener,
+                    conn,
+                    local_addr,
+                    peer_addr,
+                } => {
+                    state.accepts.entry(listener).or_default().push_back(conn);
+                    state
+                        .conns
+                        .insert(conn, ReplayTcpSocket::new(local_addr, peer_addr));
+                }
+                NetEvent::Send { conn, data } => {
+                    if let Some(socket) = state.conns.get_mut(&conn) {
+                        socket.steps.push_back(Step::Send(data[..].into()));
+                    }
+                }
+                NetEvent::Recv { conn, data } => {
+                    if let Some(socket) = state.conns.get_mut(&conn) {
+                        socket.steps.push_back(Step::Recv(data[..].into()));
+                    }
+                }
+                NetEvent::Closed { conn } => {
+                    if let Some(socket) = state.conns.get_mut(&conn) {
+                        socket.steps.push_back(Step::Closed);
+                    }
+                }
+                NetEvent::Bound {
+                    socket,
+                    bind,
+                    local_addr,
+                } => {
+                    state.binds.entry(bind).or_default().push_back(Ok(socket));
+                    state
+                        .datagram_sockets
+                        .insert(socket, ReplayDatagramSocket::new(local_addr));
+                }
+                NetEvent::BindFailed { bind, error } => {
+                    state.binds.entry(bind).or_default().push_back(Err(error))
+                }
+                NetEvent::SendTo { socket, peer, data } => {
+                    if let Some(socket) = state.datagram_sockets.get_mut(&socket) {
+                        socket.steps.push_back(DatagramStep::Send { peer, data });
+                    }
+                }
+                NetEvent::RecvFrom { socket, peer, data } => {
+                    if let Some(socket) = state.datagram_sockets.get_mut(&socket) {
+                        socket.steps.push_back(DatagramStep::Recv { peer, data });
+                    }
+                }
+            }
+        }
+        state
+    }
+}
+
+/// Serves the name resolutions, TCP connections and datagrams of a recording
+/// that was made with [`RecordingNetworking`], without touching any real
+/// network.
+///
+/// Resolutions, connects, listens and binds are matched against the
+/// recording by hostname, peer and address (in the order in which they were
+/// recorded).
+/// A replayed connection only hands out what was received after the guest
+/// sent everything that preceded it in the recording, so responses come in
+/// the same order relative to requests no matter how fast the replay runs,
+/// but as soon as the guest is ready for them rather than at the recorded
+/// times. Datagram sockets are replayed in the same way, one whole datagram
+/// at a time. What the guest sends is compared with the recording and a
+/// warning is logged when it diverges.
+#[derive(Debug, Clone)]
+pub struct ReplayNetworking {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayNetworking {
+    pub fn new(records: impl IntoIterator<Item = NetRecord>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ReplayState::new(records))),
+        }
+    }
+
+    /// Replays a recording that is read from `reader`
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        Ok(Self::new(read_records(reader)?))
+    }
+
+    /// Replays a recording file
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    fn bind(&self, bind: DatagramBind) -> Result<ReplayDatagramSocket> {
+        let mut state = self.state.lock().unwrap();
+        let socket = match state.binds.get_mut(&bind).and_then(|b| b.pop_front()) {
+            Some(result) => result?,
+            None => {
+                tracing::warn!(?bind, "socket is not in the network recording");
+                return Err(NetworkError::AddressNotAvailable);
+            }
+        };
+        state
+            .datagram_sockets
+            .remove(&socket)
+            .ok_or(NetworkError::AddressNotAvailable)
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for ReplayNetworking {
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        _only_v6: bool,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let mut state = self.state.lock().unwrap();
+        let (id, local_addr) = match state.listens.get_mut(&addr).and_then(|l| l.pop_front()) {
+            Some(result) => result?,
+            None => {
+                tracing::warn!(%addr, "listener is not in the network recording");
+                return Err(NetworkError::AddressNotAvailable);
+            }
+        };
+
+        let accepts = state.accepts.remove(&id).unwrap_or_default();
+        let accepts = accepts
+            .into_iter()
+            .filter_map(|conn| state.conns.remove(&conn))
+            .collect();
+        Ok(Box::new(ReplayTcpListener {
+            local_addr,
+            accepts,
+            handler: None,
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        _addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let mut state = self.state.lock().unwrap();
+        let conn = match state.connects.get_mut(&peer).and_then(|c| c.pop_front()) {
+            Some(result) => result?,
+            None => {
+                tracing::warn!(%peer, "connection is not in the network recording");
+                return Err(NetworkError::ConnectionRefused);
+            }
+        };
+        let socket = state
+            .conns
+            .remove(&conn)
+            .ok_or(NetworkError::ConnectionRefused)?;
+        Ok(Box::new(socket))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        Ok(Box::new(self.bind(DatagramBind::Udp(addr))?))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        Ok(Box::new(self.bind(DatagramBind::Icmp(addr))?))
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        Ok(Box::new(self.bind(DatagramBind::Raw)?))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        let mut state = self.state.lock().unwrap();
+        match state.resolves.get_mut(host) {
+            // The last answer keeps being served once the recorded ones run out
+            Some(answers) if answers.len() > 1 => answers.pop_front().unwrap(),
+            Some(answers) if !answers.is_empty() => answers[0].clone(),
+            _ => {
+                tracing::warn!(%host, "name resolution is not in the network recording");
+                Err(NetworkError::AddressNotAvailable)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ReplayTcpListener {
+    local_addr: SocketAddr,
+    accepts: VecDeque<ReplayTcpSocket>,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+}
+
+impl VirtualIoSource for ReplayTcpListener {
+    fn remove_handler(&mut self) {
+        self.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        // Nothing else is ever going to arrive once the recording ran out
+        match self.accepts.len() {
+            0 => Poll::Pending,
+            len => Poll::Ready(Ok(len)),
+        }
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for ReplayTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let socket = self.accepts.pop_front().ok_or(NetworkError::WouldBlock)?;
+        let peer = socket.peer_addr;
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        if !self.accepts.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        self.handler.replace(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn set_ttl(&mut self, _ttl: u8) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(64)
+    }
+}
+
+#[derive(Debug)]
+struct ReplayTcpSocket {
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+    steps: VecDeque<Step>,
+    diverged: bool,
+    closed: bool,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    waker: Option<Waker>,
+}
+
+impl ReplayTcpSocket {
+    fn new(local_addr: SocketAddr, peer_addr: SocketAddr) -> Self {
+        Self {
+            local_addr,
+            peer_addr,
+            steps: VecDeque::new(),
+            diverged: false,
+            closed: false,
+            handler: None,
+            waker: None,
+        }
+    }
+
+    /// Bytes that can be received right now, `None` when the peer closed
+    /// the connection
+    fn readable(&self) -> Option<usize> {
+        match self.steps.front() {
+            Some(Step::Recv(data)) => Some(data.len()),
+            Some(Step::Closed) => None,
+            _ => Some(0),
+        }
+    }
+
+    fn diverge(&mut self, msg: &str) {
+        if !self.diverged {
+            tracing::warn!(peer = %self.peer_addr, "{msg}, the replay no longer matches the network recording");
+            self.diverged = true;
+        }
+    }
+
+    fn notify_readable(&mut self) {
+        if self.readable() != Some(0) {
+            if let Some(handler) = self.handler.as_mut() {
+                handler.push_interest(InterestType::Readable);
+            }
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl VirtualIoSource for ReplayTcpSocket {
+    fn remove_handler(&mut self) {
+        self.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match self.readable() {
+            Some(0) => {
+                self.waker.replace(cx.waker().clone());
+                Poll::Pending
+            }
+            Some(len) => Poll::Ready(Ok(len)),
+            None => Poll::Ready(Ok(0)),
+        }
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(usize::MAX))
+    }
+}
+
+impl VirtualSocket for ReplayTcpSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(64)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(match self.closed {
+            true => SocketStatus::Closed,
+            false => SocketStatus::Opened,
+        })
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        handler.push_interest(InterestType::Writable);
+        self.handler.replace(handler);
+        self.notify_readable();
+        Ok(())
+    }
+}
+
+impl VirtualConnectedSocket for ReplayTcpSocket {
+    fn set_linger(&mut self, _linger: Option<Duration>) -> Result<()> {
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        if self.closed {
+            return Err(NetworkError::ConnectionReset);
+        }
+
+        let mut rest = data;
+        while !rest.is_empty() {
+            let Some(Step::Send(expected)) = self.steps.front_mut() else {
+                break;
+            };
+            let amt = rest.len().min(expected.len());
+            let matches = expected[..amt] == rest[..amt];
+            expected.advance(amt);
+            if expected.is_empty() {
+                self.steps.pop_front();
+            }
+            rest = &rest[amt..];
+            if !matches {
+                self.diverge("the guest sent different data");
+            }
+        }
+        if !rest.is_empty() {
+            self.diverge("the guest sent more data than was recorded");
+        }
+
+        self.notify_readable();
+        Ok(data.len())
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.closed = true;
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let data = match self.steps.front_mut() {
+            Some(Step::Recv(data)) => data,
+            Some(Step::Closed) => return Ok(0),
+            // Either the guest has yet to send what came before the response
+            // or the recording ended while the connection was still open
+            _ => return Err(NetworkError::WouldBlock),
+        };
+
+        let amt = buf.len().min(data.len());
+        for (dst, src) in buf.iter_mut().zip(data[..amt].iter()) {
+            dst.write(*src);
+        }
+        if !peek {
+            data.advance(amt);
+            if data.is_empty() {
+                self.steps.pop_front();
+            }
+        }
+        Ok(amt)
+    }
+}
+
+impl VirtualTcpSocket for ReplayTcpSocket {
+    fn set_recv_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Ok(64 * 1024)
+    }
+
+    fn set_send_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Ok(64 * 1024)
+    }
+
+    fn set_nodelay(&mut self, _reuse: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn set_keepalive(&mut self, _keepalive: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_dontroute(&mut self, _keepalive: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.peer_addr)
+    }
+
+    fn shutdown(&mut self, _how: Shutdown) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+/// What happens next on a replayed datagram socket
+#[derive(Debug)]
+enum DatagramStep {
+    /// The guest is expected to send this datagram
+    Send {
+        peer: Option<SocketAddr>,
+        data: Vec<u8>,
+    },
+    /// This datagram is received once the guest sent everything before it
+    Recv {
+        peer: Option<SocketAddr>,
+        data: Vec<u8>,
+    },
+}
+
+/// Replays the datagrams of a UDP, ICMP or raw socket
+#[derive(Debug)]
+struct ReplayDatagramSocket {
+    local_addr: SocketAddr,
+    steps: VecDeque<DatagramStep>,
+    diverged: bool,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    waker: Option<Waker>,
+}
+
+impl ReplayDatagramSocket {
+    fn new(local_addr: SocketAddr) -> Self {
+        Self {
+            local_addr,
+            steps: VecDeque::new(),
+            diverged: false,
+            handler: None,
+            waker: None,
+        }
+    }
+
+    /// Size of the datagram that can be received right now
+    fn readable(&self) -> Option<usize> {
+        match self.steps.front() {
+            Some(DatagramStep::Recv { data, .. }) => Some(data.len()),
+            _ => None,
+        }
+    }
+
+    fn diverge(&mut self, msg: &str) {
+        if !self.diverged {
+            tracing::warn!(local = %self.local_addr, "{msg}, the replay no longer matches the network recording");
+            self.diverged = true;
+        }
+    }
+
+    fn notify_readable(&mut self) {
+        if self.readable().is_some() {
+            if let Some(handler) = self.handler.as_mut() {
+                handler.push_interest(InterestType::Readable);
+            }
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    fn send(&mut self, peer: Option<SocketAddr>, data: &[u8]) -> Result<usize> {
+        match self.steps.front() {
+            Some(DatagramStep::Send {
+                peer: expected_peer,
+                data: expected,
+            }) => {
+                let matches = *expected_peer == peer && expected[..] == data[..];
+                self.steps.pop_front();
+                if !matches {
+                    self.diverge("the guest sent a different datagram");
+                }
+            }
+            _ => self.diverge("the guest sent more datagrams than were recorded"),
+        }
+
+        self.notify_readable();
+        Ok(data.len())
+    }
+
+    fn recv(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, Option<SocketAddr>)> {
+        let Some(DatagramStep::Recv { peer, data }) = self.steps.front() else {
+            return Err(NetworkError::WouldBlock);
+        };
+
+        // Whatever does not fit in the buffer is lost, as with any datagram
+        let amt = buf.len().min(data.len());
+        for (dst, src) in buf.iter_mut().zip(data[..amt].iter()) {
+            dst.write(*src);
+        }
+        let peer = *peer;
+        if !peek {
+            self.steps.pop_front();
+        }
+        Ok((amt, peer))
+    }
+}
+
+impl VirtualIoSource for ReplayDatagramSocket {
+    fn remove_handler(&mut self) {
+        self.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match self.readable() {
+            Some(len) => Poll::Ready(Ok(len)),
+            None => {
+                self.waker.replace(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(usize::MAX))
+    }
+}
+
+impl VirtualSocket for ReplayDatagramSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(64)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        handler.push_interest(InterestType::Writable);
+        self.handler.replace(handler);
+        self.notify_readable();
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for ReplayDatagramSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.send(Some(addr), data)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        let (read, peer) = self.recv(buf, peek)?;
+        Ok((read, peer.unwrap_or(self.local_addr)))
+    }
+}
+
+impl VirtualIcmpSocket for ReplayDatagramSocket {}
+
+impl VirtualUdpSocket for ReplayDatagramSocket {
+    fn set_broadcast(&mut self, _broadcast: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Ok(1)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Ok(())
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Ok(())
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
+
+impl VirtualRawSocket for ReplayDatagramSocket {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.send(None, data)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        Ok(self.recv(buf, peek)?.0)
+    }
+
+    fn set_promiscuous(&mut self, _promiscuous: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoopbackNetworking;
+
+    /// Loopback networking that also connects to its listener on port 80
+    /// and resolves a single hostname
+    #[derive(Debug, Clone)]
+    struct TestNetworking(LoopbackNetworking);
+
+    #[async_trait::async_trait]
+    impl VirtualNetworking for TestNetworking {
+        async fn listen_tcp(
+            &self,
+            addr: SocketAddr,
+            only_v6: bool,
+            reuse_port: bool,
+            reuse_addr: bool,
+        ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+            self.0
+                .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+                .await
+        }
+
+        async fn connect_tcp(
+            &self,
+            addr: SocketAddr,
+            peer: SocketAddr,
+        ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+            // The loopback connects to any of its listeners otherwise
+            if peer.port() != 80 {
+                return Err(NetworkError::ConnectionRefused);
+            }
+            let socket = self
+                .0
+                .loopback_connect_to(addr, peer)
+                .ok_or(NetworkError::ConnectionRefused)?;
+            Ok(Box::new(socket))
+        }
+
+        async fn resolve(
+            &self,
+            host: &str,
+            _port: Option<u16>,
+            _dns_server: Option<IpAddr>,
+        ) -> Result<Vec<IpAddr>> {
+            match host {
+                "example.com" => Ok(vec![[127, 0, 0, 1].into()]),
+                _ => Err(NetworkError::AddressNotAvailable),
+            }
+        }
+    }
+
+    /// A writer whose contents can still be read after it was handed over
+    #[derive(Debug, Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn recv(socket: &mut Box<dyn VirtualTcpSocket + Sync>) -> Result<Vec<u8>> {
+        let mut buf = [MaybeUninit::new(0u8); 1024];
+        let read = socket.try_recv(&mut buf, false)?;
+        Ok(buf[..read]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let buffer = SharedBuffer::default();
+        let net =
+            RecordingNetworking::new(TestNetworking(LoopbackNetworking::new()), buffer.clone())
+                .unwrap();
+        let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+
+        assert_eq!(
+            net.resolve("example.com", None, None).await.unwrap(),
+            vec![IpAddr::from([127, 0, 0, 1])]
+        );
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let mut client = net.connect_tcp(client_addr, addr).await.unwrap();
+        let (mut server, _) = listener.try_accept().unwrap();
+        assert_eq!(
+            net.connect_tcp(client_addr, "127.0.0.1:81".parse().unwrap())
+                .await
+                .unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+
+        client.try_send(b"ping").unwrap();
+        assert_eq!(recv(&mut server).unwrap(), b"ping");
+        server.try_send(b"pong").unwrap();
+        assert_eq!(recv(&mut client).unwrap(), b"pong");
+
+        let records = read_records(&buffer.0.lock().unwrap()[..]).unwrap();
+        assert_eq!(records.len(), 9);
+        assert!(records.windows(2).all(|w| w[0].at <= w[1].at));
+        assert_eq!(
+            records[6].event,
+            NetEvent::Recv {
+                conn: 2,
+                data: b"ping".to_vec()
+            }
+        );
+
+        let net = ReplayNetworking::new(records);
+        assert_eq!(
+            net.resolve("example.com", None, None).await.unwrap(),
+            vec![IpAddr::from([127, 0, 0, 1])]
+        );
+        // The last answer keeps being served
+        assert!(net.resolve("example.com", None, None).await.is_ok());
+        assert_eq!(
+            net.resolve("example.org", None, None).await.unwrap_err(),
+            NetworkError::AddressNotAvailable
+        );
+
+        let mut client = net.connect_tcp(client_addr, addr).await.unwrap();
+        assert_eq!(client.addr_peer().unwrap(), addr);
+        // The response only arrives once the request was sent
+        assert_eq!(recv(&mut client).unwrap_err(), NetworkError::WouldBlock);
+        assert_eq!(client.try_send(b"pi").unwrap(), 2);
+        assert_eq!(recv(&mut client).unwrap_err(), NetworkError::WouldBlock);
+        client.try_send(b"ng").unwrap();
+        assert_eq!(recv(&mut client).unwrap(), b"pong");
+        assert_eq!(recv(&mut client).unwrap_err(), NetworkError::WouldBlock);
+        assert_eq!(
+            net.connect_tcp(client_addr, "127.0.0.1:81".parse().unwrap())
+                .await
+                .unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let (mut server, _) = listener.try_accept().unwrap();
+        assert_eq!(recv(&mut server).unwrap(), b"ping");
+        server.try_send(b"pong").unwrap();
+        assert!(listener.try_accept().is_err());
+
+        // Sockets that were not recorded can not be bound
+        assert_eq!(
+            net.bind_udp(client_addr, false, false).await.unwrap_err(),
+            NetworkError::AddressNotAvailable
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay_datagrams() {
+        let addr: SocketAddr = "0.0.0.0:5000".parse().unwrap();
+        let dns: SocketAddr = "10.0.0.1:53".parse().unwrap();
+        let events = vec![
+            NetEvent::Bound {
+                socket: 0,
+                bind: DatagramBind::Udp(addr),
+                local_addr: addr,
+            },
+            NetEvent::SendTo {
+                socket: 0,
+                peer: Some(dns),
+                data: b"query".to_vec(),
+            },
+            NetEvent::RecvFrom {
+                socket: 0,
+                peer: Some(dns),
+                data: b"answer".to_vec(),
+            },
+            NetEvent::BindFailed {
+                bind: DatagramBind::Icmp([127, 0, 0, 1].into()),
+                error: NetworkError::PermissionDenied,
+            },
+            NetEvent::Bound {
+                socket: 1,
+                bind: DatagramBind::Raw,
+                local_addr: "0.0.0.0:0".parse().unwrap(),
+            },
+            NetEvent::RecvFrom {
+                socket: 1,
+                peer: None,
+                data: b"frame".to_vec(),
+            },
+        ];
+        let records = events.iter().cloned().map(|event| NetRecord {
+            at: Duration::ZERO,
+            event,
+        });
+
+        // Recording a replay records the same events again
+        let buffer = SharedBuffer::default();
+        let net = RecordingNetworking::new(ReplayNetworking::new(records), buffer.clone()).unwrap();
+        let mut buf = [MaybeUninit::new(0u8); 1024];
+
+        let mut udp = net.bind_udp(addr, false, false).await.unwrap();
+        // The answer only arrives once the query was sent
+        assert_eq!(
+            udp.try_recv_from(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        assert_eq!(udp.try_send_to(b"query", dns).unwrap(), 5);
+        assert_eq!(udp.try_recv_from(&mut buf, true).unwrap(), (6, dns));
+        assert_eq!(udp.try_recv_from(&mut buf, false).unwrap(), (6, dns));
+        assert_eq!(
+            udp.try_recv_from(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        assert_eq!(
+            net.bind_icmp([127, 0, 0, 1].into()).await.unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+        let mut raw = net.bind_raw().await.unwrap();
+        assert_eq!(raw.try_recv(&mut buf[..3], false).unwrap(), 3);
+        assert_eq!(
+            raw.try_recv(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock,
+            "the rest of a truncated datagram is lost"
+        );
+
+        let recorded = read_records(&buffer.0.lock().unwrap()[..])
+            .unwrap()
+            .into_iter()
+            .map(|record| record.event)
+            .collect::<Vec<_>>();
+        assert_eq!(recorded[..5], events[..5]);
+        assert_eq!(
+            recorded[5],
+            NetEvent::RecvFrom {
+                socket: 1,
+                peer: None,
+                data: b"fra".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_truncated_recording() {
+        let buffer = SharedBuffer::default();
+        let recorder = Recorder::new(Box::new(buffer.clone())).unwrap();
+        recorder.record(NetEvent::Closed { conn: 1 });
+        recorder.record(NetEvent::Recv {
+            conn: 1,
+            data: b"hello".to_vec(),
+        });
+
+        // A process that stops half way through a record loses only that one
+        let mut data = buffer.0.lock().unwrap().clone();
+        data.truncate(data.len() - 2);
+        let records = read_records(&data[..]).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].event, NetEvent::Closed { conn: 1 });
+
+        assert!(read_records(&b"something else"[..]).is_err());
+    }
+}
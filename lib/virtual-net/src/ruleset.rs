@@ -54,10 +54,16 @@
 /// ipv4:deny=192.168.1.1/24:80,
 /// ipv4:deny=192.168.1.1/24:443
 /// ```
+///
+/// ### Auditing
+/// Every decision is counted against the rule that took it (see [`Ruleset::rule_hits`]) and can
+/// be reported to an event handler as a [`RulesetEvent`]. In dry-run mode denied accesses are
+/// logged but let through, which makes it possible to try out a new policy safely.
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::RangeInclusive;
 use std::str::FromStr;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
 use ipnet::{Ipv4Net, Ipv6Net};
 use iprange::IpRange;
@@ -663,49 +669,178 @@ fn parse_ruleset_segments(s: impl AsRef<str>) -> Result<Vec<RulesetSegment>, Rul
     Ok(segments)
 }
 
+/// An allow or deny decision that was taken by a [`Ruleset`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RulesetEvent {
+    /// Index of the rule that took the decision, `None` when no rule matched
+    /// and the access was denied by default
+    pub rule: Option<usize>,
+    /// Direction of the traffic, only set for socket decisions
+    pub direction: Option<Direction>,
+    /// Address that was accessed, only set for socket decisions
+    pub addr: Option<SocketAddr>,
+    /// Domain that was queried, only set for DNS decisions
+    pub domain: Option<String>,
+    /// Whether the rules allow the access
+    pub allowed: bool,
+    /// Set when the access was denied by the rules but let through anyway
+    /// because the ruleset is in dry-run mode
+    pub dry_run: bool,
+}
+
+/// Number of decisions that were taken by one of the rules of a [`Ruleset`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleHits {
+    pub index: usize,
+    pub rule: Rule,
+    pub hits: u64,
+}
+
+#[derive(Clone)]
+struct RulesetEventHandler(Arc<dyn Fn(&RulesetEvent) + Send + Sync>);
+
+impl std::fmt::Debug for RulesetEventHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RulesetEventHandler")
+            .finish_non_exhaustive()
+    }
+}
+
 /// Represents a ruleset that can be used to specify a whitelist and a blacklist in order to
 /// control the inbound and outbound traffic of a network.
 #[derive(Debug, Clone)]
 pub struct Ruleset {
     rules: Arc<RwLock<Vec<Rule>>>,
+    hits: Arc<Mutex<Vec<u64>>>,
+    unmatched: Arc<AtomicU64>,
+    dry_run: bool,
+    event_handler: Option<RulesetEventHandler>,
 }
 
 impl Ruleset {
+    /// In dry-run mode accesses that the rules deny are logged (and reported to the
+    /// event handler) but not blocked
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Calls `handler` for every allow or deny decision that is taken
+    pub fn with_event_handler(
+        mut self,
+        handler: impl Fn(&RulesetEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.event_handler = Some(RulesetEventHandler(Arc::new(handler)));
+        self
+    }
+
+    /// Returns `true` if denied accesses are only logged and not blocked
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Returns how many decisions each of the rules took, in the order of the rules
+    pub fn rule_hits(&self) -> Vec<RuleHits> {
+        let rules = self.rules.read().unwrap();
+        let hits = self.hits.lock().unwrap();
+        rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| RuleHits {
+                index,
+                rule: rule.clone(),
+                hits: hits.get(index).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Returns how many accesses were denied because none of the rules matched them
+    pub fn unmatched_hits(&self) -> u64 {
+        self.unmatched.load(Ordering::Relaxed)
+    }
+
+    /// Counts and reports a decision, returns `true` if the access may go ahead
+    fn decide(&self, mut event: RulesetEvent) -> bool {
+        match event.rule {
+            Some(index) => {
+                let mut hits = self.hits.lock().unwrap();
+                if hits.len() <= index {
+                    hits.resize(index + 1, 0);
+                }
+                hits[index] += 1;
+            }
+            None => {
+                self.unmatched.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        if !event.allowed && self.dry_run {
+            event.dry_run = true;
+            tracing::warn!(
+                rule = ?event.rule,
+                direction = ?event.direction,
+                addr = ?event.addr,
+                domain = ?event.domain,
+                "network ruleset would deny access (dry-run)"
+            );
+        }
+
+        if let Some(handler) = self.event_handler.as_ref() {
+            (handler.0)(&event);
+        }
+
+        event.allowed || event.dry_run
+    }
+
     /// Returns `true` if at least one rule allows accessing `socket_addr` in the specific `direction`
     /// and no rule blocks it
     pub fn allows_socket(&self, addr: impl Into<SocketAddr>, dir: Direction) -> bool {
         let addr = addr.into();
 
-        let is_allowed = {
+        let (rule, allowed) = {
             let ruleset = self.rules.read().unwrap();
 
-            let is_blacklisted = ruleset.iter().any(|r| r.blocks_socket(addr, dir));
-            if is_blacklisted {
-                return false;
+            if let Some(index) = ruleset.iter().position(|r| r.blocks_socket(addr, dir)) {
+                (Some(index), false)
+            } else {
+                let index = ruleset.iter().position(|r| r.allows_socket(addr, dir));
+                (index, index.is_some())
             }
-
-            ruleset.iter().any(|r| r.allows_socket(addr, dir))
         };
 
-        is_allowed
+        self.decide(RulesetEvent {
+            rule,
+            direction: Some(dir),
+            addr: Some(addr),
+            domain: None,
+            allowed,
+            dry_run: false,
+        })
     }
 
     /// Returns `true` if at least one rule allows querying the specific `domain` and no rule blocks it
     pub fn allows_domain(&self, domain: impl AsRef<str>) -> bool {
         let domain = domain.as_ref();
 
-        let is_allowed = {
+        let (rule, allowed) = {
             let ruleset = self.rules.read().unwrap();
 
-            let is_blacklisted = ruleset.iter().any(|r| r.blocks_domain(domain));
-            if is_blacklisted {
-                return false;
+            if let Some(index) = ruleset.iter().position(|r| r.blocks_domain(domain)) {
+                (Some(index), false)
+            } else {
+                let index = ruleset.iter().position(|r| r.allows_domain(domain));
+                (index, index.is_some())
             }
-
-            ruleset.iter().any(|r| r.allows_domain(domain))
         };
 
-        is_allowed
+        self.decide(RulesetEvent {
+            rule,
+            direction: None,
+            addr: None,
+            domain: Some(domain.to_string()),
+            allowed,
+            dry_run: false,
+        })
     }
 
     /// Expands the DNS rule that allows the specified `domain` into a list of IP based
@@ -804,6 +939,10 @@ impl FromStr for Ruleset {
 
         Ok(Self {
             rules: Arc::new(RwLock::new(rules)),
+            hits: Default::default(),
+            unmatched: Default::default(),
+            dry_run: false,
+            event_handler: None,
         })
     }
 }
@@ -1490,4 +1629,68 @@ mod tests {
             Direction::Inbound
         ));
     }
+
+    #[test]
+    fn ruleset_hits() {
+        let ruleset = Ruleset::from_str(
+            "dns:allow=a.com:443,
+            ipv4:deny=10.0.0.1:*,
+            ipv4:allow=10.0.0.0/24:*",
+        )
+        .unwrap();
+
+        assert!(ruleset.allows_domain("a.com"));
+        assert!(!ruleset.allows_domain("b.com"));
+        assert!(!ruleset.allows_socket(([10, 0, 0, 1], 80), Direction::Outbound));
+        assert!(ruleset.allows_socket(([10, 0, 0, 2], 80), Direction::Outbound));
+        assert!(ruleset.allows_socket(([10, 0, 0, 3], 80), Direction::Outbound));
+        assert!(!ruleset.allows_socket(([10, 0, 1, 1], 80), Direction::Outbound));
+
+        let hits: Vec<_> = ruleset.rule_hits().iter().map(|r| r.hits).collect();
+        assert_eq!(hits, vec![1, 1, 2]);
+        assert_eq!(ruleset.unmatched_hits(), 2);
+
+        // Rules that are added when a domain is expanded are counted as well
+        ruleset
+            .expand_domain("a.com", [IpAddr::from([1, 2, 3, 4])])
+            .unwrap();
+        assert!(ruleset.allows_socket(([1, 2, 3, 4], 443), Direction::Outbound));
+        let hits: Vec<_> = ruleset.rule_hits().iter().map(|r| r.hits).collect();
+        assert_eq!(hits, vec![1, 1, 2, 1]);
+    }
+
+    #[test]
+    fn ruleset_events_and_dry_run() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let ruleset = Ruleset::from_str("ipv4:allow=10.0.0.0/24:80/out")
+            .unwrap()
+            .with_dry_run(true)
+            .with_event_handler({
+                let events = events.clone();
+                move |event| events.lock().unwrap().push(event.clone())
+            });
+
+        let denied: SocketAddr = ([10, 0, 0, 1], 443).into();
+        assert!(ruleset.allows_socket(([10, 0, 0, 1], 80), Direction::Outbound));
+        assert!(ruleset.allows_socket(denied, Direction::Outbound));
+        assert!(ruleset.allows_domain("a.com"));
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].rule, Some(0));
+        assert!(events[0].allowed && !events[0].dry_run);
+        assert_eq!(
+            events[1],
+            RulesetEvent {
+                rule: None,
+                direction: Some(Direction::Outbound),
+                addr: Some(denied),
+                domain: None,
+                allowed: false,
+                dry_run: true,
+            }
+        );
+        assert_eq!(events[2].domain.as_deref(), Some("a.com"));
+        assert!(!events[2].allowed && events[2].dry_run);
+    }
 }
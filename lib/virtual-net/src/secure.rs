@@ -0,0 +1,1002 @@
+//! Authentication and encryption for the remote networking protocol
+//!
+//! Before any [`MessageRequest`](crate::meta::MessageRequest) is exchanged the
+//! client and the server run a handshake over the byte stream:
+//!
+//! 1. The client sends its ID, the highest protocol version it speaks, an
+//!    ephemeral X25519 public key and a random nonce.
+//! 2. The server looks up the pre-shared key of the client, picks the
+//!    protocol version and answers with its own ephemeral key, nonce and a
+//!    MAC of the handshake made with the pre-shared key.
+//! 3. The client checks that MAC and proves that it knows the key too.
+//!
+//! The traffic keys are derived from both the pre-shared key and the
+//! ephemeral Diffie-Hellman secret, after which every record is sealed with
+//! ChaCha20-Poly1305. A client that does not know the key of its ID never
+//! gets to send a request, and the server enforces the [`Ruleset`] that was
+//! registered for that client on everything the client asks for.
+//!
+//! The handshake runs over byte streams and over websockets, where the
+//! encrypted stream is carried in binary messages (see the `new_from_secure_*`
+//! constructors of [`RemoteNetworkingClient`](crate::RemoteNetworkingClient)
+//! and [`RemoteNetworkingServer`](crate::RemoteNetworkingServer)). The `mpsc`
+//! constructors connect both ends within the same process and are not
+//! authenticated.
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bytes::{Buf, BytesMut};
+use ring::{aead, agreement, digest, hkdf, hmac, rand};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::ruleset::Ruleset;
+use crate::{io_err_into_net_error, NetworkError, Result};
+
+/// Version of the protocol that is spoken on top of the handshake
+pub const PROTOCOL_VERSION: u16 = 1;
+/// Oldest version of the protocol that is still accepted
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+const MAGIC: [u8; 4] = *b"WVNS";
+const MAX_HANDSHAKE_LEN: usize = 4096;
+const MAX_RECORD_LEN: usize = 64 * 1024;
+/// Set in the length of the (empty) record that closes a stream, the length
+/// is part of the sealed data so the close can not be forged
+const CLOSE_RECORD_FLAG: u32 = 0x8000_0000;
+
+type BoxedWrite = Pin<Box<dyn AsyncWrite + Send + 'static>>;
+type BoxedRead = Pin<Box<dyn AsyncRead + Send + 'static>>;
+
+/// The identity and pre-shared key (or token) that a client connects with
+#[derive(Clone)]
+pub struct RemoteCredentials {
+    client_id: String,
+    key: Vec<u8>,
+}
+
+impl RemoteCredentials {
+    pub fn new(client_id: impl Into<String>, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            key: key.into(),
+        }
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+}
+
+impl fmt::Debug for RemoteCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteCredentials")
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone)]
+struct RemoteClient {
+    key: Vec<u8>,
+    ruleset: Option<Ruleset>,
+}
+
+/// The clients that may connect to a server and what each of them is
+/// allowed to do
+#[derive(Clone, Default)]
+pub struct RemoteAuthenticator {
+    clients: HashMap<String, RemoteClient>,
+}
+
+impl RemoteAuthenticator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the client with `client_id` to connect with `key`, its
+    /// requests are checked against `ruleset` when one is given
+    pub fn add_client(
+        &mut self,
+        client_id: impl Into<String>,
+        key: impl Into<Vec<u8>>,
+        ruleset: Option<Ruleset>,
+    ) {
+        self.clients.insert(
+            client_id.into(),
+            RemoteClient {
+                key: key.into(),
+                ruleset,
+            },
+        );
+    }
+
+    pub fn remove_client(&mut self, client_id: &str) {
+        self.clients.remove(client_id);
+    }
+}
+
+impl fmt::Debug for RemoteAuthenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteAuthenticator")
+            .field("clients", &self.clients.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ClientHello {
+    magic: [u8; 4],
+    version: u16,
+    client_id: String,
+    public_key: Vec<u8>,
+    nonce: [u8; 32],
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ServerHello {
+    Accept {
+        version: u16,
+        public_key: Vec<u8>,
+        nonce: [u8; 32],
+        mac: Vec<u8>,
+    },
+    Reject {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ClientFinished {
+    mac: Vec<u8>,
+}
+
+/// Everything that both sides have said during the handshake, which the
+/// MACs and the traffic keys are bound to
+fn transcript(client_hello: &[u8], version: u16, public_key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(client_hello.len() + 2 + public_key.len() + nonce.len());
+    ret.extend(client_hello);
+    ret.extend(version.to_be_bytes());
+    ret.extend(public_key);
+    ret.extend(nonce);
+    ret
+}
+
+fn handshake_mac(key: &[u8], label: &[u8], transcript: &[u8]) -> hmac::Tag {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    let mut ctx = hmac::Context::with_key(&key);
+    ctx.update(label);
+    ctx.update(transcript);
+    ctx.sign()
+}
+
+fn verify_mac(key: &[u8], label: &[u8], transcript: &[u8], mac: &[u8]) -> Result<()> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    let mut data = label.to_vec();
+    data.extend(transcript);
+    hmac::verify(&key, &data, mac).map_err(|_| NetworkError::PermissionDenied)
+}
+
+/// Derives the keys of both directions from the pre-shared key and the
+/// ephemeral Diffie-Hellman secret
+fn traffic_keys(psk: &[u8], shared: &[u8], transcript: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let salt = digest::digest(&digest::SHA256, transcript);
+    let mut ikm = shared.to_vec();
+    ikm.extend(psk);
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, salt.as_ref()).extract(&ikm);
+
+    let expand = |label: &[u8]| -> Result<Vec<u8>> {
+        let mut key = vec![0u8; aead::CHACHA20_POLY1305.key_len()];
+        prk.expand(&[label], &aead::CHACHA20_POLY1305)
+            .and_then(|okm| okm.fill(&mut key))
+            .map_err(|_| NetworkError::UnknownError)?;
+        Ok(key)
+    };
+    Ok((expand(b"client to server")?, expand(b"server to client")?))
+}
+
+fn random_nonce(rng: &rand::SystemRandom) -> Result<[u8; 32]> {
+    let mut nonce = [0u8; 32];
+    rand::SecureRandom::fill(rng, &mut nonce).map_err(|_| NetworkError::UnknownError)?;
+    Ok(nonce)
+}
+
+fn ephemeral_key(
+    rng: &rand::SystemRandom,
+) -> Result<(agreement::EphemeralPrivateKey, agreement::PublicKey)> {
+    let private = agreement::EphemeralPrivateKey::generate(&agreement::X25519, rng)
+        .map_err(|_| NetworkError::UnknownError)?;
+    let public = private
+        .compute_public_key()
+        .map_err(|_| NetworkError::UnknownError)?;
+    Ok((private, public))
+}
+
+fn agree(private: agreement::EphemeralPrivateKey, peer: &[u8]) -> Result<Vec<u8>> {
+    agreement::agree_ephemeral(
+        private,
+        &agreement::UnparsedPublicKey::new(&agreement::X25519, peer),
+        |shared| shared.to_vec(),
+    )
+    .map_err(|_| NetworkError::InvalidData)
+}
+
+async fn write_handshake(tx: &mut BoxedWrite, msg: &impl Serialize) -> Result<Vec<u8>> {
+    let data = bincode::serialize(msg).map_err(|_| NetworkError::InvalidData)?;
+    tx.write_all(&(data.len() as u16).to_be_bytes())
+        .await
+        .map_err(io_err_into_net_error)?;
+    tx.write_all(&data).await.map_err(io_err_into_net_error)?;
+    tx.flush().await.map_err(io_err_into_net_error)?;
+    Ok(data)
+}
+
+async fn read_handshake(rx: &mut BoxedRead) -> Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    rx.read_exact(&mut len)
+        .await
+        .map_err(io_err_into_net_error)?;
+    let len = u16::from_be_bytes(len) as usize;
+    if len > MAX_HANDSHAKE_LEN {
+        return Err(NetworkError::InvalidData);
+    }
+    let mut data = vec![0u8; len];
+    rx.read_exact(&mut data)
+        .await
+        .map_err(io_err_into_net_error)?;
+    Ok(data)
+}
+
+fn decode<'a, T: Deserialize<'a>>(data: &'a [u8]) -> Result<T> {
+    bincode::deserialize(data).map_err(|_| NetworkError::InvalidData)
+}
+
+/// Runs the client side of the handshake and returns the encrypted halves
+/// of the stream
+pub(crate) async fn client_handshake<TX, RX>(
+    tx: TX,
+    rx: RX,
+    credentials: &RemoteCredentials,
+) -> Result<(SecureWriter, SecureReader)>
+where
+    TX: AsyncWrite + Send + 'static,
+    RX: AsyncRead + Send + 'static,
+{
+    let mut tx: BoxedWrite = Box::pin(tx);
+    let mut rx: BoxedRead = Box::pin(rx);
+    let rng = rand::SystemRandom::new();
+
+    let (private, public) = ephemeral_key(&rng)?;
+    let hello = write_handshake(
+        &mut tx,
+        &ClientHello {
+            magic: MAGIC,
+            version: PROTOCOL_VERSION,
+            client_id: credentials.client_id.clone(),
+            public_key: public.as_ref().to_vec(),
+            nonce: random_nonce(&rng)?,
+        },
+    )
+    .await?;
+
+    let (version, public_key, nonce, mac) = match decode(&read_handshake(&mut rx).await?)? {
+        ServerHello::Accept {
+            version,
+            public_key,
+            nonce,
+            mac,
+        } => (version, public_key, nonce, mac),
+        ServerHello::Reject { reason } => {
+            tracing::warn!(%reason, "remote networking server rejected the handshake");
+            return Err(NetworkError::PermissionDenied);
+        }
+    };
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        return Err(NetworkError::Unsupported);
+    }
+
+    let transcript = transcript(&hello, version, &public_key, &nonce);
+    verify_mac(&credentials.key, b"server", &transcript, &mac)?;
+    let mac = handshake_mac(&credentials.key, b"client", &transcript);
+    write_handshake(
+        &mut tx,
+        &ClientFinished {
+            mac: mac.as_ref().to_vec(),
+        },
+    )
+    .await?;
+
+    let shared = agree(private, &public_key)?;
+    let (client_key, server_key) = traffic_keys(&credentials.key, &shared, &transcript)?;
+    Ok((
+        SecureWriter::new(tx, &client_key)?,
+        SecureReader::new(rx, &server_key)?,
+    ))
+}
+
+/// Runs the server side of the handshake and returns the encrypted halves
+/// of the stream along with the ruleset of the client that connected
+pub(crate) async fn server_handshake<TX, RX>(
+    tx: TX,
+    rx: RX,
+    authenticator: &RemoteAuthenticator,
+) -> Result<(SecureWriter, SecureReader, Option<Ruleset>)>
+where
+    TX: AsyncWrite + Send + 'static,
+    RX: AsyncRead + Send + 'static,
+{
+    let mut tx: BoxedWrite = Box::pin(tx);
+    let mut rx: BoxedRead = Box::pin(rx);
+    let rng = rand::SystemRandom::new();
+
+    let hello_data = read_handshake(&mut rx).await?;
+    let hello: ClientHello = decode(&hello_data)?;
+    if hello.magic != MAGIC {
+        return Err(NetworkError::InvalidData);
+    }
+
+    let reject = |reason: &str| ServerHello::Reject {
+        reason: reason.to_string(),
+    };
+    if hello.version < MIN_PROTOCOL_VERSION {
+        write_handshake(&mut tx, &reject("unsupported protocol version")).await?;
+        return Err(NetworkError::Unsupported);
+    }
+    let Some(client) = authenticator.clients.get(&hello.client_id) else {
+        tracing::warn!(client_id = %hello.client_id, "unknown remote networking client");
+        write_handshake(&mut tx, &reject("authentication failed")).await?;
+        return Err(NetworkError::PermissionDenied);
+    };
+
+    let version = hello.version.min(PROTOCOL_VERSION);
+    let (private, public) = ephemeral_key(&rng)?;
+    let nonce = random_nonce(&rng)?;
+    let transcript = transcript(&hello_data, version, public.as_ref(), &nonce);
+    let mac = handshake_mac(&client.key, b"server", &transcript);
+    write_handshake(
+        &mut tx,
+        &ServerHello::Accept {
+            version,
+            public_key: public.as_ref().to_vec(),
+            nonce,
+            mac: mac.as_ref().to_vec(),
+        },
+    )
+    .await?;
+
+    let finished: ClientFinished = decode(&read_handshake(&mut rx).await?)?;
+    if let Err(err) = verify_mac(&client.key, b"client", &transcript, &finished.mac) {
+        tracing::warn!(client_id = %hello.client_id, "remote networking client failed to authenticate");
+        return Err(err);
+    }
+
+    let shared = agree(private, &hello.public_key)?;
+    let (client_key, server_key) = traffic_keys(&client.key, &shared, &transcript)?;
+    Ok((
+        SecureWriter::new(tx, &server_key)?,
+        SecureReader::new(rx, &client_key)?,
+        client.ruleset.clone(),
+    ))
+}
+
+fn record_key(key: &[u8]) -> Result<aead::LessSafeKey> {
+    let key = aead::UnboundKey::new(&aead::CHACHA20_POLY1305, key)
+        .map_err(|_| NetworkError::UnknownError)?;
+    Ok(aead::LessSafeKey::new(key))
+}
+
+/// Every record is sealed with its own nonce, made from a counter that is
+/// never reused for the same key
+fn record_nonce(counter: &mut u64) -> io::Result<aead::Nonce> {
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    *counter = counter
+        .checked_add(1)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "record counter exhausted"))?;
+    Ok(aead::Nonce::assume_unique_for_key(nonce))
+}
+
+/// Writing half of an encrypted stream, each write becomes a record made of
+/// its length followed by the sealed data, shutting it down sends a close
+/// record
+pub struct SecureWriter {
+    inner: BoxedWrite,
+    key: aead::LessSafeKey,
+    counter: u64,
+    pending: BytesMut,
+    closed: bool,
+}
+
+impl SecureWriter {
+    fn new(inner: BoxedWrite, key: &[u8]) -> Result<Self> {
+        Ok(Self {
+            inner,
+            key: record_key(key)?,
+            counter: 0,
+            pending: BytesMut::new(),
+            closed: false,
+        })
+    }
+
+    /// Seals a record and queues it to be sent
+    fn seal_record(&mut self, data: &[u8], flags: u32) -> io::Result<()> {
+        let mut record = data.to_vec();
+        let sealed_len = (data.len() + aead::CHACHA20_POLY1305.tag_len()) as u32;
+        let header = (sealed_len | flags).to_be_bytes();
+        let nonce = record_nonce(&mut self.counter)?;
+        self.key
+            .seal_in_place_append_tag(nonce, aead::Aad::from(header), &mut record)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "failed to seal record"))?;
+        self.pending.extend_from_slice(&header);
+        self.pending.extend_from_slice(&record);
+        Ok(())
+    }
+
+    /// Writes the records that were sealed but not yet sent
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let written = ready!(self.inner.as_mut().poll_write(cx, &self.pending))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.advance(written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl fmt::Debug for SecureWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureWriter")
+            .field("counter", &self.counter)
+            .field("pending", &self.pending.len())
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncWrite for SecureWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        ready!(this.poll_pending(cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let len = buf.len().min(MAX_RECORD_LEN);
+        this.seal_record(&buf[..len], 0)?;
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        this.inner.as_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.closed {
+            ready!(this.poll_pending(cx))?;
+            this.seal_record(&[], CLOSE_RECORD_FLAG)?;
+            this.closed = true;
+        }
+        ready!(this.poll_pending(cx))?;
+        this.inner.as_mut().poll_shutdown(cx)
+    }
+}
+
+/// Reading half of an encrypted stream, records that fail to open end the
+/// stream with an error as does a stream that ends without a close record
+/// (which could otherwise be truncated unnoticed)
+pub struct SecureReader {
+    inner: BoxedRead,
+    key: aead::LessSafeKey,
+    counter: u64,
+    sealed: BytesMut,
+    plain: BytesMut,
+    closed: bool,
+}
+
+impl SecureReader {
+    fn new(inner: BoxedRead, key: &[u8]) -> Result<Self> {
+        Ok(Self {
+            inner,
+            key: record_key(key)?,
+            counter: 0,
+            sealed: BytesMut::new(),
+            plain: BytesMut::new(),
+            closed: false,
+        })
+    }
+
+    /// Opens the next record if it was received in full
+    fn open_record(&mut self) -> io::Result<bool> {
+        if self.sealed.len() < 4 {
+            return Ok(false);
+        }
+        let header: [u8; 4] = self.sealed[..4].try_into().unwrap();
+        let header_value = u32::from_be_bytes(header);
+        let close = header_value & CLOSE_RECORD_FLAG != 0;
+        let len = (header_value & !CLOSE_RECORD_FLAG) as usize;
+        if len > MAX_RECORD_LEN + aead::CHACHA20_POLY1305.tag_len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "record is too large",
+            ));
+        }
+        if self.sealed.len() < 4 + len {
+            return Ok(false);
+        }
+
+        self.sealed.advance(4);
+        let mut record = self.sealed.split_to(len);
+        let nonce = record_nonce(&mut self.counter)?;
+        let plain = self
+            .key
+            .open_in_place(nonce, aead::Aad::from(header), &mut record)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "failed to open record"))?;
+        if close {
+            if !plain.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "close record carries data",
+                ));
+            }
+            self.closed = true;
+        }
+        self.plain.extend_from_slice(plain);
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for SecureReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureReader")
+            .field("counter", &self.counter)
+            .field("buffered", &self.plain.len())
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncRead for SecureReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if !this.plain.is_empty() {
+                let len = this.plain.len().min(buf.remaining());
+                buf.put_slice(&this.plain.split_to(len));
+                return Poll::Ready(Ok(()));
+            }
+            if this.closed {
+                return Poll::Ready(Ok(()));
+            }
+            if this.open_record()? {
+                continue;
+            }
+
+            let mut chunk = [0u8; 8192];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            ready!(this.inner.as_mut().poll_read(cx, &mut chunk_buf))?;
+            if chunk_buf.filled().is_empty() {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "stream ended without a close record",
+                )));
+            }
+            this.sealed.extend_from_slice(chunk_buf.filled());
+        }
+    }
+}
+
+/// What a websocket message means to the byte stream that is carried over
+/// the websocket
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+pub(crate) enum WebSocketPayload {
+    Data(Vec<u8>),
+    Control,
+    Close,
+}
+
+/// A websocket message type that the encrypted stream can be carried in
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+pub(crate) trait WebSocketMessage: Sized {
+    fn binary(data: Vec<u8>) -> Self;
+    fn into_payload(self) -> WebSocketPayload;
+}
+
+#[cfg(feature = "hyper")]
+impl WebSocketMessage for hyper_tungstenite::tungstenite::Message {
+    fn binary(data: Vec<u8>) -> Self {
+        Self::Binary(data)
+    }
+
+    fn into_payload(self) -> WebSocketPayload {
+        match self {
+            Self::Binary(data) => WebSocketPayload::Data(data),
+            Self::Close(_) => WebSocketPayload::Close,
+            _ => WebSocketPayload::Control,
+        }
+    }
+}
+
+#[cfg(feature = "tokio-tungstenite")]
+impl WebSocketMessage for tokio_tungstenite::tungstenite::Message {
+    fn binary(data: Vec<u8>) -> Self {
+        Self::Binary(data)
+    }
+
+    fn into_payload(self) -> WebSocketPayload {
+        match self {
+            Self::Binary(data) => WebSocketPayload::Data(data),
+            Self::Close(_) => WebSocketPayload::Close,
+            _ => WebSocketPayload::Control,
+        }
+    }
+}
+
+/// Turns the sending half of a websocket into a byte stream so that the
+/// handshake and the records can be sent over it, every write becomes a
+/// binary message
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+pub(crate) struct WebSocketWriter<S, M> {
+    inner: S,
+    _message: std::marker::PhantomData<fn(M)>,
+}
+
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+impl<S, M> WebSocketWriter<S, M> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            _message: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+impl<S, M> AsyncWrite for WebSocketWriter<S, M>
+where
+    S: futures_util::Sink<M> + Unpin,
+    S::Error: std::error::Error + Send + Sync + 'static,
+    M: WebSocketMessage,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        ready!(Pin::new(&mut this.inner).poll_ready(cx)).map_err(websocket_err)?;
+        Pin::new(&mut this.inner)
+            .start_send(M::binary(buf.to_vec()))
+            .map_err(websocket_err)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll_flush(cx)
+            .map_err(websocket_err)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll_close(cx)
+            .map_err(websocket_err)
+    }
+}
+
+/// Turns the receiving half of a websocket into a byte stream made of the
+/// binary messages, control messages are skipped and a close message ends
+/// the stream
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+pub(crate) struct WebSocketReader<S> {
+    inner: S,
+    pending: BytesMut,
+    closed: bool,
+}
+
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+impl<S> WebSocketReader<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            pending: BytesMut::new(),
+            closed: false,
+        }
+    }
+}
+
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+impl<S, M, E> AsyncRead for WebSocketReader<S>
+where
+    S: futures_util::Stream<Item = std::result::Result<M, E>> + Unpin,
+    M: WebSocketMessage,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if !this.pending.is_empty() {
+                let len = this.pending.len().min(buf.remaining());
+                buf.put_slice(&this.pending.split_to(len));
+                return Poll::Ready(Ok(()));
+            }
+            if this.closed {
+                return Poll::Ready(Ok(()));
+            }
+
+            match ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                Some(Ok(msg)) => match msg.into_payload() {
+                    WebSocketPayload::Data(data) => this.pending.extend_from_slice(&data),
+                    WebSocketPayload::Control => {}
+                    WebSocketPayload::Close => this.closed = true,
+                },
+                Some(Err(err)) => return Poll::Ready(Err(websocket_err(err))),
+                None => this.closed = true,
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "hyper", feature = "tokio-tungstenite"))]
+fn websocket_err(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::meta::FrameSerializationFormat;
+    use crate::{
+        LoopbackNetworking, RemoteNetworkingClient, RemoteNetworkingServer, VirtualNetworking,
+    };
+
+    fn authenticator() -> RemoteAuthenticator {
+        let mut auth = RemoteAuthenticator::new();
+        auth.add_client("alice", "alice-key", None);
+        auth.add_client(
+            "bob",
+            "bob-key",
+            Some(Ruleset::from_str("ipv4:allow=127.0.0.1:80/out").unwrap()),
+        );
+        auth
+    }
+
+    async fn handshake(
+        credentials: RemoteCredentials,
+    ) -> (
+        Result<(SecureWriter, SecureReader)>,
+        Result<(SecureWriter, SecureReader, Option<Ruleset>)>,
+    ) {
+        let (client, server) = tokio::io::duplex(1024);
+        let (client_rx, client_tx) = tokio::io::split(client);
+        let (server_rx, server_tx) = tokio::io::split(server);
+
+        let server =
+            tokio::spawn(
+                async move { server_handshake(server_tx, server_rx, &authenticator()).await },
+            );
+        let client = client_handshake(client_tx, client_rx, &credentials).await;
+        (client, server.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_and_records() {
+        let (client, server) = handshake(RemoteCredentials::new("alice", "alice-key")).await;
+        let (mut client_tx, mut client_rx) = client.unwrap();
+        let (mut server_tx, mut server_rx, ruleset) = server.unwrap();
+        assert!(ruleset.is_none());
+
+        // Larger than a record so that it gets split
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let sent = data.clone();
+        let writer = tokio::spawn(async move {
+            client_tx.write_all(&sent).await.unwrap();
+            client_tx.shutdown().await.unwrap();
+        });
+        let mut received = Vec::new();
+        server_rx.read_to_end(&mut received).await.unwrap();
+        writer.await.unwrap();
+        assert_eq!(received, data);
+
+        server_tx.write_all(b"pong").await.unwrap();
+        server_tx.flush().await.unwrap();
+        let mut pong = [0u8; 4];
+        client_rx.read_exact(&mut pong).await.unwrap();
+        assert_eq!(&pong, b"pong");
+    }
+
+    #[cfg(feature = "tokio-tungstenite")]
+    #[tokio::test]
+    async fn test_handshake_over_websocket() {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::{tungstenite::protocol::Role, WebSocketStream};
+
+        let (client, server) = tokio::io::duplex(1024);
+        let (client_tx, client_rx) = WebSocketStream::from_raw_socket(client, Role::Client, None)
+            .await
+            .split();
+        let (server_tx, server_rx) = WebSocketStream::from_raw_socket(server, Role::Server, None)
+            .await
+            .split();
+
+        let server = tokio::spawn(async move {
+            server_handshake(
+                WebSocketWriter::new(server_tx),
+                WebSocketReader::new(server_rx),
+                &authenticator(),
+            )
+            .await
+        });
+        let (mut client_tx, _client_rx) = client_handshake(
+            WebSocketWriter::new(client_tx),
+            WebSocketReader::new(client_rx),
+            &RemoteCredentials::new("alice", "alice-key"),
+        )
+        .await
+        .unwrap();
+        let (_server_tx, mut server_rx, _) = server.await.unwrap().unwrap();
+
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let sent = data.clone();
+        let writer = tokio::spawn(async move {
+            client_tx.write_all(&sent).await.unwrap();
+            client_tx.shutdown().await.unwrap();
+        });
+        let mut received = Vec::new();
+        server_rx.read_to_end(&mut received).await.unwrap();
+        writer.await.unwrap();
+        assert_eq!(received, data);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_bad_credentials() {
+        let (client, server) = handshake(RemoteCredentials::new("alice", "wrong-key")).await;
+        assert_eq!(client.unwrap_err(), NetworkError::PermissionDenied);
+        assert!(server.is_err());
+
+        let (client, server) = handshake(RemoteCredentials::new("mallory", "alice-key")).await;
+        assert_eq!(client.unwrap_err(), NetworkError::PermissionDenied);
+        assert_eq!(server.unwrap_err(), NetworkError::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_tampered_record() {
+        let (client, server) = handshake(RemoteCredentials::new("alice", "alice-key")).await;
+        let (mut client_tx, _client_rx) = client.unwrap();
+        let (_server_tx, mut server_rx, _) = server.unwrap();
+
+        client_tx.write_all(b"hello").await.unwrap();
+        client_tx.flush().await.unwrap();
+        let mut hello = [0u8; 5];
+        server_rx.read_exact(&mut hello).await.unwrap();
+
+        // A forged record fails to open and ends the stream
+        let header = ((5 + aead::CHACHA20_POLY1305.tag_len()) as u32).to_be_bytes();
+        server_rx.sealed.extend_from_slice(&header);
+        server_rx.sealed.extend_from_slice(&[0u8; 21]);
+        let err = server_rx.read_exact(&mut hello).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_stream() {
+        let (client, server) = handshake(RemoteCredentials::new("alice", "alice-key")).await;
+        let (mut client_tx, client_rx) = client.unwrap();
+        let (_server_tx, mut server_rx, _) = server.unwrap();
+
+        // The connection is cut without the close record
+        client_tx.write_all(b"hello").await.unwrap();
+        client_tx.flush().await.unwrap();
+        drop(client_tx);
+        drop(client_rx);
+
+        let mut received = Vec::new();
+        let err = server_rx.read_to_end(&mut received).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(received, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_forged_close_record() {
+        let (client, server) = handshake(RemoteCredentials::new("alice", "alice-key")).await;
+        let (_client_tx, _client_rx) = client.unwrap();
+        let (_server_tx, mut server_rx, _) = server.unwrap();
+
+        let header = (aead::CHACHA20_POLY1305.tag_len() as u32 | CLOSE_RECORD_FLAG).to_be_bytes();
+        server_rx.sealed.extend_from_slice(&header);
+        server_rx
+            .sealed
+            .extend_from_slice(&[0u8; aead::MAX_TAG_LEN]);
+        let mut received = Vec::new();
+        let err = server_rx.read_to_end(&mut received).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_client_ruleset_is_enforced() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (client_rx, client_tx) = tokio::io::split(client);
+        let (server_rx, server_tx) = tokio::io::split(server);
+
+        let server = tokio::spawn(async move {
+            let (_server, driver) = RemoteNetworkingServer::new_from_secure_async_io(
+                server_tx,
+                server_rx,
+                FrameSerializationFormat::Bincode,
+                Arc::new(LoopbackNetworking::new()),
+                &authenticator(),
+            )
+            .await
+            .unwrap();
+            driver.await;
+        });
+        let (client, driver) = RemoteNetworkingClient::new_from_secure_async_io(
+            client_tx,
+            client_rx,
+            FrameSerializationFormat::Bincode,
+            &RemoteCredentials::new("bob", "bob-key"),
+        )
+        .await
+        .unwrap();
+        tokio::spawn(driver);
+
+        let local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        assert_eq!(
+            client
+                .connect_tcp(local, "10.0.0.1:80".parse().unwrap())
+                .await
+                .unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+        assert_eq!(
+            client.resolve("example.com", None, None).await.unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+        // Changing the network around the ruleset is never allowed
+        assert_eq!(
+            client.dhcp_acquire().await.unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+        // Allowed by the ruleset, so the request reaches the loopback
+        // networking which does not support it
+        assert_eq!(
+            client
+                .connect_tcp(local, "127.0.0.1:80".parse().unwrap())
+                .await
+                .unwrap_err(),
+            NetworkError::Unsupported
+        );
+
+        drop(client);
+        server.abort();
+    }
+}
@@ -1,4 +1,5 @@
 use crate::meta::{FrameSerializationFormat, ResponseType};
+use crate::ruleset::{Direction, Ruleset};
 use crate::rx_tx::{RemoteRx, RemoteTx, RemoteTxWakers};
 use crate::{
     meta::{MessageRequest, MessageResponse, RequestType, SocketId},
@@ -56,6 +57,7 @@ impl RemoteNetworkingServer {
         rx: RemoteRx<MessageRequest>,
         work: mpsc::UnboundedReceiver<BoxFuture<'static, ()>>,
         inner: Arc<dyn VirtualNetworking + Send + Sync + 'static>,
+        ruleset: Option<Ruleset>,
     ) -> (Self, RemoteNetworkingServerDriver) {
         let common = RemoteAdapterCommon {
             tx,
@@ -72,6 +74,7 @@ impl RemoteNetworkingServer {
             tasks: Default::default(),
             common: common.clone(),
             inner: inner.clone(),
+            ruleset,
         };
         let networking = Self { common, inner };
 
@@ -96,7 +99,7 @@ impl RemoteNetworkingServer {
             rx,
             wakers: tx_wakers,
         };
-        Self::new(tx, rx, rx_work, inner)
+        Self::new(tx, rx, rx_work, inner, None)
     }
 
     /// Creates a new interface on the remote location using
@@ -161,7 +164,30 @@ impl RemoteNetworkingServer {
             wakers: RemoteTxWakers::default(),
         };
         let rx = RemoteRx::Stream { rx };
-        Self::new(tx, rx, rx_work, inner)
+        Self::new(tx, rx, rx_work, inner, None)
+    }
+
+    /// Authenticates the client on the other end of the stream with
+    /// `authenticator` and then serves it over an encrypted channel
+    ///
+    /// The requests of the client are checked against the ruleset that
+    /// was registered for it, see [`crate::secure`] for the details
+    #[cfg(feature = "remote-secure")]
+    pub async fn new_from_secure_async_io<TX, RX>(
+        tx: TX,
+        rx: RX,
+        format: FrameSerializationFormat,
+        inner: Arc<dyn VirtualNetworking + Send + Sync + 'static>,
+        authenticator: &crate::secure::RemoteAuthenticator,
+    ) -> Result<(Self, RemoteNetworkingServerDriver), NetworkError>
+    where
+        TX: AsyncWrite + Send + 'static,
+        RX: AsyncRead + Send + 'static,
+    {
+        let (tx, rx, ruleset) = crate::secure::server_handshake(tx, rx, authenticator).await?;
+        let (networking, mut driver) = Self::new_from_async_io(tx, rx, format, inner);
+        driver.ruleset = ruleset;
+        Ok((networking, driver))
     }
 
     /// Creates a new interface on the remote location using
@@ -185,7 +211,31 @@ impl RemoteNetworkingServer {
             format,
         };
         let rx = RemoteRx::HyperWebSocket { rx, format };
-        Self::new(tx, rx, rx_work, inner)
+        Self::new(tx, rx, rx_work, inner, None)
+    }
+
+    /// Authenticates the client on the other end of the websocket with
+    /// `authenticator` and then serves it over an encrypted channel, the
+    /// same way as [`Self::new_from_secure_async_io`]
+    #[cfg(all(feature = "hyper", feature = "remote-secure"))]
+    pub async fn new_from_secure_hyper_ws_io(
+        tx: SplitSink<
+            hyper_tungstenite::WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+            hyper_tungstenite::tungstenite::Message,
+        >,
+        rx: SplitStream<hyper_tungstenite::WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>>,
+        format: FrameSerializationFormat,
+        inner: Arc<dyn VirtualNetworking + Send + Sync + 'static>,
+        authenticator: &crate::secure::RemoteAuthenticator,
+    ) -> Result<(Self, RemoteNetworkingServerDriver), NetworkError> {
+        Self::new_from_secure_async_io(
+            crate::secure::WebSocketWriter::new(tx),
+            crate::secure::WebSocketReader::new(rx),
+            format,
+            inner,
+            authenticator,
+        )
+        .await
     }
 }
 
@@ -313,6 +363,7 @@ pin_project_lite::pin_project! {
         #[pin]
         tasks: FuturesOrdered<BoxFuture<'static, ()>>,
         inner: Arc<dyn VirtualNetworking + Send + Sync + 'static>,
+        ruleset: Option<Ruleset>,
     }
 }
 
@@ -335,11 +386,12 @@ impl Future for RemoteNetworkingServerDriver {
             // When a socket is marked as readable then we should drain all the data
             // from it and start sending it to the client
             let common = self.common.clone();
+            let ruleset = self.ruleset.clone();
             let mut guard = common.sockets.lock().unwrap();
             for socket_id in readable {
                 if let Some(task) = guard
                     .get_mut(&socket_id)
-                    .map(|s| s.drain_reads_and_accepts(&common, socket_id))
+                    .map(|s| s.drain_reads_and_accepts(&common, ruleset.as_ref(), socket_id))
                     .unwrap_or(None)
                 {
                     self.tasks.push_back(task);
@@ -446,6 +498,13 @@ impl RemoteNetworkingServerDriver {
         addr: SocketAddr,
         req_id: Option<u64>,
     ) -> BackgroundTask {
+        if let Some(ruleset) = self.ruleset.as_ref() {
+            if !ruleset.allows_socket(addr, Direction::Outbound) {
+                tracing::debug!(%addr, "datagram blocked by the client ruleset");
+                return None;
+            }
+        }
+
         let mut guard = self.common.sockets.lock().unwrap();
         guard
             .get_mut(&socket_id)
@@ -644,7 +703,42 @@ impl RemoteNetworkingServerDriver {
         )
     }
 
+    /// Returns `false` if the ruleset of the client does not allow the
+    /// request, when there is a ruleset only the requests that were vetted
+    /// against it are allowed (raw and ICMP sockets would bypass the rules
+    /// so they are never allowed)
+    fn allows_request(&self, req: &RequestType) -> bool {
+        let Some(ruleset) = self.ruleset.as_ref() else {
+            return true;
+        };
+        match req {
+            RequestType::BindRaw(_) | RequestType::BindIcmp { .. } => false,
+            RequestType::ListenTcp { addr, .. } | RequestType::BindUdp { addr, .. } => {
+                ruleset.allows_socket(*addr, Direction::Inbound)
+            }
+            RequestType::ConnectTcp { peer, .. } => {
+                ruleset.allows_socket(*peer, Direction::Outbound)
+            }
+            RequestType::Resolve { host, .. } => ruleset.allows_domain(host),
+            // Queries that do not change the network or open anything
+            RequestType::GetIpList | RequestType::GetMac | RequestType::GetRouteList => true,
+            // Everything else (bridging, addresses, routes, DHCP...) would
+            // let the client reshape the network around the rules
+            _ => false,
+        }
+    }
+
     fn process_interface(&mut self, req: RequestType, req_id: Option<u64>) -> BackgroundTask {
+        if !self.allows_request(&req) {
+            tracing::debug!(?req, "request blocked by the client ruleset");
+            return req_id.and_then(|req_id| {
+                self.common.send(MessageResponse::ResponseToRequest {
+                    req_id,
+                    res: ResponseType::Err(NetworkError::PermissionDenied),
+                })
+            });
+        }
+
         match req {
             RequestType::Bridge {
                 network,
@@ -811,16 +905,27 @@ impl RemoteNetworkingServerDriver {
                 host,
                 port,
                 dns_server,
-            } => self.process_async_inner(
-                move |inner: Arc<dyn VirtualNetworking + Send + Sync>| async move {
-                    inner.resolve(&host, port, dns_server).await
-                },
-                |ret| match ret {
-                    Ok(ips) => ResponseType::IpAddressList(ips),
-                    Err(err) => ResponseType::Err(err),
-                },
-                req_id,
-            ),
+            } => {
+                let ruleset = self.ruleset.clone();
+                self.process_async_inner(
+                    move |inner: Arc<dyn VirtualNetworking + Send + Sync>| async move {
+                        let addrs = inner.resolve(&host, port, dns_server).await?;
+                        // Connections to the addresses of an allowed domain
+                        // are allowed as well
+                        if let Some(ruleset) = ruleset {
+                            if let Err(err) = ruleset.expand_domain(&host, &addrs) {
+                                tracing::debug!(%err, "ruleset expansion failed");
+                            }
+                        }
+                        Ok(addrs)
+                    },
+                    |ret| match ret {
+                        Ok(ips) => ResponseType::IpAddressList(ips),
+                        Err(err) => ResponseType::Err(err),
+                    },
+                    req_id,
+                )
+            }
             _ => req_id.and_then(|req_id| {
                 self.common.send(MessageResponse::ResponseToRequest {
                     req_id,
@@ -1404,6 +1509,7 @@ impl RemoteAdapterSocket {
     pub fn drain_reads_and_accepts(
         &mut self,
         common: &Arc<RemoteAdapterCommon>,
+        ruleset: Option<&Ruleset>,
         socket_id: SocketId,
     ) -> BackgroundTask {
         // We loop reading the socket until all the pending reads are either
@@ -1417,6 +1523,16 @@ impl RemoteAdapterSocket {
                 } => {
                     if next_accept.is_some() {
                         match socket.try_accept() {
+                            Ok((_, addr))
+                                if ruleset.is_some_and(|r| {
+                                    !r.allows_socket(addr, Direction::Inbound)
+                                }) =>
+                            {
+                                // The connection is dropped and the accept stays
+                                // pending for the next peer
+                                tracing::debug!(%addr, "connection blocked by the client ruleset");
+                                continue;
+                            }
                             Ok((mut child_socket, addr)) => {
                                 let child_id = next_accept.take().unwrap();
 
@@ -1478,6 +1594,13 @@ impl RemoteAdapterSocket {
                         unsafe { MaybeUninit::uninit().assume_init() };
                     match this.try_recv_from(&mut chunk, false) {
                         Ok((0, _)) => {}
+                        Ok((_, addr))
+                            if ruleset
+                                .is_some_and(|r| !r.allows_socket(addr, Direction::Inbound)) =>
+                        {
+                            tracing::debug!(%addr, "datagram blocked by the client ruleset");
+                            continue;
+                        }
                         Ok((amt, addr)) => {
                             let chunk_unsafe: &mut [MaybeUninit<u8>] = &mut chunk[..amt];
                             let chunk_unsafe: &mut [u8] =
@@ -171,6 +171,26 @@ impl SocketBuffer {
         state.state
     }
 
+    /// Creates the two buffers of a duplex channel, the channel is broken
+    /// as soon as either of its ends is dropped
+    pub(crate) fn duplex(max_size: usize) -> (SocketBuffer, SocketBuffer) {
+        let mut buffer1 = SocketBuffer::new(max_size);
+        buffer1.dead_on_drop = true;
+
+        let mut buffer2 = SocketBuffer::new(max_size);
+        buffer2.dead_on_drop = true;
+
+        (buffer1, buffer2)
+    }
+
+    pub(crate) fn is_alive(&self) -> bool {
+        self.state() == State::Alive
+    }
+
+    pub(crate) fn shutdown(&self) {
+        self.set_state(State::Shutdown);
+    }
+
     pub fn try_send(
         &self,
         data: &[u8],
@@ -356,11 +376,7 @@ impl TcpSocketHalf {
         addr1: SocketAddr,
         addr2: SocketAddr,
     ) -> (TcpSocketHalf, TcpSocketHalf) {
-        let mut buffer1 = SocketBuffer::new(max_buffer_size);
-        buffer1.dead_on_drop = true;
-
-        let mut buffer2 = SocketBuffer::new(max_buffer_size);
-        buffer2.dead_on_drop = true;
+        let (buffer1, buffer2) = SocketBuffer::duplex(max_buffer_size);
 
         let half1 = Self {
             tx: buffer1.clone(),
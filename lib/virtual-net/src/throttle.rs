@@ -0,0 +1,1221 @@
+//! Bandwidth and connection limits for a [`VirtualNetworking`] implementation.
+//!
+//! [`ThrottledNetworking`] wraps another implementation and puts every socket
+//! it hands out behind a set of shared token buckets. A socket that ran out of
+//! budget reports [`NetworkError::WouldBlock`] (or [`Poll::Pending`] from the
+//! readiness polls) and its handler is woken again once the bucket refilled,
+//! which means guests experience backpressure rather than errors.
+use std::future::poll_fn;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use virtual_mio::{ArcInterestHandler, InterestHandler, InterestType};
+
+use crate::{
+    IpCidr, IpRoute, NetworkError, Result, SocketStatus, StreamSecurity, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualIoSource, VirtualNetworking,
+    VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualUnixDatagramSocket, VirtualUnixListener, VirtualUnixSocket,
+};
+
+/// How long the timer thread sleeps when it has nothing to do, before it
+/// checks whether the networking it belongs to is still alive
+const TIMER_IDLE: Duration = Duration::from_secs(1);
+
+/// Limits that are shared by all the sockets of a [`ThrottledNetworking`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThrottleLimits {
+    /// Maximum number of bytes per second that may be sent
+    pub upload_bytes_per_sec: Option<u64>,
+    /// Maximum number of bytes per second that may be received
+    pub download_bytes_per_sec: Option<u64>,
+    /// Maximum number of TCP, UDP, raw and ICMP sockets (including
+    /// listeners) that may be open at the same time, opening another one
+    /// waits until one of them is closed
+    pub max_sockets: Option<usize>,
+    /// Maximum number of new connections per second, which covers both
+    /// outbound connects and accepted inbound connections
+    pub connections_per_sec: Option<u32>,
+}
+
+impl ThrottleLimits {
+    /// Returns true if none of the limits are set
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Wraps a [`VirtualNetworking`] implementation and enforces a set of
+/// [`ThrottleLimits`] on the sockets it creates.
+///
+/// Unix domain sockets never leave the host and are passed through as is.
+#[derive(Debug, Clone)]
+pub struct ThrottledNetworking<N> {
+    inner: N,
+    state: Arc<ThrottleState>,
+}
+
+impl<N: VirtualNetworking> ThrottledNetworking<N> {
+    pub fn new(inner: N, limits: ThrottleLimits) -> Self {
+        Self {
+            inner,
+            state: Arc::new(ThrottleState::new(limits)),
+        }
+    }
+
+    /// Returns the limits that are enforced by this networking
+    pub fn limits(&self) -> ThrottleLimits {
+        self.state.limits
+    }
+
+    /// Returns the networking implementation that is being throttled
+    pub fn inner(&self) -> &N {
+        &self.inner
+    }
+
+    /// Returns the number of throttled sockets that are currently open
+    pub fn open_sockets(&self) -> usize {
+        self.state.open_sockets.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl<N: VirtualNetworking> VirtualNetworking for ThrottledNetworking<N> {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let permit = self.state.open_socket().await;
+        let socket = self.inner.bind_raw().await?;
+        Ok(Box::new(ThrottledRawSocket {
+            inner: socket,
+            throttle: SocketThrottle::new(permit),
+        }))
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let permit = self.state.open_socket().await;
+        let listener = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(ThrottledTcpListener {
+            inner: listener,
+            throttle: SocketThrottle::new(permit),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let permit = self.state.open_socket().await;
+        let socket = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(ThrottledUdpSocket {
+            inner: socket,
+            throttle: SocketThrottle::new(permit),
+        }))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let permit = self.state.open_socket().await;
+        let socket = self.inner.bind_icmp(addr).await?;
+        Ok(Box::new(ThrottledIcmpSocket {
+            inner: socket,
+            throttle: SocketThrottle::new(permit),
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let permit = self.state.open_socket().await;
+        poll_fn(|cx| match self.state.try_take_connection() {
+            Ok(()) => Poll::Ready(()),
+            Err(when) => {
+                self.state
+                    .timer
+                    .call_at(when, Wakeup::Waker(cx.waker().clone()));
+                Poll::Pending
+            }
+        })
+        .await;
+        let socket = self.inner.connect_tcp(addr, peer).await?;
+        Ok(Box::new(ThrottledTcpSocket {
+            inner: socket,
+            throttle: SocketThrottle::new(permit),
+        }))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixListener + Sync>> {
+        self.inner.listen_unix(path).await
+    }
+
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        self.inner.connect_unix(path).await
+    }
+
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        self.inner.bind_unix_datagram(path).await
+    }
+}
+
+/// Token bucket that holds at most one second worth of tokens
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        let rate = rate.max(1) as f64;
+        Self {
+            rate,
+            tokens: rate,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last = now;
+    }
+
+    /// Returns true if at least one whole token is available
+    fn has_tokens(&mut self) -> bool {
+        self.refill();
+        self.tokens >= 1.0
+    }
+
+    /// Takes up to `amount` whole tokens and returns how many were taken
+    fn take_up_to(&mut self, amount: usize) -> usize {
+        self.refill();
+        let taken = (self.tokens.max(0.0) as usize).min(amount);
+        self.tokens -= taken as f64;
+        taken
+    }
+
+    /// Takes `amount` tokens even if they are not available, the debt is
+    /// paid back before any further tokens are handed out
+    fn charge(&mut self, amount: usize) {
+        self.tokens -= amount as f64;
+    }
+
+    /// Returns tokens that were taken but ended up not being used
+    fn refund(&mut self, amount: usize) {
+        self.tokens = (self.tokens + amount as f64).min(self.rate);
+    }
+
+    /// Returns the point in time at which a whole token is available again
+    fn available_at(&self) -> Instant {
+        let missing = (1.0 - self.tokens).max(0.0);
+        self.last + Duration::from_secs_f64(missing / self.rate)
+    }
+}
+
+/// Something that is notified once a throttled socket can make progress
+#[derive(Debug)]
+enum Wakeup {
+    /// Pushes an interest into the handler of a socket, `pending` is
+    /// cleared before that happens so that a new wakeup can be scheduled
+    Handler {
+        handler: ArcInterestHandler,
+        interest: InterestType,
+        pending: Arc<AtomicBool>,
+    },
+    Waker(Waker),
+}
+
+impl Wakeup {
+    fn wake(self) {
+        match self {
+            Wakeup::Handler {
+                mut handler,
+                interest,
+                pending,
+            } => {
+                pending.store(false, Ordering::SeqCst);
+                handler.push_interest(interest);
+            }
+            Wakeup::Waker(waker) => waker.wake(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TimerQueue {
+    entries: Vec<(Instant, Wakeup)>,
+    running: bool,
+}
+
+/// Fires wakeups at a later point in time from a background thread which
+/// is started on first use and exits once the timer is dropped
+#[derive(Debug, Default)]
+struct Timer {
+    shared: Arc<(Mutex<TimerQueue>, Condvar)>,
+}
+
+impl Timer {
+    fn call_at(&self, when: Instant, wakeup: Wakeup) {
+        let (queue, condvar) = &*self.shared;
+        let mut queue = queue.lock().unwrap();
+        queue.entries.push((when, wakeup));
+        if !queue.running {
+            let shared = Arc::downgrade(&self.shared);
+            let spawned = std::thread::Builder::new()
+                .name("net-throttle".to_string())
+                .spawn(move || Self::run(shared));
+            match spawned {
+                Ok(_) => queue.running = true,
+                Err(err) => {
+                    // Waking up early is better than never waking up at all
+                    tracing::warn!("failed to start the network throttle timer - {err}");
+                    let entries = std::mem::take(&mut queue.entries);
+                    drop(queue);
+                    entries.into_iter().for_each(|(_, wakeup)| wakeup.wake());
+                    return;
+                }
+            }
+        }
+        condvar.notify_one();
+    }
+
+    fn run(shared: Weak<(Mutex<TimerQueue>, Condvar)>) {
+        while let Some(strong) = shared.upgrade() {
+            let (queue, condvar) = &*strong;
+            let mut queue = queue.lock().unwrap();
+            let now = Instant::now();
+            let (due, pending): (Vec<_>, Vec<_>) =
+                queue.entries.drain(..).partition(|(when, _)| *when <= now);
+            queue.entries = pending;
+
+            if due.is_empty() {
+                let timeout = queue
+                    .entries
+                    .iter()
+                    .map(|(when, _)| when.saturating_duration_since(now))
+                    .min()
+                    .unwrap_or(TIMER_IDLE);
+                drop(condvar.wait_timeout(queue, timeout).unwrap());
+            } else {
+                drop(queue);
+                due.into_iter().for_each(|(_, wakeup)| wakeup.wake());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Upload,
+    Download,
+}
+
+impl Direction {
+    fn interest(self) -> InterestType {
+        match self {
+            Direction::Upload => InterestType::Writable,
+            Direction::Download => InterestType::Readable,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ThrottleState {
+    limits: ThrottleLimits,
+    upload: Option<Mutex<TokenBucket>>,
+    download: Option<Mutex<TokenBucket>>,
+    connections: Option<Mutex<TokenBucket>>,
+    open_sockets: AtomicUsize,
+    slot_waiters: Mutex<Vec<Wakeup>>,
+    timer: Timer,
+}
+
+impl ThrottleState {
+    fn new(limits: ThrottleLimits) -> Self {
+        Self {
+            limits,
+            upload: limits
+                .upload_bytes_per_sec
+                .map(|rate| Mutex::new(TokenBucket::new(rate))),
+            download: limits
+                .download_bytes_per_sec
+                .map(|rate| Mutex::new(TokenBucket::new(rate))),
+            connections: limits
+                .connections_per_sec
+                .map(|rate| Mutex::new(TokenBucket::new(rate as u64))),
+            open_sockets: AtomicUsize::new(0),
+            slot_waiters: Mutex::new(Vec::new()),
+            timer: Timer::default(),
+        }
+    }
+
+    fn bucket(&self, direction: Direction) -> &Option<Mutex<TokenBucket>> {
+        match direction {
+            Direction::Upload => &self.upload,
+            Direction::Download => &self.download,
+        }
+    }
+
+    fn has_free_slot(&self) -> bool {
+        let max = self.limits.max_sockets.unwrap_or(usize::MAX);
+        self.open_sockets.load(Ordering::SeqCst) < max
+    }
+
+    /// Reserves one of the socket slots, the slot is given back when the
+    /// returned permit is dropped
+    fn try_open_socket(self: &Arc<Self>) -> Option<SocketPermit> {
+        let max = self.limits.max_sockets.unwrap_or(usize::MAX);
+        self.open_sockets
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                (open < max).then_some(open + 1)
+            })
+            .ok()?;
+        Some(SocketPermit {
+            state: self.clone(),
+        })
+    }
+
+    /// Reserves one of the socket slots, waiting for a slot to be given
+    /// back when all of them are in use
+    async fn open_socket(self: &Arc<Self>) -> SocketPermit {
+        poll_fn(|cx| match self.try_open_socket() {
+            Some(permit) => Poll::Ready(permit),
+            None => {
+                self.wait_for_slot(Wakeup::Waker(cx.waker().clone()));
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Fires the wakeup once a socket slot is given back
+    fn wait_for_slot(&self, wakeup: Wakeup) {
+        self.slot_waiters.lock().unwrap().push(wakeup);
+        // A slot might have been freed while the waiter was being added
+        if self.has_free_slot() {
+            self.wake_slot_waiters();
+        }
+    }
+
+    fn wake_slot_waiters(&self) {
+        let waiters = std::mem::take(&mut *self.slot_waiters.lock().unwrap());
+        waiters.into_iter().for_each(Wakeup::wake);
+    }
+
+    /// Takes a token for a new connection, or returns the point in time at
+    /// which the next one becomes available
+    fn try_take_connection(&self) -> std::result::Result<(), Instant> {
+        let Some(bucket) = &self.connections else {
+            return Ok(());
+        };
+        let mut bucket = bucket.lock().unwrap();
+        match bucket.take_up_to(1) {
+            1 => Ok(()),
+            _ => Err(bucket.available_at()),
+        }
+    }
+
+    fn refund_connection(&self) {
+        if let Some(bucket) = &self.connections {
+            bucket.lock().unwrap().refund(1);
+        }
+    }
+
+    /// Polls until `bucket` has at least one token without taking it
+    fn poll_bucket(&self, bucket: &Option<Mutex<TokenBucket>>, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(bucket) = bucket else {
+            return Poll::Ready(());
+        };
+        let mut bucket = bucket.lock().unwrap();
+        if bucket.has_tokens() {
+            return Poll::Ready(());
+        }
+        let when = bucket.available_at();
+        drop(bucket);
+        self.timer.call_at(when, Wakeup::Waker(cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+/// Holds one of the socket slots of a [`ThrottleState`]
+#[derive(Debug)]
+struct SocketPermit {
+    state: Arc<ThrottleState>,
+}
+
+impl Drop for SocketPermit {
+    fn drop(&mut self) {
+        self.state.open_sockets.fetch_sub(1, Ordering::SeqCst);
+        self.state.wake_slot_waiters();
+    }
+}
+
+/// Throttling state of a single socket
+#[derive(Debug)]
+struct SocketThrottle {
+    permit: SocketPermit,
+    handler: Option<ArcInterestHandler>,
+    read_pending: Arc<AtomicBool>,
+    write_pending: Arc<AtomicBool>,
+}
+
+impl SocketThrottle {
+    fn new(permit: SocketPermit) -> Self {
+        Self {
+            permit,
+            handler: None,
+            read_pending: Arc::new(AtomicBool::new(false)),
+            write_pending: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn state(&self) -> &Arc<ThrottleState> {
+        &self.permit.state
+    }
+
+    /// Keeps a copy of the handler so that it can be woken up by the
+    /// throttle and returns the handler to give to the inner socket
+    fn set_handler(
+        &mut self,
+        handler: Box<dyn InterestHandler + Send + Sync>,
+    ) -> Box<dyn InterestHandler + Send + Sync> {
+        let handler = ArcInterestHandler::new(handler);
+        self.handler = Some(handler.clone());
+        Box::new(handler)
+    }
+
+    fn remove_handler(&mut self) {
+        self.handler = None;
+    }
+
+    /// Builds a wakeup for the handler of this socket, unless one is
+    /// already outstanding for the same interest
+    fn handler_wakeup(&self, interest: InterestType) -> Option<Wakeup> {
+        let handler = self.handler.clone()?;
+        let pending = match interest {
+            InterestType::Readable => &self.read_pending,
+            _ => &self.write_pending,
+        };
+        if pending.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        Some(Wakeup::Handler {
+            handler,
+            interest,
+            pending: pending.clone(),
+        })
+    }
+
+    fn wake_handler_at(&self, when: Instant, interest: InterestType) {
+        if let Some(wakeup) = self.handler_wakeup(interest) {
+            self.state().timer.call_at(when, wakeup);
+        }
+    }
+
+    fn poll_ready(&self, direction: Direction, cx: &mut Context<'_>) -> Poll<()> {
+        let state = self.state();
+        state.poll_bucket(state.bucket(direction), cx)
+    }
+
+    /// Grants up to `len` bytes of bandwidth, if none is left then the
+    /// handler is woken once there is
+    fn take_up_to(&self, direction: Direction, len: usize) -> Result<usize> {
+        let Some(bucket) = self.state().bucket(direction) else {
+            return Ok(len);
+        };
+        let mut bucket = bucket.lock().unwrap();
+        match bucket.take_up_to(len) {
+            0 if len > 0 => {
+                let when = bucket.available_at();
+                drop(bucket);
+                self.wake_handler_at(when, direction.interest());
+                Err(NetworkError::WouldBlock)
+            }
+            granted => Ok(granted),
+        }
+    }
+
+    fn refund(&self, direction: Direction, amount: usize) {
+        if let Some(bucket) = self.state().bucket(direction) {
+            bucket.lock().unwrap().refund(amount);
+        }
+    }
+
+    /// Datagrams can not be split up, so they are let through whole as long
+    /// as there is any bandwidth left and the bucket goes into debt instead
+    fn datagram<T>(
+        &self,
+        direction: Direction,
+        peek: bool,
+        io: impl FnOnce() -> Result<(usize, T)>,
+    ) -> Result<(usize, T)> {
+        let Some(bucket) = self.state().bucket(direction) else {
+            return io();
+        };
+        {
+            let mut bucket = bucket.lock().unwrap();
+            if !bucket.has_tokens() {
+                let when = bucket.available_at();
+                drop(bucket);
+                self.wake_handler_at(when, direction.interest());
+                return Err(NetworkError::WouldBlock);
+            }
+        }
+        let (amount, ret) = io()?;
+        if !peek {
+            bucket.lock().unwrap().charge(amount);
+        }
+        Ok((amount, ret))
+    }
+}
+
+#[derive(Debug)]
+struct ThrottledTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    throttle: SocketThrottle,
+}
+
+impl VirtualIoSource for ThrottledTcpListener {
+    fn remove_handler(&mut self) {
+        self.throttle.remove_handler();
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let state = self.throttle.state();
+        if !state.has_free_slot() {
+            state.wait_for_slot(Wakeup::Waker(cx.waker().clone()));
+            return Poll::Pending;
+        }
+        if state.poll_bucket(&state.connections, cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for ThrottledTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let state = self.throttle.state().clone();
+        let Some(permit) = state.try_open_socket() else {
+            if let Some(wakeup) = self.throttle.handler_wakeup(InterestType::Readable) {
+                state.wait_for_slot(wakeup);
+            }
+            return Err(NetworkError::WouldBlock);
+        };
+        if let Err(when) = state.try_take_connection() {
+            self.throttle.wake_handler_at(when, InterestType::Readable);
+            return Err(NetworkError::WouldBlock);
+        }
+        match self.inner.try_accept() {
+            Ok((socket, addr)) => Ok((
+                Box::new(ThrottledTcpSocket {
+                    inner: socket,
+                    throttle: SocketThrottle::new(permit),
+                }),
+                addr,
+            )),
+            Err(err) => {
+                state.refund_connection();
+                Err(err)
+            }
+        }
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let handler = self.throttle.set_handler(handler);
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+#[derive(Debug)]
+struct ThrottledTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    throttle: SocketThrottle,
+}
+
+impl VirtualIoSource for ThrottledTcpSocket {
+    fn remove_handler(&mut self) {
+        self.throttle.remove_handler();
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self
+            .throttle
+            .poll_ready(Direction::Download, cx)
+            .is_pending()
+        {
+            return Poll::Pending;
+        }
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self.throttle.poll_ready(Direction::Upload, cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for ThrottledTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let handler = self.throttle.set_handler(handler);
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for ThrottledTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let granted = self.throttle.take_up_to(Direction::Upload, data.len())?;
+        let ret = self.inner.try_send(&data[..granted]);
+        let sent = *ret.as_ref().unwrap_or(&0);
+        self.throttle.refund(Direction::Upload, granted - sent);
+        ret
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let granted = self.throttle.take_up_to(Direction::Download, buf.len())?;
+        let ret = self.inner.try_recv(&mut buf[..granted], peek);
+        let read = match ret {
+            Ok(read) if !peek => read,
+            _ => 0,
+        };
+        self.throttle.refund(Direction::Download, granted - read);
+        ret
+    }
+}
+
+impl VirtualTcpSocket for ThrottledTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, reuse: bool) -> Result<()> {
+        self.inner.set_nodelay(reuse)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_dontroute(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_dontroute(keepalive)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// Implements the parts that are common to all the datagram sockets
+macro_rules! throttled_datagram_socket {
+    ($name:ident, $inner:ty) => {
+        #[derive(Debug)]
+        struct $name {
+            inner: Box<$inner>,
+            throttle: SocketThrottle,
+        }
+
+        impl VirtualIoSource for $name {
+            fn remove_handler(&mut self) {
+                self.throttle.remove_handler();
+                self.inner.remove_handler();
+            }
+
+            fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+                if self
+                    .throttle
+                    .poll_ready(Direction::Download, cx)
+                    .is_pending()
+                {
+                    return Poll::Pending;
+                }
+                self.inner.poll_read_ready(cx)
+            }
+
+            fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+                if self.throttle.poll_ready(Direction::Upload, cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.inner.poll_write_ready(cx)
+            }
+        }
+
+        impl VirtualSocket for $name {
+            fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+                self.inner.set_ttl(ttl)
+            }
+
+            fn ttl(&self) -> Result<u32> {
+                self.inner.ttl()
+            }
+
+            fn addr_local(&self) -> Result<SocketAddr> {
+                self.inner.addr_local()
+            }
+
+            fn status(&self) -> Result<SocketStatus> {
+                self.inner.status()
+            }
+
+            fn set_handler(
+                &mut self,
+                handler: Box<dyn InterestHandler + Send + Sync>,
+            ) -> Result<()> {
+                let handler = self.throttle.set_handler(handler);
+                self.inner.set_handler(handler)
+            }
+        }
+    };
+}
+
+/// Implements [`VirtualConnectionlessSocket`] for a datagram socket
+macro_rules! throttled_connectionless_socket {
+    ($name:ident) => {
+        impl VirtualConnectionlessSocket for $name {
+            fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+                let inner = &mut self.inner;
+                self.throttle
+                    .datagram(Direction::Upload, false, || {
+                        inner.try_send_to(data, addr).map(|sent| (sent, ()))
+                    })
+                    .map(|(sent, ())| sent)
+            }
+
+            fn try_recv_from(
+                &mut self,
+                buf: &mut [MaybeUninit<u8>],
+                peek: bool,
+            ) -> Result<(usize, SocketAddr)> {
+                let inner = &mut self.inner;
+                self.throttle
+                    .datagram(Direction::Download, peek, || inner.try_recv_from(buf, peek))
+            }
+        }
+    };
+}
+
+throttled_datagram_socket!(ThrottledUdpSocket, dyn VirtualUdpSocket + Sync);
+throttled_connectionless_socket!(ThrottledUdpSocket);
+
+impl VirtualUdpSocket for ThrottledUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+throttled_datagram_socket!(ThrottledIcmpSocket, dyn VirtualIcmpSocket + Sync);
+throttled_connectionless_socket!(ThrottledIcmpSocket);
+
+impl VirtualIcmpSocket for ThrottledIcmpSocket {}
+
+throttled_datagram_socket!(ThrottledRawSocket, dyn VirtualRawSocket + Sync);
+
+impl VirtualRawSocket for ThrottledRawSocket {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let inner = &mut self.inner;
+        self.throttle
+            .datagram(Direction::Upload, false, || {
+                inner.try_send(data).map(|sent| (sent, ()))
+            })
+            .map(|(sent, ())| sent)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let inner = &mut self.inner;
+        self.throttle
+            .datagram(Direction::Download, peek, || {
+                inner.try_recv(buf, peek).map(|read| (read, ()))
+            })
+            .map(|(read, ())| read)
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.inner.set_promiscuous(promiscuous)
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        self.inner.promiscuous()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+
+    use super::*;
+    use crate::tcp_pair::TcpSocketHalf;
+    use crate::LoopbackNetworking;
+
+    #[derive(Debug, Clone, Default)]
+    struct RecordingHandler(Arc<Mutex<Vec<InterestType>>>);
+
+    impl InterestHandler for RecordingHandler {
+        fn push_interest(&mut self, interest: InterestType) {
+            self.0.lock().unwrap().push(interest);
+        }
+
+        fn pop_interest(&mut self, interest: InterestType) -> bool {
+            let mut interests = self.0.lock().unwrap();
+            let len = interests.len();
+            interests.retain(|i| *i != interest);
+            interests.len() != len
+        }
+
+        fn has_interest(&self, interest: InterestType) -> bool {
+            self.0.lock().unwrap().contains(&interest)
+        }
+    }
+
+    fn listen_addr() -> SocketAddr {
+        SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8080)
+    }
+
+    fn connect(net: &ThrottledNetworking<LoopbackNetworking>) -> TcpSocketHalf {
+        let client = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+        net.inner()
+            .loopback_connect_to(client, listen_addr())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(10);
+        assert_eq!(bucket.take_up_to(25), 10);
+        assert_eq!(bucket.take_up_to(1), 0);
+        assert!(bucket.available_at() > bucket.last);
+
+        bucket.refund(4);
+        assert_eq!(bucket.take_up_to(25), 4);
+
+        bucket.charge(5);
+        assert!(!bucket.has_tokens());
+        assert!(bucket.available_at() >= bucket.last + Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_max_sockets() {
+        let net = ThrottledNetworking::new(
+            LoopbackNetworking::new(),
+            ThrottleLimits {
+                max_sockets: Some(2),
+                ..Default::default()
+            },
+        );
+
+        let mut listener = net
+            .listen_tcp(listen_addr(), false, false, false)
+            .await
+            .unwrap();
+        let _clients = [connect(&net), connect(&net)];
+
+        let (server, _) = listener.try_accept().unwrap();
+        assert_eq!(net.open_sockets(), 2);
+        assert_eq!(listener.try_accept().unwrap_err(), NetworkError::WouldBlock);
+
+        // Opening another socket waits for a slot rather than failing
+        let other_addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8081);
+        let mut other = net.listen_tcp(other_addr, false, false, false);
+        assert!((&mut other).now_or_never().is_none());
+
+        // Closing a socket frees up a slot and wakes up the listener
+        let handler = RecordingHandler::default();
+        listener.set_handler(Box::new(handler.clone())).unwrap();
+        assert_eq!(listener.try_accept().unwrap_err(), NetworkError::WouldBlock);
+        handler.0.lock().unwrap().clear();
+        drop(server);
+        assert!(handler.has_interest(InterestType::Readable));
+        let server = listener.try_accept().unwrap();
+
+        drop(server);
+        assert_eq!(
+            other.await.unwrap().addr_local().unwrap(),
+            other_addr,
+            "the waiting socket is opened once a slot is free"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_rate() {
+        let net = ThrottledNetworking::new(
+            LoopbackNetworking::new(),
+            ThrottleLimits {
+                connections_per_sec: Some(1),
+                ..Default::default()
+            },
+        );
+
+        let mut listener = net
+            .listen_tcp(listen_addr(), false, false, false)
+            .await
+            .unwrap();
+        let _clients = [connect(&net), connect(&net)];
+
+        listener.try_accept().unwrap();
+        assert_eq!(listener.try_accept().unwrap_err(), NetworkError::WouldBlock);
+    }
+
+    #[tokio::test]
+    async fn test_upload_backpressure() {
+        let net = ThrottledNetworking::new(
+            LoopbackNetworking::new(),
+            ThrottleLimits {
+                upload_bytes_per_sec: Some(100),
+                ..Default::default()
+            },
+        );
+
+        let mut listener = net
+            .listen_tcp(listen_addr(), false, false, false)
+            .await
+            .unwrap();
+        let _client = connect(&net);
+        let (mut server, _) = listener.try_accept().unwrap();
+        let handler = RecordingHandler::default();
+        server.set_handler(Box::new(handler.clone())).unwrap();
+
+        assert_eq!(server.try_send(&[0u8; 150]).unwrap(), 100);
+        assert_eq!(
+            server.try_send(&[0u8; 50]).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        handler.0.lock().unwrap().clear();
+
+        // The handler is told when the socket becomes writable again
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !handler.has_interest(InterestType::Writable) {
+            assert!(Instant::now() < deadline, "the handler was never woken");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(server.try_send(&[0u8; 50]).unwrap() > 0);
+    }
+}
@@ -0,0 +1,487 @@
+//! Unix domain sockets that only exist within this process.
+//!
+//! Binding a socket hands out the [`UnixEndpoint`] that other sockets reach
+//! it through, it is up to whoever owns the namespace of the paths (such as
+//! the virtual file system of an instance) to keep track of the endpoints.
+
+use std::collections::VecDeque;
+use std::mem::MaybeUninit;
+use std::net::Shutdown;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+use bytes::Bytes;
+use virtual_mio::{ArcInterestHandler, InterestHandler, InterestType};
+
+use crate::tcp_pair::SocketBuffer;
+use crate::{
+    NetworkError, Result, VirtualIoSource, VirtualUnixDatagramSocket, VirtualUnixListener,
+    VirtualUnixSocket,
+};
+
+/// Size of the buffers of each direction of a stream
+const STREAM_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Number of connections queued up when the backlog given to `listen` is zero
+const DEFAULT_BACKLOG: usize = 128;
+
+/// Number of datagrams that can be waiting to be received before the
+/// senders are blocked
+const MAX_DATAGRAM_QUEUE: usize = 256;
+
+/// The address that a unix domain socket of this process is bound to
+#[derive(Debug, Clone)]
+pub struct UnixEndpoint {
+    inner: EndpointInner,
+}
+
+#[derive(Debug, Clone)]
+enum EndpointInner {
+    Listener(Weak<Mutex<ListenerState>>),
+    Datagram(Weak<Mutex<MailboxState>>),
+}
+
+/// Binds a stream socket to a path, it only accepts connections once
+/// [`VirtualUnixListener::listen`] has been called
+pub fn listen(path: PathBuf) -> (UnixPairListener, UnixEndpoint) {
+    let state = Arc::new(Mutex::new(ListenerState {
+        path,
+        backlog: None,
+        pending: VecDeque::new(),
+        handler: None,
+        wakers: Vec::new(),
+    }));
+    let endpoint = UnixEndpoint {
+        inner: EndpointInner::Listener(Arc::downgrade(&state)),
+    };
+    (UnixPairListener { state }, endpoint)
+}
+
+/// Opens a datagram socket which is reachable through the returned endpoint
+pub fn bind_datagram(path: Option<PathBuf>) -> (UnixPairDatagram, UnixEndpoint) {
+    let state = Arc::new(Mutex::new(MailboxState {
+        path,
+        queue: VecDeque::new(),
+        handler: None,
+        wakers: Vec::new(),
+        senders: Vec::new(),
+    }));
+    let endpoint = UnixEndpoint {
+        inner: EndpointInner::Datagram(Arc::downgrade(&state)),
+    };
+    (UnixPairDatagram { state }, endpoint)
+}
+
+/// Creates two unnamed stream sockets that are connected to each other
+pub fn pair() -> (UnixPairStream, UnixPairStream) {
+    UnixPairStream::channel(None, None)
+}
+
+impl UnixEndpoint {
+    /// Returns true if this is the endpoint of a stream socket
+    pub fn is_stream(&self) -> bool {
+        matches!(self.inner, EndpointInner::Listener(_))
+    }
+
+    /// Returns false once the socket bound to the endpoint has been closed
+    pub fn is_alive(&self) -> bool {
+        match &self.inner {
+            EndpointInner::Listener(state) => state.strong_count() > 0,
+            EndpointInner::Datagram(state) => state.strong_count() > 0,
+        }
+    }
+
+    /// Connects to the stream socket bound to this endpoint, `local` is
+    /// the path the connecting socket is bound to (if any)
+    pub fn connect(&self, local: Option<PathBuf>) -> Result<UnixPairStream> {
+        let state = match &self.inner {
+            EndpointInner::Listener(state) => state.upgrade(),
+            EndpointInner::Datagram(_) => return Err(NetworkError::Unsupported),
+        };
+        let state = state.ok_or(NetworkError::ConnectionRefused)?;
+        let mut state = state.lock().unwrap();
+
+        match state.backlog {
+            Some(backlog) if state.pending.len() < backlog => {}
+            _ => return Err(NetworkError::ConnectionRefused),
+        }
+        let (ours, theirs) = UnixPairStream::channel(local, Some(state.path.clone()));
+        state.pending.push_back(theirs);
+        if let Some(handler) = state.handler.as_mut() {
+            handler.push_interest(InterestType::Readable);
+        }
+        state.wakers.drain(..).for_each(|w| w.wake());
+        Ok(ours)
+    }
+
+    /// Delivers a datagram to the socket bound to this endpoint, `from` is
+    /// the path the sending socket is bound to (if any). The waker is woken
+    /// when there is room again if the receiver is lagging behind.
+    pub fn try_send(
+        &self,
+        data: &[u8],
+        from: Option<PathBuf>,
+        waker: Option<&Waker>,
+    ) -> Result<usize> {
+        let state = match &self.inner {
+            EndpointInner::Datagram(state) => state.upgrade(),
+            EndpointInner::Listener(_) => return Err(NetworkError::Unsupported),
+        };
+        let state = state.ok_or(NetworkError::ConnectionRefused)?;
+        let mut state = state.lock().unwrap();
+
+        if state.queue.len() >= MAX_DATAGRAM_QUEUE {
+            if let Some(waker) = waker {
+                if !state.senders.iter().any(|w| w.will_wake(waker)) {
+                    state.senders.push(waker.clone());
+                }
+            }
+            return Err(NetworkError::WouldBlock);
+        }
+        state.queue.push_back((Bytes::copy_from_slice(data), from));
+        if let Some(handler) = state.handler.as_mut() {
+            handler.push_interest(InterestType::Readable);
+        }
+        state.wakers.drain(..).for_each(|w| w.wake());
+        Ok(data.len())
+    }
+}
+
+#[derive(Debug)]
+struct ListenerState {
+    path: PathBuf,
+    /// Set once the socket is listening
+    backlog: Option<usize>,
+    pending: VecDeque<UnixPairStream>,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    wakers: Vec<Waker>,
+}
+
+/// Stream socket of this process that is bound to a path
+#[derive(Debug)]
+pub struct UnixPairListener {
+    state: Arc<Mutex<ListenerState>>,
+}
+
+impl VirtualUnixListener for UnixPairListener {
+    fn listen(&mut self, backlog: usize) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.backlog = Some(match backlog {
+            0 => DEFAULT_BACKLOG,
+            backlog => backlog,
+        });
+        Ok(())
+    }
+
+    fn try_accept(&mut self) -> Result<Box<dyn VirtualUnixSocket + Sync>> {
+        let mut state = self.state.lock().unwrap();
+        match state.pending.pop_front() {
+            Some(stream) => Ok(Box::new(stream)),
+            None => Err(NetworkError::WouldBlock),
+        }
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.pending.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        state.handler.replace(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<Option<PathBuf>> {
+        let state = self.state.lock().unwrap();
+        Ok(Some(state.path.clone()))
+    }
+}
+
+impl VirtualIoSource for UnixPairListener {
+    fn remove_handler(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.pending.is_empty() {
+            return Poll::Ready(Ok(state.pending.len()));
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.poll_read_ready(cx)
+    }
+}
+
+/// Connected stream socket of this process
+#[derive(Debug)]
+pub struct UnixPairStream {
+    local: Option<PathBuf>,
+    peer: Option<PathBuf>,
+    tx: SocketBuffer,
+    rx: SocketBuffer,
+}
+
+impl UnixPairStream {
+    fn channel(path1: Option<PathBuf>, path2: Option<PathBuf>) -> (UnixPairStream, UnixPairStream) {
+        let (buffer1, buffer2) = SocketBuffer::duplex(STREAM_BUFFER_SIZE);
+        let half1 = Self {
+            local: path1.clone(),
+            peer: path2.clone(),
+            tx: buffer1.clone(),
+            rx: buffer2.clone(),
+        };
+        let half2 = Self {
+            local: path2,
+            peer: path1,
+            tx: buffer2,
+            rx: buffer1,
+        };
+        (half1, half2)
+    }
+}
+
+impl VirtualUnixSocket for UnixPairStream {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.tx.try_send(data, false, None)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        self.rx.try_read(buf, peek, None)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        match how {
+            Shutdown::Both => {
+                self.tx.shutdown();
+                self.rx.shutdown();
+            }
+            Shutdown::Read => self.rx.shutdown(),
+            Shutdown::Write => self.tx.shutdown(),
+        }
+        Ok(())
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let handler = ArcInterestHandler::new(handler);
+        self.tx.set_pull_handler(handler.clone());
+        self.rx.set_push_handler(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<Option<PathBuf>> {
+        Ok(self.local.clone())
+    }
+
+    fn addr_peer(&self) -> Result<Option<PathBuf>> {
+        Ok(self.peer.clone())
+    }
+
+    fn is_closed(&self) -> bool {
+        !self.tx.is_alive()
+    }
+}
+
+impl VirtualIoSource for UnixPairStream {
+    fn remove_handler(&mut self) {
+        self.tx.clear_pull_handler();
+        self.rx.clear_push_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.rx.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.tx.poll_write_ready(cx)
+    }
+}
+
+#[derive(Debug)]
+struct MailboxState {
+    path: Option<PathBuf>,
+    queue: VecDeque<(Bytes, Option<PathBuf>)>,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    wakers: Vec<Waker>,
+    /// Senders waiting for room in the queue
+    senders: Vec<Waker>,
+}
+
+/// Datagram socket of this process, datagrams are sent to other sockets
+/// through their [`UnixEndpoint`]
+#[derive(Debug)]
+pub struct UnixPairDatagram {
+    state: Arc<Mutex<MailboxState>>,
+}
+
+impl VirtualUnixDatagramSocket for UnixPairDatagram {
+    fn try_send_to(&mut self, _data: &[u8], _path: &Path) -> Result<usize> {
+        // Paths are resolved by the owner of the namespace
+        Err(NetworkError::Unsupported)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, Option<PathBuf>)> {
+        let mut state = self.state.lock().unwrap();
+        let (data, from) = match peek {
+            true => state.queue.front().cloned(),
+            false => state.queue.pop_front(),
+        }
+        .ok_or(NetworkError::WouldBlock)?;
+        if !peek {
+            state.senders.drain(..).for_each(|w| w.wake());
+        }
+
+        let buf: &mut [u8] = unsafe { std::mem::transmute(buf) };
+        let amt = buf.len().min(data.len());
+        buf[..amt].copy_from_slice(&data[..amt]);
+        Ok((amt, from))
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.queue.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        handler.push_interest(InterestType::Writable);
+        state.handler.replace(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<Option<PathBuf>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.path.clone())
+    }
+}
+
+impl VirtualIoSource for UnixPairDatagram {
+    fn remove_handler(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((data, _)) = state.queue.front() {
+            return Poll::Ready(Ok(data.len()));
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        // Whether a datagram can be sent depends on its destination
+        Poll::Ready(Ok(STREAM_BUFFER_SIZE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recv(socket: &mut dyn VirtualUnixSocket) -> Vec<u8> {
+        let mut buf = [MaybeUninit::uninit(); 64];
+        let read = socket.try_recv(&mut buf, false).unwrap();
+        buf[..read]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect()
+    }
+
+    #[test]
+    fn test_unix_stream() {
+        let (mut listener, endpoint) = listen("/run/test.sock".into());
+        assert_eq!(
+            endpoint.connect(None).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+        listener.listen(1).unwrap();
+
+        let mut client = endpoint.connect(Some("/tmp/client.sock".into())).unwrap();
+        assert_eq!(
+            endpoint.connect(None).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+        let mut server = listener.try_accept().unwrap();
+        assert_eq!(listener.try_accept().unwrap_err(), NetworkError::WouldBlock);
+        assert_eq!(
+            server.addr_peer().unwrap(),
+            Some(PathBuf::from("/tmp/client.sock"))
+        );
+        assert_eq!(
+            client.addr_peer().unwrap(),
+            Some(PathBuf::from("/run/test.sock"))
+        );
+
+        client.try_send(b"ping").unwrap();
+        assert_eq!(recv(server.as_mut()), b"ping");
+        server.try_send(b"pong").unwrap();
+        assert_eq!(recv(&mut client), b"pong");
+
+        drop(server);
+        assert!(client.is_closed());
+        assert_eq!(recv(&mut client), b"");
+
+        drop(listener);
+        assert!(!endpoint.is_alive());
+        assert_eq!(
+            endpoint.connect(None).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+    }
+
+    #[test]
+    fn test_unix_datagram() {
+        let (mut server, endpoint) = bind_datagram(Some("/run/log.sock".into()));
+        assert!(!endpoint.is_stream());
+
+        endpoint
+            .try_send(b"hello", Some("/tmp/a.sock".into()), None)
+            .unwrap();
+        endpoint.try_send(b"world", None, None).unwrap();
+
+        let mut buf = [MaybeUninit::uninit(); 64];
+        assert_eq!(
+            server.try_recv_from(&mut buf, true).unwrap(),
+            (5, Some(PathBuf::from("/tmp/a.sock")))
+        );
+        assert_eq!(
+            server.try_recv_from(&mut buf, false).unwrap(),
+            (5, Some(PathBuf::from("/tmp/a.sock")))
+        );
+        assert_eq!(server.try_recv_from(&mut buf, false).unwrap(), (5, None));
+        assert_eq!(
+            server.try_recv_from(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        for _ in 0..MAX_DATAGRAM_QUEUE {
+            endpoint.try_send(b"x", None, None).unwrap();
+        }
+        assert_eq!(
+            endpoint.try_send(b"x", None, None).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        drop(server);
+        assert_eq!(
+            endpoint.try_send(b"x", None, None).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use smoltcp::{
+    phy::{self, Device, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+/// Maximum size of an Ethernet frame (without the FCS)
+pub const MAX_FRAME_SIZE: usize = 1514;
+
+/// Maximum number of received frames that are queued up before the
+/// interface is polled, any further frames are dropped like a real NIC
+/// would do when its ring is full
+const MAX_RX_QUEUE: usize = 1024;
+
+/// The virtual Ethernet card of a user space network stack, the frames it
+/// transmits are queued up until the stack hands them over to its link
+#[derive(Debug, Default)]
+pub(super) struct VirtualEthernet {
+    rx: VecDeque<Vec<u8>>,
+    tx: Vec<Bytes>,
+}
+
+impl VirtualEthernet {
+    /// Queues up a frame that was received from the link
+    pub fn push_rx(&mut self, frame: &[u8]) -> bool {
+        if self.rx.len() >= MAX_RX_QUEUE || frame.len() > MAX_FRAME_SIZE {
+            return false;
+        }
+        self.rx.push_back(frame.to_vec());
+        true
+    }
+
+    /// Queues up a frame that is to be sent out on the link
+    pub fn push_tx(&mut self, frame: Bytes) {
+        self.tx.push(frame);
+    }
+
+    /// Takes all the frames that were sent out by the interface
+    pub fn take_tx(&mut self) -> Vec<Bytes> {
+        std::mem::take(&mut self.tx)
+    }
+}
+
+impl<'a> Device<'a> for VirtualEthernet {
+    type RxToken = RxToken;
+    type TxToken = TxToken<'a>;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        let frame = self.rx.pop_front()?;
+        Some((RxToken { frame }, TxToken { tx: &mut self.tx }))
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(TxToken { tx: &mut self.tx })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = MAX_FRAME_SIZE;
+        caps
+    }
+}
+
+pub(super) struct RxToken {
+    frame: Vec<u8>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(&mut self.frame)
+    }
+}
+
+pub(super) struct TxToken<'a> {
+    tx: &'a mut Vec<Bytes>,
+}
+
+impl phy::TxToken for TxToken<'_> {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        let mut frame = vec![0; len];
+        let ret = f(&mut frame)?;
+        self.tx.push(frame.into());
+        Ok(ret)
+    }
+}
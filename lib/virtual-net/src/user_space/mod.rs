@@ -0,0 +1,1343 @@
+//! A user space TCP/IP stack (built on top of `smoltcp`) that implements
+//! [`VirtualNetworking`] over a virtual Ethernet interface.
+//!
+//! Every [`UserSpaceNetworking`] has its own MAC address, IP addresses and
+//! routing table and never touches the network namespace of the host. The
+//! frames that its interface sends out are handed to an [`EthernetLink`]
+//! and the frames it receives are fed in with [`UserSpaceNetworking::receive`],
+//! two stacks can also be wired back to back with [`UserSpaceNetworking::connect`].
+//!
+//! Bridging a stack to a network ID plugs it into the [`VirtualSwitch`] of
+//! that network, which connects all the stacks of the process that were
+//! bridged to it.
+
+mod device;
+mod socket;
+mod switch;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::poll_fn;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use bytes::Bytes;
+use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Route, Routes, SocketHandle};
+use smoltcp::socket::{
+    Dhcpv4Event, Dhcpv4Socket, IcmpPacketMetadata, IcmpSocket, IcmpSocketBuffer, TcpSocket,
+    TcpSocketBuffer, TcpState, UdpPacketMetadata, UdpSocket, UdpSocketBuffer,
+};
+use smoltcp::time::Instant as SmolInstant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr as SmolIpCidr, IpEndpoint, Ipv4Cidr};
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+use virtual_mio::InterestType;
+
+use crate::{
+    InterestHandler, IpCidr, IpRoute, NetworkError, Result, StreamSecurity, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+use device::VirtualEthernet;
+
+pub use device::MAX_FRAME_SIZE;
+pub use socket::{
+    UserSpaceIcmpSocket, UserSpaceRawSocket, UserSpaceTcpListener, UserSpaceTcpSocket,
+    UserSpaceUdpSocket,
+};
+pub use switch::{SwitchPort, SwitchRegistry, VirtualSwitch};
+
+const TCP_BUFFER_SIZE: usize = 65536;
+const UDP_PACKETS: usize = 64;
+const UDP_BUFFER_SIZE: usize = 65536;
+const ICMP_PACKETS: usize = 16;
+const ICMP_BUFFER_SIZE: usize = 16384;
+/// Number of sockets that are kept listening for every TCP listener,
+/// which is the number of connections that can be opened concurrently
+const LISTEN_BACKLOG: usize = 8;
+/// Maximum number of frames that are queued up for a raw socket
+const MAX_RAW_QUEUE: usize = 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+const DHCP_TIMEOUT: Duration = Duration::from_secs(30);
+const EPHEMERAL_PORTS: std::ops::RangeInclusive<u16> = 49152..=65535;
+
+/// The other end of the virtual Ethernet interface of a
+/// [`UserSpaceNetworking`], it receives every frame the interface sends out
+pub trait EthernetLink: std::fmt::Debug + Send + Sync + 'static {
+    fn send(&self, frame: Bytes);
+}
+
+/// Networking implementation that runs its own TCP/IP stack in user space
+#[derive(Debug, Clone)]
+pub struct UserSpaceNetworking {
+    stack: Arc<Stack>,
+}
+
+type SocketId = u64;
+
+struct Stack {
+    state: Mutex<StackState>,
+    notify: Arc<Notify>,
+    start: std::time::Instant,
+}
+
+struct StackState {
+    iface: Interface<'static, VirtualEthernet>,
+    mac: [u8; 6],
+    link: Option<Arc<dyn EthernetLink>>,
+    /// Where the switches of the networks that the stack is bridged to
+    /// are looked up, the global registry is used when not set
+    switches: Option<SwitchRegistry>,
+    vlan: Option<u16>,
+    sockets: HashMap<SocketId, SocketEntry>,
+    next_socket_id: SocketId,
+    next_ephemeral_port: u16,
+    routes: Vec<IpRoute>,
+    dhcp: Option<DhcpState>,
+}
+
+struct DhcpState {
+    handle: SocketHandle,
+    addr: Option<Ipv4Cidr>,
+    wakers: Vec<Waker>,
+}
+
+/// Sockets that were handed out to the guest together with the interest
+/// handler and wakers that are notified when they become ready
+struct SocketEntry {
+    kind: SocketKind,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    wakers: Vec<Waker>,
+    readiness: Readiness,
+}
+
+enum SocketKind {
+    Tcp(TcpEntry),
+    Listener(ListenerEntry),
+    Udp(SocketHandle),
+    Icmp(IcmpEntry),
+    Raw(RawEntry),
+}
+
+struct TcpEntry {
+    handle: SocketHandle,
+    /// Set once the connection has been established, a closed socket
+    /// that was never connected failed to connect
+    connected: bool,
+    /// Set once the guest dropped the socket, it is removed from the
+    /// interface once the connection is fully closed
+    orphaned: bool,
+}
+
+struct ListenerEntry {
+    addr: SocketAddr,
+    listening: Vec<SocketHandle>,
+    accepted: VecDeque<SocketHandle>,
+}
+
+struct IcmpEntry {
+    handle: SocketHandle,
+    addr: IpAddr,
+}
+
+struct RawEntry {
+    frames: VecDeque<Bytes>,
+    promiscuous: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Readiness {
+    /// Amount of data (or connections) that can be read
+    readable: usize,
+    writable: bool,
+    /// The peer closed the connection (or it failed)
+    hangup: bool,
+}
+
+impl UserSpaceNetworking {
+    /// Creates a new network stack with a random MAC address, the stack is
+    /// driven by a task spawned on the given tokio runtime
+    pub fn new(handle: &Handle) -> Self {
+        let mut mac = random_u64().to_le_bytes();
+        // Locally administered unicast address
+        mac[0] = (mac[0] & 0xfe) | 0x02;
+        Self::with_mac(handle, mac[..6].try_into().unwrap())
+    }
+
+    /// Creates a new network stack with a specific MAC address
+    pub fn with_mac(handle: &Handle, mac: [u8; 6]) -> Self {
+        let iface = InterfaceBuilder::new(VirtualEthernet::default(), vec![])
+            .hardware_addr(EthernetAddress(mac).into())
+            .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+            .ip_addrs(Vec::new())
+            .routes(Routes::new(BTreeMap::new()))
+            .ipv4_multicast_groups(BTreeMap::new())
+            .random_seed(random_u64())
+            .finalize();
+
+        let stack = Arc::new(Stack {
+            state: Mutex::new(StackState {
+                iface,
+                mac,
+                link: None,
+                switches: None,
+                vlan: None,
+                sockets: HashMap::new(),
+                next_socket_id: 0,
+                next_ephemeral_port: *EPHEMERAL_PORTS.start()
+                    + (random_u64() % EPHEMERAL_PORTS.len() as u64) as u16,
+                routes: Vec::new(),
+                dhcp: None,
+            }),
+            notify: Arc::new(Notify::new()),
+            start: std::time::Instant::now(),
+        });
+        handle.spawn(drive(Arc::downgrade(&stack), stack.notify.clone()));
+        Self { stack }
+    }
+
+    /// Sets the link that the frames sent out by the interface are handed
+    /// to, without a link they are dropped
+    pub fn set_link(&self, link: Option<Arc<dyn EthernetLink>>) {
+        let mut state = self.stack.state.lock().unwrap();
+        state.link = link;
+    }
+
+    /// Feeds a frame that arrived over the link into the interface
+    pub fn receive(&self, frame: &[u8]) {
+        self.stack.receive(frame);
+    }
+
+    /// Sets the registry of the switches that the stack joins when it is
+    /// bridged to a network
+    pub fn set_switches(&self, switches: SwitchRegistry) {
+        let mut state = self.stack.state.lock().unwrap();
+        state.switches = Some(switches);
+    }
+
+    /// Puts the port of the stack on a VLAN the next time it is bridged
+    /// to a network, it can then only talk to the stacks of the same VLAN
+    pub fn set_vlan(&self, vlan: Option<u16>) {
+        let mut state = self.stack.state.lock().unwrap();
+        state.vlan = vlan;
+    }
+
+    /// Wires the interfaces of two stacks back to back as if they were
+    /// plugged into each other with a cable
+    pub fn connect(&self, other: &UserSpaceNetworking) {
+        self.set_link(Some(Arc::new(PeerLink(Arc::downgrade(&other.stack)))));
+        other.set_link(Some(Arc::new(PeerLink(Arc::downgrade(&self.stack)))));
+    }
+}
+
+/// Link that delivers the frames straight into the interface of another stack
+#[derive(Debug)]
+struct PeerLink(Weak<Stack>);
+
+impl EthernetLink for PeerLink {
+    fn send(&self, frame: Bytes) {
+        if let Some(stack) = self.0.upgrade() {
+            stack.receive(&frame);
+        }
+    }
+}
+
+/// Polls the interface whenever something happened on the stack (or one
+/// of its timers expired) until the stack is dropped
+async fn drive(stack: Weak<Stack>, notify: Arc<Notify>) {
+    loop {
+        let delay = match stack.upgrade() {
+            Some(stack) => stack.poll(),
+            None => return,
+        };
+        match delay {
+            Some(delay) => {
+                tokio::time::timeout(delay, notify.notified()).await.ok();
+            }
+            None => notify.notified().await,
+        }
+    }
+}
+
+impl std::fmt::Debug for Stack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("Stack")
+            .field("mac", &EthernetAddress(state.mac))
+            .field("ip_addrs", &state.iface.ip_addrs())
+            .field("sockets", &state.sockets.len())
+            .finish()
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        // Lets the driver notice that the stack is gone
+        self.notify.notify_one();
+    }
+}
+
+impl Stack {
+    fn now(&self) -> SmolInstant {
+        SmolInstant::from_micros(self.start.elapsed().as_micros() as i64)
+    }
+
+    /// Schedules the interface to be polled
+    fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    fn receive(&self, frame: &[u8]) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.tap(frame);
+            if !state.iface.device_mut().push_rx(frame) {
+                tracing::trace!(len = frame.len(), "dropping a received frame");
+            }
+        }
+        self.wake();
+    }
+
+    /// Processes the received frames and the sockets then sends out the
+    /// frames that were produced, returns when it needs to be polled again
+    fn poll(&self) -> Option<Duration> {
+        let (frames, link, delay) = {
+            let mut state = self.state.lock().unwrap();
+            let now = self.now();
+            let activity = match state.iface.poll(now) {
+                Ok(activity) => activity,
+                Err(err) => {
+                    tracing::trace!(%err, "failed to poll the interface");
+                    true
+                }
+            };
+            state.dispatch(activity);
+            let frames = state.iface.device_mut().take_tx();
+            let delay = state.iface.poll_delay(now);
+            (frames, state.link.clone(), delay)
+        };
+        if let Some(link) = link {
+            for frame in frames {
+                link.send(frame);
+            }
+        }
+        delay.map(|delay| Duration::from_micros(delay.total_micros()))
+    }
+
+    fn add_socket(&self, kind: SocketKind) -> SocketId {
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            state.add_socket(kind)
+        };
+        self.wake();
+        id
+    }
+
+    /// Runs a closure against a socket and its entry, the readiness of the
+    /// socket is refreshed afterwards so that only changes that happen
+    /// later on are notified
+    fn with_entry<R>(
+        &self,
+        id: SocketId,
+        f: impl FnOnce(&mut Interface<'static, VirtualEthernet>, &mut SocketEntry) -> R,
+    ) -> R {
+        let ret = {
+            let mut state = self.state.lock().unwrap();
+            let StackState { iface, sockets, .. } = &mut *state;
+            let entry = sockets.get_mut(&id).expect("unknown socket");
+            let ret = f(iface, entry);
+            entry.readiness = entry.kind.readiness(iface);
+            ret
+        };
+        self.wake();
+        ret
+    }
+
+    fn close_socket(&self, id: SocketId) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let Some(entry) = state.sockets.remove(&id) else {
+                return;
+            };
+            match entry.kind {
+                SocketKind::Tcp(mut tcp) => {
+                    // The connection is closed gracefully and the socket is
+                    // only removed once the peer acknowledged it
+                    state.iface.get_socket::<TcpSocket>(tcp.handle).close();
+                    tcp.orphaned = true;
+                    state.add_socket(SocketKind::Tcp(tcp));
+                }
+                SocketKind::Listener(listener) => {
+                    for handle in listener.listening {
+                        state.iface.remove_socket(handle);
+                    }
+                    // Connections that were never accepted are reset and
+                    // cleaned up like any other orphaned socket
+                    for handle in listener.accepted {
+                        state.iface.get_socket::<TcpSocket>(handle).abort();
+                        state.add_socket(SocketKind::Tcp(TcpEntry {
+                            handle,
+                            connected: true,
+                            orphaned: true,
+                        }));
+                    }
+                }
+                SocketKind::Udp(handle) => {
+                    state.iface.remove_socket(handle);
+                }
+                SocketKind::Icmp(icmp) => {
+                    state.iface.remove_socket(icmp.handle);
+                }
+                SocketKind::Raw(_) => {}
+            }
+        }
+        self.wake();
+    }
+}
+
+impl StackState {
+    fn add_socket(&mut self, mut kind: SocketKind) -> SocketId {
+        let id = self.next_socket_id;
+        self.next_socket_id += 1;
+        let readiness = kind.readiness(&mut self.iface);
+        self.sockets.insert(
+            id,
+            SocketEntry {
+                kind,
+                handler: None,
+                wakers: Vec::new(),
+                readiness,
+            },
+        );
+        id
+    }
+
+    fn ephemeral_port(&mut self, tcp: bool) -> Result<u16> {
+        for _ in EPHEMERAL_PORTS {
+            let port = self.next_ephemeral_port;
+            self.next_ephemeral_port = if port == *EPHEMERAL_PORTS.end() {
+                *EPHEMERAL_PORTS.start()
+            } else {
+                port + 1
+            };
+            if !self.port_in_use(tcp, port) {
+                return Ok(port);
+            }
+        }
+        Err(NetworkError::AddressInUse)
+    }
+
+    fn port_in_use(&mut self, tcp: bool, port: u16) -> bool {
+        let iface = &mut self.iface;
+        self.sockets.values().any(|entry| match &entry.kind {
+            SocketKind::Tcp(entry) if tcp => {
+                iface
+                    .get_socket::<TcpSocket>(entry.handle)
+                    .local_endpoint()
+                    .port
+                    == port
+            }
+            SocketKind::Listener(listener) if tcp => listener.addr.port() == port,
+            SocketKind::Udp(handle) if !tcp => {
+                iface.get_socket::<UdpSocket>(*handle).endpoint().port == port
+            }
+            _ => false,
+        })
+    }
+
+    /// Copies a received frame to the raw sockets that are interested in it
+    fn tap(&mut self, frame: &[u8]) {
+        if frame.len() < 14 {
+            return;
+        }
+        let for_us = frame[..6] == self.mac || frame[0] & 0x01 != 0;
+        let mut copy = None;
+        for entry in self.sockets.values_mut() {
+            if let SocketKind::Raw(raw) = &mut entry.kind {
+                if (for_us || raw.promiscuous) && raw.frames.len() < MAX_RAW_QUEUE {
+                    let frame = copy.get_or_insert_with(|| Bytes::copy_from_slice(frame));
+                    raw.frames.push_back(frame.clone());
+                }
+            }
+        }
+    }
+
+    /// Runs after every poll of the interface to hand over the established
+    /// connections to the listeners, track the DHCP lease, notify the
+    /// sockets that became ready and clean up the closed ones
+    fn dispatch(&mut self, activity: bool) {
+        self.dispatch_dhcp();
+
+        let iface = &mut self.iface;
+        let mut closed = Vec::new();
+        for (id, entry) in self.sockets.iter_mut() {
+            match &mut entry.kind {
+                SocketKind::Listener(listener) => {
+                    let addr = listener.addr;
+                    for handle in listener.listening.iter_mut() {
+                        let state = iface.get_socket::<TcpSocket>(*handle).state();
+                        if matches!(state, TcpState::Listen | TcpState::SynReceived) {
+                            continue;
+                        }
+                        // This socket now carries a connection so another
+                        // one takes its place in the backlog
+                        match listen_socket(iface, addr) {
+                            Ok(next) => {
+                                listener.accepted.push_back(std::mem::replace(handle, next));
+                            }
+                            Err(err) => {
+                                tracing::debug!(%addr, %err, "failed to relisten");
+                            }
+                        }
+                    }
+                }
+                SocketKind::Tcp(tcp) if tcp.orphaned => {
+                    let state = iface.get_socket::<TcpSocket>(tcp.handle).state();
+                    if matches!(state, TcpState::Closed | TcpState::TimeWait) {
+                        iface.remove_socket(tcp.handle);
+                        closed.push(*id);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let readiness = entry.kind.readiness(iface);
+            entry.notify(readiness, activity);
+        }
+        for id in closed {
+            self.sockets.remove(&id);
+        }
+    }
+
+    fn dispatch_dhcp(&mut self) {
+        let Some(dhcp) = self.dhcp.as_mut() else {
+            return;
+        };
+        let event = self.iface.get_socket::<Dhcpv4Socket>(dhcp.handle).poll();
+        match event {
+            Some(Dhcpv4Event::Configured(config)) => {
+                tracing::debug!(address = %config.address, router = ?config.router, "acquired a DHCP lease");
+                let previous = dhcp.addr.replace(config.address);
+                dhcp.wakers.drain(..).for_each(Waker::wake);
+                self.iface.update_ip_addrs(|addrs| {
+                    let addrs = owned(addrs);
+                    if let Some(previous) = previous {
+                        addrs.retain(|cidr| *cidr != SmolIpCidr::Ipv4(previous));
+                    }
+                    addrs.push(SmolIpCidr::Ipv4(config.address));
+                });
+                if let Some(router) = config.router {
+                    self.set_route(IpRoute {
+                        cidr: IpCidr {
+                            ip: Ipv4Addr::UNSPECIFIED.into(),
+                            prefix: 0,
+                        },
+                        via_router: Ipv4Addr::from(router).into(),
+                        preferred_until: None,
+                        expires_at: None,
+                    });
+                }
+            }
+            Some(Dhcpv4Event::Deconfigured) => {
+                tracing::debug!("lost the DHCP lease");
+                if let Some(previous) = dhcp.addr.take() {
+                    self.iface.update_ip_addrs(|addrs| {
+                        owned(addrs).retain(|cidr| *cidr != SmolIpCidr::Ipv4(previous));
+                    });
+                    self.remove_routes(Ipv4Addr::UNSPECIFIED.into());
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn set_route(&mut self, route: IpRoute) {
+        let cidr = SmolIpCidr::new(route.cidr.ip.into(), route.cidr.prefix);
+        let via_router = route.via_router.into();
+        self.iface.routes_mut().update(|routes| {
+            routes
+                .insert(
+                    cidr,
+                    Route {
+                        via_router,
+                        preferred_until: None,
+                        expires_at: None,
+                    },
+                )
+                .ok();
+        });
+        self.routes.retain(|r| r.cidr != route.cidr);
+        self.routes.push(route);
+    }
+
+    fn remove_routes(&mut self, ip: IpAddr) {
+        let removed: Vec<_> = self
+            .routes
+            .iter()
+            .filter(|route| route.cidr.ip == ip)
+            .map(|route| SmolIpCidr::new(route.cidr.ip.into(), route.cidr.prefix))
+            .collect();
+        self.iface.routes_mut().update(|routes| {
+            for cidr in removed {
+                routes.remove(&cidr);
+            }
+        });
+        self.routes.retain(|route| route.cidr.ip != ip);
+    }
+
+    fn has_ip(&self, ip: IpAddr) -> bool {
+        ip.is_unspecified() || self.iface.has_ip_addr(ip)
+    }
+}
+
+impl SocketKind {
+    fn readiness(&mut self, iface: &mut Interface<'static, VirtualEthernet>) -> Readiness {
+        match self {
+            SocketKind::Tcp(tcp) => {
+                let socket = iface.get_socket::<TcpSocket>(tcp.handle);
+                if !matches!(
+                    socket.state(),
+                    TcpState::Closed | TcpState::Listen | TcpState::SynSent | TcpState::SynReceived
+                ) {
+                    tcp.connected = true;
+                }
+                let failed = !tcp.connected && socket.state() == TcpState::Closed;
+                Readiness {
+                    readable: socket.recv_queue(),
+                    writable: socket.can_send(),
+                    hangup: failed || (tcp.connected && !socket.may_recv()),
+                }
+            }
+            SocketKind::Listener(listener) => Readiness {
+                readable: listener.accepted.len(),
+                writable: false,
+                hangup: false,
+            },
+            SocketKind::Udp(handle) => {
+                let socket = iface.get_socket::<UdpSocket>(*handle);
+                Readiness {
+                    readable: socket.can_recv() as usize,
+                    writable: socket.can_send(),
+                    hangup: false,
+                }
+            }
+            SocketKind::Icmp(icmp) => {
+                let socket = iface.get_socket::<IcmpSocket>(icmp.handle);
+                Readiness {
+                    readable: socket.can_recv() as usize,
+                    writable: socket.can_send(),
+                    hangup: false,
+                }
+            }
+            SocketKind::Raw(raw) => Readiness {
+                readable: raw.frames.len(),
+                writable: true,
+                hangup: false,
+            },
+        }
+    }
+}
+
+impl SocketEntry {
+    /// Notifies the handler and wakers about the readiness that changed
+    /// since the last time, datagram sockets can't tell how many packets
+    /// are queued so they are notified again on any activity
+    fn notify(&mut self, readiness: Readiness, activity: bool) {
+        let datagram = matches!(self.kind, SocketKind::Udp(_) | SocketKind::Icmp(_));
+        let previous = std::mem::replace(&mut self.readiness, readiness);
+
+        let mut interests = Vec::new();
+        if readiness.readable > previous.readable
+            || (datagram && activity && readiness.readable > 0)
+        {
+            interests.push(InterestType::Readable);
+        }
+        if readiness.writable && !previous.writable {
+            interests.push(InterestType::Writable);
+        }
+        if readiness.hangup && !previous.hangup {
+            interests.push(InterestType::Readable);
+            interests.push(InterestType::Closed);
+        }
+        if interests.is_empty() {
+            return;
+        }
+        if let Some(handler) = self.handler.as_mut() {
+            for interest in interests {
+                handler.push_interest(interest);
+            }
+        }
+        self.wakers.drain(..).for_each(Waker::wake);
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) {
+        if self.readiness.readable > 0 || self.readiness.hangup {
+            handler.push_interest(InterestType::Readable);
+        }
+        if self.readiness.writable {
+            handler.push_interest(InterestType::Writable);
+        }
+        if self.readiness.hangup {
+            handler.push_interest(InterestType::Closed);
+        }
+        self.handler.replace(handler);
+    }
+
+    fn add_waker(&mut self, waker: &Waker) {
+        add_waker(&mut self.wakers, waker);
+    }
+}
+
+fn add_waker(wakers: &mut Vec<Waker>, waker: &Waker) {
+    if !wakers.iter().any(|w| w.will_wake(waker)) {
+        wakers.push(waker.clone());
+    }
+}
+
+fn listen_socket(
+    iface: &mut Interface<'static, VirtualEthernet>,
+    addr: SocketAddr,
+) -> smoltcp::Result<SocketHandle> {
+    let mut socket = new_tcp_socket();
+    socket.listen(endpoint(addr))?;
+    Ok(iface.add_socket(socket))
+}
+
+fn new_tcp_socket() -> TcpSocket<'static> {
+    TcpSocket::new(
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+    )
+}
+
+fn endpoint(addr: SocketAddr) -> IpEndpoint {
+    if addr.ip().is_unspecified() {
+        IpEndpoint::new(IpAddress::Unspecified, addr.port())
+    } else {
+        addr.into()
+    }
+}
+
+fn socket_addr(endpoint: IpEndpoint) -> SocketAddr {
+    SocketAddr::new(ip_addr(endpoint.addr), endpoint.port)
+}
+
+fn ip_addr(addr: IpAddress) -> IpAddr {
+    match addr {
+        IpAddress::Unspecified => Ipv4Addr::UNSPECIFIED.into(),
+        addr => addr.into(),
+    }
+}
+
+/// The addresses of the interface are always stored in a `Vec`
+fn owned<'a, T>(slice: &'a mut managed::ManagedSlice<'static, T>) -> &'a mut Vec<T> {
+    match slice {
+        managed::ManagedSlice::Owned(vec) => vec,
+        managed::ManagedSlice::Borrowed(_) => unreachable!("the interface owns its addresses"),
+    }
+}
+
+fn random_u64() -> u64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.finish()
+}
+
+fn smol_err_into_net_error(err: smoltcp::Error) -> NetworkError {
+    match err {
+        smoltcp::Error::Exhausted => NetworkError::WouldBlock,
+        smoltcp::Error::Illegal => NetworkError::InvalidInput,
+        smoltcp::Error::Unaddressable => NetworkError::AddressNotAvailable,
+        smoltcp::Error::Finished => NetworkError::NotConnected,
+        smoltcp::Error::Truncated => NetworkError::InvalidInput,
+        _ => NetworkError::IOError,
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for UserSpaceNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        _security: StreamSecurity,
+    ) -> Result<()> {
+        // Frames never leave the process so any security level is met
+        let (switches, vlan) = {
+            let state = self.stack.state.lock().unwrap();
+            (state.switches.clone(), state.vlan)
+        };
+        let switches = switches
+            .as_ref()
+            .unwrap_or_else(|| SwitchRegistry::global());
+        let switch = switches.switch(network, access_token)?;
+        let port = switch.join(Arc::new(PeerLink(Arc::downgrade(&self.stack))), vlan);
+        self.set_link(Some(Arc::new(port)));
+        Ok(())
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.set_link(None);
+        Ok(())
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        {
+            let mut state = self.stack.state.lock().unwrap();
+            if state.dhcp.is_none() {
+                let handle = state.iface.add_socket(Dhcpv4Socket::new());
+                state.dhcp = Some(DhcpState {
+                    handle,
+                    addr: None,
+                    wakers: Vec::new(),
+                });
+            }
+        }
+        self.stack.wake();
+
+        let lease = poll_fn(|cx| {
+            let mut state = self.stack.state.lock().unwrap();
+            match state.dhcp.as_mut() {
+                Some(DhcpState {
+                    addr: Some(addr), ..
+                }) => Poll::Ready(Ok(*addr)),
+                Some(dhcp) => {
+                    add_waker(&mut dhcp.wakers, cx.waker());
+                    Poll::Pending
+                }
+                None => Poll::Ready(Err(NetworkError::Interrupted)),
+            }
+        });
+        tokio::time::timeout(DHCP_TIMEOUT, lease)
+            .await
+            .map_err(|_| NetworkError::TimedOut)??;
+
+        let state = self.stack.state.lock().unwrap();
+        Ok(state
+            .iface
+            .ip_addrs()
+            .iter()
+            .map(|cidr| ip_addr(cidr.address()))
+            .collect())
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        let cidr = SmolIpCidr::new(ip.into(), prefix);
+        let mut state = self.stack.state.lock().unwrap();
+        state.iface.update_ip_addrs(|addrs| {
+            let addrs = owned(addrs);
+            addrs.retain(|c| c.address() != cidr.address());
+            addrs.push(cidr);
+        });
+        Ok(())
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        let ip: IpAddress = ip.into();
+        let mut state = self.stack.state.lock().unwrap();
+        state.iface.update_ip_addrs(|addrs| {
+            owned(addrs).retain(|c| c.address() != ip);
+        });
+        Ok(())
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        let mut state = self.stack.state.lock().unwrap();
+        state.iface.update_ip_addrs(|addrs| owned(addrs).clear());
+        Ok(())
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        let state = self.stack.state.lock().unwrap();
+        Ok(state
+            .iface
+            .ip_addrs()
+            .iter()
+            .map(|cidr| IpCidr {
+                ip: ip_addr(cidr.address()),
+                prefix: cidr.prefix_len(),
+            })
+            .collect())
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        let state = self.stack.state.lock().unwrap();
+        Ok(state.mac)
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        let any: IpAddr = match ip {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let mut state = self.stack.state.lock().unwrap();
+        state.set_route(IpRoute {
+            cidr: IpCidr { ip: any, prefix: 0 },
+            via_router: ip,
+            preferred_until: None,
+            expires_at: None,
+        });
+        Ok(())
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        let mut state = self.stack.state.lock().unwrap();
+        state.set_route(IpRoute {
+            cidr,
+            via_router,
+            preferred_until,
+            expires_at,
+        });
+        Ok(())
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        let mut state = self.stack.state.lock().unwrap();
+        state.remove_routes(cidr);
+        Ok(())
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        let mut state = self.stack.state.lock().unwrap();
+        state.iface.routes_mut().update(|routes| routes.clear());
+        state.routes.clear();
+        Ok(())
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        let state = self.stack.state.lock().unwrap();
+        Ok(state.routes.clone())
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let id = self.stack.add_socket(SocketKind::Raw(RawEntry {
+            frames: VecDeque::new(),
+            promiscuous: false,
+        }));
+        Ok(Box::new(UserSpaceRawSocket::new(self.stack.clone(), id)))
+    }
+
+    async fn listen_tcp(
+        &self,
+        mut addr: SocketAddr,
+        _only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let id = {
+            let mut state = self.stack.state.lock().unwrap();
+            if !state.has_ip(addr.ip()) {
+                return Err(NetworkError::AddressNotAvailable);
+            }
+            if addr.port() == 0 {
+                addr.set_port(state.ephemeral_port(true)?);
+            } else if !(reuse_port || reuse_addr) && state.port_in_use(true, addr.port()) {
+                return Err(NetworkError::AddressInUse);
+            }
+
+            let mut listening = Vec::with_capacity(LISTEN_BACKLOG);
+            for _ in 0..LISTEN_BACKLOG {
+                match listen_socket(&mut state.iface, addr) {
+                    Ok(handle) => listening.push(handle),
+                    Err(err) => {
+                        for handle in listening {
+                            state.iface.remove_socket(handle);
+                        }
+                        return Err(smol_err_into_net_error(err));
+                    }
+                }
+            }
+            state.add_socket(SocketKind::Listener(ListenerEntry {
+                addr,
+                listening,
+                accepted: VecDeque::new(),
+            }))
+        };
+        self.stack.wake();
+        Ok(Box::new(UserSpaceTcpListener::new(self.stack.clone(), id)))
+    }
+
+    async fn bind_udp(
+        &self,
+        mut addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let id = {
+            let mut state = self.stack.state.lock().unwrap();
+            if !state.has_ip(addr.ip()) {
+                return Err(NetworkError::AddressNotAvailable);
+            }
+            if addr.port() == 0 {
+                addr.set_port(state.ephemeral_port(false)?);
+            } else if !(reuse_port || reuse_addr) && state.port_in_use(false, addr.port()) {
+                return Err(NetworkError::AddressInUse);
+            }
+
+            let mut socket = UdpSocket::new(
+                UdpSocketBuffer::new(
+                    vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+                    vec![0; UDP_BUFFER_SIZE],
+                ),
+                UdpSocketBuffer::new(
+                    vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+                    vec![0; UDP_BUFFER_SIZE],
+                ),
+            );
+            socket
+                .bind(endpoint(addr))
+                .map_err(smol_err_into_net_error)?;
+            let handle = state.iface.add_socket(socket);
+            state.add_socket(SocketKind::Udp(handle))
+        };
+        self.stack.wake();
+        Ok(Box::new(UserSpaceUdpSocket::new(self.stack.clone(), id)))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let id = {
+            let mut state = self.stack.state.lock().unwrap();
+            if !state.has_ip(addr) {
+                return Err(NetworkError::AddressNotAvailable);
+            }
+            // The socket is bound to the identifier of the first echo
+            // request that is sent through it
+            let socket = IcmpSocket::new(
+                IcmpSocketBuffer::new(
+                    vec![IcmpPacketMetadata::EMPTY; ICMP_PACKETS],
+                    vec![0; ICMP_BUFFER_SIZE],
+                ),
+                IcmpSocketBuffer::new(
+                    vec![IcmpPacketMetadata::EMPTY; ICMP_PACKETS],
+                    vec![0; ICMP_BUFFER_SIZE],
+                ),
+            );
+            let handle = state.iface.add_socket(socket);
+            state.add_socket(SocketKind::Icmp(IcmpEntry { handle, addr }))
+        };
+        Ok(Box::new(UserSpaceIcmpSocket::new(self.stack.clone(), id)))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let id = {
+            let mut state = self.stack.state.lock().unwrap();
+            if !state.has_ip(addr.ip()) {
+                return Err(NetworkError::AddressNotAvailable);
+            }
+            let port = match addr.port() {
+                0 => state.ephemeral_port(true)?,
+                port => port,
+            };
+
+            let mut socket = new_tcp_socket();
+            socket.set_timeout(Some(CONNECT_TIMEOUT.into()));
+            let handle = state.iface.add_socket(socket);
+            let (socket, cx) = state.iface.get_socket_and_context::<TcpSocket>(handle);
+            if let Err(err) = socket.connect(cx, peer, endpoint(SocketAddr::new(addr.ip(), port))) {
+                state.iface.remove_socket(handle);
+                return Err(smol_err_into_net_error(err));
+            }
+            state.add_socket(SocketKind::Tcp(TcpEntry {
+                handle,
+                connected: false,
+                orphaned: false,
+            }))
+        };
+        self.stack.wake();
+
+        let mut socket = UserSpaceTcpSocket::new(self.stack.clone(), id);
+        poll_fn(|cx| socket.poll_connected(cx)).await?;
+        Ok(Box::new(socket))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        // There is no DNS client in the stack, only literal addresses
+        // can be resolved
+        host.parse::<IpAddr>()
+            .map(|ip| vec![ip])
+            .map_err(|_| NetworkError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+
+    use super::*;
+    use crate::{VirtualConnectedSocketExt, VirtualConnectionlessSocketExt, VirtualTcpListenerExt};
+
+    async fn pair() -> (UserSpaceNetworking, UserSpaceNetworking) {
+        let a = UserSpaceNetworking::new(&Handle::current());
+        let b = UserSpaceNetworking::new(&Handle::current());
+        a.connect(&b);
+        a.ip_add(Ipv4Addr::new(10, 0, 0, 1).into(), 24)
+            .await
+            .unwrap();
+        b.ip_add(Ipv4Addr::new(10, 0, 0, 2).into(), 24)
+            .await
+            .unwrap();
+        (a, b)
+    }
+
+    async fn recv_exact(socket: &mut Box<dyn VirtualTcpSocket + Sync>, len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        while data.len() < len {
+            let mut buf = [MaybeUninit::uninit(); 4096];
+            let read = socket.recv(&mut buf, false).await.unwrap();
+            assert_ne!(read, 0, "unexpected end of stream");
+            data.extend(buf[..read].iter().map(|b| unsafe { b.assume_init() }));
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_user_space_tcp() {
+        let (a, b) = pair().await;
+        let server_addr: SocketAddr = "10.0.0.2:8080".parse().unwrap();
+
+        let mut listener = b
+            .listen_tcp(server_addr, false, false, false)
+            .await
+            .unwrap();
+        assert_eq!(listener.addr_local().unwrap(), server_addr);
+        assert_eq!(
+            b.listen_tcp(server_addr, false, false, false)
+                .await
+                .unwrap_err(),
+            NetworkError::AddressInUse
+        );
+
+        let mut client = a
+            .connect_tcp("0.0.0.0:0".parse().unwrap(), server_addr)
+            .await
+            .unwrap();
+        let (mut server, peer) = listener.accept().await.unwrap();
+        assert_eq!(peer.ip(), IpAddr::from(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(client.addr_peer().unwrap(), server_addr);
+
+        // More than the socket buffers so that backpressure kicks in
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let send = async {
+            let mut sent = 0;
+            while sent < data.len() {
+                sent += client.send(&data[sent..]).await.unwrap();
+            }
+            client.shutdown(std::net::Shutdown::Write).unwrap();
+            client
+        };
+        let (mut client, received) = tokio::join!(send, recv_exact(&mut server, data.len()));
+        assert_eq!(received, data);
+
+        // The client closed its side of the connection
+        let mut buf = [MaybeUninit::uninit(); 16];
+        assert_eq!(server.recv(&mut buf, false).await.unwrap(), 0);
+
+        server.send(b"bye").await.unwrap();
+        assert_eq!(recv_exact(&mut client, 3).await, b"bye");
+
+        // Nobody is listening on that port
+        let err = a
+            .connect_tcp(
+                "0.0.0.0:0".parse().unwrap(),
+                "10.0.0.2:9090".parse().unwrap(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err, NetworkError::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn test_user_space_udp() {
+        let (a, b) = pair().await;
+
+        let mut sa = a
+            .bind_udp("10.0.0.1:5000".parse().unwrap(), false, false)
+            .await
+            .unwrap();
+        let mut sb = b
+            .bind_udp("0.0.0.0:6000".parse().unwrap(), false, false)
+            .await
+            .unwrap();
+
+        sa.send_to(b"ping", "10.0.0.2:6000".parse().unwrap())
+            .await
+            .unwrap();
+        let mut buf = [MaybeUninit::uninit(); 64];
+        let (read, from) = sb.recv_from(&mut buf, false).await.unwrap();
+        assert_eq!(from, "10.0.0.1:5000".parse::<SocketAddr>().unwrap());
+        let data: Vec<u8> = buf[..read]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        assert_eq!(data, b"ping");
+    }
+
+    #[tokio::test]
+    async fn test_user_space_icmp_and_raw() {
+        let (a, b) = pair().await;
+        let mut raw = b.bind_raw().await.unwrap();
+
+        // Echo request with identifier 0x1234 and sequence number 1, the
+        // stack fills in the checksum
+        let request = [8, 0, 0, 0, 0x12, 0x34, 0, 1, b'h', b'i'];
+
+        let mut icmp = a.bind_icmp(Ipv4Addr::UNSPECIFIED.into()).await.unwrap();
+        icmp.send_to(&request, "10.0.0.2:0".parse().unwrap())
+            .await
+            .unwrap();
+
+        // The other stack answers the ping by itself
+        let mut buf = [MaybeUninit::uninit(); 64];
+        let (read, from) = icmp.recv_from(&mut buf, false).await.unwrap();
+        assert_eq!(from.ip(), IpAddr::from(Ipv4Addr::new(10, 0, 0, 2)));
+        let reply: Vec<u8> = buf[..read]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        assert_eq!(reply[0], 0);
+        assert_eq!(&reply[4..], &request[4..]);
+
+        // And its raw socket saw the frames that were addressed to it
+        let mut frame = [MaybeUninit::uninit(); MAX_FRAME_SIZE];
+        let read = raw.try_recv(&mut frame, false).unwrap();
+        assert!(read >= 14);
+    }
+
+    #[tokio::test]
+    async fn test_user_space_bridge() {
+        let switches = SwitchRegistry::new();
+        let mut stacks = Vec::new();
+        for (i, vlan) in [(1, None), (2, None), (3, Some(7))] {
+            let net = UserSpaceNetworking::new(&Handle::current());
+            net.set_switches(switches.clone());
+            net.set_vlan(vlan);
+            net.bridge("lan", "token", StreamSecurity::Unencrypted)
+                .await
+                .unwrap();
+            net.ip_add(Ipv4Addr::new(10, 1, 0, i).into(), 24)
+                .await
+                .unwrap();
+            stacks.push(net);
+        }
+        let other = UserSpaceNetworking::new(&Handle::current());
+        other.set_switches(switches.clone());
+        assert_eq!(
+            other
+                .bridge("lan", "nope", StreamSecurity::Unencrypted)
+                .await
+                .unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+
+        let socket = |i: usize| {
+            let net = stacks[i].clone();
+            async move {
+                net.bind_udp(
+                    SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 7000),
+                    false,
+                    false,
+                )
+                .await
+                .unwrap()
+            }
+        };
+        let mut s1 = socket(0).await;
+        let mut s2 = socket(1).await;
+        let mut s3 = socket(2).await;
+
+        s1.send_to(b"hello", "10.1.0.2:7000".parse().unwrap())
+            .await
+            .unwrap();
+        let mut buf = [MaybeUninit::uninit(); 64];
+        let (read, from) = s2.recv_from(&mut buf, false).await.unwrap();
+        assert_eq!((read, from), (5, "10.1.0.1:7000".parse().unwrap()));
+
+        // The third stack is on another VLAN so it can't even resolve the
+        // address of the others
+        s3.send_to(b"hello", "10.1.0.2:7000".parse().unwrap())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            s2.try_recv_from(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        stacks[1].unbridge().await.unwrap();
+        s1.send_to(b"again", "10.1.0.2:7000".parse().unwrap())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            s2.try_recv_from(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_space_addresses_and_routes() {
+        let net = UserSpaceNetworking::with_mac(&Handle::current(), [0x02, 0, 0, 0, 0, 1]);
+        assert_eq!(net.mac().await.unwrap(), [0x02, 0, 0, 0, 0, 1]);
+
+        net.ip_add(Ipv4Addr::new(192, 168, 1, 10).into(), 24)
+            .await
+            .unwrap();
+        net.ip_add(Ipv4Addr::new(192, 168, 2, 10).into(), 24)
+            .await
+            .unwrap();
+        net.ip_remove(Ipv4Addr::new(192, 168, 1, 10).into())
+            .await
+            .unwrap();
+        assert_eq!(
+            net.ip_list().await.unwrap(),
+            vec![IpCidr {
+                ip: Ipv4Addr::new(192, 168, 2, 10).into(),
+                prefix: 24
+            }]
+        );
+
+        // Addresses that aren't assigned to the interface can't be bound
+        assert_eq!(
+            net.bind_udp("192.168.1.10:53".parse().unwrap(), false, false)
+                .await
+                .unwrap_err(),
+            NetworkError::AddressNotAvailable
+        );
+
+        net.gateway_set(Ipv4Addr::new(192, 168, 2, 1).into())
+            .await
+            .unwrap();
+        let routes = net.route_list().await.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(
+            routes[0].via_router,
+            IpAddr::from(Ipv4Addr::new(192, 168, 2, 1))
+        );
+        net.route_remove(Ipv4Addr::UNSPECIFIED.into())
+            .await
+            .unwrap();
+        assert!(net.route_list().await.unwrap().is_empty());
+    }
+}
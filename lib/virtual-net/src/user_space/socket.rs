@@ -0,0 +1,834 @@
+use std::mem::MaybeUninit;
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use bytes::Bytes;
+use smoltcp::iface::Interface;
+use smoltcp::socket::{IcmpEndpoint, IcmpSocket, TcpSocket, TcpState, UdpSocket};
+
+use super::{
+    add_waker,
+    device::{VirtualEthernet, MAX_FRAME_SIZE},
+    ip_addr, smol_err_into_net_error, socket_addr, IcmpEntry, RawEntry, SocketEntry, SocketId,
+    SocketKind, Stack, StackState, TcpEntry,
+};
+use crate::{
+    InterestHandler, NetworkError, Result, SocketStatus, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualIoSource, VirtualRawSocket,
+    VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(75);
+
+type Iface = Interface<'static, VirtualEthernet>;
+
+fn hop_limit(ttl: u32) -> Result<Option<u8>> {
+    match ttl {
+        0 => Err(NetworkError::InvalidInput),
+        ttl => Ok(Some(ttl.min(u8::MAX as u32) as u8)),
+    }
+}
+
+fn copy_to(buf: &mut [MaybeUninit<u8>], data: &[u8]) -> usize {
+    let buf: &mut [u8] = unsafe { std::mem::transmute(buf) };
+    let amt = buf.len().min(data.len());
+    buf[..amt].copy_from_slice(&data[..amt]);
+    amt
+}
+
+/// TCP connection running on a [`super::UserSpaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserSpaceTcpSocket {
+    stack: Arc<Stack>,
+    id: SocketId,
+    linger: Option<Duration>,
+    dontroute: bool,
+    shut_read: bool,
+}
+
+impl UserSpaceTcpSocket {
+    pub(super) fn new(stack: Arc<Stack>, id: SocketId) -> Self {
+        Self {
+            stack,
+            id,
+            linger: None,
+            dontroute: false,
+            shut_read: false,
+        }
+    }
+
+    fn with_tcp<R>(
+        &self,
+        f: impl FnOnce(&mut TcpSocket<'static>, &TcpEntry, &mut Vec<Waker>) -> R,
+    ) -> R {
+        self.stack.with_entry(self.id, |iface, entry| {
+            let SocketEntry { kind, wakers, .. } = entry;
+            let SocketKind::Tcp(tcp) = kind else {
+                unreachable!("not a TCP socket");
+            };
+            f(iface.get_socket::<TcpSocket>(tcp.handle), tcp, wakers)
+        })
+    }
+
+    /// Waits for the connection to be established
+    pub(super) fn poll_connected(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.stack.with_entry(self.id, |iface, entry| {
+            entry.readiness = entry.kind.readiness(iface);
+            let SocketKind::Tcp(tcp) = &entry.kind else {
+                unreachable!("not a TCP socket");
+            };
+            let socket = iface.get_socket::<TcpSocket>(tcp.handle);
+            if tcp.connected {
+                socket.set_timeout(None);
+                Poll::Ready(Ok(()))
+            } else if socket.state() == TcpState::Closed {
+                Poll::Ready(Err(NetworkError::ConnectionRefused))
+            } else {
+                entry.add_waker(cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl Drop for UserSpaceTcpSocket {
+    fn drop(&mut self) {
+        self.stack.close_socket(self.id);
+    }
+}
+
+impl VirtualIoSource for UserSpaceTcpSocket {
+    fn remove_handler(&mut self) {
+        self.stack
+            .with_entry(self.id, |_, entry| entry.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let shut_read = self.shut_read;
+        self.with_tcp(|socket, tcp, wakers| {
+            if shut_read {
+                Poll::Ready(Ok(0))
+            } else if socket.can_recv() {
+                Poll::Ready(Ok(socket.recv_queue()))
+            } else if !tcp.connected && socket.state() == TcpState::Closed {
+                Poll::Ready(Err(NetworkError::ConnectionRefused))
+            } else if tcp.connected && !socket.may_recv() {
+                Poll::Ready(Ok(0))
+            } else {
+                add_waker(wakers, cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with_tcp(|socket, tcp, wakers| {
+            if socket.can_send() {
+                Poll::Ready(Ok(socket.send_capacity() - socket.send_queue()))
+            } else if !tcp.connected && socket.state() == TcpState::Closed {
+                Poll::Ready(Err(NetworkError::ConnectionRefused))
+            } else if tcp.connected && !socket.may_send() {
+                Poll::Ready(Ok(0))
+            } else {
+                add_waker(wakers, cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl VirtualSocket for UserSpaceTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = hop_limit(ttl)?;
+        self.with_tcp(|socket, _, _| socket.set_hop_limit(ttl));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.with_tcp(|socket, _, _| socket.hop_limit().unwrap_or(64)) as u32)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.with_tcp(|socket, _, _| socket_addr(socket.local_endpoint())))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(self.with_tcp(|socket, tcp, _| match socket.state() {
+            TcpState::SynSent | TcpState::SynReceived | TcpState::Listen => SocketStatus::Opening,
+            TcpState::Closed | TcpState::TimeWait if tcp.connected => SocketStatus::Closed,
+            TcpState::Closed => SocketStatus::Failed,
+            _ => SocketStatus::Opened,
+        }))
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.with_entry(self.id, |iface, entry| {
+            entry.readiness = entry.kind.readiness(iface);
+            entry.set_handler(handler);
+        });
+        Ok(())
+    }
+}
+
+impl VirtualConnectedSocket for UserSpaceTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.linger = linger;
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(self.linger)
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.with_tcp(|socket, tcp, _| {
+            if !socket.may_send() {
+                return Err(if tcp.connected {
+                    NetworkError::BrokenPipe
+                } else {
+                    NetworkError::NotConnected
+                });
+            }
+            match socket.send_slice(data) {
+                Ok(0) if !data.is_empty() => Err(NetworkError::WouldBlock),
+                Ok(amt) => Ok(amt),
+                Err(err) => Err(smol_err_into_net_error(err)),
+            }
+        })
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.with_tcp(|socket, _, _| socket.close());
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        if self.shut_read {
+            return Ok(0);
+        }
+        self.with_tcp(|socket, tcp, _| {
+            if socket.can_recv() {
+                let buf: &mut [u8] = unsafe { std::mem::transmute(buf) };
+                let ret = if peek {
+                    socket.peek_slice(buf)
+                } else {
+                    socket.recv_slice(buf)
+                };
+                ret.map_err(smol_err_into_net_error)
+            } else if !tcp.connected && socket.state() == TcpState::Closed {
+                Err(NetworkError::ConnectionRefused)
+            } else if tcp.connected && !socket.may_recv() {
+                Ok(0)
+            } else {
+                Err(NetworkError::WouldBlock)
+            }
+        })
+    }
+}
+
+impl VirtualTcpSocket for UserSpaceTcpSocket {
+    fn set_recv_buf_size(&mut self, _size: usize) -> Result<()> {
+        // The buffers of the stack are allocated up front
+        Err(NetworkError::Unsupported)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Ok(self.with_tcp(|socket, _, _| socket.recv_capacity()))
+    }
+
+    fn set_send_buf_size(&mut self, _size: usize) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Ok(self.with_tcp(|socket, _, _| socket.send_capacity()))
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.with_tcp(|socket, _, _| socket.set_nagle_enabled(!nodelay));
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(self.with_tcp(|socket, _, _| !socket.nagle_enabled()))
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        let interval = keepalive.then(|| KEEPALIVE_INTERVAL.into());
+        self.with_tcp(|socket, _, _| socket.set_keep_alive(interval));
+        Ok(())
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        Ok(self.with_tcp(|socket, _, _| socket.keep_alive().is_some()))
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.dontroute = dontroute;
+        Ok(())
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        Ok(self.dontroute)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.with_tcp(|socket, _, _| socket_addr(socket.remote_endpoint())))
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        if matches!(how, Shutdown::Read | Shutdown::Both) {
+            self.shut_read = true;
+        }
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.with_tcp(|socket, _, _| socket.close());
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.with_tcp(|socket, _, _| !socket.is_open())
+    }
+}
+
+/// TCP listener running on a [`super::UserSpaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserSpaceTcpListener {
+    stack: Arc<Stack>,
+    id: SocketId,
+    ttl: u8,
+}
+
+impl UserSpaceTcpListener {
+    pub(super) fn new(stack: Arc<Stack>, id: SocketId) -> Self {
+        Self { stack, id, ttl: 64 }
+    }
+}
+
+impl Drop for UserSpaceTcpListener {
+    fn drop(&mut self) {
+        self.stack.close_socket(self.id);
+    }
+}
+
+impl VirtualIoSource for UserSpaceTcpListener {
+    fn remove_handler(&mut self) {
+        self.stack
+            .with_entry(self.id, |_, entry| entry.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.stack.with_entry(self.id, |_, entry| {
+            let SocketKind::Listener(listener) = &entry.kind else {
+                unreachable!("not a TCP listener");
+            };
+            if !listener.accepted.is_empty() {
+                return Poll::Ready(Ok(listener.accepted.len()));
+            }
+            entry.add_waker(cx.waker());
+            Poll::Pending
+        })
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for UserSpaceTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (id, peer) = {
+            let mut state = self.stack.state.lock().unwrap();
+            let SocketKind::Listener(listener) = &mut state
+                .sockets
+                .get_mut(&self.id)
+                .expect("unknown socket")
+                .kind
+            else {
+                unreachable!("not a TCP listener");
+            };
+            let handle = listener
+                .accepted
+                .pop_front()
+                .ok_or(NetworkError::WouldBlock)?;
+
+            let socket = state.iface.get_socket::<TcpSocket>(handle);
+            socket.set_hop_limit(Some(self.ttl));
+            let peer = socket_addr(socket.remote_endpoint());
+            let id = state.add_socket(SocketKind::Tcp(TcpEntry {
+                handle,
+                connected: true,
+                orphaned: false,
+            }));
+
+            let StackState { iface, sockets, .. } = &mut *state;
+            let entry = sockets.get_mut(&self.id).unwrap();
+            entry.readiness = entry.kind.readiness(iface);
+            (id, peer)
+        };
+        let socket = UserSpaceTcpSocket::new(self.stack.clone(), id);
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.with_entry(self.id, |iface, entry| {
+            entry.readiness = entry.kind.readiness(iface);
+            entry.set_handler(handler);
+        });
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.stack.with_entry(self.id, |_, entry| {
+            let SocketKind::Listener(listener) = &entry.kind else {
+                unreachable!("not a TCP listener");
+            };
+            listener.addr
+        }))
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        if ttl == 0 {
+            return Err(NetworkError::InvalidInput);
+        }
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(self.ttl)
+    }
+}
+
+/// UDP socket running on a [`super::UserSpaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserSpaceUdpSocket {
+    stack: Arc<Stack>,
+    id: SocketId,
+    broadcast: bool,
+    multicast_loop_v4: bool,
+    multicast_loop_v6: bool,
+    multicast_ttl_v4: u32,
+}
+
+impl UserSpaceUdpSocket {
+    pub(super) fn new(stack: Arc<Stack>, id: SocketId) -> Self {
+        Self {
+            stack,
+            id,
+            broadcast: false,
+            multicast_loop_v4: false,
+            multicast_loop_v6: false,
+            multicast_ttl_v4: 1,
+        }
+    }
+
+    fn with_udp<R>(&self, f: impl FnOnce(&mut UdpSocket<'static>, &mut SocketEntry) -> R) -> R {
+        self.stack.with_entry(self.id, |iface, entry| {
+            let SocketKind::Udp(handle) = entry.kind else {
+                unreachable!("not a UDP socket");
+            };
+            f(iface.get_socket::<UdpSocket>(handle), entry)
+        })
+    }
+
+    fn with_iface<R>(&self, f: impl FnOnce(&mut Iface) -> R) -> R {
+        self.stack.with_entry(self.id, |iface, _| f(iface))
+    }
+}
+
+impl Drop for UserSpaceUdpSocket {
+    fn drop(&mut self) {
+        self.stack.close_socket(self.id);
+    }
+}
+
+impl VirtualIoSource for UserSpaceUdpSocket {
+    fn remove_handler(&mut self) {
+        self.stack
+            .with_entry(self.id, |_, entry| entry.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with_udp(|socket, entry| match socket.peek() {
+            Ok((data, _)) => Poll::Ready(Ok(data.len())),
+            Err(_) => {
+                entry.add_waker(cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with_udp(|socket, entry| {
+            if socket.can_send() {
+                Poll::Ready(Ok(socket.payload_send_capacity()))
+            } else {
+                entry.add_waker(cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl VirtualSocket for UserSpaceUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = hop_limit(ttl)?;
+        self.with_udp(|socket, _| socket.set_hop_limit(ttl));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.with_udp(|socket, _| socket.hop_limit().unwrap_or(64)) as u32)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.with_udp(|socket, _| socket_addr(socket.endpoint())))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.with_entry(self.id, |iface, entry| {
+            entry.readiness = entry.kind.readiness(iface);
+            entry.set_handler(handler);
+        });
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for UserSpaceUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.with_udp(|socket, _| {
+            socket
+                .send_slice(data, addr.into())
+                .map_err(smol_err_into_net_error)
+        })?;
+        Ok(data.len())
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        self.with_udp(|socket, _| {
+            let (data, endpoint) = if peek {
+                let (data, endpoint) = socket.peek().map_err(smol_err_into_net_error)?;
+                (data, *endpoint)
+            } else {
+                socket.recv().map_err(smol_err_into_net_error)?
+            };
+            Ok((copy_to(buf, data), socket_addr(endpoint)))
+        })
+    }
+}
+
+impl VirtualUdpSocket for UserSpaceUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.broadcast = broadcast;
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(self.broadcast)
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.multicast_loop_v4 = val;
+        Ok(())
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Ok(self.multicast_loop_v4)
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.multicast_loop_v6 = val;
+        Ok(())
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Ok(self.multicast_loop_v6)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.multicast_ttl_v4 = ttl;
+        Ok(())
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Ok(self.multicast_ttl_v4)
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        let now = self.stack.now();
+        self.with_iface(|iface| iface.join_multicast_group(multiaddr, now))
+            .map_err(smol_err_into_net_error)?;
+        Ok(())
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        let now = self.stack.now();
+        self.with_iface(|iface| iface.leave_multicast_group(multiaddr, now))
+            .map_err(smol_err_into_net_error)?;
+        Ok(())
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
+
+/// ICMP socket running on a [`super::UserSpaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserSpaceIcmpSocket {
+    stack: Arc<Stack>,
+    id: SocketId,
+}
+
+impl UserSpaceIcmpSocket {
+    pub(super) fn new(stack: Arc<Stack>, id: SocketId) -> Self {
+        Self { stack, id }
+    }
+
+    fn with_icmp<R>(
+        &self,
+        f: impl FnOnce(&mut IcmpSocket<'static>, &IcmpEntry, &mut Vec<Waker>) -> R,
+    ) -> R {
+        self.stack.with_entry(self.id, |iface, entry| {
+            let SocketEntry { kind, wakers, .. } = entry;
+            let SocketKind::Icmp(icmp) = kind else {
+                unreachable!("not an ICMP socket");
+            };
+            f(iface.get_socket::<IcmpSocket>(icmp.handle), icmp, wakers)
+        })
+    }
+}
+
+impl Drop for UserSpaceIcmpSocket {
+    fn drop(&mut self) {
+        self.stack.close_socket(self.id);
+    }
+}
+
+impl VirtualIoSource for UserSpaceIcmpSocket {
+    fn remove_handler(&mut self) {
+        self.stack
+            .with_entry(self.id, |_, entry| entry.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with_icmp(|socket, _, wakers| {
+            if socket.can_recv() {
+                Poll::Ready(Ok(1))
+            } else {
+                add_waker(wakers, cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with_icmp(|socket, _, wakers| {
+            if socket.can_send() {
+                Poll::Ready(Ok(socket.payload_send_capacity()))
+            } else {
+                add_waker(wakers, cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl VirtualSocket for UserSpaceIcmpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = hop_limit(ttl)?;
+        self.with_icmp(|socket, _, _| socket.set_hop_limit(ttl));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.with_icmp(|socket, _, _| socket.hop_limit().unwrap_or(64)) as u32)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.with_icmp(|_, icmp, _| SocketAddr::new(icmp.addr, 0)))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.with_entry(self.id, |iface, entry| {
+            entry.readiness = entry.kind.readiness(iface);
+            entry.set_handler(handler);
+        });
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for UserSpaceIcmpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.with_icmp(|socket, _, _| {
+            if !socket.is_open() {
+                // Replies are matched on the identifier of the echo requests
+                let ident = match data {
+                    [8 | 128, _, _, _, a, b, ..] => u16::from_be_bytes([*a, *b]),
+                    _ => 0,
+                };
+                socket
+                    .bind(IcmpEndpoint::Ident(ident))
+                    .map_err(smol_err_into_net_error)?;
+            }
+            socket
+                .send_slice(data, addr.ip().into())
+                .map_err(smol_err_into_net_error)
+        })?;
+        Ok(data.len())
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        _peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        self.with_icmp(|socket, _, _| {
+            let (data, addr) = socket.recv().map_err(smol_err_into_net_error)?;
+            Ok((copy_to(buf, data), SocketAddr::new(ip_addr(addr), 0)))
+        })
+    }
+}
+
+impl VirtualIcmpSocket for UserSpaceIcmpSocket {}
+
+/// Raw socket that sends and receives Ethernet frames on the interface of
+/// a [`super::UserSpaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserSpaceRawSocket {
+    stack: Arc<Stack>,
+    id: SocketId,
+}
+
+impl UserSpaceRawSocket {
+    pub(super) fn new(stack: Arc<Stack>, id: SocketId) -> Self {
+        Self { stack, id }
+    }
+
+    fn with_raw<R>(&self, f: impl FnOnce(&mut RawEntry, &mut Iface) -> R) -> R {
+        self.stack.with_entry(self.id, |iface, entry| {
+            let SocketKind::Raw(raw) = &mut entry.kind else {
+                unreachable!("not a raw socket");
+            };
+            f(raw, iface)
+        })
+    }
+}
+
+impl Drop for UserSpaceRawSocket {
+    fn drop(&mut self) {
+        self.stack.close_socket(self.id);
+    }
+}
+
+impl VirtualIoSource for UserSpaceRawSocket {
+    fn remove_handler(&mut self) {
+        self.stack
+            .with_entry(self.id, |_, entry| entry.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.stack.with_entry(self.id, |_, entry| {
+            let SocketKind::Raw(raw) = &entry.kind else {
+                unreachable!("not a raw socket");
+            };
+            if let Some(frame) = raw.frames.front() {
+                return Poll::Ready(Ok(frame.len()));
+            }
+            entry.add_waker(cx.waker());
+            Poll::Pending
+        })
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(MAX_FRAME_SIZE))
+    }
+}
+
+impl VirtualSocket for UserSpaceRawSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(64)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.with_entry(self.id, |iface, entry| {
+            entry.readiness = entry.kind.readiness(iface);
+            entry.set_handler(handler);
+        });
+        Ok(())
+    }
+}
+
+impl VirtualRawSocket for UserSpaceRawSocket {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        if data.len() < 14 || data.len() > MAX_FRAME_SIZE {
+            return Err(NetworkError::InvalidInput);
+        }
+        self.with_raw(|_, iface| iface.device_mut().push_tx(Bytes::copy_from_slice(data)));
+        Ok(data.len())
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        self.with_raw(|raw, _| {
+            let frame = if peek {
+                raw.frames.front().cloned()
+            } else {
+                raw.frames.pop_front()
+            };
+            let frame = frame.ok_or(NetworkError::WouldBlock)?;
+            Ok(copy_to(buf, &frame))
+        })
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.with_raw(|raw, _| raw.promiscuous = promiscuous);
+        Ok(())
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        Ok(self.with_raw(|raw, _| raw.promiscuous))
+    }
+}
@@ -0,0 +1,322 @@
+//! An in-process Ethernet switch that connects the interfaces of several
+//! [`super::UserSpaceNetworking`] stacks (or any other [`EthernetLink`]).
+//!
+//! The switch learns which port every MAC address is behind so that unicast
+//! frames are only forwarded to the port of their destination, everything
+//! else is flooded. Ports can be put on a VLAN in which case they only ever
+//! exchange frames with the other ports of the same VLAN.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use super::EthernetLink;
+use crate::{NetworkError, Result};
+
+/// How long a learned MAC address is remembered without seeing any frame
+/// coming from it
+const MAC_AGING_TIME: Duration = Duration::from_secs(300);
+
+type PortId = u64;
+type Mac = [u8; 6];
+
+/// Virtual Ethernet switch with MAC learning and port based VLANs
+#[derive(Debug, Clone, Default)]
+pub struct VirtualSwitch {
+    inner: Arc<SwitchInner>,
+}
+
+#[derive(Debug, Default)]
+struct SwitchInner {
+    state: Mutex<SwitchState>,
+}
+
+#[derive(Debug, Default)]
+struct SwitchState {
+    ports: HashMap<PortId, PortState>,
+    next_port_id: PortId,
+    macs: HashMap<(Option<u16>, Mac), LearnedMac>,
+}
+
+#[derive(Debug)]
+struct PortState {
+    link: Arc<dyn EthernetLink>,
+    vlan: Option<u16>,
+}
+
+#[derive(Debug)]
+struct LearnedMac {
+    port: PortId,
+    seen: Instant,
+}
+
+/// A port of a [`VirtualSwitch`], the frames sent through it are switched
+/// to the other ports and it leaves the switch when dropped
+#[derive(Debug)]
+pub struct SwitchPort {
+    switch: Arc<SwitchInner>,
+    id: PortId,
+    vlan: Option<u16>,
+}
+
+impl VirtualSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plugs a link into a new port of the switch, the link receives the
+    /// frames that are switched to this port
+    pub fn join(&self, link: Arc<dyn EthernetLink>, vlan: Option<u16>) -> SwitchPort {
+        let mut state = self.inner.state.lock().unwrap();
+        let id = state.next_port_id;
+        state.next_port_id += 1;
+        state.ports.insert(id, PortState { link, vlan });
+        SwitchPort {
+            switch: self.inner.clone(),
+            id,
+            vlan,
+        }
+    }
+
+    /// Number of ports that are plugged into the switch
+    pub fn port_count(&self) -> usize {
+        let state = self.inner.state.lock().unwrap();
+        state.ports.len()
+    }
+}
+
+impl SwitchInner {
+    fn forward(&self, from: PortId, vlan: Option<u16>, frame: Bytes) {
+        if frame.len() < 14 {
+            return;
+        }
+        let dst: Mac = frame[..6].try_into().unwrap();
+        let src: Mac = frame[6..12].try_into().unwrap();
+
+        let targets: Vec<_> = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            // Multicast addresses are never a source
+            if src[0] & 0x01 == 0 {
+                state.macs.insert(
+                    (vlan, src),
+                    LearnedMac {
+                        port: from,
+                        seen: now,
+                    },
+                );
+            }
+
+            let known = if dst[0] & 0x01 == 0 {
+                state
+                    .macs
+                    .get(&(vlan, dst))
+                    .filter(|learned| now.duration_since(learned.seen) < MAC_AGING_TIME)
+                    .map(|learned| learned.port)
+            } else {
+                None
+            };
+            match known {
+                // The destination is on the port the frame came from
+                Some(port) if port == from => return,
+                Some(port) => state
+                    .ports
+                    .get(&port)
+                    .map(|port| port.link.clone())
+                    .into_iter()
+                    .collect(),
+                None => {
+                    if state.macs.len() > 4096 {
+                        state
+                            .macs
+                            .retain(|_, learned| now.duration_since(learned.seen) < MAC_AGING_TIME);
+                    }
+                    state
+                        .ports
+                        .iter()
+                        .filter(|(id, port)| **id != from && port.vlan == vlan)
+                        .map(|(_, port)| port.link.clone())
+                        .collect()
+                }
+            }
+        };
+
+        // The links are called without holding the lock as they might
+        // send frames back into the switch
+        for link in targets {
+            link.send(frame.clone());
+        }
+    }
+}
+
+impl SwitchPort {
+    pub fn vlan(&self) -> Option<u16> {
+        self.vlan
+    }
+}
+
+impl EthernetLink for SwitchPort {
+    fn send(&self, frame: Bytes) {
+        self.switch.forward(self.id, self.vlan, frame);
+    }
+}
+
+impl Drop for SwitchPort {
+    fn drop(&mut self) {
+        let mut state = self.switch.state.lock().unwrap();
+        state.ports.remove(&self.id);
+        state.macs.retain(|_, learned| learned.port != self.id);
+    }
+}
+
+/// The switches that stacks join when they are bridged to a network ID
+#[derive(Debug, Clone, Default)]
+pub struct SwitchRegistry {
+    networks: Arc<Mutex<HashMap<String, Network>>>,
+}
+
+#[derive(Debug)]
+struct Network {
+    switch: Weak<SwitchInner>,
+    access_token: String,
+}
+
+impl SwitchRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The registry that is used by all stacks of this process unless
+    /// they were given another one
+    pub fn global() -> &'static SwitchRegistry {
+        static GLOBAL: OnceLock<SwitchRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(SwitchRegistry::new)
+    }
+
+    /// Returns the switch of a network, which is created on the fly if
+    /// nobody is connected to it yet. The access token given by whoever
+    /// created the network must be given by everyone that joins it later.
+    pub fn switch(&self, network: &str, access_token: &str) -> Result<VirtualSwitch> {
+        let mut networks = self.networks.lock().unwrap();
+        if let Some(existing) = networks.get(network) {
+            if let Some(inner) = existing.switch.upgrade() {
+                if existing.access_token != access_token {
+                    return Err(NetworkError::PermissionDenied);
+                }
+                return Ok(VirtualSwitch { inner });
+            }
+        }
+
+        let switch = VirtualSwitch::new();
+        networks.retain(|_, network| network.switch.strong_count() > 0);
+        networks.insert(
+            network.to_string(),
+            Network {
+                switch: Arc::downgrade(&switch.inner),
+                access_token: access_token.to_string(),
+            },
+        );
+        Ok(switch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Collect(Mutex<Vec<Bytes>>);
+
+    impl EthernetLink for Collect {
+        fn send(&self, frame: Bytes) {
+            self.0.lock().unwrap().push(frame);
+        }
+    }
+
+    impl Collect {
+        fn take(&self) -> Vec<Bytes> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    fn frame(dst: u8, src: u8) -> Bytes {
+        let mut frame = vec![0u8; 60];
+        frame[..6].copy_from_slice(&[0x02, 0, 0, 0, 0, dst]);
+        frame[6..12].copy_from_slice(&[0x02, 0, 0, 0, 0, src]);
+        frame.into()
+    }
+
+    #[test]
+    fn test_switch_learning() {
+        let switch = VirtualSwitch::new();
+        let links: Vec<_> = (0..3).map(|_| Arc::new(Collect::default())).collect();
+        let ports: Vec<_> = links
+            .iter()
+            .map(|link| switch.join(link.clone(), None))
+            .collect();
+        assert_eq!(switch.port_count(), 3);
+
+        // Unknown destinations are flooded
+        ports[0].send(frame(2, 1));
+        assert_eq!(links[0].take().len(), 0);
+        assert_eq!(links[1].take().len(), 1);
+        assert_eq!(links[2].take().len(), 1);
+
+        // Once the switch knows where an address is it only goes there
+        ports[1].send(frame(1, 2));
+        assert_eq!(links[0].take().len(), 1);
+        assert_eq!(links[2].take().len(), 0);
+        ports[2].send(frame(2, 3));
+        assert_eq!(links[0].take().len(), 0);
+        assert_eq!(links[1].take().len(), 1);
+
+        // Broadcasts go everywhere
+        let mut broadcast = frame(0, 1).to_vec();
+        broadcast[..6].copy_from_slice(&[0xff; 6]);
+        ports[0].send(broadcast.into());
+        assert_eq!(links[1].take().len(), 1);
+        assert_eq!(links[2].take().len(), 1);
+
+        // Addresses are forgotten when their port leaves
+        let mut ports = ports;
+        ports.remove(1);
+        assert_eq!(switch.port_count(), 2);
+        ports[0].send(frame(2, 1));
+        assert_eq!(links[1].take().len(), 0);
+        assert_eq!(links[2].take().len(), 1);
+    }
+
+    #[test]
+    fn test_switch_vlans() {
+        let switch = VirtualSwitch::new();
+        let a = Arc::new(Collect::default());
+        let b = Arc::new(Collect::default());
+        let c = Arc::new(Collect::default());
+        let pa = switch.join(a.clone(), Some(10));
+        let pb = switch.join(b.clone(), Some(10));
+        let _pc = switch.join(c.clone(), Some(20));
+
+        pa.send(frame(2, 1));
+        pb.send(frame(1, 2));
+        assert_eq!(a.take().len(), 1);
+        assert_eq!(b.take().len(), 1);
+        assert_eq!(c.take().len(), 0);
+    }
+
+    #[test]
+    fn test_switch_registry() {
+        let registry = SwitchRegistry::new();
+        let switch = registry.switch("office", "secret").unwrap();
+        let _port = switch.join(Arc::new(Collect::default()), None);
+
+        let again = registry.switch("office", "secret").unwrap();
+        assert_eq!(again.port_count(), 1);
+        assert_eq!(
+            registry.switch("office", "wrong").unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+        assert_eq!(registry.switch("lab", "").unwrap().port_count(), 0);
+    }
+}
@@ -370,6 +370,7 @@ impl From<std::io::Error> for Errno {
             ErrorKind::Other => Errno::Io,
             ErrorKind::UnexpectedEof => Errno::Io,
             ErrorKind::Unsupported => Errno::Notsup,
+            ErrorKind::StorageFull => Errno::Nospc,
             _ => Errno::Io,
         }
     }
@@ -442,6 +442,61 @@ unsafe impl wasmer::FromToNativeWasmType for EpollCtl {
     }
 }
 
+#[doc = " Advisory lock operation."]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, num_enum :: TryFromPrimitive, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum Flockop {
+    #[doc = " Place a shared lock, more than one process may hold a shared lock on a file."]
+    Shared,
+    #[doc = " Place an exclusive lock, only one process may hold an exclusive lock on a file."]
+    Exclusive,
+    #[doc = " Remove an existing lock held by this process."]
+    Unlock,
+    #[doc = " Unknown."]
+    Unknown,
+}
+impl core::fmt::Debug for Flockop {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Flockop::Shared => f.debug_tuple("LOCK_SH").finish(),
+            Flockop::Exclusive => f.debug_tuple("LOCK_EX").finish(),
+            Flockop::Unlock => f.debug_tuple("LOCK_UN").finish(),
+            Flockop::Unknown => f.debug_tuple("Unknown").finish(),
+        }
+    }
+}
+// TODO: if necessary, must be implemented in wit-bindgen
+unsafe impl ValueType for Flockop {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+unsafe impl wasmer::FromToNativeWasmType for Flockop {
+    type Native = i32;
+
+    fn to_native(self) -> Self::Native {
+        self as i32
+    }
+
+    fn from_native(n: Self::Native) -> Self {
+        match n {
+            0 => Self::Shared,
+            1 => Self::Exclusive,
+            2 => Self::Unlock,
+
+            q => {
+                tracing::debug!("could not serialize number {q} to enum Flockop");
+                Self::Unknown
+            }
+        }
+    }
+
+    fn is_from_store(&self, _store: &impl wasmer::AsStoreRef) -> bool {
+        false
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct EpollEventCtl {
@@ -11,14 +11,14 @@ use futures::Future;
 use serde_derive::{Deserialize, Serialize};
 use std::sync::Mutex as StdMutex;
 use tokio::sync::{watch, Mutex as AsyncMutex};
-use virtual_fs::{Pipe, PipeRx, PipeTx, VirtualFile};
+use virtual_fs::{new_lock_owner, LockOwner, Pipe, PipeRx, PipeTx, VirtualFile};
 use wasmer_wasix_types::wasi::{EpollType, Fd as WasiFd, Fdflags, Fdflagsext, Filestat, Rights};
 
 use crate::{net::socket::InodeSocket, syscalls::EpollJoinWaker};
 
 use super::{
     InodeGuard, InodeValFilePollGuard, InodeValFilePollGuardJoin, InodeValFilePollGuardMode,
-    InodeWeakGuard, NotificationInner,
+    InodeWeakGuard, NotificationInner, WatchNotificationsInner,
 };
 
 #[derive(Debug, Clone)]
@@ -45,6 +45,55 @@ pub struct FdInner {
     pub flags: Fdflags,         // This is file table related flags, not fd flags
     pub offset: Arc<AtomicU64>, // This also belongs in the file table
     pub fd_flags: Fdflagsext,   // This is the actual FD flags that belongs here
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub locks: Arc<FdLocks>, // This also belongs in the file table
+}
+
+/// The file of an inode, which is shared by all the descriptors that refer to it
+pub type SharedFile = Arc<RwLock<Box<dyn VirtualFile + Send + Sync + 'static>>>;
+
+/// The advisory locks placed through an open file description, these are
+/// shared by all the duplicates of the description and are released once
+/// the last of them is closed
+#[derive(Debug)]
+pub struct FdLocks {
+    owner: LockOwner,
+    files: StdMutex<Vec<SharedFile>>,
+}
+
+impl Default for FdLocks {
+    fn default() -> Self {
+        Self {
+            owner: new_lock_owner(),
+            files: StdMutex::new(Vec::new()),
+        }
+    }
+}
+
+impl FdLocks {
+    /// The owner of the locks placed through the description
+    pub fn owner(&self) -> LockOwner {
+        self.owner
+    }
+
+    /// Remembers a file that locks are placed on so they can be released
+    pub(crate) fn placed_on(&self, file: &SharedFile) {
+        let mut files = self.files.lock().unwrap();
+        if !files.iter().any(|f| Arc::ptr_eq(f, file)) {
+            files.push(file.clone());
+        }
+    }
+}
+
+impl Drop for FdLocks {
+    fn drop(&mut self) {
+        let files = self.files.get_mut().map(std::mem::take).unwrap_or_default();
+        for file in files {
+            if let Ok(mut file) = file.write() {
+                file.unlock_all(self.owner);
+            }
+        }
+    }
 }
 
 impl Fd {
@@ -240,4 +289,9 @@ pub enum Kind {
     EventNotifications {
         inner: Arc<NotificationInner>,
     },
+    /// Delivers the events of file system watches (see `watch_create`)
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    WatchNotifications {
+        inner: Arc<WatchNotificationsInner>,
+    },
 }
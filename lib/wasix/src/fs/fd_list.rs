@@ -323,6 +323,7 @@ mod tests {
                 rights_inheriting: Rights::empty(),
                 flags: Fdflags::from_bits_preserve(n),
                 fd_flags: Fdflagsext::empty(),
+                locks: Default::default(),
             },
         }
     }
@@ -15,7 +15,10 @@ use wasmer_wasix_types::{
     wasi::{Errno, EventFdReadwrite, Eventrwflags, Subscription},
 };
 
-use super::{notification::NotificationInner, InodeGuard, Kind};
+use super::{
+    notification::{NotificationInner, WatchNotificationsInner},
+    InodeGuard, Kind,
+};
 use crate::{
     net::socket::{InodeSocketInner, InodeSocketKind},
     state::{iterate_poll_events, PollEvent, PollEventSet, WasiState},
@@ -27,6 +30,7 @@ use crate::{
 pub(crate) enum InodeValFilePollGuardMode {
     File(Arc<RwLock<Box<dyn VirtualFile + Send + Sync + 'static>>>),
     EventNotifications(Arc<NotificationInner>),
+    WatchNotifications(Arc<WatchNotificationsInner>),
     Socket { inner: Arc<InodeSocketInner> },
     PipeRx { rx: Arc<RwLock<Box<PipeRx>>> },
     PipeTx { tx: Arc<RwLock<Box<PipeTx>>> },
@@ -51,6 +55,9 @@ impl InodeValFilePollGuard {
             Kind::EventNotifications { inner, .. } => {
                 InodeValFilePollGuardMode::EventNotifications(inner.clone())
             }
+            Kind::WatchNotifications { inner } => {
+                InodeValFilePollGuardMode::WatchNotifications(inner.clone())
+            }
             Kind::Socket { socket, .. } => InodeValFilePollGuardMode::Socket {
                 inner: socket.inner.clone(),
             },
@@ -89,6 +96,9 @@ impl std::fmt::Debug for InodeValFilePollGuard {
             InodeValFilePollGuardMode::EventNotifications { .. } => {
                 write!(f, "guard-notifications(fd={}, peb={})", self.fd, self.peb)
             }
+            InodeValFilePollGuardMode::WatchNotifications { .. } => {
+                write!(f, "guard-watch(fd={}, peb={})", self.fd, self.peb)
+            }
             InodeValFilePollGuardMode::Socket { inner } => {
                 let inner = inner.protected.read().unwrap();
                 match inner.kind {
@@ -112,6 +122,15 @@ impl std::fmt::Debug for InodeValFilePollGuard {
                     InodeSocketKind::Raw(..) => {
                         write!(f, "guard-raw-socket(fd={}, peb={})", self.fd, self.peb)
                     }
+                    InodeSocketKind::UnixListener { .. } => {
+                        write!(f, "guard-unix-listener(fd={}, peb={})", self.fd, self.peb)
+                    }
+                    InodeSocketKind::UnixStream { .. } => {
+                        write!(f, "guard-unix-stream(fd={}, peb={})", self.fd, self.peb)
+                    }
+                    InodeSocketKind::UnixDatagram { .. } => {
+                        write!(f, "guard-unix-datagram(fd={}, peb={})", self.fd, self.peb)
+                    }
                     _ => write!(f, "guard-socket(fd={}), peb={})", self.fd, self.peb),
                 }
             }
@@ -162,7 +181,8 @@ impl InodeValFilePollGuardJoin {
             InodeValFilePollGuardMode::EventNotifications(inner) => {
                 inner.reset();
             }
-            InodeValFilePollGuardMode::Socket { .. }
+            InodeValFilePollGuardMode::WatchNotifications(_)
+            | InodeValFilePollGuardMode::Socket { .. }
             | InodeValFilePollGuardMode::PipeRx { .. }
             | InodeValFilePollGuardMode::PipeTx { .. }
             | InodeValFilePollGuardMode::DuplexPipe { .. } => {}
@@ -212,6 +232,7 @@ impl Future for InodeValFilePollGuardJoin {
                     file.poll_read_ready(cx)
                 }
                 InodeValFilePollGuardMode::EventNotifications(inner) => inner.poll(waker).map(Ok),
+                InodeValFilePollGuardMode::WatchNotifications(inner) => inner.poll(waker).map(Ok),
                 InodeValFilePollGuardMode::Socket { ref inner } => {
                     let mut guard = inner.protected.write().unwrap();
                     guard.poll_read_ready(cx)
@@ -311,6 +332,8 @@ impl Future for InodeValFilePollGuardJoin {
                     file.poll_write_ready(cx)
                 }
                 InodeValFilePollGuardMode::EventNotifications(inner) => inner.poll(waker).map(Ok),
+                // Watches can not be written to
+                InodeValFilePollGuardMode::WatchNotifications(_) => Poll::Pending,
                 InodeValFilePollGuardMode::Socket { ref inner } => {
                     let mut guard = inner.protected.write().unwrap();
                     guard.poll_write_ready(cx)
@@ -517,6 +540,16 @@ impl WasiStateFileGuard {
 }
 
 impl VirtualFile for WasiStateFileGuard {
+    fn poll_sync(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let mut guard = self.lock_write();
+        if let Some(guard) = guard.as_mut() {
+            let file = Pin::new(guard.deref_mut());
+            file.poll_sync(cx)
+        } else {
+            Poll::Ready(Err(std::io::ErrorKind::Unsupported.into()))
+        }
+    }
+
     fn last_accessed(&self) -> u64 {
         let guard = self.lock_read();
         if let Some(file) = guard.as_ref() {
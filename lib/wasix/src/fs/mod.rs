@@ -33,7 +33,7 @@ use futures::{future::BoxFuture, Future, TryStreamExt};
 use serde_derive::{Deserialize, Serialize};
 use tokio::io::AsyncWriteExt;
 use tracing::{debug, trace};
-use virtual_fs::{copy_reference, FileSystem, FsError, OpenOptions, VirtualFile};
+use virtual_fs::{copy_reference, FileSystem, FsError, OpenOptions, VirtualFile, Watch, WatchMask};
 use wasmer_config::package::PackageId;
 use wasmer_wasix_types::{
     types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
@@ -43,12 +43,14 @@ use wasmer_wasix_types::{
     },
 };
 
-pub use self::fd::{EpollFd, EpollInterest, EpollJoinGuard, Fd, FdInner, InodeVal, Kind};
+pub use self::fd::{
+    EpollFd, EpollInterest, EpollJoinGuard, Fd, FdInner, FdLocks, InodeVal, Kind, SharedFile,
+};
 pub(crate) use self::inode_guard::{
     InodeValFilePollGuard, InodeValFilePollGuardJoin, InodeValFilePollGuardMode,
     InodeValFileReadGuard, InodeValFileWriteGuard, WasiStateFileGuard, POLL_GUARD_MAX_RET,
 };
-pub use self::notification::NotificationInner;
+pub use self::notification::{NotificationInner, WatchNotificationsInner};
 use crate::syscalls::map_io_err;
 use crate::{bin_factory::BinaryPackage, state::PreopenedDir, ALL_RIGHTS};
 
@@ -441,6 +443,58 @@ impl FileSystem for WasiFsRoot {
             WasiFsRoot::Backing(fs) => fs.remove_file(path),
         }
     }
+    fn symlink(&self, original: &Path, link: &Path) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.symlink(original, link),
+            WasiFsRoot::Backing(fs) => fs.symlink(original, link),
+        }
+    }
+    fn hard_link(&self, original: &Path, link: &Path) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.hard_link(original, link),
+            WasiFsRoot::Backing(fs) => fs.hard_link(original, link),
+        }
+    }
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: virtual_fs::Permissions,
+    ) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.set_permissions(path, permissions),
+            WasiFsRoot::Backing(fs) => fs.set_permissions(path, permissions),
+        }
+    }
+    fn get_xattr(&self, path: &Path, name: &str) -> virtual_fs::Result<Vec<u8>> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.get_xattr(path, name),
+            WasiFsRoot::Backing(fs) => fs.get_xattr(path, name),
+        }
+    }
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.set_xattr(path, name, value),
+            WasiFsRoot::Backing(fs) => fs.set_xattr(path, name, value),
+        }
+    }
+    fn list_xattr(&self, path: &Path) -> virtual_fs::Result<Vec<String>> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.list_xattr(path),
+            WasiFsRoot::Backing(fs) => fs.list_xattr(path),
+        }
+    }
+    fn remove_xattr(&self, path: &Path, name: &str) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.remove_xattr(path, name),
+            WasiFsRoot::Backing(fs) => fs.remove_xattr(path, name),
+        }
+    }
+    fn watch(&self, path: &Path, mask: WatchMask) -> virtual_fs::Result<Watch> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.watch(path, mask),
+            WasiFsRoot::Backing(fs) => fs.watch(path, mask),
+        }
+    }
     fn new_open_options(&self) -> OpenOptions {
         match self {
             WasiFsRoot::Sandbox(fs) => fs.new_open_options(),
@@ -521,6 +575,29 @@ fn create_dir_all(fs: &dyn FileSystem, path: &Path) -> Result<(), virtual_fs::Fs
     Ok(())
 }
 
+/// The user and group a process accesses the file system as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct WasiIdentity {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl WasiIdentity {
+    pub fn new(uid: u32, gid: u32) -> Self {
+        Self { uid, gid }
+    }
+
+    /// The superuser, which is not subject to any permission checks
+    pub fn root() -> Self {
+        Self::new(virtual_fs::Permissions::ROOT_UID, 0)
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.uid == virtual_fs::Permissions::ROOT_UID
+    }
+}
+
 /// Warning, modifying these fields directly may cause invariants to break and
 /// should be considered unsafe.  These fields may be made private in a future release
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
@@ -529,6 +606,12 @@ pub struct WasiFs {
     pub preopen_fds: RwLock<Vec<u32>>,
     pub fd_map: RwLock<FdList>,
     pub current_dir: Mutex<String>,
+    /// The identity that file system permissions are checked against, no
+    /// checks are made when this is not set
+    pub identity: RwLock<Option<WasiIdentity>>,
+    /// Directories (guest path, host path) whose unix domain sockets are
+    /// bound and connected on the host rather than in the virtual namespace
+    pub host_socket_dirs: RwLock<Vec<(PathBuf, PathBuf)>>,
     #[cfg_attr(feature = "enable-serde", serde(skip, default))]
     pub root_fs: WasiFsRoot,
     pub root_inode: InodeGuard,
@@ -563,6 +646,8 @@ impl WasiFs {
             preopen_fds: RwLock::new(self.preopen_fds.read().unwrap().clone()),
             fd_map: RwLock::new(self.fd_map.read().unwrap().clone()),
             current_dir: Mutex::new(self.current_dir.lock().unwrap().clone()),
+            identity: RwLock::new(*self.identity.read().unwrap()),
+            host_socket_dirs: RwLock::new(self.host_socket_dirs.read().unwrap().clone()),
             is_wasix: AtomicBool::new(self.is_wasix.load(Ordering::Acquire)),
             root_fs: self.root_fs.clone(),
             root_inode: self.root_inode.clone(),
@@ -703,6 +788,8 @@ impl WasiFs {
             preopen_fds: RwLock::new(vec![]),
             fd_map: RwLock::new(FdList::new()),
             current_dir: Mutex::new("/".to_string()),
+            identity: RwLock::new(None),
+            host_socket_dirs: RwLock::new(Vec::new()),
             is_wasix: AtomicBool::new(false),
             root_fs: fs_backing,
             root_inode,
@@ -966,6 +1053,144 @@ impl WasiFs {
         *guard = path.to_string();
     }
 
+    /// Changes the identity that file system permissions are checked against
+    pub fn set_identity(&self, identity: Option<WasiIdentity>) {
+        let mut guard = self.identity.write().unwrap();
+        *guard = identity;
+    }
+
+    /// Gets the identity that file system permissions are checked against
+    pub fn get_identity(&self) -> Option<WasiIdentity> {
+        *self.identity.read().unwrap()
+    }
+
+    /// Passes the unix domain sockets under a guest directory through to
+    /// the sockets under a directory of the host
+    pub fn add_host_socket_dir(&self, guest: PathBuf, host: PathBuf) {
+        let mut guard = self.host_socket_dirs.write().unwrap();
+        guard.push((guest, host));
+    }
+
+    /// Turns the path of a unix domain socket into an absolute path without
+    /// any `.` or `..` components, so that it can not escape the directories
+    /// that are passed through to the host
+    pub(crate) fn unix_socket_path(&self, path: &str) -> PathBuf {
+        let current_dir = self.current_dir.lock().unwrap();
+        let mut ret = PathBuf::from("/");
+        for component in Path::new(current_dir.as_str()).join(path).components() {
+            match component {
+                Component::Normal(name) => ret.push(name),
+                Component::ParentDir => {
+                    ret.pop();
+                }
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+        ret
+    }
+
+    /// Returns the host path of a unix domain socket if it is under one of
+    /// the directories passed through to the host
+    pub(crate) fn host_socket_path(&self, path: &Path) -> Option<PathBuf> {
+        let guard = self.host_socket_dirs.read().unwrap();
+        guard.iter().find_map(|(guest, host)| {
+            path.strip_prefix(guest)
+                .ok()
+                .map(|relative| host.join(relative))
+        })
+    }
+
+    /// Checks that the identity of the process is granted `access` (a
+    /// combination of the [`virtual_fs::Permissions`] access flags) to the
+    /// entry at `path`, and search permission on every directory leading
+    /// up to it. Entries without permissions are always accessible.
+    pub(crate) fn check_access(&self, path: &Path, access: u32) -> Result<(), Errno> {
+        let identity = match self.get_identity() {
+            Some(identity) if !identity.is_root() => identity,
+            _ => return Ok(()),
+        };
+
+        for ancestor in path.ancestors().skip(1) {
+            self.check_entry_access(identity, ancestor, virtual_fs::Permissions::EXECUTE)?;
+        }
+        self.check_entry_access(identity, path, access)
+    }
+
+    fn check_entry_access(
+        &self,
+        identity: WasiIdentity,
+        path: &Path,
+        access: u32,
+    ) -> Result<(), Errno> {
+        match self
+            .root_fs
+            .metadata(path)
+            .ok()
+            .and_then(|metadata| metadata.permissions())
+        {
+            Some(permissions) if !permissions.allows(identity.uid, identity.gid, access) => {
+                Err(Errno::Access)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like [`Self::check_access`] but for the entry behind an inode, only
+    /// files and directories are checked.
+    pub(crate) fn check_inode_access(&self, inode: &InodeGuard, access: u32) -> Result<(), Errno> {
+        match Self::path_of_inode(inode) {
+            Some(path) => self.check_access(&path, access),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the permissions of the entry at `path`, entries on file
+    /// systems that do not track them get the permissive defaults.
+    pub(crate) fn get_permissions(&self, path: &Path) -> Result<virtual_fs::Permissions, Errno> {
+        let metadata = self
+            .root_fs
+            .metadata(path)
+            .map_err(fs_error_into_wasi_err)?;
+        Ok(metadata.permissions().unwrap_or_else(|| {
+            if metadata.is_dir() {
+                virtual_fs::Permissions::new_dir()
+            } else {
+                virtual_fs::Permissions::new_file()
+            }
+        }))
+    }
+
+    /// Returns the path of the entry behind an inode in the root file
+    /// system, only files and directories have one.
+    pub(crate) fn path_of_inode(inode: &InodeGuard) -> Option<PathBuf> {
+        match inode.read().deref() {
+            Kind::File { path, .. } | Kind::Dir { path, .. } => Some(path.clone()),
+            _ => None,
+        }
+    }
+
+    /// Hands the ownership of a newly created entry to the identity of
+    /// the process, file systems that have no notion of ownership are
+    /// left untouched.
+    pub(crate) fn assign_ownership(&self, path: &Path) {
+        let identity = match self.get_identity() {
+            Some(identity) if !identity.is_root() => identity,
+            _ => return,
+        };
+
+        if let Some(permissions) = self
+            .root_fs
+            .metadata(path)
+            .ok()
+            .and_then(|metadata| metadata.permissions())
+        {
+            let permissions = permissions.with_owner(identity.uid, identity.gid);
+            if let Err(err) = self.root_fs.set_permissions(path, permissions) {
+                trace!("failed to change the owner of {}: {}", path.display(), err);
+            }
+        }
+    }
+
     /// Gets the current directory
     pub fn get_current_dir(
         &self,
@@ -1233,6 +1458,7 @@ impl WasiFs {
                     | Kind::PipeTx { .. }
                     | Kind::DuplexPipe { .. }
                     | Kind::EventNotifications { .. }
+                    | Kind::WatchNotifications { .. }
                     | Kind::Epoll { .. } => {
                         return Err(Errno::Notdir);
                     }
@@ -1439,6 +1665,7 @@ impl WasiFs {
                     flags: Fdflags::empty(),
                     offset: Arc::new(AtomicU64::new(0)),
                     fd_flags: Fdflagsext::empty(),
+                    locks: Default::default(),
                 },
                 open_flags: 0,
                 inode: self.root_inode.clone(),
@@ -1515,7 +1742,14 @@ impl WasiFs {
                 Kind::Dir { .. } => Filetype::Directory,
                 Kind::Symlink { .. } => Filetype::SymbolicLink,
                 Kind::Socket { socket } => match &socket.inner.protected.read().unwrap().kind {
-                    InodeSocketKind::TcpStream { .. } => Filetype::SocketStream,
+                    InodeSocketKind::TcpStream { .. }
+                    | InodeSocketKind::UnixListener { .. }
+                    | InodeSocketKind::UnixStream { .. } => Filetype::SocketStream,
+                    InodeSocketKind::UnixDatagram { .. } => Filetype::SocketDgram,
+                    InodeSocketKind::UnixAddress(endpoint) => match endpoint.is_stream() {
+                        true => Filetype::SocketStream,
+                        false => Filetype::SocketDgram,
+                    },
                     InodeSocketKind::Raw { .. } => Filetype::SocketRaw,
                     InodeSocketKind::PreSocket { props, .. } => match props.ty {
                         Socktype::Stream => Filetype::SocketStream,
@@ -1736,6 +1970,7 @@ impl WasiFs {
                 flags: fs_flags,
                 offset: Arc::new(AtomicU64::new(0)),
                 fd_flags,
+                locks: Default::default(),
             },
             open_flags,
             inode,
@@ -1774,6 +2009,7 @@ impl WasiFs {
                     rights_inheriting: fd.inner.rights_inheriting,
                     flags: fd.inner.flags,
                     offset: fd.inner.offset.clone(),
+                    locks: fd.inner.locks.clone(),
                     fd_flags: match cloexec {
                         None => fd.inner.fd_flags,
                         Some(cloexec) => {
@@ -2095,6 +2331,7 @@ impl WasiFs {
                     flags: fd_flags,
                     offset: Arc::new(AtomicU64::new(0)),
                     fd_flags: Fdflagsext::empty(),
+                    locks: Default::default(),
                 },
                 // since we're not calling open on this, we don't need open flags
                 open_flags: 0,
@@ -2259,6 +2496,34 @@ impl FileSystem for FallbackFileSystem {
     fn remove_file(&self, _path: &Path) -> Result<(), FsError> {
         Self::fail();
     }
+    fn symlink(&self, _original: &Path, _link: &Path) -> Result<(), FsError> {
+        Self::fail();
+    }
+    fn hard_link(&self, _original: &Path, _link: &Path) -> Result<(), FsError> {
+        Self::fail();
+    }
+    fn set_permissions(
+        &self,
+        _path: &Path,
+        _permissions: virtual_fs::Permissions,
+    ) -> Result<(), FsError> {
+        Self::fail();
+    }
+    fn get_xattr(&self, _path: &Path, _name: &str) -> Result<Vec<u8>, FsError> {
+        Self::fail();
+    }
+    fn set_xattr(&self, _path: &Path, _name: &str, _value: &[u8]) -> Result<(), FsError> {
+        Self::fail();
+    }
+    fn list_xattr(&self, _path: &Path) -> Result<Vec<String>, FsError> {
+        Self::fail();
+    }
+    fn remove_xattr(&self, _path: &Path, _name: &str) -> Result<(), FsError> {
+        Self::fail();
+    }
+    fn watch(&self, _path: &Path, _mask: WatchMask) -> Result<Watch, FsError> {
+        Self::fail();
+    }
     fn new_open_options(&self) -> virtual_fs::OpenOptions {
         Self::fail();
     }
@@ -2336,7 +2601,8 @@ pub fn fs_error_into_wasi_err(fs_error: FsError) -> Errno {
         FsError::WouldBlock => Errno::Again,
         FsError::WriteZero => Errno::Nospc,
         FsError::DirectoryNotEmpty => Errno::Notempty,
-        FsError::StorageFull => Errno::Overflow,
+        FsError::StorageFull => Errno::Nospc,
+        FsError::AttributeNotFound => Errno::Noent,
         FsError::Lock | FsError::UnknownError => Errno::Io,
         FsError::Unsupported => Errno::Notsup,
     }
@@ -1,9 +1,11 @@
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     sync::Mutex,
     task::{Poll, Waker},
 };
 
+use virtual_fs::{Watch, WatchEvent, WatchEventKind};
+
 #[derive(Debug)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 struct NotificationState {
@@ -112,3 +114,213 @@ impl NotificationInner {
         state.last_poll = u64::MAX;
     }
 }
+
+/// Set on the mask of the events that are about a directory
+const IN_ISDIR: u32 = 0x4000_0000;
+
+/// Size of the fixed part of a serialized event (`wd`, `mask`, `cookie`
+/// and `len`, just like the `inotify_event` structure of Linux)
+const EVENT_HEADER_SIZE: usize = 16;
+
+/// Names are padded with NUL bytes to a multiple of this
+const EVENT_NAME_ALIGN: usize = 16;
+
+#[derive(Debug, Default)]
+struct WatchNotificationsState {
+    /// Descriptor given to the next watch
+    next_wd: i32,
+    /// The watches that are still active, indexed by their descriptor
+    watches: BTreeMap<i32, Watch>,
+    /// Events taken from the watches that were not read yet
+    pending: VecDeque<(i32, WatchEvent)>,
+    /// All the registered wakers
+    wakers: VecDeque<Waker>,
+}
+
+impl WatchNotificationsState {
+    fn add_waker(&mut self, waker: &Waker) {
+        if !self.wakers.iter().any(|a| a.will_wake(waker)) {
+            self.wakers.push_front(waker.clone());
+        }
+    }
+
+    fn wake_all(&mut self) {
+        while let Some(waker) = self.wakers.pop_front() {
+            waker.wake();
+        }
+    }
+
+    /// Moves the events of the watches into the pending queue, the watches
+    /// that will not receive any more events are forgotten
+    fn drain(&mut self) {
+        let mut finished = Vec::new();
+        for (wd, watch) in self.watches.iter() {
+            while let Some(event) = watch.try_recv() {
+                let kind = event.kind;
+                self.pending.push_back((*wd, event));
+                if kind == WatchEventKind::Ignored {
+                    finished.push(*wd);
+                    break;
+                }
+            }
+        }
+        for wd in finished {
+            self.watches.remove(&wd);
+        }
+    }
+
+    fn pending_bytes(&self) -> usize {
+        self.pending.iter().map(|(_, e)| event_size(e)).sum()
+    }
+}
+
+/// The state behind a file descriptor created by `watch_create`, reading
+/// from it returns the events of its watches in the format of `inotify(7)`.
+#[derive(Debug, Default)]
+pub struct WatchNotificationsInner {
+    state: Mutex<WatchNotificationsState>,
+}
+
+impl WatchNotificationsInner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a watch and returns its descriptor
+    pub fn add_watch(&self, watch: Watch) -> i32 {
+        let mut state = self.state.lock().unwrap();
+        state.next_wd += 1;
+        let wd = state.next_wd;
+        state.watches.insert(wd, watch);
+        state.wake_all();
+        wd
+    }
+
+    /// Registers a watch under a descriptor that was handed out before,
+    /// which is used when the watches are restored from a journal
+    pub fn insert_watch(&self, wd: i32, watch: Watch) {
+        let mut state = self.state.lock().unwrap();
+        state.next_wd = state.next_wd.max(wd);
+        state.watches.insert(wd, watch);
+        state.wake_all();
+    }
+
+    /// Removes a watch, an [`WatchEventKind::Ignored`] event is queued for
+    /// it. Returns `false` if there is no watch with this descriptor.
+    pub fn remove_watch(&self, wd: i32) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.watches.remove(&wd).is_none() {
+            return false;
+        }
+        state
+            .pending
+            .push_back((wd, WatchEvent::new(WatchEventKind::Ignored)));
+        state.wake_all();
+        true
+    }
+
+    /// Returns the number of bytes that are ready to be read
+    pub fn poll(&self, waker: &Waker) -> Poll<usize> {
+        let mut state = self.state.lock().unwrap();
+        state.drain();
+        if state.pending.is_empty() {
+            state.add_waker(waker);
+            // Events may have arrived since the watches were drained
+            let ready = state
+                .watches
+                .values()
+                .fold(false, |ready, w| w.poll_ready(waker).is_ready() || ready);
+            if !ready {
+                return Poll::Pending;
+            }
+            state.drain();
+        }
+        Poll::Ready(state.pending_bytes())
+    }
+
+    /// Serializes as many of the queued events as fit in `max_len` bytes.
+    /// Returns `None` when there are no events and an empty buffer when the
+    /// next event does not fit.
+    pub fn try_read(&self, max_len: usize) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        state.drain();
+        if state.pending.is_empty() {
+            return None;
+        }
+
+        let mut buf = Vec::new();
+        while let Some((wd, event)) = state.pending.front() {
+            if buf.len() + event_size(event) > max_len {
+                break;
+            }
+            encode_event(*wd, event, &mut buf);
+            state.pending.pop_front();
+        }
+        Some(buf)
+    }
+}
+
+fn event_size(event: &WatchEvent) -> usize {
+    EVENT_HEADER_SIZE + padded_name_len(event)
+}
+
+fn padded_name_len(event: &WatchEvent) -> usize {
+    match &event.name {
+        // There is always room for at least one NUL byte
+        Some(name) => (name.len() + 1).next_multiple_of(EVENT_NAME_ALIGN),
+        None => 0,
+    }
+}
+
+fn encode_event(wd: i32, event: &WatchEvent, buf: &mut Vec<u8>) {
+    let mut mask = event.kind.bits();
+    if event.is_dir {
+        mask |= IN_ISDIR;
+    }
+    let name_len = padded_name_len(event);
+
+    buf.extend_from_slice(&wd.to_le_bytes());
+    buf.extend_from_slice(&mask.to_le_bytes());
+    buf.extend_from_slice(&event.cookie.to_le_bytes());
+    buf.extend_from_slice(&(name_len as u32).to_le_bytes());
+    if let Some(name) = &event.name {
+        let start = buf.len();
+        buf.extend_from_slice(name.as_encoded_bytes());
+        buf.resize(start + name_len, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use virtual_fs::WatchMask;
+
+    use super::*;
+
+    #[test]
+    fn test_watch_notifications() {
+        let inner = WatchNotificationsInner::new();
+        let (sender, watch) = Watch::channel(WatchMask::ALL);
+        let wd = inner.add_watch(watch);
+
+        assert_eq!(inner.try_read(1024), None);
+        sender.send(WatchEvent::new(WatchEventKind::Create).with_name("a.txt", true));
+        sender.send(WatchEvent::new(WatchEventKind::Modify));
+
+        // The buffer must be large enough for the first event
+        assert_eq!(inner.try_read(EVENT_HEADER_SIZE), Some(Vec::new()));
+
+        let buf = inner.try_read(1024).unwrap();
+        assert_eq!(buf.len(), 2 * EVENT_HEADER_SIZE + EVENT_NAME_ALIGN);
+        assert_eq!(&buf[0..4], &wd.to_le_bytes());
+        assert_eq!(&buf[4..8], &(0x100 | IN_ISDIR).to_le_bytes());
+        assert_eq!(&buf[12..16], &16u32.to_le_bytes());
+        assert_eq!(&buf[16..32], b"a.txt\0\0\0\0\0\0\0\0\0\0\0");
+        assert_eq!(&buf[36..40], &0x2u32.to_le_bytes());
+
+        assert!(inner.remove_watch(wd));
+        assert!(!inner.remove_watch(wd));
+        let buf = inner.try_read(1024).unwrap();
+        assert_eq!(&buf[4..8], &0x8000u32.to_le_bytes());
+        assert!(sender.is_closed());
+    }
+}
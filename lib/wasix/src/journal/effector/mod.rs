@@ -8,7 +8,7 @@ pub(super) use wasmer_wasix_types::{
     types::__wasi_ciovec_t,
     wasi::{
         Advice, EpollCtl, EpollEventCtl, Errno, ExitCode, Fd, Fdflags, Fdflagsext, Filesize,
-        Fstflags, LookupFlags, Oflags, Rights, Snapshot0Clockid, Timestamp, Whence,
+        Flockop, Fstflags, LookupFlags, Oflags, Rights, Snapshot0Clockid, Timestamp, Whence,
     },
 };
 
@@ -34,6 +34,8 @@ mod syscalls {
     mod fd_close;
     mod fd_duplicate;
     mod fd_event;
+    mod fd_lock;
+    mod fd_lock_range;
     mod fd_pipe;
     mod fd_renumber;
     mod fd_seek;
@@ -79,6 +81,9 @@ mod syscalls {
     mod sock_set_opt_time;
     mod sock_shutdown;
     mod tty_set;
+    mod watch_add;
+    mod watch_create;
+    mod watch_remove;
 }
 #[cfg(feature = "journal")]
 mod memory_and_snapshot;
@@ -0,0 +1,34 @@
+use virtual_fs::LockRange;
+
+use super::*;
+
+impl JournalEffector {
+    pub fn save_fd_lock(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        op: Flockop,
+    ) -> anyhow::Result<()> {
+        Self::save_event(ctx, JournalEntry::FileDescriptorLockV1 { fd, op })
+    }
+
+    pub fn apply_fd_lock(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        op: Flockop,
+    ) -> anyhow::Result<()> {
+        // Nothing else can be holding the lock while the journal is being
+        // replayed so there is never a reason to wait for it
+        crate::syscalls::fd_lock_internal(ctx, fd, op, LockRange::File, true)
+            .map(|r| r.map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| Err(err.to_string()))
+            .map_err(|err| {
+                anyhow::format_err!(
+                    "journal restore error: failed to lock file (fd={}, op={:?}) - {}",
+                    fd,
+                    op,
+                    err
+                )
+            })?;
+        Ok(())
+    }
+}
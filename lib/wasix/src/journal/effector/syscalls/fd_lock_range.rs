@@ -0,0 +1,44 @@
+use virtual_fs::LockRange;
+
+use super::*;
+
+impl JournalEffector {
+    pub fn save_fd_lock_range(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        op: Flockop,
+        start: u64,
+        len: u64,
+    ) -> anyhow::Result<()> {
+        Self::save_event(
+            ctx,
+            JournalEntry::FileDescriptorLockRangeV1 { fd, op, start, len },
+        )
+    }
+
+    pub fn apply_fd_lock_range(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        op: Flockop,
+        start: u64,
+        len: u64,
+    ) -> anyhow::Result<()> {
+        // Nothing else can be holding the lock while the journal is being
+        // replayed so there is never a reason to wait for it
+        let range = LockRange::Bytes { start, len };
+        crate::syscalls::fd_lock_internal(ctx, fd, op, range, true)
+            .map(|r| r.map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| Err(err.to_string()))
+            .map_err(|err| {
+                anyhow::format_err!(
+                    "journal restore error: failed to lock file range (fd={}, op={:?}, start={}, len={}) - {}",
+                    fd,
+                    op,
+                    start,
+                    len,
+                    err
+                )
+            })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use super::*;
+
+impl JournalEffector {
+    pub fn save_watch_add(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        dirfd: Fd,
+        flags: LookupFlags,
+        path: String,
+        mask: u32,
+        wd: i32,
+    ) -> anyhow::Result<()> {
+        Self::save_event(
+            ctx,
+            JournalEntry::WatchAddV1 {
+                fd,
+                dirfd,
+                flags,
+                path: path.into(),
+                mask,
+                wd,
+            },
+        )
+    }
+
+    pub fn apply_watch_add(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        dirfd: Fd,
+        flags: LookupFlags,
+        path: &str,
+        mask: u32,
+        wd: i32,
+    ) -> anyhow::Result<()> {
+        let env = ctx.data();
+        let (_, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+        crate::syscalls::watch_add_internal(state, inodes, fd, dirfd, flags, path, mask, Some(wd))
+            .map_err(|err| {
+                anyhow::format_err!(
+                    "journal restore error: failed to add watch (fd={}, path={}, wd={}) - {}",
+                    fd,
+                    path,
+                    wd,
+                    err
+                )
+            })?;
+        Ok(())
+    }
+}
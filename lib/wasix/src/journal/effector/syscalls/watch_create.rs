@@ -0,0 +1,36 @@
+use super::*;
+
+impl JournalEffector {
+    pub fn save_watch_create(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        flags: Fdflags,
+    ) -> anyhow::Result<()> {
+        Self::save_event(ctx, JournalEntry::WatchCreateV1 { fd, flags })
+    }
+
+    pub fn apply_watch_create(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        flags: Fdflags,
+    ) -> anyhow::Result<()> {
+        let env = ctx.data();
+        let (_, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+        let ret_fd =
+            crate::syscalls::watch_create_internal(state, inodes, flags).map_err(|err| {
+                anyhow::format_err!("journal restore error: failed to create watch - {}", err)
+            })?;
+
+        let ret = crate::syscalls::fd_renumber_internal(ctx, ret_fd, fd);
+        if !matches!(ret, Ok(Errno::Success)) {
+            bail!(
+                "journal restore error: failed renumber file descriptor after watch create (from={}, to={}) - {}",
+                ret_fd,
+                fd,
+                ret.unwrap_or(Errno::Unknown)
+            );
+        }
+
+        Ok(())
+    }
+}
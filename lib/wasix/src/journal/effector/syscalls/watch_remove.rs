@@ -0,0 +1,34 @@
+use super::*;
+
+impl JournalEffector {
+    pub fn save_watch_remove(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        wd: i32,
+    ) -> anyhow::Result<()> {
+        Self::save_event(ctx, JournalEntry::WatchRemoveV1 { fd, wd })
+    }
+
+    pub fn apply_watch_remove(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        fd: Fd,
+        wd: i32,
+    ) -> anyhow::Result<()> {
+        let state = &ctx.data().state;
+        let inner = crate::syscalls::watch_notifications_of(state, fd).map_err(|err| {
+            anyhow::format_err!(
+                "journal restore error: failed to find watch (fd={}) - {}",
+                fd,
+                err
+            )
+        })?;
+        if !inner.remove_watch(wd) {
+            bail!(
+                "journal restore error: failed to remove watch (fd={}, wd={})",
+                fd,
+                wd
+            );
+        }
+        Ok(())
+    }
+}
@@ -93,7 +93,7 @@ pub use virtual_net::{
 use wasmer_wasix_types::wasi::{Errno, ExitCode};
 
 pub use crate::{
-    fs::{default_fs_backing, Fd, WasiFs, WasiInodes, VIRTUAL_ROOT_FD},
+    fs::{default_fs_backing, Fd, WasiFs, WasiIdentity, WasiInodes, VIRTUAL_ROOT_FD},
     os::{
         task::{
             control_plane::WasiControlPlane,
@@ -542,6 +542,9 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "fd_dup2" => Function::new_typed_with_env(&mut store, env, fd_dup2::<Memory32>),
         "fd_fdflags_get" => Function::new_typed_with_env(&mut store, env, fd_fdflags_get::<Memory32>),
         "fd_fdflags_set" => Function::new_typed_with_env(&mut store, env, fd_fdflags_set),
+        "fd_lock" => Function::new_typed_with_env(&mut store, env, fd_lock),
+        "fd_lock_range" => Function::new_typed_with_env(&mut store, env, fd_lock_range),
+        "fd_lock_range_get" => Function::new_typed_with_env(&mut store, env, fd_lock_range_get::<Memory32>),
         "fd_event" => Function::new_typed_with_env(&mut store, env, fd_event::<Memory32>),
         "fd_seek" => Function::new_typed_with_env(&mut store, env, fd_seek::<Memory32>),
         "fd_sync" => Function::new_typed_with_env(&mut store, env, fd_sync),
@@ -553,7 +556,17 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "path_filestat_set_times" => Function::new_typed_with_env(&mut store, env, path_filestat_set_times::<Memory32>),
         "path_link" => Function::new_typed_with_env(&mut store, env, path_link::<Memory32>),
         "path_open" => Function::new_typed_with_env(&mut store, env, path_open::<Memory32>),
+        "path_chmod" => Function::new_typed_with_env(&mut store, env, path_chmod::<Memory32>),
+        "path_chown" => Function::new_typed_with_env(&mut store, env, path_chown::<Memory32>),
         "path_open2" => Function::new_typed_with_env(&mut store, env, path_open2::<Memory32>),
+        "path_permissions_get" => Function::new_typed_with_env(&mut store, env, path_permissions_get::<Memory32>),
+        "path_xattr_get" => Function::new_typed_with_env(&mut store, env, path_xattr_get::<Memory32>),
+        "path_xattr_list" => Function::new_typed_with_env(&mut store, env, path_xattr_list::<Memory32>),
+        "path_xattr_remove" => Function::new_typed_with_env(&mut store, env, path_xattr_remove::<Memory32>),
+        "path_xattr_set" => Function::new_typed_with_env(&mut store, env, path_xattr_set::<Memory32>),
+        "watch_create" => Function::new_typed_with_env(&mut store, env, watch_create::<Memory32>),
+        "watch_add" => Function::new_typed_with_env(&mut store, env, watch_add::<Memory32>),
+        "watch_remove" => Function::new_typed_with_env(&mut store, env, watch_remove),
         "path_readlink" => Function::new_typed_with_env(&mut store, env, path_readlink::<Memory32>),
         "path_remove_directory" => Function::new_typed_with_env(&mut store, env, path_remove_directory::<Memory32>),
         "path_rename" => Function::new_typed_with_env(&mut store, env, path_rename::<Memory32>),
@@ -614,7 +627,9 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "port_route_list" => Function::new_typed_with_env(&mut store, env, port_route_list::<Memory32>),
         "sock_status" => Function::new_typed_with_env(&mut store, env, sock_status::<Memory32>),
         "sock_addr_local" => Function::new_typed_with_env(&mut store, env, sock_addr_local::<Memory32>),
+        "sock_addr_local_unix" => Function::new_typed_with_env(&mut store, env, sock_addr_local_unix::<Memory32>),
         "sock_addr_peer" => Function::new_typed_with_env(&mut store, env, sock_addr_peer::<Memory32>),
+        "sock_addr_peer_unix" => Function::new_typed_with_env(&mut store, env, sock_addr_peer_unix::<Memory32>),
         "sock_open" => Function::new_typed_with_env(&mut store, env, sock_open::<Memory32>),
         "sock_pair" => Function::new_typed_with_env(&mut store, env, sock_pair::<Memory32>),
         "sock_set_opt_flag" => Function::new_typed_with_env(&mut store, env, sock_set_opt_flag),
@@ -628,10 +643,13 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_join_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_join_multicast_v6::<Memory32>),
         "sock_leave_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_leave_multicast_v6::<Memory32>),
         "sock_bind" => Function::new_typed_with_env(&mut store, env, sock_bind::<Memory32>),
+        "sock_bind_unix" => Function::new_typed_with_env(&mut store, env, sock_bind_unix::<Memory32>),
         "sock_listen" => Function::new_typed_with_env(&mut store, env, sock_listen::<Memory32>),
         "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory32>),
         "sock_accept_v2" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory32>),
         "sock_connect" => Function::new_typed_with_env(&mut store, env, sock_connect::<Memory32>),
+        "sock_connect_unix" => Function::new_typed_with_env(&mut store, env, sock_connect_unix::<Memory32>),
+        "sock_upgrade_tls" => Function::new_typed_with_env(&mut store, env, sock_upgrade_tls::<Memory32>),
         "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv::<Memory32>),
         "sock_recv_from" => Function::new_typed_with_env(&mut store, env, sock_recv_from::<Memory32>),
         "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send::<Memory32>),
@@ -681,6 +699,9 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "fd_dup2" => Function::new_typed_with_env(&mut store, env, fd_dup2::<Memory64>),
         "fd_fdflags_get" => Function::new_typed_with_env(&mut store, env, fd_fdflags_get::<Memory64>),
         "fd_fdflags_set" => Function::new_typed_with_env(&mut store, env, fd_fdflags_set),
+        "fd_lock" => Function::new_typed_with_env(&mut store, env, fd_lock),
+        "fd_lock_range" => Function::new_typed_with_env(&mut store, env, fd_lock_range),
+        "fd_lock_range_get" => Function::new_typed_with_env(&mut store, env, fd_lock_range_get::<Memory64>),
         "fd_event" => Function::new_typed_with_env(&mut store, env, fd_event::<Memory64>),
         "fd_seek" => Function::new_typed_with_env(&mut store, env, fd_seek::<Memory64>),
         "fd_sync" => Function::new_typed_with_env(&mut store, env, fd_sync),
@@ -692,7 +713,17 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "path_filestat_set_times" => Function::new_typed_with_env(&mut store, env, path_filestat_set_times::<Memory64>),
         "path_link" => Function::new_typed_with_env(&mut store, env, path_link::<Memory64>),
         "path_open" => Function::new_typed_with_env(&mut store, env, path_open::<Memory64>),
+        "path_chmod" => Function::new_typed_with_env(&mut store, env, path_chmod::<Memory64>),
+        "path_chown" => Function::new_typed_with_env(&mut store, env, path_chown::<Memory64>),
         "path_open2" => Function::new_typed_with_env(&mut store, env, path_open2::<Memory64>),
+        "path_permissions_get" => Function::new_typed_with_env(&mut store, env, path_permissions_get::<Memory64>),
+        "path_xattr_get" => Function::new_typed_with_env(&mut store, env, path_xattr_get::<Memory64>),
+        "path_xattr_list" => Function::new_typed_with_env(&mut store, env, path_xattr_list::<Memory64>),
+        "path_xattr_remove" => Function::new_typed_with_env(&mut store, env, path_xattr_remove::<Memory64>),
+        "path_xattr_set" => Function::new_typed_with_env(&mut store, env, path_xattr_set::<Memory64>),
+        "watch_create" => Function::new_typed_with_env(&mut store, env, watch_create::<Memory64>),
+        "watch_add" => Function::new_typed_with_env(&mut store, env, watch_add::<Memory64>),
+        "watch_remove" => Function::new_typed_with_env(&mut store, env, watch_remove),
         "path_readlink" => Function::new_typed_with_env(&mut store, env, path_readlink::<Memory64>),
         "path_remove_directory" => Function::new_typed_with_env(&mut store, env, path_remove_directory::<Memory64>),
         "path_rename" => Function::new_typed_with_env(&mut store, env, path_rename::<Memory64>),
@@ -753,7 +784,9 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "port_route_list" => Function::new_typed_with_env(&mut store, env, port_route_list::<Memory64>),
         "sock_status" => Function::new_typed_with_env(&mut store, env, sock_status::<Memory64>),
         "sock_addr_local" => Function::new_typed_with_env(&mut store, env, sock_addr_local::<Memory64>),
+        "sock_addr_local_unix" => Function::new_typed_with_env(&mut store, env, sock_addr_local_unix::<Memory64>),
         "sock_addr_peer" => Function::new_typed_with_env(&mut store, env, sock_addr_peer::<Memory64>),
+        "sock_addr_peer_unix" => Function::new_typed_with_env(&mut store, env, sock_addr_peer_unix::<Memory64>),
         "sock_open" => Function::new_typed_with_env(&mut store, env, sock_open::<Memory64>),
         "sock_pair" => Function::new_typed_with_env(&mut store, env, sock_pair::<Memory64>),
         "sock_set_opt_flag" => Function::new_typed_with_env(&mut store, env, sock_set_opt_flag),
@@ -767,10 +800,13 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_join_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_join_multicast_v6::<Memory64>),
         "sock_leave_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_leave_multicast_v6::<Memory64>),
         "sock_bind" => Function::new_typed_with_env(&mut store, env, sock_bind::<Memory64>),
+        "sock_bind_unix" => Function::new_typed_with_env(&mut store, env, sock_bind_unix::<Memory64>),
         "sock_listen" => Function::new_typed_with_env(&mut store, env, sock_listen::<Memory64>),
         "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory64>),
         "sock_accept_v2" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory64>),
         "sock_connect" => Function::new_typed_with_env(&mut store, env, sock_connect::<Memory64>),
+        "sock_connect_unix" => Function::new_typed_with_env(&mut store, env, sock_connect_unix::<Memory64>),
+        "sock_upgrade_tls" => Function::new_typed_with_env(&mut store, env, sock_upgrade_tls::<Memory64>),
         "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv::<Memory64>),
         "sock_recv_from" => Function::new_typed_with_env(&mut store, env, sock_recv_from::<Memory64>),
         "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send::<Memory64>),
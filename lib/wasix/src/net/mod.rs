@@ -1,6 +1,7 @@
 use std::{
     mem::transmute,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
     time::Duration,
 };
 
@@ -187,6 +188,36 @@ pub(crate) fn read_ip_port<M: MemorySize>(
     })
 }
 
+/// Writes the path of a unix domain socket, it is truncated when `buf` is
+/// too small and `ret_len` always receives the full length
+pub(crate) fn write_unix_path<M: MemorySize>(
+    memory: &MemoryView,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+    path: Option<&Path>,
+) -> Result<(), Errno> {
+    let path = path
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let path = path.as_bytes();
+
+    let len: M::Offset = path.len().try_into().map_err(|_| Errno::Overflow)?;
+    ret_len
+        .write(memory, len)
+        .map_err(crate::mem_error_to_wasi)?;
+
+    let buf_len: u64 = buf_len.into();
+    let amt = path.len().min(buf_len as usize);
+    if amt > 0 {
+        let amt_offset: M::Offset = amt.try_into().map_err(|_| Errno::Overflow)?;
+        buf.slice(memory, amt_offset)
+            .and_then(|out| out.write_slice(&path[..amt]))
+            .map_err(crate::mem_error_to_wasi)?;
+    }
+    Ok(())
+}
+
 #[allow(dead_code)]
 pub(crate) fn write_ip_port<M: MemorySize>(
     memory: &MemoryView,
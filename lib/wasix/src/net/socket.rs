@@ -3,6 +3,7 @@ use std::{
     io,
     mem::MaybeUninit,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
     pin::Pin,
     sync::{Arc, RwLock},
     task::{Context, Poll},
@@ -13,8 +14,9 @@ use std::{
 use serde_derive::{Deserialize, Serialize};
 use virtual_mio::InterestHandler;
 use virtual_net::{
-    net_error_into_io_err, NetworkError, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
-    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    net_error_into_io_err, unix, NetworkError, UnixEndpoint, VirtualIcmpSocket, VirtualNetworking,
+    VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualUnixDatagramSocket, VirtualUnixListener, VirtualUnixSocket,
 };
 use wasmer_types::MemorySize;
 use wasmer_wasix_types::wasi::{Addressfamily, Errno, Rights, SockProto, Sockoption, Socktype};
@@ -82,6 +84,34 @@ pub enum InodeSocketKind {
         multicast_ttl: u32,
         is_dead: bool,
     },
+    UnixListener {
+        socket: Box<dyn VirtualUnixListener + Sync>,
+        accept_timeout: Option<Duration>,
+    },
+    UnixStream {
+        socket: Box<dyn VirtualUnixSocket + Sync>,
+        write_timeout: Option<Duration>,
+        read_timeout: Option<Duration>,
+    },
+    UnixDatagram {
+        socket: Box<dyn VirtualUnixDatagramSocket + Sync>,
+        peer: Option<UnixPeer>,
+    },
+    /// The name a unix domain socket is bound to in the file system, other
+    /// sockets connect to the bound socket through it
+    UnixAddress(UnixEndpoint),
+}
+
+/// The socket a connected unix domain datagram socket sends to
+#[derive(Debug, Clone)]
+pub enum UnixPeer {
+    /// Socket bound to a path of the virtual file system
+    Virtual {
+        endpoint: UnixEndpoint,
+        path: PathBuf,
+    },
+    /// Socket bound to a path of the host file system
+    Host(PathBuf),
 }
 
 pub enum WasiSocketOption {
@@ -353,7 +383,7 @@ impl InodeSocket {
         &self,
         tasks: &dyn VirtualTaskManager,
         net: &dyn VirtualNetworking,
-        _backlog: usize,
+        backlog: usize,
     ) -> Result<Option<InodeSocket>, Errno> {
         let timeout = self
             .opt_time(TimeType::AcceptTimeout)
@@ -365,6 +395,10 @@ impl InodeSocket {
             let inner = self.inner.protected.read().unwrap();
             match &inner.kind {
                 InodeSocketKind::PreSocket { props, addr, .. } => match props.ty {
+                    Socktype::Stream if props.family == Addressfamily::Unix => {
+                        tracing::warn!("wasi[?]::sock_listen - failed - unix socket not bound");
+                        return Err(Errno::Inval);
+                    }
                     Socktype::Stream => {
                         if addr.is_none() {
                             tracing::warn!("wasi[?]::sock_listen - failed - address not set");
@@ -430,6 +464,20 @@ impl InodeSocket {
                     tracing::warn!("wasi[?]::sock_listen - failed - not supported(udp-socket)");
                     return Err(Errno::Notsup);
                 }
+                InodeSocketKind::UnixListener { .. } => {
+                    drop(inner);
+                    let mut inner = self.inner.protected.write().unwrap();
+                    if let InodeSocketKind::UnixListener { socket, .. } = &mut inner.kind {
+                        socket.listen(backlog).map_err(net_error_into_wasi_err)?;
+                    }
+                    return Ok(None);
+                }
+                InodeSocketKind::UnixStream { .. }
+                | InodeSocketKind::UnixDatagram { .. }
+                | InodeSocketKind::UnixAddress(_) => {
+                    tracing::warn!("wasi[?]::sock_listen - failed - not supported(unix)");
+                    return Err(Errno::Notsup);
+                }
             }
         };
 
@@ -450,7 +498,7 @@ impl InodeSocket {
         tasks: &dyn VirtualTaskManager,
         nonblocking: bool,
         timeout: Option<Duration>,
-    ) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr), Errno> {
+    ) -> Result<(InodeSocketKind, SocketAddr), Errno> {
         struct SocketAccepter<'a> {
             sock: &'a InodeSocket,
             nonblocking: bool,
@@ -465,33 +513,57 @@ impl InodeSocket {
             }
         }
         impl Future for SocketAccepter<'_> {
-            type Output = Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr), Errno>;
+            type Output = Result<(InodeSocketKind, SocketAddr), Errno>;
             fn poll(
                 mut self: Pin<&mut Self>,
                 cx: &mut std::task::Context<'_>,
             ) -> std::task::Poll<Self::Output> {
                 loop {
                     let mut inner = self.sock.inner.protected.write().unwrap();
-                    return match &mut inner.kind {
-                        InodeSocketKind::TcpListener { socket, .. } => match socket.try_accept() {
-                            Ok((child, addr)) => Poll::Ready(Ok((child, addr))),
-                            Err(NetworkError::WouldBlock) if self.nonblocking => {
-                                Poll::Ready(Err(Errno::Again))
-                            }
-                            Err(NetworkError::WouldBlock) if !self.handler_registered => {
-                                let res = socket.set_handler(cx.waker().into());
-                                if let Err(err) = res {
-                                    return Poll::Ready(Err(net_error_into_wasi_err(err)));
-                                }
-                                drop(inner);
-                                self.handler_registered = true;
-                                continue;
+                    let res = match &mut inner.kind {
+                        InodeSocketKind::TcpListener { socket, .. } => {
+                            socket.try_accept().map(|(child, addr)| {
+                                let child = InodeSocketKind::TcpStream {
+                                    socket: child,
+                                    write_timeout: None,
+                                    read_timeout: None,
+                                };
+                                (child, addr)
+                            })
+                        }
+                        InodeSocketKind::UnixListener { socket, .. } => {
+                            // unix domain peers have no address that fits in
+                            // an inet address so an unspecified one is returned
+                            socket.try_accept().map(|child| {
+                                let child = InodeSocketKind::UnixStream {
+                                    socket: child,
+                                    write_timeout: None,
+                                    read_timeout: None,
+                                };
+                                (child, unspecified_addr())
+                            })
+                        }
+                        InodeSocketKind::PreSocket { .. } => {
+                            return Poll::Ready(Err(Errno::Notconn))
+                        }
+                        _ => return Poll::Ready(Err(Errno::Notsup)),
+                    };
+                    return match res {
+                        Ok((child, addr)) => Poll::Ready(Ok((child, addr))),
+                        Err(NetworkError::WouldBlock) if self.nonblocking => {
+                            Poll::Ready(Err(Errno::Again))
+                        }
+                        Err(NetworkError::WouldBlock) if !self.handler_registered => {
+                            let res = inner.set_handler(cx.waker().into());
+                            if let Err(err) = res {
+                                return Poll::Ready(Err(net_error_into_wasi_err(err)));
                             }
-                            Err(NetworkError::WouldBlock) => Poll::Pending,
-                            Err(err) => Poll::Ready(Err(net_error_into_wasi_err(err))),
-                        },
-                        InodeSocketKind::PreSocket { .. } => Poll::Ready(Err(Errno::Notconn)),
-                        _ => Poll::Ready(Err(Errno::Notsup)),
+                            drop(inner);
+                            self.handler_registered = true;
+                            continue;
+                        }
+                        Err(NetworkError::WouldBlock) => Poll::Pending,
+                        Err(err) => Poll::Ready(Err(net_error_into_wasi_err(err))),
                     };
                 }
             }
@@ -524,6 +596,14 @@ impl InodeSocket {
             InodeSocketKind::Raw(_) => {}
             InodeSocketKind::PreSocket { .. } => return Err(Errno::Notconn),
             InodeSocketKind::RemoteSocket { .. } => {}
+            InodeSocketKind::UnixListener { .. } => {}
+            InodeSocketKind::UnixStream { socket, .. } => {
+                socket
+                    .shutdown(std::net::Shutdown::Both)
+                    .map_err(net_error_into_wasi_err)?;
+            }
+            InodeSocketKind::UnixDatagram { .. } => {}
+            InodeSocketKind::UnixAddress(_) => {}
         };
         Ok(())
     }
@@ -630,6 +710,13 @@ impl InodeSocket {
                 true => WasiSocketStatus::Closed,
                 false => WasiSocketStatus::Opened,
             },
+            InodeSocketKind::UnixListener { .. } | InodeSocketKind::UnixDatagram { .. } => {
+                WasiSocketStatus::Opened
+            }
+            InodeSocketKind::UnixStream { socket, .. } => match socket.is_closed() {
+                true => WasiSocketStatus::Closed,
+                false => WasiSocketStatus::Opened,
+            },
             _ => WasiSocketStatus::Failed,
         })
     }
@@ -645,6 +732,7 @@ impl InodeSocket {
                         match props.family {
                             Addressfamily::Inet4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
                             Addressfamily::Inet6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                            Addressfamily::Unix => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
                             _ => return Err(Errno::Inval),
                         },
                         0,
@@ -664,6 +752,9 @@ impl InodeSocket {
             InodeSocketKind::RemoteSocket {
                 local_addr: addr, ..
             } => *addr,
+            InodeSocketKind::UnixListener { .. }
+            | InodeSocketKind::UnixStream { .. }
+            | InodeSocketKind::UnixDatagram { .. } => unspecified_addr(),
             _ => return Err(Errno::Notsup),
         })
     }
@@ -675,6 +766,7 @@ impl InodeSocket {
                 match props.family {
                     Addressfamily::Inet4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
                     Addressfamily::Inet6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                    Addressfamily::Unix => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
                     _ => return Err(Errno::Inval),
                 },
                 0,
@@ -701,10 +793,251 @@ impl InodeSocket {
                         })
                 })?,
             InodeSocketKind::RemoteSocket { peer_addr, .. } => *peer_addr,
+            InodeSocketKind::UnixStream { .. } | InodeSocketKind::UnixDatagram { .. } => {
+                unspecified_addr()
+            }
             _ => return Err(Errno::Notsup),
         })
     }
 
+    /// Returns the path the unix domain socket is bound to, unbound sockets
+    /// and sockets of other families return nothing
+    pub fn addr_local_unix(&self) -> Result<Option<PathBuf>, Errno> {
+        let inner = self.inner.protected.read().unwrap();
+        match &inner.kind {
+            InodeSocketKind::PreSocket { props, .. } if props.family == Addressfamily::Unix => {
+                Ok(None)
+            }
+            InodeSocketKind::UnixListener { socket, .. } => {
+                socket.addr_local().map_err(net_error_into_wasi_err)
+            }
+            InodeSocketKind::UnixStream { socket, .. } => {
+                socket.addr_local().map_err(net_error_into_wasi_err)
+            }
+            InodeSocketKind::UnixDatagram { socket, .. } => {
+                socket.addr_local().map_err(net_error_into_wasi_err)
+            }
+            _ => Err(Errno::Afnosupport),
+        }
+    }
+
+    /// Returns the path of the unix domain socket that this socket is
+    /// connected to
+    pub fn addr_peer_unix(&self) -> Result<Option<PathBuf>, Errno> {
+        let inner = self.inner.protected.read().unwrap();
+        match &inner.kind {
+            InodeSocketKind::PreSocket { props, .. } if props.family == Addressfamily::Unix => {
+                Err(Errno::Notconn)
+            }
+            InodeSocketKind::UnixStream { socket, .. } => {
+                socket.addr_peer().map_err(net_error_into_wasi_err)
+            }
+            InodeSocketKind::UnixDatagram { peer, .. } => match peer {
+                Some(UnixPeer::Host(path)) | Some(UnixPeer::Virtual { path, .. }) => {
+                    Ok(Some(path.clone()))
+                }
+                None => Err(Errno::Notconn),
+            },
+            InodeSocketKind::UnixListener { .. } => Err(Errno::Notconn),
+            _ => Err(Errno::Afnosupport),
+        }
+    }
+
+    /// Returns the type of a unix domain socket that is yet to be bound or
+    /// connected
+    fn unix_pre_socket_type(&self) -> Result<Socktype, Errno> {
+        let inner = self.inner.protected.read().unwrap();
+        match &inner.kind {
+            InodeSocketKind::PreSocket { props, .. } if props.family == Addressfamily::Unix => {
+                match props.ty {
+                    Socktype::Stream | Socktype::Dgram => Ok(props.ty),
+                    _ => Err(Errno::Notsup),
+                }
+            }
+            InodeSocketKind::PreSocket { .. } => Err(Errno::Afnosupport),
+            _ => Err(Errno::Inval),
+        }
+    }
+
+    /// Replaces a unix domain socket that is yet to be bound or connected
+    /// with the socket that was created for it, carrying over the options
+    /// that were set in the meantime
+    fn upgrade_unix(&self, mut kind: InodeSocketKind) -> Result<(), Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        let props = match &mut inner.kind {
+            InodeSocketKind::PreSocket { props, .. } => props,
+            _ => return Err(Errno::Inval),
+        };
+        let handler = props.handler.take();
+        match &mut kind {
+            InodeSocketKind::UnixListener {
+                socket,
+                accept_timeout,
+            } => {
+                *accept_timeout = props.accept_timeout;
+                if let Some(handler) = handler {
+                    socket
+                        .set_handler(handler)
+                        .map_err(net_error_into_wasi_err)?;
+                }
+            }
+            InodeSocketKind::UnixStream {
+                socket,
+                write_timeout,
+                read_timeout,
+            } => {
+                *write_timeout = props.write_timeout;
+                *read_timeout = props.read_timeout;
+                if let Some(handler) = handler {
+                    socket
+                        .set_handler(handler)
+                        .map_err(net_error_into_wasi_err)?;
+                }
+            }
+            InodeSocketKind::UnixDatagram { socket, .. } => {
+                if let Some(handler) = handler {
+                    socket
+                        .set_handler(handler)
+                        .map_err(net_error_into_wasi_err)?;
+                }
+            }
+            _ => return Err(Errno::Inval),
+        }
+        inner.kind = kind;
+        Ok(())
+    }
+
+    /// Binds a unix domain socket to a path of the virtual file system and
+    /// returns the endpoint that the path is to refer to
+    pub fn bind_unix(&self, path: PathBuf) -> Result<UnixEndpoint, Errno> {
+        let (kind, endpoint) = match self.unix_pre_socket_type()? {
+            Socktype::Stream => {
+                let (socket, endpoint) = unix::listen(path);
+                let kind = InodeSocketKind::UnixListener {
+                    socket: Box::new(socket),
+                    accept_timeout: None,
+                };
+                (kind, endpoint)
+            }
+            _ => {
+                let (socket, endpoint) = unix::bind_datagram(Some(path));
+                let kind = InodeSocketKind::UnixDatagram {
+                    socket: Box::new(socket),
+                    peer: None,
+                };
+                (kind, endpoint)
+            }
+        };
+        self.upgrade_unix(kind)?;
+        Ok(endpoint)
+    }
+
+    /// Binds a unix domain socket to a path of the host file system
+    pub async fn bind_unix_host(
+        &self,
+        net: &dyn VirtualNetworking,
+        path: &Path,
+    ) -> Result<(), Errno> {
+        let kind = match self.unix_pre_socket_type()? {
+            Socktype::Stream => InodeSocketKind::UnixListener {
+                socket: net
+                    .listen_unix(path)
+                    .await
+                    .map_err(net_error_into_wasi_err)?,
+                accept_timeout: None,
+            },
+            _ => InodeSocketKind::UnixDatagram {
+                socket: net
+                    .bind_unix_datagram(Some(path))
+                    .await
+                    .map_err(net_error_into_wasi_err)?,
+                peer: None,
+            },
+        };
+        self.upgrade_unix(kind)
+    }
+
+    /// Connects a unix domain socket to a socket that is bound to a path of
+    /// the virtual file system
+    pub fn connect_unix(&self, endpoint: &UnixEndpoint, path: PathBuf) -> Result<(), Errno> {
+        {
+            let mut inner = self.inner.protected.write().unwrap();
+            if let InodeSocketKind::UnixDatagram { peer, .. } = &mut inner.kind {
+                if endpoint.is_stream() {
+                    return Err(Errno::Prototype);
+                }
+                peer.replace(UnixPeer::Virtual {
+                    endpoint: endpoint.clone(),
+                    path,
+                });
+                return Ok(());
+            }
+        }
+
+        let kind = match self.unix_pre_socket_type()? {
+            Socktype::Stream => {
+                if !endpoint.is_stream() {
+                    return Err(Errno::Prototype);
+                }
+                let socket = endpoint.connect(None).map_err(net_error_into_wasi_err)?;
+                InodeSocketKind::UnixStream {
+                    socket: Box::new(socket),
+                    write_timeout: None,
+                    read_timeout: None,
+                }
+            }
+            _ => {
+                if endpoint.is_stream() {
+                    return Err(Errno::Prototype);
+                }
+                let (socket, _) = unix::bind_datagram(None);
+                InodeSocketKind::UnixDatagram {
+                    socket: Box::new(socket),
+                    peer: Some(UnixPeer::Virtual {
+                        endpoint: endpoint.clone(),
+                        path,
+                    }),
+                }
+            }
+        };
+        self.upgrade_unix(kind)
+    }
+
+    /// Connects a unix domain socket to a socket that is bound to a path of
+    /// the host file system
+    pub async fn connect_unix_host(
+        &self,
+        net: &dyn VirtualNetworking,
+        path: &Path,
+    ) -> Result<(), Errno> {
+        {
+            let mut inner = self.inner.protected.write().unwrap();
+            if let InodeSocketKind::UnixDatagram { peer, .. } = &mut inner.kind {
+                peer.replace(UnixPeer::Host(path.to_path_buf()));
+                return Ok(());
+            }
+        }
+
+        let kind = match self.unix_pre_socket_type()? {
+            Socktype::Stream => InodeSocketKind::UnixStream {
+                socket: net
+                    .connect_unix(path)
+                    .await
+                    .map_err(net_error_into_wasi_err)?,
+                write_timeout: None,
+                read_timeout: None,
+            },
+            _ => InodeSocketKind::UnixDatagram {
+                socket: net
+                    .bind_unix_datagram(None)
+                    .await
+                    .map_err(net_error_into_wasi_err)?,
+                peer: Some(UnixPeer::Host(path.to_path_buf())),
+            },
+        };
+        self.upgrade_unix(kind)
+    }
+
     pub fn set_opt_flag(&mut self, option: WasiSocketOption, val: bool) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
         match &mut inner.kind {
@@ -896,6 +1229,11 @@ impl InodeSocket {
                 write_timeout,
                 read_timeout,
                 ..
+            }
+            | InodeSocketKind::UnixStream {
+                write_timeout,
+                read_timeout,
+                ..
             } => {
                 match ty {
                     TimeType::WriteTimeout => *write_timeout = timeout,
@@ -904,7 +1242,8 @@ impl InodeSocket {
                 }
                 Ok(())
             }
-            InodeSocketKind::TcpListener { accept_timeout, .. } => {
+            InodeSocketKind::TcpListener { accept_timeout, .. }
+            | InodeSocketKind::UnixListener { accept_timeout, .. } => {
                 match ty {
                     TimeType::AcceptTimeout => *accept_timeout = timeout,
                     _ => return Err(Errno::Inval),
@@ -933,12 +1272,18 @@ impl InodeSocket {
                 read_timeout,
                 write_timeout,
                 ..
+            }
+            | InodeSocketKind::UnixStream {
+                read_timeout,
+                write_timeout,
+                ..
             } => Ok(match ty {
                 TimeType::ReadTimeout => *read_timeout,
                 TimeType::WriteTimeout => *write_timeout,
                 _ => return Err(Errno::Inval),
             }),
-            InodeSocketKind::TcpListener { accept_timeout, .. } => Ok(match ty {
+            InodeSocketKind::TcpListener { accept_timeout, .. }
+            | InodeSocketKind::UnixListener { accept_timeout, .. } => Ok(match ty {
                 TimeType::AcceptTimeout => *accept_timeout,
                 _ => return Err(Errno::Inval),
             }),
@@ -1104,6 +1449,15 @@ impl InodeSocket {
                                 Err(NetworkError::NotConnected)
                             }
                         }
+                        InodeSocketKind::UnixStream { socket, .. } => socket.try_send(self.data),
+                        InodeSocketKind::UnixDatagram { socket, peer } => match peer {
+                            Some(UnixPeer::Virtual { endpoint, .. }) => {
+                                let local = socket.addr_local().ok().flatten();
+                                endpoint.try_send(self.data, local, Some(cx.waker()))
+                            }
+                            Some(UnixPeer::Host(path)) => socket.try_send_to(self.data, path),
+                            None => Err(NetworkError::NotConnected),
+                        },
                         InodeSocketKind::PreSocket { .. } => {
                             return Poll::Ready(Err(Errno::Notconn))
                         }
@@ -1286,6 +1640,12 @@ impl InodeSocket {
                                 }
                             }
                         }
+                        InodeSocketKind::UnixStream { socket, .. } => {
+                            socket.try_recv(self.data, peek)
+                        }
+                        InodeSocketKind::UnixDatagram { socket, .. } => {
+                            socket.try_recv_from(self.data, peek).map(|(amt, _)| amt)
+                        }
                         InodeSocketKind::RemoteSocket { is_dead, .. } => {
                             return match is_dead {
                                 true => Poll::Ready(Ok(0)),
@@ -1372,6 +1732,9 @@ impl InodeSocket {
                         InodeSocketKind::UdpSocket { socket, .. } => {
                             socket.try_recv_from(self.data, peek)
                         }
+                        InodeSocketKind::UnixDatagram { socket, .. } => socket
+                            .try_recv_from(self.data, peek)
+                            .map(|(amt, _)| (amt, unspecified_addr())),
                         InodeSocketKind::RemoteSocket {
                             is_dead, peer_addr, ..
                         } => {
@@ -1427,6 +1790,9 @@ impl InodeSocket {
             InodeSocketKind::TcpStream { socket, .. } => {
                 socket.shutdown(how).map_err(net_error_into_wasi_err)?;
             }
+            InodeSocketKind::UnixStream { socket, .. } => {
+                socket.shutdown(how).map_err(net_error_into_wasi_err)?;
+            }
             InodeSocketKind::RemoteSocket { .. } => return Ok(()),
             InodeSocketKind::PreSocket { .. } => return Err(Errno::Notconn),
             _ => return Err(Errno::Notsup),
@@ -1434,12 +1800,28 @@ impl InodeSocket {
         Ok(())
     }
 
+    /// Upgrades a connected TCP stream to TLS that is terminated on the host
+    #[cfg(feature = "host-tls")]
+    pub fn upgrade_tls(&self, session: virtual_net::TlsSession) -> Result<(), Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        match &mut inner.kind {
+            InodeSocketKind::TcpStream { socket, .. } => {
+                session.upgrade(socket);
+                Ok(())
+            }
+            InodeSocketKind::PreSocket { .. } => Err(Errno::Notconn),
+            _ => Err(Errno::Notsup),
+        }
+    }
+
     pub async fn can_write(&self) -> bool {
         if let Ok(mut guard) = self.inner.protected.try_write() {
             #[allow(clippy::match_like_matches_macro)]
             match &mut guard.kind {
                 InodeSocketKind::TcpStream { .. }
                 | InodeSocketKind::UdpSocket { .. }
+                | InodeSocketKind::UnixStream { .. }
+                | InodeSocketKind::UnixDatagram { .. }
                 | InodeSocketKind::Raw(..) => true,
                 InodeSocketKind::RemoteSocket { is_dead, .. } => !(*is_dead),
                 _ => false,
@@ -1464,6 +1846,10 @@ impl InodeSocketProtected {
             InodeSocketKind::RemoteSocket { props, .. } => {
                 props.handler.take();
             }
+            InodeSocketKind::UnixListener { socket, .. } => socket.remove_handler(),
+            InodeSocketKind::UnixStream { socket, .. } => socket.remove_handler(),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.remove_handler(),
+            InodeSocketKind::UnixAddress(_) => {}
         }
     }
 
@@ -1479,6 +1865,10 @@ impl InodeSocketProtected {
                 true => Poll::Ready(Ok(0)),
                 false => Poll::Pending,
             },
+            InodeSocketKind::UnixListener { socket, .. } => socket.poll_read_ready(cx),
+            InodeSocketKind::UnixStream { socket, .. } => socket.poll_read_ready(cx),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.poll_read_ready(cx),
+            InodeSocketKind::UnixAddress(_) => Poll::Pending,
         }
         .map_err(net_error_into_io_err)
     }
@@ -1495,6 +1885,10 @@ impl InodeSocketProtected {
                 true => Poll::Ready(Ok(0)),
                 false => Poll::Pending,
             },
+            InodeSocketKind::UnixListener { socket, .. } => socket.poll_write_ready(cx),
+            InodeSocketKind::UnixStream { socket, .. } => socket.poll_write_ready(cx),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.poll_write_ready(cx),
+            InodeSocketKind::UnixAddress(_) => Poll::Pending,
         }
         .map_err(net_error_into_io_err)
     }
@@ -1514,10 +1908,20 @@ impl InodeSocketProtected {
                 props.handler.replace(handler);
                 Ok(())
             }
+            InodeSocketKind::UnixListener { socket, .. } => socket.set_handler(handler),
+            InodeSocketKind::UnixStream { socket, .. } => socket.set_handler(handler),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.set_handler(handler),
+            InodeSocketKind::UnixAddress(_) => Err(NetworkError::Unsupported),
         }
     }
 }
 
+/// Address reported for unix domain sockets by calls that can only return
+/// inet addresses
+fn unspecified_addr() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+}
+
 #[derive(Default)]
 struct IndefinitePoll {}
 
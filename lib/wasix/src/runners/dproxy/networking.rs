@@ -1,6 +1,7 @@
 use std::sync::LazyLock;
 use std::{
     net::{IpAddr, SocketAddr},
+    path::Path,
     sync::{Arc, Mutex},
     task::{Context, Poll, Waker},
     time::Duration,
@@ -9,7 +10,8 @@ use std::{
 use virtual_net::{
     host::LocalNetworking, loopback::LoopbackNetworking, IpCidr, IpRoute, NetworkError,
     StreamSecurity, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualTcpListener,
-    VirtualTcpSocket, VirtualUdpSocket,
+    VirtualTcpSocket, VirtualUdpSocket, VirtualUnixDatagramSocket, VirtualUnixListener,
+    VirtualUnixSocket,
 };
 
 #[derive(Debug, Default)]
@@ -219,4 +221,30 @@ impl VirtualNetworking for LocalWithLoopbackNetworking {
     ) -> Result<Vec<IpAddr>, NetworkError> {
         self.inner_networking.resolve(host, port, dns_server).await
     }
+
+    /// Binds a unix domain socket to a path of the host file system that
+    /// connections can then be accepted on
+    async fn listen_unix(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn VirtualUnixListener + Sync>, NetworkError> {
+        self.inner_networking.listen_unix(path).await
+    }
+
+    /// Connects to the unix domain socket at a path of the host file system
+    async fn connect_unix(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn VirtualUnixSocket + Sync>, NetworkError> {
+        self.inner_networking.connect_unix(path).await
+    }
+
+    /// Opens a datagram unix domain socket, which is bound to a path of the
+    /// host file system if one is given
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>, NetworkError> {
+        self.inner_networking.bind_unix_datagram(path).await
+    }
 }
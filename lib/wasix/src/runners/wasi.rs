@@ -15,7 +15,7 @@ use crate::{
     journal::{DynJournal, DynReadableJournal, SnapshotTrigger},
     runners::{wasi_common::CommonWasiOptions, MappedDirectory, MountedDirectory},
     runtime::task_manager::VirtualTaskManagerExt,
-    Runtime, WasiEnvBuilder, WasiError, WasiRuntimeError,
+    Runtime, WasiEnvBuilder, WasiError, WasiIdentity, WasiRuntimeError,
 };
 
 use super::wasi_common::{MappedCommand, MAPPED_CURRENT_DIR_DEFAULT_PATH};
@@ -135,6 +135,25 @@ impl WasiRunner {
         self
     }
 
+    /// Run the program as a particular user and group.
+    pub fn with_identity(&mut self, identity: WasiIdentity) -> &mut Self {
+        self.wasi.identity = Some(identity);
+        self
+    }
+
+    /// Pass the unix domain sockets of a guest directory through to the
+    /// sockets of a host directory.
+    pub fn with_host_socket_directories<I, D>(&mut self, dirs: I) -> &mut Self
+    where
+        I: IntoIterator<Item = D>,
+        D: Into<MappedDirectory>,
+    {
+        self.wasi
+            .host_socket_dirs
+            .extend(dirs.into_iter().map(|d| d.into()));
+        self
+    }
+
     /// Add a package that should be available to the instance at runtime.
     pub fn with_injected_package(&mut self, pkg: BinaryPackage) -> &mut Self {
         self.wasi.injected_packages.push(pkg);
@@ -316,6 +335,14 @@ impl WasiRunner {
             builder.set_current_dir(cwd);
         }
 
+        if let Some(identity) = self.wasi.identity {
+            builder.set_identity(identity);
+        }
+
+        for MappedDirectory { host, guest } in &self.wasi.host_socket_dirs {
+            builder.add_host_socket_dir(guest, host);
+        }
+
         self.wasi
             .prepare_webc_env(&mut builder, container_fs, wasi, root_fs)?;
 
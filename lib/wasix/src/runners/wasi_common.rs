@@ -7,14 +7,18 @@ use std::{
 use anyhow::{Context, Error};
 use futures::future::BoxFuture;
 use tokio::runtime::Handle;
-use virtual_fs::{FileSystem, FsError, OverlayFileSystem, RootFileSystemBuilder, TmpFileSystem};
+use virtual_fs::{
+    FileSystem, FsError, OverlayFileSystem, QuotaFileSystem, QuotaLimits, RootFileSystemBuilder,
+    TmpFileSystem,
+};
+use wasmer_config::app::AppVolume;
 use webc::metadata::annotations::Wasi as WasiAnnotation;
 
 use crate::{
     bin_factory::BinaryPackage,
     capabilities::Capabilities,
     journal::{DynJournal, DynReadableJournal, SnapshotTrigger},
-    WasiEnvBuilder,
+    WasiEnvBuilder, WasiIdentity,
 };
 
 pub const MAPPED_CURRENT_DIR_DEFAULT_PATH: &str = "/home";
@@ -46,6 +50,8 @@ pub(crate) struct CommonWasiOptions {
     pub(crate) stop_running_after_snapshot: bool,
     pub(crate) skip_stdio_during_bootstrap: bool,
     pub(crate) current_dir: Option<PathBuf>,
+    pub(crate) identity: Option<WasiIdentity>,
+    pub(crate) host_socket_dirs: Vec<MappedDirectory>,
 }
 
 impl CommonWasiOptions {
@@ -274,6 +280,32 @@ pub struct MountedDirectory {
     pub fs: Arc<dyn FileSystem + Send + Sync>,
 }
 
+impl MountedDirectory {
+    /// Mounts the file system that backs a volume of an app at the mount
+    /// point of the volume, the quota of the volume (if it has one) is
+    /// enforced with a [`QuotaFileSystem`].
+    pub fn from_app_volume(
+        volume: &AppVolume,
+        fs: Arc<dyn FileSystem + Send + Sync>,
+    ) -> Result<Self, FsError> {
+        let fs: Arc<dyn FileSystem + Send + Sync> = match &volume.quota {
+            Some(quota) => Arc::new(QuotaFileSystem::new(
+                fs,
+                QuotaLimits {
+                    max_bytes: quota.max_size.map(|size| size.as_u64()),
+                    max_inodes: quota.max_entries,
+                },
+            )?),
+            None => fs,
+        };
+
+        Ok(MountedDirectory {
+            guest: volume.mount.clone(),
+            fs,
+        })
+    }
+}
+
 /// A directory that should be mapped from the host filesystem into a WASI
 /// instance (the "guest").
 ///
@@ -364,6 +396,46 @@ impl<F: FileSystem> virtual_fs::FileSystem for RelativeOrAbsolutePathHack<F> {
         self.execute(path, |fs, p| fs.remove_file(p))
     }
 
+    fn symlink(&self, original: &Path, link: &Path) -> virtual_fs::Result<()> {
+        self.execute(link, |fs, p| fs.symlink(original, p))
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> virtual_fs::Result<()> {
+        self.execute(link, |fs, p| fs.hard_link(original, p))
+    }
+
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: virtual_fs::Permissions,
+    ) -> virtual_fs::Result<()> {
+        self.execute(path, |fs, p| fs.set_permissions(p, permissions))
+    }
+
+    fn get_xattr(&self, path: &Path, name: &str) -> virtual_fs::Result<Vec<u8>> {
+        self.execute(path, |fs, p| fs.get_xattr(p, name))
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> virtual_fs::Result<()> {
+        self.execute(path, |fs, p| fs.set_xattr(p, name, value))
+    }
+
+    fn list_xattr(&self, path: &Path) -> virtual_fs::Result<Vec<String>> {
+        self.execute(path, |fs, p| fs.list_xattr(p))
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> virtual_fs::Result<()> {
+        self.execute(path, |fs, p| fs.remove_xattr(p, name))
+    }
+
+    fn watch(
+        &self,
+        path: &Path,
+        mask: virtual_fs::WatchMask,
+    ) -> virtual_fs::Result<virtual_fs::Watch> {
+        self.execute(path, |fs, p| fs.watch(p, mask))
+    }
+
     fn new_open_options(&self) -> virtual_fs::OpenOptions {
         virtual_fs::OpenOptions::new(self)
     }
@@ -399,6 +471,7 @@ mod tests {
     use std::time::SystemTime;
 
     use tempfile::TempDir;
+    use tokio::io::AsyncWriteExt;
     use virtual_fs::{DirEntry, FileType, Metadata, WebcVolumeFileSystem};
     use wasmer_package::utils::from_bytes;
 
@@ -466,6 +539,32 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn app_volume_quota_is_enforced() {
+        let volume: AppVolume = serde_yaml::from_str(
+            "name: data\nmount: /data\nquota:\n  max_size: 16B\n  max_entries: 8\n",
+        )
+        .unwrap();
+        let MountedDirectory { guest, fs } =
+            MountedDirectory::from_app_volume(&volume, Arc::new(TmpFileSystem::new())).unwrap();
+        assert_eq!(guest, "/data");
+
+        let write = |path: &'static str, data: Vec<u8>| {
+            let fs = fs.clone();
+            async move {
+                let mut file = fs.new_open_options().write(true).create(true).open(path)?;
+                file.write_all(&data).await?;
+                file.flush().await?;
+                Ok::<_, FsError>(())
+            }
+        };
+        assert_eq!(write("/small.txt", b"hello".to_vec()).await, Ok(()));
+        assert_eq!(
+            write("/large.txt", vec![0u8; 32]).await,
+            Err(FsError::StorageFull)
+        );
+    }
+
     #[tokio::test]
     #[cfg_attr(not(feature = "host-fs"), ignore)]
     async fn python_use_case() {
@@ -545,6 +644,17 @@ mod tests {
                         .and_then(unix_timestamp_nanos)
                         .unwrap_or(0),
                     len: contents.len() as u64,
+                    #[cfg(unix)]
+                    permissions: {
+                        use std::os::unix::fs::MetadataExt;
+                        Some(virtual_fs::Permissions::new(
+                            metadata.mode(),
+                            metadata.uid(),
+                            metadata.gid(),
+                        ))
+                    },
+                    #[cfg(not(unix))]
+                    permissions: None,
                 })
             }]
         );
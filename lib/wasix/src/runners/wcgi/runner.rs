@@ -18,7 +18,7 @@ use crate::{
     runners::{
         wasi_common::CommonWasiOptions,
         wcgi::handler::{Handler, SharedState},
-        MappedDirectory,
+        MappedDirectory, MountedDirectory,
     },
     runtime::task_manager::VirtualTaskManagerExt,
     Runtime, WasiEnvBuilder,
@@ -269,6 +269,16 @@ impl Config {
         self
     }
 
+    /// Mount [`FileSystem`](virtual_fs::FileSystem) instances at their
+    /// guest locations.
+    pub fn mount_directories(
+        &mut self,
+        mounts: impl IntoIterator<Item = MountedDirectory>,
+    ) -> &mut Self {
+        self.wasi.mounts.extend(mounts);
+        self
+    }
+
     /// Set callbacks that will be triggered at various points in the runner's
     /// lifecycle.
     pub fn callbacks(&mut self, callbacks: impl Callbacks + 'static) -> &mut Self {
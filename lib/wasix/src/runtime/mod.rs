@@ -113,6 +113,13 @@ where
         None
     }
 
+    /// Certificates and trust roots used when guests upgrade their
+    /// sockets to TLS, upgrades are refused when this is not set
+    #[cfg(feature = "host-tls")]
+    fn tls(&self) -> Option<&virtual_net::tls::TlsConfig> {
+        None
+    }
+
     /// Load the module for a command.
     ///
     /// NOTE: This always be preferred over [`Self::load_module`] to avoid
@@ -267,6 +274,8 @@ pub struct PluggableRuntime {
     pub engine: wasmer::Engine,
     pub module_cache: Arc<dyn ModuleCache + Send + Sync>,
     pub tty: Option<Arc<dyn TtyBridge + Send + Sync>>,
+    #[cfg(feature = "host-tls")]
+    pub tls: Option<Arc<virtual_net::tls::TlsConfig>>,
     #[cfg(feature = "journal")]
     pub read_only_journals: Vec<Arc<DynReadableJournal>>,
     #[cfg(feature = "journal")]
@@ -305,6 +314,8 @@ impl PluggableRuntime {
             source: Arc::new(source),
             package_loader: Arc::new(loader),
             module_cache: Arc::new(module_cache::in_memory()),
+            #[cfg(feature = "host-tls")]
+            tls: None,
             #[cfg(feature = "journal")]
             read_only_journals: Vec::new(),
             #[cfg(feature = "journal")]
@@ -359,6 +370,12 @@ impl PluggableRuntime {
         self
     }
 
+    #[cfg(feature = "host-tls")]
+    pub fn set_tls(&mut self, tls: virtual_net::tls::TlsConfig) -> &mut Self {
+        self.tls = Some(Arc::new(tls));
+        self
+    }
+
     #[cfg(feature = "journal")]
     pub fn add_read_only_journal(&mut self, journal: Arc<DynReadableJournal>) -> &mut Self {
         self.read_only_journals.push(journal);
@@ -405,6 +422,11 @@ impl Runtime for PluggableRuntime {
         self.tty.as_deref()
     }
 
+    #[cfg(feature = "host-tls")]
+    fn tls(&self) -> Option<&virtual_net::tls::TlsConfig> {
+        self.tls.as_deref()
+    }
+
     fn module_cache(&self) -> Arc<dyn ModuleCache + Send + Sync> {
         self.module_cache.clone()
     }
@@ -438,6 +460,8 @@ pub struct OverriddenRuntime {
     engine: Option<wasmer::Engine>,
     module_cache: Option<Arc<dyn ModuleCache + Send + Sync>>,
     tty: Option<Arc<dyn TtyBridge + Send + Sync>>,
+    #[cfg(feature = "host-tls")]
+    tls: Option<Arc<virtual_net::tls::TlsConfig>>,
     #[cfg(feature = "journal")]
     pub read_only_journals: Option<Vec<Arc<DynReadableJournal>>>,
     #[cfg(feature = "journal")]
@@ -456,6 +480,8 @@ impl OverriddenRuntime {
             engine: None,
             module_cache: None,
             tty: None,
+            #[cfg(feature = "host-tls")]
+            tls: None,
             #[cfg(feature = "journal")]
             read_only_journals: None,
             #[cfg(feature = "journal")]
@@ -506,6 +532,12 @@ impl OverriddenRuntime {
         self
     }
 
+    #[cfg(feature = "host-tls")]
+    pub fn with_tls(mut self, tls: Arc<virtual_net::tls::TlsConfig>) -> Self {
+        self.tls.replace(tls);
+        self
+    }
+
     #[cfg(feature = "journal")]
     pub fn with_read_only_journals(mut self, journals: Vec<Arc<DynReadableJournal>>) -> Self {
         self.read_only_journals.replace(journals);
@@ -592,6 +624,15 @@ impl Runtime for OverriddenRuntime {
         }
     }
 
+    #[cfg(feature = "host-tls")]
+    fn tls(&self) -> Option<&virtual_net::tls::TlsConfig> {
+        if let Some(tls) = self.tls.as_ref() {
+            Some(tls.deref())
+        } else {
+            self.inner.tls()
+        }
+    }
+
     #[cfg(feature = "journal")]
     fn read_only_journals<'a>(&'a self) -> Box<dyn Iterator<Item = Arc<DynReadableJournal>> + 'a> {
         if let Some(journals) = self.read_only_journals.as_ref() {
@@ -673,6 +673,62 @@ where
         self.inner.remove_file(&path)
     }
 
+    fn symlink(&self, original: &Path, link: &Path) -> virtual_fs::Result<()> {
+        // Relative targets are resolved against the link so only absolute
+        // targets need to be mapped
+        let original = if original.has_root() {
+            self.path(original)?
+        } else {
+            original.to_owned()
+        };
+        let link = self.path(link)?;
+        self.inner.symlink(&original, &link)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> virtual_fs::Result<()> {
+        let original = self.path(original)?;
+        let link = self.path(link)?;
+        self.inner.hard_link(&original, &link)
+    }
+
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: virtual_fs::Permissions,
+    ) -> virtual_fs::Result<()> {
+        let path = self.path(path)?;
+        self.inner.set_permissions(&path, permissions)
+    }
+
+    fn get_xattr(&self, path: &Path, name: &str) -> virtual_fs::Result<Vec<u8>> {
+        let path = self.path(path)?;
+        self.inner.get_xattr(&path, name)
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> virtual_fs::Result<()> {
+        let path = self.path(path)?;
+        self.inner.set_xattr(&path, name, value)
+    }
+
+    fn list_xattr(&self, path: &Path) -> virtual_fs::Result<Vec<String>> {
+        let path = self.path(path)?;
+        self.inner.list_xattr(&path)
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> virtual_fs::Result<()> {
+        let path = self.path(path)?;
+        self.inner.remove_xattr(&path, name)
+    }
+
+    fn watch(
+        &self,
+        path: &Path,
+        mask: virtual_fs::WatchMask,
+    ) -> virtual_fs::Result<virtual_fs::Watch> {
+        let path = self.path(path)?;
+        self.inner.watch(&path, mask)
+    }
+
     fn new_open_options(&self) -> virtual_fs::OpenOptions {
         virtual_fs::OpenOptions::new(self)
     }
@@ -17,7 +17,7 @@ use crate::journal::{DynJournal, DynReadableJournal, SnapshotTrigger};
 use crate::{
     bin_factory::{BinFactory, BinaryPackage},
     capabilities::Capabilities,
-    fs::{WasiFs, WasiFsRoot, WasiInodes},
+    fs::{WasiFs, WasiFsRoot, WasiIdentity, WasiInodes},
     os::task::control_plane::{ControlPlaneConfig, ControlPlaneError, WasiControlPlane},
     state::WasiState,
     syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
@@ -70,6 +70,8 @@ pub struct WasiEnvBuilder {
     pub(super) engine: Option<Engine>,
     pub(super) runtime: Option<Arc<dyn crate::Runtime + Send + Sync + 'static>>,
     pub(super) current_dir: Option<PathBuf>,
+    pub(super) identity: Option<WasiIdentity>,
+    pub(super) host_socket_dirs: Vec<(PathBuf, PathBuf)>,
 
     /// List of webc dependencies to be injected.
     pub(super) uses: Vec<BinaryPackage>,
@@ -654,6 +656,38 @@ impl WasiEnvBuilder {
         self
     }
 
+    pub fn get_identity(&self) -> Option<WasiIdentity> {
+        self.identity
+    }
+
+    /// Runs the program as a particular user and group, the permissions of
+    /// the entries in the file system are then enforced by the path syscalls.
+    pub fn set_identity(&mut self, identity: WasiIdentity) {
+        self.identity = Some(identity);
+    }
+
+    /// Runs the program as a particular user and group, the permissions of
+    /// the entries in the file system are then enforced by the path syscalls.
+    pub fn identity(mut self, identity: WasiIdentity) -> Self {
+        self.set_identity(identity);
+        self
+    }
+
+    /// Passes the unix domain sockets bound or connected under a directory
+    /// of the guest through to the sockets under a directory of the host,
+    /// any other unix domain socket only exists in the virtual file system.
+    pub fn add_host_socket_dir(&mut self, guest: impl Into<PathBuf>, host: impl Into<PathBuf>) {
+        self.host_socket_dirs.push((guest.into(), host.into()));
+    }
+
+    /// Passes the unix domain sockets bound or connected under a directory
+    /// of the guest through to the sockets under a directory of the host,
+    /// any other unix domain socket only exists in the virtual file system.
+    pub fn host_socket_dir(mut self, guest: impl Into<PathBuf>, host: impl Into<PathBuf>) -> Self {
+        self.add_host_socket_dir(guest, host);
+        self
+    }
+
     /// Overwrite the default WASI `stdout`, if you want to hold on to the
     /// original `stdout` use [`WasiFs::swap_file`] after building.
     pub fn stdout(mut self, new_file: Box<dyn VirtualFile + Send + Sync + 'static>) -> Self {
@@ -908,6 +942,13 @@ impl WasiEnvBuilder {
             wasi_fs.set_current_dir(s);
         }
 
+        wasi_fs.set_identity(self.identity);
+
+        for (guest, host) in &self.host_socket_dirs {
+            let guest = wasi_fs.unix_socket_path(&guest.to_string_lossy());
+            wasi_fs.add_host_socket_dir(guest, host.clone());
+        }
+
         for id in &self.included_packages {
             wasi_fs.has_unioned.lock().unwrap().insert(id.clone());
         }
@@ -205,6 +205,33 @@ impl WasiState {
             .map_err(fs_error_into_wasi_err)
     }
 
+    pub(crate) fn fs_symlink<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        original: P,
+        link: Q,
+    ) -> Result<(), FsError> {
+        self.fs.root_fs.symlink(original.as_ref(), link.as_ref())
+    }
+
+    pub(crate) fn fs_hard_link<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        original: P,
+        link: Q,
+    ) -> Result<(), FsError> {
+        self.fs.root_fs.hard_link(original.as_ref(), link.as_ref())
+    }
+
+    pub(crate) fn fs_set_permissions<P: AsRef<Path>>(
+        &self,
+        path: P,
+        permissions: virtual_fs::Permissions,
+    ) -> Result<(), Errno> {
+        self.fs
+            .root_fs
+            .set_permissions(path.as_ref(), permissions)
+            .map_err(fs_error_into_wasi_err)
+    }
+
     pub(crate) fn fs_new_open_options(&self) -> OpenOptions {
         self.fs.root_fs.new_open_options()
     }
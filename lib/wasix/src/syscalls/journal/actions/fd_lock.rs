@@ -0,0 +1,14 @@
+use super::*;
+
+impl JournalSyscallPlayer<'_, '_> {
+    #[allow(clippy::result_large_err)]
+    pub(crate) unsafe fn action_fd_lock(
+        &mut self,
+        fd: Fd,
+        op: Flockop,
+    ) -> Result<(), WasiRuntimeError> {
+        tracing::trace!(%fd, ?op, "Replay journal - FdLock");
+        JournalEffector::apply_fd_lock(&mut self.ctx, fd, op).map_err(anyhow_err_to_runtime_err)?;
+        Ok(())
+    }
+}
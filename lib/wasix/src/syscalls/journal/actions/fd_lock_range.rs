@@ -0,0 +1,17 @@
+use super::*;
+
+impl JournalSyscallPlayer<'_, '_> {
+    #[allow(clippy::result_large_err)]
+    pub(crate) unsafe fn action_fd_lock_range(
+        &mut self,
+        fd: Fd,
+        op: Flockop,
+        start: u64,
+        len: u64,
+    ) -> Result<(), WasiRuntimeError> {
+        tracing::trace!(%fd, ?op, %start, %len, "Replay journal - FdLockRange");
+        JournalEffector::apply_fd_lock_range(&mut self.ctx, fd, op, start, len)
+            .map_err(anyhow_err_to_runtime_err)?;
+        Ok(())
+    }
+}
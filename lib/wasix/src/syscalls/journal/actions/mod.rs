@@ -3,6 +3,8 @@ mod fd_advise;
 mod fd_allocate;
 mod fd_close;
 mod fd_dup;
+mod fd_lock;
+mod fd_lock_range;
 mod fd_open;
 mod fd_renumber;
 mod fd_seek;
@@ -38,6 +40,7 @@ use wasmer_wasix_types::wasi::Advice;
 use wasmer_wasix_types::wasi::ExitCode;
 use wasmer_wasix_types::wasi::Fd;
 use wasmer_wasix_types::wasi::Filesize;
+use wasmer_wasix_types::wasi::Flockop;
 use wasmer_wasix_types::wasi::Tty;
 use wasmer_wasix_types::wasi::Whence;
 use wasmer_wasix_types::wasi::{Fdflags, Fdflagsext};
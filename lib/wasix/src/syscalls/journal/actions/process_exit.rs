@@ -13,6 +13,7 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
             self.differ_memory.clear();
             self.rewind = None;
         } else {
+            self.memory_pages.clear();
             JournalEffector::apply_process_exit(&mut self.ctx, exit_code)
                 .map_err(anyhow_err_to_runtime_err)?;
         }
@@ -29,4 +29,34 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
         }
         Ok(())
     }
+
+    #[allow(clippy::result_large_err)]
+    pub(crate) unsafe fn action_memory_page(
+        &mut self,
+        hash: [u8; 32],
+        compressed_data: Cow<'a, [u8]>,
+    ) -> Result<(), WasiRuntimeError> {
+        tracing::trace!("Replay journal - MemoryPage");
+        self.memory_pages
+            .insert_compressed(hash, compressed_data.as_ref())
+            .map_err(anyhow_err_to_runtime_err)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub(crate) unsafe fn action_update_memory_pages(
+        &mut self,
+        region: Range<u64>,
+        page_size: u64,
+        page_hashes: Cow<'a, [u8]>,
+        differ_ethereal: Option<&mut Vec<JournalEntry<'a>>>,
+    ) -> Result<(), WasiRuntimeError> {
+        // The pages are resolved immediately (rather than when the update is
+        // applied) as the page cache only holds the pages seen so far
+        let data = self
+            .memory_pages
+            .resolve(region.clone(), page_size, page_hashes.as_ref())
+            .map_err(anyhow_err_to_runtime_err)?;
+        let compressed_data = lz4_flex::compress_prepend_size(&data);
+        self.action_update_compressed_memory(region, compressed_data.into(), differ_ethereal)
+    }
 }
@@ -22,5 +22,9 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
 
         differ_ethereal.iter_mut().for_each(|e| e.clear());
         self.staged_differ_memory.clear();
+
+        // Pages written before this point are no longer referenced as the
+        // journal writes them again after the process restarts
+        self.memory_pages.clear();
     }
 }
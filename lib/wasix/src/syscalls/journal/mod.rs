@@ -49,6 +49,8 @@ pub struct JournalSyscallPlayer<'a, 'c> {
     pub spawn_threads: BTreeMap<WasiThreadId, RewindState>,
     pub staged_differ_memory: Vec<(Range<u64>, Cow<'a, [u8]>)>,
     pub differ_memory: Vec<(Range<u64>, Cow<'a, [u8]>)>,
+    // Distinct memory pages that deduplicated memory updates refer to
+    pub memory_pages: crate::journal::MemoryPageCache,
 
     // We capture the stdout and stderr while we replay
     pub stdout: Option<Vec<JournalStdIoWrite<'a>>>,
@@ -72,6 +74,7 @@ impl<'c> JournalSyscallPlayer<'_, 'c> {
             spawn_threads: Default::default(),
             staged_differ_memory: Default::default(),
             differ_memory: Default::default(),
+            memory_pages: Default::default(),
             // We capture stdout and stderr while we replay
             stdout_fds: [1 as WasiFd].into(),
             stderr_fds: [2 as WasiFd].into(),
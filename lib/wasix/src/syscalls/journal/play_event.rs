@@ -55,6 +55,19 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
             } => {
                 self.action_update_compressed_memory(region, compressed_data, differ_ethereal)?;
             }
+            JournalEntry::MemoryPageV1 {
+                hash,
+                compressed_data,
+            } => {
+                self.action_memory_page(hash, compressed_data)?;
+            }
+            JournalEntry::UpdateMemoryRegionPagesV1 {
+                region,
+                page_size,
+                page_hashes,
+            } => {
+                self.action_update_memory_pages(region, page_size, page_hashes, differ_ethereal)?;
+            }
             JournalEntry::CloseThreadV1 { id, exit_code } => {
                 self.action_close_thread(id, exit_code, differ_ethereal)?;
             }
@@ -282,6 +295,31 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
                     self.action_fd_set_fdflags(fd, flags)?;
                 }
             }
+            JournalEntry::FileDescriptorLockV1 { fd, op } => {
+                if self.real_fd.contains(&fd) {
+                    self.action_fd_lock(fd, op)?;
+                } else if let Some(differ_ethereal) = differ_ethereal {
+                    tracing::trace!(%fd, ?op, "Differ(ether) journal - FdLock");
+                    differ_ethereal.push(JournalEntry::FileDescriptorLockV1 { fd, op });
+                } else {
+                    self.action_fd_lock(fd, op)?;
+                }
+            }
+            JournalEntry::FileDescriptorLockRangeV1 { fd, op, start, len } => {
+                if self.real_fd.contains(&fd) {
+                    self.action_fd_lock_range(fd, op, start, len)?;
+                } else if let Some(differ_ethereal) = differ_ethereal {
+                    tracing::trace!(%fd, ?op, %start, %len, "Differ(ether) journal - FdLockRange");
+                    differ_ethereal.push(JournalEntry::FileDescriptorLockRangeV1 {
+                        fd,
+                        op,
+                        start,
+                        len,
+                    });
+                } else {
+                    self.action_fd_lock_range(fd, op, start, len)?;
+                }
+            }
             JournalEntry::FileDescriptorSetFlagsV1 { fd, flags } => {
                 if self.real_fd.contains(&fd) {
                     self.action_fd_set_flags(fd, flags)?;
@@ -396,6 +434,58 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
                         .map_err(anyhow_err_to_runtime_err)?;
                 }
             }
+            JournalEntry::WatchCreateV1 { fd, flags } => {
+                if let Some(differ_ethereal) = differ_ethereal {
+                    tracing::trace!(%fd, "Differ(ether) journal - WatchCreate");
+                    differ_ethereal.push(JournalEntry::WatchCreateV1 { fd, flags });
+                } else {
+                    tracing::trace!(%fd, "Replay journal - WatchCreate");
+                    JournalEffector::apply_watch_create(&mut self.ctx, fd, flags)
+                        .map_err(anyhow_err_to_runtime_err)?;
+                }
+            }
+            JournalEntry::WatchAddV1 {
+                fd,
+                dirfd,
+                flags,
+                path,
+                mask,
+                wd,
+            } => {
+                if let Some(differ_ethereal) = differ_ethereal {
+                    tracing::trace!(%fd, %path, %wd, "Differ(ether) journal - WatchAdd");
+                    differ_ethereal.push(JournalEntry::WatchAddV1 {
+                        fd,
+                        dirfd,
+                        flags,
+                        path,
+                        mask,
+                        wd,
+                    });
+                } else {
+                    tracing::trace!(%fd, %path, %wd, "Replay journal - WatchAdd");
+                    JournalEffector::apply_watch_add(
+                        &mut self.ctx,
+                        fd,
+                        dirfd,
+                        flags,
+                        &path,
+                        mask,
+                        wd,
+                    )
+                    .map_err(anyhow_err_to_runtime_err)?;
+                }
+            }
+            JournalEntry::WatchRemoveV1 { fd, wd } => {
+                if let Some(differ_ethereal) = differ_ethereal {
+                    tracing::trace!(%fd, %wd, "Differ(ether) journal - WatchRemove");
+                    differ_ethereal.push(JournalEntry::WatchRemoveV1 { fd, wd });
+                } else {
+                    tracing::trace!(%fd, %wd, "Replay journal - WatchRemove");
+                    JournalEffector::apply_watch_remove(&mut self.ctx, fd, wd)
+                        .map_err(anyhow_err_to_runtime_err)?;
+                }
+            }
             JournalEntry::EpollCtlV1 {
                 epfd,
                 op,
@@ -68,6 +68,7 @@ pub(crate) fn fd_allocate_internal(
             | Kind::DuplexPipe { .. }
             | Kind::Symlink { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => return Err(Errno::Badf),
             Kind::Dir { .. } | Kind::Root { .. } => return Err(Errno::Isdir),
         }
@@ -64,6 +64,7 @@ pub(crate) fn fd_filestat_set_size_internal(
             | Kind::DuplexPipe { .. }
             | Kind::Symlink { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => return Err(Errno::Badf),
             Kind::Dir { .. } | Kind::Root { .. } => return Err(Errno::Isdir),
         }
@@ -44,6 +44,7 @@ pub fn fd_prestat_dir_name<M: MemorySize>(
         | Kind::PipeTx { .. }
         | Kind::DuplexPipe { .. }
         | Kind::EventNotifications { .. }
+        | Kind::WatchNotifications { .. }
         | Kind::Epoll { .. } => Errno::Notdir,
     }
 }
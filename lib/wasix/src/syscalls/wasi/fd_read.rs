@@ -4,7 +4,7 @@ use virtual_fs::{AsyncReadExt, DeviceFile, ReadBuf};
 
 use super::*;
 use crate::{
-    fs::NotificationInner,
+    fs::{NotificationInner, WatchNotificationsInner},
     journal::SnapshotTrigger,
     net::socket::TimeType,
     os::task::process::{MaybeCheckpointResult, WasiProcessCheckpoint, WasiProcessInner},
@@ -435,6 +435,65 @@ pub(crate) fn fd_read_internal<M: MemorySize>(
                     let ret = wasi_try_ok_ok!(read_bytes(&reader[..], &memory, iovs_arr));
                     (ret, false)
                 }
+                Kind::WatchNotifications { inner } => {
+                    struct WatchPoller {
+                        inner: Arc<WatchNotificationsInner>,
+                        max_len: usize,
+                        non_blocking: bool,
+                    }
+
+                    // Events are never split so they are read in one go
+                    let max_len = {
+                        let memory = unsafe { env.memory_view(ctx) };
+                        let iovs_arr = wasi_try_mem_ok_ok!(iovs.slice(&memory, iovs_len));
+                        let iovs_arr = wasi_try_mem_ok_ok!(iovs_arr.access());
+                        iovs_arr
+                            .iter()
+                            .map(|iov| {
+                                let len: u64 = iov.buf_len.into();
+                                len as usize
+                            })
+                            .sum()
+                    };
+                    let poller = WatchPoller {
+                        inner: inner.clone(),
+                        max_len,
+                        non_blocking: fd_flags.contains(Fdflags::NONBLOCK),
+                    };
+
+                    drop(guard);
+
+                    impl Future for WatchPoller {
+                        type Output = Result<Vec<u8>, Errno>;
+                        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                            loop {
+                                if let Some(buf) = self.inner.try_read(self.max_len) {
+                                    return Poll::Ready(match buf.is_empty() {
+                                        true => Err(Errno::Inval),
+                                        false => Ok(buf),
+                                    });
+                                }
+                                if self.non_blocking {
+                                    return Poll::Ready(Err(Errno::Again));
+                                }
+                                if self.inner.poll(cx.waker()).is_pending() {
+                                    return Poll::Pending;
+                                }
+                            }
+                        }
+                    }
+
+                    let res = __asyncify_light(env, None, poller)?.map_err(|err| match err {
+                        Errno::Timedout => Errno::Again,
+                        a => a,
+                    });
+                    let reader = wasi_try_ok_ok!(res);
+
+                    let memory = unsafe { env.memory_view(ctx) };
+                    let iovs_arr = wasi_try_mem_ok_ok!(iovs.slice(&memory, iovs_len));
+                    let ret = wasi_try_ok_ok!(read_bytes(&reader[..], &memory, iovs_arr));
+                    (ret, false)
+                }
                 Kind::Symlink { .. } | Kind::Epoll { .. } => {
                     return Ok(Err(Errno::Notsup));
                 }
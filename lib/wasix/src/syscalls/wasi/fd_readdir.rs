@@ -110,6 +110,7 @@ pub fn fd_readdir<M: MemorySize>(
             | Kind::PipeTx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => return Ok(Errno::Notdir),
         }
     };
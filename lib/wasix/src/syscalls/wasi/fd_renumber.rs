@@ -71,6 +71,7 @@ pub(crate) fn fd_renumber_internal(
         // TODO: verify this is correct
         inner: FdInner {
             offset: fd_entry.inner.offset.clone(),
+            locks: fd_entry.inner.locks.clone(),
             rights: fd_entry.inner.rights_inheriting,
             fd_flags: {
                 let mut f = fd_entry.inner.fd_flags;
@@ -125,6 +125,7 @@ pub(crate) fn fd_seek_internal(
                 | Kind::PipeTx { .. }
                 | Kind::DuplexPipe { .. }
                 | Kind::EventNotifications { .. }
+                | Kind::WatchNotifications { .. }
                 | Kind::Epoll { .. } => {
                     // TODO: check this
                     return Ok(Err(Errno::Inval));
@@ -450,7 +450,9 @@ pub(crate) fn fd_write_internal<M: MemorySize>(
 
                     (written, false, true)
                 }
-                Kind::Symlink { .. } | Kind::Epoll { .. } => return Ok(Err(Errno::Inval)),
+                Kind::Symlink { .. } | Kind::Epoll { .. } | Kind::WatchNotifications { .. } => {
+                    return Ok(Err(Errno::Inval))
+                }
                 Kind::Buffer { buffer } => {
                     let mut written = 0usize;
 
@@ -70,6 +70,10 @@ pub(crate) fn path_create_directory_internal(
         state
             .fs
             .get_parent_inode_at_path(inodes, fd, Path::new(path), true)?;
+    state.fs.check_inode_access(
+        &parent_inode,
+        virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE,
+    )?;
 
     let mut guard = parent_inode.write();
     match guard.deref_mut() {
@@ -103,6 +107,7 @@ pub(crate) fn path_create_directory_internal(
             }
 
             state.fs_create_dir(&new_dir_path)?;
+            state.fs.assign_ownership(&new_dir_path);
 
             let kind = Kind::Dir {
                 parent: parent_inode.downgrade(),
@@ -98,6 +98,9 @@ pub(crate) fn path_filestat_set_times_internal(
         state
             .fs
             .get_inode_at_path(inodes, fd, path, flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0)?;
+    state
+        .fs
+        .check_inode_access(&file_inode, virtual_fs::Permissions::WRITE)?;
     let stat = {
         let guard = file_inode.read();
         state.fs.get_stat_for_kind(guard.deref())?
@@ -103,10 +103,54 @@ pub(crate) fn path_link_internal(
         state
             .fs
             .get_parent_inode_at_path(inodes, new_fd, &target_path_arg, false)?;
+    state.fs.check_inode_access(
+        &target_parent_inode,
+        virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE,
+    )?;
 
     if source_inode.stat.write().unwrap().st_nlink == Linkcount::MAX {
         return Err(Errno::Mlink);
     }
+
+    // When the backing file system supports hard links then the link is
+    // created there and gets its own inode, this way unlinking either name
+    // removes just that name from the backing file system
+    let source_path = match source_inode.read().deref() {
+        Kind::File { path, .. } => Some(path.clone()),
+        _ => None,
+    };
+    if let Some(source_path) = source_path {
+        let link_path = match target_parent_inode.read().deref() {
+            Kind::Dir { entries, path, .. } => {
+                if entries.contains_key(&new_entry_name) {
+                    return Err(Errno::Exist);
+                }
+                path.join(&new_entry_name)
+            }
+            _ => return Err(Errno::Notdir),
+        };
+
+        match state.fs_hard_link(&source_path, &link_path) {
+            Ok(()) => {
+                let kind = Kind::File {
+                    handle: None,
+                    path: link_path,
+                    fd: None,
+                };
+                let new_inode =
+                    state
+                        .fs
+                        .create_inode(inodes, kind, false, new_entry_name.clone())?;
+                if let Kind::Dir { entries, .. } = target_parent_inode.write().deref_mut() {
+                    entries.insert(new_entry_name, new_inode);
+                }
+                return Ok(());
+            }
+            Err(FsError::Unsupported) => {}
+            Err(err) => return Err(fs_error_into_wasi_err(err)),
+        }
+    }
+
     {
         let mut guard = target_parent_inode.write();
         match guard.deref_mut() {
@@ -125,6 +169,7 @@ pub(crate) fn path_link_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => return Err(Errno::Notdir),
         }
     }
@@ -50,6 +50,10 @@ pub(crate) fn path_remove_directory_internal(
         state
             .fs
             .get_parent_inode_at_path(inodes, fd, Path::new(path), true)?;
+    state.fs.check_inode_access(
+        &parent_inode,
+        virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE,
+    )?;
 
     let mut guard = parent_inode.write();
     match guard.deref_mut() {
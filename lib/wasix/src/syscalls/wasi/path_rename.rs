@@ -96,6 +96,12 @@ pub fn path_rename_internal(
         Path::new(target_path),
         true
     ));
+    for parent_inode in [&source_parent_inode, &target_parent_inode] {
+        wasi_try_ok!(state.fs.check_inode_access(
+            parent_inode,
+            virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE
+        ));
+    }
     let mut need_create = true;
     let host_adjusted_target_path = {
         let guard = target_parent_inode.read();
@@ -112,6 +118,7 @@ pub fn path_rename_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => return Ok(Errno::Inval),
             Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } => {
                 debug!("fatal internal logic error: parent of inode is not a directory");
@@ -132,6 +139,7 @@ pub fn path_rename_internal(
             | Kind::PipeTx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => {
                 return Ok(Errno::Inval);
             }
@@ -200,7 +208,8 @@ pub fn path_rename_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::Epoll { .. }
-            | Kind::EventNotifications { .. } => {}
+            | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. } => {}
             Kind::Root { .. } => unreachable!("The root can not be moved"),
         }
     }
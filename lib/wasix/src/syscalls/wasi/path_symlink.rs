@@ -85,15 +85,20 @@ pub fn path_symlink_internal(
         state
             .fs
             .get_parent_inode_at_path(inodes, fd, new_path_path, true)?;
+    state.fs.check_inode_access(
+        &target_parent_inode,
+        virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE,
+    )?;
 
     // short circuit if anything is wrong, before we create an inode
-    {
+    let link_path = {
         let guard = target_parent_inode.read();
         match guard.deref() {
-            Kind::Dir { entries, .. } => {
+            Kind::Dir { entries, path, .. } => {
                 if entries.contains_key(&entry_name) {
                     return Err(Errno::Exist);
                 }
+                path.join(&entry_name)
             }
             Kind::Root { .. } => return Err(Errno::Notcapable),
             Kind::Socket { .. }
@@ -101,11 +106,19 @@ pub fn path_symlink_internal(
             | Kind::PipeTx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => return Err(Errno::Inval),
             Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => {
                 unreachable!("get_parent_inode_at_path returned something other than a Dir or Root")
             }
         }
+    };
+
+    // Create the link in the backing file system so that it persists, file
+    // systems that do not support links only get the virtual link below
+    match state.fs_symlink(old_path, &link_path) {
+        Ok(()) | Err(FsError::Unsupported) => {}
+        Err(err) => return Err(fs_error_into_wasi_err(err)),
     }
 
     let mut source_path = std::path::Path::new(old_path);
@@ -62,18 +62,21 @@ pub(crate) fn path_unlink_file_internal(
         std::path::Path::new(path),
         false
     ));
+    wasi_try_ok!(state.fs.check_inode_access(
+        &parent_inode,
+        virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE
+    ));
 
-    let removed_inode = {
-        let mut guard = parent_inode.write();
-        match guard.deref_mut() {
-            Kind::Dir {
-                ref mut entries, ..
-            } => {
-                let removed_inode = wasi_try_ok!(entries.remove(&childs_name).ok_or(Errno::Inval));
+    let (removed_inode, removed_path) = {
+        let guard = parent_inode.read();
+        match guard.deref() {
+            Kind::Dir { entries, path, .. } => {
+                let removed_inode =
+                    wasi_try_ok!(entries.get(&childs_name).cloned().ok_or(Errno::Inval));
                 // TODO: make this a debug assert in the future
                 assert!(inode.ino() == removed_inode.ino());
                 debug_assert!(inode.stat.read().unwrap().st_nlink > 0);
-                removed_inode
+                (removed_inode, path.join(&childs_name))
             }
             Kind::Root { .. } => return Ok(Errno::Access),
             _ => unreachable!(
@@ -82,36 +85,51 @@ pub(crate) fn path_unlink_file_internal(
         }
     };
 
-    let st_nlink = {
-        let mut guard = removed_inode.stat.write().unwrap();
-        guard.st_nlink -= 1;
-        guard.st_nlink
-    };
-    if st_nlink == 0 {
-        {
-            let mut guard = removed_inode.read();
-            match guard.deref() {
-                Kind::File { handle, path, .. } => {
-                    if let Some(h) = handle {
-                        let mut h = h.write().unwrap();
-                        wasi_try_ok!(h.unlink().map_err(fs_error_into_wasi_err));
-                    } else {
-                        // File is closed
-                        // problem with the abstraction, we can't call unlink because there's no handle
-                        // drop mutable borrow on `path`
-                        let path = path.clone();
-                        drop(guard);
-                        wasi_try_ok!(state.fs_remove_file(path));
-                    }
+    // The backing file is removed with the last link to it, the entry
+    // is only removed from the inode tree once that has succeeded
+    let st_nlink = removed_inode.stat.read().unwrap().st_nlink;
+    {
+        let guard = removed_inode.read();
+        match guard.deref() {
+            Kind::Dir { .. } | Kind::Root { .. } => return Ok(Errno::Isdir),
+            Kind::File { handle, path, .. } if st_nlink <= 1 => {
+                if let Some(h) = handle {
+                    let mut h = h.write().unwrap();
+                    wasi_try_ok!(h.unlink().map_err(fs_error_into_wasi_err));
+                } else {
+                    // File is closed
+                    // problem with the abstraction, we can't call unlink because there's no handle
+                    // drop mutable borrow on `path`
+                    let path = path.clone();
+                    drop(guard);
+                    wasi_try_ok!(state.fs_remove_file(path));
                 }
-                Kind::Dir { .. } | Kind::Root { .. } => return Ok(Errno::Isdir),
-                Kind::Symlink { .. } => {
-                    // TODO: actually delete real symlinks and do nothing for virtual symlinks
+            }
+            Kind::Symlink { .. } if st_nlink <= 1 => {
+                // Symlinks that only exist virtually are not found in the
+                // backing file system, which is fine
+                drop(guard);
+                match state.fs_remove_file(&removed_path) {
+                    Ok(()) | Err(Errno::Noent) | Err(Errno::Notsup) => {}
+                    Err(err) => return Ok(err),
                 }
-                _ => unimplemented!("wasi::path_unlink_file for Buffer"),
+            }
+            // The names unix domain sockets are bound to only exist
+            // virtually, the bound socket itself stays open
+            Kind::File { .. } | Kind::Symlink { .. } | Kind::Socket { .. } => {}
+            _ => unimplemented!("wasi::path_unlink_file for Buffer"),
+        }
+    }
+
+    {
+        let mut guard = parent_inode.write();
+        if let Kind::Dir { entries, .. } = guard.deref_mut() {
+            if entries.get(&childs_name).map(|i| i.ino()) == Some(removed_inode.ino()) {
+                entries.remove(&childs_name);
             }
         }
     }
+    removed_inode.stat.write().unwrap().st_nlink -= 1;
 
     Ok(Errno::Success)
 }
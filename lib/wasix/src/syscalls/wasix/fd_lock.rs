@@ -0,0 +1,109 @@
+use virtual_fs::{FileLock, LockRange};
+
+use super::*;
+use crate::{
+    fs::{FdLocks, SharedFile},
+    syscalls::*,
+    types::wasi::Flockop,
+};
+
+/// ### `fd_lock()`
+/// Places or removes an advisory lock on an open file (see `flock(2)`),
+/// the lock is shared with all the processes that hold a duplicate of
+/// the file descriptor and is released when the last of them is closed
+/// Inputs:
+/// - `Fd fd`
+///     The file descriptor of the file to lock
+/// - `Flockop op`
+///     Whether to place a shared lock, an exclusive lock or to unlock
+/// - `Bool nonblocking`
+///     When set the call fails with `Errno::Again` instead of waiting for
+///     a conflicting lock to be released
+#[instrument(level = "trace", skip_all, fields(%fd, ?op, ?nonblocking), ret)]
+pub fn fd_lock(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    op: Flockop,
+    nonblocking: Bool,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    wasi_try_ok!(fd_lock_internal(
+        &mut ctx,
+        fd,
+        op,
+        LockRange::File,
+        nonblocking == Bool::True
+    )?);
+
+    #[cfg(feature = "journal")]
+    if ctx.data().enable_journal {
+        JournalEffector::save_fd_lock(&mut ctx, fd, op).map_err(|err| {
+            tracing::error!("failed to save file lock event - {}", err);
+            WasiError::Exit(ExitCode::from(Errno::Fault))
+        })?;
+    }
+
+    Ok(Errno::Success)
+}
+
+/// Places (or removes) a lock through the open file description of `fd`,
+/// waiting for conflicting locks to be released unless `nonblocking` is set
+pub(crate) fn fd_lock_internal(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    op: Flockop,
+    range: LockRange,
+    nonblocking: bool,
+) -> Result<Result<(), Errno>, WasiError> {
+    let lock = match op {
+        Flockop::Shared => FileLock::Shared,
+        Flockop::Exclusive => FileLock::Exclusive,
+        Flockop::Unlock => FileLock::Unlock,
+        Flockop::Unknown => return Ok(Err(Errno::Inval)),
+    };
+
+    let env = ctx.data();
+    let (_, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let (handle, locks) = wasi_try_ok_ok!(fd_lock_file(state, fd));
+    locks.placed_on(&handle);
+    let owner = locks.owner();
+
+    // Unlocking never has to wait and neither do callers that asked not to
+    let res = handle
+        .write()
+        .unwrap()
+        .try_lock(owner, lock, range)
+        .map_err(fs_error_into_wasi_err);
+    match res {
+        Err(Errno::Again) if !nonblocking && lock != FileLock::Unlock => {}
+        res => return Ok(res),
+    }
+
+    // Otherwise we wait for the holder of the conflicting lock (which may
+    // be another process) to release it
+    __asyncify(ctx, None, async move {
+        futures::future::poll_fn(|cx| handle.write().unwrap().poll_lock(cx, owner, lock, range))
+            .await
+            .map_err(fs_error_into_wasi_err)
+    })
+}
+
+/// Returns the file that `fd` refers to along with the locks of its open
+/// file description
+pub(crate) fn fd_lock_file(
+    state: &WasiState,
+    fd: WasiFd,
+) -> Result<(SharedFile, Arc<FdLocks>), Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    let guard = fd_entry.inode.read();
+    match guard.deref() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => Ok((handle.clone(), fd_entry.inner.locks.clone())),
+        Kind::File { handle: None, .. } => Err(Errno::Badf),
+        Kind::Dir { .. } | Kind::Root { .. } => Err(Errno::Isdir),
+        _ => Err(Errno::Notsup),
+    }
+}
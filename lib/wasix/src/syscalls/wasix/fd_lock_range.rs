@@ -0,0 +1,52 @@
+use virtual_fs::LockRange;
+
+use super::*;
+use crate::{syscalls::*, types::wasi::Flockop};
+
+/// ### `fd_lock_range()`
+/// Places or removes an advisory lock on a range of bytes of an open file
+/// (see `F_OFD_SETLK` in `fcntl(2)`), the lock is shared with all the
+/// processes that hold a duplicate of the file descriptor and is released
+/// when the last of them is closed. These locks are independent of the
+/// ones placed by `fd_lock`.
+/// Inputs:
+/// - `Fd fd`
+///     The file descriptor of the file to lock
+/// - `Flockop op`
+///     Whether to place a shared lock, an exclusive lock or to unlock
+/// - `Filesize start`
+///     The offset of the first byte of the range
+/// - `Filesize len`
+///     The length of the range, zero extends it to the end of the file
+/// - `Bool nonblocking`
+///     When set the call fails with `Errno::Again` instead of waiting for
+///     a conflicting lock to be released
+#[instrument(level = "trace", skip_all, fields(%fd, ?op, %start, %len, ?nonblocking), ret)]
+pub fn fd_lock_range(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    op: Flockop,
+    start: Filesize,
+    len: Filesize,
+    nonblocking: Bool,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    wasi_try_ok!(fd_lock_internal(
+        &mut ctx,
+        fd,
+        op,
+        LockRange::Bytes { start, len },
+        nonblocking == Bool::True
+    )?);
+
+    #[cfg(feature = "journal")]
+    if ctx.data().enable_journal {
+        JournalEffector::save_fd_lock_range(&mut ctx, fd, op, start, len).map_err(|err| {
+            tracing::error!("failed to save file range lock event - {}", err);
+            WasiError::Exit(ExitCode::from(Errno::Fault))
+        })?;
+    }
+
+    Ok(Errno::Success)
+}
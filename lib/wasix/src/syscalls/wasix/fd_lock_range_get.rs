@@ -0,0 +1,66 @@
+use virtual_fs::{FileLock, LockRange};
+
+use super::*;
+use crate::{syscalls::*, types::wasi::Flockop};
+
+/// ### `fd_lock_range_get()`
+/// Returns a lock held through another open file description that would
+/// prevent a lock from being placed on a range of bytes of an open file
+/// (see `F_OFD_GETLK` in `fcntl(2)`)
+/// Inputs:
+/// - `Fd fd`
+///     The file descriptor of the file
+/// - `Flockop op`
+///     The kind of lock that would be placed
+/// - `Filesize start`
+///     The offset of the first byte of the range
+/// - `Filesize len`
+///     The length of the range, zero extends it to the end of the file
+/// Output:
+/// - `Flockop *ret_op`
+///     The kind of the conflicting lock, `Unlock` when there is none
+/// - `Filesize *ret_start`
+///     The offset of the first byte of the conflicting lock
+/// - `Filesize *ret_len`
+///     The length of the conflicting lock, zero when it extends to the end
+///     of the file
+#[instrument(level = "trace", skip_all, fields(%fd, ?op, %start, %len), ret)]
+#[allow(clippy::too_many_arguments)]
+pub fn fd_lock_range_get<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    op: Flockop,
+    start: Filesize,
+    len: Filesize,
+    ret_op: WasmPtr<Flockop, M>,
+    ret_start: WasmPtr<Filesize, M>,
+    ret_len: WasmPtr<Filesize, M>,
+) -> Errno {
+    let lock = match op {
+        Flockop::Shared => FileLock::Shared,
+        Flockop::Exclusive => FileLock::Exclusive,
+        Flockop::Unlock | Flockop::Unknown => return Errno::Inval,
+    };
+
+    let env = ctx.data();
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let (handle, locks) = wasi_try!(fd_lock_file(state, fd));
+
+    let conflict = wasi_try!(handle
+        .write()
+        .unwrap()
+        .test_lock(locks.owner(), lock, LockRange::Bytes { start, len })
+        .map_err(fs_error_into_wasi_err));
+    let (op, start, len) = match conflict {
+        Some((FileLock::Shared, LockRange::Bytes { start, len })) => (Flockop::Shared, start, len),
+        Some((_, LockRange::Bytes { start, len })) => (Flockop::Exclusive, start, len),
+        Some((_, LockRange::File)) => return Errno::Io,
+        None => (Flockop::Unlock, start, len),
+    };
+
+    wasi_try_mem!(ret_op.write(&memory, op));
+    wasi_try_mem!(ret_start.write(&memory, start));
+    wasi_try_mem!(ret_len.write(&memory, len));
+
+    Errno::Success
+}
@@ -13,12 +13,22 @@ mod epoll_wait;
 mod fd_dup2;
 mod fd_fdflags_get;
 mod fd_fdflags_set;
+mod fd_lock;
+mod fd_lock_range;
+mod fd_lock_range_get;
 mod fd_pipe;
 mod futex_wait;
 mod futex_wake;
 mod futex_wake_all;
 mod getcwd;
+mod path_chmod;
+mod path_chown;
 mod path_open2;
+mod path_permissions_get;
+mod path_xattr_get;
+mod path_xattr_list;
+mod path_xattr_remove;
+mod path_xattr_set;
 mod port_addr_add;
 mod port_addr_clear;
 mod port_addr_list;
@@ -49,9 +59,13 @@ mod resolve;
 mod sched_yield;
 mod sock_accept;
 mod sock_addr_local;
+mod sock_addr_local_unix;
 mod sock_addr_peer;
+mod sock_addr_peer_unix;
 mod sock_bind;
+mod sock_bind_unix;
 mod sock_connect;
+mod sock_connect_unix;
 mod sock_get_opt_flag;
 mod sock_get_opt_size;
 mod sock_get_opt_time;
@@ -72,6 +86,7 @@ mod sock_set_opt_size;
 mod sock_set_opt_time;
 mod sock_shutdown;
 mod sock_status;
+mod sock_upgrade_tls;
 mod stack_checkpoint;
 mod stack_restore;
 mod thread_exit;
@@ -83,6 +98,9 @@ mod thread_sleep;
 mod thread_spawn;
 mod tty_get;
 mod tty_set;
+mod watch_add;
+mod watch_create;
+mod watch_remove;
 
 pub use call_dynamic::*;
 pub use callback_signal::*;
@@ -99,12 +117,22 @@ pub use epoll_wait::*;
 pub use fd_dup2::*;
 pub use fd_fdflags_get::*;
 pub use fd_fdflags_set::*;
+pub use fd_lock::*;
+pub use fd_lock_range::*;
+pub use fd_lock_range_get::*;
 pub use fd_pipe::*;
 pub use futex_wait::*;
 pub use futex_wake::*;
 pub use futex_wake_all::*;
 pub use getcwd::*;
+pub use path_chmod::*;
+pub use path_chown::*;
 pub use path_open2::*;
+pub use path_permissions_get::*;
+pub use path_xattr_get::*;
+pub use path_xattr_list::*;
+pub use path_xattr_remove::*;
+pub use path_xattr_set::*;
 pub use port_addr_add::*;
 pub use port_addr_clear::*;
 pub use port_addr_list::*;
@@ -135,9 +163,13 @@ pub use resolve::*;
 pub use sched_yield::*;
 pub use sock_accept::*;
 pub use sock_addr_local::*;
+pub use sock_addr_local_unix::*;
 pub use sock_addr_peer::*;
+pub use sock_addr_peer_unix::*;
 pub use sock_bind::*;
+pub use sock_bind_unix::*;
 pub use sock_connect::*;
+pub use sock_connect_unix::*;
 pub use sock_get_opt_flag::*;
 pub use sock_get_opt_size::*;
 pub use sock_get_opt_time::*;
@@ -158,6 +190,7 @@ pub use sock_set_opt_size::*;
 pub use sock_set_opt_time::*;
 pub use sock_shutdown::*;
 pub use sock_status::*;
+pub use sock_upgrade_tls::*;
 pub use stack_checkpoint::*;
 pub use stack_restore::*;
 pub use thread_exit::*;
@@ -169,5 +202,8 @@ pub use thread_sleep::*;
 pub use thread_spawn::*;
 pub use tty_get::*;
 pub use tty_set::*;
+pub use watch_add::*;
+pub use watch_create::*;
+pub use watch_remove::*;
 
 use tracing::{debug_span, field, instrument, trace_span, Span};
@@ -0,0 +1,78 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_chmod()`
+/// Changes the permission bits of a file or directory, only the owner of
+/// the entry (or the superuser) is allowed to do so
+/// Inputs:
+/// - `Fd fd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// - `u32 mode`
+///     The new permission bits (including the setuid, setgid and sticky bits)
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty, mode = format!("{mode:o}")), ret)]
+pub fn path_chmod<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    mode: u32,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+
+    wasi_try_ok!(path_chmod_internal(
+        state,
+        inodes,
+        fd,
+        flags,
+        &path_string,
+        mode
+    ));
+
+    Ok(Errno::Success)
+}
+
+pub(crate) fn path_chmod_internal(
+    state: &WasiState,
+    inodes: &crate::WasiInodes,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: &str,
+    mode: u32,
+) -> Result<(), Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !fd_entry
+        .inner
+        .rights
+        .contains(Rights::PATH_FILESTAT_SET_TIMES)
+    {
+        return Err(Errno::Access);
+    }
+
+    let inode =
+        state
+            .fs
+            .get_inode_at_path(inodes, fd, path, flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0)?;
+    let path = crate::fs::WasiFs::path_of_inode(&inode).ok_or(Errno::Notsup)?;
+    let permissions = state.fs.get_permissions(&path)?;
+
+    if let Some(identity) = state.fs.get_identity() {
+        if !identity.is_root() && identity.uid != permissions.uid {
+            return Err(Errno::Perm);
+        }
+    }
+
+    state.fs_set_permissions(&path, permissions.with_mode(mode))
+}
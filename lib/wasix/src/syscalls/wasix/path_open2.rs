@@ -249,6 +249,16 @@ pub(crate) fn path_open_internal(
                 if minimum_rights.truncate {
                     open_flags |= Fd::TRUNCATE;
                 }
+
+                let mut access = 0;
+                if minimum_rights.read {
+                    access |= virtual_fs::Permissions::READ;
+                }
+                if minimum_rights.write || minimum_rights.truncate {
+                    access |= virtual_fs::Permissions::WRITE;
+                }
+                wasi_try_ok_ok!(state.fs.check_access(path, access));
+
                 // TODO: I strongly suspect that assigning the handle unconditionally
                 // breaks opening the same file multiple times.
                 *handle = Some(Arc::new(std::sync::RwLock::new(wasi_try_ok_ok!(
@@ -287,6 +297,7 @@ pub(crate) fn path_open_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::WatchNotifications { .. }
             | Kind::Epoll { .. } => {}
             Kind::Symlink {
                 base_po_dir,
@@ -336,6 +347,12 @@ pub(crate) fn path_open_internal(
                     _ => return Ok(Err(Errno::Inval)),
                 }
             };
+            // creating an entry requires the permission to modify the parent
+            wasi_try_ok_ok!(state.fs.check_inode_access(
+                &parent_inode,
+                virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE
+            ));
+
             // once we got the data we need from the parent, we lookup the host file
             // todo: extra check that opening with write access is okay
             let handle = {
@@ -375,6 +392,8 @@ pub(crate) fn path_open_internal(
                 }
             };
 
+            state.fs.assign_ownership(&new_file_host_path);
+
             let new_inode = {
                 let kind = Kind::File {
                     handle: handle.map(|a| Arc::new(std::sync::RwLock::new(a))),
@@ -0,0 +1,59 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_permissions_get()`
+/// Reads the permission bits and the ownership of a file or directory
+/// Inputs:
+/// - `Fd fd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// Output:
+/// - `u32 *ret_mode`
+///     The permission bits of the entry
+/// - `u32 *ret_uid`
+///     The user that owns the entry
+/// - `u32 *ret_gid`
+///     The group that owns the entry
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty), ret)]
+pub fn path_permissions_get<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    ret_mode: WasmPtr<u32, M>,
+    ret_uid: WasmPtr<u32, M>,
+    ret_gid: WasmPtr<u32, M>,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+
+    let root_dir = wasi_try_ok!(state.fs.get_fd(fd));
+    if !root_dir.inner.rights.contains(Rights::PATH_FILESTAT_GET) {
+        return Ok(Errno::Access);
+    }
+    let inode = wasi_try_ok!(state.fs.get_inode_at_path(
+        inodes,
+        fd,
+        &path_string,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    ));
+    let path = wasi_try_ok!(crate::fs::WasiFs::path_of_inode(&inode).ok_or(Errno::Notsup));
+    let permissions = wasi_try_ok!(state.fs.get_permissions(&path));
+
+    wasi_try_mem_ok!(ret_mode.write(&memory, permissions.mode));
+    wasi_try_mem_ok!(ret_uid.write(&memory, permissions.uid));
+    wasi_try_mem_ok!(ret_gid.write(&memory, permissions.gid));
+
+    Ok(Errno::Success)
+}
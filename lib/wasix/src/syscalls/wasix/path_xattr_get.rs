@@ -0,0 +1,83 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_xattr_get()`
+/// Reads the value of an extended attribute of a file or directory
+/// Inputs:
+/// - `Fd fd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// - `const char *name`
+///     String containing the name of the attribute
+/// - `u32 name_len`
+///     The length of the `name` string
+/// - `u32 buf_len`
+///     Space available pointed to by `buf`, when zero only the size of
+///     the value is returned
+/// Output:
+/// - `u8 *buf`
+///     Buffer that receives the value of the attribute
+/// - `u32 *ret_len`
+///     The size of the value, `Errno::Range` is returned when it does not
+///     fit in `buf` and `Errno::Noent` when the attribute does not exist
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty, name = field::Empty), ret)]
+pub fn path_xattr_get<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    name: WasmPtr<u8, M>,
+    name_len: M::Offset,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+    let name_string = unsafe { get_input_str_ok!(&memory, name, name_len) };
+    Span::current().record("name", name_string.as_str());
+
+    let root_dir = wasi_try_ok!(state.fs.get_fd(fd));
+    if !root_dir.inner.rights.contains(Rights::PATH_FILESTAT_GET) {
+        return Ok(Errno::Access);
+    }
+    let inode = wasi_try_ok!(state.fs.get_inode_at_path(
+        inodes,
+        fd,
+        &path_string,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    ));
+    let path = wasi_try_ok!(crate::fs::WasiFs::path_of_inode(&inode).ok_or(Errno::Notsup));
+    wasi_try_ok!(state.fs.check_access(&path, virtual_fs::Permissions::READ));
+
+    let value = wasi_try_ok!(state
+        .fs
+        .root_fs
+        .get_xattr(&path, &name_string)
+        .map_err(fs_error_into_wasi_err));
+
+    wasi_try_mem_ok!(ret_len.write(&memory, wasi_try_ok!(to_offset::<M>(value.len()))));
+    let buf_len: u64 = buf_len.into();
+    if buf_len == 0 {
+        return Ok(Errno::Success);
+    }
+    if value.len() as u64 > buf_len {
+        return Ok(Errno::Range);
+    }
+
+    let out = wasi_try_mem_ok!(buf.slice(&memory, wasi_try_ok!(to_offset::<M>(value.len()))));
+    wasi_try_mem_ok!(out.write_slice(&value));
+
+    Ok(Errno::Success)
+}
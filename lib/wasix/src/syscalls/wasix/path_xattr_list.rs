@@ -0,0 +1,80 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_xattr_list()`
+/// Lists the names of the extended attributes of a file or directory
+/// Inputs:
+/// - `Fd fd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// - `u32 buf_len`
+///     Space available pointed to by `buf`, when zero only the size of
+///     the list is returned
+/// Output:
+/// - `char *buf`
+///     Buffer that receives the names, each of them is terminated by a
+///     null byte
+/// - `u32 *ret_len`
+///     The size of the list, `Errno::Range` is returned when it does not
+///     fit in `buf`
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty), ret)]
+pub fn path_xattr_list<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+
+    let root_dir = wasi_try_ok!(state.fs.get_fd(fd));
+    if !root_dir.inner.rights.contains(Rights::PATH_FILESTAT_GET) {
+        return Ok(Errno::Access);
+    }
+    let inode = wasi_try_ok!(state.fs.get_inode_at_path(
+        inodes,
+        fd,
+        &path_string,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    ));
+    let path = wasi_try_ok!(crate::fs::WasiFs::path_of_inode(&inode).ok_or(Errno::Notsup));
+    wasi_try_ok!(state.fs.check_access(&path, virtual_fs::Permissions::READ));
+
+    let names = wasi_try_ok!(state
+        .fs
+        .root_fs
+        .list_xattr(&path)
+        .map_err(fs_error_into_wasi_err));
+    let list = names
+        .into_iter()
+        .flat_map(|name| name.into_bytes().into_iter().chain(std::iter::once(0)))
+        .collect::<Vec<_>>();
+
+    wasi_try_mem_ok!(ret_len.write(&memory, wasi_try_ok!(to_offset::<M>(list.len()))));
+    let buf_len: u64 = buf_len.into();
+    if buf_len == 0 {
+        return Ok(Errno::Success);
+    }
+    if list.len() as u64 > buf_len {
+        return Ok(Errno::Range);
+    }
+
+    let out = wasi_try_mem_ok!(buf.slice(&memory, wasi_try_ok!(to_offset::<M>(list.len()))));
+    wasi_try_mem_ok!(out.write_slice(&list));
+
+    Ok(Errno::Success)
+}
@@ -0,0 +1,82 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_xattr_remove()`
+/// Removes an extended attribute from a file or directory
+/// Inputs:
+/// - `Fd fd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// - `const char *name`
+///     String containing the name of the attribute
+/// - `u32 name_len`
+///     The length of the `name` string
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty, name = field::Empty), ret)]
+pub fn path_xattr_remove<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    name: WasmPtr<u8, M>,
+    name_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+    let name_string = unsafe { get_input_str_ok!(&memory, name, name_len) };
+    Span::current().record("name", name_string.as_str());
+
+    wasi_try_ok!(path_xattr_remove_internal(
+        state,
+        inodes,
+        fd,
+        flags,
+        &path_string,
+        &name_string
+    ));
+
+    Ok(Errno::Success)
+}
+
+pub(crate) fn path_xattr_remove_internal(
+    state: &WasiState,
+    inodes: &crate::WasiInodes,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: &str,
+    name: &str,
+) -> Result<(), Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !fd_entry
+        .inner
+        .rights
+        .contains(Rights::PATH_FILESTAT_SET_TIMES)
+    {
+        return Err(Errno::Access);
+    }
+
+    let inode =
+        state
+            .fs
+            .get_inode_at_path(inodes, fd, path, flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0)?;
+    let path = crate::fs::WasiFs::path_of_inode(&inode).ok_or(Errno::Notsup)?;
+    state
+        .fs
+        .check_access(&path, virtual_fs::Permissions::WRITE)?;
+
+    state
+        .fs
+        .root_fs
+        .remove_xattr(&path, name)
+        .map_err(fs_error_into_wasi_err)
+}
@@ -0,0 +1,93 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_xattr_set()`
+/// Creates or replaces an extended attribute of a file or directory
+/// Inputs:
+/// - `Fd fd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// - `const char *name`
+///     String containing the name of the attribute
+/// - `u32 name_len`
+///     The length of the `name` string
+/// - `const u8 *value`
+///     The new value of the attribute
+/// - `u32 value_len`
+///     The length of `value`
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty, name = field::Empty, value_len = field::Empty), ret)]
+pub fn path_xattr_set<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    name: WasmPtr<u8, M>,
+    name_len: M::Offset,
+    value: WasmPtr<u8, M>,
+    value_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+    let name_string = unsafe { get_input_str_ok!(&memory, name, name_len) };
+    Span::current().record("name", name_string.as_str());
+    let value = wasi_try_mem_ok!(value.slice(&memory, value_len));
+    let value = wasi_try_mem_ok!(value.read_to_vec());
+    Span::current().record("value_len", value.len());
+
+    wasi_try_ok!(path_xattr_set_internal(
+        state,
+        inodes,
+        fd,
+        flags,
+        &path_string,
+        &name_string,
+        &value
+    ));
+
+    Ok(Errno::Success)
+}
+
+pub(crate) fn path_xattr_set_internal(
+    state: &WasiState,
+    inodes: &crate::WasiInodes,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: &str,
+    name: &str,
+    value: &[u8],
+) -> Result<(), Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !fd_entry
+        .inner
+        .rights
+        .contains(Rights::PATH_FILESTAT_SET_TIMES)
+    {
+        return Err(Errno::Access);
+    }
+
+    let inode =
+        state
+            .fs
+            .get_inode_at_path(inodes, fd, path, flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0)?;
+    let path = crate::fs::WasiFs::path_of_inode(&inode).ok_or(Errno::Notsup)?;
+    state
+        .fs
+        .check_access(&path, virtual_fs::Permissions::WRITE)?;
+
+    state
+        .fs
+        .root_fs
+        .set_xattr(&path, name, value)
+        .map_err(fs_error_into_wasi_err)
+}
@@ -223,6 +223,7 @@ fn apply_fd_op<M: MemorySize>(
                 // TODO: verify this is correct
                 inner: FdInner {
                     offset: fd_entry.inner.offset.clone(),
+                    locks: fd_entry.inner.locks.clone(),
                     rights: fd_entry.inner.rights_inheriting,
                     fd_flags: {
                         let mut f = fd_entry.inner.fd_flags;
@@ -0,0 +1,44 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_addr_local_unix()`
+/// Returns the path that the unix domain socket is bound to.
+///
+/// Note: This is similar to `getsockname` in POSIX using PF_UNIX
+///
+/// The path is empty for unnamed sockets, it is truncated when it does not
+/// fit in `buf` and `ret_len` always receives its full length.
+///
+/// ## Parameters
+///
+/// * `fd` - Socket descriptor
+/// * `buf` - Buffer that receives the path
+/// * `buf_len` - Space available pointed to by `buf`
+/// * `ret_len` - The length of the path
+#[instrument(level = "trace", skip_all, fields(%sock, path = field::Empty), ret)]
+pub fn sock_addr_local_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+) -> Errno {
+    let path = wasi_try!(__sock_actor(
+        &mut ctx,
+        sock,
+        Rights::empty(),
+        |socket, _| socket.addr_local_unix()
+    ));
+
+    Span::current().record("path", format!("{path:?}"));
+
+    let memory = unsafe { ctx.data().memory_view(&ctx) };
+    wasi_try!(crate::net::write_unix_path(
+        &memory,
+        buf,
+        buf_len,
+        ret_len,
+        path.as_deref()
+    ));
+    Errno::Success
+}
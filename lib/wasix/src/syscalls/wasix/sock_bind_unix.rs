@@ -0,0 +1,108 @@
+use super::*;
+use crate::{syscalls::*, VIRTUAL_ROOT_FD};
+
+/// ### `sock_bind_unix()`
+/// Bind a unix domain socket to a path
+/// Note: This is similar to `bind` in POSIX using PF_UNIX
+///
+/// The socket becomes reachable through a socket inode that is created at
+/// the path, or through a socket on the host when the path is in one of
+/// the directories that are passed through to the host.
+///
+/// ## Parameters
+///
+/// * `fd` - File descriptor of the socket to be bound
+/// * `path` - Path to bind the socket to
+/// * `path_len` - The length of the `path` string
+#[instrument(level = "trace", skip_all, fields(%sock, path = field::Empty), ret)]
+pub fn sock_bind_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let path = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path.as_str());
+
+    wasi_try_ok!(sock_bind_unix_internal(&mut ctx, sock, &path)?);
+
+    Ok(Errno::Success)
+}
+
+pub(crate) fn sock_bind_unix_internal(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: &str,
+) -> Result<Result<(), Errno>, WasiError> {
+    let env = ctx.data();
+    let net = env.net().clone();
+    let state = env.state();
+    let inodes = &state.inodes;
+
+    let path = state.fs.unix_socket_path(path);
+    if let Some(host_path) = state.fs.host_socket_path(&path) {
+        wasi_try_ok_ok!(__sock_asyncify(
+            env,
+            sock,
+            Rights::SOCK_BIND,
+            move |socket, _| async move { socket.bind_unix_host(net.deref(), &host_path).await }
+        ));
+        return Ok(Ok(()));
+    }
+
+    // the name must not be taken by anything else
+    if state
+        .fs
+        .get_inode_at_path(inodes, VIRTUAL_ROOT_FD, &path.to_string_lossy(), false)
+        .is_ok()
+    {
+        return Ok(Err(Errno::Addrinuse));
+    }
+    let (parent_inode, name) =
+        wasi_try_ok_ok!(state
+            .fs
+            .get_parent_inode_at_path(inodes, VIRTUAL_ROOT_FD, &path, true));
+    wasi_try_ok_ok!(state.fs.check_inode_access(
+        &parent_inode,
+        virtual_fs::Permissions::WRITE | virtual_fs::Permissions::EXECUTE
+    ));
+    match parent_inode.read().deref() {
+        Kind::Dir { .. } => {}
+        Kind::Root { .. } => return Ok(Err(Errno::Notcapable)),
+        _ => return Ok(Err(Errno::Notdir)),
+    }
+
+    let endpoint = wasi_try_ok_ok!(__sock_asyncify(
+        env,
+        sock,
+        Rights::SOCK_BIND,
+        move |socket, _| async move { socket.bind_unix(path) }
+    ));
+
+    let st_filetype = match endpoint.is_stream() {
+        true => Filetype::SocketStream,
+        false => Filetype::SocketDgram,
+    };
+    let kind = Kind::Socket {
+        socket: InodeSocket::new(InodeSocketKind::UnixAddress(endpoint)),
+    };
+    let new_inode = state.fs.create_inode_with_stat(
+        inodes,
+        kind,
+        false,
+        name.clone().into(),
+        Filestat {
+            st_filetype,
+            ..Filestat::default()
+        },
+    );
+    if let Kind::Dir { entries, .. } = parent_inode.write().deref_mut() {
+        entries.insert(name, new_inode);
+    }
+
+    Ok(Ok(()))
+}
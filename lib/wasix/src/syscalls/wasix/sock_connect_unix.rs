@@ -0,0 +1,83 @@
+use super::*;
+use crate::{syscalls::*, VIRTUAL_ROOT_FD};
+
+/// ### `sock_connect_unix()`
+/// Connects a unix domain socket to the socket bound to a path
+/// Note: This is similar to `connect` in POSIX using PF_UNIX
+///
+/// Stream sockets are connected to the listening socket, datagram
+/// sockets only remember the peer that `sock_send` sends to.
+///
+/// ## Parameters
+///
+/// * `fd` - Socket descriptor
+/// * `path` - Path of the socket to connect to
+/// * `path_len` - The length of the `path` string
+#[instrument(level = "trace", skip_all, fields(%sock, path = field::Empty), ret)]
+pub fn sock_connect_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let path = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path.as_str());
+
+    wasi_try_ok!(sock_connect_unix_internal(&mut ctx, sock, &path)?);
+
+    Ok(Errno::Success)
+}
+
+pub(crate) fn sock_connect_unix_internal(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: &str,
+) -> Result<Result<(), Errno>, WasiError> {
+    let env = ctx.data();
+    let net = env.net().clone();
+    let state = env.state();
+    let inodes = &state.inodes;
+
+    let path = state.fs.unix_socket_path(path);
+    if let Some(host_path) = state.fs.host_socket_path(&path) {
+        wasi_try_ok_ok!(__sock_asyncify(
+            env,
+            sock,
+            Rights::SOCK_CONNECT,
+            move |socket, _| async move { socket.connect_unix_host(net.deref(), &host_path).await }
+        ));
+        return Ok(Ok(()));
+    }
+
+    let inode = wasi_try_ok_ok!(state.fs.get_inode_at_path(
+        inodes,
+        VIRTUAL_ROOT_FD,
+        &path.to_string_lossy(),
+        true
+    ));
+    wasi_try_ok_ok!(state
+        .fs
+        .check_inode_access(&inode, virtual_fs::Permissions::WRITE));
+
+    // anything other than a bound unix domain socket refuses the connection
+    let endpoint = match inode.read().deref() {
+        Kind::Socket { socket } => match &socket.inner.protected.read().unwrap().kind {
+            InodeSocketKind::UnixAddress(endpoint) => endpoint.clone(),
+            _ => return Ok(Err(Errno::Connrefused)),
+        },
+        _ => return Ok(Err(Errno::Connrefused)),
+    };
+
+    wasi_try_ok_ok!(__sock_asyncify(
+        env,
+        sock,
+        Rights::SOCK_CONNECT,
+        move |socket, _| async move { socket.connect_unix(&endpoint, path) }
+    ));
+
+    Ok(Ok(()))
+}
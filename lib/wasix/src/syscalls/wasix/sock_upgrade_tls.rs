@@ -0,0 +1,82 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_upgrade_tls()`
+/// Upgrades a connected TCP socket to TLS that is terminated on the host,
+/// after which the socket keeps sending and receiving plaintext
+///
+/// The certificates and trust roots are managed by the host, the upgrade
+/// is refused with `Notsup` when the host does not provide TLS.
+///
+/// ## Parameters
+///
+/// * `fd` - Socket descriptor
+/// * `server` - Whether this end of the connection is the TLS server
+/// * `server_name` - Name the certificate of the server is checked against,
+///   ignored by TLS servers
+/// * `server_name_len` - The length of the `server_name` string
+#[instrument(level = "trace", skip_all, fields(%sock, server_name = field::Empty), ret)]
+pub fn sock_upgrade_tls<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    server: Bool,
+    server_name: WasmPtr<u8, M>,
+    server_name_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let server_name = match server {
+        Bool::True => None,
+        _ => {
+            let env = ctx.data();
+            let memory = unsafe { env.memory_view(&ctx) };
+            let server_name = unsafe { get_input_str_ok!(&memory, server_name, server_name_len) };
+            Span::current().record("server_name", server_name.as_str());
+            Some(server_name)
+        }
+    };
+
+    wasi_try_ok!(sock_upgrade_tls_internal(
+        &mut ctx,
+        sock,
+        server_name.as_deref()
+    )?);
+
+    Ok(Errno::Success)
+}
+
+#[cfg(feature = "host-tls")]
+pub(crate) fn sock_upgrade_tls_internal(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    server_name: Option<&str>,
+) -> Result<Result<(), Errno>, WasiError> {
+    let env = ctx.data();
+    let tls = match env.runtime.tls() {
+        Some(tls) => tls,
+        None => return Ok(Err(Errno::Notsup)),
+    };
+    let session = match server_name {
+        Some(server_name) => tls.client_session(server_name),
+        None => tls.server_session(),
+    };
+    let session = wasi_try_ok_ok!(session.map_err(net_error_into_wasi_err));
+
+    wasi_try_ok_ok!(__sock_actor_mut(
+        ctx,
+        sock,
+        Rights::SOCK_SEND | Rights::SOCK_RECV,
+        |socket, _| socket.upgrade_tls(session)
+    ));
+
+    Ok(Ok(()))
+}
+
+#[cfg(not(feature = "host-tls"))]
+pub(crate) fn sock_upgrade_tls_internal(
+    _ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    _sock: WasiFd,
+    _server_name: Option<&str>,
+) -> Result<Result<(), Errno>, WasiError> {
+    Ok(Err(Errno::Notsup))
+}
@@ -0,0 +1,128 @@
+use super::*;
+use crate::{fs::WatchNotificationsInner, syscalls::*};
+
+/// ### `watch_add()`
+/// Starts watching a file or directory for changes, the events are
+/// delivered to a file handle created with `watch_create`
+/// Inputs:
+/// - `Fd fd`
+///     The file handle created with `watch_create`
+/// - `Fd dirfd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// - `u32 mask`
+///     The events to watch for (the `IN_*` bits of Linux)
+/// Output:
+/// - `i32 ret_wd`
+///     The descriptor of the watch, it is part of every event
+#[instrument(level = "trace", skip_all, fields(%fd, %dirfd, path = field::Empty, mask, ret_wd = field::Empty), ret)]
+pub fn watch_add<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    dirfd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    mask: u32,
+    ret_wd: WasmPtr<i32, M>,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str_ok!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+
+    let wd = wasi_try_ok!(watch_add_internal(
+        state,
+        inodes,
+        fd,
+        dirfd,
+        flags,
+        &path_string,
+        mask,
+        None
+    ));
+    Span::current().record("ret_wd", wd);
+
+    #[cfg(feature = "journal")]
+    if env.enable_journal {
+        JournalEffector::save_watch_add(&mut ctx, fd, dirfd, flags, path_string, mask, wd)
+            .map_err(|err| {
+                tracing::error!("failed to save watch_add event - {}", err);
+                WasiError::Exit(ExitCode::from(Errno::Fault))
+            })?;
+    }
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem_ok!(ret_wd.write(&memory, wd));
+
+    Ok(Errno::Success)
+}
+
+pub(crate) fn watch_add_internal(
+    state: &WasiState,
+    inodes: &crate::WasiInodes,
+    fd: WasiFd,
+    dirfd: WasiFd,
+    flags: LookupFlags,
+    path: &str,
+    mask: u32,
+    with_wd: Option<i32>,
+) -> Result<i32, Errno> {
+    let inner = watch_notifications_of(state, fd)?;
+
+    let mask = virtual_fs::WatchMask::from_bits_truncate(mask);
+    if mask.is_empty() {
+        return Err(Errno::Inval);
+    }
+
+    let dir_entry = state.fs.get_fd(dirfd)?;
+    if !dir_entry.inner.rights.contains(Rights::PATH_FILESTAT_GET) {
+        return Err(Errno::Access);
+    }
+
+    let inode = state.fs.get_inode_at_path(
+        inodes,
+        dirfd,
+        path,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    )?;
+    let path = crate::fs::WasiFs::path_of_inode(&inode).ok_or(Errno::Notsup)?;
+    state
+        .fs
+        .check_access(&path, virtual_fs::Permissions::READ)?;
+
+    let watch = state
+        .fs
+        .root_fs
+        .watch(&path, mask)
+        .map_err(fs_error_into_wasi_err)?;
+    Ok(match with_wd {
+        Some(wd) => {
+            inner.insert_watch(wd, watch);
+            wd
+        }
+        None => inner.add_watch(watch),
+    })
+}
+
+/// Returns the state of a file handle created with `watch_create`
+pub(crate) fn watch_notifications_of(
+    state: &WasiState,
+    fd: WasiFd,
+) -> Result<Arc<WatchNotificationsInner>, Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    let guard = fd_entry.inode.read();
+    match guard.deref() {
+        Kind::WatchNotifications { inner } => Ok(inner.clone()),
+        _ => Err(Errno::Inval),
+    }
+}
@@ -0,0 +1,64 @@
+use super::*;
+use crate::{fs::WatchNotificationsInner, syscalls::*};
+
+/// ### `watch_create()`
+/// Creates a file handle that receives the events of file system watches,
+/// the events are read from it in the format of Linux's `inotify(7)`
+/// Inputs:
+/// - `Fdflags flags`
+///     Flags of the new file handle (only `NONBLOCK` is meaningful)
+/// Output:
+/// - `Fd ret_fd`
+///     The new file handle
+#[instrument(level = "trace", skip_all, fields(ret_fd = field::Empty), ret)]
+pub fn watch_create<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    flags: Fdflags,
+    ret_fd: WasmPtr<WasiFd, M>,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let fd = wasi_try_ok!(watch_create_internal(state, inodes, flags));
+    Span::current().record("ret_fd", fd);
+
+    #[cfg(feature = "journal")]
+    if env.enable_journal {
+        JournalEffector::save_watch_create(&mut ctx, fd, flags).map_err(|err| {
+            tracing::error!("failed to save watch_create event - {}", err);
+            WasiError::Exit(ExitCode::from(Errno::Fault))
+        })?;
+    }
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem_ok!(ret_fd.write(&memory, fd));
+
+    Ok(Errno::Success)
+}
+
+pub(crate) fn watch_create_internal(
+    state: &WasiState,
+    inodes: &crate::WasiInodes,
+    flags: Fdflags,
+) -> Result<WasiFd, Errno> {
+    let kind = Kind::WatchNotifications {
+        inner: Arc::new(WatchNotificationsInner::new()),
+    };
+    let inode =
+        state
+            .fs
+            .create_inode_with_default_stat(inodes, kind, false, "watch".to_string().into());
+
+    let rights = Rights::FD_READ | Rights::POLL_FD_READWRITE | Rights::FD_FDSTAT_SET_FLAGS;
+    state.fs.create_fd(
+        rights,
+        rights,
+        flags & Fdflags::NONBLOCK,
+        Fdflagsext::empty(),
+        0,
+        inode,
+    )
+}
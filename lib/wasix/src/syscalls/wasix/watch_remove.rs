@@ -0,0 +1,37 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `watch_remove()`
+/// Stops a watch that was started with `watch_add`, a final event with
+/// the `IN_IGNORED` bit is delivered for it
+/// Inputs:
+/// - `Fd fd`
+///     The file handle created with `watch_create`
+/// - `i32 wd`
+///     The descriptor of the watch
+#[instrument(level = "trace", skip_all, fields(%fd, %wd), ret)]
+pub fn watch_remove(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    wd: i32,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let state = &env.state;
+
+    let inner = wasi_try_ok!(watch_notifications_of(state, fd));
+    if !inner.remove_watch(wd) {
+        return Ok(Errno::Inval);
+    }
+
+    #[cfg(feature = "journal")]
+    if env.enable_journal {
+        JournalEffector::save_watch_remove(&mut ctx, fd, wd).map_err(|err| {
+            tracing::error!("failed to save watch_remove event - {}", err);
+            WasiError::Exit(ExitCode::from(Errno::Fault))
+        })?;
+    }
+
+    Ok(Errno::Success)
+}
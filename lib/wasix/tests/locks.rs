@@ -0,0 +1,135 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::{path::Path, sync::Arc};
+
+use virtual_fs::{mem_fs, AsyncReadExt, FileSystem};
+use virtual_mio::InlineWaker;
+use wasmer::Module;
+use wasmer_types::ModuleHash;
+use wasmer_wasix::{
+    runners::wasi::{RuntimeOrEngine, WasiRunner},
+    Pipe,
+};
+
+/// Opens `/data/file.txt` twice and places locks through both descriptors,
+/// writing the errnos that come back (and the conflicting lock that is
+/// reported) to stdout as one byte each.
+const PROGRAM: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_lock" (func $fd_lock (param i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_lock_range" (func $fd_lock_range (param i32 i32 i64 i64 i32) (result i32)))
+    (import "wasix_32v1" "fd_lock_range_get" (func $fd_lock_range_get (param i32 i32 i64 i64 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 64) "data/file.txt")
+
+    (func $open (param $ret i32)
+        (drop (call $path_open
+            (i32.const 3)        ;; fd - the preopen of "/"
+            (i32.const 0)        ;; dirflags
+            (i32.const 64)       ;; path
+            (i32.const 13)       ;; path_len
+            (i32.const 0)        ;; oflags
+            (i64.const -1)       ;; rights_base
+            (i64.const 0)        ;; rights_inheriting
+            (i32.const 0)        ;; fdflags
+            (local.get $ret)))
+    )
+
+    (func $main (export "_start")
+        (call $open (i32.const 24))
+        (call $open (i32.const 28))
+
+        ;; Both descriptors refer to the same file but are independent
+        (i32.store8 (i32.const 128)
+            (call $fd_lock (i32.load (i32.const 24)) (i32.const 1) (i32.const 1)))
+        (i32.store8 (i32.const 129)
+            (call $fd_lock (i32.load (i32.const 28)) (i32.const 1) (i32.const 1)))
+
+        ;; Byte range locks are independent of whole file locks
+        (i32.store8 (i32.const 130)
+            (call $fd_lock_range (i32.load (i32.const 28)) (i32.const 1) (i64.const 0) (i64.const 10) (i32.const 1)))
+        (i32.store8 (i32.const 131)
+            (call $fd_lock_range (i32.load (i32.const 24)) (i32.const 0) (i64.const 5) (i64.const 10) (i32.const 1)))
+        (i32.store8 (i32.const 132)
+            (call $fd_lock_range_get (i32.load (i32.const 24)) (i32.const 0) (i64.const 5) (i64.const 10)
+                (i32.const 40) (i32.const 48) (i32.const 56)))
+        (i32.store8 (i32.const 133) (i32.load (i32.const 40)))
+        (i32.store8 (i32.const 134) (i32.wrap_i64 (i64.load (i32.const 48))))
+        (i32.store8 (i32.const 135) (i32.wrap_i64 (i64.load (i32.const 56))))
+
+        ;; Closing a descriptor releases its locks
+        (drop (call $fd_close (i32.load (i32.const 28))))
+        (i32.store8 (i32.const 136)
+            (call $fd_lock_range (i32.load (i32.const 24)) (i32.const 1) (i64.const 0) (i64.const 0) (i32.const 1)))
+
+        (i32.store (i32.const 0) (i32.const 128))
+        (i32.store (i32.const 4) (i32.const 9))
+        (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20))
+        drop
+    )
+)
+"#;
+
+const ERRNO_SUCCESS: u8 = 0;
+const ERRNO_AGAIN: u8 = 6;
+const FLOCKOP_EXCLUSIVE: u8 = 1;
+
+#[test]
+fn test_locks_belong_to_open_file_descriptions() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let handle = runtime.handle().clone();
+    let _guard = handle.enter();
+
+    let fs = mem_fs::FileSystem::default();
+    fs.new_open_options()
+        .write(true)
+        .create(true)
+        .open(Path::new("/file.txt"))
+        .unwrap();
+
+    let engine = wasmer::Engine::default();
+    let module = Module::new(&engine, PROGRAM).unwrap();
+    let (stdout_tx, mut stdout_rx) = Pipe::channel();
+
+    {
+        let mut runner = WasiRunner::new();
+        runner
+            .with_stdout(Box::new(stdout_tx))
+            .with_mount("/data".to_string(), Arc::new(fs));
+
+        runner
+            .run_wasm(
+                RuntimeOrEngine::Engine(engine),
+                "locks",
+                module,
+                ModuleHash::random(),
+            )
+            .unwrap();
+    }
+
+    let mut stdout = Vec::new();
+    InlineWaker::block_on(stdout_rx.read_to_end(&mut stdout)).unwrap();
+    assert_eq!(
+        stdout,
+        [
+            ERRNO_SUCCESS,
+            ERRNO_AGAIN,
+            ERRNO_SUCCESS,
+            ERRNO_AGAIN,
+            ERRNO_SUCCESS,
+            FLOCKOP_EXCLUSIVE,
+            0,
+            10,
+            ERRNO_SUCCESS,
+        ]
+    );
+}
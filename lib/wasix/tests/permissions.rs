@@ -0,0 +1,163 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::{path::Path, sync::Arc};
+
+use virtual_fs::{mem_fs, AsyncReadExt, FileSystem, Permissions};
+use virtual_mio::InlineWaker;
+use wasmer::Module;
+use wasmer_types::ModuleHash;
+use wasmer_wasix::{
+    runners::wasi::{RuntimeOrEngine, WasiRunner},
+    Pipe, WasiIdentity,
+};
+
+/// Opens and then chmods `/{path}`, writing the two errnos that come back
+/// to stdout as one byte each.
+const PROGRAM: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasix_32v1" "path_chmod" (func $path_chmod (param i32 i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 64) "{path}")
+
+    (func $main (export "_start")
+        (i32.store8 (i32.const 32)
+            (call $path_open
+                (i32.const 3)        ;; fd - the preopen of "/"
+                (i32.const 0)        ;; dirflags
+                (i32.const 64)       ;; path
+                (i32.const {path_len}) ;; path_len
+                (i32.const 0)        ;; oflags
+                (i64.const 2)        ;; rights_base - fd_read
+                (i64.const 0)        ;; rights_inheriting
+                (i32.const 0)        ;; fdflags
+                (i32.const 24)))     ;; opened fd
+        (i32.store8 (i32.const 33)
+            (call $path_chmod
+                (i32.const 3)        ;; fd
+                (i32.const 0)        ;; flags
+                (i32.const 64)       ;; path
+                (i32.const {path_len}) ;; path_len
+                (i32.const 511)))    ;; mode - 0o777
+
+        (i32.store (i32.const 0) (i32.const 32))
+        (i32.store (i32.const 4) (i32.const 2))
+        (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20))
+        drop
+    )
+)
+"#;
+
+const ERRNO_SUCCESS: u8 = 0;
+const ERRNO_ACCESS: u8 = 2;
+const ERRNO_PERM: u8 = 63;
+
+fn run_as(identity: Option<WasiIdentity>) -> Vec<u8> {
+    run_with(identity, "data/secret.txt", |fs| {
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(Path::new("/secret.txt"))
+            .unwrap();
+        fs.set_permissions(
+            Path::new("/secret.txt"),
+            Permissions::new(0o600, Permissions::ROOT_UID, 0),
+        )
+        .unwrap();
+    })
+}
+
+/// Runs the program on `path` with a file system that is prepared by
+/// `setup` and mounted at `/data`.
+fn run_with(
+    identity: Option<WasiIdentity>,
+    path: &str,
+    setup: impl FnOnce(&mem_fs::FileSystem),
+) -> Vec<u8> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let handle = runtime.handle().clone();
+    let _guard = handle.enter();
+
+    let fs = mem_fs::FileSystem::default();
+    setup(&fs);
+
+    let program = PROGRAM
+        .replace("{path_len}", &path.len().to_string())
+        .replace("{path}", path);
+    let engine = wasmer::Engine::default();
+    let module = Module::new(&engine, program).unwrap();
+    let (stdout_tx, mut stdout_rx) = Pipe::channel();
+
+    {
+        let mut runner = WasiRunner::new();
+        runner
+            .with_stdout(Box::new(stdout_tx))
+            .with_mount("/data".to_string(), Arc::new(fs));
+        if let Some(identity) = identity {
+            runner.with_identity(identity);
+        }
+
+        runner
+            .run_wasm(
+                RuntimeOrEngine::Engine(engine),
+                "permissions",
+                module,
+                ModuleHash::random(),
+            )
+            .unwrap();
+    }
+
+    let mut stdout = Vec::new();
+    InlineWaker::block_on(stdout_rx.read_to_end(&mut stdout)).unwrap();
+    stdout
+}
+
+#[test]
+fn test_permissions_enforced_for_other_users() {
+    let errnos = run_as(Some(WasiIdentity::new(1000, 1000)));
+    assert_eq!(errnos, [ERRNO_ACCESS, ERRNO_PERM]);
+}
+
+#[test]
+fn test_permissions_bypassed_by_root() {
+    let errnos = run_as(Some(WasiIdentity::root()));
+    assert_eq!(errnos, [ERRNO_SUCCESS, ERRNO_SUCCESS]);
+}
+
+#[test]
+fn test_permissions_require_search_on_ancestors() {
+    let run = |dir_mode| {
+        run_with(
+            Some(WasiIdentity::new(1000, 1000)),
+            "data/private/a.txt",
+            |fs| {
+                fs.create_dir(Path::new("/private")).unwrap();
+                fs.new_open_options()
+                    .write(true)
+                    .create(true)
+                    .open(Path::new("/private/a.txt"))
+                    .unwrap();
+                fs.set_permissions(
+                    Path::new("/private/a.txt"),
+                    Permissions::new(0o666, Permissions::ROOT_UID, 0),
+                )
+                .unwrap();
+                fs.set_permissions(
+                    Path::new("/private"),
+                    Permissions::new(dir_mode, Permissions::ROOT_UID, 0),
+                )
+                .unwrap();
+            },
+        )
+    };
+
+    assert_eq!(run(0o755)[0], ERRNO_SUCCESS);
+    assert_eq!(run(0o700)[0], ERRNO_ACCESS);
+}